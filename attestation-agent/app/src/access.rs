@@ -0,0 +1,1044 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Access control of the callers of the AA services.
+//!
+//! Any process reaching the sockets of AA can get evidence and tokens from
+//! it. The callers can be limited to allow-lists: callers over Unix sockets
+//! by the uid and the SELinux label of their process, as reported by the
+//! kernel, and callers over vsock by their source port. The requests of
+//! every caller, i.e. of a process, a vsock cid or a TCP address, can also
+//! be limited to a rate, and the requests of all of them to a global one.
+//!
+//! The rates are checked first, before the caller is authorized, so that
+//! rejected callers are limited as well. Rejected calls are logged to the
+//! `audit` log target and recorded in the audit log of AA, at most
+//! [`MAX_LOGGED_REJECTIONS`] a minute. Calls rejected for the rate are
+//! recorded once until the caller gets a call through again, so that a
+//! flood of calls doesn't flood the audit log, nor keep AA busy with it.
+//!
+//! The gRPC listener doesn't see the SELinux labels of its callers, nor can
+//! TCP callers be authenticated, so they are rejected once the uid or the
+//! label of the callers is checked.
+//!
+//! The keys of a purpose, see `DeriveKey`, are only derived for the uids
+//! allowed the purpose, root if none is, whether or not the other checks
+//! are enabled. Only processes calling over Unix sockets can derive keys.
+//! Privileged calls, e.g. `ReloadConfig`, are only served to root processes
+//! calling over Unix sockets.
+
+use anyhow::{anyhow, bail, Result};
+use clap::Args;
+use log::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::Instant;
+
+use crate::ASYNC_ATTESTATION_AGENT;
+
+/// Most callers whose rate is tracked at once. The least recently seen one
+/// is forgotten to track a new one.
+const MAX_TRACKED_CALLERS: usize = 1024;
+
+/// Most rejected calls logged and recorded a minute.
+pub const MAX_LOGGED_REJECTIONS: u32 = 60;
+
+/// The uid allowed to derive the keys of the purposes no uid is allowed,
+/// and to make privileged calls.
+const ROOT_UID: u32 = 0;
+
+#[derive(Debug, Default, Args, Serialize)]
+pub struct AccessArgs {
+    /// Uid allowed to call AA over Unix sockets. Can be given several times,
+    /// all the uids are allowed if not set, for example:
+    ///
+    /// `--allow_uid 0 --allow_uid 1000`
+    #[arg(long = "allow_uid")]
+    pub allow_uid: Vec<u32>,
+
+    /// SELinux label of the processes allowed to call AA over Unix sockets.
+    /// Can be given several times, all the labels are allowed if not set,
+    /// for example:
+    ///
+    /// `--allow_selinux_label system_u:system_r:kata_agent_t:s0`
+    #[arg(long = "allow_selinux_label")]
+    pub allow_selinux_label: Vec<String>,
+
+    /// Vsock port, or inclusive range of ports, allowed to call AA from.
+    /// Can be given several times, all the ports are allowed if not set,
+    /// for example:
+    ///
+    /// `--allow_vsock_port 1024-2047`
+    #[arg(long = "allow_vsock_port", value_parser = parse_port_range)]
+    pub allow_vsock_port: Vec<PortRange>,
+
+    /// Calls a caller, i.e. a uid, an SELinux label, a vsock cid or a TCP
+    /// address, can make per minute, in bursts of up to as many. The calls are not limited if not set.
+    #[arg(long = "rate_limit", value_parser = clap::value_parser!(u32).range(1..))]
+    pub rate_limit: Option<u32>,
+
+    /// Calls all the callers can make per minute together, in bursts of up
+    /// to as many. The calls are not limited if not set.
+    #[arg(long = "global_rate_limit", value_parser = clap::value_parser!(u32).range(1..))]
+    pub global_rate_limit: Option<u32>,
+
+    /// Uid allowed to derive the keys of a purpose, as `<purpose>=<uid>`.
+    /// Can be given several times, the keys of the purposes no uid is
+    /// allowed are only derived for root, for example:
+    ///
+    /// `--allow_derive_key eccfs=0 --allow_derive_key app=1000`
+    #[arg(long = "allow_derive_key", value_parser = parse_purpose_uid)]
+    pub allow_derive_key: Vec<PurposeUid>,
+}
+
+/// Uid allowed to derive the keys of a purpose.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PurposeUid {
+    pub purpose: String,
+    pub uid: u32,
+}
+
+fn parse_purpose_uid(allowed: &str) -> Result<PurposeUid> {
+    let Some((purpose, uid)) = allowed.split_once('=') else {
+        bail!("expected <purpose>=<uid>, got {allowed:?}");
+    };
+    if purpose.is_empty() {
+        bail!("purpose of {allowed:?} is empty");
+    }
+    let uid = uid
+        .trim()
+        .parse::<u32>()
+        .map_err(|e| anyhow!("invalid uid {uid:?}: {e}"))?;
+    Ok(PurposeUid {
+        purpose: purpose.to_string(),
+        uid,
+    })
+}
+
+/// Inclusive range of vsock ports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct PortRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl PortRange {
+    fn contains(&self, port: u32) -> bool {
+        (self.start..=self.end).contains(&port)
+    }
+}
+
+fn parse_port_range(range: &str) -> Result<PortRange> {
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let port = |port: &str| {
+        port.trim()
+            .parse::<u32>()
+            .map_err(|e| anyhow!("invalid vsock port {port:?}: {e}"))
+    };
+    let range = PortRange {
+        start: port(start)?,
+        end: port(end)?,
+    };
+    if range.start > range.end {
+        bail!("invalid vsock port range {}-{}", range.start, range.end);
+    }
+    Ok(range)
+}
+
+/// Caller of an AA service.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Caller {
+    /// Process calling over a Unix socket. The SELinux label is only known
+    /// to the ttRPC listener, if the guest runs SELinux.
+    Unix {
+        uid: u32,
+        pid: Option<i32>,
+        label: Option<String>,
+    },
+
+    /// Caller over vsock, e.g. the host.
+    #[cfg_attr(not(feature = "ttrpc"), allow(dead_code))]
+    Vsock { cid: u32, port: u32 },
+
+    /// Caller over TCP, of the gRPC listener.
+    #[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+    Tcp(IpAddr),
+}
+
+impl Caller {
+    /// Get the caller at the other end of the socket `fd`, a connection of
+    /// the ttRPC listener.
+    #[cfg(feature = "ttrpc")]
+    pub fn from_fd(fd: std::os::unix::io::RawFd) -> std::io::Result<Self> {
+        use std::{io, mem};
+
+        let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        let addr_ptr = &mut addr as *mut libc::sockaddr_storage as *mut libc::sockaddr;
+        if unsafe { libc::getpeername(fd, addr_ptr, &mut len) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        match addr.ss_family as libc::c_int {
+            libc::AF_UNIX => {
+                let mut cred: libc::ucred = unsafe { mem::zeroed() };
+                let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+                let cred_ptr = &mut cred as *mut libc::ucred as *mut libc::c_void;
+                let res = unsafe {
+                    libc::getsockopt(fd, libc::SOL_SOCKET, libc::SO_PEERCRED, cred_ptr, &mut len)
+                };
+                if res != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(Self::Unix {
+                    uid: cred.uid,
+                    pid: Some(cred.pid),
+                    label: peer_label(fd),
+                })
+            }
+            libc::AF_VSOCK => {
+                let addr = unsafe { &*(addr_ptr as *const libc::sockaddr_vm) };
+                Ok(Self::Vsock {
+                    cid: addr.svm_cid,
+                    port: addr.svm_port,
+                })
+            }
+            family => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("callers over socket family {family} are not supported"),
+            )),
+        }
+    }
+
+    /// Get the caller of a gRPC `request`.
+    #[cfg(feature = "grpc")]
+    pub fn from_request<T>(request: &tonic::Request<T>) -> std::io::Result<Self> {
+        use tonic::transport::server::UdsConnectInfo;
+
+        if let Some(addr) = request.remote_addr() {
+            return Ok(Self::Tcp(addr.ip()));
+        }
+
+        let cred = request
+            .extensions()
+            .get::<UdsConnectInfo>()
+            .and_then(|info| info.peer_cred)
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "no credentials of the gRPC caller",
+                )
+            })?;
+        Ok(Self::Unix {
+            uid: cred.uid(),
+            pid: cred.pid(),
+            label: None,
+        })
+    }
+
+    /// AA itself, e.g. to measure its own configuration.
+    pub fn own() -> std::io::Result<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        Ok(Self::Unix {
+            uid: std::fs::metadata("/proc/self")?.uid(),
+            pid: Some(std::process::id() as i32),
+            label: None,
+        })
+    }
+
+    /// The identity the configuration measurements of the caller are bound
+    /// to, see [`attestation_agent::config_measurement`]: the executable and
+    /// the uid of a process calling over a Unix socket. Other callers have
+    /// none.
+    pub fn measured_identity(&self) -> Option<String> {
+        let Self::Unix {
+            uid,
+            pid: Some(pid),
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let exe = std::fs::read_link(format!("/proc/{pid}/exe")).ok()?;
+        Some(format!("{} (uid {uid})", exe.display()))
+    }
+}
+
+impl fmt::Display for Caller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unix { uid, pid, label } => {
+                write!(f, "uid {uid}")?;
+                if let Some(pid) = pid {
+                    write!(f, " pid {pid}")?;
+                }
+                if let Some(label) = label {
+                    write!(f, " label {label}")?;
+                }
+                Ok(())
+            }
+            Self::Vsock { cid, port } => write!(f, "vsock {cid}:{port}"),
+            Self::Tcp(addr) => write!(f, "tcp {addr}"),
+        }
+    }
+}
+
+// SELinux label of the peer of the Unix socket `fd`, none without SELinux
+#[cfg(feature = "ttrpc")]
+fn peer_label(fd: std::os::unix::io::RawFd) -> Option<String> {
+    let mut label = [0u8; 256];
+    let mut len = label.len() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_PEERSEC,
+            label.as_mut_ptr() as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if res != 0 {
+        return None;
+    }
+
+    let label = &label[..len as usize];
+    let label = label.strip_suffix(b"\0").unwrap_or(label);
+    String::from_utf8(label.to_vec()).ok()
+}
+
+/// Calls a caller has left, refilled over time.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+
+    /// Whether a call was rejected since the last one got through.
+    throttled: bool,
+}
+
+impl Bucket {
+    fn full(limit: f64, now: Instant) -> Self {
+        Self {
+            tokens: limit,
+            updated: now,
+            throttled: false,
+        }
+    }
+
+    // whether the bucket is full at `now`, i.e. the same as a new one
+    fn is_full(&self, limit: f64, now: Instant) -> bool {
+        let refill = now.duration_since(self.updated).as_secs_f64() / 60.0;
+        self.tokens + refill * limit >= limit
+    }
+
+    // take a call at `now`, refilling `limit` calls a minute
+    fn take(&mut self, limit: f64, now: Instant) -> Result<(), Rejection> {
+        let refill = now.duration_since(self.updated).as_secs_f64() / 60.0 * limit;
+        self.tokens = (self.tokens + refill).min(limit);
+        self.updated = now;
+        if self.tokens < 1.0 {
+            let first = !self.throttled;
+            self.throttled = true;
+            return Err(Rejection::Throttled { first });
+        }
+
+        self.tokens -= 1.0;
+        self.throttled = false;
+        Ok(())
+    }
+}
+
+/// Why a call is rejected.
+#[derive(Debug, PartialEq, Eq)]
+enum Rejection {
+    Denied(String),
+    Throttled { first: bool },
+}
+
+/// Access control of the AA services, see [`crate::access`].
+#[derive(Debug)]
+pub struct AccessControl {
+    allow_uid: Vec<u32>,
+    allow_selinux_label: Vec<String>,
+    allow_vsock_port: Vec<PortRange>,
+    rate_limit: Option<u32>,
+    global_rate_limit: Option<u32>,
+    allow_derive_key: Vec<PurposeUid>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    global: Mutex<Bucket>,
+    rejections: Mutex<Bucket>,
+}
+
+static ACCESS_CONTROL: OnceLock<AccessControl> = OnceLock::new();
+
+/// Enforce the access control of `args` on the calls of all the listeners.
+pub fn init(args: &AccessArgs) {
+    let access = AccessControl::new(args);
+    if access.is_enabled() {
+        info!("Access control of the callers: {args:?}");
+    }
+    let _ = ACCESS_CONTROL.set(access);
+}
+
+impl AccessControl {
+    pub fn new(args: &AccessArgs) -> Self {
+        let now = Instant::now();
+        Self {
+            allow_uid: args.allow_uid.clone(),
+            allow_selinux_label: args.allow_selinux_label.clone(),
+            allow_vsock_port: args.allow_vsock_port.clone(),
+            rate_limit: args.rate_limit,
+            global_rate_limit: args.global_rate_limit,
+            allow_derive_key: args.allow_derive_key.clone(),
+            buckets: Mutex::new(HashMap::new()),
+            global: Mutex::new(Bucket::full(
+                f64::from(args.global_rate_limit.unwrap_or(0)),
+                now,
+            )),
+            rejections: Mutex::new(Bucket::full(f64::from(MAX_LOGGED_REJECTIONS), now)),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.allow_uid.is_empty()
+            || !self.allow_selinux_label.is_empty()
+            || !self.allow_vsock_port.is_empty()
+            || self.rate_limit.is_some()
+            || self.global_rate_limit.is_some()
+    }
+
+    fn authorize(&self, caller: &Caller) -> Result<(), String> {
+        let checks_unix = !self.allow_uid.is_empty() || !self.allow_selinux_label.is_empty();
+        match caller {
+            Caller::Unix { uid, label, .. } => {
+                if !self.allow_uid.is_empty() && !self.allow_uid.contains(uid) {
+                    return Err(format!("uid {uid} is not allowed"));
+                }
+
+                if self.allow_selinux_label.is_empty() {
+                    return Ok(());
+                }
+                match label {
+                    Some(label) if self.allow_selinux_label.contains(label) => {}
+                    Some(label) => return Err(format!("SELinux label {label} is not allowed")),
+                    None => return Err("SELinux label of the caller is unknown".to_string()),
+                }
+            }
+            Caller::Vsock { port, .. } => {
+                if !self.allow_vsock_port.is_empty()
+                    && !self.allow_vsock_port.iter().any(|r| r.contains(*port))
+                {
+                    return Err(format!("vsock port {port} is not allowed"));
+                }
+            }
+            Caller::Tcp(_) if checks_unix => {
+                return Err("TCP callers cannot be authenticated".to_string());
+            }
+            Caller::Tcp(_) => {}
+        }
+
+        Ok(())
+    }
+
+    fn authorize_derive_key(&self, caller: &Caller, purpose: &str) -> Result<(), String> {
+        let Caller::Unix { uid, .. } = caller else {
+            return Err("only processes calling over Unix sockets can derive keys".to_string());
+        };
+
+        let mut allowed = self
+            .allow_derive_key
+            .iter()
+            .filter(|allowed| allowed.purpose == purpose)
+            .map(|allowed| allowed.uid)
+            .peekable();
+        let is_allowed = match allowed.peek() {
+            Some(_) => allowed.any(|allowed| allowed == *uid),
+            None => *uid == ROOT_UID,
+        };
+        if !is_allowed {
+            return Err(format!(
+                "uid {uid} is not allowed to derive keys for {purpose}"
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn authorize_privileged(&self, caller: &Caller, method: &str) -> Result<(), String> {
+        match caller {
+            Caller::Unix { uid: ROOT_UID, .. } => Ok(()),
+            Caller::Unix { uid, .. } => Err(format!("uid {uid} is not allowed to call {method}")),
+            _ => Err(format!(
+                "only processes calling over Unix sockets can call {method}"
+            )),
+        }
+    }
+
+    // the caller the calls are counted for by the rate limit: the uid of a
+    // process, with its SELinux label if the callers are admitted by label,
+    // so that a process can't get a rate of its own by forking
+    fn rate_key(&self, caller: &Caller) -> String {
+        match caller {
+            Caller::Unix {
+                uid,
+                label: Some(label),
+                ..
+            } if !self.allow_selinux_label.is_empty() => format!("uid:{uid}:label:{label}"),
+            Caller::Unix { uid, .. } => format!("uid:{uid}"),
+            Caller::Vsock { cid, .. } => format!("vsock:{cid}"),
+            Caller::Tcp(addr) => format!("tcp:{addr}"),
+        }
+    }
+
+    // take a call of the rate of `caller` and of the global rate at `now`
+    fn take(&self, caller: &Caller, now: Instant) -> Result<(), Rejection> {
+        if let Some(limit) = self.rate_limit.map(f64::from) {
+            let mut buckets = lock(&self.buckets);
+            let key = self.rate_key(caller);
+            if !buckets.contains_key(&key) && buckets.len() >= MAX_TRACKED_CALLERS {
+                // callers with a full bucket are the same as new ones
+                buckets.retain(|_, bucket| !bucket.is_full(limit, now));
+                if buckets.len() >= MAX_TRACKED_CALLERS {
+                    // the callers tracked must not keep the new ones out
+                    let least_recent = buckets
+                        .iter()
+                        .min_by_key(|(_, bucket)| bucket.updated)
+                        .map(|(key, _)| key.clone());
+                    if let Some(least_recent) = least_recent {
+                        buckets.remove(&least_recent);
+                    }
+                }
+            }
+
+            buckets
+                .entry(key)
+                .or_insert_with(|| Bucket::full(limit, now))
+                .take(limit, now)?;
+        }
+
+        if let Some(limit) = self.global_rate_limit.map(f64::from) {
+            lock(&self.global).take(limit, now)?;
+        }
+
+        Ok(())
+    }
+
+    // the rates are taken first, so that rejected callers are limited too
+    fn admit(&self, caller: &Caller, now: Instant) -> Result<(), Rejection> {
+        self.take(caller, now)?;
+        self.authorize(caller).map_err(Rejection::Denied)
+    }
+
+    // whether a rejected call at `now` is still to be logged and recorded
+    fn log_rejection(&self, now: Instant) -> bool {
+        lock(&self.rejections)
+            .take(f64::from(MAX_LOGGED_REJECTIONS), now)
+            .is_ok()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Check whether `caller` may call `method`. Callers that can't be
+/// identified are only let through without access control.
+pub async fn check(caller: std::io::Result<Caller>, method: &str) -> Result<()> {
+    let Some(access) = ACCESS_CONTROL.get().filter(|access| access.is_enabled()) else {
+        return Ok(());
+    };
+
+    let now = Instant::now();
+    let (caller, rejection) = match caller {
+        Ok(caller) => match access.admit(&caller, now) {
+            Ok(()) => return Ok(()),
+            Err(rejection) => (caller.to_string(), rejection),
+        },
+        Err(e) => (
+            "unknown caller".to_string(),
+            Rejection::Denied(format!("cannot identify the caller: {e}")),
+        ),
+    };
+
+    Err(reject(access, method, &caller, rejection, now).await)
+}
+
+/// Check whether `caller` may derive the keys of `purpose`, see
+/// [`AccessArgs::allow_derive_key`].
+pub async fn check_derive_key(caller: std::io::Result<Caller>, purpose: &str) -> Result<()> {
+    check_with(caller, "DeriveKey", |access, caller| {
+        access.authorize_derive_key(caller, purpose)
+    })
+    .await
+}
+
+/// Check whether `caller` may make the privileged call of `method`, i.e.
+/// is a root process.
+pub async fn check_privileged(caller: std::io::Result<Caller>, method: &str) -> Result<()> {
+    check_with(caller, method, |access, caller| {
+        access.authorize_privileged(caller, method)
+    })
+    .await
+}
+
+// check `caller` with `authorize`, whether or not the access control is
+// enabled
+async fn check_with(
+    caller: std::io::Result<Caller>,
+    method: &str,
+    authorize: impl FnOnce(&AccessControl, &Caller) -> Result<(), String>,
+) -> Result<()> {
+    let access = ACCESS_CONTROL.get_or_init(|| AccessControl::new(&AccessArgs::default()));
+    let (caller, reason) = match caller {
+        Ok(caller) => match authorize(access, &caller) {
+            Ok(()) => return Ok(()),
+            Err(reason) => (caller.to_string(), reason),
+        },
+        Err(e) => (
+            "unknown caller".to_string(),
+            format!("cannot identify the caller: {e}"),
+        ),
+    };
+
+    let rejection = Rejection::Denied(reason);
+    Err(reject(access, method, &caller, rejection, Instant::now()).await)
+}
+
+// log and record the rejected call of `method`, as long as rejections are
+// not coming too fast
+async fn reject(
+    access: &AccessControl,
+    method: &str,
+    caller: &str,
+    rejection: Rejection,
+    now: Instant,
+) -> anyhow::Error {
+    let (reason, log) = match rejection {
+        Rejection::Denied(reason) => (reason, true),
+        Rejection::Throttled { first } => ("rate limit exceeded".to_string(), first),
+    };
+
+    // AA is only locked to record the rejections logged
+    if !log || !access.log_rejection(now) {
+        debug!(target: "audit", "rejected {method} from {caller}: {reason}");
+        return anyhow!("{method} rejected: {reason}");
+    }
+
+    warn!(target: "audit", "rejected {method} from {caller}: {reason}");
+    ASYNC_ATTESTATION_AGENT.lock().await.record_rejected_call(
+        method,
+        caller,
+        anyhow!(reason.clone()),
+    );
+    anyhow!("{method} rejected: {reason}")
+}
+
+/// Check the caller of a ttRPC call of `method`.
+#[cfg(feature = "ttrpc")]
+pub async fn check_ttrpc(
+    ctx: &::ttrpc::r#async::TtrpcContext,
+    method: &str,
+) -> ::ttrpc::Result<()> {
+    check(Caller::from_fd(ctx.fd), method)
+        .await
+        .map_err(ttrpc_denied)
+}
+
+/// Check the caller of a ttRPC call deriving the keys of `purpose`.
+#[cfg(feature = "ttrpc")]
+pub async fn check_derive_key_ttrpc(
+    ctx: &::ttrpc::r#async::TtrpcContext,
+    purpose: &str,
+) -> ::ttrpc::Result<()> {
+    check_derive_key(Caller::from_fd(ctx.fd), purpose)
+        .await
+        .map_err(ttrpc_denied)
+}
+
+/// Check the caller of a privileged ttRPC call of `method`.
+#[cfg(feature = "ttrpc")]
+pub async fn check_privileged_ttrpc(
+    ctx: &::ttrpc::r#async::TtrpcContext,
+    method: &str,
+) -> ::ttrpc::Result<()> {
+    check_privileged(Caller::from_fd(ctx.fd), method)
+        .await
+        .map_err(ttrpc_denied)
+}
+
+#[cfg(feature = "ttrpc")]
+fn ttrpc_denied(e: anyhow::Error) -> ::ttrpc::Error {
+    let mut error_status = ::ttrpc::proto::Status::new();
+    error_status.set_code(::ttrpc::proto::Code::PERMISSION_DENIED);
+    error_status.set_message(format!("[ERROR:{}] {}", crate::rpc::AGENT_NAME, e));
+    ::ttrpc::Error::RpcStatus(error_status)
+}
+
+/// Check the caller of a gRPC call of `method`.
+#[cfg(feature = "grpc")]
+pub async fn check_grpc<T>(request: &tonic::Request<T>, method: &str) -> Result<(), tonic::Status> {
+    check(Caller::from_request(request), method)
+        .await
+        .map_err(grpc_denied)
+}
+
+/// Check the caller of a gRPC call deriving the keys of `purpose`.
+#[cfg(feature = "grpc")]
+pub async fn check_derive_key_grpc<T>(
+    request: &tonic::Request<T>,
+    purpose: &str,
+) -> Result<(), tonic::Status> {
+    check_derive_key(Caller::from_request(request), purpose)
+        .await
+        .map_err(grpc_denied)
+}
+
+/// Check the caller of a privileged gRPC call of `method`.
+#[cfg(feature = "grpc")]
+pub async fn check_privileged_grpc<T>(
+    request: &tonic::Request<T>,
+    method: &str,
+) -> Result<(), tonic::Status> {
+    check_privileged(Caller::from_request(request), method)
+        .await
+        .map_err(grpc_denied)
+}
+
+#[cfg(feature = "grpc")]
+fn grpc_denied(e: anyhow::Error) -> tonic::Status {
+    tonic::Status::permission_denied(format!("[ERROR:{}] {}", crate::rpc::AGENT_NAME, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn unix(uid: u32, label: Option<&str>) -> Caller {
+        Caller::Unix {
+            uid,
+            pid: None,
+            label: label.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_measured_identity() {
+        let own = Caller::own().unwrap();
+        let exe = std::env::current_exe().unwrap();
+        assert!(own
+            .measured_identity()
+            .unwrap()
+            .starts_with(&format!("{} (uid ", exe.display())));
+
+        assert_eq!(unix(0, None).measured_identity(), None);
+        let vsock = Caller::Vsock { cid: 2, port: 1024 };
+        assert_eq!(vsock.measured_identity(), None);
+    }
+
+    #[test]
+    fn test_parse_port_range() {
+        assert_eq!(
+            parse_port_range("1024").unwrap(),
+            PortRange {
+                start: 1024,
+                end: 1024
+            }
+        );
+        assert_eq!(
+            parse_port_range("1024-2047").unwrap(),
+            PortRange {
+                start: 1024,
+                end: 2047
+            }
+        );
+        assert!(parse_port_range("2047-1024").is_err());
+        assert!(parse_port_range("port").is_err());
+        assert!(parse_port_range("1-").is_err());
+    }
+
+    #[test]
+    fn test_authorize() {
+        let open = AccessControl::new(&AccessArgs::default());
+        assert!(!open.is_enabled());
+        assert_eq!(open.authorize(&unix(1000, None)), Ok(()));
+        assert_eq!(open.authorize(&Caller::Tcp([127, 0, 0, 1].into())), Ok(()));
+
+        let access = AccessControl::new(&AccessArgs {
+            allow_uid: vec![0],
+            allow_selinux_label: vec!["system_u:system_r:kata_agent_t:s0".into()],
+            allow_vsock_port: vec![parse_port_range("1024-2047").unwrap()],
+            rate_limit: None,
+            global_rate_limit: None,
+            allow_derive_key: Vec::new(),
+        });
+        assert!(access.is_enabled());
+        assert_eq!(
+            access.authorize(&unix(0, Some("system_u:system_r:kata_agent_t:s0"))),
+            Ok(())
+        );
+        assert!(access
+            .authorize(&unix(1000, Some("system_u:system_r:kata_agent_t:s0")))
+            .is_err());
+        assert!(access
+            .authorize(&unix(0, Some("system_u:system_r:container_t:s0")))
+            .is_err());
+        assert!(access.authorize(&unix(0, None)).is_err());
+        assert!(access
+            .authorize(&Caller::Tcp([127, 0, 0, 1].into()))
+            .is_err());
+        assert_eq!(
+            access.authorize(&Caller::Vsock { cid: 2, port: 2047 }),
+            Ok(())
+        );
+        assert!(access
+            .authorize(&Caller::Vsock { cid: 2, port: 4096 })
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_purpose_uid() {
+        assert_eq!(
+            parse_purpose_uid("eccfs=0").unwrap(),
+            PurposeUid {
+                purpose: "eccfs".into(),
+                uid: 0
+            }
+        );
+        assert!(parse_purpose_uid("eccfs").is_err());
+        assert!(parse_purpose_uid("=0").is_err());
+        assert!(parse_purpose_uid("eccfs=root").is_err());
+    }
+
+    #[test]
+    fn test_authorize_derive_key() {
+        let open = AccessControl::new(&AccessArgs::default());
+        assert_eq!(open.authorize_derive_key(&unix(0, None), "eccfs"), Ok(()));
+        assert!(open
+            .authorize_derive_key(&unix(1000, None), "eccfs")
+            .is_err());
+        let vsock = Caller::Vsock { cid: 2, port: 1024 };
+        assert!(open.authorize_derive_key(&vsock, "eccfs").is_err());
+        let tcp = Caller::Tcp([127, 0, 0, 1].into());
+        assert!(open.authorize_derive_key(&tcp, "eccfs").is_err());
+
+        let access = AccessControl::new(&AccessArgs {
+            allow_derive_key: vec![
+                parse_purpose_uid("app=1000").unwrap(),
+                parse_purpose_uid("app=1001").unwrap(),
+            ],
+            ..Default::default()
+        });
+        assert_eq!(
+            access.authorize_derive_key(&unix(1001, None), "app"),
+            Ok(())
+        );
+        assert!(access.authorize_derive_key(&unix(0, None), "app").is_err());
+        assert!(access
+            .authorize_derive_key(&unix(1000, None), "cdh")
+            .is_err());
+        assert_eq!(access.authorize_derive_key(&unix(0, None), "cdh"), Ok(()));
+    }
+
+    #[test]
+    fn test_authorize_privileged() {
+        let access = AccessControl::new(&AccessArgs {
+            allow_uid: vec![0, 1000],
+            ..Default::default()
+        });
+        assert_eq!(
+            access.authorize_privileged(&unix(0, None), "ReloadConfig"),
+            Ok(())
+        );
+        assert!(access
+            .authorize_privileged(&unix(1000, None), "ReloadConfig")
+            .is_err());
+        let vsock = Caller::Vsock { cid: 2, port: 1024 };
+        assert!(access.authorize_privileged(&vsock, "ReloadConfig").is_err());
+        let tcp = Caller::Tcp([127, 0, 0, 1].into());
+        assert!(access.authorize_privileged(&tcp, "ReloadConfig").is_err());
+    }
+
+    #[test]
+    fn test_check_privileged() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        // claims can't be taken by unprivileged processes
+        let unprivileged = check_privileged(Ok(unix(1000, None)), "RegisterClaims");
+        assert!(runtime.block_on(unprivileged).is_err());
+        let root = check_privileged(Ok(unix(0, None)), "RegisterClaims");
+        runtime.block_on(root).unwrap();
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let access = AccessControl::new(&AccessArgs {
+            rate_limit: Some(2),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        let root = unix(0, None);
+        assert_eq!(access.admit(&root, start), Ok(()));
+        assert_eq!(access.admit(&root, start), Ok(()));
+        assert_eq!(
+            access.admit(&root, start),
+            Err(Rejection::Throttled { first: true })
+        );
+        assert_eq!(
+            access.admit(&root, start),
+            Err(Rejection::Throttled { first: false })
+        );
+
+        // other callers have their own rate
+        assert_eq!(access.admit(&unix(1000, None), start), Ok(()));
+
+        // but not the other processes of the same uid
+        let process = Caller::Unix {
+            uid: 0,
+            pid: Some(1),
+            label: None,
+        };
+        assert_eq!(
+            access.admit(&process, start),
+            Err(Rejection::Throttled { first: false })
+        );
+
+        // a call is refilled every 30s
+        let later = start + Duration::from_secs(30);
+        assert_eq!(access.admit(&root, later), Ok(()));
+        assert_eq!(
+            access.admit(&root, later),
+            Err(Rejection::Throttled { first: true })
+        );
+    }
+
+    #[test]
+    fn test_rate_limit_denied() {
+        let access = AccessControl::new(&AccessArgs {
+            allow_uid: vec![0],
+            rate_limit: Some(1),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        let other = unix(1000, None);
+        assert!(matches!(
+            access.admit(&other, start),
+            Err(Rejection::Denied(_))
+        ));
+
+        // denied callers are throttled before they are authorized again
+        assert_eq!(
+            access.admit(&other, start),
+            Err(Rejection::Throttled { first: true })
+        );
+    }
+
+    #[test]
+    fn test_global_rate_limit() {
+        let access = AccessControl::new(&AccessArgs {
+            rate_limit: Some(2),
+            global_rate_limit: Some(3),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        for uid in 0..3 {
+            assert_eq!(access.admit(&unix(uid, None), start), Ok(()));
+        }
+        assert_eq!(
+            access.admit(&unix(3, None), start),
+            Err(Rejection::Throttled { first: true })
+        );
+        assert_eq!(
+            access.admit(&unix(0, None), start + Duration::from_secs(20)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_max_tracked_callers() {
+        let access = AccessControl::new(&AccessArgs {
+            rate_limit: Some(2),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        for uid in 0..MAX_TRACKED_CALLERS as u32 {
+            let now = start + Duration::from_millis(uid.into());
+            assert_eq!(access.admit(&unix(uid, None), now), Ok(()));
+        }
+
+        // the least recently seen caller is forgotten for a new one
+        let later = start + Duration::from_secs(2);
+        let new = unix(MAX_TRACKED_CALLERS as u32, None);
+        assert_eq!(access.admit(&new, later), Ok(()));
+        let buckets = lock(&access.buckets);
+        assert_eq!(buckets.len(), MAX_TRACKED_CALLERS);
+        assert!(!buckets.contains_key("uid:0"));
+    }
+
+    #[test]
+    fn test_rate_limit_per_uid() {
+        let access = AccessControl::new(&AccessArgs {
+            rate_limit: Some(2),
+            ..Default::default()
+        });
+        let start = Instant::now();
+
+        // the processes of a uid share its rate, however many it forks
+        for pid in 0..2 * MAX_TRACKED_CALLERS as u32 {
+            let process = Caller::Unix {
+                uid: 1000,
+                pid: Some(pid),
+                label: None,
+            };
+            let admitted = access.admit(&process, start);
+            assert_eq!(admitted.is_ok(), pid < 2, "pid {pid}");
+        }
+        assert_eq!(lock(&access.buckets).len(), 1);
+
+        // and can't starve the other uids
+        assert_eq!(access.admit(&unix(0, None), start), Ok(()));
+
+        // the label is part of the caller when the callers are admitted by it
+        let access = AccessControl::new(&AccessArgs {
+            allow_selinux_label: vec!["kata_agent_t".into(), "other_t".into()],
+            rate_limit: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(access.admit(&unix(0, Some("kata_agent_t")), start), Ok(()));
+        assert_eq!(access.admit(&unix(0, Some("other_t")), start), Ok(()));
+        assert!(access.admit(&unix(0, Some("kata_agent_t")), start).is_err());
+    }
+
+    #[test]
+    fn test_log_rejection() {
+        let access = AccessControl::new(&AccessArgs::default());
+        let start = Instant::now();
+        for _ in 0..MAX_LOGGED_REJECTIONS {
+            assert!(access.log_rejection(start));
+        }
+        assert!(!access.log_rejection(start));
+        assert!(access.log_rejection(start + Duration::from_secs(1)));
+    }
+
+    #[cfg(feature = "ttrpc")]
+    #[test]
+    fn test_caller_from_fd() {
+        use std::os::unix::io::AsRawFd;
+        use std::os::unix::net::UnixStream;
+
+        let (aa, client) = UnixStream::pair().unwrap();
+        let Caller::Unix { uid, pid, .. } = Caller::from_fd(aa.as_raw_fd()).unwrap() else {
+            panic!("not a unix caller");
+        };
+        assert_eq!(uid, unsafe { libc::getuid() });
+        assert_eq!(pid, Some(std::process::id() as i32));
+        drop(client);
+    }
+}
@@ -3,76 +3,125 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use clap::Parser;
-
 use super::*;
+use crate::socket::{self, SocketArgs};
+use clap::Args;
+use serde::Serialize;
 use std::net::SocketAddr;
+use tokio::net::UnixListener;
+use tokio_stream::wrappers::UnixListenerStream;
+use tonic::transport::server::Router;
 
 const DEFAULT_KEYPROVIDER_ADDR: &str = "127.0.0.1:50000";
 const DEFAULT_GETRESOURCE_ADDR: &str = "127.0.0.1:50001";
 const DEFAULT_ATTESTATION_AGENT_ADDR: &str = "127.0.0.1:50002";
 
-lazy_static! {
-    pub static ref ASYNC_ATTESTATION_AGENT: Arc<tokio::sync::Mutex<AttestationAgent>> =
-        Arc::new(tokio::sync::Mutex::new(AttestationAgent::new()));
-}
+// With ttRPC enabled as well, the gRPC socket options are prefixed with
+// `grpc_` so that they don't clash with the ttRPC ones.
+#[derive(Debug, Args, Serialize)]
+pub struct GrpcArgs {
+    /// Serve the gRPC services.
+    ///
+    /// `--enable_grpc false` turns the gRPC listener off.
+    #[arg(long = "enable_grpc", default_value_t = true, action = clap::ArgAction::Set)]
+    pub enable: bool,
 
-#[derive(Debug, Parser)]
-#[command(author, version, about, long_about = None)]
-struct Cli {
     /// KeyProvider gRPC socket addr.
     ///
-    /// This socket address which the KeyProvider gRPC service
+    /// This TCP or Unix socket address which the KeyProvider gRPC service
     /// will listen to, for example:
     ///
     /// `--keyprovider_sock 127.0.0.1:11223`
-    #[arg(default_value_t = DEFAULT_KEYPROVIDER_ADDR.to_string(), short, long = "keyprovider_sock")]
+    /// `--keyprovider_sock unix:///run/aa/keyprovider.sock`
+    #[cfg_attr(feature = "ttrpc", arg(long = "grpc_keyprovider_sock"))]
+    #[cfg_attr(not(feature = "ttrpc"), arg(short, long = "keyprovider_sock"))]
+    #[arg(default_value_t = DEFAULT_KEYPROVIDER_ADDR.to_string())]
     keyprovider_sock: String,
 
-    /// GetResource gRPC Unix socket addr.
+    /// GetResource gRPC socket addr.
     ///
-    /// This socket address which the GetResource gRPC service
+    /// This TCP or Unix socket address which the GetResource gRPC service
     /// will listen to, for example:
     ///
     /// `--getresource_sock 127.0.0.1:11223`
-    #[arg(default_value_t = DEFAULT_GETRESOURCE_ADDR.to_string(), short, long = "getresource_sock")]
+    #[cfg_attr(feature = "ttrpc", arg(long = "grpc_getresource_sock"))]
+    #[cfg_attr(not(feature = "ttrpc"), arg(short, long = "getresource_sock"))]
+    #[arg(default_value_t = DEFAULT_GETRESOURCE_ADDR.to_string())]
     getresource_sock: String,
 
-    /// Attestation gRPC Unix socket addr.
+    /// Attestation gRPC socket addr.
     ///
-    /// This Unix socket address which the Attestation ttRPC service
+    /// This TCP or Unix socket address which the Attestation gRPC service
     /// will listen to, for example:
     ///
     /// `--attestation_sock 127.0.0.1:11223`
-    #[arg(default_value_t = DEFAULT_ATTESTATION_AGENT_ADDR.to_string(), short, long = "attestation_sock")]
+    #[cfg_attr(feature = "ttrpc", arg(long = "grpc_attestation_sock"))]
+    #[cfg_attr(not(feature = "ttrpc"), arg(short, long = "attestation_sock"))]
+    #[arg(default_value_t = DEFAULT_ATTESTATION_AGENT_ADDR.to_string())]
     attestation_sock: String,
 }
 
-pub async fn grpc_main() -> Result<()> {
-    let cli = Cli::parse();
+/// Where a gRPC service listens.
+pub enum GrpcListener {
+    Tcp(SocketAddr),
+    Unix(UnixListener),
+}
+
+impl GrpcListener {
+    /// Listen on `addr`, a TCP address or a `unix://` path. Unix sockets
+    /// get the ownership and permissions of `socket_args`.
+    fn bind(addr: &str, socket_args: &SocketArgs) -> Result<Self> {
+        let Some(path) = socket::unix_socket_path(addr) else {
+            return Ok(Self::Tcp(addr.parse::<SocketAddr>()?));
+        };
+
+        socket::prepare(path)?;
+        let listener = UnixListener::bind(path)?;
+        socket_args.apply(path)?;
+        Ok(Self::Unix(listener))
+    }
+
+    /// Serve `router` until it fails.
+    pub async fn serve(self, router: Router) -> Result<()> {
+        match self {
+            Self::Tcp(addr) => router.serve(addr).await?,
+            Self::Unix(listener) => {
+                router
+                    .serve_with_incoming(UnixListenerStream::new(listener))
+                    .await?
+            }
+        }
+        Ok(())
+    }
+}
 
-    let keyprovider_socket = cli.keyprovider_sock.parse::<SocketAddr>()?;
+pub async fn grpc_main(args: &GrpcArgs, socket_args: &SocketArgs) -> Result<()> {
+    let keyprovider_socket = GrpcListener::bind(&args.keyprovider_sock, socket_args)
+        .context("cannot bind keyprovider grpc service")?;
 
-    let getresource_socket = cli.getresource_sock.parse::<SocketAddr>()?;
+    let getresource_socket = GrpcListener::bind(&args.getresource_sock, socket_args)
+        .context("cannot bind getresource grpc service")?;
 
-    let attestation_socket = cli.attestation_sock.parse::<SocketAddr>()?;
+    let attestation_socket = GrpcListener::bind(&args.attestation_sock, socket_args)
+        .context("cannot bind attestation grpc service")?;
 
     debug!(
         "KeyProvider gRPC service listening on: {:?}",
-        cli.keyprovider_sock
+        args.keyprovider_sock
     );
     debug!(
         "GetResource gRPC service listening on: {:?}",
-        cli.getresource_sock
+        args.getresource_sock
     );
     debug!(
         "Attestation gRPC service listening on: {:?}",
-        cli.attestation_sock
+        args.attestation_sock
     );
 
     let keyprovider_server = rpc::keyprovider::grpc::start_grpc_service(keyprovider_socket);
     let getresource_server = rpc::getresource::grpc::start_grpc_service(getresource_socket);
     let attestation_server = rpc::attestation::grpc::start_grpc_service(attestation_socket);
 
-    tokio::join!(keyprovider_server, getresource_server, attestation_server).0
+    tokio::try_join!(keyprovider_server, getresource_server, attestation_server)?;
+    Ok(())
 }
@@ -7,8 +7,13 @@
 extern crate lazy_static;
 
 use anyhow::*;
+use attestation_agent::config_measurement::ConfigMeasurement;
 use attestation_agent::AttestationAgent;
+use clap::Parser;
 use log::*;
+use serde::Serialize;
+use serde_json::json;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 #[cfg(feature = "ttrpc")]
@@ -17,19 +22,164 @@ mod ttrpc;
 #[cfg(feature = "grpc")]
 mod grpc;
 
+mod access;
+mod reload;
 mod rpc;
+mod socket;
+
+lazy_static! {
+    /// The AA shared by the services of all the listeners.
+    pub static ref ASYNC_ATTESTATION_AGENT: Arc<tokio::sync::Mutex<AttestationAgent>> =
+        Arc::new(tokio::sync::Mutex::new(AttestationAgent::new()));
+}
+
+/// AA serves ttRPC (for kata-agent) and gRPC (for in-pod clients like
+/// sidecars) at the same time if both features are enabled. Each of the
+/// listeners can be turned off at runtime.
+///
+/// The effective options and the config loaded from `--config` are
+/// measured at startup, see [`attestation_agent::config_measurement`].
+#[derive(Debug, Parser, Serialize)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    #[cfg(feature = "ttrpc")]
+    #[command(flatten)]
+    ttrpc: ttrpc::TtrpcArgs,
+
+    #[cfg(feature = "grpc")]
+    #[command(flatten)]
+    grpc: grpc::GrpcArgs,
+
+    #[command(flatten)]
+    socket: socket::SocketArgs,
+
+    #[command(flatten)]
+    access: access::AccessArgs,
+
+    /// Pre-attested file to hand out the token of instead of attesting, for
+    /// tests and environments without TEE hardware only. Needs
+    /// `--insecure_pre_attested`, for example:
+    ///
+    /// `--pre_attested /run/aa/pre-attested.json --insecure_pre_attested`
+    #[arg(long = "pre_attested")]
+    pre_attested: Option<PathBuf>,
+
+    /// Allow the insecure `--pre_attested` mode.
+    #[arg(long = "insecure_pre_attested")]
+    insecure_pre_attested: bool,
+
+    /// TOML config file setting the KBS, which is reloaded on SIGHUP or
+    /// with the `ReloadConfig` API, see
+    /// [`attestation_agent::config`], for example:
+    ///
+    /// `--config /etc/attestation-agent/config.toml`
+    #[arg(long = "config")]
+    config: Option<PathBuf>,
+
+    /// OTLP/gRPC endpoint of the OpenTelemetry collector the spans of the
+    /// requests are exported to, see [`telemetry`]. Not exported if not
+    /// given, for example:
+    ///
+    /// `--otlp_endpoint http://127.0.0.1:4317`
+    #[cfg(feature = "otlp")]
+    #[arg(long = "otlp_endpoint")]
+    otlp_endpoint: Option<String>,
+}
 
 #[tokio::main]
 async fn main() {
     env_logger::init();
 
-    cfg_if::cfg_if! {
-        if #[cfg(feature = "ttrpc")] {
-            ttrpc::ttrpc_main().await.unwrap();
-        } else if #[cfg(feature = "grpc")] {
-            grpc::grpc_main().await.unwrap();
-        } else {
-            compile_error!("one feature of `grpc` or `ttrpc` must be enabled.");
+    #[cfg(not(any(feature = "ttrpc", feature = "grpc")))]
+    compile_error!("at least one feature of `grpc` or `ttrpc` must be enabled.");
+
+    let cli = Cli::parse();
+    #[cfg(feature = "otlp")]
+    let otlp_endpoint = cli.otlp_endpoint.as_deref();
+    #[cfg(not(feature = "otlp"))]
+    let otlp_endpoint = None;
+    if let Err(e) = telemetry::init("attestation-agent", otlp_endpoint) {
+        error!("{:?}", e.context("init telemetry"));
+        std::process::exit(1);
+    }
+
+    let res = serve(&cli).await;
+    telemetry::shutdown();
+    if let Err(e) = res {
+        error!("{:?}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Serve the enabled listeners until one of them exits.
+async fn serve(cli: &Cli) -> Result<()> {
+    if let Some(path) = &cli.pre_attested {
+        ASYNC_ATTESTATION_AGENT
+            .lock()
+            .await
+            .enable_pre_attested(path, cli.insecure_pre_attested)
+            .await
+            .context("pre-attested mode")?;
+    }
+
+    if let Some(path) = &cli.config {
+        reload::init(path).await.context("load config")?;
+    }
+
+    let caller = access::Caller::own()?
+        .measured_identity()
+        .context("identify AA")?;
+    let mut attestation_agent = ASYNC_ATTESTATION_AGENT.lock().await;
+    let config = json!({
+        "cli": cli,
+        "config": attestation_agent.config(),
+    });
+    let measurement = ConfigMeasurement::new("aa", &config)?;
+    info!("AA configuration digest: {}", measurement.digest);
+    attestation_agent
+        .measure_config(measurement, &caller)
+        .await
+        .context("measure configuration")?;
+    drop(attestation_agent);
+
+    access::init(&cli.access);
+
+    let mut enabled = false;
+
+    #[cfg(feature = "ttrpc")]
+    let ttrpc = {
+        enabled |= cli.ttrpc.enable;
+        async {
+            if cli.ttrpc.enable {
+                ttrpc::ttrpc_main(&cli.ttrpc, &cli.socket).await
+            } else {
+                std::future::pending().await
+            }
+        }
+    };
+    #[cfg(not(feature = "ttrpc"))]
+    let ttrpc = std::future::pending::<Result<()>>();
+
+    #[cfg(feature = "grpc")]
+    let grpc = {
+        enabled |= cli.grpc.enable;
+        async {
+            if cli.grpc.enable {
+                grpc::grpc_main(&cli.grpc, &cli.socket).await
+            } else {
+                std::future::pending().await
+            }
         }
+    };
+    #[cfg(not(feature = "grpc"))]
+    let grpc = std::future::pending::<Result<()>>();
+
+    if !enabled {
+        bail!("all the listeners are disabled");
+    }
+
+    tokio::select! {
+        res = ttrpc => res.context("ttRPC listener"),
+        res = grpc => res.context("gRPC listener"),
     }
 }
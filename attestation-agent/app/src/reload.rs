@@ -0,0 +1,57 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Reload of the config file of AA, see [`attestation_agent::config`].
+//!
+//! The file given with `--config` is read at startup, and read again on
+//! SIGHUP or when a root process calls the `ReloadConfig` API. Configs are
+//! only ever read from the file, so that a caller cannot point AA to
+//! another KBS.
+
+use anyhow::{bail, Context, Result};
+use attestation_agent::config::Config;
+use log::*;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::ASYNC_ATTESTATION_AGENT;
+
+/// The config file given at startup.
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Put the config file at `path` in effect, and reload it on SIGHUP.
+pub async fn init(path: &Path) -> Result<()> {
+    CONFIG_FILE
+        .set(path.to_path_buf())
+        .expect("config file set twice");
+    reload().await?;
+
+    let mut hangup = signal(SignalKind::hangup()).context("listen for SIGHUP")?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match reload().await {
+                Ok(()) => info!("AA config reloaded on SIGHUP"),
+                Err(e) => error!("reload AA config on SIGHUP failed, keeping the old one: {e:#}"),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Put the config file given at startup in effect.
+pub async fn reload() -> Result<()> {
+    let Some(path) = CONFIG_FILE.get() else {
+        bail!("AA was started without a config file");
+    };
+    let config = Config::load(path).await?;
+
+    ASYNC_ATTESTATION_AGENT
+        .lock()
+        .await
+        .reload_config(config)
+        .await
+}
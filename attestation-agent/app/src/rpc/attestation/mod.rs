@@ -3,6 +3,8 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use attestation_agent::derived_key::DEFAULT_KEY_LEN;
+use attestation_agent::ima::DEFAULT_POLICY_ENTRY;
 use attestation_agent::AttestationAPIs;
 use log::*;
 use std::sync::Arc;
@@ -15,16 +17,21 @@ pub struct Attestation {}
 #[cfg(feature = "grpc")]
 pub mod grpc {
     use super::*;
-    use crate::grpc::ASYNC_ATTESTATION_AGENT;
+    use crate::grpc::GrpcListener;
+    use crate::ASYNC_ATTESTATION_AGENT;
     use anyhow::*;
     use attestation::attestation_agent_service_server::{
         AttestationAgentService, AttestationAgentServiceServer,
     };
     use attestation::{
-        ExtendRuntimeMeasurementRequest, ExtendRuntimeMeasurementResponse, GetEvidenceRequest,
-        GetEvidenceResponse, GetTokenRequest, GetTokenResponse,
+        DeriveKeyRequest, DeriveKeyResponse, ExportAuditLogRequest, ExportAuditLogResponse,
+        ExtendRuntimeMeasurementRequest, ExtendRuntimeMeasurementResponse,
+        GetChallengeEvidenceRequest, GetChallengeEvidenceResponse, GetEvidenceRequest,
+        GetEvidenceResponse, GetTokenRequest, GetTokenResponse, ProvisionInitDataRequest,
+        ProvisionInitDataResponse, RegisterClaimsRequest, RegisterClaimsResponse,
+        ReloadConfigRequest, ReloadConfigResponse, SetImaPolicyRequest, SetImaPolicyResponse,
     };
-    use std::net::SocketAddr;
+    use telemetry::FutureExt;
     use tonic::{transport::Server, Request, Response, Status};
 
     mod attestation {
@@ -37,6 +44,9 @@ pub mod grpc {
             &self,
             request: Request<GetTokenRequest>,
         ) -> Result<Response<GetTokenResponse>, Status> {
+            let span = telemetry::grpc_server_span(&request, "GetToken");
+            crate::access::check_grpc(&request, "GetToken").await?;
+
             let request = request.into_inner();
 
             let attestation_agent_mutex_clone = Arc::clone(&ASYNC_ATTESTATION_AGENT);
@@ -46,6 +56,7 @@ pub mod grpc {
 
             let token = attestation_agent
                 .get_token(&request.token_type)
+                .with_context(span)
                 .await
                 .map_err(|e| {
                     error!("Call AA to get token failed: {}", e);
@@ -63,6 +74,9 @@ pub mod grpc {
             &self,
             request: Request<GetEvidenceRequest>,
         ) -> Result<Response<GetEvidenceResponse>, Status> {
+            let span = telemetry::grpc_server_span(&request, "GetEvidence");
+            crate::access::check_grpc(&request, "GetEvidence").await?;
+
             let request = request.into_inner();
 
             let attestation_agent_mutex_clone = Arc::clone(&ASYNC_ATTESTATION_AGENT);
@@ -72,6 +86,7 @@ pub mod grpc {
 
             let evidence = attestation_agent
                 .get_evidence(&request.runtime_data)
+                .with_context(span)
                 .await
                 .map_err(|e| {
                     error!("Call AA to get evidence failed: {}", e);
@@ -88,10 +103,59 @@ pub mod grpc {
             Result::Ok(Response::new(reply))
         }
 
+        async fn get_challenge_evidence(
+            &self,
+            request: Request<GetChallengeEvidenceRequest>,
+        ) -> Result<Response<GetChallengeEvidenceResponse>, Status> {
+            let span = telemetry::grpc_server_span(&request, "GetChallengeEvidence");
+            crate::access::check_grpc(&request, "GetChallengeEvidence").await?;
+
+            let request = request.into_inner();
+
+            let attestation_agent_mutex_clone = Arc::clone(&ASYNC_ATTESTATION_AGENT);
+            let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
+
+            debug!("Call AA to get challenge evidence ...");
+
+            let evidence = attestation_agent
+                .get_challenge_evidence(&request.nonce, &request.runtime_data)
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to get challenge evidence failed: {}", e);
+                    Status::internal(format!(
+                        "[ERROR:{}] AA get challenge evidence failed: {}",
+                        AGENT_NAME, e
+                    ))
+                })?;
+
+            debug!("Get challenge evidence successfully!");
+
+            let ima = evidence.ima;
+            let reply = GetChallengeEvidenceResponse {
+                tee: evidence.tee,
+                evidence: evidence.evidence,
+                runtime_data: evidence.runtime_data,
+                ima_measurements: ima
+                    .as_ref()
+                    .map(|ima| ima.measurements.clone())
+                    .unwrap_or_default(),
+                evm_status: ima.and_then(|ima| ima.evm_status),
+            };
+
+            Result::Ok(Response::new(reply))
+        }
+
         async fn extend_runtime_measurement(
             &self,
             request: Request<ExtendRuntimeMeasurementRequest>,
         ) -> Result<Response<ExtendRuntimeMeasurementResponse>, Status> {
+            let span = telemetry::grpc_server_span(&request, "ExtendRuntimeMeasurement");
+            crate::access::check_grpc(&request, "ExtendRuntimeMeasurement").await?;
+
+            let caller = crate::access::Caller::from_request(&request)
+                .ok()
+                .and_then(|caller| caller.measured_identity());
             let request = request.into_inner();
 
             let attestation_agent_mutex_clone = Arc::clone(&ASYNC_ATTESTATION_AGENT);
@@ -100,7 +164,12 @@ pub mod grpc {
             debug!("Call AA to extend runtime measurement ...");
 
             attestation_agent
-                .extend_runtime_measurement(request.events, request.register_index)
+                .extend_runtime_measurement_of(
+                    caller.as_deref(),
+                    request.events,
+                    request.register_index,
+                )
+                .with_context(span)
                 .await
                 .map_err(|e| {
                     error!("Call AA to extend runtime measurement failed: {}", e);
@@ -116,15 +185,217 @@ pub mod grpc {
 
             Result::Ok(Response::new(reply))
         }
+
+        async fn provision_init_data(
+            &self,
+            request: Request<ProvisionInitDataRequest>,
+        ) -> Result<Response<ProvisionInitDataResponse>, Status> {
+            let span = telemetry::grpc_server_span(&request, "ProvisionInitData");
+            crate::access::check_grpc(&request, "ProvisionInitData").await?;
+
+            let request = request.into_inner();
+
+            let attestation_agent_mutex_clone = Arc::clone(&ASYNC_ATTESTATION_AGENT);
+            let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
+
+            debug!("Call AA to provision init-data ...");
+
+            let provisioned = attestation_agent
+                .provision_init_data(&request.init_data)
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to provision init-data failed: {}", e);
+                    Status::internal(format!(
+                        "[ERROR:{}] AA provision init-data failed: {}",
+                        AGENT_NAME, e
+                    ))
+                })?;
+
+            debug!("Provision init-data successfully!");
+
+            let reply = ProvisionInitDataResponse {
+                digest: provisioned.digest,
+                hardware_bound: provisioned.hardware_bound,
+                entries: provisioned.entries,
+            };
+
+            Result::Ok(Response::new(reply))
+        }
+
+        async fn register_claims(
+            &self,
+            request: Request<RegisterClaimsRequest>,
+        ) -> Result<Response<RegisterClaimsResponse>, Status> {
+            let span = telemetry::grpc_server_span(&request, "RegisterClaims");
+            crate::access::check_grpc(&request, "RegisterClaims").await?;
+            crate::access::check_privileged_grpc(&request, "RegisterClaims").await?;
+
+            let request = request.into_inner();
+
+            let attestation_agent_mutex_clone = Arc::clone(&ASYNC_ATTESTATION_AGENT);
+            let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
+
+            debug!("Call AA to register claims ...");
+
+            attestation_agent
+                .register_claims(request.claims)
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to register claims failed: {}", e);
+                    Status::internal(format!(
+                        "[ERROR:{}] AA register claims failed: {}",
+                        AGENT_NAME, e
+                    ))
+                })?;
+
+            debug!("Register claims successfully!");
+
+            let reply = RegisterClaimsResponse {};
+
+            Result::Ok(Response::new(reply))
+        }
+
+        async fn set_ima_policy(
+            &self,
+            request: Request<SetImaPolicyRequest>,
+        ) -> Result<Response<SetImaPolicyResponse>, Status> {
+            let span = telemetry::grpc_server_span(&request, "SetImaPolicy");
+            crate::access::check_grpc(&request, "SetImaPolicy").await?;
+
+            let request = request.into_inner();
+
+            let attestation_agent_mutex_clone = Arc::clone(&ASYNC_ATTESTATION_AGENT);
+            let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
+
+            debug!("Call AA to set IMA policy ...");
+
+            let entry = match request.init_data_entry.as_str() {
+                "" => DEFAULT_POLICY_ENTRY,
+                entry => entry,
+            };
+            attestation_agent
+                .set_ima_policy(entry)
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to set IMA policy failed: {}", e);
+                    Status::internal(format!(
+                        "[ERROR:{}] AA set IMA policy failed: {}",
+                        AGENT_NAME, e
+                    ))
+                })?;
+
+            debug!("Set IMA policy successfully!");
+
+            let reply = SetImaPolicyResponse {};
+
+            Result::Ok(Response::new(reply))
+        }
+
+        async fn derive_key(
+            &self,
+            request: Request<DeriveKeyRequest>,
+        ) -> Result<Response<DeriveKeyResponse>, Status> {
+            let span = telemetry::grpc_server_span(&request, "DeriveKey");
+            crate::access::check_grpc(&request, "DeriveKey").await?;
+            crate::access::check_derive_key_grpc(&request, &request.get_ref().purpose).await?;
+
+            let request = request.into_inner();
+
+            let attestation_agent_mutex_clone = Arc::clone(&ASYNC_ATTESTATION_AGENT);
+            let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
+
+            debug!("Call AA to derive key ...");
+
+            let len = match request.length {
+                0 => DEFAULT_KEY_LEN,
+                len => len as usize,
+            };
+            let key = attestation_agent
+                .derive_key(&request.purpose, &request.context, request.per_boot, len)
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to derive key failed: {}", e);
+                    Status::internal(format!(
+                        "[ERROR:{}] AA derive key failed: {}",
+                        AGENT_NAME, e
+                    ))
+                })?;
+
+            debug!("Derive key successfully!");
+
+            let reply = DeriveKeyResponse { key };
+
+            Result::Ok(Response::new(reply))
+        }
+
+        async fn reload_config(
+            &self,
+            request: Request<ReloadConfigRequest>,
+        ) -> Result<Response<ReloadConfigResponse>, Status> {
+            let span = telemetry::grpc_server_span(&request, "ReloadConfig");
+            crate::access::check_grpc(&request, "ReloadConfig").await?;
+            crate::access::check_privileged_grpc(&request, "ReloadConfig").await?;
+
+            debug!("Call AA to reload config ...");
+
+            crate::reload::reload()
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to reload config failed: {:#}", e);
+                    Status::internal(format!(
+                        "[ERROR:{}] AA reload config failed: {:#}",
+                        AGENT_NAME, e
+                    ))
+                })?;
+
+            debug!("Reload config successfully!");
+
+            let reply = ReloadConfigResponse {};
+
+            Result::Ok(Response::new(reply))
+        }
+
+        async fn export_audit_log(
+            &self,
+            request: Request<ExportAuditLogRequest>,
+        ) -> Result<Response<ExportAuditLogResponse>, Status> {
+            let span = telemetry::grpc_server_span(&request, "ExportAuditLog");
+            crate::access::check_grpc(&request, "ExportAuditLog").await?;
+
+            let attestation_agent_mutex_clone = Arc::clone(&ASYNC_ATTESTATION_AGENT);
+            let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
+
+            debug!("Call AA to export audit log ...");
+
+            let log = attestation_agent
+                .export_audit_log()
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to export audit log failed: {}", e);
+                    Status::internal(format!(
+                        "[ERROR:{}] AA export audit log failed: {}",
+                        AGENT_NAME, e
+                    ))
+                })?;
+
+            debug!("Export audit log successfully!");
+
+            let reply = ExportAuditLogResponse { log };
+
+            Result::Ok(Response::new(reply))
+        }
     }
 
-    pub async fn start_grpc_service(socket: SocketAddr) -> Result<()> {
+    pub async fn start_grpc_service(listener: GrpcListener) -> Result<()> {
         let service = Attestation::default();
-        Server::builder()
-            .add_service(AttestationAgentServiceServer::new(service))
-            .serve(socket)
-            .await?;
-        Ok(())
+        let router = Server::builder().add_service(AttestationAgentServiceServer::new(service));
+        listener.serve(router).await
     }
 }
 
@@ -135,11 +406,12 @@ pub mod ttrpc {
         create_attestation_agent_service, AttestationAgentService,
     };
     use crate::rpc::ttrpc_protocol::{attestation_agent, attestation_agent_ttrpc};
-    use crate::ttrpc::ASYNC_ATTESTATION_AGENT;
+    use crate::ASYNC_ATTESTATION_AGENT;
     use ::ttrpc::asynchronous::Service;
     use ::ttrpc::proto::Code;
     use anyhow::*;
     use async_trait::async_trait;
+    use telemetry::FutureExt;
 
     use std::collections::HashMap;
 
@@ -147,9 +419,12 @@ pub mod ttrpc {
     impl attestation_agent_ttrpc::AttestationAgentService for Attestation {
         async fn get_token(
             &self,
-            _ctx: &::ttrpc::r#async::TtrpcContext,
+            ctx: &::ttrpc::r#async::TtrpcContext,
             req: attestation_agent::GetTokenRequest,
         ) -> ::ttrpc::Result<attestation_agent::GetTokenResponse> {
+            let span = telemetry::ttrpc_server_span(&ctx.metadata, "GetToken");
+            crate::access::check_ttrpc(ctx, "GetToken").await?;
+
             debug!("Call AA to get token ...");
 
             let attestation_agent_mutex_clone = ASYNC_ATTESTATION_AGENT.clone();
@@ -157,6 +432,7 @@ pub mod ttrpc {
 
             let token = attestation_agent
                 .get_token(&req.TokenType)
+                .with_context(span)
                 .await
                 .map_err(|e| {
                     error!("Call AA-KBC to get token failed: {}", e);
@@ -179,9 +455,12 @@ pub mod ttrpc {
 
         async fn get_evidence(
             &self,
-            _ctx: &::ttrpc::r#async::TtrpcContext,
+            ctx: &::ttrpc::r#async::TtrpcContext,
             req: attestation_agent::GetEvidenceRequest,
         ) -> ::ttrpc::Result<attestation_agent::GetEvidenceResponse> {
+            let span = telemetry::ttrpc_server_span(&ctx.metadata, "GetEvidence");
+            crate::access::check_ttrpc(ctx, "GetEvidence").await?;
+
             debug!("Call AA to get evidence ...");
 
             let attestation_agent_mutex_clone = ASYNC_ATTESTATION_AGENT.clone();
@@ -189,6 +468,7 @@ pub mod ttrpc {
 
             let evidence = attestation_agent
                 .get_evidence(&req.RuntimeData)
+                .with_context(span)
                 .await
                 .map_err(|e| {
                     error!("Call AA-KBC to get evidence failed: {}", e);
@@ -209,18 +489,67 @@ pub mod ttrpc {
             ::ttrpc::Result::Ok(reply)
         }
 
+        async fn get_challenge_evidence(
+            &self,
+            ctx: &::ttrpc::r#async::TtrpcContext,
+            req: attestation_agent::GetChallengeEvidenceRequest,
+        ) -> ::ttrpc::Result<attestation_agent::GetChallengeEvidenceResponse> {
+            let span = telemetry::ttrpc_server_span(&ctx.metadata, "GetChallengeEvidence");
+            crate::access::check_ttrpc(ctx, "GetChallengeEvidence").await?;
+
+            debug!("Call AA to get challenge evidence ...");
+
+            let attestation_agent_mutex_clone = ASYNC_ATTESTATION_AGENT.clone();
+            let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
+
+            let evidence = attestation_agent
+                .get_challenge_evidence(&req.Nonce, &req.RuntimeData)
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to get challenge evidence failed: {}", e);
+                    let mut error_status = ::ttrpc::proto::Status::new();
+                    error_status.set_code(Code::INTERNAL);
+                    error_status.set_message(format!(
+                        "[ERROR:{}] AA get challenge evidence failed: {}",
+                        AGENT_NAME, e
+                    ));
+                    ::ttrpc::Error::RpcStatus(error_status)
+                })?;
+
+            debug!("Get challenge evidence successfully!");
+
+            let mut reply = attestation_agent::GetChallengeEvidenceResponse::new();
+            reply.Tee = evidence.tee;
+            reply.Evidence = evidence.evidence;
+            reply.RuntimeData = evidence.runtime_data;
+            if let Some(ima) = evidence.ima {
+                reply.ImaMeasurements = ima.measurements;
+                reply.EvmStatus = ima.evm_status;
+            }
+
+            ::ttrpc::Result::Ok(reply)
+        }
+
         async fn extend_runtime_measurement(
             &self,
-            _ctx: &::ttrpc::r#async::TtrpcContext,
+            ctx: &::ttrpc::r#async::TtrpcContext,
             req: attestation_agent::ExtendRuntimeMeasurementRequest,
         ) -> ::ttrpc::Result<attestation_agent::ExtendRuntimeMeasurementResponse> {
+            let span = telemetry::ttrpc_server_span(&ctx.metadata, "ExtendRuntimeMeasurement");
+            crate::access::check_ttrpc(ctx, "ExtendRuntimeMeasurement").await?;
+
             debug!("Call AA to extend runtime measurement ...");
 
+            let caller = crate::access::Caller::from_fd(ctx.fd)
+                .ok()
+                .and_then(|caller| caller.measured_identity());
             let attestation_agent_mutex_clone = ASYNC_ATTESTATION_AGENT.clone();
             let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
 
             attestation_agent
-                .extend_runtime_measurement(req.Events, req.RegisterIndex)
+                .extend_runtime_measurement_of(caller.as_deref(), req.Events, req.RegisterIndex)
+                .with_context(span)
                 .await
                 .map_err(|e| {
                     error!("Call AA to extend runtime measurement failed: {}", e);
@@ -236,6 +565,226 @@ pub mod ttrpc {
             let reply = attestation_agent::ExtendRuntimeMeasurementResponse::new();
             ::ttrpc::Result::Ok(reply)
         }
+
+        async fn provision_init_data(
+            &self,
+            ctx: &::ttrpc::r#async::TtrpcContext,
+            req: attestation_agent::ProvisionInitDataRequest,
+        ) -> ::ttrpc::Result<attestation_agent::ProvisionInitDataResponse> {
+            let span = telemetry::ttrpc_server_span(&ctx.metadata, "ProvisionInitData");
+            crate::access::check_ttrpc(ctx, "ProvisionInitData").await?;
+
+            debug!("Call AA to provision init-data ...");
+
+            let attestation_agent_mutex_clone = ASYNC_ATTESTATION_AGENT.clone();
+            let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
+
+            let provisioned = attestation_agent
+                .provision_init_data(&req.InitData)
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to provision init-data failed: {}", e);
+                    let mut error_status = ::ttrpc::proto::Status::new();
+                    error_status.set_code(Code::INTERNAL);
+                    error_status.set_message(format!(
+                        "[ERROR:{}] AA provision init-data failed: {}",
+                        AGENT_NAME, e
+                    ));
+                    ::ttrpc::Error::RpcStatus(error_status)
+                })?;
+
+            debug!("Provision init-data successfully!");
+
+            let mut reply = attestation_agent::ProvisionInitDataResponse::new();
+            reply.Digest = provisioned.digest;
+            reply.HardwareBound = provisioned.hardware_bound;
+            reply.Entries = provisioned.entries;
+
+            ::ttrpc::Result::Ok(reply)
+        }
+
+        async fn register_claims(
+            &self,
+            ctx: &::ttrpc::r#async::TtrpcContext,
+            req: attestation_agent::RegisterClaimsRequest,
+        ) -> ::ttrpc::Result<attestation_agent::RegisterClaimsResponse> {
+            let span = telemetry::ttrpc_server_span(&ctx.metadata, "RegisterClaims");
+            crate::access::check_ttrpc(ctx, "RegisterClaims").await?;
+            crate::access::check_privileged_ttrpc(ctx, "RegisterClaims").await?;
+
+            debug!("Call AA to register claims ...");
+
+            let attestation_agent_mutex_clone = ASYNC_ATTESTATION_AGENT.clone();
+            let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
+
+            attestation_agent
+                .register_claims(req.Claims)
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to register claims failed: {}", e);
+                    let mut error_status = ::ttrpc::proto::Status::new();
+                    error_status.set_code(Code::INTERNAL);
+                    error_status.set_message(format!(
+                        "[ERROR:{}] AA register claims failed: {}",
+                        AGENT_NAME, e
+                    ));
+                    ::ttrpc::Error::RpcStatus(error_status)
+                })?;
+
+            debug!("Register claims successfully!");
+
+            let reply = attestation_agent::RegisterClaimsResponse::new();
+            ::ttrpc::Result::Ok(reply)
+        }
+
+        async fn set_ima_policy(
+            &self,
+            ctx: &::ttrpc::r#async::TtrpcContext,
+            req: attestation_agent::SetImaPolicyRequest,
+        ) -> ::ttrpc::Result<attestation_agent::SetImaPolicyResponse> {
+            let span = telemetry::ttrpc_server_span(&ctx.metadata, "SetImaPolicy");
+            crate::access::check_ttrpc(ctx, "SetImaPolicy").await?;
+
+            debug!("Call AA to set IMA policy ...");
+
+            let attestation_agent_mutex_clone = ASYNC_ATTESTATION_AGENT.clone();
+            let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
+
+            let entry = match req.InitDataEntry.as_str() {
+                "" => DEFAULT_POLICY_ENTRY,
+                entry => entry,
+            };
+            attestation_agent
+                .set_ima_policy(entry)
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to set IMA policy failed: {}", e);
+                    let mut error_status = ::ttrpc::proto::Status::new();
+                    error_status.set_code(Code::INTERNAL);
+                    error_status.set_message(format!(
+                        "[ERROR:{}] AA set IMA policy failed: {}",
+                        AGENT_NAME, e
+                    ));
+                    ::ttrpc::Error::RpcStatus(error_status)
+                })?;
+
+            debug!("Set IMA policy successfully!");
+
+            let reply = attestation_agent::SetImaPolicyResponse::new();
+            ::ttrpc::Result::Ok(reply)
+        }
+
+        async fn derive_key(
+            &self,
+            ctx: &::ttrpc::r#async::TtrpcContext,
+            req: attestation_agent::DeriveKeyRequest,
+        ) -> ::ttrpc::Result<attestation_agent::DeriveKeyResponse> {
+            let span = telemetry::ttrpc_server_span(&ctx.metadata, "DeriveKey");
+            crate::access::check_ttrpc(ctx, "DeriveKey").await?;
+            crate::access::check_derive_key_ttrpc(ctx, &req.Purpose).await?;
+
+            debug!("Call AA to derive key ...");
+
+            let attestation_agent_mutex_clone = ASYNC_ATTESTATION_AGENT.clone();
+            let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
+
+            let len = match req.Length {
+                0 => DEFAULT_KEY_LEN,
+                len => len as usize,
+            };
+            let key = attestation_agent
+                .derive_key(&req.Purpose, &req.Context, req.PerBoot, len)
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to derive key failed: {}", e);
+                    let mut error_status = ::ttrpc::proto::Status::new();
+                    error_status.set_code(Code::INTERNAL);
+                    error_status.set_message(format!(
+                        "[ERROR:{}] AA derive key failed: {}",
+                        AGENT_NAME, e
+                    ));
+                    ::ttrpc::Error::RpcStatus(error_status)
+                })?;
+
+            debug!("Derive key successfully!");
+
+            let mut reply = attestation_agent::DeriveKeyResponse::new();
+            reply.Key = key;
+
+            ::ttrpc::Result::Ok(reply)
+        }
+
+        async fn reload_config(
+            &self,
+            ctx: &::ttrpc::r#async::TtrpcContext,
+            _req: attestation_agent::ReloadConfigRequest,
+        ) -> ::ttrpc::Result<attestation_agent::ReloadConfigResponse> {
+            let span = telemetry::ttrpc_server_span(&ctx.metadata, "ReloadConfig");
+            crate::access::check_ttrpc(ctx, "ReloadConfig").await?;
+            crate::access::check_privileged_ttrpc(ctx, "ReloadConfig").await?;
+
+            debug!("Call AA to reload config ...");
+
+            crate::reload::reload()
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to reload config failed: {:#}", e);
+                    let mut error_status = ::ttrpc::proto::Status::new();
+                    error_status.set_code(Code::INTERNAL);
+                    error_status.set_message(format!(
+                        "[ERROR:{}] AA reload config failed: {:#}",
+                        AGENT_NAME, e
+                    ));
+                    ::ttrpc::Error::RpcStatus(error_status)
+                })?;
+
+            debug!("Reload config successfully!");
+
+            let reply = attestation_agent::ReloadConfigResponse::new();
+
+            ::ttrpc::Result::Ok(reply)
+        }
+
+        async fn export_audit_log(
+            &self,
+            ctx: &::ttrpc::r#async::TtrpcContext,
+            _req: attestation_agent::ExportAuditLogRequest,
+        ) -> ::ttrpc::Result<attestation_agent::ExportAuditLogResponse> {
+            let span = telemetry::ttrpc_server_span(&ctx.metadata, "ExportAuditLog");
+            crate::access::check_ttrpc(ctx, "ExportAuditLog").await?;
+
+            debug!("Call AA to export audit log ...");
+
+            let attestation_agent_mutex_clone = ASYNC_ATTESTATION_AGENT.clone();
+            let mut attestation_agent = attestation_agent_mutex_clone.lock().await;
+
+            let log = attestation_agent
+                .export_audit_log()
+                .with_context(span)
+                .await
+                .map_err(|e| {
+                    error!("Call AA to export audit log failed: {}", e);
+                    let mut error_status = ::ttrpc::proto::Status::new();
+                    error_status.set_code(Code::INTERNAL);
+                    error_status.set_message(format!(
+                        "[ERROR:{}] AA export audit log failed: {}",
+                        AGENT_NAME, e
+                    ));
+                    ::ttrpc::Error::RpcStatus(error_status)
+                })?;
+
+            debug!("Export audit log successfully!");
+
+            let mut reply = attestation_agent::ExportAuditLogResponse::new();
+            reply.Log = log;
+
+            ::ttrpc::Result::Ok(reply)
+        }
     }
 
     pub fn start_ttrpc_service() -> Result<HashMap<String, Service>> {
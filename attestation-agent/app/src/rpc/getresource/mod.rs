@@ -15,11 +15,12 @@ pub struct GetResource {}
 #[cfg(feature = "grpc")]
 pub mod grpc {
     use super::*;
-    use crate::grpc::ASYNC_ATTESTATION_AGENT;
+    use crate::grpc::GrpcListener;
+    use crate::ASYNC_ATTESTATION_AGENT;
     use anyhow::*;
     use get_resource::get_resource_service_server::{GetResourceService, GetResourceServiceServer};
     use get_resource::{GetResourceRequest, GetResourceResponse};
-    use std::net::SocketAddr;
+    use telemetry::FutureExt;
     use tonic::{transport::Server, Request, Response, Status};
 
     mod get_resource {
@@ -32,6 +33,9 @@ pub mod grpc {
             &self,
             request: Request<GetResourceRequest>,
         ) -> Result<Response<GetResourceResponse>, Status> {
+            let span = telemetry::grpc_server_span(&request, "GetResource");
+            crate::access::check_grpc(&request, "GetResource").await?;
+
             let request = request.into_inner();
 
             let attestation_agent_mutex_clone = Arc::clone(&ASYNC_ATTESTATION_AGENT);
@@ -45,6 +49,7 @@ pub mod grpc {
                     &request.resource_path,
                     &request.kbs_uri,
                 )
+                .with_context(span)
                 .await
                 .map_err(|e| {
                     error!("Call AA-KBC to get resource failed: {}", e);
@@ -64,13 +69,10 @@ pub mod grpc {
         }
     }
 
-    pub async fn start_grpc_service(socket: SocketAddr) -> Result<()> {
+    pub async fn start_grpc_service(listener: GrpcListener) -> Result<()> {
         let service = GetResource::default();
-        Server::builder()
-            .add_service(GetResourceServiceServer::new(service))
-            .serve(socket)
-            .await?;
-        Ok(())
+        let router = Server::builder().add_service(GetResourceServiceServer::new(service));
+        listener.serve(router).await
     }
 }
 
@@ -81,11 +83,12 @@ pub mod ttrpc {
         create_get_resource_service, GetResourceService,
     };
     use crate::rpc::ttrpc_protocol::{getresource, getresource_ttrpc};
-    use crate::ttrpc::ASYNC_ATTESTATION_AGENT;
+    use crate::ASYNC_ATTESTATION_AGENT;
     use ::ttrpc::asynchronous::Service;
     use ::ttrpc::proto::Code;
     use anyhow::*;
     use async_trait::async_trait;
+    use telemetry::FutureExt;
 
     use std::collections::HashMap;
 
@@ -93,9 +96,12 @@ pub mod ttrpc {
     impl getresource_ttrpc::GetResourceService for GetResource {
         async fn get_resource(
             &self,
-            _ctx: &::ttrpc::r#async::TtrpcContext,
+            ctx: &::ttrpc::r#async::TtrpcContext,
             req: getresource::GetResourceRequest,
         ) -> ::ttrpc::Result<getresource::GetResourceResponse> {
+            let span = telemetry::ttrpc_server_span(&ctx.metadata, "GetResource");
+            crate::access::check_ttrpc(ctx, "GetResource").await?;
+
             debug!("Call AA-KBC to download resource ...");
 
             let attestation_agent_mutex_clone = ASYNC_ATTESTATION_AGENT.clone();
@@ -103,6 +109,7 @@ pub mod ttrpc {
 
             let target_resource = attestation_agent
                 .download_confidential_resource(&req.KbcName, &req.ResourcePath, &req.KbsUri)
+                .with_context(span)
                 .await
                 .map_err(|e| {
                     error!("Call AA-KBC to get resource failed: {}", e);
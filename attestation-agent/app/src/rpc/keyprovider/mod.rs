@@ -43,10 +43,11 @@ impl TryFrom<Vec<u8>> for InputPayload {
 #[cfg(feature = "grpc")]
 pub mod grpc {
     use super::*;
-    use crate::grpc::ASYNC_ATTESTATION_AGENT;
+    use crate::grpc::GrpcListener;
+    use crate::ASYNC_ATTESTATION_AGENT;
     use key_provider::key_provider_service_server::{KeyProviderService, KeyProviderServiceServer};
     use key_provider::{KeyProviderKeyWrapProtocolInput, KeyProviderKeyWrapProtocolOutput};
-    use std::net::SocketAddr;
+    use telemetry::FutureExt;
     use tonic::{transport::Server, Request, Response, Status};
     mod key_provider {
         tonic::include_proto!("keyprovider");
@@ -58,6 +59,9 @@ pub mod grpc {
             &self,
             request: Request<KeyProviderKeyWrapProtocolInput>,
         ) -> Result<Response<KeyProviderKeyWrapProtocolOutput>, Status> {
+            let span = telemetry::grpc_server_span(&request, "UnWrapKey");
+            crate::access::check_grpc(&request, "UnWrapKey").await?;
+
             debug!("The UnWrapKey API is called...");
 
             // Deserialize and parse the gRPC input to get KBC name, KBS URI and annotation.
@@ -82,6 +86,7 @@ pub mod grpc {
                     &input_payload.kbs_uri,
                     &input_payload.annotation,
                 )
+                .with_context(span)
                 .await
                 .map_err(|e| {
                     error!("Call AA-KBC to provide key failed: {}", e);
@@ -120,8 +125,11 @@ pub mod grpc {
 
         async fn wrap_key(
             &self,
-            _request: Request<KeyProviderKeyWrapProtocolInput>,
+            request: Request<KeyProviderKeyWrapProtocolInput>,
         ) -> Result<Response<KeyProviderKeyWrapProtocolOutput>, Status> {
+            let _span = telemetry::grpc_server_span(&request, "WrapKey");
+            crate::access::check_grpc(&request, "WrapKey").await?;
+
             debug!("The WrapKey API is called...");
             debug!("WrapKey API is unimplemented!");
             Err(Status::unimplemented(format!(
@@ -131,13 +139,10 @@ pub mod grpc {
         }
     }
 
-    pub async fn start_grpc_service(socket: SocketAddr) -> Result<()> {
+    pub async fn start_grpc_service(listener: GrpcListener) -> Result<()> {
         let service = KeyProvider::default();
-        Server::builder()
-            .add_service(KeyProviderServiceServer::new(service))
-            .serve(socket)
-            .await?;
-        Ok(())
+        let router = Server::builder().add_service(KeyProviderServiceServer::new(service));
+        listener.serve(router).await
     }
 }
 
@@ -148,10 +153,11 @@ pub mod ttrpc {
         create_key_provider_service, KeyProviderService,
     };
     use crate::rpc::ttrpc_protocol::{keyprovider, keyprovider_ttrpc};
-    use crate::ttrpc::ASYNC_ATTESTATION_AGENT;
+    use crate::ASYNC_ATTESTATION_AGENT;
     use ::ttrpc::asynchronous::Service;
     use ::ttrpc::proto::Code;
     use async_trait::async_trait;
+    use telemetry::FutureExt;
 
     use std::collections::HashMap;
 
@@ -159,9 +165,12 @@ pub mod ttrpc {
     impl keyprovider_ttrpc::KeyProviderService for KeyProvider {
         async fn un_wrap_key(
             &self,
-            _ctx: &::ttrpc::r#async::TtrpcContext,
+            ctx: &::ttrpc::r#async::TtrpcContext,
             req: keyprovider::KeyProviderKeyWrapProtocolInput,
         ) -> ::ttrpc::Result<keyprovider::KeyProviderKeyWrapProtocolOutput> {
+            let span = telemetry::ttrpc_server_span(&ctx.metadata, "UnWrapKey");
+            crate::access::check_ttrpc(ctx, "UnWrapKey").await?;
+
             debug!("The UnWrapKey API is called...");
 
             // Deserialize and parse the gRPC input to get KBC name, KBS URI and annotation.
@@ -188,6 +197,7 @@ pub mod ttrpc {
                     &input_payload.kbs_uri,
                     &input_payload.annotation,
                 )
+                .with_context(span)
                 .await
                 .map_err(|e| {
                     error!("Call AA-KBC to provide key failed: {}", e);
@@ -238,9 +248,12 @@ pub mod ttrpc {
 
         async fn wrap_key(
             &self,
-            _ctx: &::ttrpc::r#async::TtrpcContext,
+            ctx: &::ttrpc::r#async::TtrpcContext,
             _req: keyprovider::KeyProviderKeyWrapProtocolInput,
         ) -> ::ttrpc::Result<keyprovider::KeyProviderKeyWrapProtocolOutput> {
+            let _span = telemetry::ttrpc_server_span(&ctx.metadata, "WrapKey");
+            crate::access::check_ttrpc(ctx, "WrapKey").await?;
+
             debug!("The WrapKey API is called...");
             debug!("WrapKey API is unimplemented!");
             let mut error_status = ::ttrpc::proto::Status::new();
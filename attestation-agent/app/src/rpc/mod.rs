@@ -14,10 +14,12 @@ use crate::AttestationAgent;
 
 pub const AGENT_NAME: &str = "attestation-agent";
 
-#[cfg(feature = "ttrpc")]
+#[cfg(all(feature = "ttrpc", not(feature = "grpc")))]
 const PROTOCOL: &str = "ttrpc";
-#[cfg(feature = "grpc")]
+#[cfg(all(feature = "grpc", not(feature = "ttrpc")))]
 const PROTOCOL: &str = "grpc";
+#[cfg(all(feature = "ttrpc", feature = "grpc"))]
+const PROTOCOL: &str = "ttrpc, grpc";
 
 lazy_static! {
     pub static ref ABOUT: String = {
@@ -0,0 +1,125 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Unix sockets shared by the ttRPC and gRPC listeners.
+//!
+//! The sockets of AA are reached by clients of different users, e.g. the
+//! kata-agent running as root and sidecars of a pod mounting the socket
+//! dir. Their ownership and permissions are set after binding, so that
+//! access can be granted to a group without opening them to everyone.
+
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use serde::Serialize;
+use std::fs::{self, Permissions};
+use std::os::unix::fs::{chown, PermissionsExt};
+use std::path::Path;
+
+pub const UNIX_SOCKET_PREFIX: &str = "unix://";
+
+#[derive(Debug, Args, Serialize)]
+pub struct SocketArgs {
+    /// Permissions of the Unix sockets, in octal.
+    ///
+    /// The mode set by the umask of AA is kept if not set, for example:
+    ///
+    /// `--socket_mode 660`
+    #[arg(long = "socket_mode", value_parser = parse_mode)]
+    pub mode: Option<u32>,
+
+    /// Owner uid of the Unix sockets.
+    #[arg(long = "socket_uid")]
+    pub uid: Option<u32>,
+
+    /// Owner gid of the Unix sockets.
+    #[arg(long = "socket_gid")]
+    pub gid: Option<u32>,
+}
+
+fn parse_mode(mode: &str) -> Result<u32> {
+    let mode = u32::from_str_radix(mode, 8).map_err(|e| anyhow!("invalid socket mode: {e}"))?;
+    if mode > 0o7777 {
+        return Err(anyhow!("invalid socket mode: {mode:o}"));
+    }
+    Ok(mode)
+}
+
+/// Path of the Unix socket `addr`, if it is one.
+pub fn unix_socket_path(addr: &str) -> Option<&str> {
+    addr.strip_prefix(UNIX_SOCKET_PREFIX)
+}
+
+/// Get the Unix socket at `path` ready to be bound: create its dir and
+/// remove the socket file left by a previous run.
+pub fn prepare(path: &str) -> Result<()> {
+    let path = Path::new(path);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("create unix socket dir")?;
+    }
+
+    if path.exists() {
+        fs::remove_file(path).context("clean previous socket file")?;
+    }
+
+    Ok(())
+}
+
+impl SocketArgs {
+    /// Set the configured ownership and permissions of the bound Unix
+    /// socket at `path`.
+    pub fn apply(&self, path: &str) -> Result<()> {
+        if self.uid.is_some() || self.gid.is_some() {
+            chown(path, self.uid, self.gid)
+                .with_context(|| format!("set owner of socket {path}"))?;
+        }
+
+        if let Some(mode) = self.mode {
+            fs::set_permissions(path, Permissions::from_mode(mode))
+                .with_context(|| format!("set permissions of socket {path}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("660").unwrap(), 0o660);
+        assert_eq!(parse_mode("0600").unwrap(), 0o600);
+        assert!(parse_mode("990").is_err());
+        assert!(parse_mode("17777").is_err());
+    }
+
+    #[test]
+    fn test_prepare_and_apply() {
+        let dir = std::env::temp_dir().join(format!("aa-socket-{}", std::process::id()));
+        let path = dir.join("sub/test.sock");
+        let path = path.to_str().unwrap();
+        assert_eq!(unix_socket_path(&format!("unix://{path}")), Some(path));
+        assert_eq!(unix_socket_path("vsock://3:1024"), None);
+
+        prepare(path).unwrap();
+        let _first = UnixListener::bind(path).unwrap();
+        // the socket file of the previous run is removed
+        prepare(path).unwrap();
+        let _second = UnixListener::bind(path).unwrap();
+
+        let args = SocketArgs {
+            mode: Some(0o660),
+            uid: None,
+            gid: None,
+        };
+        args.apply(path).unwrap();
+        let mode = fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o660);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
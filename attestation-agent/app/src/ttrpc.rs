@@ -4,17 +4,14 @@
 //
 
 use super::*;
+use crate::socket::{self, SocketArgs, UNIX_SOCKET_PREFIX};
 use ::ttrpc::asynchronous::Server;
-use clap::{arg, command, Parser};
+use clap::Args;
 use const_format::concatcp;
-use std::path::Path;
-use tokio::{
-    signal::unix::{signal, SignalKind},
-    sync::Mutex,
-};
+use serde::Serialize;
+use tokio::signal::unix::{signal, SignalKind};
 
 const DEFAULT_UNIX_SOCKET_DIR: &str = "/run/confidential-containers/attestation-agent/";
-const UNIX_SOCKET_PREFIX: &str = "unix://";
 const DEFAULT_KEYPROVIDER_SOCKET_ADDR: &str = concatcp!(
     UNIX_SOCKET_PREFIX,
     DEFAULT_UNIX_SOCKET_DIR,
@@ -31,76 +28,60 @@ const DEFAULT_ATTESTATION_SOCKET_ADDR: &str = concatcp!(
     "attestation-agent.sock"
 );
 
-lazy_static! {
-    pub static ref ASYNC_ATTESTATION_AGENT: Arc<Mutex<AttestationAgent>> =
-        Arc::new(Mutex::new(AttestationAgent::new()));
-}
+#[derive(Debug, Args, Serialize)]
+pub struct TtrpcArgs {
+    /// Serve the ttRPC services.
+    ///
+    /// `--enable_ttrpc false` turns the ttRPC listener off.
+    #[arg(long = "enable_ttrpc", default_value_t = true, action = clap::ArgAction::Set)]
+    pub enable: bool,
 
-#[derive(Debug, Parser)]
-#[command(author, version, about, long_about = None)]
-struct Cli {
-    /// KeyProvider ttRPC Unix socket addr.
+    /// KeyProvider ttRPC socket addr.
     ///
-    /// This Unix socket address which the KeyProvider ttRPC service
-    /// will listen to, for example:
+    /// This Unix or vsock socket address which the KeyProvider ttRPC
+    /// service will listen to, for example:
     ///
     /// `--keyprovider_sock unix:///tmp/aa_keyprovider`
     #[arg(default_value_t = DEFAULT_KEYPROVIDER_SOCKET_ADDR.to_string(), short, long = "keyprovider_sock")]
     keyprovider_sock: String,
 
-    /// GetResource ttRPC Unix socket addr.
+    /// GetResource ttRPC socket addr.
     ///
-    /// This Unix socket address which the GetResource ttRPC service
-    /// will listen to, for example:
+    /// This Unix or vsock socket address which the GetResource ttRPC
+    /// service will listen to, for example:
     ///
     /// `--getresource_sock unix:///tmp/aa_getresource`
     #[arg(default_value_t = DEFAULT_GETRESOURCE_SOCKET_ADDR.to_string(), short, long = "getresource_sock")]
     getresource_sock: String,
 
-    /// Attestation ttRPC Unix socket addr.
+    /// Attestation ttRPC socket addr.
     ///
-    /// This Unix socket address which the Attestation ttRPC service
-    /// will listen to, for example:
+    /// This Unix or vsock socket address which the Attestation ttRPC
+    /// service will listen to, for example:
     ///
     /// `--attestation_sock unix:///tmp/attestation`
     #[arg(default_value_t = DEFAULT_ATTESTATION_SOCKET_ADDR.to_string(), short, long = "attestation_sock")]
     attestation_sock: String,
 }
 
-pub async fn ttrpc_main() -> Result<()> {
-    let cli = Cli::parse();
-
-    if !Path::new(DEFAULT_UNIX_SOCKET_DIR).exists() {
-        std::fs::create_dir_all(DEFAULT_UNIX_SOCKET_DIR).expect("Create unix socket dir failed");
-    }
-
-    clean_previous_sock_file(&cli.keyprovider_sock)
-        .context("clean previous keyprovider socket file")?;
-    clean_previous_sock_file(&cli.getresource_sock)
-        .context("clean previous getresource socket file")?;
-    clean_previous_sock_file(&cli.attestation_sock)
-        .context("clean previous attestation socket file")?;
-
+pub async fn ttrpc_main(args: &TtrpcArgs, socket_args: &SocketArgs) -> Result<()> {
     let kp = rpc::keyprovider::ttrpc::start_ttrpc_service()?;
     let gs = rpc::getresource::ttrpc::start_ttrpc_service()?;
     let att = rpc::attestation::ttrpc::start_ttrpc_service()?;
 
-    let mut kps = Server::new()
-        .bind(&cli.getresource_sock)
+    let mut gss = bind(&args.getresource_sock, socket_args)
         .context("cannot bind getresource ttrpc service")?
         .register_service(gs);
 
-    kps.start().await?;
+    gss.start().await?;
 
-    let mut gss = Server::new()
-        .bind(&cli.keyprovider_sock)
+    let mut kps = bind(&args.keyprovider_sock, socket_args)
         .context("cannot bind keyprovider ttrpc service")?
         .register_service(kp);
 
-    gss.start().await?;
+    kps.start().await?;
 
-    let mut atts = Server::new()
-        .bind(&cli.attestation_sock)
+    let mut atts = bind(&args.attestation_sock, socket_args)
         .context("cannot bind attestation ttrpc service")?
         .register_service(att);
 
@@ -108,15 +89,15 @@ pub async fn ttrpc_main() -> Result<()> {
 
     debug!(
         "KeyProvider ttRPC service listening on: {:?}",
-        cli.keyprovider_sock
+        args.keyprovider_sock
     );
     debug!(
         "GetResource ttRPC service listening on: {:?}",
-        cli.getresource_sock
+        args.getresource_sock
     );
     debug!(
         "Attestation ttRPC service listening on: {:?}",
-        cli.attestation_sock
+        args.attestation_sock
     );
 
     let mut interrupt = signal(SignalKind::interrupt())?;
@@ -124,27 +105,27 @@ pub async fn ttrpc_main() -> Result<()> {
     tokio::select! {
         _ = hangup.recv() => {
             info!("Client terminal disconnected.");
-            kps.shutdown().await?;
-            gss.shutdown().await?;
         }
         _ = interrupt.recv() => {
             info!("SIGINT received, gracefully shutdown.");
-            kps.shutdown().await?;
-            gss.shutdown().await?;
         }
     };
+    kps.shutdown().await?;
+    gss.shutdown().await?;
+    atts.shutdown().await?;
 
     Ok(())
 }
 
-fn clean_previous_sock_file(unix_socket: &str) -> Result<()> {
-    let path = unix_socket
-        .strip_prefix(UNIX_SOCKET_PREFIX)
-        .ok_or_else(|| anyhow!("socket address scheme is not expected"))?;
-
-    if Path::new(path).exists() {
-        std::fs::remove_file(path)?;
-    }
+/// Bind a ttRPC server to `addr`. Unix sockets get the ownership and
+/// permissions of `socket_args`.
+fn bind(addr: &str, socket_args: &SocketArgs) -> Result<Server> {
+    let Some(path) = socket::unix_socket_path(addr) else {
+        return Ok(Server::new().bind(addr)?);
+    };
 
-    Ok(())
+    socket::prepare(path)?;
+    let server = Server::new().bind(addr)?;
+    socket_args.apply(path)?;
+    Ok(server)
 }
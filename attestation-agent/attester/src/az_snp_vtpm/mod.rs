@@ -3,10 +3,32 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+//! Attester of Azure SEV-SNP confidential VMs.
+//!
+//! On Azure the guest runs on top of a paravisor, the HCL, which owns the
+//! SNP device, so the plain SNP attester cannot get a report. At boot the
+//! HCL gets one with the public vTPM attestation key (AK) in its runtime
+//! data, and stores both as the HCL report in an NV index of the vTPM. The
+//! evidence follows the Azure CVM convention:
+//! - the HCL report, binding the AK to the SNP report,
+//! - the VCEK and the ASK/ARK chain from IMDS, endorsing the SNP report,
+//! - a quote of the PCRs signed by the AK, carrying the report data,
+//! - the AK certificate issued by Azure, if provisioned, so that the AK can
+//!   be checked against the Azure vTPM CA as well.
+
 use super::Attester;
+use anyhow::*;
 use az_snp_vtpm::{imds, is_snp_cvm, vtpm};
-use log::debug;
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
+use std::result::Result::Ok;
+use tss_esapi::abstraction::nv;
+use tss_esapi::handles::NvIndexTpmHandle;
+use tss_esapi::interface_types::resource_handles::NvAuth;
+use tss_esapi::tcti_ldr::{DeviceConfig, TctiNameConf};
+
+/// NV index of the vTPM the AK certificate is provisioned to.
+const AK_CERT_NV_INDEX: u32 = 0x01C1_01D0;
 
 pub fn detect_platform() -> bool {
     match is_snp_cvm() {
@@ -24,24 +46,55 @@ pub struct AzSnpVtpmAttester;
 #[derive(Serialize, Deserialize)]
 struct Evidence {
     quote: vtpm::Quote,
+
+    /// HCL report, the SNP report followed by the runtime data.
     report: Vec<u8>,
+
+    /// PEM VCEK endorsing the SNP report.
     vcek: String,
+
+    /// PEM ASK and ARK endorsing the VCEK.
+    #[serde(default)]
+    cert_chain: String,
+
+    /// DER AK certificate.
+    #[serde(default)]
+    ak_cert: Option<Vec<u8>>,
+}
+
+/// Read the AK certificate from the vTPM.
+fn get_ak_cert() -> Result<Vec<u8>> {
+    let mut context = tss_esapi::Context::new(TctiNameConf::Device(DeviceConfig::default()))?;
+    let index = NvIndexTpmHandle::new(AK_CERT_NV_INDEX)?;
+    nv::read_full(&mut context, NvAuth::Owner, index).context("read AK certificate")
 }
 
 #[async_trait::async_trait]
 impl Attester for AzSnpVtpmAttester {
-    async fn get_evidence(&self, report_data: Vec<u8>) -> anyhow::Result<String> {
+    async fn get_evidence(&self, report_data: Vec<u8>) -> Result<String> {
         let report = vtpm::get_report()?;
         let quote = vtpm::get_quote(&report_data)?;
         let certs = imds::get_certs()?;
-        let vcek = certs.vcek;
+        let ak_cert = match get_ak_cert() {
+            Ok(cert) => Some(cert),
+            Err(e) => {
+                warn!("Azure SNP vTPM Attester: no AK certificate in the evidence: {e:#}");
+                None
+            }
+        };
 
         let evidence = Evidence {
             quote,
             report,
-            vcek,
+            vcek: certs.vcek,
+            cert_chain: certs.amd_chain,
+            ak_cert,
         };
 
         Ok(serde_json::to_string(&evidence)?)
     }
+
+    async fn get_sealing_key(&self) -> Result<Vec<u8>> {
+        crate::vtpm_key::get_sealing_key()
+    }
 }
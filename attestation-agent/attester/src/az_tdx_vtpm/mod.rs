@@ -48,4 +48,8 @@ impl Attester for AzTdxVtpmAttester {
         };
         Ok(serde_json::to_string(&evidence)?)
     }
+
+    async fn get_sealing_key(&self) -> Result<Vec<u8>> {
+        crate::vtpm_key::get_sealing_key()
+    }
 }
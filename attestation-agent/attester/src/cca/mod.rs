@@ -4,6 +4,7 @@
 //
 
 use super::Attester;
+use crate::tsm_report::{self, TsmReport};
 use anyhow::*;
 use base64::Engine;
 use nix::fcntl::{open, OFlag};
@@ -14,9 +15,32 @@ use std::path::Path;
 
 const CCA_DEVICE_PATH: &str = "/dev/cca_attestation";
 
-// NOTE: The path might be different when the CCA feature is public available, will come back to update the actual path if needed.
+/// `provider` of the `configfs-tsm` reports of the `arm-cca-guest` driver,
+/// which gets the token through the RSI.
+const TSM_CCA_PROVIDER: &str = "arm_cca_guest";
+
+/// Size of the realm challenge.
+const CCA_CHALLENGE_SIZE: usize = 64;
+
+// NOTE: `/dev/cca_attestation` is the device of the early CCA test kernels,
+// upstream kernels expose the RSI through configfs-tsm. The device is only
+// used on kernels without configfs-tsm.
 pub fn detect_platform() -> bool {
-    Path::new(CCA_DEVICE_PATH).exists()
+    match tsm_report::is_available() {
+        Result::Ok(true) => tsm_report().is_ok(),
+        Result::Ok(false) => Path::new(CCA_DEVICE_PATH).exists(),
+        Err(_) => false,
+    }
+}
+
+fn tsm_report() -> Result<TsmReport> {
+    let report = TsmReport::new()?;
+    let provider = report.provider()?;
+    if provider != TSM_CCA_PROVIDER {
+        bail!("TSM report provider is {provider:?}, not {TSM_CCA_PROVIDER}");
+    }
+
+    Ok(report)
 }
 
 #[derive(Debug, Default)]
@@ -41,8 +65,17 @@ nix::ioctl_readwrite!(cca_attestation_request, b'A', 1, cca_ioctl_request);
 #[async_trait::async_trait]
 impl Attester for CCAAttester {
     async fn get_evidence(&self, mut challenge: Vec<u8>) -> Result<String> {
-        challenge.resize(64, 0);
-        let token = attestation(challenge)?;
+        if challenge.len() > CCA_CHALLENGE_SIZE {
+            bail!("CCA Attester: challenge must be at most {CCA_CHALLENGE_SIZE} bytes");
+        }
+        challenge.resize(CCA_CHALLENGE_SIZE, 0);
+        let token = match tsm_report::is_available()? {
+            true => tsm_report()?.get(&challenge)?.outblob,
+            false => {
+                log::debug!("CCA Attester: configfs-tsm unavailable, using {CCA_DEVICE_PATH}");
+                attestation(challenge)?
+            }
+        };
         let evidence = CcaEvidence { token };
         let ev = serde_json::to_string(&evidence).context("Serialize CCA evidence failed")?;
         Ok(ev)
@@ -29,12 +29,26 @@ pub mod snp;
 #[cfg(feature = "csv-attester")]
 pub mod csv;
 
+#[cfg(feature = "tsm-attester")]
+pub mod tsm;
+
+#[cfg(any(feature = "cca-attester", feature = "tsm-attester"))]
+pub mod tsm_report;
+
+#[cfg(any(feature = "az-snp-vtpm-attester", feature = "az-tdx-vtpm-attester"))]
+pub mod vtpm_key;
+
 pub type BoxedAttester = Box<dyn Attester + Send + Sync>;
 
 impl TryFrom<Tee> for BoxedAttester {
     type Error = anyhow::Error;
 
     fn try_from(value: Tee) -> Result<Self> {
+        #[cfg(feature = "tsm-attester")]
+        if !has_dedicated_attester(value) && tsm::detect_platform() == Some(value) {
+            return Ok(Box::<tsm::TsmAttester>::default());
+        }
+
         let attester: Box<dyn Attester + Send + Sync> = match value {
             Tee::Sample => Box::<sample::SampleAttester>::default(),
             #[cfg(feature = "tdx-attester")]
@@ -58,6 +72,56 @@ impl TryFrom<Tee> for BoxedAttester {
     }
 }
 
+// Whether the dedicated attester of the TEE is built in and its driver is
+// present. Only needed for TEEs `configfs-tsm` reports can be got on.
+#[cfg(feature = "tsm-attester")]
+fn has_dedicated_attester(tee: Tee) -> bool {
+    match tee {
+        #[cfg(feature = "tdx-attester")]
+        Tee::Tdx => tdx::detect_platform(),
+        #[cfg(feature = "snp-attester")]
+        Tee::Snp => snp::detect_platform(),
+        #[cfg(feature = "cca-attester")]
+        Tee::Cca => cca::detect_platform(),
+        _ => false,
+    }
+}
+
+/// The result of checking init-data against the TEE launch measurement.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InitDataResult {
+    /// The init-data digest matches the one recorded by the hardware.
+    Ok,
+
+    /// The platform cannot bind init-data to its launch measurement.
+    Unsupported,
+}
+
+/// Check the init-data digest against `register` of the TEE, e.g. TDX
+/// `MRCONFIGID`, which the host sets to the digest zero-padded to the size
+/// of the register. Digests longer than the register are rejected rather
+/// than truncated.
+pub(crate) fn check_init_data_digest(
+    register_name: &str,
+    register: &[u8],
+    init_data_digest: &[u8],
+) -> Result<InitDataResult> {
+    if init_data_digest.len() > register.len() {
+        bail!(
+            "init-data digest must be no more than {} bytes to fit {register_name}",
+            register.len()
+        );
+    }
+
+    let mut expected = init_data_digest.to_vec();
+    expected.resize(register.len(), 0);
+    if register != expected.as_slice() {
+        bail!("init-data digest does not match {register_name}");
+    }
+
+    Ok(InitDataResult::Ok)
+}
+
 #[async_trait::async_trait]
 pub trait Attester {
     /// Call the hardware driver to get the Hardware specific evidence.
@@ -74,6 +138,22 @@ pub trait Attester {
     ) -> Result<()> {
         bail!("Unimplemented")
     }
+
+    /// Check that the digest of the init-data equals the digest the TEE was
+    /// launched with (e.g. TDX `MRCONFIGID`, SNP `HOST_DATA`). An error is
+    /// returned on mismatch. TEEs without such a register return
+    /// [`InitDataResult::Unsupported`].
+    async fn bind_init_data(&self, _init_data_digest: &[u8]) -> Result<InitDataResult> {
+        Ok(InitDataResult::Unsupported)
+    }
+
+    /// Get a secret only this TEE can get again, e.g. the SNP derived key
+    /// bound to the launch measurement. It is never handed out, but keys are
+    /// derived from it. TEEs without a sealing primitive, e.g. TDX, return
+    /// an error.
+    async fn get_sealing_key(&self) -> Result<Vec<u8>> {
+        bail!("Unimplemented")
+    }
 }
 
 // Detect which TEE platform the KBC running environment is.
@@ -113,6 +193,30 @@ pub fn detect_tee_type() -> Tee {
         return Tee::Cca;
     }
 
+    #[cfg(feature = "tsm-attester")]
+    if let Some(tee) = tsm::detect_platform() {
+        return tee;
+    }
+
     log::warn!("No TEE platform detected. Sample Attester will be used.");
     Tee::Sample
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_init_data_digest() {
+        let mut register = [0u8; 48];
+        register[..32].fill(1);
+        assert_eq!(
+            check_init_data_digest("MRCONFIGID", &register, &[1; 32]).unwrap(),
+            InitDataResult::Ok
+        );
+
+        // zero-padded, never truncated
+        assert!(check_init_data_digest("MRCONFIGID", &register, &[1; 31]).is_err());
+        assert!(check_init_data_digest("HOST_DATA", &register[..32], &[1; 48]).is_err());
+    }
+}
@@ -8,6 +8,9 @@ use anyhow::*;
 use base64::Engine;
 use serde::{Deserialize, Serialize};
 
+/// Sealing key of the sample attester, which protects nothing.
+const SAMPLE_SEALING_KEY: [u8; 32] = [0x5a; 32];
+
 // Sample attester is always supported
 pub fn detect_platform() -> bool {
     true
@@ -33,4 +36,8 @@ impl Attester for SampleAttester {
 
         serde_json::to_string(&evidence).map_err(|_| anyhow!("Serialize sample evidence failed"))
     }
+
+    async fn get_sealing_key(&self) -> Result<Vec<u8>> {
+        Ok(SAMPLE_SEALING_KEY.to_vec())
+    }
 }
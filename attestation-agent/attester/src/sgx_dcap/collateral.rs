@@ -0,0 +1,520 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Collateral of SGX DCAP quotes.
+//!
+//! Verifying a quote needs the PCK CRL, the TCB info of the platform and
+//! the identity of the QE, with their issuer chains, and the CRL of the
+//! root CA. They are embedded into the evidence, so that verifiers behind a
+//! firewall need not reach a PCCS. For the FMSPC and the PCK CA of the
+//! quote, the collateral is taken from, in order:
+//!
+//! 1. the provided dir ([`SGX_COLLATERAL_DIR`]), for offline deployments,
+//! 2. the cache dir ([`SGX_COLLATERAL_CACHE_DIR`]), while neither the TCB
+//!    info nor the QE identity has passed its `nextUpdate`,
+//! 3. the PCCS at [`SGX_PCCS_URL_ENV`] (or [`DEFAULT_PCCS_URL`]), unless
+//!    [`SGX_PCCS_OFFLINE_ENV`] is set. Fetched collateral is written to the
+//!    cache dir.
+//!
+//! Outdated cached collateral is still used if the PCCS can't be reached,
+//! the verifier then decides whether to accept it.
+//!
+//! After a TCB recovery, Intel publishes TCB info and a QE identity with a
+//! higher `tcbEvaluationDataNumber`. Setting [`SGX_TCB_UPDATE_ENV`] to
+//! `early` fetches them as soon as they are published, instead of after
+//! the grace period of the `standard` update. Fetched collateral with a
+//! lower evaluation data number than the cached one is refused, so that a
+//! lagging PCCS can't roll the cache back, and so is collateral whose TCB
+//! info and QE identity have different evaluation data numbers, which
+//! comes from a PCCS halfway through the update of a TCB recovery.
+
+use anyhow::*;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hyper::body::HttpBody as _;
+use hyper::Client;
+use hyper_tls::HttpsConnector;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+/// Dir of collateral provided to the enclave, e.g. by the image.
+pub const SGX_COLLATERAL_DIR: &str = "/etc/sgx/collateral";
+
+/// Dir where collateral fetched from the PCCS is cached.
+pub const SGX_COLLATERAL_CACHE_DIR: &str = "/run/attestation-agent/sgx-collateral";
+
+/// Overrides the url of the PCCS API, e.g.
+/// `https://pccs.example.com/sgx/certification/v4`.
+pub const SGX_PCCS_URL_ENV: &str = "SGX_PCCS_URL";
+
+/// If this env is set, the PCCS is never contacted.
+pub const SGX_PCCS_OFFLINE_ENV: &str = "SGX_PCCS_OFFLINE";
+
+/// TCB recovery update (`early` or `standard`) the TCB info and QE identity
+/// are fetched for.
+pub const SGX_TCB_UPDATE_ENV: &str = "SGX_TCB_UPDATE";
+
+/// PCCS API url of the default QCNL configuration.
+pub const DEFAULT_PCCS_URL: &str = "https://localhost:8081/sgx/certification/v4";
+
+const QUOTE_HEADER_SIZE: usize = 48;
+const REPORT_BODY_SIZE: usize = 384;
+const ECDSA_SIGNATURE_SIZE: usize = 64;
+const ECDSA_KEY_SIZE: usize = 64;
+
+const CERT_DATA_PCK_CERT_CHAIN: u16 = 5;
+const CERT_DATA_QE_REPORT: u16 = 6;
+
+// DER of the FMSPC extension OID 1.2.840.113741.1.13.1.4, followed by the
+// header of its 6 bytes OCTET STRING value
+const FMSPC_OID: &[u8] = &[
+    0x06, 0x0a, 0x2a, 0x86, 0x48, 0x86, 0xf8, 0x4d, 0x01, 0x0d, 0x01, 0x04, 0x04, 0x06,
+];
+
+/// The collateral needed to verify a quote, as served by the PCCS.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SgxCollateral {
+    /// PEM issuer chain of the PCK CRL.
+    pub pck_crl_issuer_chain: String,
+
+    /// CRL of the Intel SGX root CA.
+    pub root_ca_crl: String,
+
+    /// CRL of the PCK CA that issued the PCK certificate of the quote.
+    pub pck_crl: String,
+
+    /// PEM issuer chain of the TCB info.
+    pub tcb_info_issuer_chain: String,
+
+    /// Signed TCB info JSON of the FMSPC of the quote.
+    pub tcb_info: String,
+
+    /// PEM issuer chain of the QE identity.
+    pub qe_identity_issuer_chain: String,
+
+    /// Signed QE identity JSON.
+    pub qe_identity: String,
+}
+
+/// The fields of the signed TCB info and QE identity the cache is managed
+/// with.
+#[derive(Debug, PartialEq, Eq)]
+struct Validity {
+    next_update: String,
+    tcb_evaluation_data_number: u64,
+}
+
+impl SgxCollateral {
+    fn validity(&self) -> Result<Validity> {
+        let tcb_info = validity_of(&self.tcb_info, "tcbInfo")?;
+        let qe_identity = validity_of(&self.qe_identity, "enclaveIdentity")?;
+        if tcb_info.tcb_evaluation_data_number != qe_identity.tcb_evaluation_data_number {
+            bail!(
+                "TCB evaluation data number {} of the TCB info and {} of the QE identity differ, \
+                 the PCCS is being updated for a TCB recovery",
+                tcb_info.tcb_evaluation_data_number,
+                qe_identity.tcb_evaluation_data_number
+            );
+        }
+
+        Ok(Validity {
+            next_update: tcb_info.next_update.min(qe_identity.next_update),
+            tcb_evaluation_data_number: tcb_info.tcb_evaluation_data_number,
+        })
+    }
+}
+
+fn validity_of(signed: &str, body: &str) -> Result<Validity> {
+    let json: serde_json::Value =
+        serde_json::from_str(signed).with_context(|| format!("illegal {body} JSON"))?;
+    let body = json
+        .get(body)
+        .ok_or_else(|| anyhow!("no {body} in collateral"))?;
+    let next_update = body
+        .get("nextUpdate")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow!("no nextUpdate in collateral"))?;
+    let tcb_evaluation_data_number = body
+        .get("tcbEvaluationDataNumber")
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| anyhow!("no tcbEvaluationDataNumber in collateral"))?;
+
+    Ok(Validity {
+        next_update: next_update.to_string(),
+        tcb_evaluation_data_number,
+    })
+}
+
+/// Get the collateral of `quote`.
+pub async fn get_collateral(quote: &[u8]) -> Result<SgxCollateral> {
+    let pck_chain = pck_cert_chain(quote)?;
+    let (fmspc, ca) = fmspc_and_ca(&pck_chain)?;
+    let name = format!("collateral-{fmspc}-{ca}.json");
+
+    let provided = Path::new(SGX_COLLATERAL_DIR).join(&name);
+    if let Some(collateral) = load_collateral(&provided).await? {
+        return Ok(collateral);
+    }
+
+    let cached_path = Path::new(SGX_COLLATERAL_CACHE_DIR).join(&name);
+    let cached = load_collateral(&cached_path).await?;
+    let offline = std::env::var_os(SGX_PCCS_OFFLINE_ENV).is_some();
+    let cached_validity = match &cached {
+        Some(collateral) => Some(collateral.validity()?),
+        None => None,
+    };
+    if let (Some(collateral), Some(validity)) = (&cached, &cached_validity) {
+        if offline || validity.next_update > now_rfc3339() {
+            return Ok(collateral.clone());
+        }
+    }
+    if offline {
+        bail!("SGX Attester: collateral of FMSPC {fmspc} not provided and PCCS is offline");
+    }
+
+    let fetched = match fetch_collateral(&fmspc, ca).await {
+        Result::Ok(fetched) => fetched,
+        Err(e) => match cached {
+            Some(collateral) => {
+                warn!("SGX Attester: using outdated collateral, PCCS failed: {e:?}");
+                return Ok(collateral);
+            }
+            None => return Err(e.context("fetch collateral from PCCS")),
+        },
+    };
+    let validity = fetched.validity()?;
+    if let Some(cached_validity) = cached_validity {
+        if validity.tcb_evaluation_data_number < cached_validity.tcb_evaluation_data_number {
+            bail!(
+                "SGX Attester: PCCS serves TCB evaluation data number {}, older than the cached {}",
+                validity.tcb_evaluation_data_number,
+                cached_validity.tcb_evaluation_data_number
+            );
+        }
+    }
+
+    store_collateral(&cached_path, &fetched).await;
+    Ok(fetched)
+}
+
+async fn load_collateral(path: &Path) -> Result<Option<SgxCollateral>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    debug!("SGX Attester: using collateral {}", path.display());
+    let data = fs::read(path)
+        .await
+        .with_context(|| format!("read {}", path.display()))?;
+    let collateral = serde_json::from_slice(&data)
+        .with_context(|| format!("illegal collateral {}", path.display()))?;
+    Ok(Some(collateral))
+}
+
+// Failing to cache only costs another PCCS round trip next time.
+async fn store_collateral(path: &Path, collateral: &SgxCollateral) {
+    let res = async {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(path, serde_json::to_vec(collateral)?).await?;
+        Ok(())
+    }
+    .await;
+    if let Err(e) = res {
+        warn!("SGX Attester: failed to cache {}: {e}", path.display());
+    }
+}
+
+async fn fetch_collateral(fmspc: &str, ca: &str) -> Result<SgxCollateral> {
+    let base = std::env::var(SGX_PCCS_URL_ENV).unwrap_or_else(|_| DEFAULT_PCCS_URL.into());
+    let base = base.trim_end_matches('/');
+    let update = std::env::var(SGX_TCB_UPDATE_ENV).unwrap_or_else(|_| "standard".into());
+    if update != "early" && update != "standard" {
+        bail!("SGX Attester: {SGX_TCB_UPDATE_ENV} must be early or standard, got {update}");
+    }
+
+    let (pck_crl, pck_crl_issuer_chain) = pccs_get(
+        &format!("{base}/pckcrl?ca={ca}&encoding=pem"),
+        Some("SGX-PCK-CRL-Issuer-Chain"),
+    )
+    .await?;
+    let (root_ca_crl, _) = pccs_get(&format!("{base}/rootcacrl"), None).await?;
+    let (tcb_info, tcb_info_issuer_chain) = pccs_get(
+        &format!("{base}/tcb?fmspc={fmspc}&update={update}"),
+        Some("TCB-Info-Issuer-Chain"),
+    )
+    .await?;
+    let (qe_identity, qe_identity_issuer_chain) = pccs_get(
+        &format!("{base}/qe/identity?update={update}"),
+        Some("SGX-Enclave-Identity-Issuer-Chain"),
+    )
+    .await?;
+
+    Ok(SgxCollateral {
+        pck_crl_issuer_chain,
+        root_ca_crl,
+        pck_crl,
+        tcb_info_issuer_chain,
+        tcb_info,
+        qe_identity_issuer_chain,
+        qe_identity,
+    })
+}
+
+/// GET `url`, returning the body and the url encoded `issuer_chain_header`.
+async fn pccs_get(url: &str, issuer_chain_header: Option<&str>) -> Result<(String, String)> {
+    info!("SGX Attester: fetching {url}");
+    let https = HttpsConnector::new();
+    let client = Client::builder().build::<_, hyper::Body>(https);
+
+    let request = hyper::Request::builder()
+        .uri(url)
+        .method(hyper::Method::GET)
+        .header("User-Agent", "Hyper")
+        .body(hyper::Body::empty())?;
+
+    let response = client.request(request).await?;
+    if !response.status().is_success() {
+        bail!("PCCS returned {} for {url}", response.status());
+    }
+
+    let issuer_chain = match issuer_chain_header {
+        Some(name) => {
+            let value = response
+                .headers()
+                .get(name)
+                .ok_or_else(|| anyhow!("PCCS response without {name}"))?
+                .to_str()?;
+            percent_decode(value)?
+        }
+        None => String::new(),
+    };
+
+    let mut response_body = Vec::new();
+    let mut response = response.into_body();
+    while let Some(chunk) = response.data().await {
+        let chunk = chunk?;
+        response_body.extend_from_slice(&chunk);
+    }
+
+    Ok((String::from_utf8(response_body)?, issuer_chain))
+}
+
+fn percent_decode(s: &str) -> Result<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s
+                .get(i + 1..i + 3)
+                .ok_or_else(|| anyhow!("truncated percent encoding"))?;
+            decoded.push(u8::from_str_radix(hex, 16).context("illegal percent encoding")?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    Ok(String::from_utf8(decoded)?)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .ok_or_else(|| anyhow!("SGX quote too short"))?;
+    Ok(u16::from_le_bytes(bytes.try_into()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .ok_or_else(|| anyhow!("SGX quote too short"))?;
+    Ok(u32::from_le_bytes(bytes.try_into()?))
+}
+
+/// The PEM PCK certificate chain in the certification data of an SGX
+/// quote, either directly (version 3) or in the one of the QE report
+/// (version 4).
+fn pck_cert_chain(quote: &[u8]) -> Result<Vec<u8>> {
+    // ECDSA signature, attestation key, QE report and its signature
+    let mut offset = QUOTE_HEADER_SIZE
+        + REPORT_BODY_SIZE
+        + 4
+        + ECDSA_SIGNATURE_SIZE
+        + ECDSA_KEY_SIZE
+        + REPORT_BODY_SIZE
+        + ECDSA_SIGNATURE_SIZE;
+    let version = read_u16(quote, 0)?;
+    if version == 4 {
+        // version 4 starts with the certification data, the QE report is
+        // nested in it
+        offset = QUOTE_HEADER_SIZE + REPORT_BODY_SIZE + 4 + ECDSA_SIGNATURE_SIZE + ECDSA_KEY_SIZE;
+        if read_u16(quote, offset)? != CERT_DATA_QE_REPORT {
+            bail!("SGX quote v4 without QE report certification data");
+        }
+        offset += 6 + REPORT_BODY_SIZE + ECDSA_SIGNATURE_SIZE;
+    } else if version != 3 {
+        bail!("unsupported SGX quote version {version}");
+    }
+
+    // QE authentication data
+    offset += 2 + read_u16(quote, offset)? as usize;
+
+    let cert_type = read_u16(quote, offset)?;
+    if cert_type != CERT_DATA_PCK_CERT_CHAIN {
+        bail!("SGX quote certification data of type {cert_type}, expected a PCK cert chain");
+    }
+    let size = read_u32(quote, offset + 2)? as usize;
+    let data = quote
+        .get(offset + 6..offset + 6 + size)
+        .ok_or_else(|| anyhow!("SGX quote too short"))?;
+
+    Ok(data.to_vec())
+}
+
+/// The FMSPC (hex) of the PCK certificate, the first of the PEM `chain`,
+/// and the PCK CA (`processor` or `platform`) that issued it.
+fn fmspc_and_ca(chain: &[u8]) -> Result<(String, &'static str)> {
+    let chain = std::str::from_utf8(chain).context("PCK cert chain is not PEM")?;
+    let body = chain
+        .split("-----BEGIN CERTIFICATE-----")
+        .nth(1)
+        .and_then(|block| block.split("-----END CERTIFICATE-----").next())
+        .ok_or_else(|| anyhow!("no certificate in PCK cert chain"))?;
+    let body: String = body.split_whitespace().collect();
+    let pck = STANDARD.decode(body).context("illegal PEM certificate")?;
+
+    let fmspc = pck
+        .windows(FMSPC_OID.len())
+        .position(|w| w == FMSPC_OID)
+        .and_then(|i| pck.get(i + FMSPC_OID.len()..i + FMSPC_OID.len() + 6))
+        .ok_or_else(|| anyhow!("no FMSPC in PCK certificate"))?;
+    let fmspc: String = fmspc.iter().map(|b| format!("{b:02X}")).collect();
+
+    let contains = |s: &[u8]| pck.windows(s.len()).any(|w| w == s);
+    let ca = if contains(b"Intel SGX PCK Platform CA") {
+        "platform"
+    } else if contains(b"Intel SGX PCK Processor CA") {
+        "processor"
+    } else {
+        bail!("unknown issuer of PCK certificate");
+    };
+
+    Ok((fmspc, ca))
+}
+
+/// The current UTC time in the format of the `nextUpdate` of the collateral
+/// (`2023-10-13T12:00:00Z`), so that the two compare as strings.
+fn now_rfc3339() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    rfc3339(secs)
+}
+
+fn rfc3339(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let (h, m, s) = (secs % 86400 / 3600, secs % 3600 / 60, secs % 60);
+
+    // civil from days, http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}T{h:02}:{m:02}:{s:02}Z")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collateral(tcb_number: u64, qe_number: u64, next_update: &str) -> SgxCollateral {
+        SgxCollateral {
+            tcb_info: format!(
+                r#"{{"tcbInfo":{{"nextUpdate":"{next_update}","tcbEvaluationDataNumber":{tcb_number}}},"signature":"00"}}"#
+            ),
+            qe_identity: format!(
+                r#"{{"enclaveIdentity":{{"nextUpdate":"2099-01-01T00:00:00Z","tcbEvaluationDataNumber":{qe_number}}},"signature":"00"}}"#
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validity() {
+        assert_eq!(
+            collateral(16, 16, "2023-10-13T12:00:00Z").validity().unwrap(),
+            Validity {
+                next_update: "2023-10-13T12:00:00Z".into(),
+                tcb_evaluation_data_number: 16,
+            }
+        );
+
+        // halfway through a TCB recovery
+        assert!(collateral(17, 16, "2023-10-13T12:00:00Z")
+            .validity()
+            .is_err());
+        assert!(SgxCollateral::default().validity().is_err());
+    }
+
+    #[test]
+    fn test_pck_cert_chain() {
+        let pem = format!(
+            "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
+            STANDARD.encode(
+                [
+                    b"CN=Intel SGX PCK Platform CA".as_slice(),
+                    FMSPC_OID,
+                    &[0x00, 0x90, 0x6e, 0xa1, 0x00, 0x00],
+                ]
+                .concat()
+            )
+        );
+
+        let mut quote = vec![0u8; 1012];
+        quote[0] = 3;
+        quote.extend_from_slice(&2u16.to_le_bytes());
+        quote.extend_from_slice(b"qe");
+        quote.extend_from_slice(&CERT_DATA_PCK_CERT_CHAIN.to_le_bytes());
+        quote.extend_from_slice(&(pem.len() as u32).to_le_bytes());
+        quote.extend_from_slice(pem.as_bytes());
+
+        let chain = pck_cert_chain(&quote).unwrap();
+        assert_eq!(chain, pem.as_bytes());
+        assert_eq!(
+            fmspc_and_ca(&chain).unwrap(),
+            ("00906EA10000".to_string(), "platform")
+        );
+
+        assert!(pck_cert_chain(&quote[..quote.len() - 1]).is_err());
+        quote[0] = 5;
+        assert!(pck_cert_chain(&quote).is_err());
+    }
+
+    #[test]
+    fn test_helpers() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(1697198400), "2023-10-13T12:00:00Z");
+        assert_eq!(rfc3339(951782400), "2000-02-29T00:00:00Z");
+
+        assert_eq!(
+            percent_decode("-----BEGIN%20CERTIFICATE-----%0A").unwrap(),
+            "-----BEGIN CERTIFICATE-----\n"
+        );
+        assert!(percent_decode("%2").is_err());
+    }
+}
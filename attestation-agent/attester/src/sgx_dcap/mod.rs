@@ -6,11 +6,17 @@
 use super::Attester;
 use anyhow::{anyhow, bail, Result};
 use base64::Engine;
+use collateral::SgxCollateral;
 use occlum_dcap::{sgx_report_data_t, DcapQuote};
 use serde::{Deserialize, Serialize};
 
+pub mod collateral;
+
 const OCCLUM_ENV: &str = "OCCLUM";
 
+/// Sealing key of Gramine bound to the MRENCLAVE of the enclave.
+const GRAMINE_SEALING_KEY: &str = "/dev/attestation/keys/_sgx_mrenclave";
+
 enum SgxLibOsType {
     Invalid,
     Occlum,
@@ -40,6 +46,11 @@ pub fn detect_platform() -> bool {
 struct SgxDcapAttesterEvidence {
     /// Base64 encoded SGX quote.
     quote: String,
+
+    /// Collateral to verify the quote with, if it could be got, see
+    /// [`collateral`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    collateral: Option<SgxCollateral>,
 }
 
 #[derive(Debug, Default)]
@@ -77,13 +88,33 @@ impl Attester for SgxDcapAttester {
             }
         };
 
+        // the verifier can still get the collateral itself, so failing to
+        // get it is not fatal
+        let collateral = match collateral::get_collateral(&quote).await {
+            Ok(collateral) => Some(collateral),
+            Err(e) => {
+                log::warn!("SGX Attester: evidence without collateral: {e:?}");
+                None
+            }
+        };
+
         let evidence = SgxDcapAttesterEvidence {
             quote: base64::engine::general_purpose::STANDARD.encode(quote),
+            collateral,
         };
 
         serde_json::to_string(&evidence)
             .map_err(|e| anyhow!("Serialize SGX DCAP Attester evidence failed: {:?}", e))
     }
+
+    async fn get_sealing_key(&self) -> Result<Vec<u8>> {
+        match get_libos_type() {
+            SgxLibOsType::Gramine => {
+                std::fs::read(GRAMINE_SEALING_KEY).map_err(|e| anyhow!("read SGX sealing key: {e}"))
+            }
+            _ => bail!("SGX Attester: sealing keys are only supported on Gramine"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,312 @@
+// Copyright (c) 2023 IBM
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Completes the VCEK/ASK/ARK certificate chain of SNP evidence.
+//!
+//! The extended report only carries the certificates the host has been
+//! provisioned with, which is often none. Missing certificates are taken
+//! from, in order:
+//!
+//! 1. the provided certs dir ([`SNP_CERTS_DIR`], `vcek.der`, `ask.der` and
+//!    `ark.der`), for offline deployments,
+//! 2. the cache dir ([`SNP_CERTS_CACHE_DIR`]),
+//! 3. the AMD Key Distribution Service, unless [`SNP_KDS_OFFLINE_ENV`] is
+//!    set. Fetched certificates are written to the cache dir.
+//!
+//! All certificates are DER encoded. Completing the chain is best-effort:
+//! the evidence is still sent if some certificates cannot be got, and the
+//! verifier has to fetch them itself then.
+
+use anyhow::*;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hyper::body::HttpBody as _;
+use hyper::client::HttpConnector;
+use hyper::Client;
+use hyper_tls::HttpsConnector;
+use log::{debug, info, warn};
+use sev::firmware::guest::AttestationReport;
+use sev::firmware::host::{CertTableEntry, CertType, TcbVersion};
+use std::path::Path;
+use std::time::Duration;
+use tokio::fs;
+
+/// Dir of certificates provided to the guest, e.g. by the image.
+pub const SNP_CERTS_DIR: &str = "/etc/sev-snp/certs";
+
+/// Dir where certificates fetched from the KDS are cached.
+pub const SNP_CERTS_CACHE_DIR: &str = "/run/attestation-agent/snp-certs";
+
+/// If this env is set, the KDS is never contacted.
+pub const SNP_KDS_OFFLINE_ENV: &str = "SNP_KDS_OFFLINE";
+
+/// Overrides the processor model (`Milan`, `Genoa`, ...) used in KDS urls.
+pub const SNP_PRODUCT_ENV: &str = "SNP_PRODUCT";
+
+const KDS_VCEK_URL: &str = "https://kds.amd.com/vcek/v1";
+
+/// Timeout of connecting to the KDS.
+const KDS_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Timeout of a whole request to the KDS, including reading the response.
+const KDS_REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Add the certificates of the VCEK chain missing in `certs`, as far as
+/// they can be got. Failures are only logged, so that evidence can still be
+/// generated when the KDS is unreachable.
+pub async fn complete_cert_chain_best_effort(
+    report: &AttestationReport,
+    certs: &mut Vec<CertTableEntry>,
+) {
+    if let Err(e) = complete_cert_chain(report, certs).await {
+        warn!("SNP Attester: certificate chain of the evidence is incomplete: {e:#}");
+    }
+}
+
+/// Add the certificates of the VCEK chain missing in `certs`.
+pub async fn complete_cert_chain(
+    report: &AttestationReport,
+    certs: &mut Vec<CertTableEntry>,
+) -> Result<()> {
+    let missing = |certs: &Vec<CertTableEntry>, cert_type: &CertType| {
+        !certs.iter().any(|c| &c.cert_type == cert_type)
+    };
+    if [CertType::VCEK, CertType::ASK, CertType::ARK]
+        .iter()
+        .all(|t| !missing(certs, t))
+    {
+        return Ok(());
+    }
+
+    let provided = Path::new(SNP_CERTS_DIR);
+    let cache = Path::new(SNP_CERTS_CACHE_DIR);
+    let offline = std::env::var_os(SNP_KDS_OFFLINE_ENV).is_some();
+    let product = if offline {
+        // only used in KDS urls and cache file names
+        std::env::var(SNP_PRODUCT_ENV).unwrap_or_default()
+    } else {
+        get_product().await?
+    };
+
+    if missing(certs, &CertType::VCEK) {
+        let cached = cache.join(vcek_cache_name(&product, &report.reported_tcb));
+        let vcek = match load_cert(provided, "vcek.der", &cached).await? {
+            Some(vcek) => vcek,
+            None if offline => bail!("SNP Attester: VCEK not provided and KDS is offline"),
+            None => {
+                let url = vcek_url(&product, &report.chip_id, &report.reported_tcb);
+                let vcek = kds_get(&url).await.context("fetch VCEK from KDS")?;
+                store_cert(&cached, &vcek).await;
+                vcek
+            }
+        };
+        certs.push(CertTableEntry::new(CertType::VCEK, vcek));
+    }
+
+    if missing(certs, &CertType::ASK) || missing(certs, &CertType::ARK) {
+        let cached_ask = cache.join(format!("ask-{product}.der"));
+        let cached_ark = cache.join(format!("ark-{product}.der"));
+        let (ask, ark) = match (
+            load_cert(provided, "ask.der", &cached_ask).await?,
+            load_cert(provided, "ark.der", &cached_ark).await?,
+        ) {
+            (Some(ask), Some(ark)) => (ask, ark),
+            _ if offline => bail!("SNP Attester: ASK/ARK not provided and KDS is offline"),
+            _ => {
+                let url = format!("{KDS_VCEK_URL}/{product}/cert_chain");
+                let chain = kds_get(&url).await.context("fetch cert chain from KDS")?;
+                let (ask, ark) = parse_cert_chain(&chain)?;
+                store_cert(&cached_ask, &ask).await;
+                store_cert(&cached_ark, &ark).await;
+                (ask, ark)
+            }
+        };
+        if missing(certs, &CertType::ASK) {
+            certs.push(CertTableEntry::new(CertType::ASK, ask));
+        }
+        if missing(certs, &CertType::ARK) {
+            certs.push(CertTableEntry::new(CertType::ARK, ark));
+        }
+    }
+
+    Ok(())
+}
+
+async fn load_cert(provided: &Path, name: &str, cached: &Path) -> Result<Option<Vec<u8>>> {
+    for path in [&provided.join(name), cached] {
+        if path.exists() {
+            debug!("SNP Attester: using certificate {}", path.display());
+            let cert = fs::read(path)
+                .await
+                .with_context(|| format!("read {}", path.display()))?;
+            return Ok(Some(cert));
+        }
+    }
+
+    Ok(None)
+}
+
+// Failing to cache only costs another KDS round trip next time.
+async fn store_cert(path: &Path, cert: &[u8]) {
+    let res = async {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(path, cert).await
+    }
+    .await;
+    if let Err(e) = res {
+        log::warn!("SNP Attester: failed to cache {}: {e}", path.display());
+    }
+}
+
+fn vcek_cache_name(product: &str, tcb: &TcbVersion) -> String {
+    format!(
+        "vcek-{product}-{:02x}{:02x}{:02x}{:02x}.der",
+        tcb.bootloader, tcb.tee, tcb.snp, tcb.microcode
+    )
+}
+
+fn vcek_url(product: &str, chip_id: &[u8], tcb: &TcbVersion) -> String {
+    let hw_id: String = chip_id.iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{KDS_VCEK_URL}/{product}/{hw_id}?blSPL={:02}&teeSPL={:02}&snpSPL={:02}&ucodeSPL={:02}",
+        tcb.bootloader, tcb.tee, tcb.snp, tcb.microcode
+    )
+}
+
+/// Split the PEM `cert_chain` of the KDS (ASK first, then ARK) into DER
+/// certificates.
+fn parse_cert_chain(chain: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let chain = std::str::from_utf8(chain).context("cert chain is not PEM")?;
+    let mut certs = Vec::new();
+    for block in chain.split("-----BEGIN CERTIFICATE-----").skip(1) {
+        let body = block
+            .split("-----END CERTIFICATE-----")
+            .next()
+            .unwrap_or_default();
+        let body: String = body.split_whitespace().collect();
+        certs.push(STANDARD.decode(body).context("illegal PEM certificate")?);
+    }
+
+    match <[Vec<u8>; 2]>::try_from(certs) {
+        Result::Ok([ask, ark]) => Ok((ask, ark)),
+        Err(certs) => bail!(
+            "expected ASK and ARK in cert chain, got {} certs",
+            certs.len()
+        ),
+    }
+}
+
+async fn get_product() -> Result<String> {
+    if let Result::Ok(product) = std::env::var(SNP_PRODUCT_ENV) {
+        return Ok(product);
+    }
+
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").await?;
+    product_from_cpuinfo(&cpuinfo)
+}
+
+// EPYC processors supporting SNP are family 19h; models 0h-Fh are Milan,
+// 10h-1Fh and A0h-AFh are Genoa.
+fn product_from_cpuinfo(cpuinfo: &str) -> Result<String> {
+    let field = |name: &str| -> Option<u32> {
+        cpuinfo
+            .lines()
+            .find(|l| l.split(':').next().map(str::trim) == Some(name))
+            .and_then(|l| l.split(':').nth(1))
+            .and_then(|v| v.trim().parse().ok())
+    };
+
+    let (family, model) = match (field("cpu family"), field("model")) {
+        (Some(family), Some(model)) => (family, model),
+        _ => bail!("SNP Attester: cannot get cpu family and model, set {SNP_PRODUCT_ENV}"),
+    };
+
+    match (family, model) {
+        (0x19, 0x00..=0x0f) => Ok("Milan".into()),
+        (0x19, 0x10..=0x1f) | (0x19, 0xa0..=0xaf) => Ok("Genoa".into()),
+        _ => bail!(
+            "SNP Attester: unknown processor family {family:#x} model {model:#x}, set {SNP_PRODUCT_ENV}"
+        ),
+    }
+}
+
+async fn kds_get(url: &str) -> Result<Vec<u8>> {
+    info!("SNP Attester: fetching {url}");
+    tokio::time::timeout(KDS_REQUEST_TIMEOUT, kds_request(url))
+        .await
+        .map_err(|_| anyhow!("KDS request timed out after {KDS_REQUEST_TIMEOUT:?}"))?
+}
+
+async fn kds_request(url: &str) -> Result<Vec<u8>> {
+    let mut http = HttpConnector::new();
+    http.set_connect_timeout(Some(KDS_CONNECT_TIMEOUT));
+    http.enforce_http(false);
+    let https = HttpsConnector::new_with_connector(http);
+    let client = Client::builder().build::<_, hyper::Body>(https);
+
+    let request = hyper::Request::builder()
+        .uri(url)
+        .method(hyper::Method::GET)
+        .header("User-Agent", "Hyper")
+        .body(hyper::Body::empty())?;
+
+    let response = client.request(request).await?;
+    if !response.status().is_success() {
+        bail!("KDS returned {}", response.status());
+    }
+
+    let mut response_body = Vec::new();
+    let mut response = response.into_body();
+    while let Some(chunk) = response.data().await {
+        let chunk = chunk?;
+        response_body.extend_from_slice(&chunk);
+    }
+
+    Ok(response_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_product_from_cpuinfo() {
+        let cpuinfo = "processor\t: 0\nvendor_id\t: AuthenticAMD\ncpu family\t: 25\nmodel\t\t: 1\n";
+        assert_eq!(product_from_cpuinfo(cpuinfo).unwrap(), "Milan");
+
+        let cpuinfo = "cpu family\t: 25\nmodel\t\t: 17\nmodel name\t: AMD EPYC 9654\n";
+        assert_eq!(product_from_cpuinfo(cpuinfo).unwrap(), "Genoa");
+
+        assert!(product_from_cpuinfo("cpu family\t: 6\nmodel\t\t: 143\n").is_err());
+        assert!(product_from_cpuinfo("").is_err());
+    }
+
+    #[test]
+    fn test_parse_cert_chain() {
+        let chain = format!(
+            "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n\
+             -----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
+            STANDARD.encode(b"ask"),
+            STANDARD.encode(b"ark"),
+        );
+        let (ask, ark) = parse_cert_chain(chain.as_bytes()).unwrap();
+        assert_eq!(ask, b"ask");
+        assert_eq!(ark, b"ark");
+
+        assert!(parse_cert_chain(b"").is_err());
+    }
+
+    #[test]
+    fn test_vcek_url() {
+        let tcb = TcbVersion::new(3, 0, 8, 115);
+        let url = vcek_url("Milan", &[0xab; 4], &tcb);
+        assert_eq!(
+            url,
+            "https://kds.amd.com/vcek/v1/Milan/abababab?blSPL=03&teeSPL=00&snpSPL=08&ucodeSPL=115"
+        );
+        assert_eq!(vcek_cache_name("Milan", &tcb), "vcek-Milan-03000873.der");
+    }
+}
@@ -3,18 +3,34 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use super::Attester;
+use super::{check_init_data_digest, Attester, InitDataResult};
 use anyhow::*;
 use serde::{Deserialize, Serialize};
 use sev::firmware::guest::AttestationReport;
 use sev::firmware::guest::Firmware;
+use sev::firmware::guest::{DerivedKey, GuestFieldSelect};
 use sev::firmware::host::CertTableEntry;
+#[cfg(feature = "tsm-attester")]
+use sev::firmware::host::CertType;
 use std::path::Path;
 
+pub mod kds;
+
+/// The least privileged VMPL of SEV-SNP.
+const MAX_VMPL: u32 = 3;
+
 pub fn detect_platform() -> bool {
     Path::new("/sys/devices/platform/sev-guest").exists()
 }
 
+// The firmware refuses reports and keys for VMPLs more privileged than the
+// one of the guest, so the first one it accepts is the guest's.
+fn guest_vmpl(firmware: &mut Firmware) -> Result<u32> {
+    (0..=MAX_VMPL)
+        .find(|vmpl| firmware.get_report(None, None, Some(*vmpl)).is_ok())
+        .ok_or_else(|| anyhow!("Failed to get the VMPL of the guest"))
+}
+
 #[derive(Serialize, Deserialize)]
 struct SnpEvidence {
     attestation_report: AttestationReport,
@@ -36,10 +52,14 @@ impl Attester for SnpAttester {
         let mut firmware = Firmware::open()?;
         let data = report_data.as_slice().try_into()?;
 
-        let (report, certs) = firmware
+        let (report, mut certs) = firmware
             .get_ext_report(None, Some(data), Some(0))
             .context("Failed to get attestation report")?;
 
+        // bundle the VCEK chain as far as it can be got, so that verifiers
+        // need not contact the KDS
+        kds::complete_cert_chain_best_effort(&report, &mut certs).await;
+
         let evidence = SnpEvidence {
             attestation_report: report,
             cert_chain: certs,
@@ -47,4 +67,136 @@ impl Attester for SnpAttester {
 
         serde_json::to_string(&evidence).context("Serialize SNP evidence failed")
     }
+
+    async fn bind_init_data(&self, init_data_digest: &[u8]) -> Result<InitDataResult> {
+        let mut firmware = Firmware::open()?;
+        let report = firmware
+            .get_report(None, None, Some(0))
+            .context("Failed to get attestation report")?;
+
+        check_init_data_digest("HOST_DATA", &report.host_data, init_data_digest)
+            .context("SNP Attester")
+    }
+
+    async fn get_sealing_key(&self) -> Result<Vec<u8>> {
+        // derived from the VCEK, mixing in the launch measurement and the
+        // policy of the guest, at the VMPL of the guest, so that a guest
+        // running at another VMPL cannot derive the same key
+        let mut fields = GuestFieldSelect::default();
+        fields.set_guest_policy(1);
+        fields.set_measurement(1);
+
+        let mut firmware = Firmware::open()?;
+        let vmpl = guest_vmpl(&mut firmware)?;
+        let request = DerivedKey::new(false, fields, vmpl, 0, 0);
+        let key = firmware
+            .get_derived_key(None, request)
+            .context("Failed to get SNP derived key")?;
+
+        Ok(key.to_vec())
+    }
+}
+
+#[cfg(feature = "tsm-attester")]
+/// Build SNP evidence of a raw attestation report and the certificate
+/// table of the host, as returned by `configfs-tsm`.
+pub(crate) async fn evidence_from_raw(report: &[u8], cert_table: Option<&[u8]>) -> Result<String> {
+    if report.len() < std::mem::size_of::<AttestationReport>() {
+        bail!("SNP attestation report too short ({} bytes)", report.len());
+    }
+
+    // SAFETY: `AttestationReport` is the `repr(C)` layout of the report and
+    // `report` is long enough.
+    let report = unsafe { std::ptr::read_unaligned(report.as_ptr() as *const AttestationReport) };
+    let mut certs = match cert_table {
+        Some(table) => parse_cert_table(table)?,
+        None => Vec::new(),
+    };
+    kds::complete_cert_chain_best_effort(&report, &mut certs).await;
+
+    let evidence = SnpEvidence {
+        attestation_report: report,
+        cert_chain: certs,
+    };
+
+    serde_json::to_string(&evidence).context("Serialize SNP evidence failed")
+}
+
+#[cfg(feature = "tsm-attester")]
+/// Parse the certificate table of the GHCB extended guest request: entries
+/// of a 16 bytes GUID, offset and length of the certificate, terminated by
+/// an all zero entry. Certificates other than VCEK, ASK and ARK are skipped.
+fn parse_cert_table(table: &[u8]) -> Result<Vec<CertTableEntry>> {
+    const ENTRY_SIZE: usize = 24;
+
+    let mut certs = Vec::new();
+    for entry in table.chunks(ENTRY_SIZE) {
+        if entry.len() < ENTRY_SIZE {
+            bail!("truncated SNP certificate table");
+        }
+
+        let guid = &entry[..16];
+        if guid.iter().all(|b| *b == 0) {
+            return Ok(certs);
+        }
+        let offset = u32::from_le_bytes(entry[16..20].try_into()?) as usize;
+        let length = u32::from_le_bytes(entry[20..24].try_into()?) as usize;
+        let data = table
+            .get(offset..offset + length)
+            .ok_or_else(|| anyhow!("SNP certificate out of the certificate table"))?;
+
+        let guid = format!(
+            "{}-{}-{}-{}-{}",
+            hex_string(&guid[..4]),
+            hex_string(&guid[4..6]),
+            hex_string(&guid[6..8]),
+            hex_string(&guid[8..10]),
+            hex_string(&guid[10..])
+        );
+        match [CertType::VCEK, CertType::ASK, CertType::ARK]
+            .into_iter()
+            .find(|t| t.to_string() == guid)
+        {
+            Some(cert_type) => certs.push(CertTableEntry::new(cert_type, data.to_vec())),
+            None => log::debug!("SNP Attester: skip certificate {guid}"),
+        }
+    }
+
+    bail!("SNP certificate table is not terminated")
+}
+
+#[cfg(feature = "tsm-attester")]
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(all(test, feature = "tsm-attester"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cert_table() {
+        // VCEK, an unknown GUID and the terminator, then the certificates
+        let mut table = Vec::new();
+        table.extend_from_slice(&[
+            0x63, 0xda, 0x75, 0x8d, 0xe6, 0x64, 0x45, 0x64, 0xad, 0xc5, 0xf4, 0xb9, 0x3b, 0xe8,
+            0xac, 0xcd,
+        ]);
+        table.extend_from_slice(&72u32.to_le_bytes());
+        table.extend_from_slice(&4u32.to_le_bytes());
+        table.extend_from_slice(&[0xab; 16]);
+        table.extend_from_slice(&76u32.to_le_bytes());
+        table.extend_from_slice(&2u32.to_le_bytes());
+        table.extend_from_slice(&[0; 24]);
+        table.extend_from_slice(b"vcekxx");
+
+        let certs = parse_cert_table(&table).unwrap();
+        assert_eq!(
+            certs,
+            vec![CertTableEntry::new(CertType::VCEK, b"vcek".to_vec())]
+        );
+
+        assert!(parse_cert_table(&table[..48]).is_err());
+        assert_eq!(std::mem::size_of::<AttestationReport>(), 1184);
+    }
 }
@@ -0,0 +1,221 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Replay of the CC event log (CCEL) against the RTMRs of a TD quote.
+//!
+//! The CCEL uses the crypto agile format of the TCG PC Client Platform
+//! Firmware Profile: a `TCG_PCR_EVENT` carrying the spec id event, followed
+//! by `TCG_PCR_EVENT2` entries. The index of an entry is the TDX measurement
+//! register index, where `1..=4` are RTMR0 to RTMR3.
+
+use anyhow::*;
+use sha2::{Digest, Sha384};
+
+pub const RTMR_SIZE: usize = 48;
+
+/// Number of RTMRs of a TD.
+pub const RTMR_COUNT: usize = 4;
+
+// TD quote v4: header (48 bytes), then the TD report body with TEE_TCB_SVN
+// (16), MRSEAM (48), MRSIGNERSEAM (48), SEAMATTRIBUTES (8), TDATTRIBUTES (8),
+// XFAM (8), MRTD, MRCONFIGID, MROWNER and MROWNERCONFIG (48 each) before
+// RTMR0..RTMR3.
+const QUOTE_RTMR_OFFSET: usize = 48 + 16 + 48 + 48 + 8 + 8 + 8 + 48 * 4;
+
+const TPM_ALG_SHA384: u16 = 0x000c;
+const EV_NO_ACTION: u32 = 0x3;
+
+/// Get RTMR0..RTMR3 from a TD quote.
+pub fn rtmrs_from_quote(quote: &[u8]) -> Result<[[u8; RTMR_SIZE]; RTMR_COUNT]> {
+    let end = QUOTE_RTMR_OFFSET + RTMR_SIZE * RTMR_COUNT;
+    if quote.len() < end {
+        bail!("TD quote too short ({} bytes)", quote.len());
+    }
+
+    let mut rtmrs = [[0u8; RTMR_SIZE]; RTMR_COUNT];
+    for (i, rtmr) in rtmrs.iter_mut().enumerate() {
+        let start = QUOTE_RTMR_OFFSET + i * RTMR_SIZE;
+        rtmr.copy_from_slice(&quote[start..start + RTMR_SIZE]);
+    }
+
+    Ok(rtmrs)
+}
+
+/// Replay the SHA-384 digests of the event log. Returns the RTMR values the
+/// log results in.
+pub fn replay(eventlog: &[u8]) -> Result<[[u8; RTMR_SIZE]; RTMR_COUNT]> {
+    let mut reader = Reader(eventlog);
+
+    // TCG_PCR_EVENT with the spec id event, which lists the digest sizes
+    let _index = reader.u32()?;
+    let _event_type = reader.u32()?;
+    reader.take(20)?;
+    let spec_id_size = reader.u32()? as usize;
+    let mut spec_id = Reader(reader.take(spec_id_size)?);
+    spec_id.take(16 + 4 + 4)?;
+    let algorithms = spec_id.u32()?;
+    let mut digest_sizes = Vec::new();
+    for _ in 0..algorithms {
+        let alg = spec_id.u16()?;
+        let size = spec_id.u16()? as usize;
+        digest_sizes.push((alg, size));
+    }
+
+    let mut rtmrs = [[0u8; RTMR_SIZE]; RTMR_COUNT];
+    // the log is padded to the end of its area
+    while reader.0.len() >= 8 && !reader.0.iter().all(|b| *b == 0xff) {
+        let index = reader.u32()?;
+        let event_type = reader.u32()?;
+        let digests = reader.u32()?;
+        let mut sha384 = None;
+        for _ in 0..digests {
+            let alg = reader.u16()?;
+            let size = digest_sizes
+                .iter()
+                .find(|(a, _)| *a == alg)
+                .map(|(_, s)| *s)
+                .ok_or_else(|| anyhow!("unknown digest algorithm {alg:#x} in event log"))?;
+            let digest = reader.take(size)?;
+            if alg == TPM_ALG_SHA384 {
+                sha384 = Some(digest);
+            }
+        }
+        let event_size = reader.u32()? as usize;
+        reader.take(event_size)?;
+
+        if event_type == EV_NO_ACTION || index == 0 {
+            continue;
+        }
+
+        let rtmr = rtmrs
+            .get_mut(index as usize - 1)
+            .ok_or_else(|| anyhow!("illegal measurement register index {index} in event log"))?;
+        let digest =
+            sha384.ok_or_else(|| anyhow!("event of RTMR{} has no SHA-384 digest", index - 1))?;
+        let mut hasher = Sha384::new();
+        hasher.update(&rtmr[..]);
+        hasher.update(digest);
+        rtmr.copy_from_slice(&hasher.finalize());
+    }
+
+    Ok(rtmrs)
+}
+
+/// Check that replaying the event log gives the RTMRs of the quote. Only
+/// RTMR0 to RTMR2 are checked: RTMR3 is extended at runtime (see
+/// `extend_runtime_measurement`) and those events are not in the CCEL.
+pub fn verify(eventlog: &[u8], quote: &[u8]) -> Result<()> {
+    let expected = rtmrs_from_quote(quote)?;
+    let replayed = replay(eventlog).context("parse CC event log")?;
+    for i in 0..RTMR_COUNT - 1 {
+        if replayed[i] != expected[i] {
+            bail!("replayed CC event log does not match RTMR{i} of the quote");
+        }
+    }
+
+    Ok(())
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!("truncated event log");
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec_id_event() -> Vec<u8> {
+        let mut spec_id = b"Spec ID Event03\0".to_vec();
+        spec_id.extend_from_slice(&0u32.to_le_bytes());
+        spec_id.extend_from_slice(&[0, 2, 0, 2]);
+        spec_id.extend_from_slice(&1u32.to_le_bytes());
+        spec_id.extend_from_slice(&TPM_ALG_SHA384.to_le_bytes());
+        spec_id.extend_from_slice(&(RTMR_SIZE as u16).to_le_bytes());
+        spec_id.push(0);
+
+        let mut event = Vec::new();
+        event.extend_from_slice(&0u32.to_le_bytes());
+        event.extend_from_slice(&EV_NO_ACTION.to_le_bytes());
+        event.extend_from_slice(&[0; 20]);
+        event.extend_from_slice(&(spec_id.len() as u32).to_le_bytes());
+        event.extend_from_slice(&spec_id);
+        event
+    }
+
+    fn event(index: u32, digest: &[u8; RTMR_SIZE]) -> Vec<u8> {
+        let mut event = Vec::new();
+        event.extend_from_slice(&index.to_le_bytes());
+        event.extend_from_slice(&0xdu32.to_le_bytes());
+        event.extend_from_slice(&1u32.to_le_bytes());
+        event.extend_from_slice(&TPM_ALG_SHA384.to_le_bytes());
+        event.extend_from_slice(digest);
+        event.extend_from_slice(&3u32.to_le_bytes());
+        event.extend_from_slice(b"foo");
+        event
+    }
+
+    fn extend(rtmr: &[u8; RTMR_SIZE], digest: &[u8; RTMR_SIZE]) -> [u8; RTMR_SIZE] {
+        let mut hasher = Sha384::new();
+        hasher.update(rtmr);
+        hasher.update(digest);
+        hasher.finalize().into()
+    }
+
+    #[test]
+    fn test_replay_and_verify() {
+        let mut eventlog = spec_id_event();
+        eventlog.extend(event(1, &[1; RTMR_SIZE]));
+        eventlog.extend(event(2, &[2; RTMR_SIZE]));
+        eventlog.extend(event(1, &[3; RTMR_SIZE]));
+        eventlog.extend([0xff; 64]);
+
+        let rtmr0 = extend(&extend(&[0; RTMR_SIZE], &[1; RTMR_SIZE]), &[3; RTMR_SIZE]);
+        let rtmr1 = extend(&[0; RTMR_SIZE], &[2; RTMR_SIZE]);
+        let replayed = replay(&eventlog).unwrap();
+        assert_eq!(replayed[0], rtmr0);
+        assert_eq!(replayed[1], rtmr1);
+        assert_eq!(replayed[2], [0; RTMR_SIZE]);
+
+        let mut quote = vec![0; QUOTE_RTMR_OFFSET];
+        quote.extend_from_slice(&rtmr0);
+        quote.extend_from_slice(&rtmr1);
+        quote.extend_from_slice(&[0; RTMR_SIZE]);
+        // RTMR3 is not checked
+        quote.extend_from_slice(&[9; RTMR_SIZE]);
+        verify(&eventlog, &quote).unwrap();
+
+        quote[QUOTE_RTMR_OFFSET] ^= 1;
+        assert!(verify(&eventlog, &quote).is_err());
+    }
+
+    #[test]
+    fn test_replay_corrupt() {
+        let mut eventlog = spec_id_event();
+        eventlog.extend(event(1, &[1; RTMR_SIZE]));
+        eventlog.truncate(eventlog.len() - 2);
+        assert!(replay(&eventlog).is_err());
+
+        let mut eventlog = spec_id_event();
+        eventlog.extend(event(7, &[1; RTMR_SIZE]));
+        assert!(replay(&eventlog).is_err());
+    }
+}
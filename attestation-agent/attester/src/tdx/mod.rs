@@ -3,15 +3,23 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use super::Attester;
+use super::{check_init_data_digest, Attester, InitDataResult};
 use anyhow::*;
 use base64::Engine;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use tdx_attest_rs;
 
+pub mod eventlog;
+
 const CCEL_PATH: &str = "/sys/firmware/acpi/tables/data/CCEL";
 
+// TDREPORT is REPORTMACSTRUCT (256 bytes), TEE_TCB_INFO (239 bytes) and a
+// reserved area (17 bytes) followed by TDINFO, which starts with ATTRIBUTES
+// (8 bytes), XFAM (8 bytes) and MRTD (48 bytes) before MRCONFIGID.
+const MRCONFIGID_OFFSET: usize = 512 + 8 + 8 + 48;
+const MRCONFIGID_SIZE: usize = 48;
+
 pub fn detect_platform() -> bool {
     Path::new("/dev/tdx-attest").exists() || Path::new("/dev/tdx-guest").exists()
 }
@@ -43,7 +51,7 @@ impl Attester for TdxAttester {
 
         let engine = base64::engine::general_purpose::STANDARD;
         let quote = match tdx_attest_rs::tdx_att_get_quote(Some(&tdx_report_data), None, None, 0) {
-            (tdx_attest_rs::tdx_attest_error_t::TDX_ATTEST_SUCCESS, Some(q)) => engine.encode(q),
+            (tdx_attest_rs::tdx_attest_error_t::TDX_ATTEST_SUCCESS, Some(q)) => q,
             (error_code, _) => {
                 return Err(anyhow!(
                     "TDX Attester: Failed to get TD quote. Error code: {:?}",
@@ -53,14 +61,22 @@ impl Attester for TdxAttester {
         };
 
         let cc_eventlog = match std::fs::read(CCEL_PATH) {
-            Result::Ok(el) => Some(engine.encode(el)),
+            Result::Ok(el) => {
+                // a log not matching the quote would only be rejected by
+                // the verifier
+                eventlog::verify(&el, &quote).context("TDX Attester: invalid CC Eventlog")?;
+                Some(engine.encode(el))
+            }
             Result::Err(e) => {
                 log::warn!("Read CC Eventlog failed: {:?}", e);
                 None
             }
         };
 
-        let evidence = TdxEvidence { cc_eventlog, quote };
+        let evidence = TdxEvidence {
+            cc_eventlog,
+            quote: engine.encode(quote),
+        };
 
         serde_json::to_string(&evidence)
             .map_err(|e| anyhow!("Serialize TDX evidence failed: {:?}", e))
@@ -87,6 +103,21 @@ impl Attester for TdxAttester {
 
         Ok(())
     }
+
+    async fn bind_init_data(&self, init_data_digest: &[u8]) -> Result<InitDataResult> {
+        let report = match tdx_attest_rs::tdx_att_get_report(None) {
+            (tdx_attest_rs::tdx_attest_error_t::TDX_ATTEST_SUCCESS, Some(r)) => r,
+            (error_code, _) => {
+                bail!(
+                    "TDX Attester: Failed to get TD report. Error code: {:?}",
+                    error_code
+                );
+            }
+        };
+
+        let mr_config_id = &report.d[MRCONFIGID_OFFSET..MRCONFIGID_OFFSET + MRCONFIGID_SIZE];
+        check_init_data_digest("MRCONFIGID", mr_config_id, init_data_digest).context("TDX Attester")
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,174 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Generic attester on the `configfs-tsm` report ABI of Linux.
+//!
+//! It is used when no dedicated attester of the TEE is built in or can be
+//! used, e.g. on TDX guests without `/dev/tdx-attest`. The TEE is given by
+//! the provider of the reports, and the evidence has the format of the
+//! dedicated attester, so verifiers need not tell them apart.
+
+use super::{check_init_data_digest, Attester, InitDataResult};
+use crate::tsm_report::{self, TsmReport, TSM_INBLOB_SIZE};
+use anyhow::*;
+use base64::Engine;
+use kbs_types::Tee;
+use serde::{Deserialize, Serialize};
+
+const CCEL_PATH: &str = "/sys/firmware/acpi/tables/data/CCEL";
+
+// MRCONFIGID of a TDX quote: 48 bytes of header, then TEE_TCB_SVN (16
+// bytes), MRSEAM, MRSIGNERSEAM (48 bytes each), SEAMATTRIBUTES,
+// TDATTRIBUTES, XFAM (8 bytes each) and MRTD (48 bytes) of the report body.
+const TDX_QUOTE_MRCONFIGID_OFFSET: usize = 48 + 16 + 48 + 48 + 8 + 8 + 8 + 48;
+const TDX_MRCONFIGID_SIZE: usize = 48;
+
+// HOST_DATA of an SNP attestation report.
+const SNP_REPORT_HOST_DATA_OFFSET: usize = 0xc0;
+const SNP_HOST_DATA_SIZE: usize = 32;
+
+/// Get the TEE of the `configfs-tsm` reports, if the ABI is available and
+/// the TEE is supported.
+pub fn detect_platform() -> Option<Tee> {
+    tsm_report::detect_provider().and_then(|p| provider_tee(&p))
+}
+
+fn provider_tee(provider: &str) -> Option<Tee> {
+    match provider {
+        "tdx_guest" => Some(Tee::Tdx),
+        "sev_guest" => Some(Tee::Snp),
+        "arm_cca_guest" => Some(Tee::Cca),
+        _ => None,
+    }
+}
+
+/// Same as the evidence of the TDX attester.
+#[derive(Serialize, Deserialize)]
+struct TdxEvidence {
+    cc_eventlog: Option<String>,
+    quote: String,
+}
+
+/// Same as the evidence of the CCA attester.
+#[derive(Serialize, Deserialize)]
+struct CcaEvidence {
+    token: Vec<u8>,
+}
+
+// the name and content of the register of the report `outblob` the host
+// sets the init-data digest into, if the TEE has one
+fn init_data_register(tee: Tee, outblob: &[u8]) -> Result<Option<(&'static str, &[u8])>> {
+    let (name, offset, size) = match tee {
+        Tee::Tdx => (
+            "MRCONFIGID",
+            TDX_QUOTE_MRCONFIGID_OFFSET,
+            TDX_MRCONFIGID_SIZE,
+        ),
+        Tee::Snp => ("HOST_DATA", SNP_REPORT_HOST_DATA_OFFSET, SNP_HOST_DATA_SIZE),
+        _ => return Ok(None),
+    };
+    let register = outblob
+        .get(offset..offset + size)
+        .ok_or_else(|| anyhow!("{tee:?} report is too short to hold {name}"))?;
+    Ok(Some((name, register)))
+}
+
+#[derive(Debug, Default)]
+pub struct TsmAttester {}
+
+#[async_trait::async_trait]
+impl Attester for TsmAttester {
+    async fn get_evidence(&self, mut report_data: Vec<u8>) -> Result<String> {
+        if report_data.len() > TSM_INBLOB_SIZE {
+            bail!("TSM Attester: Report data must be no more than {TSM_INBLOB_SIZE} bytes");
+        }
+        report_data.resize(TSM_INBLOB_SIZE, 0);
+
+        let report = TsmReport::new()?;
+        let provider = report.provider()?;
+        let tee = provider_tee(&provider)
+            .ok_or_else(|| anyhow!("TSM Attester: unsupported provider {provider:?}"))?;
+        let blobs = report
+            .get(&report_data)
+            .context("TSM Attester: Failed to get report")?;
+
+        match tee {
+            Tee::Tdx => {
+                let engine = base64::engine::general_purpose::STANDARD;
+                let cc_eventlog = match std::fs::read(CCEL_PATH) {
+                    Result::Ok(el) => Some(engine.encode(el)),
+                    Result::Err(e) => {
+                        log::warn!("Read CC Eventlog failed: {:?}", e);
+                        None
+                    }
+                };
+                let evidence = TdxEvidence {
+                    cc_eventlog,
+                    quote: engine.encode(blobs.outblob),
+                };
+                serde_json::to_string(&evidence).context("Serialize TDX evidence failed")
+            }
+            Tee::Cca => {
+                let evidence = CcaEvidence {
+                    token: blobs.outblob,
+                };
+                serde_json::to_string(&evidence).context("Serialize CCA evidence failed")
+            }
+            #[cfg(feature = "snp-attester")]
+            Tee::Snp => {
+                crate::snp::evidence_from_raw(&blobs.outblob, blobs.auxblob.as_deref()).await
+            }
+            _ => bail!("TSM Attester: {tee:?} is not supported in this build"),
+        }
+    }
+
+    async fn bind_init_data(&self, init_data_digest: &[u8]) -> Result<InitDataResult> {
+        let report = TsmReport::new()?;
+        let provider = report.provider()?;
+        let tee = provider_tee(&provider)
+            .ok_or_else(|| anyhow!("TSM Attester: unsupported provider {provider:?}"))?;
+        let blobs = report
+            .get(&[0; TSM_INBLOB_SIZE])
+            .context("TSM Attester: Failed to get report")?;
+
+        match init_data_register(tee, &blobs.outblob).context("TSM Attester")? {
+            Some((name, register)) => {
+                check_init_data_digest(name, register, init_data_digest).context("TSM Attester")
+            }
+            None => Ok(InitDataResult::Unsupported),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_tee() {
+        assert_eq!(provider_tee("tdx_guest"), Some(Tee::Tdx));
+        assert_eq!(provider_tee("sev_guest"), Some(Tee::Snp));
+        assert_eq!(provider_tee("arm_cca_guest"), Some(Tee::Cca));
+        assert_eq!(provider_tee("foo_guest"), None);
+    }
+
+    #[test]
+    fn test_init_data_register() {
+        let mut quote = vec![0u8; 632];
+        quote[TDX_QUOTE_MRCONFIGID_OFFSET..][..TDX_MRCONFIGID_SIZE].fill(1);
+        let (name, register) = init_data_register(Tee::Tdx, &quote).unwrap().unwrap();
+        assert_eq!(name, "MRCONFIGID");
+        assert_eq!(register, [1; TDX_MRCONFIGID_SIZE]);
+
+        let mut report = vec![0u8; 1184];
+        report[SNP_REPORT_HOST_DATA_OFFSET..][..SNP_HOST_DATA_SIZE].fill(2);
+        let (name, register) = init_data_register(Tee::Snp, &report).unwrap().unwrap();
+        assert_eq!(name, "HOST_DATA");
+        assert_eq!(register, [2; SNP_HOST_DATA_SIZE]);
+
+        assert!(init_data_register(Tee::Snp, &report[..0xd0]).is_err());
+        assert!(init_data_register(Tee::Cca, &report).unwrap().is_none());
+    }
+}
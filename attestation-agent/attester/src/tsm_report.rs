@@ -0,0 +1,156 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! The `configfs-tsm` report ABI of Linux.
+//!
+//! An entry created under [`TSM_REPORT_PATH`] gets a report of the TEE the
+//! guest runs in: the report data is written to `inblob`, the report is read
+//! from `outblob` and, for some TEEs, additional data such as certificates
+//! from `auxblob`. `provider` names the kernel driver serving the entry.
+
+use anyhow::*;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub const TSM_REPORT_PATH: &str = "/sys/kernel/config/tsm/report";
+
+/// Max size of the report data written to `inblob`.
+pub const TSM_INBLOB_SIZE: usize = 64;
+
+static NEXT_REPORT: AtomicUsize = AtomicUsize::new(0);
+
+/// A report entry, removed on drop.
+pub struct TsmReport {
+    path: PathBuf,
+}
+
+/// Blobs of a report.
+pub struct TsmReportBlobs {
+    pub outblob: Vec<u8>,
+    pub auxblob: Option<Vec<u8>>,
+}
+
+// name of a new report entry, unique to the call, so that concurrent
+// calls, e.g. `get_evidence` and `detect_platform`, don't share an entry
+fn report_name() -> String {
+    format!(
+        "attester-{}-{}",
+        std::process::id(),
+        NEXT_REPORT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+impl TsmReport {
+    pub fn new() -> Result<Self> {
+        let path = Path::new(TSM_REPORT_PATH).join(report_name());
+        fs::create_dir(&path).with_context(|| format!("create TSM report {}", path.display()))?;
+
+        Ok(Self { path })
+    }
+
+    /// Name of the driver serving the report, e.g. `tdx_guest`.
+    pub fn provider(&self) -> Result<String> {
+        let provider = self.read("provider")?;
+        Ok(String::from_utf8_lossy(&provider).trim().to_string())
+    }
+
+    fn read(&self, attr: &str) -> Result<Vec<u8>> {
+        fs::read(self.path.join(attr)).with_context(|| format!("read TSM report {attr}"))
+    }
+
+    /// Get the report bound to `inblob`.
+    pub fn get(&self, inblob: &[u8]) -> Result<TsmReportBlobs> {
+        if inblob.len() > TSM_INBLOB_SIZE {
+            bail!("TSM report data must be no more than {TSM_INBLOB_SIZE} bytes");
+        }
+
+        fs::write(self.path.join("inblob"), inblob).context("write TSM report inblob")?;
+        let outblob = self.read("outblob")?;
+        let auxblob = match fs::read(self.path.join("auxblob")) {
+            Result::Ok(auxblob) if auxblob.is_empty() => None,
+            Result::Ok(auxblob) => Some(auxblob),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(e).context("read TSM report auxblob"),
+        };
+
+        // Every write of inblob bumps the generation; a different value
+        // means the report belongs to someone else's report data.
+        let generation = self.read("generation")?;
+        if String::from_utf8_lossy(&generation).trim() != "1" {
+            bail!("TSM report was written concurrently");
+        }
+
+        Ok(TsmReportBlobs { outblob, auxblob })
+    }
+}
+
+impl Drop for TsmReport {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir(&self.path) {
+            log::warn!("failed to remove TSM report {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Whether the `configfs-tsm` ABI is available. Only its absence is `false`,
+/// other errors are surfaced.
+pub fn is_available() -> Result<bool> {
+    match fs::metadata(TSM_REPORT_PATH) {
+        Result::Ok(_) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("stat {TSM_REPORT_PATH}")),
+    }
+}
+
+/// Get the provider of `configfs-tsm` reports, if the ABI is available.
+pub fn detect_provider() -> Option<String> {
+    if !is_available().ok()? {
+        return None;
+    }
+
+    TsmReport::new().and_then(|r| r.provider()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_name() {
+        assert_ne!(report_name(), report_name());
+    }
+
+    #[test]
+    fn test_tsm_report_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("provider"), b"arm_cca_guest\n").unwrap();
+        fs::write(path.join("outblob"), b"token").unwrap();
+        fs::write(path.join("generation"), b"1\n").unwrap();
+
+        let report = TsmReport { path: path.clone() };
+        assert_eq!(report.provider().unwrap(), "arm_cca_guest");
+        let blobs = report.get(&[1; TSM_INBLOB_SIZE]).unwrap();
+        assert_eq!(blobs.outblob, b"token");
+        assert!(blobs.auxblob.is_none());
+        assert_eq!(fs::read(path.join("inblob")).unwrap(), [1; TSM_INBLOB_SIZE]);
+
+        fs::write(path.join("auxblob"), b"certs").unwrap();
+        let blobs = report.get(&[1; TSM_INBLOB_SIZE]).unwrap();
+        assert_eq!(blobs.auxblob.unwrap(), b"certs");
+
+        assert!(report.get(&[1; TSM_INBLOB_SIZE + 1]).is_err());
+
+        fs::write(path.join("generation"), b"2\n").unwrap();
+        assert!(report.get(&[1; TSM_INBLOB_SIZE]).is_err());
+
+        // the entry is only removed when empty, as in configfs
+        drop(report);
+        assert!(path.exists());
+    }
+}
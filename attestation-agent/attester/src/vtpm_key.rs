@@ -0,0 +1,59 @@
+// Copyright (c) 2023 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Sealing key of the vTPM of Azure confidential VMs.
+//!
+//! The guest cannot get a key from the paravisor, but the vTPM state is
+//! kept with the VM. The primary HMAC key of the owner hierarchy is derived
+//! from the owner seed of the vTPM with a fixed template, so it is the same
+//! key on every boot of the VM and on no other VM, and never leaves the
+//! vTPM. The sealing key is the HMAC of a fixed label with it.
+
+use anyhow::*;
+use tss_esapi::attributes::ObjectAttributesBuilder;
+use tss_esapi::interface_types::algorithm::{HashingAlgorithm, PublicAlgorithm};
+use tss_esapi::interface_types::resource_handles::Hierarchy;
+use tss_esapi::structures::{
+    Digest, KeyedHashScheme, MaxBuffer, PublicBuilder, PublicKeyedHashParameters,
+};
+use tss_esapi::tcti_ldr::{DeviceConfig, TctiNameConf};
+
+/// Label the HMAC key of the vTPM is applied to.
+const SEALING_KEY_LABEL: &[u8] = b"attestation-agent sealing key";
+
+/// Get the sealing key of the vTPM.
+pub fn get_sealing_key() -> Result<Vec<u8>> {
+    let mut context = tss_esapi::Context::new(TctiNameConf::Device(DeviceConfig::default()))?;
+
+    let attributes = ObjectAttributesBuilder::new()
+        .with_fixed_tpm(true)
+        .with_fixed_parent(true)
+        .with_sensitive_data_origin(true)
+        .with_user_with_auth(true)
+        .with_sign_encrypt(true)
+        .build()?;
+    let template = PublicBuilder::new()
+        .with_public_algorithm(PublicAlgorithm::KeyedHash)
+        .with_name_hashing_algorithm(HashingAlgorithm::Sha256)
+        .with_object_attributes(attributes)
+        .with_keyed_hash_parameters(PublicKeyedHashParameters::new(
+            KeyedHashScheme::HMAC_SHA_256,
+        ))
+        .with_keyed_hash_unique_identifier(Digest::default())
+        .build()?;
+
+    let key = context
+        .execute_with_nullauth_session(|ctx| {
+            ctx.create_primary(Hierarchy::Owner, template, None, None, None, None)
+        })
+        .context("create vTPM HMAC key")?;
+    let label = MaxBuffer::try_from(SEALING_KEY_LABEL.to_vec())?;
+    let hmac = context.execute_with_nullauth_session(|ctx| {
+        ctx.hmac(key.key_handle.into(), label, HashingAlgorithm::Sha256)
+    });
+    context.flush_context(key.key_handle.into())?;
+
+    Ok(hmac.context("HMAC with vTPM key")?.value().to_vec())
+}
@@ -185,8 +185,12 @@ fn normalize_path(keyid: &str) -> Result<(String, String)> {
 /// | keyid     | a KBS Resource URI, s.t. `kbs://..`  | Specify the KEK of this image. keyid field will be included in AnnotationPacket                  |
 /// | keypath   | path to the KEK, e.g. `/home/key`    | Specify the KEK to encrypted the image in local filesystem                                       |
 /// | algorithm | `A256GCM` or `A256CTR`               | Encryption algorithm, included in the `wrap_type` field of AnnotationPacket. By default `A256GCM`|
+///
+/// The KEK is registered into every KBS of `kbs_parameter`. Images encrypted
+/// for several KBSes with different KEKs get one `provider:attestation-agent`
+/// parameter per KEK, each wrapped by a call of its own.
 pub async fn enc_optsdata_gen_anno(
-    kbs_parameter: (&Option<Url>, &Option<Ed25519KeyPair>),
+    kbs_parameter: (&[Url], &Option<Ed25519KeyPair>),
     optsdata: &[u8],
     params: Vec<String>,
 ) -> Result<String> {
@@ -201,13 +205,15 @@ pub async fn enc_optsdata_gen_anno(
     let encrypt_optsdata = crypto::encrypt(optsdata, &key, &iv, &algorithm)
         .map_err(|e| anyhow!("Encrypt failed: {:?}", e))?;
 
-    if let (Some(addr), Some(private_key)) = kbs_parameter {
+    if let (addrs, Some(private_key)) = kbs_parameter {
+        // We do not register KEK for sample kbc
         if !input_params.sample {
-            // We do not register KEK for sample kbc
-            register_kek(private_key, addr, key, &k_path)
-                .await
-                .context("register KEK failed")?;
-            info!("register KEK succeeded.");
+            for addr in addrs {
+                register_kek(private_key, addr, key.clone(), &k_path)
+                    .await
+                    .with_context(|| format!("register KEK into {addr} failed"))?;
+                info!("register KEK into {addr} succeeded.");
+            }
         }
     }
 
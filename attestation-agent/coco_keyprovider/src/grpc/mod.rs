@@ -28,15 +28,18 @@ pub mod key_provider {
 
 pub struct KeyProvider {
     auth_private_key: Option<Ed25519KeyPair>,
-    kbs: Option<Url>,
+    kbs: Vec<Url>,
 }
 
 impl KeyProvider {
-    pub fn new(auth_private_key: Option<Ed25519KeyPair>, kbs: Option<String>) -> Result<Self> {
-        let kbs = match kbs {
-            Some(addr) => addr.parse().ok(),
-            None => None,
-        };
+    pub fn new(auth_private_key: Option<Ed25519KeyPair>, kbs: Vec<String>) -> Result<Self> {
+        let kbs = kbs
+            .iter()
+            .map(|addr| {
+                addr.parse()
+                    .with_context(|| format!("illegal KBS address {addr}"))
+            })
+            .collect::<Result<_>>()?;
 
         Ok(Self {
             auth_private_key,
@@ -94,7 +97,7 @@ impl KeyProviderService for KeyProvider {
             .collect();
 
         let annotation: String = enc_mods::enc_optsdata_gen_anno(
-            (&self.kbs, &self.auth_private_key),
+            (self.kbs.as_slice(), &self.auth_private_key),
             &engine
                 .decode(optsdata)
                 .map_err(|_| Status::aborted("base64 decode"))?,
@@ -140,7 +143,7 @@ impl KeyProviderService for KeyProvider {
 pub async fn start_service(
     socket: SocketAddr,
     auth_private_key: Option<PathBuf>,
-    kbs: Option<String>,
+    kbs: Vec<String>,
 ) -> Result<()> {
     let auth_private_key = match auth_private_key {
         Some(key_path) => {
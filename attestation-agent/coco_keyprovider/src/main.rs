@@ -29,9 +29,11 @@ struct Cli {
 
     /// Address of Key Broker Service. If both `auth_private_key` and
     /// this field are specified, the keys generated to encrypt an image
-    /// will be automatically registered into the KBS.
+    /// will be automatically registered into the KBS. Can be given
+    /// several times, to register the keys into every KBS the image is
+    /// encrypted for.
     #[arg(long)]
-    kbs: Option<String>,
+    kbs: Vec<String>,
 
     /// Whether this process is launched in daemon mode. If it is set to
     /// true, the stdio and stderr will be redirected to
@@ -52,7 +54,7 @@ async fn main() -> Result<()> {
     debug!("starting keyprovider gRPC service...");
     info!("listening to socket addr: {:?}", cli.socket);
 
-    if cli.auth_private_key.is_some() && cli.kbs.is_some() {
+    if cli.auth_private_key.is_some() && !cli.kbs.is_empty() {
         info!(
             "The encryption key will be registered to kbs: {:?}",
             cli.kbs
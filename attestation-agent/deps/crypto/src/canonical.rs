@@ -0,0 +1,57 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Canonical JSON of configurations, so that their digests do not depend on
+//! the field order. Shared by the guest components measuring their
+//! configuration, so that the digests of the same configuration agree.
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+/// `value` with the keys of all its objects sorted.
+pub fn canonical_json(value: &Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut keys: Vec<_> = object.keys().collect();
+            keys.sort();
+            let sorted: Map<String, Value> = keys
+                .into_iter()
+                .map(|key| (key.clone(), canonical_json(&object[key])))
+                .collect();
+            Value::Object(sorted)
+        }
+        Value::Array(array) => Value::Array(array.iter().map(canonical_json).collect()),
+        value => value.clone(),
+    }
+}
+
+/// `sha256:<hex>` digest of the serialized [`canonical_json`] of `value`.
+pub fn canonical_digest(value: &Value) -> Result<String> {
+    let canonical = serde_json::to_vec(&canonical_json(value))?;
+    Ok(format!("sha256:{:x}", Sha256::digest(canonical)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_canonical_json() {
+        let value = json!({"b": {"d": 1, "c": [{"f": 2, "e": 3}]}, "a": null});
+        assert_eq!(
+            serde_json::to_string(&canonical_json(&value)).unwrap(),
+            r#"{"a":null,"b":{"c":[{"e":3,"f":2}],"d":1}}"#
+        );
+        assert_eq!(
+            canonical_digest(&value).unwrap(),
+            format!(
+                "sha256:{:x}",
+                Sha256::digest(br#"{"a":null,"b":{"c":[{"e":3,"f":2}],"d":1}}"#)
+            )
+        );
+    }
+}
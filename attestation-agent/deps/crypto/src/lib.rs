@@ -17,6 +17,7 @@
 //! This crate include the following public submodules:
 //! - `symmetric`: Symmetric key en/decryption
 //! - `teekey`: Asymmetric key pair used in KBS Attestation Protocol
+//! - `canonical`: Canonical JSON and digests of configurations
 
 #[macro_use]
 extern crate strum;
@@ -31,3 +32,5 @@ pub use symmetric::*;
 
 mod asymmetric;
 pub use asymmetric::*;
+
+pub mod canonical;
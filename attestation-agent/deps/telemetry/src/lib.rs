@@ -0,0 +1,228 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! OpenTelemetry tracing of the guest components.
+//!
+//! The start of a confidential pod goes from the kata-agent to CDH, which
+//! pulls the image with image-rs, which fetches resources and keys through
+//! CDH, which gets its token from AA. Each hop takes the W3C `traceparent`
+//! of the request (ttRPC or gRPC metadata) as the parent of its server
+//! span, and passes the current trace context on to the next hop, so that
+//! the spans of all the components end up in a single trace.
+//!
+//! Spans are only exported once [`init`] is given an OTLP endpoint, with
+//! the `otlp` feature. Otherwise the trace context is still passed on, so
+//! that a caller tracing the pod start sees the hops after a component
+//! that does not export its own spans.
+
+use std::{collections::HashMap, future::Future};
+
+use anyhow::Result;
+use opentelemetry::{
+    global,
+    propagation::{Extractor, Injector},
+    sdk::propagation::TraceContextPropagator,
+    trace::{SpanKind, TraceContextExt, Tracer},
+    KeyValue,
+};
+
+pub use opentelemetry::{trace::FutureExt, Context};
+
+/// Name of the tracer the spans are created with.
+const TRACER_NAME: &str = "guest-components";
+
+/// Set up the propagation of the trace context, and the export of the
+/// spans of `service_name` to the OTLP/gRPC collector at `otlp_endpoint`,
+/// e.g. `http://localhost:4317`, if any.
+pub fn init(service_name: &'static str, otlp_endpoint: Option<&str>) -> Result<()> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    #[cfg(feature = "otlp")]
+    if let Some(endpoint) = otlp_endpoint {
+        use opentelemetry::sdk::{trace, Resource};
+        use opentelemetry_otlp::WithExportConfig;
+
+        opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(
+                trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                    "service.name",
+                    service_name,
+                )])),
+            )
+            .install_batch(opentelemetry::runtime::Tokio)?;
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    if otlp_endpoint.is_some() {
+        anyhow::bail!("{service_name} is built without the `otlp` feature");
+    }
+
+    Ok(())
+}
+
+/// Export the spans not exported yet, before the process exits.
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+/// Run `fut` in a span `name`, child of the current span if any.
+pub async fn in_span<F: Future>(name: &'static str, fut: F) -> F::Output {
+    in_span_with(name, [], fut).await
+}
+
+/// Run `fut` in a span `name` with `attributes`, child of the current span
+/// if any.
+pub async fn in_span_with<F: Future>(
+    name: &'static str,
+    attributes: impl IntoIterator<Item = (&'static str, String)>,
+    fut: F,
+) -> F::Output {
+    let tracer = global::tracer(TRACER_NAME);
+    let parent = Context::current();
+    let span = tracer
+        .span_builder(name)
+        .with_attributes(attributes.into_iter().map(|(k, v)| KeyValue::new(k, v)))
+        .start_with_context(&tracer, &parent);
+    fut.with_context(parent.with_span(span)).await
+}
+
+// start the server span of the request `name` traced by `parent`; the span
+// ends once the returned context and its clones are dropped
+fn server_span(parent: Context, name: &'static str) -> Context {
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer
+        .span_builder(name)
+        .with_kind(SpanKind::Server)
+        .start_with_context(&tracer, &parent);
+    parent.with_span(span)
+}
+
+struct TtrpcMetadata<'a>(&'a HashMap<String, Vec<String>>);
+
+impl Extractor for TtrpcMetadata<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key)?.first().map(String::as_str)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(String::as_str).collect()
+    }
+}
+
+struct TtrpcMetadataMut<'a>(&'a mut HashMap<String, Vec<String>>);
+
+impl Injector for TtrpcMetadataMut<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        self.0.insert(key.to_string(), vec![value]);
+    }
+}
+
+/// Start the server span of the ttRPC request `name` with `metadata`. Run
+/// the handling of the request with the returned context, see
+/// [`FutureExt::with_context`], for its spans and the requests it makes to
+/// be part of the trace. The span ends once the context is dropped.
+pub fn ttrpc_server_span(metadata: &HashMap<String, Vec<String>>, name: &'static str) -> Context {
+    let parent = global::get_text_map_propagator(|p| p.extract(&TtrpcMetadata(metadata)));
+    server_span(parent, name)
+}
+
+/// Add the current trace context to the `metadata` of a ttRPC request.
+pub fn inject_ttrpc(metadata: &mut HashMap<String, Vec<String>>) {
+    global::get_text_map_propagator(|p| {
+        p.inject_context(&Context::current(), &mut TtrpcMetadataMut(metadata))
+    });
+}
+
+#[cfg(feature = "grpc")]
+struct GrpcMetadata<'a>(&'a tonic::metadata::MetadataMap);
+
+#[cfg(feature = "grpc")]
+impl Extractor for GrpcMetadata<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key)?.to_str().ok()
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0
+            .keys()
+            .filter_map(|key| match key {
+                tonic::metadata::KeyRef::Ascii(key) => Some(key.as_str()),
+                tonic::metadata::KeyRef::Binary(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Start the server span of the gRPC `request` of `name`, as
+/// [`ttrpc_server_span`] does.
+#[cfg(feature = "grpc")]
+pub fn grpc_server_span<T>(request: &tonic::Request<T>, name: &'static str) -> Context {
+    let parent = global::get_text_map_propagator(|p| p.extract(&GrpcMetadata(request.metadata())));
+    server_span(parent, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[tokio::test]
+    async fn test_propagation() {
+        init("telemetry-test", None).unwrap();
+
+        let metadata = HashMap::from([("traceparent".to_string(), vec![TRACEPARENT.to_string()])]);
+        let cx = ttrpc_server_span(&metadata, "Test");
+        let trace_id = cx.span().span_context().trace_id();
+        assert_eq!(
+            trace_id,
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap()
+        );
+
+        // the nested spans and the requests they make stay in the trace
+        let mut outgoing = HashMap::new();
+        in_span("nested", async {
+            assert_eq!(
+                Context::current().span().span_context().trace_id(),
+                trace_id
+            );
+            inject_ttrpc(&mut outgoing);
+        })
+        .with_context(cx)
+        .await;
+        assert!(outgoing["traceparent"][0].starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+
+        // no trace context, nothing to pass on
+        let mut outgoing = HashMap::new();
+        inject_ttrpc(&mut outgoing);
+        assert!(outgoing.is_empty());
+    }
+
+    #[test]
+    fn test_ttrpc_metadata() {
+        init("telemetry-test", None).unwrap();
+
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let cx = Context::new().with_remote_span_context(span_context);
+        let mut metadata = HashMap::new();
+        global::get_text_map_propagator(|p| {
+            p.inject_context(&cx, &mut TtrpcMetadataMut(&mut metadata))
+        });
+        assert_eq!(metadata["traceparent"], [TRACEPARENT]);
+    }
+}
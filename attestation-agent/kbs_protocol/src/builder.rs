@@ -9,10 +9,12 @@ use std::time::Duration;
 use anyhow::*;
 
 use crate::{
-    client::ClientTee,
+    claims::Claims,
+    client::{ClientTee, KBS_PROTOCOL_VERSION},
     evidence_provider::EvidenceProvider,
     keypair::TeeKeyPair,
     token_provider::{Token, TokenProvider},
+    version,
 };
 
 use super::client::KbsClient;
@@ -25,6 +27,8 @@ pub struct KbsClientBuilder<T> {
     kbs_host_url: String,
     token: Option<String>,
     tee_key: Option<String>,
+    claims: Claims,
+    protocol_versions: Vec<String>,
 }
 
 impl KbsClientBuilder<Box<dyn EvidenceProvider>> {
@@ -38,8 +42,31 @@ impl KbsClientBuilder<Box<dyn EvidenceProvider>> {
             kbs_host_url: kbs_host_url.trim_end_matches('/').to_string(),
             token: None,
             tee_key: None,
+            claims: Claims::new(),
+            protocol_versions: vec![KBS_PROTOCOL_VERSION.to_string()],
         }
     }
+
+    /// Add a claim of the workload to the attestation, see
+    /// [`crate::claims`].
+    pub fn add_claim(mut self, name: &str, value: &str) -> Self {
+        self.claims.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Replace the claims of the workload added so far.
+    pub fn set_claims(mut self, claims: Claims) -> Self {
+        self.claims = claims;
+        self
+    }
+
+    /// Set the KBS protocol versions to negotiate with the KBS, see
+    /// [`crate::version`]. Only [`KBS_PROTOCOL_VERSION`] is supported by
+    /// default.
+    pub fn set_protocol_versions(mut self, versions: &[&str]) -> Self {
+        self.protocol_versions = versions.iter().map(|v| v.to_string()).collect();
+        self
+    }
 }
 
 impl KbsClientBuilder<Box<dyn TokenProvider>> {
@@ -50,6 +77,8 @@ impl KbsClientBuilder<Box<dyn TokenProvider>> {
             kbs_host_url: kbs_host_url.trim_end_matches('/').to_string(),
             token: None,
             tee_key: None,
+            claims: Claims::new(),
+            protocol_versions: vec![KBS_PROTOCOL_VERSION.to_string()],
         }
     }
 }
@@ -99,6 +128,9 @@ impl<T> KbsClientBuilder<T> {
             None => None,
         };
 
+        let protocol_versions =
+            version::sort(&self.protocol_versions).context("KBS protocol versions")?;
+
         let client = KbsClient {
             _tee: ClientTee::Unitialized,
             tee_key,
@@ -108,6 +140,9 @@ impl<T> KbsClientBuilder<T> {
                 .build()
                 .context("Build KBS http client")?,
             kbs_host_url: self.kbs_host_url,
+            claims: self.claims,
+            protocol_versions,
+            protocol_version: None,
         };
 
         Ok(client)
@@ -145,4 +180,24 @@ x13TMfDeczAFBgMrZXADQQBpP6ABBkzVj3mF55nWUtP5vxwq3t91wqQJ6NyC7WsT
         .build()
         .expect("build client failed");
     }
+
+    #[test]
+    fn test_build_client_protocol_versions() {
+        let client = KbsClientBuilder::with_evidence_provider(
+            Box::<MockedEvidenceProvider>::default(),
+            "test.io",
+        )
+        .set_protocol_versions(&["0.1.0", "0.2.0"])
+        .build()
+        .expect("build client failed");
+        assert_eq!(client.protocol_versions, ["0.2.0", "0.1.0"]);
+
+        assert!(KbsClientBuilder::with_evidence_provider(
+            Box::<MockedEvidenceProvider>::default(),
+            "test.io",
+        )
+        .set_protocol_versions(&[])
+        .build()
+        .is_err());
+    }
 }
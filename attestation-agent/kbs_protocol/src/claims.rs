@@ -0,0 +1,102 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! # Workload Claims
+//!
+//! Guest components can add claims about the workload to the attestation,
+//! e.g. the digests of the pulled images or the hash of the policy in use,
+//! so that the KBS can condition key release on the workload identity.
+//!
+//! The claims are sent in the `extra-params` of the auth request as
+//! `{"claims": {...}}`, and embedded in the runtime data the evidence is
+//! bound to:
+//!
+//! ```json
+//! {"claims":{"<name>":"<value>",...},"nonce":"...","tee-pubkey":{...}}
+//! ```
+//!
+//! Claims are kept sorted by name, so their serialization is stable.
+//! Without claims, the runtime data is the same as before.
+
+use std::collections::BTreeMap;
+
+use kbs_types::TeePubKey;
+use serde_json::json;
+
+/// Claims of the workload, by name.
+pub type Claims = BTreeMap<String, String>;
+
+/// Runtime data of the RCAR handshake, which the evidence is bound to.
+pub fn runtime_data(
+    tee_pubkey: &TeePubKey,
+    nonce: &str,
+    claims: &Claims,
+) -> serde_json::Result<String> {
+    let runtime_data = match claims.is_empty() {
+        true => json!({
+            "tee-pubkey": tee_pubkey,
+            "nonce": nonce,
+        }),
+        false => json!({
+            "tee-pubkey": tee_pubkey,
+            "nonce": nonce,
+            "claims": claims,
+        }),
+    };
+
+    serde_json::to_string(&runtime_data)
+}
+
+/// `extra-params` of the auth request.
+pub fn extra_params(claims: &Claims) -> serde_json::Result<String> {
+    if claims.is_empty() {
+        return Ok(String::new());
+    }
+
+    serde_json::to_string(&json!({ "claims": claims }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tee_pubkey() -> TeePubKey {
+        TeePubKey {
+            kty: "RSA".into(),
+            alg: "RSA1_5".into(),
+            k_mod: "AQAB".into(),
+            k_exp: "AQAB".into(),
+        }
+    }
+
+    #[test]
+    fn test_runtime_data() {
+        let claims = Claims::from([
+            ("policy".to_string(), "sha256:2".to_string()),
+            ("image".to_string(), "sha256:1".to_string()),
+        ]);
+
+        let with_claims = runtime_data(&tee_pubkey(), "nonce", &claims).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&with_claims).unwrap();
+        assert_eq!(parsed["claims"]["image"], "sha256:1");
+        assert!(with_claims.contains(r#""claims":{"image":"sha256:1","policy":"sha256:2"}"#));
+
+        let without_claims = runtime_data(&tee_pubkey(), "nonce", &Claims::new()).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&without_claims).unwrap();
+        assert!(parsed.get("claims").is_none());
+        assert_eq!(parsed["nonce"], "nonce");
+    }
+
+    #[test]
+    fn test_extra_params() {
+        assert_eq!(extra_params(&Claims::new()).unwrap(), "");
+
+        let claims = Claims::from([("image".to_string(), "sha256:1".to_string())]);
+        assert_eq!(
+            extra_params(&claims).unwrap(),
+            r#"{"claims":{"image":"sha256:1"}}"#
+        );
+    }
+}
@@ -21,7 +21,7 @@ pub mod token_client;
 
 use kbs_types::Tee;
 
-use crate::{keypair::TeeKeyPair, token_provider::Token};
+use crate::{claims::Claims, keypair::TeeKeyPair, token_provider::Token};
 
 pub(crate) enum ClientTee {
     Unitialized,
@@ -46,6 +46,19 @@ pub struct KbsClient<T> {
 
     /// token
     pub(crate) token: Option<Token>,
+
+    /// Claims of the workload bound to the attestation
+    #[cfg_attr(not(feature = "background_check"), allow(dead_code))]
+    pub(crate) claims: Claims,
+
+    /// KBS protocol versions supported, highest first, see
+    /// [`crate::version`]
+    #[cfg_attr(not(feature = "background_check"), allow(dead_code))]
+    pub(crate) protocol_versions: Vec<String>,
+
+    /// KBS protocol version negotiated in the last RCAR handshake
+    #[cfg_attr(not(feature = "background_check"), allow(dead_code))]
+    pub(crate) protocol_version: Option<String>,
 }
 
 pub const KBS_PROTOCOL_VERSION: &str = "0.1.0";
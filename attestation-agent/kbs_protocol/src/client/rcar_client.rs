@@ -11,18 +11,16 @@ use kbs_types::{Attestation, Challenge, ErrorInformation, Request, Response};
 use log::{debug, warn};
 use resource_uri::ResourceUri;
 use serde::Deserialize;
-use serde_json::json;
 use sha2::{Digest, Sha384};
 
 use crate::{
     api::KbsClientCapabilities,
-    client::{
-        ClientTee, KbsClient, KBS_GET_RESOURCE_MAX_ATTEMPT, KBS_PREFIX, KBS_PROTOCOL_VERSION,
-    },
+    claims,
+    client::{ClientTee, KbsClient, KBS_GET_RESOURCE_MAX_ATTEMPT, KBS_PREFIX},
     evidence_provider::EvidenceProvider,
     keypair::TeeKeyPair,
     token_provider::Token,
-    Error, Result,
+    version, Error, Result,
 };
 
 /// When executing get token, RCAR handshake should retry if failed to
@@ -114,32 +112,67 @@ impl KbsClient<Box<dyn EvidenceProvider>> {
             ClientTee::_Initializated(tee) => *tee,
         };
 
-        let request = Request {
-            version: String::from(KBS_PROTOCOL_VERSION),
-            tee,
-            extra_params: String::new(),
+        // The version negotiated once is the only one requested again.
+        let advertised = match &self.protocol_version {
+            Some(pinned) => vec![pinned.clone()],
+            None => self.protocol_versions.clone(),
         };
 
-        debug!("send auth request to {auth_endpoint}");
+        let extra_params = version::extra_params(
+            claims::extra_params(&self.claims).context("serialize extra params failed")?,
+            &advertised,
+        )
+        .context("serialize extra params failed")?;
 
-        let challenge = self
-            .http_client
-            .post(auth_endpoint)
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?
-            .json::<Challenge>()
-            .await?;
+        // Ask again with the next lower version if the KBS rejects the
+        // requested one.
+        let mut versions = advertised.iter().peekable();
+        let (challenge, protocol_version) = loop {
+            let Some(requested) = versions.next() else {
+                bail!("KBS supports none of the protocol versions {advertised:?}");
+            };
+
+            let request = Request {
+                version: requested.clone(),
+                tee,
+                extra_params: extra_params.clone(),
+            };
+
+            debug!("send auth request to {auth_endpoint} with protocol version {requested}");
+
+            let auth_response = self
+                .http_client
+                .post(&auth_endpoint)
+                .header("Content-Type", "application/json")
+                .json(&request)
+                .send()
+                .await?;
+
+            if auth_response.status().is_success() {
+                let challenge = auth_response.json::<Challenge>().await?;
+                let negotiated =
+                    version::negotiated(&advertised, requested, &challenge.extra_params)?;
+                break (challenge, negotiated);
+            }
+
+            let status = auth_response.status();
+            let body = auth_response.text().await?;
+            let Ok(error_info) = serde_json::from_str::<ErrorInformation>(&body) else {
+                bail!("KBS auth failed ({status}), Response: {body:?}");
+            };
+            if version::is_version_error(&error_info) && versions.peek().is_some() {
+                warn!("KBS rejected protocol version {requested}: {error_info:?}");
+                continue;
+            }
 
-        debug!("get challenge: {challenge:#?}");
+            bail!("KBS auth failed ({status}), Error Info: {:?}", error_info);
+        };
+
+        debug!("get challenge with protocol version {protocol_version}: {challenge:#?}");
+        self.protocol_version = Some(protocol_version);
         let tee_pubkey = self.tee_key.export_pubkey()?;
-        let runtime_data = json!({
-            "tee-pubkey": tee_pubkey,
-            "nonce": challenge.nonce,
-        });
-        let runtime_data =
-            serde_json::to_string(&runtime_data).context("serialize runtime data failed")?;
+        let runtime_data = claims::runtime_data(&tee_pubkey, &challenge.nonce, &self.claims)
+            .context("serialize runtime data failed")?;
         let evidence = self.generate_evidence(runtime_data).await?;
         debug!("get evidence with challenge: {evidence}");
 
@@ -165,12 +198,15 @@ impl KbsClient<Box<dyn EvidenceProvider>> {
                 self.token = Some(token);
             }
             reqwest::StatusCode::UNAUTHORIZED => {
-                let error_info = attest_response.json::<ErrorInformation>().await?;
+                let body = attest_response.text().await?;
+                let Ok(error_info) = serde_json::from_str::<ErrorInformation>(&body) else {
+                    bail!("KBS attest unauthorized, Response: {body:?}");
+                };
                 bail!("KBS attest unauthorized, Error Info: {:?}", error_info);
             }
-            _ => {
+            status => {
                 bail!(
-                    "KBS Server Internal Failed, Response: {:?}",
+                    "KBS Server Internal Failed ({status}), Response: {:?}",
                     attest_response.text().await?
                 );
             }
@@ -179,6 +215,12 @@ impl KbsClient<Box<dyn EvidenceProvider>> {
         Ok(())
     }
 
+    /// The KBS protocol version negotiated in the last RCAR handshake, if
+    /// any.
+    pub fn protocol_version(&self) -> Option<&str> {
+        self.protocol_version.as_deref()
+    }
+
     async fn generate_evidence(&self, runtime_data: String) -> Result<String> {
         let mut hasher = Sha384::new();
         hasher.update(runtime_data);
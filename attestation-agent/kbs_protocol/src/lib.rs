@@ -72,14 +72,17 @@
 
 pub mod api;
 pub mod builder;
+pub mod claims;
 pub mod client;
 pub mod error;
 pub mod evidence_provider;
 pub mod keypair;
 pub mod token_provider;
+pub mod version;
 
 pub use api::*;
 pub use builder::KbsClientBuilder;
+pub use claims::Claims;
 pub use error::{Error, Result};
 pub use keypair::TeeKeyPair;
 pub use token_provider::Token;
@@ -15,7 +15,8 @@ use ttrpc::context;
 use crate::{Error, Result, TeeKeyPair, Token};
 
 use self::{
-    attestation_agent::GetTokenRequest, attestation_agent_ttrpc::AttestationAgentServiceClient,
+    attestation_agent::{ExtendRuntimeMeasurementRequest, GetEvidenceRequest, GetTokenRequest},
+    attestation_agent_ttrpc::AttestationAgentServiceClient,
 };
 
 use super::TokenProvider;
@@ -42,6 +43,43 @@ impl AATokenProvider {
         let client = AttestationAgentServiceClient::new(c);
         Ok(Self { client })
     }
+
+    /// Get the evidence of the TEE from the attestation-agent, with
+    /// `runtime_data` bound into it.
+    pub async fn get_evidence(&self, runtime_data: Vec<u8>) -> Result<Vec<u8>> {
+        let req = GetEvidenceRequest {
+            RuntimeData: runtime_data,
+            ..Default::default()
+        };
+        let res = self
+            .client
+            .get_evidence(request_context(), &req)
+            .await
+            .map_err(|e| Error::AATokenProvider(format!("call ttrpc failed: {e}")))?;
+        Ok(res.Evidence)
+    }
+
+    /// Extend `events` into the runtime measurement register of the TEE
+    /// through the attestation-agent.
+    pub async fn extend_runtime_measurement(&self, events: Vec<Vec<u8>>) -> Result<()> {
+        let req = ExtendRuntimeMeasurementRequest {
+            Events: events,
+            ..Default::default()
+        };
+        self.client
+            .extend_runtime_measurement(request_context(), &req)
+            .await
+            .map_err(|e| Error::AATokenProvider(format!("call ttrpc failed: {e}")))?;
+        Ok(())
+    }
+}
+
+// context of a request to the attestation-agent, carrying the current
+// trace context
+fn request_context() -> context::Context {
+    let mut ctx = context::with_timeout(50 * 1000 * 1000 * 1000);
+    telemetry::inject_ttrpc(&mut ctx.metadata);
+    ctx
 }
 
 #[async_trait]
@@ -53,16 +91,22 @@ impl TokenProvider for AATokenProvider {
         };
         let bytes = self
             .client
-            .get_token(context::with_timeout(50 * 1000 * 1000 * 1000), &req)
+            .get_token(request_context(), &req)
             .await
             .map_err(|e| Error::AATokenProvider(format!("cal ttrpc failed: {e}")))?;
-        let message: Message = serde_json::from_slice(&bytes.Token).map_err(|e| {
-            Error::AATokenProvider(format!("deserialize attestation-agent reply failed: {e}"))
-        })?;
-        let token = Token::new(message.token)
-            .map_err(|e| Error::AATokenProvider(format!("deserialize token failed: {e}")))?;
-        let tee_keypair = TeeKeyPair::from_pkcs1_pem(&message.tee_keypair)
-            .map_err(|e| Error::AATokenProvider(format!("deserialize tee keypair failed: {e}")))?;
-        Ok((token, tee_keypair))
+        parse_token_reply(&bytes.Token)
     }
 }
+
+/// Parse the reply of the attestation-agent to a `GetToken` request of the
+/// `kbs` type into the token and the TEE key pair it is bound to.
+pub fn parse_token_reply(reply: &[u8]) -> Result<(Token, TeeKeyPair)> {
+    let message: Message = serde_json::from_slice(reply).map_err(|e| {
+        Error::AATokenProvider(format!("deserialize attestation-agent reply failed: {e}"))
+    })?;
+    let token = Token::new(message.token)
+        .map_err(|e| Error::AATokenProvider(format!("deserialize token failed: {e}")))?;
+    let tee_keypair = TeeKeyPair::from_pkcs1_pem(&message.tee_keypair)
+        .map_err(|e| Error::AATokenProvider(format!("deserialize tee keypair failed: {e}")))?;
+    Ok((token, tee_keypair))
+}
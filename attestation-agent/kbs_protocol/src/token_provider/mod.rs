@@ -35,6 +35,7 @@ pub struct Token {
     pub content: String,
     exp: Option<UnixTimeStamp>,
     nbf: Option<UnixTimeStamp>,
+    policies: Vec<String>,
 }
 
 impl Token {
@@ -45,13 +46,29 @@ impl Token {
             .ok_or_else(|| anyhow!("illegal token format"))?;
         let claims = URL_SAFE_NO_PAD.decode(claims_b64)?;
         let claims = serde_json::from_slice::<JWTClaims<Value>>(&claims)?;
+        let mut policies = Vec::new();
+        collect_policies("", &claims.custom, &mut policies);
+        policies.sort();
+        policies.dedup();
         Ok(Self {
             content: token,
             exp: claims.expires_at,
             nbf: claims.invalid_before,
+            policies,
         })
     }
 
+    /// The policies the token was appraised with, as `name=value` of the
+    /// claims whose names hold `policy`, e.g. `ear.appraisal-policy-id`.
+    pub fn policies(&self) -> &[String] {
+        &self.policies
+    }
+
+    /// Seconds since the epoch the token expires at, if it does.
+    pub fn expires_at(&self) -> Option<u64> {
+        self.exp.map(|exp| exp.as_secs())
+    }
+
     pub fn check_valid(&self) -> Result<()> {
         let now = Clock::now_since_epoch();
         if let Some(exp) = self.exp {
@@ -69,3 +86,59 @@ impl Token {
         Ok(())
     }
 }
+
+fn collect_policies(name: &str, value: &Value, policies: &mut Vec<String>) {
+    match value {
+        Value::Object(claims) => {
+            for (name, value) in claims {
+                collect_policies(name, value, policies);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                collect_policies(name, value, policies);
+            }
+        }
+        Value::String(value) if name.contains("policy") => {
+            policies.push(format!("{name}={value}"));
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use serde_json::json;
+
+    use super::Token;
+
+    #[test]
+    fn test_policies() {
+        let claims = json!({
+            "exp": 1700000000,
+            "ear.appraisal-policy-id": "default",
+            "submods": {
+                "cpu": {
+                    "ear.status": "affirming",
+                    "policy-ids": ["a", "b"],
+                },
+            },
+            "evaluation-reports": [{ "policy-id": "a", "policy-hash": "00" }],
+        });
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let token = Token::new(format!("e30.{claims}.sig")).unwrap();
+
+        assert_eq!(
+            token.policies(),
+            [
+                "ear.appraisal-policy-id=default",
+                "policy-hash=00",
+                "policy-id=a",
+                "policy-ids=a",
+                "policy-ids=b",
+            ]
+        );
+        assert_eq!(token.expires_at(), Some(1700000000));
+    }
+}
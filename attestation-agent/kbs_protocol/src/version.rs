@@ -0,0 +1,154 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! # Protocol Version Negotiation
+//!
+//! The client puts the highest KBS protocol version it supports in the
+//! `version` of the auth request, which is all a KBS not negotiating looks
+//! at. If it supports more than one version, they are all advertised in the
+//! `extra-params` of the auth request, highest first:
+//!
+//! ```json
+//! {"supported-versions":["0.2.0","0.1.0"]}
+//! ```
+//!
+//! A negotiating KBS picks the highest version both sides support, and
+//! tells it in the `extra-params` of the challenge:
+//!
+//! ```json
+//! {"version":"0.1.0"}
+//! ```
+//!
+//! A KBS rejecting the `version` of the auth request with a
+//! `ProtocolVersion` error is asked again with the next lower version. The
+//! KBS may not pick a version higher than the one requested, nor one not
+//! advertised.
+//!
+//! Once negotiated, the version is pinned for the client: later handshakes,
+//! e.g. to refresh the token, request only that version and fail rather than
+//! fall back to a lower one.
+
+use std::cmp::Ordering;
+
+use anyhow::{anyhow, bail, Result};
+use kbs_types::ErrorInformation;
+use serde_json::{Map, Value};
+
+/// Suffix of the type of the error a KBS rejects an unsupported protocol
+/// version with.
+const ERR_PROTOCOL_VERSION: &str = "ProtocolVersion";
+
+fn parse(version: &str) -> Result<Vec<u64>> {
+    version
+        .split('.')
+        .map(|n| n.parse::<u64>())
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|_| anyhow!("invalid KBS protocol version {version:?}"))
+}
+
+fn compare(a: &[u64], b: &[u64]) -> Ordering {
+    let len = a.len().max(b.len());
+    let at = |v: &[u64], i: usize| v.get(i).copied().unwrap_or_default();
+    (0..len)
+        .map(|i| at(a, i).cmp(&at(b, i)))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Sort the supported `versions` highest first, dropping duplicates.
+pub fn sort(versions: &[String]) -> Result<Vec<String>> {
+    if versions.is_empty() {
+        bail!("no KBS protocol version supported");
+    }
+
+    let mut parsed = versions
+        .iter()
+        .map(|v| Ok((parse(v)?, v.clone())))
+        .collect::<Result<Vec<_>>>()?;
+    parsed.sort_by(|(a, _), (b, _)| compare(b, a));
+    parsed.dedup_by(|(a, _), (b, _)| compare(a, b).is_eq());
+
+    Ok(parsed.into_iter().map(|(_, v)| v).collect())
+}
+
+/// Add the `advertised` versions to the `extra-params` of the auth request,
+/// if there is more than one.
+pub fn extra_params(extra_params: String, advertised: &[String]) -> Result<String> {
+    if advertised.len() <= 1 {
+        return Ok(extra_params);
+    }
+
+    let mut params = if extra_params.is_empty() {
+        Map::new()
+    } else {
+        serde_json::from_str(&extra_params)?
+    };
+    params.insert("supported-versions".into(), advertised.into());
+
+    Ok(Value::Object(params).to_string())
+}
+
+/// Get the version picked by the KBS from the `extra-params` of the
+/// challenge, which is `requested` if the KBS did not negotiate.
+pub fn negotiated(advertised: &[String], requested: &str, extra_params: &str) -> Result<String> {
+    let Ok(Value::Object(params)) = serde_json::from_str::<Value>(extra_params) else {
+        return Ok(requested.to_string());
+    };
+
+    let version = match params.get("version") {
+        None => return Ok(requested.to_string()),
+        Some(Value::String(version)) if advertised.contains(version) => version,
+        Some(version) => bail!("KBS picked unsupported protocol version {version}"),
+    };
+
+    if compare(&parse(version)?, &parse(requested)?).is_gt() {
+        bail!("KBS picked protocol version {version} higher than the requested {requested}");
+    }
+
+    Ok(version.clone())
+}
+
+/// Whether the KBS rejected the requested protocol version.
+pub fn is_version_error(error: &ErrorInformation) -> bool {
+    error.error_type.ends_with(ERR_PROTOCOL_VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(v: &[&str]) -> Vec<String> {
+        v.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_negotiation() {
+        let supported = sort(&versions(&["0.1.0", "0.10.0", "0.2", "0.1.0"])).unwrap();
+        assert_eq!(supported, versions(&["0.10.0", "0.2", "0.1.0"]));
+        assert!(sort(&[]).is_err());
+        assert!(sort(&versions(&["0.1.x"])).is_err());
+
+        // a single version keeps the auth request as before
+        assert_eq!(extra_params("".into(), &supported[2..]).unwrap(), "");
+        let params = extra_params(r#"{"claims":{"a":"b"}}"#.into(), &supported).unwrap();
+        let params: Value = serde_json::from_str(&params).unwrap();
+        assert_eq!(params["claims"]["a"], "b");
+        assert_eq!(params["supported-versions"][0], "0.10.0");
+
+        assert_eq!(negotiated(&supported, "0.10.0", "").unwrap(), "0.10.0");
+        assert_eq!(
+            negotiated(&supported, "0.10.0", r#"{"version":"0.2"}"#).unwrap(),
+            "0.2"
+        );
+        assert!(negotiated(&supported, "0.10.0", r#"{"version":"1.0.0"}"#).is_err());
+        assert!(negotiated(&supported, "0.2", r#"{"version":"0.10.0"}"#).is_err());
+
+        assert!(is_version_error(&ErrorInformation {
+            error_type: "https://github.com/confidential-containers/kbs/errors/ProtocolVersion"
+                .into(),
+            detail: "".into(),
+        }));
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Audit log of the evidence generation and the token and key release
+//! requests handled by AA, so operators can reconstruct which secrets a
+//! guest asked for, e.g. when it is suspected to be compromised.
+//!
+//! The log can only be appended to, and every entry carries the hash of the
+//! previous one, so removing or rewriting an entry breaks the chain. The
+//! SHA-256 `hash` of an entry is taken over the JSON of the entry without
+//! the `hash` field, and the `prev_hash` of the first entry is all zeroes.
+//!
+//! The chain alone can be recomputed by whoever rewrites the log, so the
+//! head is extended into the runtime measurement register whenever the log
+//! is exported, as the event of [`AuditLog::anchor_event`]. An exported log
+//! is trusted up to the last anchor the evidence of the guest holds.
+//!
+//! Only the last [`MAX_ENTRIES`] entries are kept. The first entry kept
+//! then chains to the `hash` of the entry dropped before it.
+
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// `prev_hash` of the first entry.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Max number of entries kept, the oldest ones are dropped beyond.
+pub const MAX_ENTRIES: usize = 4096;
+
+/// Prefix of the runtime measurement event anchoring the log.
+pub const AUDIT_EVENT_PREFIX: &str = "audit_log:";
+
+/// A request handled by AA.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum Operation {
+    /// Evidence generated for the runtime data with the given SHA-256 digest.
+    GetEvidence { runtime_data_digest: String },
+
+    /// Evidence generated for the nonce of an external verifier, and the
+    /// runtime data with the given SHA-256 digest.
+    GetChallengeEvidence {
+        nonce: String,
+        runtime_data_digest: String,
+    },
+
+    /// Attestation token of the given type.
+    GetToken { token_type: String },
+
+    /// Confidential resource got through a KBC.
+    GetResource { kbc: String, resource: String },
+
+    /// Image layer annotation decrypted through a KBC, with the id of the key.
+    DecryptAnnotation { kbc: String, kid: String },
+
+    /// Pre-attested file loaded, with its SHA-256 digest, see
+    /// [`crate::offline`].
+    LoadPreAttested { path: String, digest: String },
+
+    /// Effective configuration of a component measured for a caller, see
+    /// [`crate::config_measurement`].
+    MeasureConfig {
+        component: String,
+        digest: String,
+        caller: String,
+    },
+
+    /// Config of AA reloaded, with the digest of the config, see
+    /// [`crate::config`].
+    ReloadConfig { digest: String },
+
+    /// IMA policy set from the init-data entry, see [`crate::ima`].
+    SetImaPolicy { entry: String },
+
+    /// Key derived from the sealing key of the TEE for the purpose, see
+    /// [`crate::derived_key`].
+    DeriveKey { purpose: String, per_boot: bool },
+
+    /// Call of an API by a caller of the AA service, recorded when the call
+    /// is rejected by its access control.
+    Call { method: String, caller: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct Body {
+    index: u64,
+    /// Seconds since the UNIX epoch.
+    timestamp: u64,
+    #[serde(flatten)]
+    operation: Operation,
+    /// Why the request failed, `None` if it succeeded.
+    error: Option<String>,
+    prev_hash: String,
+}
+
+impl Body {
+    fn hash(&self) -> Result<String> {
+        let body = serde_json::to_vec(self)?;
+        Ok(hex::encode(Sha256::digest(body)))
+    }
+}
+
+/// An entry of the [`AuditLog`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    #[serde(flatten)]
+    body: Body,
+    hash: String,
+}
+
+impl Entry {
+    pub fn operation(&self) -> &Operation {
+        &self.body.operation
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.body.error.as_deref()
+    }
+
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+}
+
+/// Hash-chained log of the requests handled by AA.
+#[derive(Default, Debug)]
+pub struct AuditLog {
+    entries: VecDeque<Entry>,
+    next_index: u64,
+    head: Option<String>,
+}
+
+impl AuditLog {
+    /// Append an entry for `operation`, with the outcome of the request.
+    pub fn record<T>(&mut self, operation: Operation, result: &Result<T>) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let body = Body {
+            index: self.next_index,
+            timestamp,
+            operation,
+            error: result.as_ref().err().map(|e| format!("{e:#}")),
+            prev_hash: self.head().to_string(),
+        };
+
+        // Serializing the body cannot fail, as it only holds strings and integers.
+        let hash = body.hash().expect("serialize audit log entry");
+        self.head = Some(hash.clone());
+        self.next_index += 1;
+        self.entries.push_back(Entry { body, hash });
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Hash of the last entry, which the whole log can be checked against.
+    pub fn head(&self) -> &str {
+        self.head.as_deref().unwrap_or(GENESIS_HASH)
+    }
+
+    pub fn entries(&self) -> Vec<Entry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    /// Runtime measurement event binding the log as it is now: the number of
+    /// entries recorded so far and the head.
+    pub fn anchor_event(&self) -> Vec<u8> {
+        format!("{AUDIT_EVENT_PREFIX}{}:{}", self.next_index, self.head()).into_bytes()
+    }
+
+    /// Export the log as a JSON array of entries.
+    pub fn export(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&self.entries)?)
+    }
+}
+
+/// Check that `entries` form an unbroken hash chain starting from the first
+/// entry, and return the hash of the last one. The first entry chains to
+/// [`GENESIS_HASH`], unless the entries before it were dropped.
+pub fn verify(entries: &[Entry]) -> Result<String> {
+    let (first_index, mut prev_hash) = match entries.first() {
+        Some(first) if first.body.index > 0 => (first.body.index, first.body.prev_hash.clone()),
+        _ => (0, GENESIS_HASH.to_string()),
+    };
+    for (i, entry) in entries.iter().enumerate() {
+        let index = first_index + i as u64;
+        if entry.body.index != index {
+            bail!("audit log entry {index} has index {}", entry.body.index);
+        }
+
+        if entry.body.prev_hash != prev_hash {
+            bail!("audit log entry {index} does not chain to the previous entry");
+        }
+
+        if entry.body.hash()? != entry.hash {
+            bail!("audit log entry {index} has been tampered with");
+        }
+
+        prev_hash = entry.hash.clone();
+    }
+
+    Ok(prev_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_audit_log() {
+        let mut log = AuditLog::default();
+        assert_eq!(log.head(), GENESIS_HASH);
+
+        log.record(
+            Operation::GetEvidence {
+                runtime_data_digest: hex::encode(Sha256::digest(b"nonce")),
+            },
+            &Ok(()),
+        );
+        log.record(
+            Operation::GetResource {
+                kbc: "cc_kbc".into(),
+                resource: "kbs:///default/key/1".into(),
+            },
+            &Err::<(), _>(anyhow!("unauthorized")),
+        );
+        log.record(
+            Operation::GetToken {
+                token_type: "kbs".into(),
+            },
+            &Ok(()),
+        );
+        assert_eq!(log.entries().len(), 3);
+        assert_eq!(log.entries()[1].error(), Some("unauthorized"));
+
+        let entries: Vec<Entry> = serde_json::from_slice(&log.export().unwrap()).unwrap();
+        assert_eq!(entries, log.entries());
+        assert_eq!(verify(&entries).unwrap(), log.head());
+
+        let exported: serde_json::Value = serde_json::from_slice(&log.export().unwrap()).unwrap();
+        assert_eq!(exported[1]["operation"], "get_resource");
+        assert_eq!(exported[1]["resource"], "kbs:///default/key/1");
+        assert_eq!(exported[1]["prev_hash"], exported[0]["hash"]);
+
+        // rewriting an entry
+        let mut tampered = entries.clone();
+        tampered[1].body.error = None;
+        assert!(verify(&tampered).is_err());
+
+        // dropping an entry
+        let mut dropped = entries.clone();
+        dropped.remove(1);
+        assert!(verify(&dropped).is_err());
+
+        // truncating the log keeps a valid chain, but not the same head
+        assert_ne!(verify(&entries[..2]).unwrap(), log.head());
+
+        assert_eq!(
+            String::from_utf8(log.anchor_event()).unwrap(),
+            format!("audit_log:3:{}", log.head())
+        );
+    }
+
+    #[test]
+    fn test_audit_log_rotation() {
+        let mut log = AuditLog::default();
+        for i in 0..MAX_ENTRIES + 10 {
+            log.record(
+                Operation::GetToken {
+                    token_type: i.to_string(),
+                },
+                &Ok(()),
+            );
+        }
+
+        let entries = log.entries();
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries[0].body.index, 10);
+        assert_eq!(verify(&entries).unwrap(), log.head());
+
+        // dropping an entry past the first one kept
+        let mut dropped = entries.clone();
+        dropped.remove(1);
+        assert!(verify(&dropped).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Evidence for external verifiers.
+//!
+//! A relying party not using a KBS can attest the guest in the background
+//! check model: it sends a fresh nonce, together with the runtime data it
+//! wants bound to the evidence (e.g. the hash of a public key of the
+//! workload), and checks the returned evidence with its own verifier.
+//!
+//! The evidence is bound to the runtime data
+//!
+//! ```json
+//! {"nonce":"<nonce>","runtime-data":"<hex of the runtime data>"}
+//! ```
+//!
+//! whose SHA-384 digest is the report data of the evidence, as for the
+//! runtime data of the KBS protocol.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha384};
+
+use crate::ima::ImaEvidence;
+
+/// Max length of the nonce and of the runtime data, in bytes.
+pub const MAX_CHALLENGE_LEN: usize = 1024;
+
+/// Evidence bound to the nonce of a verifier.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChallengeEvidence {
+    /// Type of the TEE, e.g. `tdx`, picking the verifier of the evidence.
+    pub tee: String,
+
+    /// The evidence, whose report data is the SHA-384 digest of
+    /// `runtime_data`.
+    pub evidence: Vec<u8>,
+
+    /// The runtime data the evidence is bound to.
+    pub runtime_data: String,
+
+    /// The IMA measurement list, read after the evidence, if the guest
+    /// has IMA, see [`crate::ima`].
+    pub ima: Option<ImaEvidence>,
+}
+
+/// The runtime data binding `nonce` and the `runtime_data` of a verifier.
+pub fn runtime_data(nonce: &str, runtime_data: &[u8]) -> Result<String> {
+    if nonce.is_empty() {
+        bail!("nonce must not be empty");
+    }
+
+    if nonce.len() > MAX_CHALLENGE_LEN || runtime_data.len() > MAX_CHALLENGE_LEN {
+        bail!("nonce and runtime data must not exceed {MAX_CHALLENGE_LEN} bytes");
+    }
+
+    Ok(json!({
+        "nonce": nonce,
+        "runtime-data": hex::encode(runtime_data),
+    })
+    .to_string())
+}
+
+/// The report data of the evidence bound to `runtime_data`.
+pub fn report_data(runtime_data: &str) -> Vec<u8> {
+    Sha384::digest(runtime_data).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_data() {
+        let pubkey_hash = [0xab; 32];
+        let bound = runtime_data("nonce", &pubkey_hash).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&bound).unwrap();
+        assert_eq!(parsed["nonce"], "nonce");
+        assert_eq!(parsed["runtime-data"], "ab".repeat(32));
+        assert_eq!(report_data(&bound).len(), 48);
+
+        assert!(runtime_data("", &pubkey_hash).is_err());
+        assert!(runtime_data("nonce", &[0; MAX_CHALLENGE_LEN + 1]).is_err());
+        assert!(runtime_data("nonce", &[]).is_ok());
+    }
+}
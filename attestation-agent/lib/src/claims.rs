@@ -0,0 +1,118 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Claims of the workload registered by other guest components, e.g. the
+//! digests of the pulled images or the hash of the policy in use. They are
+//! bound to the attestation tokens AA gets, so relying parties can condition
+//! key release on the workload identity.
+
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::config_measurement::{ConfigMeasurement, CONFIG_PREFIX};
+use crate::ima::CLAIM_PREFIX;
+
+/// Registered claims, sorted by name.
+pub type Claims = BTreeMap<String, String>;
+
+/// Add `new` claims to `claims`. A claim cannot be changed once registered,
+/// so nothing is added if any of `new` conflicts.
+pub fn register(claims: &mut Claims, new: HashMap<String, String>) -> Result<()> {
+    for name in new.keys() {
+        if name.is_empty() {
+            bail!("claim name must not be empty");
+        }
+
+        if name.starts_with(CLAIM_PREFIX) {
+            bail!("claim {name:?} is reserved for the IMA measurement list");
+        }
+
+        if name.starts_with(CONFIG_PREFIX) {
+            bail!("claim {name:?} is reserved for the configuration measurements");
+        }
+    }
+
+    insert(claims, new)
+}
+
+/// Add the claims of the configuration `measurement` taken for `caller`,
+/// see [`ConfigMeasurement::claims`].
+pub(crate) fn register_config(
+    claims: &mut Claims,
+    measurement: &ConfigMeasurement,
+    caller: &str,
+) -> Result<()> {
+    insert(claims, measurement.claims(caller))
+}
+
+fn insert(claims: &mut Claims, new: HashMap<String, String>) -> Result<()> {
+    for (name, value) in &new {
+        if let Some(registered) = claims.get(name) {
+            if registered != value {
+                bail!("claim {name:?} has already been registered with a different value");
+            }
+        }
+    }
+
+    claims.extend(new);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register() {
+        let mut claims = Claims::new();
+        register(
+            &mut claims,
+            HashMap::from([("image".into(), "sha256:1".into())]),
+        )
+        .unwrap();
+
+        // re-registering the same value is fine
+        register(
+            &mut claims,
+            HashMap::from([
+                ("image".into(), "sha256:1".into()),
+                ("policy".into(), "sha256:2".into()),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(claims.len(), 2);
+
+        // a conflicting claim rejects the whole request
+        assert!(register(
+            &mut claims,
+            HashMap::from([
+                ("image".into(), "sha256:3".into()),
+                ("cdh".into(), "sha256:4".into()),
+            ]),
+        )
+        .is_err());
+        assert_eq!(claims.get("image").unwrap(), "sha256:1");
+        assert!(!claims.contains_key("cdh"));
+
+        assert!(register(&mut claims, HashMap::from([(String::new(), "x".into())])).is_err());
+        assert!(register(
+            &mut claims,
+            HashMap::from([(crate::ima::CLAIM_DIGEST.into(), "sha256:5".into())]),
+        )
+        .is_err());
+
+        // the configuration claims are only registered for their caller
+        assert!(register(
+            &mut claims,
+            HashMap::from([("config.cdh".into(), "sha256:6".into())]),
+        )
+        .is_err());
+        let measurement = ConfigMeasurement::new("cdh", &serde_json::json!({})).unwrap();
+        register_config(&mut claims, &measurement, "/usr/bin/cdh (uid 0)").unwrap();
+        register_config(&mut claims, &measurement, "/usr/bin/cdh (uid 0)").unwrap();
+        assert_eq!(claims["config.cdh.caller"], "/usr/bin/cdh (uid 0)");
+        assert!(register_config(&mut claims, &measurement, "/tmp/cdh (uid 0)").is_err());
+    }
+}
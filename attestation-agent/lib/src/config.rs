@@ -0,0 +1,226 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Config of AA which can be reloaded while AA is running.
+//!
+//! Long-lived guests outlive their KBS, e.g. when it is migrated. The KBS
+//! AA talks to, and the certificates it trusts for it, are set by a TOML
+//! file like
+//!
+//! ```toml
+//! kbs_url = "https://kbs.example.com:8080"
+//! kbs_cert_bundle = "/etc/attestation-agent/kbs-certs.pem"
+//!
+//! [token_cache]
+//! max_age_secs = 60
+//! ```
+//!
+//! and can be changed without restarting AA, see
+//! [`AttestationAgent::reload_config`](crate::AttestationAgent::reload_config).
+//! A config is checked and the files it refers to are read before it
+//! replaces the one in effect, so that a bad config leaves AA as it was.
+//! A new config is extended into the runtime measurement register as a
+//! [`RELOAD_CONFIG_EVENT_PREFIX`] event before it is put in effect, so that
+//! relying parties see which KBS the guest talks to. The KBC instances are
+//! dropped when the config changes, together with the KBS sessions and
+//! tokens they keep.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Prefix of the runtime measurement event of a reloaded config, followed
+/// by its `sha256:<hex>` digest, see
+/// [`ConfigMeasurement`](crate::config_measurement::ConfigMeasurement).
+pub const RELOAD_CONFIG_EVENT_PREFIX: &str = "aa.reload_config:";
+
+const PEM_CERT_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_CERT_END: &str = "-----END CERTIFICATE-----";
+
+/// Config of AA, see the [module docs](self).
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// `https://` URL of the KBS, overriding the one of `aa_kbc_params` and
+    /// the ones given with the requests.
+    #[serde(default)]
+    pub kbs_url: Option<String>,
+
+    /// PEM bundle of the certificates to trust for the KBS, on top of the
+    /// system roots.
+    #[serde(default)]
+    pub kbs_cert_bundle: Option<PathBuf>,
+
+    /// Cache of the tokens, see [`token_cache`](crate::token_cache).
+    #[serde(default)]
+    pub token_cache: TokenCacheConfig,
+}
+
+/// Config of the token cache, see [`token_cache`](crate::token_cache).
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TokenCacheConfig {
+    /// Seconds a token is handed out again for at most, whatever its
+    /// expiry. `0`, the default, disables the cache.
+    #[serde(default)]
+    pub max_age_secs: u64,
+
+    /// Length in seconds of the windows the evidence of a token is fresh
+    /// in. A token is only handed out again in the window it was got in.
+    #[serde(default = "default_freshness_window_secs")]
+    pub freshness_window_secs: u64,
+}
+
+impl Default for TokenCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_age_secs: 0,
+            freshness_window_secs: default_freshness_window_secs(),
+        }
+    }
+}
+
+fn default_freshness_window_secs() -> u64 {
+    300
+}
+
+/// A checked [`Config`], with the files it refers to read.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResolvedConfig {
+    pub config: Config,
+
+    /// PEM of the certificates of `kbs_cert_bundle`.
+    pub kbs_certs: Vec<String>,
+}
+
+impl Config {
+    /// Parse the TOML `content` of a config.
+    pub fn parse(content: &str) -> Result<Self> {
+        toml::from_str(content).context("parse AA config")
+    }
+
+    /// Read and parse the config file at `path`.
+    pub async fn load(path: &Path) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("read AA config {}", path.display()))?;
+        Self::parse(&content)
+    }
+
+    /// Check the config and read the files it refers to.
+    pub async fn resolve(self) -> Result<ResolvedConfig> {
+        if let Some(url) = &self.kbs_url {
+            if !url.starts_with("https://") {
+                bail!("kbs_url {url:?} is not a https URL");
+            }
+        }
+
+        if self.token_cache.freshness_window_secs == 0 {
+            bail!("token_cache.freshness_window_secs must be at least 1");
+        }
+
+        let kbs_certs = match &self.kbs_cert_bundle {
+            Some(path) => {
+                let bundle = tokio::fs::read_to_string(path)
+                    .await
+                    .with_context(|| format!("read kbs_cert_bundle {}", path.display()))?;
+                split_pem_certs(&bundle)
+                    .with_context(|| format!("kbs_cert_bundle {}", path.display()))?
+            }
+            None => Vec::new(),
+        };
+
+        Ok(ResolvedConfig {
+            config: self,
+            kbs_certs,
+        })
+    }
+}
+
+// the PEM blocks of the certificates in `bundle`
+fn split_pem_certs(bundle: &str) -> Result<Vec<String>> {
+    let mut certs = Vec::new();
+    let mut rest = bundle;
+    while let Some(begin) = rest.find(PEM_CERT_BEGIN) {
+        let Some(end) = rest[begin..].find(PEM_CERT_END) else {
+            bail!("unterminated certificate");
+        };
+        let end = begin + end + PEM_CERT_END.len();
+        certs.push(format!("{}\n", &rest[begin..end]));
+        rest = &rest[end..];
+    }
+
+    if certs.is_empty() {
+        bail!("no certificate found");
+    }
+
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERT: &str = "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----";
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let config = Config::parse(r#"kbs_url = "https://kbs.example.com:8080""#).unwrap();
+        let resolved = config.clone().resolve().await.unwrap();
+        assert_eq!(resolved.config, config);
+        assert!(resolved.kbs_certs.is_empty());
+
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse(r#"kbs_uri = "https://kbs""#).is_err());
+        for url in ["ftp://kbs", "http://kbs.example.com:8080"] {
+            let config = Config {
+                kbs_url: Some(url.into()),
+                ..Default::default()
+            };
+            assert!(config.resolve().await.is_err(), "{url}");
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = dir.path().join("certs.pem");
+        tokio::fs::write(&bundle, format!("# kbs\n{CERT}\n{CERT}\n"))
+            .await
+            .unwrap();
+        let config = Config {
+            kbs_cert_bundle: Some(bundle.clone()),
+            ..Default::default()
+        };
+        let resolved = config.clone().resolve().await.unwrap();
+        assert_eq!(resolved.kbs_certs, vec![format!("{CERT}\n"); 2]);
+
+        for content in ["", "-----BEGIN CERTIFICATE-----\nMIIB\n"] {
+            tokio::fs::write(&bundle, content).await.unwrap();
+            assert!(config.clone().resolve().await.is_err(), "{content}");
+        }
+
+        let missing = Config {
+            kbs_cert_bundle: Some(dir.path().join("missing.pem")),
+            ..Default::default()
+        };
+        assert!(missing.resolve().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_token_cache_config() {
+        assert_eq!(Config::default().token_cache.max_age_secs, 0);
+
+        let config = Config::parse("[token_cache]\nmax_age_secs = 60").unwrap();
+        assert_eq!(
+            config.token_cache,
+            TokenCacheConfig {
+                max_age_secs: 60,
+                freshness_window_secs: 300,
+            }
+        );
+        config.resolve().await.unwrap();
+
+        let config = Config::parse("[token_cache]\nfreshness_window_secs = 0").unwrap();
+        assert!(config.resolve().await.is_err());
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Measurement of the effective configuration of the guest components.
+//!
+//! A guest launched with a weakened configuration, e.g. with signature
+//! verification disabled, attests the same as any other. At startup AA and
+//! CDH hash their configuration as in effect after the environment and the
+//! command line are applied. AA extends the digest into the runtime
+//! measurement register, so that it is covered by the evidence, and
+//! registers it as the claim `config.<component>`, so that it is bound to
+//! the tokens, see [`claims`](crate::claims).
+//!
+//! Other components report theirs with `ExtendRuntimeMeasurement`, as the
+//! event `config.<component>:sha256:<hex>` of [`ConfigMeasurement::event`],
+//! which AA registers as a claim as well.
+//!
+//! The claims are bound to the caller the configuration is measured for,
+//! i.e. the executable and the uid of the process reporting it, which is
+//! registered as the claim `config.<component>.caller`. Callers AA can't
+//! identify, e.g. over vsock, can't report configurations, and the
+//! `config.*` claims can't be registered with `RegisterClaims`. A component
+//! is measured once per boot: the measurements are recorded under
+//! [`CONFIG_RECORD_DIR`], so that a restarted AA still refuses to measure a
+//! component again with another configuration or for another caller.
+
+use anyhow::{bail, Context, Result};
+use crypto::canonical::canonical_digest;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// Prefix of the runtime measurement events and of the claims of the
+/// configuration of a component.
+pub const CONFIG_PREFIX: &str = "config.";
+
+/// Suffix of the claim of the caller a configuration is measured for.
+pub const CALLER_CLAIM_SUFFIX: &str = ".caller";
+
+/// Directory the configuration measurements of this boot are recorded in.
+pub const CONFIG_RECORD_DIR: &str = "/run/confidential-containers/attestation-agent/config";
+
+/// Digest of the effective configuration of a component.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigMeasurement {
+    /// Name of the component, e.g. `aa` or `cdh`.
+    pub component: String,
+
+    /// `sha256:<hex>` digest of the canonical JSON of the configuration.
+    pub digest: String,
+}
+
+impl ConfigMeasurement {
+    /// Measure the `config` of `component`. Objects are hashed with their
+    /// keys sorted, so the digest does not depend on the field order.
+    pub fn new(component: &str, config: &Value) -> Result<Self> {
+        check_component(component)?;
+        Ok(Self {
+            component: component.to_string(),
+            digest: canonical_digest(config)?,
+        })
+    }
+
+    /// Parse a runtime measurement event of [`ConfigMeasurement::event`].
+    /// Other events are `None`.
+    pub fn from_event(event: &[u8]) -> Option<Self> {
+        let event = std::str::from_utf8(event).ok()?;
+        let (component, digest) = event.strip_prefix(CONFIG_PREFIX)?.split_once(':')?;
+        let hex = digest.strip_prefix("sha256:")?;
+        if check_component(component).is_err()
+            || hex.len() != 64
+            || !hex.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            return None;
+        }
+
+        Some(Self {
+            component: component.to_string(),
+            digest: digest.to_string(),
+        })
+    }
+
+    /// The runtime measurement event of the configuration.
+    pub fn event(&self) -> Vec<u8> {
+        format!("{CONFIG_PREFIX}{}:{}", self.component, self.digest).into_bytes()
+    }
+
+    /// Name of the claim of the configuration.
+    pub fn claim_name(&self) -> String {
+        format!("{CONFIG_PREFIX}{}", self.component)
+    }
+
+    /// The claims of the configuration measured for `caller`: its digest and
+    /// the caller.
+    pub fn claims(&self, caller: &str) -> HashMap<String, String> {
+        HashMap::from([
+            (self.claim_name(), self.digest.clone()),
+            (
+                format!("{}{CALLER_CLAIM_SUFFIX}", self.claim_name()),
+                caller.to_string(),
+            ),
+        ])
+    }
+
+    /// Record the measurement for `caller` in `record_dir`, e.g.
+    /// [`CONFIG_RECORD_DIR`]. Recording a component again with the same
+    /// digest for the same caller is a no-op, anything else is refused.
+    pub fn record(&self, record_dir: &Path, caller: &str) -> Result<()> {
+        let record = json!({ "digest": self.digest, "caller": caller });
+        let path = record_dir.join(&self.component);
+        std::fs::create_dir_all(record_dir)
+            .with_context(|| format!("create {}", record_dir.display()))?;
+
+        // written aside and linked, so that a record is never seen partially
+        // written
+        let staged = record_dir.join(format!(".{}.{}", self.component, std::process::id()));
+        std::fs::write(&staged, serde_json::to_vec(&record)?)
+            .with_context(|| format!("write {}", staged.display()))?;
+        let linked = std::fs::hard_link(&staged, &path);
+        let _ = std::fs::remove_file(&staged);
+        match linked {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e).with_context(|| format!("record {}", path.display())),
+        }
+
+        let recorded: Value = serde_json::from_slice(
+            &std::fs::read(&path).with_context(|| format!("read {}", path.display()))?,
+        )?;
+        if recorded != record {
+            bail!(
+                "configuration of {} has already been measured with {} for {}",
+                self.component,
+                recorded["digest"],
+                recorded["caller"]
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn check_component(component: &str) -> Result<()> {
+    if component.is_empty()
+        || !component
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    {
+        bail!("illegal component name {component:?}");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::Digest;
+
+    #[test]
+    fn test_config_measurement() {
+        let measurement = ConfigMeasurement::new(
+            "cdh",
+            &json!({"socket": "unix:///run/cdh.sock", "cache": {"ttl": 60, "max": 1}}),
+        )
+        .unwrap();
+        let reordered = ConfigMeasurement::new(
+            "cdh",
+            &json!({"cache": {"max": 1, "ttl": 60}, "socket": "unix:///run/cdh.sock"}),
+        )
+        .unwrap();
+        assert_eq!(measurement, reordered);
+        assert_eq!(
+            measurement.digest,
+            format!(
+                "sha256:{}",
+                hex::encode(sha2::Sha256::digest(
+                    br#"{"cache":{"max":1,"ttl":60},"socket":"unix:///run/cdh.sock"}"#
+                ))
+            )
+        );
+        assert_ne!(
+            measurement,
+            ConfigMeasurement::new("cdh", &json!({"socket": "unix:///run/cdh.sock"})).unwrap()
+        );
+        assert_eq!(measurement.claim_name(), "config.cdh");
+
+        let event = measurement.event();
+        assert_eq!(
+            event,
+            format!("config.cdh:{}", measurement.digest).into_bytes()
+        );
+        assert_eq!(ConfigMeasurement::from_event(&event), Some(measurement));
+
+        let malformed: [&[u8]; 6] = [
+            b"aa.pre_attested:00",
+            b"config.cdh",
+            b"config.:sha256:00",
+            b"config.cdh:sha256:00",
+            b"config.c/h:sha256:0000000000000000000000000000000000000000000000000000000000000000",
+            b"\xff",
+        ];
+        for event in malformed {
+            assert_eq!(ConfigMeasurement::from_event(event), None);
+        }
+
+        assert!(ConfigMeasurement::new("", &json!({})).is_err());
+        assert!(ConfigMeasurement::new("a:b", &json!({})).is_err());
+    }
+
+    #[test]
+    fn test_record() {
+        let record_dir = tempfile::tempdir().unwrap();
+        let measurement = ConfigMeasurement::new("cdh", &json!({"socket": "a"})).unwrap();
+        let caller = "/usr/local/bin/confidential-data-hub (uid 0)";
+        measurement.record(record_dir.path(), caller).unwrap();
+
+        // once per boot, for the same caller
+        measurement.record(record_dir.path(), caller).unwrap();
+        assert!(measurement
+            .record(record_dir.path(), "/tmp/confidential-data-hub (uid 0)")
+            .is_err());
+        let other = ConfigMeasurement::new("cdh", &json!({"socket": "b"})).unwrap();
+        assert!(other.record(record_dir.path(), caller).is_err());
+
+        // nothing staged is left
+        assert_eq!(std::fs::read_dir(record_dir.path()).unwrap().count(), 1);
+
+        let claims = measurement.claims(caller);
+        assert_eq!(claims["config.cdh"], measurement.digest);
+        assert_eq!(claims["config.cdh.caller"], caller);
+    }
+}
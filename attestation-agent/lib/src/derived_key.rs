@@ -0,0 +1,116 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Keys derived from the sealing key of the TEE.
+//!
+//! Components like the eccfs snapshotter or the local sealing of CDH need
+//! keys only the guest can get again, without a KBS. AA derives them from
+//! the sealing key of the TEE, e.g. the SNP derived key bound to the launch
+//! measurement, the SGX sealing key bound to the MRENCLAVE or an HMAC key
+//! of the Azure vTPM, which is never handed out itself. TDX has no sealing
+//! primitive, so no keys can be derived there.
+//!
+//! A key is the HKDF-SHA256 of the sealing key, with the info
+//!
+//! ```text
+//! "attestation-agent derived key v1" || be32(len(purpose)) || purpose || context
+//! ```
+//!
+//! so that the key is bound to
+//! - its purpose, a label like `eccfs`, so that components cannot get the
+//!   keys of one another,
+//! - a context, e.g. the digest of an image for a key per image.
+//!
+//! Per-boot keys use a random salt drawn when AA starts, so they cannot be
+//! derived again once the guest reboots.
+
+use anyhow::{anyhow, bail, Result};
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+/// Length of the derived keys if not given, in bytes.
+pub const DEFAULT_KEY_LEN: usize = 32;
+
+/// Max length of the derived keys, in bytes.
+pub const MAX_KEY_LEN: usize = 64;
+
+/// Max length of the purpose and of the context, in bytes.
+pub const MAX_LABEL_LEN: usize = 1024;
+
+const INFO_PREFIX: &[u8] = b"attestation-agent derived key v1";
+
+/// Salt of the per-boot keys.
+pub type BootSalt = [u8; 32];
+
+/// Derive the key of `len` bytes for `purpose` and `context` from the
+/// `sealing_key` of the TEE. The key is a per-boot key if `boot_salt` is
+/// given.
+pub fn derive(
+    sealing_key: &[u8],
+    purpose: &str,
+    context: &[u8],
+    boot_salt: Option<&BootSalt>,
+    len: usize,
+) -> Result<Vec<u8>> {
+    if purpose.is_empty() {
+        bail!("purpose must not be empty");
+    }
+
+    if purpose.len() > MAX_LABEL_LEN || context.len() > MAX_LABEL_LEN {
+        bail!("purpose and context must not exceed {MAX_LABEL_LEN} bytes");
+    }
+
+    if len == 0 || len > MAX_KEY_LEN {
+        bail!("key length must be 1 to {MAX_KEY_LEN} bytes");
+    }
+
+    let mut info = INFO_PREFIX.to_vec();
+    info.extend((purpose.len() as u32).to_be_bytes());
+    info.extend(purpose.as_bytes());
+    info.extend(context);
+
+    let hkdf = Hkdf::<Sha256>::new(boot_salt.map(|salt| &salt[..]), sealing_key);
+    let mut key = vec![0; len];
+    hkdf.expand(&info, &mut key)
+        .map_err(|e| anyhow!("derive key: {e}"))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive() {
+        let sealing_key = [1; 32];
+        let key = derive(&sealing_key, "eccfs", b"", None, DEFAULT_KEY_LEN).unwrap();
+        assert_eq!(key.len(), DEFAULT_KEY_LEN);
+        assert_eq!(
+            key,
+            derive(&sealing_key, "eccfs", b"", None, DEFAULT_KEY_LEN).unwrap()
+        );
+
+        // the purpose is delimited from the context
+        let others = [
+            derive(&[2; 32], "eccfs", b"", None, DEFAULT_KEY_LEN),
+            derive(&sealing_key, "cdh", b"", None, DEFAULT_KEY_LEN),
+            derive(&sealing_key, "eccf", b"s", None, DEFAULT_KEY_LEN),
+            derive(&sealing_key, "eccfs", b"sha256:1234", None, DEFAULT_KEY_LEN),
+            derive(&sealing_key, "eccfs", b"", Some(&[3; 32]), DEFAULT_KEY_LEN),
+        ];
+        for other in others {
+            assert_ne!(other.unwrap(), key);
+        }
+
+        assert_eq!(
+            derive(&sealing_key, "eccfs", b"", None, 16).unwrap(),
+            key[..16]
+        );
+        assert!(derive(&sealing_key, "", b"", None, DEFAULT_KEY_LEN).is_err());
+        assert!(derive(&sealing_key, "eccfs", b"", None, 0).is_err());
+        assert!(derive(&sealing_key, "eccfs", b"", None, MAX_KEY_LEN + 1).is_err());
+        assert!(derive(&sealing_key, "eccfs", &[0; MAX_LABEL_LEN + 1], None, 32).is_err());
+    }
+}
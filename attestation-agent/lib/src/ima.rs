@@ -0,0 +1,200 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Runtime integrity evidence of the guest from the Linux Integrity
+//! Measurement Architecture (IMA).
+//!
+//! IMA measures the files executed or read inside the guest, as its policy
+//! says, into a measurement list, and extends every entry into a
+//! measurement register of the TEE or of the vTPM. The list itself is not
+//! signed: verifiers replay it against the register in the hardware
+//! evidence, and then see every binary executed inside the guest.
+//!
+//! The list is attached to the challenge evidence, read right after the
+//! hardware evidence so that it covers at least the entries extended into
+//! the register by then. The tokens are bound to it by the claims
+//! [`CLAIM_DIGEST`], [`CLAIM_ENTRIES`] and, if EVM is enabled,
+//! [`CLAIM_EVM_STATUS`], taken when the token is requested.
+//!
+//! The policy can be set at boot from an entry of the measured init-data,
+//! see [`crate::AttestationAPIs::set_ima_policy`], so that the policy is
+//! attested as well.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::ErrorKind;
+use std::path::Path;
+use tokio::fs;
+
+use crate::claims::Claims;
+
+/// Where securityfs, exposing IMA and EVM, is mounted.
+pub const SECURITYFS_DIR: &str = "/sys/kernel/security";
+
+/// Init-data entry holding the IMA policy by default.
+pub const DEFAULT_POLICY_ENTRY: &str = "ima_policy";
+
+/// Prefix of the claims derived from IMA, which can't be registered by
+/// other components.
+pub const CLAIM_PREFIX: &str = "ima.";
+
+/// Claim of the `sha256:` digest of the measurement list.
+pub const CLAIM_DIGEST: &str = "ima.measurements_digest";
+
+/// Claim of the number of entries of the measurement list.
+pub const CLAIM_ENTRIES: &str = "ima.measurements_entries";
+
+/// Claim of the EVM status, as the hex bitmask of the kernel.
+pub const CLAIM_EVM_STATUS: &str = "ima.evm_status";
+
+const MEASUREMENTS_FILE: &str = "ima/ascii_runtime_measurements";
+
+const POLICY_FILE: &str = "ima/policy";
+
+const EVM_FILE: &str = "evm";
+
+/// The IMA measurement list of the guest.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ImaEvidence {
+    /// The measurement list in the ASCII format of the kernel, one entry
+    /// per line: `<register> <template hash> <template> <template data>`.
+    pub measurements: String,
+
+    /// Number of entries of `measurements`.
+    pub entries: usize,
+
+    /// `sha256:` digest of `measurements`.
+    pub digest: String,
+
+    /// Bitmask of the EVM initialization, if EVM is enabled.
+    pub evm_status: Option<u32>,
+}
+
+impl ImaEvidence {
+    fn parse(measurements: String, evm_status: Option<u32>) -> Result<Self> {
+        let mut entries = 0;
+        for (i, line) in measurements.lines().enumerate() {
+            let fields: Vec<_> = line.split_ascii_whitespace().collect();
+            if fields.len() < 4 || fields[0].parse::<u32>().is_err() {
+                bail!("malformed IMA measurement {}: {line:?}", i + 1);
+            }
+            entries += 1;
+        }
+
+        Ok(Self {
+            digest: format!("sha256:{}", hex::encode(Sha256::digest(&measurements))),
+            entries,
+            measurements,
+            evm_status,
+        })
+    }
+
+    /// The claims binding a token to the measurement list.
+    pub fn claims(&self) -> Claims {
+        let mut claims = Claims::from([
+            (CLAIM_DIGEST.to_string(), self.digest.clone()),
+            (CLAIM_ENTRIES.to_string(), self.entries.to_string()),
+        ]);
+        if let Some(status) = self.evm_status {
+            claims.insert(CLAIM_EVM_STATUS.to_string(), format!("{status:#x}"));
+        }
+
+        claims
+    }
+}
+
+/// Read the IMA measurement list and the EVM status from securityfs at
+/// `dir`. Returns `None` if the kernel has no IMA.
+pub async fn collect(dir: &Path) -> Result<Option<ImaEvidence>> {
+    let measurements = match fs::read_to_string(dir.join(MEASUREMENTS_FILE)).await {
+        Ok(measurements) => measurements,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("read IMA measurement list"),
+    };
+
+    let evm_status = match fs::read_to_string(dir.join(EVM_FILE)).await {
+        Ok(status) => Some(
+            status
+                .trim()
+                .parse()
+                .with_context(|| format!("malformed EVM status {status:?}"))?,
+        ),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(e).context("read EVM status"),
+    };
+
+    ImaEvidence::parse(measurements, evm_status).map(Some)
+}
+
+/// Replace the IMA policy through securityfs at `dir` with the rules of
+/// `policy`. The kernel accepts a new policy once, unless it is built with
+/// `CONFIG_IMA_WRITE_POLICY`, so this is meant to be done at boot.
+pub async fn set_policy(dir: &Path, policy: &str) -> Result<()> {
+    let has_rules = policy.lines().any(|line| {
+        let line = line.trim();
+        !line.is_empty() && !line.starts_with('#')
+    });
+    if !has_rules {
+        bail!("IMA policy has no rules");
+    }
+
+    fs::write(dir.join(POLICY_FILE), policy)
+        .await
+        .context("write IMA policy")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEASUREMENTS: &str = "\
+10 91f34b5c671d73504b274a919661cf80dab1e127 ima-ng sha1:1801e1be3e65ef1eaa5c16617bec8f1274eaf6b3 boot_aggregate
+10 8b1683287f61f96e5448f40bdef6df32be86486a ima-ng sha256:efdd249edec97caf9328a4a01baa99b7d660d1afc2e118b69137081c9b689954 /usr/bin/kata-agent
+";
+
+    #[tokio::test]
+    async fn test_collect() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(collect(dir.path()).await.unwrap(), None);
+
+        std::fs::create_dir(dir.path().join("ima")).unwrap();
+        std::fs::write(dir.path().join(MEASUREMENTS_FILE), MEASUREMENTS).unwrap();
+        let evidence = collect(dir.path()).await.unwrap().unwrap();
+        assert_eq!(evidence.entries, 2);
+        assert_eq!(evidence.evm_status, None);
+        assert_eq!(
+            evidence.digest,
+            format!("sha256:{}", hex::encode(Sha256::digest(MEASUREMENTS)))
+        );
+        let claims = evidence.claims();
+        assert_eq!(claims[CLAIM_ENTRIES], "2");
+        assert!(!claims.contains_key(CLAIM_EVM_STATUS));
+        assert!(claims.keys().all(|name| name.starts_with(CLAIM_PREFIX)));
+
+        std::fs::write(dir.path().join(EVM_FILE), "2147483650\n").unwrap();
+        let evidence = collect(dir.path()).await.unwrap().unwrap();
+        assert_eq!(evidence.evm_status, Some(0x8000_0002));
+        assert_eq!(evidence.claims()[CLAIM_EVM_STATUS], "0x80000002");
+
+        std::fs::write(dir.path().join(MEASUREMENTS_FILE), "boot_aggregate\n").unwrap();
+        assert!(collect(dir.path()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_set_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("ima")).unwrap();
+
+        assert!(set_policy(dir.path(), "# nothing\n\n").await.is_err());
+
+        let policy = "# measure the executed binaries\nmeasure func=BPRM_CHECK\n";
+        set_policy(dir.path(), policy).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(POLICY_FILE)).unwrap(),
+            policy
+        );
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Init-data is a TOML document handed to the guest at boot, carrying
+//! configuration that must itself be attested, e.g.
+//!
+//! ```toml
+//! algorithm = "sha256"
+//! version = "0.1.0"
+//!
+//! [data]
+//! "cdh.toml" = "..."
+//! "policy.json" = "..."
+//! ```
+//!
+//! The digest of the whole document is expected to be recorded by the host
+//! in the TEE launch measurement, zero-padded to the size of the register:
+//! TDX `MRCONFIGID` holds 48 bytes, i.e. up to a `sha384` digest, and SNP
+//! `HOST_DATA` 32 bytes, i.e. only a `sha256` digest. A digest longer than
+//! the register is rejected, never truncated. Once the digest has been
+//! checked against the hardware, every entry of `data` is written to
+//! [`INITDATA_DIR`] where CDH and image-rs can consume it. Init-data is
+//! refused on TEEs that cannot bind it, unless AA is built with the
+//! `insecure-debug` feature.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Dir where verified init-data entries are exposed to other components.
+/// `/run` is on tmpfs, which lives in TEE-protected memory.
+pub const INITDATA_DIR: &str = "/run/confidential-containers/initdata";
+
+/// Name of the file holding the raw init-data document in [`INITDATA_DIR`].
+pub const INITDATA_FILE: &str = "initdata.toml";
+
+#[derive(Deserialize, Debug)]
+struct InitData {
+    algorithm: String,
+    version: String,
+    #[serde(default)]
+    data: HashMap<String, String>,
+}
+
+/// Verified init-data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvisionedInitData {
+    /// Digest of the raw init-data document.
+    pub digest: Vec<u8>,
+
+    /// Whether the digest was checked against the TEE launch measurement.
+    /// This is only `false` with the `insecure-debug` feature, on platforms
+    /// that cannot bind init-data.
+    pub hardware_bound: bool,
+
+    /// Names of the entries written to [`INITDATA_DIR`].
+    pub entries: Vec<String>,
+}
+
+/// Parse the raw init-data and calculate its digest with the algorithm
+/// named inside the document. Returns the digest and the `data` entries.
+pub(crate) fn parse(raw: &[u8]) -> Result<(Vec<u8>, HashMap<String, String>)> {
+    let content = std::str::from_utf8(raw).context("init-data is not valid UTF-8")?;
+    let init_data: InitData = toml::from_str(content).context("parse init-data")?;
+
+    if init_data.version != "0.1.0" {
+        bail!("unsupported init-data version {}", init_data.version);
+    }
+
+    let digest = match &init_data.algorithm[..] {
+        "sha256" => Sha256::digest(raw).to_vec(),
+        "sha384" => Sha384::digest(raw).to_vec(),
+        "sha512" => Sha512::digest(raw).to_vec(),
+        others => bail!("unsupported init-data digest algorithm {others}"),
+    };
+
+    for name in init_data.data.keys() {
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            bail!("illegal init-data entry name {name:?}");
+        }
+    }
+
+    Ok((digest, init_data.data))
+}
+
+/// Write the raw init-data and its entries into `dir`.
+pub(crate) async fn expose(
+    dir: &Path,
+    raw: &[u8],
+    entries: &HashMap<String, String>,
+) -> Result<Vec<String>> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .context("create init-data dir")?;
+    tokio::fs::write(dir.join(INITDATA_FILE), raw).await?;
+
+    let mut names = Vec::new();
+    for (name, content) in entries {
+        tokio::fs::write(dir.join(name), content)
+            .await
+            .with_context(|| format!("write init-data entry {name}"))?;
+        names.push(name.clone());
+    }
+
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INIT_DATA: &str = r#"
+algorithm = "sha256"
+version = "0.1.0"
+
+[data]
+"policy.json" = '{"default":[{"type":"insecureAcceptAnything"}]}'
+"#;
+
+    #[test]
+    fn test_parse() {
+        let (digest, entries) = parse(INIT_DATA.as_bytes()).unwrap();
+        assert_eq!(digest, Sha256::digest(INIT_DATA.as_bytes()).to_vec());
+        assert!(entries.contains_key("policy.json"));
+
+        let bad_algorithm = INIT_DATA.replace("sha256", "md5");
+        assert!(parse(bad_algorithm.as_bytes()).is_err());
+
+        let bad_name = INIT_DATA.replace("policy.json", "../policy.json");
+        assert!(parse(bad_name.as_bytes()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expose() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, entries) = parse(INIT_DATA.as_bytes()).unwrap();
+        let names = expose(dir.path(), INIT_DATA.as_bytes(), &entries)
+            .await
+            .unwrap();
+
+        assert_eq!(names, vec!["policy.json".to_string()]);
+        assert!(dir.path().join(INITDATA_FILE).exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("policy.json")).unwrap(),
+            entries["policy.json"]
+        );
+    }
+}
@@ -7,17 +7,51 @@
 #[macro_use]
 extern crate strum;
 
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
-use attester::{detect_tee_type, BoxedAttester};
+use attester::{detect_tee_type, Attester, BoxedAttester, InitDataResult};
 use kbc::{AnnotationPacket, KbcCheckInfo, KbcInstance, KbcModuleList};
+use kbs_types::Tee;
+use log::warn;
 use resource_uri::ResourceUri;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+pub mod audit;
+use audit::{AuditLog, Operation};
+
+pub mod challenge;
+use challenge::ChallengeEvidence;
+
+pub mod claims;
+use claims::Claims;
+
+pub mod config;
+use config::{Config, ResolvedConfig};
+
+pub mod config_measurement;
+use config_measurement::{ConfigMeasurement, CONFIG_PREFIX, CONFIG_RECORD_DIR};
+
+pub mod derived_key;
+use derived_key::BootSalt;
+
+pub mod ima;
+
+pub mod initdata;
+use initdata::{ProvisionedInitData, INITDATA_DIR, INITDATA_FILE};
+
+pub mod offline;
+use offline::PreAttested;
 
 #[cfg(feature = "cc_kbc")]
 mod token;
 #[cfg(feature = "cc_kbc")]
-use token::get_kbs_token;
+use token::{get_kbs_token, kbs_host_url};
+
+pub mod token_cache;
+use token_cache::TokenCache;
 
 /// Attestation Agent (AA for short) is a rust library crate for attestation procedure
 /// in confidential containers. It provides kinds of service APIs that need to make
@@ -80,18 +114,78 @@ pub trait AttestationAPIs {
     /// Get TEE hardware signed evidence that includes the runtime data.
     async fn get_evidence(&mut self, runtime_data: &[u8]) -> Result<Vec<u8>>;
 
-    /// Extend runtime measurement register
+    /// Get TEE hardware signed evidence bound to the `nonce` of an external
+    /// verifier and the `runtime_data` it wants attested, e.g. the hash of a
+    /// public key, see [`challenge`].
+    async fn get_challenge_evidence(
+        &mut self,
+        nonce: &str,
+        runtime_data: &[u8],
+    ) -> Result<ChallengeEvidence>;
+
+    /// Extend runtime measurement register. Configuration measurements are
+    /// refused, see [`AttestationAgent::extend_runtime_measurement_of`].
     async fn extend_runtime_measurement(
         &mut self,
         events: Vec<Vec<u8>>,
         register_index: Option<u64>,
     ) -> Result<()>;
+
+    /// Accept the measured init-data at boot.
+    ///
+    /// The digest of `init_data` is checked against the one recorded in the
+    /// TEE launch measurement, then its entries are exposed under
+    /// [`initdata::INITDATA_DIR`]. TEEs that cannot bind init-data refuse
+    /// it, unless built with the `insecure-debug` feature. Init-data can
+    /// only be provisioned once; provisioning identical init-data again is a
+    /// no-op.
+    async fn provision_init_data(&mut self, init_data: &[u8]) -> Result<ProvisionedInitData>;
+
+    /// Register claims of the workload (image digests, policy hash, ...),
+    /// which are bound to the attestation tokens got from then on, see
+    /// [`claims`].
+    ///
+    /// A claim cannot be changed once registered; registering it again with
+    /// the same value is a no-op.
+    async fn register_claims(&mut self, claims: HashMap<String, String>) -> Result<()>;
+
+    /// Set the IMA policy from the entry `init_data_entry` of the
+    /// provisioned init-data, e.g. [`ima::DEFAULT_POLICY_ENTRY`], so that
+    /// the policy is measured with the init-data, see [`ima`]. Init-data not
+    /// bound to the TEE is refused.
+    async fn set_ima_policy(&mut self, init_data_entry: &str) -> Result<()>;
+
+    /// Derive a key of `len` bytes for `purpose` and `context` from the
+    /// sealing key of the TEE, which is lost on reboot if `per_boot` is set,
+    /// see [`derived_key`]. Without a TEE, keys are only derived from the
+    /// public key of the sample attester with the `insecure-debug` feature.
+    /// The callers allowed each purpose are up to the caller of the API.
+    async fn derive_key(
+        &mut self,
+        purpose: &str,
+        context: &[u8],
+        per_boot: bool,
+        len: usize,
+    ) -> Result<Vec<u8>>;
+
+    /// Export the audit log of the evidence, tokens, resources and keys
+    /// requested so far, as a JSON array of hash-chained entries, after
+    /// extending its head into the runtime measurement register, see
+    /// [`audit`].
+    async fn export_audit_log(&mut self) -> Result<Vec<u8>>;
 }
 
 /// Attestation agent to provide attestation service.
 pub struct AttestationAgent {
     kbc_module_list: KbcModuleList,
     kbc_instance_map: HashMap<String, KbcInstance>,
+    init_data: Option<ProvisionedInitData>,
+    claims: Claims,
+    audit: AuditLog,
+    pre_attested: Option<PreAttested>,
+    boot_salt: BootSalt,
+    config: ResolvedConfig,
+    token_cache: TokenCache,
 }
 
 impl Default for AttestationAgent {
@@ -106,6 +200,13 @@ impl AttestationAgent {
         AttestationAgent {
             kbc_module_list: KbcModuleList::new(),
             kbc_instance_map: HashMap::new(),
+            init_data: None,
+            claims: Claims::new(),
+            audit: AuditLog::default(),
+            pre_attested: None,
+            boot_salt: rand::random(),
+            config: ResolvedConfig::default(),
+            token_cache: TokenCache::default(),
         }
     }
 
@@ -120,34 +221,248 @@ impl AttestationAgent {
 
     fn instantiate_kbc(&mut self, kbc_name: &str, kbs_uri: &str) -> Result<()> {
         let instantiate_func = self.kbc_module_list.get_func(kbc_name)?;
+        let kbs_uri = self.config.config.kbs_url.as_deref().unwrap_or(kbs_uri);
         let kbc_instance = (instantiate_func)(kbs_uri.to_string());
         self.register_instance(kbc_name.to_string(), kbc_instance);
         Ok(())
     }
 
-    #[allow(dead_code)]
-    fn check(&self, kbc_name: String) -> Result<KbcCheckInfo> {
-        self.kbc_instance_map
-            .get(&kbc_name)
-            .ok_or_else(|| anyhow!("The KBC instance does not exist!"))?
-            .check()
+    /// Get the init-data provisioned at boot, if any.
+    pub fn init_data(&self) -> Option<&ProvisionedInitData> {
+        self.init_data.as_ref()
     }
-}
 
-#[async_trait]
-impl AttestationAPIs for AttestationAgent {
-    async fn decrypt_image_layer_annotation(
+    /// Get the claims registered so far.
+    pub fn claims(&self) -> &Claims {
+        &self.claims
+    }
+
+    /// Get the config in effect.
+    pub fn config(&self) -> &Config {
+        &self.config.config
+    }
+
+    /// Check `config`, extend its measurement into the runtime measurement
+    /// register and put it in effect, see [`config`](crate::config). The
+    /// KBC instances, with the KBS sessions and tokens they keep, and the
+    /// cached tokens are dropped if the config changes. A bad config, or one
+    /// that cannot be measured, leaves the one in effect as it was, unless
+    /// AA is built with `insecure-debug`.
+    pub async fn reload_config(&mut self, config: Config) -> Result<()> {
+        let digest = ConfigMeasurement::new("aa", &serde_json::to_value(&config)?)?.digest;
+        let res = match TryInto::<BoxedAttester>::try_into(detect_tee_type()) {
+            Ok(attester) => self.apply_config(config, &digest, &*attester).await,
+            Err(e) => Err(e),
+        };
+        self.audit.record(Operation::ReloadConfig { digest }, &res);
+        res
+    }
+
+    // put `config` of `digest` in effect, once it is measured by `attester`
+    async fn apply_config(
+        &mut self,
+        config: Config,
+        digest: &str,
+        attester: &(dyn Attester + Send + Sync),
+    ) -> Result<()> {
+        let resolved = config.resolve().await?;
+        if resolved == self.config {
+            return Ok(());
+        }
+
+        let event = format!("{}{digest}", config::RELOAD_CONFIG_EVENT_PREFIX);
+        if let Err(e) = attester
+            .extend_runtime_measurement(vec![event.into_bytes()], None)
+            .await
+        {
+            if !cfg!(feature = "insecure-debug") {
+                return Err(e.context("measure the reloaded config"));
+            }
+            warn!("reloaded config {digest} is used unmeasured: {e:#}");
+        }
+
+        self.kbc_instance_map.clear();
+        self.token_cache = TokenCache::new(resolved.config.token_cache.clone());
+        self.config = resolved;
+        Ok(())
+    }
+
+    /// Get the audit log of the requests handled so far.
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// Record the call of `method` by `caller` rejected for `reason` in the
+    /// audit log, e.g. by the access control of the AA service.
+    pub fn record_rejected_call(&mut self, method: &str, caller: &str, reason: anyhow::Error) {
+        self.audit.record::<()>(
+            Operation::Call {
+                method: method.to_string(),
+                caller: caller.to_string(),
+            },
+            &Err(reason),
+        );
+    }
+
+    /// Hand out the token, and the evidence if any, of the pre-attested file
+    /// at `path` instead of attesting, see [`offline`]. This is only meant for
+    /// tests and environments without TEE hardware, and is refused unless
+    /// `insecure` is set.
+    pub async fn enable_pre_attested(&mut self, path: &Path, insecure: bool) -> Result<()> {
+        if !insecure {
+            bail!("pre-attested tokens are insecure and must be enabled explicitly");
+        }
+
+        let pre_attested = PreAttested::load(path).await?;
+        warn!(
+            "INSECURE: using the pre-attested file {} (sha256:{}), tokens are not attested",
+            path.display(),
+            pre_attested.digest
+        );
+        self.audit.record(
+            Operation::LoadPreAttested {
+                path: path.display().to_string(),
+                digest: pre_attested.digest.clone(),
+            },
+            &Ok(()),
+        );
+
+        let tee_type = detect_tee_type();
+        let measured = match TryInto::<BoxedAttester>::try_into(tee_type) {
+            Ok(attester) => {
+                attester
+                    .extend_runtime_measurement(vec![pre_attested.event()], None)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = measured {
+            warn!("pre-attested file is not measured: {e:#}");
+        }
+
+        self.pre_attested = Some(pre_attested);
+        Ok(())
+    }
+
+    /// Extend the configuration measurement of a component taken for
+    /// `caller` into the runtime measurement register and register its
+    /// claims, see [`config_measurement`]. A component is measured once per
+    /// boot, for one caller. TEEs without a runtime measurement register only
+    /// get the claims.
+    pub async fn measure_config(
+        &mut self,
+        measurement: ConfigMeasurement,
+        caller: &str,
+    ) -> Result<()> {
+        let res = measurement
+            .record(Path::new(CONFIG_RECORD_DIR), caller)
+            .and_then(|()| claims::register_config(&mut self.claims, &measurement, caller));
+        self.audit.record(
+            Operation::MeasureConfig {
+                component: measurement.component.clone(),
+                digest: measurement.digest.clone(),
+                caller: caller.to_string(),
+            },
+            &res,
+        );
+        res?;
+
+        // the tokens got before attest the old measurements
+        self.token_cache.clear();
+        let tee_type = detect_tee_type();
+        let measured = match TryInto::<BoxedAttester>::try_into(tee_type) {
+            Ok(attester) => {
+                attester
+                    .extend_runtime_measurement(vec![measurement.event()], None)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = measured {
+            warn!(
+                "configuration of {} is not measured: {e:#}",
+                measurement.component
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Extend the runtime measurement `events` reported by `caller`, which
+    /// is `None` if it can't be identified. The configuration measurements
+    /// among them are taken with [`AttestationAgent::measure_config`], so
+    /// they are refused of unidentified callers.
+    pub async fn extend_runtime_measurement_of(
+        &mut self,
+        caller: Option<&str>,
+        events: Vec<Vec<u8>>,
+        register_index: Option<u64>,
+    ) -> Result<()> {
+        let (configs, events): (Vec<_>, Vec<_>) = events
+            .into_iter()
+            .partition(|event| event.starts_with(CONFIG_PREFIX.as_bytes()));
+        for event in &configs {
+            let Some(measurement) = ConfigMeasurement::from_event(event) else {
+                bail!("malformed configuration measurement event");
+            };
+            let Some(caller) = caller else {
+                bail!(
+                    "configuration of {} can only be measured for an identified caller",
+                    measurement.component
+                );
+            };
+            self.measure_config(measurement, caller).await?;
+        }
+
+        if events.is_empty() && !configs.is_empty() {
+            return Ok(());
+        }
+
+        self.token_cache.clear();
+        let tee_type = detect_tee_type();
+        let attester = TryInto::<BoxedAttester>::try_into(tee_type)?;
+        attester
+            .extend_runtime_measurement(events, register_index)
+            .await?;
+        Ok(())
+    }
+
+    // read the entry `name` of the init-data, checking the init-data
+    // against the digest provisioned before
+    async fn init_data_entry(&self, name: &str) -> Result<String> {
+        let Some(provisioned) = &self.init_data else {
+            bail!("init-data has not been provisioned");
+        };
+        if !provisioned.hardware_bound {
+            if !cfg!(feature = "insecure-debug") {
+                bail!("init-data is not bound to the TEE");
+            }
+            warn!("init-data entry {name} is used unverified");
+        }
+
+        let raw = tokio::fs::read(Path::new(INITDATA_DIR).join(INITDATA_FILE))
+            .await
+            .context("read init-data")?;
+        let (digest, mut entries) = initdata::parse(&raw)?;
+        if digest != provisioned.digest {
+            bail!("init-data has changed since it was provisioned");
+        }
+
+        entries
+            .remove(name)
+            .ok_or_else(|| anyhow!("init-data has no entry {name:?}"))
+    }
+
+    async fn decrypt_payload(
         &mut self,
         kbc_name: &str,
         kbs_uri: &str,
-        annotation: &str,
+        annotation: AnnotationPacket,
     ) -> Result<Vec<u8>> {
         if !self.kbc_instance_map.contains_key(kbc_name) {
             self.instantiate_kbc(kbc_name, kbs_uri)?;
         }
 
-        let annotation: AnnotationPacket = serde_json::from_str(annotation)?;
-
         self.kbc_instance_map
             .get_mut(kbc_name)
             .ok_or_else(|| anyhow!("The KBC instance does not existing!"))?
@@ -155,14 +470,12 @@ impl AttestationAPIs for AttestationAgent {
             .await
     }
 
-    async fn download_confidential_resource(
+    async fn get_resource(
         &mut self,
         kbc_name: &str,
-        resource_path: &str,
         kbs_uri: &str,
+        resource_uri: ResourceUri,
     ) -> Result<Vec<u8>> {
-        let resource_uri = ResourceUri::new(kbs_uri, resource_path)?;
-
         if !self.kbc_instance_map.contains_key(kbc_name) {
             self.instantiate_kbc(kbc_name, kbs_uri)?;
         }
@@ -174,11 +487,43 @@ impl AttestationAPIs for AttestationAgent {
             .await
     }
 
-    async fn get_token(&mut self, _token_type: &str) -> Result<Vec<u8>> {
+    async fn get_token_of_type(&mut self, token_type: &str) -> Result<Vec<u8>> {
+        if let Some(pre_attested) = &self.pre_attested {
+            return pre_attested.token(token_type);
+        }
+
         #[cfg(feature = "cc_kbc")]
         {
-            let token = match _token_type {
-                "kbs" => get_kbs_token().await?,
+            // the measurement list keeps growing, so it is bound to every
+            // token as it is then
+            let mut claims = self.claims.clone();
+            if let Some(ima) = ima::collect(Path::new(ima::SECURITYFS_DIR)).await? {
+                claims.extend(ima.claims());
+            }
+
+            let token = match token_type {
+                "kbs" => {
+                    // a burst of requests is served by one attestation, see
+                    // [`token_cache`]
+                    let kbs_url = kbs_host_url(&self.config).await?;
+                    let now = token_cache::now();
+                    let key = self.token_cache.key(&kbs_url, &claims, now);
+                    match self.token_cache.get(&key, now) {
+                        Some((token, tee_keypair)) => token::message(&token, &tee_keypair)?,
+                        None => {
+                            let token = get_kbs_token(&claims, &self.config, &kbs_url).await?;
+                            self.token_cache.insert(
+                                key,
+                                &token.token.content,
+                                &token.tee_keypair,
+                                token.token.policies(),
+                                token.token.expires_at(),
+                                now,
+                            );
+                            token.message()?
+                        }
+                    }
+                }
                 typ => bail!("Unsupported token type {typ}"),
             };
 
@@ -193,25 +538,343 @@ impl AttestationAPIs for AttestationAgent {
         }
     }
 
+    #[allow(dead_code)]
+    fn check(&self, kbc_name: String) -> Result<KbcCheckInfo> {
+        self.kbc_instance_map
+            .get(&kbc_name)
+            .ok_or_else(|| anyhow!("The KBC instance does not exist!"))?
+            .check()
+    }
+}
+
+#[async_trait]
+impl AttestationAPIs for AttestationAgent {
+    async fn decrypt_image_layer_annotation(
+        &mut self,
+        kbc_name: &str,
+        kbs_uri: &str,
+        annotation: &str,
+    ) -> Result<Vec<u8>> {
+        let annotation: AnnotationPacket = serde_json::from_str(annotation)?;
+        let operation = Operation::DecryptAnnotation {
+            kbc: kbc_name.to_string(),
+            kid: annotation.kid.whole_uri(),
+        };
+
+        let res = self.decrypt_payload(kbc_name, kbs_uri, annotation).await;
+        self.audit.record(operation, &res);
+        res
+    }
+
+    async fn download_confidential_resource(
+        &mut self,
+        kbc_name: &str,
+        resource_path: &str,
+        kbs_uri: &str,
+    ) -> Result<Vec<u8>> {
+        let resource_uri = ResourceUri::new(kbs_uri, resource_path)?;
+        let operation = Operation::GetResource {
+            kbc: kbc_name.to_string(),
+            resource: resource_uri.whole_uri(),
+        };
+
+        let res = self.get_resource(kbc_name, kbs_uri, resource_uri).await;
+        self.audit.record(operation, &res);
+        res
+    }
+
+    async fn get_token(&mut self, token_type: &str) -> Result<Vec<u8>> {
+        let res = self.get_token_of_type(token_type).await;
+        self.audit.record(
+            Operation::GetToken {
+                token_type: token_type.to_string(),
+            },
+            &res,
+        );
+        res
+    }
+
     /// Get TEE hardware signed evidence that includes the runtime data.
     async fn get_evidence(&mut self, runtime_data: &[u8]) -> Result<Vec<u8>> {
-        let tee_type = detect_tee_type();
-        let attester = TryInto::<BoxedAttester>::try_into(tee_type)?;
-        let evidence = attester.get_evidence(runtime_data.to_vec()).await?;
-        Ok(evidence.into_bytes())
+        let res = async {
+            if let Some(evidence) = self.pre_attested.as_ref().and_then(|p| p.evidence()) {
+                return Ok(evidence.as_bytes().to_vec());
+            }
+
+            let tee_type = detect_tee_type();
+            let attester = TryInto::<BoxedAttester>::try_into(tee_type)?;
+            let evidence = attester.get_evidence(runtime_data.to_vec()).await?;
+            Ok(evidence.into_bytes())
+        }
+        .await;
+        self.audit.record(
+            Operation::GetEvidence {
+                runtime_data_digest: hex::encode(Sha256::digest(runtime_data)),
+            },
+            &res,
+        );
+        res
+    }
+
+    async fn get_challenge_evidence(
+        &mut self,
+        nonce: &str,
+        runtime_data: &[u8],
+    ) -> Result<ChallengeEvidence> {
+        let res = async {
+            let bound = challenge::runtime_data(nonce, runtime_data)?;
+            let tee_type = detect_tee_type();
+            let tee = serde_json::to_value(tee_type)?
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let attester = TryInto::<BoxedAttester>::try_into(tee_type)?;
+            let evidence = attester
+                .get_evidence(challenge::report_data(&bound))
+                .await?;
+            let ima = ima::collect(Path::new(ima::SECURITYFS_DIR)).await?;
+            Ok(ChallengeEvidence {
+                tee,
+                evidence: evidence.into_bytes(),
+                runtime_data: bound,
+                ima,
+            })
+        }
+        .await;
+        self.audit.record(
+            Operation::GetChallengeEvidence {
+                nonce: nonce.to_string(),
+                runtime_data_digest: hex::encode(Sha256::digest(runtime_data)),
+            },
+            &res,
+        );
+        res
     }
 
-    /// Extend runtime measurement register
+    /// Extend runtime measurement register. Configuration measurements are
+    /// refused, see [`AttestationAgent::extend_runtime_measurement_of`].
     async fn extend_runtime_measurement(
         &mut self,
         events: Vec<Vec<u8>>,
         register_index: Option<u64>,
     ) -> Result<()> {
+        self.extend_runtime_measurement_of(None, events, register_index)
+            .await
+    }
+
+    async fn provision_init_data(&mut self, init_data: &[u8]) -> Result<ProvisionedInitData> {
+        let (digest, entries) = initdata::parse(init_data)?;
+
+        if let Some(provisioned) = &self.init_data {
+            if provisioned.digest != digest {
+                bail!("init-data has already been provisioned with a different digest");
+            }
+
+            return Ok(provisioned.clone());
+        }
+
         let tee_type = detect_tee_type();
         let attester = TryInto::<BoxedAttester>::try_into(tee_type)?;
-        attester
-            .extend_runtime_measurement(events, register_index)
-            .await?;
-        Ok(())
+        let hardware_bound = match attester.bind_init_data(&digest).await? {
+            InitDataResult::Ok => true,
+            #[cfg(feature = "insecure-debug")]
+            InitDataResult::Unsupported => {
+                warn!("TEE cannot bind init-data, exposing it unverified");
+                false
+            }
+            #[cfg(not(feature = "insecure-debug"))]
+            InitDataResult::Unsupported => {
+                bail!("TEE cannot bind init-data to its launch measurement")
+            }
+        };
+
+        self.token_cache.clear();
+        let entries = initdata::expose(Path::new(INITDATA_DIR), init_data, &entries).await?;
+        let provisioned = ProvisionedInitData {
+            digest,
+            hardware_bound,
+            entries,
+        };
+        self.init_data = Some(provisioned.clone());
+
+        Ok(provisioned)
+    }
+
+    async fn register_claims(&mut self, claims: HashMap<String, String>) -> Result<()> {
+        claims::register(&mut self.claims, claims)
+    }
+
+    async fn set_ima_policy(&mut self, init_data_entry: &str) -> Result<()> {
+        let res = async {
+            let policy = self.init_data_entry(init_data_entry).await?;
+            self.token_cache.clear();
+            ima::set_policy(Path::new(ima::SECURITYFS_DIR), &policy).await
+        }
+        .await;
+        self.audit.record(
+            Operation::SetImaPolicy {
+                entry: init_data_entry.to_string(),
+            },
+            &res,
+        );
+        res
+    }
+
+    async fn derive_key(
+        &mut self,
+        purpose: &str,
+        context: &[u8],
+        per_boot: bool,
+        len: usize,
+    ) -> Result<Vec<u8>> {
+        let res = async {
+            let tee_type = detect_tee_type();
+            // the sealing key of the sample attester is public
+            if tee_type == Tee::Sample {
+                if !cfg!(feature = "insecure-debug") {
+                    bail!("no TEE to derive keys from");
+                }
+                warn!("key for {purpose} derived from the sample sealing key");
+            }
+            let attester = TryInto::<BoxedAttester>::try_into(tee_type)?;
+            let sealing_key = Zeroizing::new(attester.get_sealing_key().await?);
+            let boot_salt = per_boot.then_some(&self.boot_salt);
+            derived_key::derive(&sealing_key, purpose, context, boot_salt, len)
+        }
+        .await;
+        self.audit.record(
+            Operation::DeriveKey {
+                purpose: purpose.to_string(),
+                per_boot,
+            },
+            &res,
+        );
+        res
+    }
+
+    async fn export_audit_log(&mut self) -> Result<Vec<u8>> {
+        self.token_cache.clear();
+        let tee_type = detect_tee_type();
+        let anchored = match TryInto::<BoxedAttester>::try_into(tee_type) {
+            Ok(attester) => {
+                attester
+                    .extend_runtime_measurement(vec![self.audit.anchor_event()], None)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = anchored {
+            warn!("audit log head is not measured: {e:#}");
+        }
+
+        self.audit.export()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TokenCacheConfig;
+    use std::sync::Mutex;
+
+    // records the events extended, or fails like a TEE without a runtime
+    // measurement register
+    #[derive(Default)]
+    struct RecordingAttester {
+        events: Mutex<Vec<Vec<u8>>>,
+        unsupported: bool,
+    }
+
+    #[async_trait]
+    impl Attester for RecordingAttester {
+        async fn get_evidence(&self, _report_data: Vec<u8>) -> Result<String> {
+            bail!("no evidence")
+        }
+
+        async fn extend_runtime_measurement(
+            &self,
+            events: Vec<Vec<u8>>,
+            _register_index: Option<u64>,
+        ) -> Result<()> {
+            if self.unsupported {
+                bail!("Unimplemented")
+            }
+            self.events.lock().unwrap().extend(events);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_config() {
+        let mut aa = AttestationAgent::new();
+        #[cfg(feature = "sample_kbc")]
+        aa.instantiate_kbc("sample_kbc", "https://kbs.old:8080")
+            .unwrap();
+        aa.token_cache = TokenCache::new(TokenCacheConfig {
+            max_age_secs: 60,
+            ..Default::default()
+        });
+        let now = token_cache::now();
+        let key = aa
+            .token_cache
+            .key("https://kbs.old:8080", &Claims::new(), now);
+        aa.token_cache.insert(key, "token", "pem", &[], None, now);
+        assert_eq!(aa.token_cache.len(), 1);
+
+        let attester = RecordingAttester::default();
+        let config = Config::parse(r#"kbs_url = "https://kbs.new:8080""#).unwrap();
+        aa.apply_config(config.clone(), "sha256:01", &attester)
+            .await
+            .unwrap();
+        assert_eq!(aa.config(), &config);
+        assert!(aa.kbc_instance_map.is_empty());
+        assert!(aa.token_cache.is_empty());
+        assert_eq!(
+            *attester.events.lock().unwrap(),
+            [b"aa.reload_config:sha256:01".to_vec()]
+        );
+
+        // the config in effect is not measured again
+        aa.apply_config(config.clone(), "sha256:01", &attester)
+            .await
+            .unwrap();
+        assert_eq!(attester.events.lock().unwrap().len(), 1);
+
+        let http = Config::parse(r#"kbs_url = "http://kbs.new:8080""#).unwrap();
+        assert!(aa.apply_config(http, "sha256:02", &attester).await.is_err());
+        assert_eq!(aa.config(), &config);
+
+        let unmeasured = RecordingAttester {
+            unsupported: true,
+            ..Default::default()
+        };
+        let other = Config::parse(r#"kbs_url = "https://kbs.other:8080""#).unwrap();
+        let res = aa.apply_config(other, "sha256:03", &unmeasured).await;
+        if !cfg!(feature = "insecure-debug") {
+            assert!(res.is_err());
+            assert_eq!(aa.config(), &config);
+        }
+    }
+
+    // a token handed out again parses like a fresh one in the token
+    // provider of CDH, with the key pair it is bound to
+    #[cfg(feature = "cc_kbc")]
+    #[test]
+    fn test_cached_token_reply() {
+        let tee_keypair = kbs_protocol::TeeKeyPair::new().unwrap();
+        let pem = tee_keypair.to_pkcs1_pem().unwrap();
+        let mut cache = TokenCache::new(TokenCacheConfig {
+            max_age_secs: 60,
+            ..Default::default()
+        });
+        let now = token_cache::now();
+        let key = cache.key("https://kbs.example.com:8080", &Claims::new(), now);
+        cache.insert(key.clone(), "e30.e30.sig", &pem, &[], None, now);
+
+        let (token, cached_pem) = cache.get(&key, now).unwrap();
+        let reply = token::message(&token, &cached_pem).unwrap();
+        let (token, tee_keypair) = kbs_protocol::token_provider::parse_token_reply(&reply).unwrap();
+        assert_eq!(token.content, "e30.e30.sig");
+        assert_eq!(*tee_keypair.to_pkcs1_pem().unwrap(), *pem);
     }
 }
@@ -0,0 +1,149 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Pre-attested mode, where the attestation token, and optionally the
+//! evidence, AA hands out are loaded from a file instead of being got from
+//! the KBS with TEE evidence. This lets integration tests and development
+//! environments without TEE hardware exercise the whole chain from AA to
+//! CDH and image-rs.
+//!
+//! The file is a JSON object like
+//!
+//! ```json
+//! {
+//!     "token": "<JWT issued by the KBS>",
+//!     "tee_keypair": "<PKCS#1 PEM of the key pair the token is bound to>",
+//!     "evidence": "<sample evidence>"
+//! }
+//! ```
+//!
+//! where `evidence` is optional. Anyone able to write the file can make AA
+//! return any token, so the mode must be enabled explicitly as insecure.
+//! The SHA-256 digest of the file is extended into the runtime measurement
+//! register when the TEE supports it, and recorded in the audit log, so the
+//! use of a pre-attested file cannot go unnoticed.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// Prefix of the runtime measurement event of a pre-attested file, followed
+/// by the hex SHA-256 digest of the file.
+pub const PRE_ATTESTED_EVENT_PREFIX: &str = "aa.pre_attested:";
+
+/// Token, and the key pair it is bound to, as returned for the `kbs` token
+/// type.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct TokenMessage {
+    token: String,
+    tee_keypair: String,
+}
+
+#[derive(Deserialize)]
+struct PreAttestedFile {
+    #[serde(flatten)]
+    token: TokenMessage,
+    evidence: Option<String>,
+}
+
+/// Attestation material loaded from a pre-attested file.
+#[derive(Clone, Debug)]
+pub struct PreAttested {
+    /// Hex SHA-256 digest of the file.
+    pub digest: String,
+
+    token: TokenMessage,
+    evidence: Option<String>,
+}
+
+impl PreAttested {
+    /// Parse the content of a pre-attested file.
+    pub fn parse(content: &[u8]) -> Result<Self> {
+        let file: PreAttestedFile =
+            serde_json::from_slice(content).context("parse pre-attested file")?;
+        if file.token.token.split('.').count() != 3 {
+            bail!("pre-attested token is not a JWT");
+        }
+
+        if file.token.tee_keypair.is_empty() {
+            bail!("pre-attested tee_keypair must not be empty");
+        }
+
+        Ok(Self {
+            digest: hex::encode(Sha256::digest(content)),
+            token: file.token,
+            evidence: file.evidence,
+        })
+    }
+
+    /// Read and parse the pre-attested file at `path`.
+    pub async fn load(path: &Path) -> Result<Self> {
+        let content = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("read pre-attested file {}", path.display()))?;
+        Self::parse(&content)
+    }
+
+    /// The runtime measurement event of the file.
+    pub fn event(&self) -> Vec<u8> {
+        format!("{PRE_ATTESTED_EVENT_PREFIX}{}", self.digest).into_bytes()
+    }
+
+    /// The token of `token_type`, in the format real tokens are returned in.
+    pub fn token(&self, token_type: &str) -> Result<Vec<u8>> {
+        match token_type {
+            "kbs" => Ok(serde_json::to_vec(&self.token)?),
+            typ => bail!("Unsupported token type {typ}"),
+        }
+    }
+
+    /// The pre-generated evidence, if the file has some.
+    pub fn evidence(&self) -> Option<&str> {
+        self.evidence.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "eyJhbGciOiJFUzI1NiJ9.eyJleHAiOjIwMDYxNDk2MTh9.c2ln";
+
+    #[test]
+    fn test_parse() {
+        let content = format!(r#"{{"token":"{TOKEN}","tee_keypair":"pem"}}"#);
+        let pre_attested = PreAttested::parse(content.as_bytes()).unwrap();
+        assert_eq!(
+            pre_attested.digest,
+            hex::encode(Sha256::digest(content.as_bytes()))
+        );
+        assert_eq!(pre_attested.evidence(), None);
+        assert_eq!(
+            pre_attested.event(),
+            format!("aa.pre_attested:{}", pre_attested.digest).into_bytes()
+        );
+
+        let token: serde_json::Value =
+            serde_json::from_slice(&pre_attested.token("kbs").unwrap()).unwrap();
+        assert_eq!(token["token"], TOKEN);
+        assert_eq!(token["tee_keypair"], "pem");
+        assert!(pre_attested.token("other").is_err());
+
+        let content =
+            format!(r#"{{"token":"{TOKEN}","tee_keypair":"pem","evidence":"{{\"svn\":\"1\"}}"}}"#);
+        let pre_attested = PreAttested::parse(content.as_bytes()).unwrap();
+        assert_eq!(pre_attested.evidence(), Some(r#"{"svn":"1"}"#));
+
+        for content in [
+            "".to_string(),
+            r#"{"tee_keypair":"pem"}"#.to_string(),
+            r#"{"token":"not a jwt","tee_keypair":"pem"}"#.to_string(),
+            format!(r#"{{"token":"{TOKEN}","tee_keypair":""}}"#),
+        ] {
+            assert!(PreAttested::parse(content.as_bytes()).is_err(), "{content}");
+        }
+    }
+}
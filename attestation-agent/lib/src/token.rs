@@ -4,26 +4,56 @@
 //
 
 use anyhow::{anyhow, Context, Result};
-use kbs_protocol::{evidence_provider::NativeEvidenceProvider, KbsClientBuilder};
+use kbs_protocol::{
+    evidence_provider::NativeEvidenceProvider, token_provider::Token, KbsClientBuilder,
+};
 use log::debug;
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::path::Path;
 use std::sync::OnceLock;
 use tokio::fs;
+use zeroize::Zeroizing;
+
+use crate::claims::Claims;
+use crate::config::ResolvedConfig;
 
 const PEER_POD_CONFIG_PATH: &str = "/run/peerpod/daemon.json";
 
 #[derive(Serialize)]
-struct Message {
-    token: String,
-    tee_keypair: String,
+struct Message<'a> {
+    token: &'a str,
+    tee_keypair: &'a str,
+}
+
+/// A token got from the KBS, with the TEE key pair it is bound to.
+pub(crate) struct KbsToken {
+    pub token: Token,
+    pub tee_keypair: Zeroizing<String>,
+}
+
+impl KbsToken {
+    /// The `GetToken` response for the caller the token was got for.
+    pub fn message(&self) -> Result<Vec<u8>> {
+        message(&self.token.content, &self.tee_keypair)
+    }
+}
+
+/// The `GetToken` response for `token`, with the PEM of the TEE key pair it
+/// is bound to.
+pub(crate) fn message(token: &str, tee_keypair: &str) -> Result<Vec<u8>> {
+    let message = Message { token, tee_keypair };
+    Ok(serde_json::to_vec(&message)?)
 }
 
 static KATA_AGENT_CONFIG_PATH: OnceLock<String> = OnceLock::new();
 
-pub(crate) async fn get_kbs_token() -> Result<Vec<u8>> {
-    let evidence_provider = Box::new(NativeEvidenceProvider::new()?);
+/// The URL of the KBS the tokens are got from: the one of the config if
+/// any, the one of `aa_kbc_params` otherwise.
+pub(crate) async fn kbs_host_url(config: &ResolvedConfig) -> Result<String> {
+    if let Some(url) = &config.config.kbs_url {
+        return Ok(url.clone());
+    }
 
     // Check for /run/peerpod/daemon.json to see if we are in a peer pod
     // If so we need to read from the agent-config file, not /proc/cmdline
@@ -32,19 +62,27 @@ pub(crate) async fn get_kbs_token() -> Result<Vec<u8>> {
         false => get_kbc_params_from_cmdline().await?,
     };
 
-    let kbs_host_url = extract_kbs_host_url(&kbc_params)?;
+    extract_kbs_host_url(&kbc_params)
+}
+
+/// Get a token from the KBS at `kbs_host_url`.
+pub(crate) async fn get_kbs_token(
+    claims: &Claims,
+    config: &ResolvedConfig,
+    kbs_host_url: &str,
+) -> Result<KbsToken> {
+    let evidence_provider = Box::new(NativeEvidenceProvider::new()?);
 
-    let mut client =
-        KbsClientBuilder::with_evidence_provider(evidence_provider, &kbs_host_url).build()?;
+    let mut builder = KbsClientBuilder::with_evidence_provider(evidence_provider, kbs_host_url)
+        .set_claims(claims.clone());
+    for cert in &config.kbs_certs {
+        builder = builder.add_kbs_cert(cert);
+    }
+    let mut client = builder.build()?;
 
     let (token, tee_keypair) = client.get_token().await?;
-    let message = Message {
-        token: token.content,
-        tee_keypair: tee_keypair.to_pkcs1_pem()?.to_string(),
-    };
-
-    let res = serde_json::to_vec(&message)?;
-    Ok(res)
+    let tee_keypair = tee_keypair.to_pkcs1_pem()?;
+    Ok(KbsToken { token, tee_keypair })
 }
 
 fn extract_kbs_host_url(kbc_params: &str) -> Result<String> {
@@ -0,0 +1,281 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Cache of the attestation results, the tokens got from the KBS.
+//!
+//! CDH asks AA for a token for every resource it gets, so a burst of
+//! resources, e.g. at the start of a pod, used to cost as many RCAR
+//! handshakes, each with fresh evidence, which takes seconds on platforms
+//! with slow quotes like SGX. With `max_age_secs` set, the tokens got are
+//! cached instead, and handed out again for the requests with the same key:
+//!
+//! - the URL of the KBS,
+//! - the digest of the claims bound to the token, so that a new claim, or a
+//!   longer IMA measurement list, gets a new token,
+//! - the freshness window the evidence of the token was taken in: the time
+//!   is cut into windows of `freshness_window_secs`, and a token is not
+//!   handed out in the next window.
+//!
+//! On top of this, a token is deliberately not handed out once
+//! `max_age_secs` old, nor once expired, so that the attestation of a guest
+//! is never staler than that. Failed attestations are not cached.
+//!
+//! The KBS may change its policies at any time. A token got from a KBS
+//! drops the ones cached for it that name other policies in their claims,
+//! e.g. `ear.appraisal-policy-id`, so that tokens appraised with the old
+//! policies are not handed out any more.
+//!
+//! A token is of no use without the TEE key pair it is bound to, so the
+//! key pair is cached with it and handed out with it again. Both are
+//! zeroized once dropped: when stale, replaced, or when the cache is
+//! cleared. The whole cache is dropped whenever the measurements the tokens
+//! attest change: a runtime measurement extended, init-data provisioned or
+//! an IMA policy set.
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+use crate::claims::Claims;
+use crate::config::TokenCacheConfig;
+
+/// What a cached token is handed out again for, see the
+/// [module docs](self).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TokenCacheKey {
+    kbs_url: String,
+    claims_digest: String,
+    window: u64,
+}
+
+impl TokenCacheKey {
+    /// The key of the tokens got from the KBS at `kbs_url` with `claims`
+    /// at `now`.
+    pub fn new(config: &TokenCacheConfig, kbs_url: &str, claims: &Claims, now: u64) -> Self {
+        let mut hasher = Sha256::new();
+        for (name, value) in claims {
+            // lengths first, so that the boundaries are part of the digest
+            hasher.update((name.len() as u64).to_be_bytes());
+            hasher.update(name);
+            hasher.update((value.len() as u64).to_be_bytes());
+            hasher.update(value);
+        }
+
+        Self {
+            kbs_url: kbs_url.to_string(),
+            claims_digest: hex::encode(hasher.finalize()),
+            window: now / config.freshness_window_secs.max(1),
+        }
+    }
+}
+
+struct CachedToken {
+    token: Zeroizing<String>,
+    tee_keypair: Zeroizing<String>,
+    policies: Vec<String>,
+    cached_at: u64,
+    expires_at: Option<u64>,
+}
+
+impl CachedToken {
+    fn is_fresh(&self, max_age_secs: u64, now: u64) -> bool {
+        let expired = matches!(self.expires_at, Some(expires_at) if expires_at <= now);
+        now.saturating_sub(self.cached_at) < max_age_secs && !expired
+    }
+}
+
+/// Tokens got from the KBS, see the [module docs](self).
+pub struct TokenCache {
+    config: TokenCacheConfig,
+    tokens: HashMap<TokenCacheKey, CachedToken>,
+}
+
+impl TokenCache {
+    pub fn new(config: TokenCacheConfig) -> Self {
+        Self {
+            config,
+            tokens: HashMap::new(),
+        }
+    }
+
+    /// The key of the tokens got from the KBS at `kbs_url` with `claims`
+    /// at `now`.
+    pub fn key(&self, kbs_url: &str, claims: &Claims, now: u64) -> TokenCacheKey {
+        TokenCacheKey::new(&self.config, kbs_url, claims, now)
+    }
+
+    /// The token cached for `key`, with the PEM of the TEE key pair it is
+    /// bound to, unless it is stale at `now`. The stale tokens are dropped.
+    pub fn get(&mut self, key: &TokenCacheKey, now: u64) -> Option<(String, Zeroizing<String>)> {
+        let max_age_secs = self.config.max_age_secs;
+        self.tokens
+            .retain(|k, token| k.window == key.window && token.is_fresh(max_age_secs, now));
+        self.tokens
+            .get(key)
+            .map(|token| (token.token.to_string(), token.tee_keypair.clone()))
+    }
+
+    /// Cache `token` got at `now` for `key`, with the PEM of the TEE key
+    /// pair it is bound to, until `expires_at` at the latest. `policies` are
+    /// the ones the token was appraised with: the tokens cached for the same
+    /// KBS with other policies are dropped.
+    pub fn insert(
+        &mut self,
+        key: TokenCacheKey,
+        token: &str,
+        tee_keypair: &str,
+        policies: &[String],
+        expires_at: Option<u64>,
+        now: u64,
+    ) {
+        if self.config.max_age_secs == 0 {
+            return;
+        }
+
+        self.tokens
+            .retain(|k, token| k.kbs_url != key.kbs_url || token.policies == policies);
+        let token = CachedToken {
+            token: Zeroizing::new(token.to_string()),
+            tee_keypair: Zeroizing::new(tee_keypair.to_string()),
+            policies: policies.to_vec(),
+            cached_at: now,
+            expires_at,
+        };
+        self.tokens.insert(key, token);
+    }
+
+    /// Drop all the cached tokens.
+    pub fn clear(&mut self) {
+        self.tokens.clear();
+    }
+
+    /// Number of the cached tokens, stale ones included.
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
+
+impl Default for TokenCache {
+    fn default() -> Self {
+        Self::new(TokenCacheConfig::default())
+    }
+}
+
+/// Seconds since the epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KBS: &str = "https://kbs.example.com:8080";
+
+    fn enabled() -> TokenCacheConfig {
+        TokenCacheConfig {
+            max_age_secs: 60,
+            ..Default::default()
+        }
+    }
+
+    fn claims(pairs: &[(&str, &str)]) -> Claims {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_key() {
+        let config = TokenCacheConfig::default();
+        let image = claims(&[("image", "sha256:1")]);
+        let key = TokenCacheKey::new(&config, KBS, &image, 600);
+        assert_eq!(key, TokenCacheKey::new(&config, KBS, &image, 899));
+
+        // next window
+        assert_ne!(key, TokenCacheKey::new(&config, KBS, &image, 900));
+        assert_ne!(
+            key,
+            TokenCacheKey::new(&config, "https://other.example.com", &image, 600)
+        );
+        assert_ne!(key, TokenCacheKey::new(&config, KBS, &Claims::new(), 600));
+        assert_ne!(
+            TokenCacheKey::new(&config, KBS, &claims(&[("ab", "c")]), 600),
+            TokenCacheKey::new(&config, KBS, &claims(&[("a", "bc")]), 600)
+        );
+    }
+
+    #[test]
+    fn test_get() {
+        let mut cache = TokenCache::new(enabled());
+        let key = cache.key(KBS, &Claims::new(), 600);
+        assert_eq!(cache.get(&key, 600), None);
+
+        cache.insert(key.clone(), "token", "pem", &[], None, 600);
+        let (token, tee_keypair) = cache.get(&key, 659).unwrap();
+        assert_eq!(token, "token");
+        assert_eq!(*tee_keypair, "pem");
+
+        // older than max_age_secs
+        assert_eq!(cache.get(&key, 660), None);
+        assert!(cache.is_empty());
+
+        // expired
+        cache.insert(key.clone(), "token", "pem", &[], Some(630), 600);
+        assert_eq!(cache.get(&key, 629).unwrap().0, "token");
+        assert_eq!(cache.get(&key, 630), None);
+
+        // the tokens of the windows over are dropped
+        cache.insert(key.clone(), "token", "pem", &[], None, 890);
+        let next = cache.key(KBS, &Claims::new(), 900);
+        assert_eq!(cache.get(&next, 900), None);
+        assert!(cache.is_empty());
+
+        cache.insert(key.clone(), "token", "pem", &[], None, 600);
+        cache.clear();
+        assert_eq!(cache.get(&key, 600), None);
+    }
+
+    #[test]
+    fn test_policies() {
+        let mut cache = TokenCache::new(enabled());
+        let key = cache.key(KBS, &Claims::new(), 600);
+        let image = cache.key(KBS, &claims(&[("image", "sha256:1")]), 600);
+        let other = cache.key("https://other.example.com", &Claims::new(), 600);
+        let old = ["policy-id=default".to_string()];
+        cache.insert(key.clone(), "token", "pem", &old, None, 600);
+        cache.insert(other.clone(), "other", "pem", &[], None, 600);
+
+        // the same policies
+        cache.insert(image.clone(), "image", "pem", &old, None, 610);
+        assert_eq!(cache.len(), 3);
+
+        // the KBS changed its policies
+        let new = ["policy-id=new".to_string()];
+        cache.insert(image.clone(), "new", "pem", &new, None, 620);
+        assert_eq!(cache.get(&key, 620), None);
+        assert_eq!(cache.get(&image, 620).unwrap().0, "new");
+        assert_eq!(cache.get(&other, 620).unwrap().0, "other");
+    }
+
+    #[test]
+    fn test_disabled() {
+        // opt-in
+        let mut cache = TokenCache::default();
+        let key = cache.key(KBS, &Claims::new(), 600);
+        cache.insert(key.clone(), "token", "pem", &[], None, 600);
+        assert_eq!(cache.get(&key, 600), None);
+        assert_eq!(cache.len(), 0);
+    }
+}
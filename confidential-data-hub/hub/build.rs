@@ -44,4 +44,12 @@ fn main() {
             "client",
         );
     }
+
+    #[cfg(feature = "grpc")]
+    tonic_build::configure()
+        .build_server(true)
+        .build_client(false)
+        .protoc_arg("--experimental_allow_proto3_optional")
+        .compile(&["./protos/api.proto"], &["./protos"])
+        .expect("Generate gRPC protocol code failed.");
 }
@@ -5,6 +5,14 @@
 
 use async_trait::async_trait;
 
+#[cfg(feature = "image-pull")]
+use crate::image_pull::{ImagePullOptions, PulledImage};
+use crate::inject::InjectionManifest;
+#[cfg(feature = "key-service")]
+use crate::keys::{GeneratedKey, KeyRequest};
+#[cfg(feature = "secret-dirs")]
+use crate::secret_dirs::{SecretDirMount, SecretDirRequest};
+use crate::spool::{ResourceChunk, ResourceInfo};
 use crate::Result;
 use storage::volume_type::Storage;
 
@@ -23,10 +31,106 @@ pub trait DataHub {
     /// <https://github.com/confidential-containers/guest-components/blob/main/attestation-agent/docs/IMAGE_ENCRYPTION.md#annotation-packet>
     async fn unwrap_key(&self, annotation: &[u8]) -> Result<Vec<u8>>;
 
-    /// Get the resource due to the given KBS Resource URI. The KBS Resource
-    /// URI is defined in
-    /// <https://github.com/confidential-containers/guest-components/blob/main/attestation-agent/docs/KBS_URI.md>
+    /// Get the resource due to the given resource URI. Besides the KBS
+    /// Resource URI defined in
+    /// <https://github.com/confidential-containers/guest-components/blob/main/attestation-agent/docs/KBS_URI.md>,
+    /// the schemes of [`crate::resource`] are supported.
     async fn get_resource(&self, uri: String) -> Result<Vec<u8>>;
 
+    /// Get the chunk of at most `length` bytes at `offset` of the resource
+    /// of the given URI, see [`crate::spool`]. If `digest` is given, the
+    /// retrieval fails if the resource does not have it any more.
+    async fn get_resource_chunk(
+        &self,
+        uri: String,
+        offset: u64,
+        length: u32,
+        digest: Option<String>,
+    ) -> Result<ResourceChunk>;
+
+    /// Write the resource of the given URI to the file `target`, failing
+    /// without touching `target` if `digest` is given and the resource
+    /// does not have it.
+    async fn fetch_resource_to_file(
+        &self,
+        uri: String,
+        target: &str,
+        digest: Option<String>,
+    ) -> Result<ResourceInfo>;
+
+    /// Drop the cached result of `get_resource` for the given resource URI,
+    /// or all the cached results if `uri` is `None`, so that the next
+    /// `get_resource` fetches it again. The spooled resources of
+    /// `get_resource_chunk` are dropped as well. Returns the number of
+    /// dropped entries.
+    async fn invalidate_resource(&self, uri: Option<String>) -> Result<usize>;
+
     async fn secure_mount(&self, storage: Storage) -> Result<String>;
+
+    /// Tear down the secure mount at `mount_point` made by `secure_mount`,
+    /// including the device mapper mappings and the key files it set up.
+    /// The secure mounts are tracked across restarts of CDH, see
+    /// [`storage::mounts`].
+    async fn secure_unmount(&self, mount_point: &str) -> Result<()>;
+
+    /// Tear down the secure mount at `mount_point` and mount it again with
+    /// the request it was made with. Returns the mount point.
+    async fn secure_remount(&self, mount_point: &str) -> Result<String>;
+
+    /// Materialize the secrets of `manifest` as files under `rootfs`, see
+    /// [`crate::inject`]. Returns the paths of the injected files inside
+    /// the rootfs.
+    async fn inject_secrets(
+        &self,
+        manifest: InjectionManifest,
+        rootfs: &str,
+    ) -> Result<Vec<String>>;
+
+    /// Provision the secret dir of a container with the secrets of the
+    /// manifest of `request`, see [`crate::secret_dirs`]. Returns the bind
+    /// mount of the dir into the container.
+    #[cfg(feature = "secret-dirs")]
+    async fn provision_secret_dir(&self, request: SecretDirRequest) -> Result<SecretDirMount>;
+
+    /// Unmount and remove the secret dir of the container `container_id`.
+    #[cfg(feature = "secret-dirs")]
+    async fn remove_secret_dir(&self, container_id: &str) -> Result<()>;
+
+    /// Generate a key pair inside the TEE, see [`crate::keys`]. Returns the
+    /// CSR of the key pair and the evidence binding it to the TEE, the
+    /// private key is never returned.
+    #[cfg(feature = "key-service")]
+    async fn generate_key(&self, request: KeyRequest) -> Result<GeneratedKey>;
+
+    /// Install the certificate chain issued for the key pair `key_id` by
+    /// the caller `caller_uid` which generated it. Returns the dir holding
+    /// the key pair and its certificate.
+    #[cfg(feature = "key-service")]
+    async fn install_certificate(
+        &self,
+        caller_uid: u32,
+        key_id: &str,
+        certificate: &[u8],
+    ) -> Result<String>;
+
+    /// Pull the given image and create its bundle (rootfs and
+    /// `config.json`) under `bundle_path`. Unset `options` fall back to the
+    /// image-rs configuration of the CDH. Returns the image id, and the
+    /// report of the signature verification if the image was verified.
+    #[cfg(feature = "image-pull")]
+    async fn pull_image(
+        &self,
+        image_url: &str,
+        bundle_path: &str,
+        options: ImagePullOptions,
+    ) -> Result<PulledImage>;
+
+    /// Keep the layers of the image pulled with `image_ref`, or of the
+    /// image id, from being evicted from the layer store of image-rs.
+    #[cfg(feature = "image-pull")]
+    async fn pin_image(&self, image_ref: &str) -> Result<()>;
+
+    /// Undo `pin_image`. Returns whether the image was pinned.
+    #[cfg(feature = "image-pull")]
+    async fn unpin_image(&self, image_ref: &str) -> Result<bool>;
 }
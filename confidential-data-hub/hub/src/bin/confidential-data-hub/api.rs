@@ -1,4 +1,4 @@
-// This file is generated by rust-protobuf 3.3.0. Do not edit
+// This file is generated by rust-protobuf 3.7.2. Do not edit
 // .proto file is parsed by pure
 // @generated
 
@@ -9,7 +9,6 @@
 #![allow(unused_attributes)]
 #![cfg_attr(rustfmt, rustfmt::skip)]
 
-#![allow(box_pointers)]
 #![allow(dead_code)]
 #![allow(missing_docs)]
 #![allow(non_camel_case_types)]
@@ -23,7 +22,7 @@
 
 /// Generated files are compatible only with the same version
 /// of protobuf runtime.
-const _PROTOBUF_VERSION_CHECK: () = ::protobuf::VERSION_3_3_0;
+const _PROTOBUF_VERSION_CHECK: () = ::protobuf::VERSION_3_7_2;
 
 // @@protoc_insertion_point(message:api.UnsealSecretInput)
 #[derive(PartialEq,Clone,Default,Debug)]
@@ -513,81 +512,3776 @@ impl ::protobuf::reflect::ProtobufValue for GetResourceResponse {
     type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
 }
 
+// @@protoc_insertion_point(message:api.InvalidateResourceRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct InvalidateResourceRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.InvalidateResourceRequest.ResourcePath)
+    pub ResourcePath: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.InvalidateResourceRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a InvalidateResourceRequest {
+    fn default() -> &'a InvalidateResourceRequest {
+        <InvalidateResourceRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl InvalidateResourceRequest {
+    pub fn new() -> InvalidateResourceRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "ResourcePath",
+            |m: &InvalidateResourceRequest| { &m.ResourcePath },
+            |m: &mut InvalidateResourceRequest| { &mut m.ResourcePath },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<InvalidateResourceRequest>(
+            "InvalidateResourceRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for InvalidateResourceRequest {
+    const NAME: &'static str = "InvalidateResourceRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.ResourcePath = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.ResourcePath.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.ResourcePath);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.ResourcePath.is_empty() {
+            os.write_string(1, &self.ResourcePath)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> InvalidateResourceRequest {
+        InvalidateResourceRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.ResourcePath.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static InvalidateResourceRequest {
+        static instance: InvalidateResourceRequest = InvalidateResourceRequest {
+            ResourcePath: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for InvalidateResourceRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("InvalidateResourceRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for InvalidateResourceRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for InvalidateResourceRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.InvalidateResourceResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct InvalidateResourceResponse {
+    // message fields
+    // @@protoc_insertion_point(field:api.InvalidateResourceResponse.Invalidated)
+    pub Invalidated: u32,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.InvalidateResourceResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a InvalidateResourceResponse {
+    fn default() -> &'a InvalidateResourceResponse {
+        <InvalidateResourceResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl InvalidateResourceResponse {
+    pub fn new() -> InvalidateResourceResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Invalidated",
+            |m: &InvalidateResourceResponse| { &m.Invalidated },
+            |m: &mut InvalidateResourceResponse| { &mut m.Invalidated },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<InvalidateResourceResponse>(
+            "InvalidateResourceResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for InvalidateResourceResponse {
+    const NAME: &'static str = "InvalidateResourceResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                8 => {
+                    self.Invalidated = is.read_uint32()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if self.Invalidated != 0 {
+            my_size += ::protobuf::rt::uint32_size(1, self.Invalidated);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if self.Invalidated != 0 {
+            os.write_uint32(1, self.Invalidated)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> InvalidateResourceResponse {
+        InvalidateResourceResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.Invalidated = 0;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static InvalidateResourceResponse {
+        static instance: InvalidateResourceResponse = InvalidateResourceResponse {
+            Invalidated: 0,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for InvalidateResourceResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("InvalidateResourceResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for InvalidateResourceResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for InvalidateResourceResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.GetResourceChunkRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GetResourceChunkRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.GetResourceChunkRequest.ResourcePath)
+    pub ResourcePath: ::std::string::String,
+    // @@protoc_insertion_point(field:api.GetResourceChunkRequest.Offset)
+    pub Offset: u64,
+    // @@protoc_insertion_point(field:api.GetResourceChunkRequest.Length)
+    pub Length: u32,
+    // @@protoc_insertion_point(field:api.GetResourceChunkRequest.Digest)
+    pub Digest: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.GetResourceChunkRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GetResourceChunkRequest {
+    fn default() -> &'a GetResourceChunkRequest {
+        <GetResourceChunkRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GetResourceChunkRequest {
+    pub fn new() -> GetResourceChunkRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(4);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "ResourcePath",
+            |m: &GetResourceChunkRequest| { &m.ResourcePath },
+            |m: &mut GetResourceChunkRequest| { &mut m.ResourcePath },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Offset",
+            |m: &GetResourceChunkRequest| { &m.Offset },
+            |m: &mut GetResourceChunkRequest| { &mut m.Offset },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Length",
+            |m: &GetResourceChunkRequest| { &m.Length },
+            |m: &mut GetResourceChunkRequest| { &mut m.Length },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Digest",
+            |m: &GetResourceChunkRequest| { &m.Digest },
+            |m: &mut GetResourceChunkRequest| { &mut m.Digest },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetResourceChunkRequest>(
+            "GetResourceChunkRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GetResourceChunkRequest {
+    const NAME: &'static str = "GetResourceChunkRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.ResourcePath = is.read_string()?;
+                },
+                16 => {
+                    self.Offset = is.read_uint64()?;
+                },
+                24 => {
+                    self.Length = is.read_uint32()?;
+                },
+                34 => {
+                    self.Digest = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.ResourcePath.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.ResourcePath);
+        }
+        if self.Offset != 0 {
+            my_size += ::protobuf::rt::uint64_size(2, self.Offset);
+        }
+        if self.Length != 0 {
+            my_size += ::protobuf::rt::uint32_size(3, self.Length);
+        }
+        if !self.Digest.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.Digest);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.ResourcePath.is_empty() {
+            os.write_string(1, &self.ResourcePath)?;
+        }
+        if self.Offset != 0 {
+            os.write_uint64(2, self.Offset)?;
+        }
+        if self.Length != 0 {
+            os.write_uint32(3, self.Length)?;
+        }
+        if !self.Digest.is_empty() {
+            os.write_string(4, &self.Digest)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GetResourceChunkRequest {
+        GetResourceChunkRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.ResourcePath.clear();
+        self.Offset = 0;
+        self.Length = 0;
+        self.Digest.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GetResourceChunkRequest {
+        static instance: GetResourceChunkRequest = GetResourceChunkRequest {
+            ResourcePath: ::std::string::String::new(),
+            Offset: 0,
+            Length: 0,
+            Digest: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for GetResourceChunkRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GetResourceChunkRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GetResourceChunkRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GetResourceChunkRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.GetResourceChunkResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GetResourceChunkResponse {
+    // message fields
+    // @@protoc_insertion_point(field:api.GetResourceChunkResponse.Data)
+    pub Data: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:api.GetResourceChunkResponse.TotalSize)
+    pub TotalSize: u64,
+    // @@protoc_insertion_point(field:api.GetResourceChunkResponse.Digest)
+    pub Digest: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.GetResourceChunkResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GetResourceChunkResponse {
+    fn default() -> &'a GetResourceChunkResponse {
+        <GetResourceChunkResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GetResourceChunkResponse {
+    pub fn new() -> GetResourceChunkResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(3);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Data",
+            |m: &GetResourceChunkResponse| { &m.Data },
+            |m: &mut GetResourceChunkResponse| { &mut m.Data },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "TotalSize",
+            |m: &GetResourceChunkResponse| { &m.TotalSize },
+            |m: &mut GetResourceChunkResponse| { &mut m.TotalSize },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Digest",
+            |m: &GetResourceChunkResponse| { &m.Digest },
+            |m: &mut GetResourceChunkResponse| { &mut m.Digest },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetResourceChunkResponse>(
+            "GetResourceChunkResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GetResourceChunkResponse {
+    const NAME: &'static str = "GetResourceChunkResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.Data = is.read_bytes()?;
+                },
+                16 => {
+                    self.TotalSize = is.read_uint64()?;
+                },
+                26 => {
+                    self.Digest = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.Data.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(1, &self.Data);
+        }
+        if self.TotalSize != 0 {
+            my_size += ::protobuf::rt::uint64_size(2, self.TotalSize);
+        }
+        if !self.Digest.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.Digest);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.Data.is_empty() {
+            os.write_bytes(1, &self.Data)?;
+        }
+        if self.TotalSize != 0 {
+            os.write_uint64(2, self.TotalSize)?;
+        }
+        if !self.Digest.is_empty() {
+            os.write_string(3, &self.Digest)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GetResourceChunkResponse {
+        GetResourceChunkResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.Data.clear();
+        self.TotalSize = 0;
+        self.Digest.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GetResourceChunkResponse {
+        static instance: GetResourceChunkResponse = GetResourceChunkResponse {
+            Data: ::std::vec::Vec::new(),
+            TotalSize: 0,
+            Digest: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for GetResourceChunkResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GetResourceChunkResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GetResourceChunkResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GetResourceChunkResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.FetchResourceToFileRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct FetchResourceToFileRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.FetchResourceToFileRequest.ResourcePath)
+    pub ResourcePath: ::std::string::String,
+    // @@protoc_insertion_point(field:api.FetchResourceToFileRequest.TargetPath)
+    pub TargetPath: ::std::string::String,
+    // @@protoc_insertion_point(field:api.FetchResourceToFileRequest.Digest)
+    pub Digest: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.FetchResourceToFileRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a FetchResourceToFileRequest {
+    fn default() -> &'a FetchResourceToFileRequest {
+        <FetchResourceToFileRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl FetchResourceToFileRequest {
+    pub fn new() -> FetchResourceToFileRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(3);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "ResourcePath",
+            |m: &FetchResourceToFileRequest| { &m.ResourcePath },
+            |m: &mut FetchResourceToFileRequest| { &mut m.ResourcePath },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "TargetPath",
+            |m: &FetchResourceToFileRequest| { &m.TargetPath },
+            |m: &mut FetchResourceToFileRequest| { &mut m.TargetPath },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Digest",
+            |m: &FetchResourceToFileRequest| { &m.Digest },
+            |m: &mut FetchResourceToFileRequest| { &mut m.Digest },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<FetchResourceToFileRequest>(
+            "FetchResourceToFileRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for FetchResourceToFileRequest {
+    const NAME: &'static str = "FetchResourceToFileRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.ResourcePath = is.read_string()?;
+                },
+                18 => {
+                    self.TargetPath = is.read_string()?;
+                },
+                26 => {
+                    self.Digest = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.ResourcePath.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.ResourcePath);
+        }
+        if !self.TargetPath.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.TargetPath);
+        }
+        if !self.Digest.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.Digest);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.ResourcePath.is_empty() {
+            os.write_string(1, &self.ResourcePath)?;
+        }
+        if !self.TargetPath.is_empty() {
+            os.write_string(2, &self.TargetPath)?;
+        }
+        if !self.Digest.is_empty() {
+            os.write_string(3, &self.Digest)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> FetchResourceToFileRequest {
+        FetchResourceToFileRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.ResourcePath.clear();
+        self.TargetPath.clear();
+        self.Digest.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static FetchResourceToFileRequest {
+        static instance: FetchResourceToFileRequest = FetchResourceToFileRequest {
+            ResourcePath: ::std::string::String::new(),
+            TargetPath: ::std::string::String::new(),
+            Digest: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for FetchResourceToFileRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("FetchResourceToFileRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for FetchResourceToFileRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for FetchResourceToFileRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.FetchResourceToFileResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct FetchResourceToFileResponse {
+    // message fields
+    // @@protoc_insertion_point(field:api.FetchResourceToFileResponse.Size)
+    pub Size: u64,
+    // @@protoc_insertion_point(field:api.FetchResourceToFileResponse.Digest)
+    pub Digest: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.FetchResourceToFileResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a FetchResourceToFileResponse {
+    fn default() -> &'a FetchResourceToFileResponse {
+        <FetchResourceToFileResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl FetchResourceToFileResponse {
+    pub fn new() -> FetchResourceToFileResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(2);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Size",
+            |m: &FetchResourceToFileResponse| { &m.Size },
+            |m: &mut FetchResourceToFileResponse| { &mut m.Size },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Digest",
+            |m: &FetchResourceToFileResponse| { &m.Digest },
+            |m: &mut FetchResourceToFileResponse| { &mut m.Digest },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<FetchResourceToFileResponse>(
+            "FetchResourceToFileResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for FetchResourceToFileResponse {
+    const NAME: &'static str = "FetchResourceToFileResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                8 => {
+                    self.Size = is.read_uint64()?;
+                },
+                18 => {
+                    self.Digest = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if self.Size != 0 {
+            my_size += ::protobuf::rt::uint64_size(1, self.Size);
+        }
+        if !self.Digest.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.Digest);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if self.Size != 0 {
+            os.write_uint64(1, self.Size)?;
+        }
+        if !self.Digest.is_empty() {
+            os.write_string(2, &self.Digest)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> FetchResourceToFileResponse {
+        FetchResourceToFileResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.Size = 0;
+        self.Digest.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static FetchResourceToFileResponse {
+        static instance: FetchResourceToFileResponse = FetchResourceToFileResponse {
+            Size: 0,
+            Digest: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for FetchResourceToFileResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("FetchResourceToFileResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for FetchResourceToFileResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for FetchResourceToFileResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
 // @@protoc_insertion_point(message:api.SecureMountRequest)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct SecureMountRequest {
+pub struct SecureMountRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.SecureMountRequest.driver)
+    pub driver: ::std::string::String,
+    // @@protoc_insertion_point(field:api.SecureMountRequest.driver_options)
+    pub driver_options: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:api.SecureMountRequest.source)
+    pub source: ::std::string::String,
+    // @@protoc_insertion_point(field:api.SecureMountRequest.fstype)
+    pub fstype: ::std::string::String,
+    // @@protoc_insertion_point(field:api.SecureMountRequest.options)
+    pub options: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:api.SecureMountRequest.mount_point)
+    pub mount_point: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.SecureMountRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a SecureMountRequest {
+    fn default() -> &'a SecureMountRequest {
+        <SecureMountRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl SecureMountRequest {
+    pub fn new() -> SecureMountRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(6);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "driver",
+            |m: &SecureMountRequest| { &m.driver },
+            |m: &mut SecureMountRequest| { &mut m.driver },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "driver_options",
+            |m: &SecureMountRequest| { &m.driver_options },
+            |m: &mut SecureMountRequest| { &mut m.driver_options },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "source",
+            |m: &SecureMountRequest| { &m.source },
+            |m: &mut SecureMountRequest| { &mut m.source },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "fstype",
+            |m: &SecureMountRequest| { &m.fstype },
+            |m: &mut SecureMountRequest| { &mut m.fstype },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "options",
+            |m: &SecureMountRequest| { &m.options },
+            |m: &mut SecureMountRequest| { &mut m.options },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "mount_point",
+            |m: &SecureMountRequest| { &m.mount_point },
+            |m: &mut SecureMountRequest| { &mut m.mount_point },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<SecureMountRequest>(
+            "SecureMountRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for SecureMountRequest {
+    const NAME: &'static str = "SecureMountRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.driver = is.read_string()?;
+                },
+                18 => {
+                    self.driver_options.push(is.read_string()?);
+                },
+                26 => {
+                    self.source = is.read_string()?;
+                },
+                34 => {
+                    self.fstype = is.read_string()?;
+                },
+                42 => {
+                    self.options.push(is.read_string()?);
+                },
+                50 => {
+                    self.mount_point = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.driver.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.driver);
+        }
+        for value in &self.driver_options {
+            my_size += ::protobuf::rt::string_size(2, &value);
+        };
+        if !self.source.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.source);
+        }
+        if !self.fstype.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.fstype);
+        }
+        for value in &self.options {
+            my_size += ::protobuf::rt::string_size(5, &value);
+        };
+        if !self.mount_point.is_empty() {
+            my_size += ::protobuf::rt::string_size(6, &self.mount_point);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.driver.is_empty() {
+            os.write_string(1, &self.driver)?;
+        }
+        for v in &self.driver_options {
+            os.write_string(2, &v)?;
+        };
+        if !self.source.is_empty() {
+            os.write_string(3, &self.source)?;
+        }
+        if !self.fstype.is_empty() {
+            os.write_string(4, &self.fstype)?;
+        }
+        for v in &self.options {
+            os.write_string(5, &v)?;
+        };
+        if !self.mount_point.is_empty() {
+            os.write_string(6, &self.mount_point)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> SecureMountRequest {
+        SecureMountRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.driver.clear();
+        self.driver_options.clear();
+        self.source.clear();
+        self.fstype.clear();
+        self.options.clear();
+        self.mount_point.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static SecureMountRequest {
+        static instance: SecureMountRequest = SecureMountRequest {
+            driver: ::std::string::String::new(),
+            driver_options: ::std::vec::Vec::new(),
+            source: ::std::string::String::new(),
+            fstype: ::std::string::String::new(),
+            options: ::std::vec::Vec::new(),
+            mount_point: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for SecureMountRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("SecureMountRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for SecureMountRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for SecureMountRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.SecureMountResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct SecureMountResponse {
+    // message fields
+    // @@protoc_insertion_point(field:api.SecureMountResponse.mount_path)
+    pub mount_path: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.SecureMountResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a SecureMountResponse {
+    fn default() -> &'a SecureMountResponse {
+        <SecureMountResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl SecureMountResponse {
+    pub fn new() -> SecureMountResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "mount_path",
+            |m: &SecureMountResponse| { &m.mount_path },
+            |m: &mut SecureMountResponse| { &mut m.mount_path },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<SecureMountResponse>(
+            "SecureMountResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for SecureMountResponse {
+    const NAME: &'static str = "SecureMountResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.mount_path = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.mount_path.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.mount_path);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.mount_path.is_empty() {
+            os.write_string(1, &self.mount_path)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> SecureMountResponse {
+        SecureMountResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.mount_path.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static SecureMountResponse {
+        static instance: SecureMountResponse = SecureMountResponse {
+            mount_path: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for SecureMountResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("SecureMountResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for SecureMountResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for SecureMountResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.UnmountRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct UnmountRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.UnmountRequest.mount_point)
+    pub mount_point: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.UnmountRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a UnmountRequest {
+    fn default() -> &'a UnmountRequest {
+        <UnmountRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl UnmountRequest {
+    pub fn new() -> UnmountRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "mount_point",
+            |m: &UnmountRequest| { &m.mount_point },
+            |m: &mut UnmountRequest| { &mut m.mount_point },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<UnmountRequest>(
+            "UnmountRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for UnmountRequest {
+    const NAME: &'static str = "UnmountRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.mount_point = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.mount_point.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.mount_point);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.mount_point.is_empty() {
+            os.write_string(1, &self.mount_point)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> UnmountRequest {
+        UnmountRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.mount_point.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static UnmountRequest {
+        static instance: UnmountRequest = UnmountRequest {
+            mount_point: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for UnmountRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("UnmountRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for UnmountRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for UnmountRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.UnmountResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct UnmountResponse {
+    // message fields
+    // special fields
+    // @@protoc_insertion_point(special_field:api.UnmountResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a UnmountResponse {
+    fn default() -> &'a UnmountResponse {
+        <UnmountResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl UnmountResponse {
+    pub fn new() -> UnmountResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(0);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<UnmountResponse>(
+            "UnmountResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for UnmountResponse {
+    const NAME: &'static str = "UnmountResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> UnmountResponse {
+        UnmountResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static UnmountResponse {
+        static instance: UnmountResponse = UnmountResponse {
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for UnmountResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("UnmountResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for UnmountResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for UnmountResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.RemountRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct RemountRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.RemountRequest.mount_point)
+    pub mount_point: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.RemountRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a RemountRequest {
+    fn default() -> &'a RemountRequest {
+        <RemountRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl RemountRequest {
+    pub fn new() -> RemountRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "mount_point",
+            |m: &RemountRequest| { &m.mount_point },
+            |m: &mut RemountRequest| { &mut m.mount_point },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<RemountRequest>(
+            "RemountRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for RemountRequest {
+    const NAME: &'static str = "RemountRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.mount_point = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.mount_point.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.mount_point);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.mount_point.is_empty() {
+            os.write_string(1, &self.mount_point)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> RemountRequest {
+        RemountRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.mount_point.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static RemountRequest {
+        static instance: RemountRequest = RemountRequest {
+            mount_point: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for RemountRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("RemountRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for RemountRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for RemountRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.RemountResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct RemountResponse {
+    // message fields
+    // @@protoc_insertion_point(field:api.RemountResponse.mount_path)
+    pub mount_path: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.RemountResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a RemountResponse {
+    fn default() -> &'a RemountResponse {
+        <RemountResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl RemountResponse {
+    pub fn new() -> RemountResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "mount_path",
+            |m: &RemountResponse| { &m.mount_path },
+            |m: &mut RemountResponse| { &mut m.mount_path },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<RemountResponse>(
+            "RemountResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for RemountResponse {
+    const NAME: &'static str = "RemountResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.mount_path = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.mount_path.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.mount_path);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.mount_path.is_empty() {
+            os.write_string(1, &self.mount_path)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> RemountResponse {
+        RemountResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.mount_path.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static RemountResponse {
+        static instance: RemountResponse = RemountResponse {
+            mount_path: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for RemountResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("RemountResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for RemountResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for RemountResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.InjectSecretsRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct InjectSecretsRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.InjectSecretsRequest.manifest)
+    pub manifest: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:api.InjectSecretsRequest.rootfs)
+    pub rootfs: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.InjectSecretsRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a InjectSecretsRequest {
+    fn default() -> &'a InjectSecretsRequest {
+        <InjectSecretsRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl InjectSecretsRequest {
+    pub fn new() -> InjectSecretsRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(2);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "manifest",
+            |m: &InjectSecretsRequest| { &m.manifest },
+            |m: &mut InjectSecretsRequest| { &mut m.manifest },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "rootfs",
+            |m: &InjectSecretsRequest| { &m.rootfs },
+            |m: &mut InjectSecretsRequest| { &mut m.rootfs },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<InjectSecretsRequest>(
+            "InjectSecretsRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for InjectSecretsRequest {
+    const NAME: &'static str = "InjectSecretsRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.manifest = is.read_bytes()?;
+                },
+                18 => {
+                    self.rootfs = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.manifest.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(1, &self.manifest);
+        }
+        if !self.rootfs.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.rootfs);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.manifest.is_empty() {
+            os.write_bytes(1, &self.manifest)?;
+        }
+        if !self.rootfs.is_empty() {
+            os.write_string(2, &self.rootfs)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> InjectSecretsRequest {
+        InjectSecretsRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.manifest.clear();
+        self.rootfs.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static InjectSecretsRequest {
+        static instance: InjectSecretsRequest = InjectSecretsRequest {
+            manifest: ::std::vec::Vec::new(),
+            rootfs: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for InjectSecretsRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("InjectSecretsRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for InjectSecretsRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for InjectSecretsRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.InjectSecretsResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct InjectSecretsResponse {
+    // message fields
+    // @@protoc_insertion_point(field:api.InjectSecretsResponse.paths)
+    pub paths: ::std::vec::Vec<::std::string::String>,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.InjectSecretsResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a InjectSecretsResponse {
+    fn default() -> &'a InjectSecretsResponse {
+        <InjectSecretsResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl InjectSecretsResponse {
+    pub fn new() -> InjectSecretsResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "paths",
+            |m: &InjectSecretsResponse| { &m.paths },
+            |m: &mut InjectSecretsResponse| { &mut m.paths },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<InjectSecretsResponse>(
+            "InjectSecretsResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for InjectSecretsResponse {
+    const NAME: &'static str = "InjectSecretsResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.paths.push(is.read_string()?);
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        for value in &self.paths {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        for v in &self.paths {
+            os.write_string(1, &v)?;
+        };
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> InjectSecretsResponse {
+        InjectSecretsResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.paths.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static InjectSecretsResponse {
+        static instance: InjectSecretsResponse = InjectSecretsResponse {
+            paths: ::std::vec::Vec::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for InjectSecretsResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("InjectSecretsResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for InjectSecretsResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for InjectSecretsResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.GenerateKeyRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GenerateKeyRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.GenerateKeyRequest.algorithm)
+    pub algorithm: ::std::string::String,
+    // @@protoc_insertion_point(field:api.GenerateKeyRequest.common_name)
+    pub common_name: ::std::string::String,
+    // @@protoc_insertion_point(field:api.GenerateKeyRequest.dns_names)
+    pub dns_names: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:api.GenerateKeyRequest.ip_addresses)
+    pub ip_addresses: ::std::vec::Vec<::std::string::String>,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.GenerateKeyRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GenerateKeyRequest {
+    fn default() -> &'a GenerateKeyRequest {
+        <GenerateKeyRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GenerateKeyRequest {
+    pub fn new() -> GenerateKeyRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(4);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "algorithm",
+            |m: &GenerateKeyRequest| { &m.algorithm },
+            |m: &mut GenerateKeyRequest| { &mut m.algorithm },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "common_name",
+            |m: &GenerateKeyRequest| { &m.common_name },
+            |m: &mut GenerateKeyRequest| { &mut m.common_name },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "dns_names",
+            |m: &GenerateKeyRequest| { &m.dns_names },
+            |m: &mut GenerateKeyRequest| { &mut m.dns_names },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "ip_addresses",
+            |m: &GenerateKeyRequest| { &m.ip_addresses },
+            |m: &mut GenerateKeyRequest| { &mut m.ip_addresses },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GenerateKeyRequest>(
+            "GenerateKeyRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GenerateKeyRequest {
+    const NAME: &'static str = "GenerateKeyRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.algorithm = is.read_string()?;
+                },
+                18 => {
+                    self.common_name = is.read_string()?;
+                },
+                26 => {
+                    self.dns_names.push(is.read_string()?);
+                },
+                34 => {
+                    self.ip_addresses.push(is.read_string()?);
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.algorithm.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.algorithm);
+        }
+        if !self.common_name.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.common_name);
+        }
+        for value in &self.dns_names {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        for value in &self.ip_addresses {
+            my_size += ::protobuf::rt::string_size(4, &value);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.algorithm.is_empty() {
+            os.write_string(1, &self.algorithm)?;
+        }
+        if !self.common_name.is_empty() {
+            os.write_string(2, &self.common_name)?;
+        }
+        for v in &self.dns_names {
+            os.write_string(3, &v)?;
+        };
+        for v in &self.ip_addresses {
+            os.write_string(4, &v)?;
+        };
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GenerateKeyRequest {
+        GenerateKeyRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.algorithm.clear();
+        self.common_name.clear();
+        self.dns_names.clear();
+        self.ip_addresses.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GenerateKeyRequest {
+        static instance: GenerateKeyRequest = GenerateKeyRequest {
+            algorithm: ::std::string::String::new(),
+            common_name: ::std::string::String::new(),
+            dns_names: ::std::vec::Vec::new(),
+            ip_addresses: ::std::vec::Vec::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for GenerateKeyRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GenerateKeyRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GenerateKeyRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GenerateKeyRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.GenerateKeyResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GenerateKeyResponse {
+    // message fields
+    // @@protoc_insertion_point(field:api.GenerateKeyResponse.key_id)
+    pub key_id: ::std::string::String,
+    // @@protoc_insertion_point(field:api.GenerateKeyResponse.csr)
+    pub csr: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:api.GenerateKeyResponse.evidence)
+    pub evidence: ::std::vec::Vec<u8>,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.GenerateKeyResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GenerateKeyResponse {
+    fn default() -> &'a GenerateKeyResponse {
+        <GenerateKeyResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GenerateKeyResponse {
+    pub fn new() -> GenerateKeyResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(3);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "key_id",
+            |m: &GenerateKeyResponse| { &m.key_id },
+            |m: &mut GenerateKeyResponse| { &mut m.key_id },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "csr",
+            |m: &GenerateKeyResponse| { &m.csr },
+            |m: &mut GenerateKeyResponse| { &mut m.csr },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "evidence",
+            |m: &GenerateKeyResponse| { &m.evidence },
+            |m: &mut GenerateKeyResponse| { &mut m.evidence },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GenerateKeyResponse>(
+            "GenerateKeyResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GenerateKeyResponse {
+    const NAME: &'static str = "GenerateKeyResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.key_id = is.read_string()?;
+                },
+                18 => {
+                    self.csr = is.read_bytes()?;
+                },
+                26 => {
+                    self.evidence = is.read_bytes()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.key_id.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.key_id);
+        }
+        if !self.csr.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(2, &self.csr);
+        }
+        if !self.evidence.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(3, &self.evidence);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.key_id.is_empty() {
+            os.write_string(1, &self.key_id)?;
+        }
+        if !self.csr.is_empty() {
+            os.write_bytes(2, &self.csr)?;
+        }
+        if !self.evidence.is_empty() {
+            os.write_bytes(3, &self.evidence)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GenerateKeyResponse {
+        GenerateKeyResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.key_id.clear();
+        self.csr.clear();
+        self.evidence.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GenerateKeyResponse {
+        static instance: GenerateKeyResponse = GenerateKeyResponse {
+            key_id: ::std::string::String::new(),
+            csr: ::std::vec::Vec::new(),
+            evidence: ::std::vec::Vec::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for GenerateKeyResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GenerateKeyResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GenerateKeyResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GenerateKeyResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.InstallCertificateRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct InstallCertificateRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.InstallCertificateRequest.key_id)
+    pub key_id: ::std::string::String,
+    // @@protoc_insertion_point(field:api.InstallCertificateRequest.certificate)
+    pub certificate: ::std::vec::Vec<u8>,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.InstallCertificateRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a InstallCertificateRequest {
+    fn default() -> &'a InstallCertificateRequest {
+        <InstallCertificateRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl InstallCertificateRequest {
+    pub fn new() -> InstallCertificateRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(2);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "key_id",
+            |m: &InstallCertificateRequest| { &m.key_id },
+            |m: &mut InstallCertificateRequest| { &mut m.key_id },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "certificate",
+            |m: &InstallCertificateRequest| { &m.certificate },
+            |m: &mut InstallCertificateRequest| { &mut m.certificate },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<InstallCertificateRequest>(
+            "InstallCertificateRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for InstallCertificateRequest {
+    const NAME: &'static str = "InstallCertificateRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.key_id = is.read_string()?;
+                },
+                18 => {
+                    self.certificate = is.read_bytes()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.key_id.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.key_id);
+        }
+        if !self.certificate.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(2, &self.certificate);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.key_id.is_empty() {
+            os.write_string(1, &self.key_id)?;
+        }
+        if !self.certificate.is_empty() {
+            os.write_bytes(2, &self.certificate)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> InstallCertificateRequest {
+        InstallCertificateRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.key_id.clear();
+        self.certificate.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static InstallCertificateRequest {
+        static instance: InstallCertificateRequest = InstallCertificateRequest {
+            key_id: ::std::string::String::new(),
+            certificate: ::std::vec::Vec::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for InstallCertificateRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("InstallCertificateRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for InstallCertificateRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for InstallCertificateRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.InstallCertificateResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct InstallCertificateResponse {
+    // message fields
+    // @@protoc_insertion_point(field:api.InstallCertificateResponse.path)
+    pub path: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.InstallCertificateResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a InstallCertificateResponse {
+    fn default() -> &'a InstallCertificateResponse {
+        <InstallCertificateResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl InstallCertificateResponse {
+    pub fn new() -> InstallCertificateResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "path",
+            |m: &InstallCertificateResponse| { &m.path },
+            |m: &mut InstallCertificateResponse| { &mut m.path },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<InstallCertificateResponse>(
+            "InstallCertificateResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for InstallCertificateResponse {
+    const NAME: &'static str = "InstallCertificateResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.path = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.path.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.path);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.path.is_empty() {
+            os.write_string(1, &self.path)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> InstallCertificateResponse {
+        InstallCertificateResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.path.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static InstallCertificateResponse {
+        static instance: InstallCertificateResponse = InstallCertificateResponse {
+            path: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for InstallCertificateResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("InstallCertificateResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for InstallCertificateResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for InstallCertificateResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.ImagePullRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct ImagePullRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.ImagePullRequest.image_url)
+    pub image_url: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ImagePullRequest.bundle_path)
+    pub bundle_path: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ImagePullRequest.auth_info)
+    pub auth_info: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ImagePullRequest.snapshotter)
+    pub snapshotter: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ImagePullRequest.platform)
+    pub platform: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ImagePullRequest.auth)
+    pub auth: ::std::option::Option<bool>,
+    // @@protoc_insertion_point(field:api.ImagePullRequest.auth_file)
+    pub auth_file: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ImagePullRequest.security_validate)
+    pub security_validate: ::std::option::Option<bool>,
+    // @@protoc_insertion_point(field:api.ImagePullRequest.policy_path)
+    pub policy_path: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ImagePullRequest.sigstore_config)
+    pub sigstore_config: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ImagePullRequest.decrypt_config)
+    pub decrypt_config: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ImagePullRequest.selinux_label)
+    pub selinux_label: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ImagePullRequest.id_shift)
+    pub id_shift: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.ImagePullRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a ImagePullRequest {
+    fn default() -> &'a ImagePullRequest {
+        <ImagePullRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl ImagePullRequest {
+    pub fn new() -> ImagePullRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(13);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "image_url",
+            |m: &ImagePullRequest| { &m.image_url },
+            |m: &mut ImagePullRequest| { &mut m.image_url },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "bundle_path",
+            |m: &ImagePullRequest| { &m.bundle_path },
+            |m: &mut ImagePullRequest| { &mut m.bundle_path },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "auth_info",
+            |m: &ImagePullRequest| { &m.auth_info },
+            |m: &mut ImagePullRequest| { &mut m.auth_info },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "snapshotter",
+            |m: &ImagePullRequest| { &m.snapshotter },
+            |m: &mut ImagePullRequest| { &mut m.snapshotter },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "platform",
+            |m: &ImagePullRequest| { &m.platform },
+            |m: &mut ImagePullRequest| { &mut m.platform },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_option_accessor::<_, _>(
+            "auth",
+            |m: &ImagePullRequest| { &m.auth },
+            |m: &mut ImagePullRequest| { &mut m.auth },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "auth_file",
+            |m: &ImagePullRequest| { &m.auth_file },
+            |m: &mut ImagePullRequest| { &mut m.auth_file },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_option_accessor::<_, _>(
+            "security_validate",
+            |m: &ImagePullRequest| { &m.security_validate },
+            |m: &mut ImagePullRequest| { &mut m.security_validate },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "policy_path",
+            |m: &ImagePullRequest| { &m.policy_path },
+            |m: &mut ImagePullRequest| { &mut m.policy_path },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "sigstore_config",
+            |m: &ImagePullRequest| { &m.sigstore_config },
+            |m: &mut ImagePullRequest| { &mut m.sigstore_config },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "decrypt_config",
+            |m: &ImagePullRequest| { &m.decrypt_config },
+            |m: &mut ImagePullRequest| { &mut m.decrypt_config },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "selinux_label",
+            |m: &ImagePullRequest| { &m.selinux_label },
+            |m: &mut ImagePullRequest| { &mut m.selinux_label },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "id_shift",
+            |m: &ImagePullRequest| { &m.id_shift },
+            |m: &mut ImagePullRequest| { &mut m.id_shift },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<ImagePullRequest>(
+            "ImagePullRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for ImagePullRequest {
+    const NAME: &'static str = "ImagePullRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.image_url = is.read_string()?;
+                },
+                18 => {
+                    self.bundle_path = is.read_string()?;
+                },
+                26 => {
+                    self.auth_info = is.read_string()?;
+                },
+                34 => {
+                    self.snapshotter = is.read_string()?;
+                },
+                42 => {
+                    self.platform = is.read_string()?;
+                },
+                48 => {
+                    self.auth = ::std::option::Option::Some(is.read_bool()?);
+                },
+                58 => {
+                    self.auth_file = is.read_string()?;
+                },
+                64 => {
+                    self.security_validate = ::std::option::Option::Some(is.read_bool()?);
+                },
+                74 => {
+                    self.policy_path = is.read_string()?;
+                },
+                82 => {
+                    self.sigstore_config = is.read_string()?;
+                },
+                90 => {
+                    self.decrypt_config = is.read_string()?;
+                },
+                98 => {
+                    self.selinux_label = is.read_string()?;
+                },
+                106 => {
+                    self.id_shift = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.image_url.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.image_url);
+        }
+        if !self.bundle_path.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.bundle_path);
+        }
+        if !self.auth_info.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.auth_info);
+        }
+        if !self.snapshotter.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.snapshotter);
+        }
+        if !self.platform.is_empty() {
+            my_size += ::protobuf::rt::string_size(5, &self.platform);
+        }
+        if let Some(v) = self.auth {
+            my_size += 1 + 1;
+        }
+        if !self.auth_file.is_empty() {
+            my_size += ::protobuf::rt::string_size(7, &self.auth_file);
+        }
+        if let Some(v) = self.security_validate {
+            my_size += 1 + 1;
+        }
+        if !self.policy_path.is_empty() {
+            my_size += ::protobuf::rt::string_size(9, &self.policy_path);
+        }
+        if !self.sigstore_config.is_empty() {
+            my_size += ::protobuf::rt::string_size(10, &self.sigstore_config);
+        }
+        if !self.decrypt_config.is_empty() {
+            my_size += ::protobuf::rt::string_size(11, &self.decrypt_config);
+        }
+        if !self.selinux_label.is_empty() {
+            my_size += ::protobuf::rt::string_size(12, &self.selinux_label);
+        }
+        if !self.id_shift.is_empty() {
+            my_size += ::protobuf::rt::string_size(13, &self.id_shift);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.image_url.is_empty() {
+            os.write_string(1, &self.image_url)?;
+        }
+        if !self.bundle_path.is_empty() {
+            os.write_string(2, &self.bundle_path)?;
+        }
+        if !self.auth_info.is_empty() {
+            os.write_string(3, &self.auth_info)?;
+        }
+        if !self.snapshotter.is_empty() {
+            os.write_string(4, &self.snapshotter)?;
+        }
+        if !self.platform.is_empty() {
+            os.write_string(5, &self.platform)?;
+        }
+        if let Some(v) = self.auth {
+            os.write_bool(6, v)?;
+        }
+        if !self.auth_file.is_empty() {
+            os.write_string(7, &self.auth_file)?;
+        }
+        if let Some(v) = self.security_validate {
+            os.write_bool(8, v)?;
+        }
+        if !self.policy_path.is_empty() {
+            os.write_string(9, &self.policy_path)?;
+        }
+        if !self.sigstore_config.is_empty() {
+            os.write_string(10, &self.sigstore_config)?;
+        }
+        if !self.decrypt_config.is_empty() {
+            os.write_string(11, &self.decrypt_config)?;
+        }
+        if !self.selinux_label.is_empty() {
+            os.write_string(12, &self.selinux_label)?;
+        }
+        if !self.id_shift.is_empty() {
+            os.write_string(13, &self.id_shift)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> ImagePullRequest {
+        ImagePullRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.image_url.clear();
+        self.bundle_path.clear();
+        self.auth_info.clear();
+        self.snapshotter.clear();
+        self.platform.clear();
+        self.auth = ::std::option::Option::None;
+        self.auth_file.clear();
+        self.security_validate = ::std::option::Option::None;
+        self.policy_path.clear();
+        self.sigstore_config.clear();
+        self.decrypt_config.clear();
+        self.selinux_label.clear();
+        self.id_shift.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static ImagePullRequest {
+        static instance: ImagePullRequest = ImagePullRequest {
+            image_url: ::std::string::String::new(),
+            bundle_path: ::std::string::String::new(),
+            auth_info: ::std::string::String::new(),
+            snapshotter: ::std::string::String::new(),
+            platform: ::std::string::String::new(),
+            auth: ::std::option::Option::None,
+            auth_file: ::std::string::String::new(),
+            security_validate: ::std::option::Option::None,
+            policy_path: ::std::string::String::new(),
+            sigstore_config: ::std::string::String::new(),
+            decrypt_config: ::std::string::String::new(),
+            selinux_label: ::std::string::String::new(),
+            id_shift: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for ImagePullRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("ImagePullRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for ImagePullRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for ImagePullRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.ImagePullResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct ImagePullResponse {
+    // message fields
+    // @@protoc_insertion_point(field:api.ImagePullResponse.image_id)
+    pub image_id: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ImagePullResponse.verification_report)
+    pub verification_report: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ImagePullResponse.layer_group_mounts)
+    pub layer_group_mounts: ::std::vec::Vec<::std::string::String>,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.ImagePullResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a ImagePullResponse {
+    fn default() -> &'a ImagePullResponse {
+        <ImagePullResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl ImagePullResponse {
+    pub fn new() -> ImagePullResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(3);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "image_id",
+            |m: &ImagePullResponse| { &m.image_id },
+            |m: &mut ImagePullResponse| { &mut m.image_id },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "verification_report",
+            |m: &ImagePullResponse| { &m.verification_report },
+            |m: &mut ImagePullResponse| { &mut m.verification_report },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "layer_group_mounts",
+            |m: &ImagePullResponse| { &m.layer_group_mounts },
+            |m: &mut ImagePullResponse| { &mut m.layer_group_mounts },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<ImagePullResponse>(
+            "ImagePullResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for ImagePullResponse {
+    const NAME: &'static str = "ImagePullResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.image_id = is.read_string()?;
+                },
+                18 => {
+                    self.verification_report = is.read_string()?;
+                },
+                26 => {
+                    self.layer_group_mounts.push(is.read_string()?);
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.image_id.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.image_id);
+        }
+        if !self.verification_report.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.verification_report);
+        }
+        for value in &self.layer_group_mounts {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.image_id.is_empty() {
+            os.write_string(1, &self.image_id)?;
+        }
+        if !self.verification_report.is_empty() {
+            os.write_string(2, &self.verification_report)?;
+        }
+        for v in &self.layer_group_mounts {
+            os.write_string(3, &v)?;
+        };
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> ImagePullResponse {
+        ImagePullResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.image_id.clear();
+        self.verification_report.clear();
+        self.layer_group_mounts.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static ImagePullResponse {
+        static instance: ImagePullResponse = ImagePullResponse {
+            image_id: ::std::string::String::new(),
+            verification_report: ::std::string::String::new(),
+            layer_group_mounts: ::std::vec::Vec::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for ImagePullResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("ImagePullResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for ImagePullResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for ImagePullResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.PinImageRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct PinImageRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.PinImageRequest.image_url)
+    pub image_url: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.PinImageRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a PinImageRequest {
+    fn default() -> &'a PinImageRequest {
+        <PinImageRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl PinImageRequest {
+    pub fn new() -> PinImageRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "image_url",
+            |m: &PinImageRequest| { &m.image_url },
+            |m: &mut PinImageRequest| { &mut m.image_url },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<PinImageRequest>(
+            "PinImageRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for PinImageRequest {
+    const NAME: &'static str = "PinImageRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.image_url = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.image_url.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.image_url);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.image_url.is_empty() {
+            os.write_string(1, &self.image_url)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> PinImageRequest {
+        PinImageRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.image_url.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static PinImageRequest {
+        static instance: PinImageRequest = PinImageRequest {
+            image_url: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for PinImageRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("PinImageRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for PinImageRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for PinImageRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.PinImageResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct PinImageResponse {
+    // message fields
+    // special fields
+    // @@protoc_insertion_point(special_field:api.PinImageResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a PinImageResponse {
+    fn default() -> &'a PinImageResponse {
+        <PinImageResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl PinImageResponse {
+    pub fn new() -> PinImageResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(0);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<PinImageResponse>(
+            "PinImageResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for PinImageResponse {
+    const NAME: &'static str = "PinImageResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> PinImageResponse {
+        PinImageResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static PinImageResponse {
+        static instance: PinImageResponse = PinImageResponse {
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for PinImageResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("PinImageResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for PinImageResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for PinImageResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.UnpinImageRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct UnpinImageRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.UnpinImageRequest.image_url)
+    pub image_url: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.UnpinImageRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a UnpinImageRequest {
+    fn default() -> &'a UnpinImageRequest {
+        <UnpinImageRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl UnpinImageRequest {
+    pub fn new() -> UnpinImageRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "image_url",
+            |m: &UnpinImageRequest| { &m.image_url },
+            |m: &mut UnpinImageRequest| { &mut m.image_url },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<UnpinImageRequest>(
+            "UnpinImageRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for UnpinImageRequest {
+    const NAME: &'static str = "UnpinImageRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.image_url = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.image_url.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.image_url);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.image_url.is_empty() {
+            os.write_string(1, &self.image_url)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> UnpinImageRequest {
+        UnpinImageRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.image_url.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static UnpinImageRequest {
+        static instance: UnpinImageRequest = UnpinImageRequest {
+            image_url: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for UnpinImageRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("UnpinImageRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for UnpinImageRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for UnpinImageRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.UnpinImageResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct UnpinImageResponse {
+    // message fields
+    // @@protoc_insertion_point(field:api.UnpinImageResponse.unpinned)
+    pub unpinned: bool,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.UnpinImageResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a UnpinImageResponse {
+    fn default() -> &'a UnpinImageResponse {
+        <UnpinImageResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl UnpinImageResponse {
+    pub fn new() -> UnpinImageResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "unpinned",
+            |m: &UnpinImageResponse| { &m.unpinned },
+            |m: &mut UnpinImageResponse| { &mut m.unpinned },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<UnpinImageResponse>(
+            "UnpinImageResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for UnpinImageResponse {
+    const NAME: &'static str = "UnpinImageResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                8 => {
+                    self.unpinned = is.read_bool()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if self.unpinned != false {
+            my_size += 1 + 1;
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if self.unpinned != false {
+            os.write_bool(1, self.unpinned)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> UnpinImageResponse {
+        UnpinImageResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.unpinned = false;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static UnpinImageResponse {
+        static instance: UnpinImageResponse = UnpinImageResponse {
+            unpinned: false,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for UnpinImageResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("UnpinImageResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for UnpinImageResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for UnpinImageResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.ProvisionSecretDirRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct ProvisionSecretDirRequest {
+    // message fields
+    // @@protoc_insertion_point(field:api.ProvisionSecretDirRequest.container_id)
+    pub container_id: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ProvisionSecretDirRequest.manifest)
+    pub manifest: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:api.ProvisionSecretDirRequest.mount_path)
+    pub mount_path: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ProvisionSecretDirRequest.uid)
+    pub uid: u32,
+    // @@protoc_insertion_point(field:api.ProvisionSecretDirRequest.gid)
+    pub gid: u32,
+    // @@protoc_insertion_point(field:api.ProvisionSecretDirRequest.size)
+    pub size: u64,
+    // special fields
+    // @@protoc_insertion_point(special_field:api.ProvisionSecretDirRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a ProvisionSecretDirRequest {
+    fn default() -> &'a ProvisionSecretDirRequest {
+        <ProvisionSecretDirRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl ProvisionSecretDirRequest {
+    pub fn new() -> ProvisionSecretDirRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(6);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "container_id",
+            |m: &ProvisionSecretDirRequest| { &m.container_id },
+            |m: &mut ProvisionSecretDirRequest| { &mut m.container_id },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "manifest",
+            |m: &ProvisionSecretDirRequest| { &m.manifest },
+            |m: &mut ProvisionSecretDirRequest| { &mut m.manifest },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "mount_path",
+            |m: &ProvisionSecretDirRequest| { &m.mount_path },
+            |m: &mut ProvisionSecretDirRequest| { &mut m.mount_path },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "uid",
+            |m: &ProvisionSecretDirRequest| { &m.uid },
+            |m: &mut ProvisionSecretDirRequest| { &mut m.uid },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "gid",
+            |m: &ProvisionSecretDirRequest| { &m.gid },
+            |m: &mut ProvisionSecretDirRequest| { &mut m.gid },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "size",
+            |m: &ProvisionSecretDirRequest| { &m.size },
+            |m: &mut ProvisionSecretDirRequest| { &mut m.size },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<ProvisionSecretDirRequest>(
+            "ProvisionSecretDirRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for ProvisionSecretDirRequest {
+    const NAME: &'static str = "ProvisionSecretDirRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.container_id = is.read_string()?;
+                },
+                18 => {
+                    self.manifest = is.read_bytes()?;
+                },
+                26 => {
+                    self.mount_path = is.read_string()?;
+                },
+                32 => {
+                    self.uid = is.read_uint32()?;
+                },
+                40 => {
+                    self.gid = is.read_uint32()?;
+                },
+                48 => {
+                    self.size = is.read_uint64()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.container_id.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.container_id);
+        }
+        if !self.manifest.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(2, &self.manifest);
+        }
+        if !self.mount_path.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.mount_path);
+        }
+        if self.uid != 0 {
+            my_size += ::protobuf::rt::uint32_size(4, self.uid);
+        }
+        if self.gid != 0 {
+            my_size += ::protobuf::rt::uint32_size(5, self.gid);
+        }
+        if self.size != 0 {
+            my_size += ::protobuf::rt::uint64_size(6, self.size);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.container_id.is_empty() {
+            os.write_string(1, &self.container_id)?;
+        }
+        if !self.manifest.is_empty() {
+            os.write_bytes(2, &self.manifest)?;
+        }
+        if !self.mount_path.is_empty() {
+            os.write_string(3, &self.mount_path)?;
+        }
+        if self.uid != 0 {
+            os.write_uint32(4, self.uid)?;
+        }
+        if self.gid != 0 {
+            os.write_uint32(5, self.gid)?;
+        }
+        if self.size != 0 {
+            os.write_uint64(6, self.size)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> ProvisionSecretDirRequest {
+        ProvisionSecretDirRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.container_id.clear();
+        self.manifest.clear();
+        self.mount_path.clear();
+        self.uid = 0;
+        self.gid = 0;
+        self.size = 0;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static ProvisionSecretDirRequest {
+        static instance: ProvisionSecretDirRequest = ProvisionSecretDirRequest {
+            container_id: ::std::string::String::new(),
+            manifest: ::std::vec::Vec::new(),
+            mount_path: ::std::string::String::new(),
+            uid: 0,
+            gid: 0,
+            size: 0,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for ProvisionSecretDirRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("ProvisionSecretDirRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for ProvisionSecretDirRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for ProvisionSecretDirRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.ProvisionSecretDirResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct ProvisionSecretDirResponse {
     // message fields
-    // @@protoc_insertion_point(field:api.SecureMountRequest.driver)
-    pub driver: ::std::string::String,
-    // @@protoc_insertion_point(field:api.SecureMountRequest.driver_options)
-    pub driver_options: ::std::vec::Vec<::std::string::String>,
-    // @@protoc_insertion_point(field:api.SecureMountRequest.source)
+    // @@protoc_insertion_point(field:api.ProvisionSecretDirResponse.source)
     pub source: ::std::string::String,
-    // @@protoc_insertion_point(field:api.SecureMountRequest.fstype)
-    pub fstype: ::std::string::String,
-    // @@protoc_insertion_point(field:api.SecureMountRequest.options)
+    // @@protoc_insertion_point(field:api.ProvisionSecretDirResponse.destination)
+    pub destination: ::std::string::String,
+    // @@protoc_insertion_point(field:api.ProvisionSecretDirResponse.options)
     pub options: ::std::vec::Vec<::std::string::String>,
-    // @@protoc_insertion_point(field:api.SecureMountRequest.mount_point)
-    pub mount_point: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:api.SecureMountRequest.special_fields)
+    // @@protoc_insertion_point(special_field:api.ProvisionSecretDirResponse.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a SecureMountRequest {
-    fn default() -> &'a SecureMountRequest {
-        <SecureMountRequest as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a ProvisionSecretDirResponse {
+    fn default() -> &'a ProvisionSecretDirResponse {
+        <ProvisionSecretDirResponse as ::protobuf::Message>::default_instance()
     }
 }
 
-impl SecureMountRequest {
-    pub fn new() -> SecureMountRequest {
+impl ProvisionSecretDirResponse {
+    pub fn new() -> ProvisionSecretDirResponse {
         ::std::default::Default::default()
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(6);
+        let mut fields = ::std::vec::Vec::with_capacity(3);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
-        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
-            "driver",
-            |m: &SecureMountRequest| { &m.driver },
-            |m: &mut SecureMountRequest| { &mut m.driver },
-        ));
-        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
-            "driver_options",
-            |m: &SecureMountRequest| { &m.driver_options },
-            |m: &mut SecureMountRequest| { &mut m.driver_options },
-        ));
         fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
             "source",
-            |m: &SecureMountRequest| { &m.source },
-            |m: &mut SecureMountRequest| { &mut m.source },
+            |m: &ProvisionSecretDirResponse| { &m.source },
+            |m: &mut ProvisionSecretDirResponse| { &mut m.source },
         ));
         fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
-            "fstype",
-            |m: &SecureMountRequest| { &m.fstype },
-            |m: &mut SecureMountRequest| { &mut m.fstype },
+            "destination",
+            |m: &ProvisionSecretDirResponse| { &m.destination },
+            |m: &mut ProvisionSecretDirResponse| { &mut m.destination },
         ));
         fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
             "options",
-            |m: &SecureMountRequest| { &m.options },
-            |m: &mut SecureMountRequest| { &mut m.options },
-        ));
-        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
-            "mount_point",
-            |m: &SecureMountRequest| { &m.mount_point },
-            |m: &mut SecureMountRequest| { &mut m.mount_point },
+            |m: &ProvisionSecretDirResponse| { &m.options },
+            |m: &mut ProvisionSecretDirResponse| { &mut m.options },
         ));
-        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<SecureMountRequest>(
-            "SecureMountRequest",
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<ProvisionSecretDirResponse>(
+            "ProvisionSecretDirResponse",
             fields,
             oneofs,
         )
     }
 }
 
-impl ::protobuf::Message for SecureMountRequest {
-    const NAME: &'static str = "SecureMountRequest";
+impl ::protobuf::Message for ProvisionSecretDirResponse {
+    const NAME: &'static str = "ProvisionSecretDirResponse";
 
     fn is_initialized(&self) -> bool {
         true
@@ -597,23 +4291,14 @@ impl ::protobuf::Message for SecureMountRequest {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.driver = is.read_string()?;
+                    self.source = is.read_string()?;
                 },
                 18 => {
-                    self.driver_options.push(is.read_string()?);
+                    self.destination = is.read_string()?;
                 },
                 26 => {
-                    self.source = is.read_string()?;
-                },
-                34 => {
-                    self.fstype = is.read_string()?;
-                },
-                42 => {
                     self.options.push(is.read_string()?);
                 },
-                50 => {
-                    self.mount_point = is.read_string()?;
-                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -626,48 +4311,30 @@ impl ::protobuf::Message for SecureMountRequest {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.driver.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.driver);
-        }
-        for value in &self.driver_options {
-            my_size += ::protobuf::rt::string_size(2, &value);
-        };
         if !self.source.is_empty() {
-            my_size += ::protobuf::rt::string_size(3, &self.source);
+            my_size += ::protobuf::rt::string_size(1, &self.source);
         }
-        if !self.fstype.is_empty() {
-            my_size += ::protobuf::rt::string_size(4, &self.fstype);
+        if !self.destination.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.destination);
         }
         for value in &self.options {
-            my_size += ::protobuf::rt::string_size(5, &value);
+            my_size += ::protobuf::rt::string_size(3, &value);
         };
-        if !self.mount_point.is_empty() {
-            my_size += ::protobuf::rt::string_size(6, &self.mount_point);
-        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.driver.is_empty() {
-            os.write_string(1, &self.driver)?;
-        }
-        for v in &self.driver_options {
-            os.write_string(2, &v)?;
-        };
         if !self.source.is_empty() {
-            os.write_string(3, &self.source)?;
+            os.write_string(1, &self.source)?;
         }
-        if !self.fstype.is_empty() {
-            os.write_string(4, &self.fstype)?;
+        if !self.destination.is_empty() {
+            os.write_string(2, &self.destination)?;
         }
         for v in &self.options {
-            os.write_string(5, &v)?;
+            os.write_string(3, &v)?;
         };
-        if !self.mount_point.is_empty() {
-            os.write_string(6, &self.mount_point)?;
-        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -680,70 +4347,64 @@ impl ::protobuf::Message for SecureMountRequest {
         &mut self.special_fields
     }
 
-    fn new() -> SecureMountRequest {
-        SecureMountRequest::new()
+    fn new() -> ProvisionSecretDirResponse {
+        ProvisionSecretDirResponse::new()
     }
 
     fn clear(&mut self) {
-        self.driver.clear();
-        self.driver_options.clear();
         self.source.clear();
-        self.fstype.clear();
+        self.destination.clear();
         self.options.clear();
-        self.mount_point.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static SecureMountRequest {
-        static instance: SecureMountRequest = SecureMountRequest {
-            driver: ::std::string::String::new(),
-            driver_options: ::std::vec::Vec::new(),
+    fn default_instance() -> &'static ProvisionSecretDirResponse {
+        static instance: ProvisionSecretDirResponse = ProvisionSecretDirResponse {
             source: ::std::string::String::new(),
-            fstype: ::std::string::String::new(),
+            destination: ::std::string::String::new(),
             options: ::std::vec::Vec::new(),
-            mount_point: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-impl ::protobuf::MessageFull for SecureMountRequest {
+impl ::protobuf::MessageFull for ProvisionSecretDirResponse {
     fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
         static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
-        descriptor.get(|| file_descriptor().message_by_package_relative_name("SecureMountRequest").unwrap()).clone()
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("ProvisionSecretDirResponse").unwrap()).clone()
     }
 }
 
-impl ::std::fmt::Display for SecureMountRequest {
+impl ::std::fmt::Display for ProvisionSecretDirResponse {
     fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
         ::protobuf::text_format::fmt(self, f)
     }
 }
 
-impl ::protobuf::reflect::ProtobufValue for SecureMountRequest {
+impl ::protobuf::reflect::ProtobufValue for ProvisionSecretDirResponse {
     type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
 }
 
-// @@protoc_insertion_point(message:api.SecureMountResponse)
+// @@protoc_insertion_point(message:api.RemoveSecretDirRequest)
 #[derive(PartialEq,Clone,Default,Debug)]
-pub struct SecureMountResponse {
+pub struct RemoveSecretDirRequest {
     // message fields
-    // @@protoc_insertion_point(field:api.SecureMountResponse.mount_path)
-    pub mount_path: ::std::string::String,
+    // @@protoc_insertion_point(field:api.RemoveSecretDirRequest.container_id)
+    pub container_id: ::std::string::String,
     // special fields
-    // @@protoc_insertion_point(special_field:api.SecureMountResponse.special_fields)
+    // @@protoc_insertion_point(special_field:api.RemoveSecretDirRequest.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
 }
 
-impl<'a> ::std::default::Default for &'a SecureMountResponse {
-    fn default() -> &'a SecureMountResponse {
-        <SecureMountResponse as ::protobuf::Message>::default_instance()
+impl<'a> ::std::default::Default for &'a RemoveSecretDirRequest {
+    fn default() -> &'a RemoveSecretDirRequest {
+        <RemoveSecretDirRequest as ::protobuf::Message>::default_instance()
     }
 }
 
-impl SecureMountResponse {
-    pub fn new() -> SecureMountResponse {
+impl RemoveSecretDirRequest {
+    pub fn new() -> RemoveSecretDirRequest {
         ::std::default::Default::default()
     }
 
@@ -751,20 +4412,20 @@ impl SecureMountResponse {
         let mut fields = ::std::vec::Vec::with_capacity(1);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
-            "mount_path",
-            |m: &SecureMountResponse| { &m.mount_path },
-            |m: &mut SecureMountResponse| { &mut m.mount_path },
+            "container_id",
+            |m: &RemoveSecretDirRequest| { &m.container_id },
+            |m: &mut RemoveSecretDirRequest| { &mut m.container_id },
         ));
-        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<SecureMountResponse>(
-            "SecureMountResponse",
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<RemoveSecretDirRequest>(
+            "RemoveSecretDirRequest",
             fields,
             oneofs,
         )
     }
 }
 
-impl ::protobuf::Message for SecureMountResponse {
-    const NAME: &'static str = "SecureMountResponse";
+impl ::protobuf::Message for RemoveSecretDirRequest {
+    const NAME: &'static str = "RemoveSecretDirRequest";
 
     fn is_initialized(&self) -> bool {
         true
@@ -774,7 +4435,7 @@ impl ::protobuf::Message for SecureMountResponse {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
                 10 => {
-                    self.mount_path = is.read_string()?;
+                    self.container_id = is.read_string()?;
                 },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
@@ -788,8 +4449,8 @@ impl ::protobuf::Message for SecureMountResponse {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
-        if !self.mount_path.is_empty() {
-            my_size += ::protobuf::rt::string_size(1, &self.mount_path);
+        if !self.container_id.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.container_id);
         }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
@@ -797,8 +4458,8 @@ impl ::protobuf::Message for SecureMountResponse {
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
-        if !self.mount_path.is_empty() {
-            os.write_string(1, &self.mount_path)?;
+        if !self.container_id.is_empty() {
+            os.write_string(1, &self.container_id)?;
         }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
@@ -812,38 +4473,142 @@ impl ::protobuf::Message for SecureMountResponse {
         &mut self.special_fields
     }
 
-    fn new() -> SecureMountResponse {
-        SecureMountResponse::new()
+    fn new() -> RemoveSecretDirRequest {
+        RemoveSecretDirRequest::new()
     }
 
     fn clear(&mut self) {
-        self.mount_path.clear();
+        self.container_id.clear();
         self.special_fields.clear();
     }
 
-    fn default_instance() -> &'static SecureMountResponse {
-        static instance: SecureMountResponse = SecureMountResponse {
-            mount_path: ::std::string::String::new(),
+    fn default_instance() -> &'static RemoveSecretDirRequest {
+        static instance: RemoveSecretDirRequest = RemoveSecretDirRequest {
+            container_id: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
     }
 }
 
-impl ::protobuf::MessageFull for SecureMountResponse {
+impl ::protobuf::MessageFull for RemoveSecretDirRequest {
     fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
         static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
-        descriptor.get(|| file_descriptor().message_by_package_relative_name("SecureMountResponse").unwrap()).clone()
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("RemoveSecretDirRequest").unwrap()).clone()
     }
 }
 
-impl ::std::fmt::Display for SecureMountResponse {
+impl ::std::fmt::Display for RemoveSecretDirRequest {
     fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
         ::protobuf::text_format::fmt(self, f)
     }
 }
 
-impl ::protobuf::reflect::ProtobufValue for SecureMountResponse {
+impl ::protobuf::reflect::ProtobufValue for RemoveSecretDirRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:api.RemoveSecretDirResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct RemoveSecretDirResponse {
+    // message fields
+    // special fields
+    // @@protoc_insertion_point(special_field:api.RemoveSecretDirResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a RemoveSecretDirResponse {
+    fn default() -> &'a RemoveSecretDirResponse {
+        <RemoveSecretDirResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl RemoveSecretDirResponse {
+    pub fn new() -> RemoveSecretDirResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(0);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<RemoveSecretDirResponse>(
+            "RemoveSecretDirResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for RemoveSecretDirResponse {
+    const NAME: &'static str = "RemoveSecretDirResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> RemoveSecretDirResponse {
+        RemoveSecretDirResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static RemoveSecretDirResponse {
+        static instance: RemoveSecretDirResponse = RemoveSecretDirResponse {
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for RemoveSecretDirResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("RemoveSecretDirResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for RemoveSecretDirResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for RemoveSecretDirResponse {
     type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
 }
 
@@ -852,18 +4617,94 @@ static file_descriptor_proto_data: &'static [u8] = b"\
     \x18\x01\x20\x01(\x0cR\x06secret\"2\n\x12UnsealSecretOutput\x12\x1c\n\tp\
     laintext\x18\x01\x20\x01(\x0cR\tplaintext\"8\n\x12GetResourceRequest\x12\
     \"\n\x0cResourcePath\x18\x01\x20\x01(\tR\x0cResourcePath\"1\n\x13GetReso\
-    urceResponse\x12\x1a\n\x08Resource\x18\x01\x20\x01(\x0cR\x08Resource\"\
-    \xbe\x01\n\x12SecureMountRequest\x12\x16\n\x06driver\x18\x01\x20\x01(\tR\
-    \x06driver\x12%\n\x0edriver_options\x18\x02\x20\x03(\tR\rdriverOptions\
-    \x12\x16\n\x06source\x18\x03\x20\x01(\tR\x06source\x12\x16\n\x06fstype\
-    \x18\x04\x20\x01(\tR\x06fstype\x12\x18\n\x07options\x18\x05\x20\x03(\tR\
-    \x07options\x12\x1f\n\x0bmount_point\x18\x06\x20\x01(\tR\nmountPoint\"4\
-    \n\x13SecureMountResponse\x12\x1d\n\nmount_path\x18\x01\x20\x01(\tR\tmou\
-    ntPath2V\n\x13SealedSecretService\x12?\n\x0cUnsealSecret\x12\x16.api.Uns\
-    ealSecretInput\x1a\x17.api.UnsealSecretOutput2V\n\x12GetResourceService\
-    \x12@\n\x0bGetResource\x12\x17.api.GetResourceRequest\x1a\x18.api.GetRes\
-    ourceResponse2V\n\x12SecureMountService\x12@\n\x0bSecureMount\x12\x17.ap\
-    i.SecureMountRequest\x1a\x18.api.SecureMountResponseb\x06proto3\
+    urceResponse\x12\x1a\n\x08Resource\x18\x01\x20\x01(\x0cR\x08Resource\"?\
+    \n\x19InvalidateResourceRequest\x12\"\n\x0cResourcePath\x18\x01\x20\x01(\
+    \tR\x0cResourcePath\">\n\x1aInvalidateResourceResponse\x12\x20\n\x0bInva\
+    lidated\x18\x01\x20\x01(\rR\x0bInvalidated\"\x85\x01\n\x17GetResourceChu\
+    nkRequest\x12\"\n\x0cResourcePath\x18\x01\x20\x01(\tR\x0cResourcePath\
+    \x12\x16\n\x06Offset\x18\x02\x20\x01(\x04R\x06Offset\x12\x16\n\x06Length\
+    \x18\x03\x20\x01(\rR\x06Length\x12\x16\n\x06Digest\x18\x04\x20\x01(\tR\
+    \x06Digest\"d\n\x18GetResourceChunkResponse\x12\x12\n\x04Data\x18\x01\
+    \x20\x01(\x0cR\x04Data\x12\x1c\n\tTotalSize\x18\x02\x20\x01(\x04R\tTotal\
+    Size\x12\x16\n\x06Digest\x18\x03\x20\x01(\tR\x06Digest\"x\n\x1aFetchReso\
+    urceToFileRequest\x12\"\n\x0cResourcePath\x18\x01\x20\x01(\tR\x0cResourc\
+    ePath\x12\x1e\n\nTargetPath\x18\x02\x20\x01(\tR\nTargetPath\x12\x16\n\
+    \x06Digest\x18\x03\x20\x01(\tR\x06Digest\"I\n\x1bFetchResourceToFileResp\
+    onse\x12\x12\n\x04Size\x18\x01\x20\x01(\x04R\x04Size\x12\x16\n\x06Digest\
+    \x18\x02\x20\x01(\tR\x06Digest\"\xbe\x01\n\x12SecureMountRequest\x12\x16\
+    \n\x06driver\x18\x01\x20\x01(\tR\x06driver\x12%\n\x0edriver_options\x18\
+    \x02\x20\x03(\tR\rdriverOptions\x12\x16\n\x06source\x18\x03\x20\x01(\tR\
+    \x06source\x12\x16\n\x06fstype\x18\x04\x20\x01(\tR\x06fstype\x12\x18\n\
+    \x07options\x18\x05\x20\x03(\tR\x07options\x12\x1f\n\x0bmount_point\x18\
+    \x06\x20\x01(\tR\nmountPoint\"4\n\x13SecureMountResponse\x12\x1d\n\nmoun\
+    t_path\x18\x01\x20\x01(\tR\tmountPath\"1\n\x0eUnmountRequest\x12\x1f\n\
+    \x0bmount_point\x18\x01\x20\x01(\tR\nmountPoint\"\x11\n\x0fUnmountRespon\
+    se\"1\n\x0eRemountRequest\x12\x1f\n\x0bmount_point\x18\x01\x20\x01(\tR\n\
+    mountPoint\"0\n\x0fRemountResponse\x12\x1d\n\nmount_path\x18\x01\x20\x01\
+    (\tR\tmountPath\"J\n\x14InjectSecretsRequest\x12\x1a\n\x08manifest\x18\
+    \x01\x20\x01(\x0cR\x08manifest\x12\x16\n\x06rootfs\x18\x02\x20\x01(\tR\
+    \x06rootfs\"-\n\x15InjectSecretsResponse\x12\x14\n\x05paths\x18\x01\x20\
+    \x03(\tR\x05paths\"\x93\x01\n\x12GenerateKeyRequest\x12\x1c\n\talgorithm\
+    \x18\x01\x20\x01(\tR\talgorithm\x12\x1f\n\x0bcommon_name\x18\x02\x20\x01\
+    (\tR\ncommonName\x12\x1b\n\tdns_names\x18\x03\x20\x03(\tR\x08dnsNames\
+    \x12!\n\x0cip_addresses\x18\x04\x20\x03(\tR\x0bipAddresses\"Z\n\x13Gener\
+    ateKeyResponse\x12\x15\n\x06key_id\x18\x01\x20\x01(\tR\x05keyId\x12\x10\
+    \n\x03csr\x18\x02\x20\x01(\x0cR\x03csr\x12\x1a\n\x08evidence\x18\x03\x20\
+    \x01(\x0cR\x08evidence\"T\n\x19InstallCertificateRequest\x12\x15\n\x06ke\
+    y_id\x18\x01\x20\x01(\tR\x05keyId\x12\x20\n\x0bcertificate\x18\x02\x20\
+    \x01(\x0cR\x0bcertificate\"0\n\x1aInstallCertificateResponse\x12\x12\n\
+    \x04path\x18\x01\x20\x01(\tR\x04path\"\xe3\x03\n\x10ImagePullRequest\x12\
+    \x1b\n\timage_url\x18\x01\x20\x01(\tR\x08imageUrl\x12\x1f\n\x0bbundle_pa\
+    th\x18\x02\x20\x01(\tR\nbundlePath\x12\x1b\n\tauth_info\x18\x03\x20\x01(\
+    \tR\x08authInfo\x12\x20\n\x0bsnapshotter\x18\x04\x20\x01(\tR\x0bsnapshot\
+    ter\x12\x1a\n\x08platform\x18\x05\x20\x01(\tR\x08platform\x12\x17\n\x04a\
+    uth\x18\x06\x20\x01(\x08H\0R\x04auth\x88\x01\x01\x12\x1b\n\tauth_file\
+    \x18\x07\x20\x01(\tR\x08authFile\x120\n\x11security_validate\x18\x08\x20\
+    \x01(\x08H\x01R\x10securityValidate\x88\x01\x01\x12\x1f\n\x0bpolicy_path\
+    \x18\t\x20\x01(\tR\npolicyPath\x12'\n\x0fsigstore_config\x18\n\x20\x01(\
+    \tR\x0esigstoreConfig\x12%\n\x0edecrypt_config\x18\x0b\x20\x01(\tR\rdecr\
+    yptConfig\x12#\n\rselinux_label\x18\x0c\x20\x01(\tR\x0cselinuxLabel\x12\
+    \x19\n\x08id_shift\x18\r\x20\x01(\tR\x07idShiftB\x07\n\x05_authB\x14\n\
+    \x12_security_validate\"\x8d\x01\n\x11ImagePullResponse\x12\x19\n\x08ima\
+    ge_id\x18\x01\x20\x01(\tR\x07imageId\x12/\n\x13verification_report\x18\
+    \x02\x20\x01(\tR\x12verificationReport\x12,\n\x12layer_group_mounts\x18\
+    \x03\x20\x03(\tR\x10layerGroupMounts\".\n\x0fPinImageRequest\x12\x1b\n\t\
+    image_url\x18\x01\x20\x01(\tR\x08imageUrl\"\x12\n\x10PinImageResponse\"0\
+    \n\x11UnpinImageRequest\x12\x1b\n\timage_url\x18\x01\x20\x01(\tR\x08imag\
+    eUrl\"0\n\x12UnpinImageResponse\x12\x1a\n\x08unpinned\x18\x01\x20\x01(\
+    \x08R\x08unpinned\"\xb1\x01\n\x19ProvisionSecretDirRequest\x12!\n\x0ccon\
+    tainer_id\x18\x01\x20\x01(\tR\x0bcontainerId\x12\x1a\n\x08manifest\x18\
+    \x02\x20\x01(\x0cR\x08manifest\x12\x1d\n\nmount_path\x18\x03\x20\x01(\tR\
+    \tmountPath\x12\x10\n\x03uid\x18\x04\x20\x01(\rR\x03uid\x12\x10\n\x03gid\
+    \x18\x05\x20\x01(\rR\x03gid\x12\x12\n\x04size\x18\x06\x20\x01(\x04R\x04s\
+    ize\"p\n\x1aProvisionSecretDirResponse\x12\x16\n\x06source\x18\x01\x20\
+    \x01(\tR\x06source\x12\x20\n\x0bdestination\x18\x02\x20\x01(\tR\x0bdesti\
+    nation\x12\x18\n\x07options\x18\x03\x20\x03(\tR\x07options\";\n\x16Remov\
+    eSecretDirRequest\x12!\n\x0ccontainer_id\x18\x01\x20\x01(\tR\x0bcontaine\
+    rId\"\x19\n\x17RemoveSecretDirResponse2V\n\x13SealedSecretService\x12?\n\
+    \x0cUnsealSecret\x12\x16.api.UnsealSecretInput\x1a\x17.api.UnsealSecretO\
+    utput2\xd8\x02\n\x12GetResourceService\x12@\n\x0bGetResource\x12\x17.api\
+    .GetResourceRequest\x1a\x18.api.GetResourceResponse\x12U\n\x12Invalidate\
+    Resource\x12\x1e.api.InvalidateResourceRequest\x1a\x1f.api.InvalidateRes\
+    ourceResponse\x12O\n\x10GetResourceChunk\x12\x1c.api.GetResourceChunkReq\
+    uest\x1a\x1d.api.GetResourceChunkResponse\x12X\n\x13FetchResourceToFile\
+    \x12\x1f.api.FetchResourceToFileRequest\x1a\x20.api.FetchResourceToFileR\
+    esponse2\xc2\x01\n\x12SecureMountService\x12@\n\x0bSecureMount\x12\x17.a\
+    pi.SecureMountRequest\x1a\x18.api.SecureMountResponse\x124\n\x07Unmount\
+    \x12\x13.api.UnmountRequest\x1a\x14.api.UnmountResponse\x124\n\x07Remoun\
+    t\x12\x13.api.RemountRequest\x1a\x14.api.RemountResponse2`\n\x16SecretIn\
+    jectionService\x12F\n\rInjectSecrets\x12\x19.api.InjectSecretsRequest\
+    \x1a\x1a.api.InjectSecretsResponse2\xa5\x01\n\nKeyService\x12@\n\x0bGene\
+    rateKey\x12\x17.api.GenerateKeyRequest\x1a\x18.api.GenerateKeyResponse\
+    \x12U\n\x12InstallCertificate\x12\x1e.api.InstallCertificateRequest\x1a\
+    \x1f.api.InstallCertificateResponse2\xc6\x01\n\x10ImagePullService\x12:\
+    \n\tPullImage\x12\x15.api.ImagePullRequest\x1a\x16.api.ImagePullResponse\
+    \x127\n\x08PinImage\x12\x14.api.PinImageRequest\x1a\x15.api.PinImageResp\
+    onse\x12=\n\nUnpinImage\x12\x16.api.UnpinImageRequest\x1a\x17.api.UnpinI\
+    mageResponse2\xb7\x01\n\x10SecretDirService\x12U\n\x12ProvisionSecretDir\
+    \x12\x1e.api.ProvisionSecretDirRequest\x1a\x1f.api.ProvisionSecretDirRes\
+    ponse\x12L\n\x0fRemoveSecretDir\x12\x1b.api.RemoveSecretDirRequest\x1a\
+    \x1c.api.RemoveSecretDirResponseb\x06proto3\
 ";
 
 /// `FileDescriptorProto` object which was a source for this generated file
@@ -881,13 +4722,39 @@ pub fn file_descriptor() -> &'static ::protobuf::reflect::FileDescriptor {
     file_descriptor.get(|| {
         let generated_file_descriptor = generated_file_descriptor_lazy.get(|| {
             let mut deps = ::std::vec::Vec::with_capacity(0);
-            let mut messages = ::std::vec::Vec::with_capacity(6);
+            let mut messages = ::std::vec::Vec::with_capacity(32);
             messages.push(UnsealSecretInput::generated_message_descriptor_data());
             messages.push(UnsealSecretOutput::generated_message_descriptor_data());
             messages.push(GetResourceRequest::generated_message_descriptor_data());
             messages.push(GetResourceResponse::generated_message_descriptor_data());
+            messages.push(InvalidateResourceRequest::generated_message_descriptor_data());
+            messages.push(InvalidateResourceResponse::generated_message_descriptor_data());
+            messages.push(GetResourceChunkRequest::generated_message_descriptor_data());
+            messages.push(GetResourceChunkResponse::generated_message_descriptor_data());
+            messages.push(FetchResourceToFileRequest::generated_message_descriptor_data());
+            messages.push(FetchResourceToFileResponse::generated_message_descriptor_data());
             messages.push(SecureMountRequest::generated_message_descriptor_data());
             messages.push(SecureMountResponse::generated_message_descriptor_data());
+            messages.push(UnmountRequest::generated_message_descriptor_data());
+            messages.push(UnmountResponse::generated_message_descriptor_data());
+            messages.push(RemountRequest::generated_message_descriptor_data());
+            messages.push(RemountResponse::generated_message_descriptor_data());
+            messages.push(InjectSecretsRequest::generated_message_descriptor_data());
+            messages.push(InjectSecretsResponse::generated_message_descriptor_data());
+            messages.push(GenerateKeyRequest::generated_message_descriptor_data());
+            messages.push(GenerateKeyResponse::generated_message_descriptor_data());
+            messages.push(InstallCertificateRequest::generated_message_descriptor_data());
+            messages.push(InstallCertificateResponse::generated_message_descriptor_data());
+            messages.push(ImagePullRequest::generated_message_descriptor_data());
+            messages.push(ImagePullResponse::generated_message_descriptor_data());
+            messages.push(PinImageRequest::generated_message_descriptor_data());
+            messages.push(PinImageResponse::generated_message_descriptor_data());
+            messages.push(UnpinImageRequest::generated_message_descriptor_data());
+            messages.push(UnpinImageResponse::generated_message_descriptor_data());
+            messages.push(ProvisionSecretDirRequest::generated_message_descriptor_data());
+            messages.push(ProvisionSecretDirResponse::generated_message_descriptor_data());
+            messages.push(RemoveSecretDirRequest::generated_message_descriptor_data());
+            messages.push(RemoveSecretDirResponse::generated_message_descriptor_data());
             let mut enums = ::std::vec::Vec::with_capacity(0);
             ::protobuf::reflect::GeneratedFileDescriptor::new_generated(
                 file_descriptor_proto(),
@@ -84,6 +84,21 @@ impl GetResourceServiceClient {
         let mut cres = super::api::GetResourceResponse::new();
         ::ttrpc::async_client_request!(self, ctx, req, "api.GetResourceService", "GetResource", cres);
     }
+
+    pub async fn invalidate_resource(&self, ctx: ttrpc::context::Context, req: &super::api::InvalidateResourceRequest) -> ::ttrpc::Result<super::api::InvalidateResourceResponse> {
+        let mut cres = super::api::InvalidateResourceResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.GetResourceService", "InvalidateResource", cres);
+    }
+
+    pub async fn get_resource_chunk(&self, ctx: ttrpc::context::Context, req: &super::api::GetResourceChunkRequest) -> ::ttrpc::Result<super::api::GetResourceChunkResponse> {
+        let mut cres = super::api::GetResourceChunkResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.GetResourceService", "GetResourceChunk", cres);
+    }
+
+    pub async fn fetch_resource_to_file(&self, ctx: ttrpc::context::Context, req: &super::api::FetchResourceToFileRequest) -> ::ttrpc::Result<super::api::FetchResourceToFileResponse> {
+        let mut cres = super::api::FetchResourceToFileResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.GetResourceService", "FetchResourceToFile", cres);
+    }
 }
 
 struct GetResourceMethod {
@@ -97,11 +112,53 @@ impl ::ttrpc::r#async::MethodHandler for GetResourceMethod {
     }
 }
 
+struct InvalidateResourceMethod {
+    service: Arc<Box<dyn GetResourceService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for InvalidateResourceMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, InvalidateResourceRequest, invalidate_resource);
+    }
+}
+
+struct GetResourceChunkMethod {
+    service: Arc<Box<dyn GetResourceService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for GetResourceChunkMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, GetResourceChunkRequest, get_resource_chunk);
+    }
+}
+
+struct FetchResourceToFileMethod {
+    service: Arc<Box<dyn GetResourceService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for FetchResourceToFileMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, FetchResourceToFileRequest, fetch_resource_to_file);
+    }
+}
+
 #[async_trait]
 pub trait GetResourceService: Sync {
     async fn get_resource(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::GetResourceRequest) -> ::ttrpc::Result<super::api::GetResourceResponse> {
         Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.GetResourceService/GetResource is not supported".to_string())))
     }
+    async fn invalidate_resource(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::InvalidateResourceRequest) -> ::ttrpc::Result<super::api::InvalidateResourceResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.GetResourceService/InvalidateResource is not supported".to_string())))
+    }
+    async fn get_resource_chunk(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::GetResourceChunkRequest) -> ::ttrpc::Result<super::api::GetResourceChunkResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.GetResourceService/GetResourceChunk is not supported".to_string())))
+    }
+    async fn fetch_resource_to_file(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::FetchResourceToFileRequest) -> ::ttrpc::Result<super::api::FetchResourceToFileResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.GetResourceService/FetchResourceToFile is not supported".to_string())))
+    }
 }
 
 pub fn create_get_resource_service(service: Arc<Box<dyn GetResourceService + Send + Sync>>) -> HashMap<String, ::ttrpc::r#async::Service> {
@@ -112,6 +169,15 @@ pub fn create_get_resource_service(service: Arc<Box<dyn GetResourceService + Sen
     methods.insert("GetResource".to_string(),
                     Box::new(GetResourceMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
 
+    methods.insert("InvalidateResource".to_string(),
+                    Box::new(InvalidateResourceMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    methods.insert("GetResourceChunk".to_string(),
+                    Box::new(GetResourceChunkMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    methods.insert("FetchResourceToFile".to_string(),
+                    Box::new(FetchResourceToFileMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
     ret.insert("api.GetResourceService".to_string(), ::ttrpc::r#async::Service{ methods, streams });
     ret
 }
@@ -132,6 +198,16 @@ impl SecureMountServiceClient {
         let mut cres = super::api::SecureMountResponse::new();
         ::ttrpc::async_client_request!(self, ctx, req, "api.SecureMountService", "SecureMount", cres);
     }
+
+    pub async fn unmount(&self, ctx: ttrpc::context::Context, req: &super::api::UnmountRequest) -> ::ttrpc::Result<super::api::UnmountResponse> {
+        let mut cres = super::api::UnmountResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.SecureMountService", "Unmount", cres);
+    }
+
+    pub async fn remount(&self, ctx: ttrpc::context::Context, req: &super::api::RemountRequest) -> ::ttrpc::Result<super::api::RemountResponse> {
+        let mut cres = super::api::RemountResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.SecureMountService", "Remount", cres);
+    }
 }
 
 struct SecureMountMethod {
@@ -145,11 +221,39 @@ impl ::ttrpc::r#async::MethodHandler for SecureMountMethod {
     }
 }
 
+struct UnmountMethod {
+    service: Arc<Box<dyn SecureMountService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for UnmountMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, UnmountRequest, unmount);
+    }
+}
+
+struct RemountMethod {
+    service: Arc<Box<dyn SecureMountService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for RemountMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, RemountRequest, remount);
+    }
+}
+
 #[async_trait]
 pub trait SecureMountService: Sync {
     async fn secure_mount(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::SecureMountRequest) -> ::ttrpc::Result<super::api::SecureMountResponse> {
         Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.SecureMountService/SecureMount is not supported".to_string())))
     }
+    async fn unmount(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::UnmountRequest) -> ::ttrpc::Result<super::api::UnmountResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.SecureMountService/Unmount is not supported".to_string())))
+    }
+    async fn remount(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::RemountRequest) -> ::ttrpc::Result<super::api::RemountResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.SecureMountService/Remount is not supported".to_string())))
+    }
 }
 
 pub fn create_secure_mount_service(service: Arc<Box<dyn SecureMountService + Send + Sync>>) -> HashMap<String, ::ttrpc::r#async::Service> {
@@ -160,6 +264,292 @@ pub fn create_secure_mount_service(service: Arc<Box<dyn SecureMountService + Sen
     methods.insert("SecureMount".to_string(),
                     Box::new(SecureMountMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
 
+    methods.insert("Unmount".to_string(),
+                    Box::new(UnmountMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    methods.insert("Remount".to_string(),
+                    Box::new(RemountMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
     ret.insert("api.SecureMountService".to_string(), ::ttrpc::r#async::Service{ methods, streams });
     ret
 }
+
+#[derive(Clone)]
+pub struct SecretInjectionServiceClient {
+    client: ::ttrpc::r#async::Client,
+}
+
+impl SecretInjectionServiceClient {
+    pub fn new(client: ::ttrpc::r#async::Client) -> Self {
+        SecretInjectionServiceClient {
+            client,
+        }
+    }
+
+    pub async fn inject_secrets(&self, ctx: ttrpc::context::Context, req: &super::api::InjectSecretsRequest) -> ::ttrpc::Result<super::api::InjectSecretsResponse> {
+        let mut cres = super::api::InjectSecretsResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.SecretInjectionService", "InjectSecrets", cres);
+    }
+}
+
+struct InjectSecretsMethod {
+    service: Arc<Box<dyn SecretInjectionService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for InjectSecretsMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, InjectSecretsRequest, inject_secrets);
+    }
+}
+
+#[async_trait]
+pub trait SecretInjectionService: Sync {
+    async fn inject_secrets(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::InjectSecretsRequest) -> ::ttrpc::Result<super::api::InjectSecretsResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.SecretInjectionService/InjectSecrets is not supported".to_string())))
+    }
+}
+
+pub fn create_secret_injection_service(service: Arc<Box<dyn SecretInjectionService + Send + Sync>>) -> HashMap<String, ::ttrpc::r#async::Service> {
+    let mut ret = HashMap::new();
+    let mut methods = HashMap::new();
+    let streams = HashMap::new();
+
+    methods.insert("InjectSecrets".to_string(),
+                    Box::new(InjectSecretsMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    ret.insert("api.SecretInjectionService".to_string(), ::ttrpc::r#async::Service{ methods, streams });
+    ret
+}
+
+#[derive(Clone)]
+pub struct KeyServiceClient {
+    client: ::ttrpc::r#async::Client,
+}
+
+impl KeyServiceClient {
+    pub fn new(client: ::ttrpc::r#async::Client) -> Self {
+        KeyServiceClient {
+            client,
+        }
+    }
+
+    pub async fn generate_key(&self, ctx: ttrpc::context::Context, req: &super::api::GenerateKeyRequest) -> ::ttrpc::Result<super::api::GenerateKeyResponse> {
+        let mut cres = super::api::GenerateKeyResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.KeyService", "GenerateKey", cres);
+    }
+
+    pub async fn install_certificate(&self, ctx: ttrpc::context::Context, req: &super::api::InstallCertificateRequest) -> ::ttrpc::Result<super::api::InstallCertificateResponse> {
+        let mut cres = super::api::InstallCertificateResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.KeyService", "InstallCertificate", cres);
+    }
+}
+
+struct GenerateKeyMethod {
+    service: Arc<Box<dyn KeyService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for GenerateKeyMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, GenerateKeyRequest, generate_key);
+    }
+}
+
+struct InstallCertificateMethod {
+    service: Arc<Box<dyn KeyService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for InstallCertificateMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, InstallCertificateRequest, install_certificate);
+    }
+}
+
+#[async_trait]
+pub trait KeyService: Sync {
+    async fn generate_key(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::GenerateKeyRequest) -> ::ttrpc::Result<super::api::GenerateKeyResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.KeyService/GenerateKey is not supported".to_string())))
+    }
+    async fn install_certificate(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::InstallCertificateRequest) -> ::ttrpc::Result<super::api::InstallCertificateResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.KeyService/InstallCertificate is not supported".to_string())))
+    }
+}
+
+pub fn create_key_service(service: Arc<Box<dyn KeyService + Send + Sync>>) -> HashMap<String, ::ttrpc::r#async::Service> {
+    let mut ret = HashMap::new();
+    let mut methods = HashMap::new();
+    let streams = HashMap::new();
+
+    methods.insert("GenerateKey".to_string(),
+                    Box::new(GenerateKeyMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    methods.insert("InstallCertificate".to_string(),
+                    Box::new(InstallCertificateMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    ret.insert("api.KeyService".to_string(), ::ttrpc::r#async::Service{ methods, streams });
+    ret
+}
+
+#[derive(Clone)]
+pub struct ImagePullServiceClient {
+    client: ::ttrpc::r#async::Client,
+}
+
+impl ImagePullServiceClient {
+    pub fn new(client: ::ttrpc::r#async::Client) -> Self {
+        ImagePullServiceClient {
+            client,
+        }
+    }
+
+    pub async fn pull_image(&self, ctx: ttrpc::context::Context, req: &super::api::ImagePullRequest) -> ::ttrpc::Result<super::api::ImagePullResponse> {
+        let mut cres = super::api::ImagePullResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.ImagePullService", "PullImage", cres);
+    }
+
+    pub async fn pin_image(&self, ctx: ttrpc::context::Context, req: &super::api::PinImageRequest) -> ::ttrpc::Result<super::api::PinImageResponse> {
+        let mut cres = super::api::PinImageResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.ImagePullService", "PinImage", cres);
+    }
+
+    pub async fn unpin_image(&self, ctx: ttrpc::context::Context, req: &super::api::UnpinImageRequest) -> ::ttrpc::Result<super::api::UnpinImageResponse> {
+        let mut cres = super::api::UnpinImageResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.ImagePullService", "UnpinImage", cres);
+    }
+}
+
+struct PullImageMethod {
+    service: Arc<Box<dyn ImagePullService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for PullImageMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, ImagePullRequest, pull_image);
+    }
+}
+
+struct PinImageMethod {
+    service: Arc<Box<dyn ImagePullService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for PinImageMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, PinImageRequest, pin_image);
+    }
+}
+
+struct UnpinImageMethod {
+    service: Arc<Box<dyn ImagePullService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for UnpinImageMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, UnpinImageRequest, unpin_image);
+    }
+}
+
+#[async_trait]
+pub trait ImagePullService: Sync {
+    async fn pull_image(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::ImagePullRequest) -> ::ttrpc::Result<super::api::ImagePullResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.ImagePullService/PullImage is not supported".to_string())))
+    }
+    async fn pin_image(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::PinImageRequest) -> ::ttrpc::Result<super::api::PinImageResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.ImagePullService/PinImage is not supported".to_string())))
+    }
+    async fn unpin_image(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::UnpinImageRequest) -> ::ttrpc::Result<super::api::UnpinImageResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.ImagePullService/UnpinImage is not supported".to_string())))
+    }
+}
+
+pub fn create_image_pull_service(service: Arc<Box<dyn ImagePullService + Send + Sync>>) -> HashMap<String, ::ttrpc::r#async::Service> {
+    let mut ret = HashMap::new();
+    let mut methods = HashMap::new();
+    let streams = HashMap::new();
+
+    methods.insert("PullImage".to_string(),
+                    Box::new(PullImageMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    methods.insert("PinImage".to_string(),
+                    Box::new(PinImageMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    methods.insert("UnpinImage".to_string(),
+                    Box::new(UnpinImageMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    ret.insert("api.ImagePullService".to_string(), ::ttrpc::r#async::Service{ methods, streams });
+    ret
+}
+
+#[derive(Clone)]
+pub struct SecretDirServiceClient {
+    client: ::ttrpc::r#async::Client,
+}
+
+impl SecretDirServiceClient {
+    pub fn new(client: ::ttrpc::r#async::Client) -> Self {
+        SecretDirServiceClient {
+            client,
+        }
+    }
+
+    pub async fn provision_secret_dir(&self, ctx: ttrpc::context::Context, req: &super::api::ProvisionSecretDirRequest) -> ::ttrpc::Result<super::api::ProvisionSecretDirResponse> {
+        let mut cres = super::api::ProvisionSecretDirResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.SecretDirService", "ProvisionSecretDir", cres);
+    }
+
+    pub async fn remove_secret_dir(&self, ctx: ttrpc::context::Context, req: &super::api::RemoveSecretDirRequest) -> ::ttrpc::Result<super::api::RemoveSecretDirResponse> {
+        let mut cres = super::api::RemoveSecretDirResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "api.SecretDirService", "RemoveSecretDir", cres);
+    }
+}
+
+struct ProvisionSecretDirMethod {
+    service: Arc<Box<dyn SecretDirService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for ProvisionSecretDirMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, ProvisionSecretDirRequest, provision_secret_dir);
+    }
+}
+
+struct RemoveSecretDirMethod {
+    service: Arc<Box<dyn SecretDirService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for RemoveSecretDirMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, api, RemoveSecretDirRequest, remove_secret_dir);
+    }
+}
+
+#[async_trait]
+pub trait SecretDirService: Sync {
+    async fn provision_secret_dir(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::ProvisionSecretDirRequest) -> ::ttrpc::Result<super::api::ProvisionSecretDirResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.SecretDirService/ProvisionSecretDir is not supported".to_string())))
+    }
+    async fn remove_secret_dir(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::api::RemoveSecretDirRequest) -> ::ttrpc::Result<super::api::RemoveSecretDirResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/api.SecretDirService/RemoveSecretDir is not supported".to_string())))
+    }
+}
+
+pub fn create_secret_dir_service(service: Arc<Box<dyn SecretDirService + Send + Sync>>) -> HashMap<String, ::ttrpc::r#async::Service> {
+    let mut ret = HashMap::new();
+    let mut methods = HashMap::new();
+    let streams = HashMap::new();
+
+    methods.insert("ProvisionSecretDir".to_string(),
+                    Box::new(ProvisionSecretDirMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    methods.insert("RemoveSecretDir".to_string(),
+                    Box::new(RemoveSecretDirMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    ret.insert("api.SecretDirService".to_string(), ::ttrpc::r#async::Service{ methods, streams });
+    ret
+}
@@ -3,15 +3,21 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use std::{path::Path, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use anyhow::{anyhow, Context, Result};
 use api_ttrpc::{
-    create_get_resource_service, create_sealed_secret_service, create_secure_mount_service,
+    create_get_resource_service, create_sealed_secret_service, create_secret_injection_service,
+    create_secure_mount_service,
 };
 use clap::Parser;
+use confidential_data_hub::cache::ResourceCacheConfig;
 use keyprovider_ttrpc::create_key_provider_service;
 use log::info;
+use serde::Serialize;
 use server::Server;
 use tokio::{
     fs,
@@ -23,13 +29,15 @@ mod api;
 mod api_ttrpc;
 mod keyprovider;
 mod keyprovider_ttrpc;
+mod measure;
 mod server;
 
 const DEFAULT_CDH_SOCKET_ADDR: &str = "unix:///run/confidential-containers/cdh.sock";
 
 const UNIX_SOCKET_PREFIX: &str = "unix://";
 
-#[derive(Debug, Parser)]
+/// The effective options are measured at startup, see [`measure`].
+#[derive(Debug, Parser, Serialize)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     /// CDH ttRPC Unix socket addr.
@@ -39,6 +47,64 @@ struct Cli {
     /// `--socket unix:///tmp/cdh_keyprovider`
     #[arg(default_value_t = DEFAULT_CDH_SOCKET_ADDR.to_string(), short)]
     socket: String,
+
+    /// Path to the JSON config of the GetResource cache.
+    ///
+    /// If not given, the default TTLs and size bounds are used.
+    ///
+    /// `--resource-cache-config /etc/cdh/resource-cache.json`
+    #[arg(long)]
+    resource_cache_config: Option<String>,
+
+    /// Path to the JSON config of the plugins serving secret stores, see
+    /// `kms::plugins::external`.
+    ///
+    /// No plugins are registered if not given.
+    ///
+    /// `--secret-plugins-config /etc/cdh/secret-plugins.json`
+    #[cfg(feature = "external")]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret_plugins_config: Option<String>,
+
+    /// Dir the rootfs secrets are injected into must be under.
+    ///
+    /// `/run/kata-containers` if not given.
+    ///
+    /// `--inject-rootfs-base /run/kata-containers`
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inject_rootfs_base: Option<String>,
+
+    /// Dir the targets of `FetchResourceToFile` must be under.
+    ///
+    /// `/run/confidential-containers/cdh/resources` if not given.
+    ///
+    /// `--fetch-target-base /run/confidential-containers/cdh/resources`
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fetch_target_base: Option<String>,
+
+    /// Addr of the gRPC gateway of the sealed secret and resource APIs.
+    ///
+    /// A loopback TCP address or a unix socket, since the gateway has no
+    /// TLS. The gateway is not served if not given.
+    ///
+    /// `--grpc-socket 127.0.0.1:8043`
+    #[cfg(feature = "grpc")]
+    #[arg(long)]
+    grpc_socket: Option<String>,
+
+    /// OTLP/gRPC endpoint of the OpenTelemetry collector the spans of the
+    /// requests, and of the image pulls, are exported to.
+    ///
+    /// The spans are not exported if not given.
+    ///
+    /// `--otlp-endpoint http://127.0.0.1:4317`
+    #[cfg(feature = "otlp")]
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    otlp_endpoint: Option<String>,
 }
 
 macro_rules! ttrpc_service {
@@ -54,6 +120,12 @@ async fn main() -> Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
     let cli = Cli::parse();
 
+    #[cfg(feature = "otlp")]
+    let otlp_endpoint = cli.otlp_endpoint.as_deref();
+    #[cfg(not(feature = "otlp"))]
+    let otlp_endpoint = None;
+    telemetry::init("confidential-data-hub", otlp_endpoint).context("init telemetry")?;
+
     let unix_socket_path = cli
         .socket
         .strip_prefix(UNIX_SOCKET_PREFIX)
@@ -62,17 +134,60 @@ async fn main() -> Result<()> {
     create_socket_parent_directory(unix_socket_path).await?;
     clean_previous_sock_file(unix_socket_path).await?;
 
+    let cache_config = match &cli.resource_cache_config {
+        Some(path) => {
+            let config = fs::read(path).await.context("read resource cache config")?;
+            serde_json::from_slice(&config).context("parse resource cache config")?
+        }
+        None => ResourceCacheConfig::default(),
+    };
+
+    #[cfg(feature = "external")]
+    let secret_plugins = match &cli.secret_plugins_config {
+        Some(path) => {
+            let config = fs::read(path).await.context("read secret plugins config")?;
+            let config: kms::plugins::external::PluginsConfig =
+                serde_json::from_slice(&config).context("parse secret plugins config")?;
+            kms::plugins::external::register_plugins(config.clone())
+                .context("register secret plugins")?;
+            Some(serde_json::to_value(config)?)
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "external"))]
+    let secret_plugins = None;
+
+    let config_digest =
+        measure::config_digest(&cli, &cache_config, secret_plugins.as_ref()).await?;
+    info!("CDH configuration digest: {config_digest}");
+    measure::extend(&config_digest)
+        .await
+        .context("measure the configuration of CDH")?;
+
+    let inject_rootfs_base = cli.inject_rootfs_base.as_ref().map(PathBuf::from);
+    let fetch_target_base = cli.fetch_target_base.as_ref().map(PathBuf::from);
+    Server::init(cache_config, inject_rootfs_base, fetch_target_base).await?;
+
     let sealed_secret_service = ttrpc_service!(create_sealed_secret_service);
     let get_resource_service = ttrpc_service!(create_get_resource_service);
     let key_provider_service = ttrpc_service!(create_key_provider_service);
     let secure_mount_service = ttrpc_service!(create_secure_mount_service);
-    let mut server = TtrpcServer::new()
+    let secret_injection_service = ttrpc_service!(create_secret_injection_service);
+    let server = TtrpcServer::new()
         .bind(&cli.socket)
         .context("cannot bind cdh ttrpc service")?
         .register_service(sealed_secret_service)
         .register_service(get_resource_service)
         .register_service(secure_mount_service)
+        .register_service(secret_injection_service)
         .register_service(key_provider_service);
+    #[cfg(feature = "image-pull")]
+    let server = server.register_service(ttrpc_service!(api_ttrpc::create_image_pull_service));
+    #[cfg(feature = "key-service")]
+    let server = server.register_service(ttrpc_service!(api_ttrpc::create_key_service));
+    #[cfg(feature = "secret-dirs")]
+    let server = server.register_service(ttrpc_service!(api_ttrpc::create_secret_dir_service));
+    let mut server = server;
 
     info!(
         "Confidential Data Hub starts to listen to request: {}",
@@ -80,6 +195,15 @@ async fn main() -> Result<()> {
     );
     server.start().await?;
 
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = cli.grpc_socket {
+        tokio::spawn(async move {
+            if let Err(e) = server::grpc::serve(&addr).await {
+                log::error!("CDH gRPC gateway failed: {e:?}");
+            }
+        });
+    }
+
     let mut interrupt = signal(SignalKind::interrupt())?;
     let mut hangup = signal(SignalKind::hangup())?;
     tokio::select! {
@@ -92,6 +216,7 @@ async fn main() -> Result<()> {
             server.shutdown().await?;
         }
     };
+    telemetry::shutdown();
 
     Ok(())
 }
@@ -0,0 +1,66 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Measurement of the effective configuration of CDH.
+//!
+//! At startup the command line, the resource cache config, the image-rs
+//! config and the secret plugins config, if any, are hashed and sent to AA
+//! as the runtime measurement event `config.cdh:sha256:<hex>`. AA extends it
+//! into the evidence and registers it as the claim `config.cdh` of the
+//! tokens, bound to the executable of CDH, so that relying parties can tell
+//! CDHs launched with weakened configs apart. CDH doesn't start if AA
+//! refuses the measurement, e.g. because another process measured `cdh`
+//! first. The digest is the one of `crypto::canonical`, like the ones of
+//! `attestation_agent::config_measurement`.
+
+use anyhow::Result;
+use confidential_data_hub::cache::ResourceCacheConfig;
+use crypto::canonical::canonical_digest;
+use kbs_protocol::token_provider::AATokenProvider;
+use serde_json::{json, Value};
+#[cfg(feature = "image-pull")]
+use sha2::{Digest, Sha256};
+
+use crate::Cli;
+
+const COMPONENT: &str = "cdh";
+
+/// `sha256:<hex>` digest of the effective configuration.
+pub async fn config_digest(
+    cli: &Cli,
+    cache_config: &ResourceCacheConfig,
+    secret_plugins: Option<&Value>,
+) -> Result<String> {
+    #[cfg(feature = "image-pull")]
+    let image_config = match tokio::fs::read(image_rs::config::CONFIGURATION_FILE_PATH).await {
+        Ok(config) => Some(format!("sha256:{:x}", Sha256::digest(config))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => anyhow::bail!("read image-rs config: {e}"),
+    };
+    #[cfg(not(feature = "image-pull"))]
+    let image_config: Option<String> = None;
+
+    let mut config = json!({
+        "cli": cli,
+        "resource_cache": cache_config,
+        "image_config": image_config,
+    });
+    // only added if configured, so that the digests of the configs without
+    // plugins stay the same
+    if let Some(secret_plugins) = secret_plugins {
+        config["secret_plugins"] = secret_plugins.clone();
+    }
+    canonical_digest(&config)
+}
+
+/// Extend the configuration `digest` into the runtime measurement through AA.
+pub async fn extend(digest: &str) -> Result<()> {
+    let event = format!("config.{COMPONENT}:{digest}");
+    AATokenProvider::new()
+        .await?
+        .extend_runtime_measurement(vec![event.into_bytes()])
+        .await?;
+    Ok(())
+}
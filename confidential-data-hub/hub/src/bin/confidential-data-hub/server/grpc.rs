@@ -0,0 +1,209 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! gRPC gateway of the sealed secret and resource APIs, for in-pod
+//! applications without a ttRPC client. The services are generated from
+//! the same `api.proto` as the ttRPC ones, see `protos/api.openapi.yaml`
+//! for their HTTP/JSON mapping.
+//!
+//! The gateway has neither TLS nor authentication, so it only listens to a
+//! unix socket or a loopback address, never to the network.
+
+use std::net::SocketAddr;
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info};
+use telemetry::FutureExt;
+use tokio::net::UnixListener;
+use tokio_stream::wrappers::UnixListenerStream;
+use tonic::{transport::Server, Request, Response, Status};
+
+use super::HUB;
+use crate::UNIX_SOCKET_PREFIX;
+use api::get_resource_service_server::{GetResourceService, GetResourceServiceServer};
+use api::sealed_secret_service_server::{SealedSecretService, SealedSecretServiceServer};
+use api::{
+    FetchResourceToFileRequest, FetchResourceToFileResponse, GetResourceChunkRequest,
+    GetResourceChunkResponse, GetResourceRequest, GetResourceResponse, InvalidateResourceRequest,
+    InvalidateResourceResponse, UnsealSecretInput, UnsealSecretOutput,
+};
+
+mod api {
+    tonic::include_proto!("api");
+}
+
+#[derive(Debug, Default)]
+pub struct GrpcServer;
+
+#[tonic::async_trait]
+impl SealedSecretService for GrpcServer {
+    async fn unseal_secret(
+        &self,
+        request: Request<UnsealSecretInput>,
+    ) -> Result<Response<UnsealSecretOutput>, Status> {
+        debug!("get new gRPC UnsealSecret request");
+        let span = telemetry::grpc_server_span(&request, "UnsealSecret");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let plaintext = reader
+            .unseal_secret(request.into_inner().secret)
+            .with_context(span)
+            .await
+            .map_err(|e| Status::internal(format!("[CDH] [ERROR]: Unseal Secret failed: {e}")))?;
+
+        debug!("send back plaintext of the sealed secret");
+        Ok(Response::new(UnsealSecretOutput { plaintext }))
+    }
+}
+
+#[tonic::async_trait]
+impl GetResourceService for GrpcServer {
+    async fn get_resource(
+        &self,
+        request: Request<GetResourceRequest>,
+    ) -> Result<Response<GetResourceResponse>, Status> {
+        debug!("get new gRPC GetResource request");
+        let span = telemetry::grpc_server_span(&request, "GetResource");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let resource = reader
+            .get_resource(request.into_inner().resource_path)
+            .with_context(span)
+            .await
+            .map_err(|e| Status::internal(format!("[CDH] [ERROR]: Get Resource failed: {e}")))?;
+
+        debug!("send back the resource");
+        Ok(Response::new(GetResourceResponse { resource }))
+    }
+
+    async fn invalidate_resource(
+        &self,
+        request: Request<InvalidateResourceRequest>,
+    ) -> Result<Response<InvalidateResourceResponse>, Status> {
+        debug!("get new gRPC InvalidateResource request");
+        let span = telemetry::grpc_server_span(&request, "InvalidateResource");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let uri = Some(request.into_inner().resource_path).filter(|uri| !uri.is_empty());
+        let invalidated = reader
+            .invalidate_resource(uri)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("[CDH] [ERROR]: Invalidate Resource failed: {e}"))
+            })?;
+
+        debug!("send back the number of invalidated resources");
+        Ok(Response::new(InvalidateResourceResponse {
+            invalidated: invalidated as u32,
+        }))
+    }
+
+    async fn get_resource_chunk(
+        &self,
+        request: Request<GetResourceChunkRequest>,
+    ) -> Result<Response<GetResourceChunkResponse>, Status> {
+        debug!("get new gRPC GetResourceChunk request");
+        let span = telemetry::grpc_server_span(&request, "GetResourceChunk");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let request = request.into_inner();
+        let digest = Some(request.digest).filter(|digest| !digest.is_empty());
+        let chunk = reader
+            .get_resource_chunk(
+                request.resource_path,
+                request.offset,
+                request.length,
+                digest,
+            )
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("[CDH] [ERROR]: Get Resource Chunk failed: {e}"))
+            })?;
+
+        debug!("send back the resource chunk");
+        Ok(Response::new(GetResourceChunkResponse {
+            data: chunk.data,
+            total_size: chunk.info.size,
+            digest: chunk.info.digest,
+        }))
+    }
+
+    async fn fetch_resource_to_file(
+        &self,
+        request: Request<FetchResourceToFileRequest>,
+    ) -> Result<Response<FetchResourceToFileResponse>, Status> {
+        debug!("get new gRPC FetchResourceToFile request");
+        let span = telemetry::grpc_server_span(&request, "FetchResourceToFile");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let request = request.into_inner();
+        let digest = Some(request.digest).filter(|digest| !digest.is_empty());
+        let info = reader
+            .fetch_resource_to_file(request.resource_path, &request.target_path, digest)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("[CDH] [ERROR]: Fetch Resource To File failed: {e}"))
+            })?;
+
+        debug!("send back the size and digest of the written resource");
+        Ok(Response::new(FetchResourceToFileResponse {
+            size: info.size,
+            digest: info.digest,
+        }))
+    }
+}
+
+/// Serve the gRPC gateway on `addr`, a TCP address or a `unix://` path,
+/// until it fails.
+pub async fn serve(addr: &str) -> Result<()> {
+    let router = Server::builder()
+        .add_service(SealedSecretServiceServer::new(GrpcServer))
+        .add_service(GetResourceServiceServer::new(GrpcServer));
+
+    info!("Confidential Data Hub gRPC gateway listens to: {addr}");
+    match addr.strip_prefix(UNIX_SOCKET_PREFIX) {
+        Some(path) => {
+            crate::create_socket_parent_directory(path).await?;
+            crate::clean_previous_sock_file(path).await?;
+            let listener = UnixListener::bind(path).context("cannot bind cdh grpc service")?;
+            router
+                .serve_with_incoming(UnixListenerStream::new(listener))
+                .await?
+        }
+        None => router.serve(loopback_addr(addr)?).await?,
+    }
+
+    Ok(())
+}
+
+// parse the TCP address of the gateway, which must be a loopback one
+fn loopback_addr(addr: &str) -> Result<SocketAddr> {
+    let addr = addr
+        .parse::<SocketAddr>()
+        .context("illegal cdh grpc address")?;
+    if !addr.ip().is_loopback() {
+        bail!("cdh grpc gateway must listen to a unix socket or a loopback address, not {addr}");
+    }
+
+    Ok(addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loopback_addr() {
+        assert!(loopback_addr("127.0.0.1:8043").is_ok());
+        assert!(loopback_addr("[::1]:8043").is_ok());
+        assert!(loopback_addr("0.0.0.0:8043").is_err());
+        assert!(loopback_addr("10.0.0.1:8043").is_err());
+        assert!(loopback_addr("[::]:8043").is_err());
+        assert!(loopback_addr("localhost").is_err());
+    }
+}
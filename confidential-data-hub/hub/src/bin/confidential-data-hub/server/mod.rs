@@ -3,41 +3,95 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+#[cfg(feature = "key-service")]
+use std::net::IpAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use anyhow::Result;
 use async_trait::async_trait;
-use confidential_data_hub::{hub::Hub, DataHub};
+#[cfg(feature = "image-pull")]
+use confidential_data_hub::image_pull::ImagePullOptions;
+#[cfg(feature = "key-service")]
+use confidential_data_hub::keys::{KeyAlgorithm, KeyRequest};
+#[cfg(feature = "secret-dirs")]
+use confidential_data_hub::secret_dirs::SecretDirRequest;
+use confidential_data_hub::{
+    cache::ResourceCacheConfig, hub::Hub, inject::InjectionManifest, DataHub,
+};
 use lazy_static::lazy_static;
 use log::debug;
 use storage::volume_type::Storage;
+use telemetry::FutureExt;
 use tokio::sync::RwLock;
 use ttrpc::{asynchronous::TtrpcContext, Code, Error, Status};
 
 use crate::{
     api::{
-        GetResourceRequest, GetResourceResponse, SecureMountRequest, SecureMountResponse,
-        UnsealSecretInput, UnsealSecretOutput,
+        FetchResourceToFileRequest, FetchResourceToFileResponse, GetResourceChunkRequest,
+        GetResourceChunkResponse, GetResourceRequest, GetResourceResponse, InjectSecretsRequest,
+        InjectSecretsResponse, InvalidateResourceRequest, InvalidateResourceResponse,
+        RemountRequest, RemountResponse, SecureMountRequest, SecureMountResponse, UnmountRequest,
+        UnmountResponse, UnsealSecretInput, UnsealSecretOutput,
+    },
+    api_ttrpc::{
+        GetResourceService, SealedSecretService, SecretInjectionService, SecureMountService,
     },
-    api_ttrpc::{GetResourceService, SealedSecretService, SecureMountService},
     keyprovider::{KeyProviderKeyWrapProtocolInput, KeyProviderKeyWrapProtocolOutput},
     keyprovider_ttrpc::KeyProviderService,
     server::message::{KeyProviderInput, KeyUnwrapOutput, KeyUnwrapResults},
 };
+#[cfg(feature = "key-service")]
+use crate::{
+    api::{
+        GenerateKeyRequest, GenerateKeyResponse, InstallCertificateRequest,
+        InstallCertificateResponse,
+    },
+    api_ttrpc::KeyService,
+};
+#[cfg(feature = "image-pull")]
+use crate::{
+    api::{
+        ImagePullRequest, ImagePullResponse, PinImageRequest, PinImageResponse, UnpinImageRequest,
+        UnpinImageResponse,
+    },
+    api_ttrpc::ImagePullService,
+};
+#[cfg(feature = "secret-dirs")]
+use crate::{
+    api::{
+        ProvisionSecretDirRequest, ProvisionSecretDirResponse, RemoveSecretDirRequest,
+        RemoveSecretDirResponse,
+    },
+    api_ttrpc::SecretDirService,
+};
 
 lazy_static! {
     static ref HUB: Arc<RwLock<Option<Hub>>> = Arc::new(RwLock::new(None));
 }
 
+#[cfg(feature = "grpc")]
+pub mod grpc;
 mod message;
 
 pub struct Server;
 
 impl Server {
-    async fn init() -> Result<()> {
+    /// Initialize the global hub. Only the first call takes effect.
+    pub async fn init(
+        cache_config: ResourceCacheConfig,
+        inject_rootfs_base: Option<PathBuf>,
+        fetch_target_base: Option<PathBuf>,
+    ) -> Result<()> {
         let mut writer = HUB.write().await;
         if writer.is_none() {
-            let hub = Hub::new().await?;
+            let mut hub = Hub::new_with_cache_config(cache_config).await?;
+            if let Some(base) = inject_rootfs_base {
+                hub.set_inject_rootfs_base(base);
+            }
+            if let Some(base) = fetch_target_base {
+                hub.set_fetch_target_base(base);
+            }
             *writer = Some(hub);
         }
 
@@ -45,7 +99,7 @@ impl Server {
     }
 
     pub async fn new() -> Result<Self> {
-        Self::init().await?;
+        Self::init(ResourceCacheConfig::default(), None, None).await?;
         Ok(Self)
     }
 }
@@ -54,18 +108,23 @@ impl Server {
 impl SealedSecretService for Server {
     async fn unseal_secret(
         &self,
-        _ctx: &TtrpcContext,
+        ctx: &TtrpcContext,
         input: UnsealSecretInput,
     ) -> ::ttrpc::Result<UnsealSecretOutput> {
         debug!("get new UnsealSecret request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "UnsealSecret");
         let reader = HUB.read().await;
         let reader = reader.as_ref().expect("must be initialized");
-        let plaintext = reader.unseal_secret(input.secret).await.map_err(|e| {
-            let mut status = Status::new();
-            status.set_code(Code::INTERNAL);
-            status.set_message(format!("[CDH] [ERROR]: Unseal Secret failed: {e}"));
-            Error::RpcStatus(status)
-        })?;
+        let plaintext = reader
+            .unseal_secret(input.secret)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Unseal Secret failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
 
         let mut reply = UnsealSecretOutput::new();
         reply.plaintext = plaintext;
@@ -78,34 +137,124 @@ impl SealedSecretService for Server {
 impl GetResourceService for Server {
     async fn get_resource(
         &self,
-        _ctx: &TtrpcContext,
+        ctx: &TtrpcContext,
         req: GetResourceRequest,
     ) -> ::ttrpc::Result<GetResourceResponse> {
         debug!("get new GetResource request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "GetResource");
         let reader = HUB.read().await;
         let reader = reader.as_ref().expect("must be initialized");
-        let resource = reader.get_resource(req.ResourcePath).await.map_err(|e| {
-            let mut status = Status::new();
-            status.set_code(Code::INTERNAL);
-            status.set_message(format!("[CDH] [ERROR]: Get Resource failed: {e}"));
-            Error::RpcStatus(status)
-        })?;
+        let resource = reader
+            .get_resource(req.ResourcePath)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Get Resource failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
 
         let mut reply = GetResourceResponse::new();
         reply.Resource = resource;
         debug!("send back the resource");
         Ok(reply)
     }
+
+    async fn invalidate_resource(
+        &self,
+        ctx: &TtrpcContext,
+        req: InvalidateResourceRequest,
+    ) -> ::ttrpc::Result<InvalidateResourceResponse> {
+        debug!("get new InvalidateResource request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "InvalidateResource");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let uri = Some(req.ResourcePath).filter(|uri| !uri.is_empty());
+        let invalidated = reader
+            .invalidate_resource(uri)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Invalidate Resource failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        let mut reply = InvalidateResourceResponse::new();
+        reply.Invalidated = invalidated as u32;
+        debug!("send back the number of invalidated resources");
+        Ok(reply)
+    }
+
+    async fn get_resource_chunk(
+        &self,
+        ctx: &TtrpcContext,
+        req: GetResourceChunkRequest,
+    ) -> ::ttrpc::Result<GetResourceChunkResponse> {
+        debug!("get new GetResourceChunk request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "GetResourceChunk");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let digest = Some(req.Digest).filter(|digest| !digest.is_empty());
+        let chunk = reader
+            .get_resource_chunk(req.ResourcePath, req.Offset, req.Length, digest)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Get Resource Chunk failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        let mut reply = GetResourceChunkResponse::new();
+        reply.Data = chunk.data;
+        reply.TotalSize = chunk.info.size;
+        reply.Digest = chunk.info.digest;
+        debug!("send back the resource chunk");
+        Ok(reply)
+    }
+
+    async fn fetch_resource_to_file(
+        &self,
+        ctx: &TtrpcContext,
+        req: FetchResourceToFileRequest,
+    ) -> ::ttrpc::Result<FetchResourceToFileResponse> {
+        debug!("get new FetchResourceToFile request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "FetchResourceToFile");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let digest = Some(req.Digest).filter(|digest| !digest.is_empty());
+        let info = reader
+            .fetch_resource_to_file(req.ResourcePath, &req.TargetPath, digest)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Fetch Resource To File failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        let mut reply = FetchResourceToFileResponse::new();
+        reply.Size = info.size;
+        reply.Digest = info.digest;
+        debug!("send back the size and digest of the written resource");
+        Ok(reply)
+    }
 }
 
 #[async_trait]
 impl KeyProviderService for Server {
     async fn un_wrap_key(
         &self,
-        _ctx: &TtrpcContext,
+        ctx: &TtrpcContext,
         req: KeyProviderKeyWrapProtocolInput,
     ) -> ::ttrpc::Result<KeyProviderKeyWrapProtocolOutput> {
         debug!("get new UnWrapKey request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "UnWrapKey");
         let reader = HUB.read().await;
         let reader = reader.as_ref().expect("must be initialized");
         let key_provider_input: KeyProviderInput =
@@ -124,12 +273,16 @@ impl KeyProviderService for Server {
         })?;
 
         debug!("Call CDH to Unwrap Key...");
-        let decrypted_optsdata = reader.unwrap_key(&annotation_packet).await.map_err(|e| {
-            let mut status = Status::new();
-            status.set_code(Code::INTERNAL);
-            status.set_message(format!("[CDH] [ERROR]: UnwrapKey failed: {e}"));
-            Error::RpcStatus(status)
-        })?;
+        let decrypted_optsdata = reader
+            .unwrap_key(&annotation_packet)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: UnwrapKey failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
 
         let mut reply = KeyProviderKeyWrapProtocolOutput::new();
 
@@ -159,10 +312,11 @@ impl KeyProviderService for Server {
 impl SecureMountService for Server {
     async fn secure_mount(
         &self,
-        _ctx: &TtrpcContext,
+        ctx: &TtrpcContext,
         req: SecureMountRequest,
     ) -> ::ttrpc::Result<SecureMountResponse> {
         debug!("get new Secure mount request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "SecureMount");
         let reader = HUB.read().await;
         let reader = reader.as_ref().expect("must be initialized");
         let storage = Storage {
@@ -173,16 +327,396 @@ impl SecureMountService for Server {
             options: req.options,
             mount_point: req.mount_point,
         };
-        let resource = reader.secure_mount(storage).await.map_err(|e| {
-            let mut status = Status::new();
-            status.set_code(Code::INTERNAL);
-            status.set_message(format!("[CDH] [ERROR]: secure mount failed: {e}"));
-            Error::RpcStatus(status)
-        })?;
+        let resource = reader
+            .secure_mount(storage)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: secure mount failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
 
         let mut reply = SecureMountResponse::new();
         reply.mount_path = resource;
         debug!("send back the resource");
         Ok(reply)
     }
+
+    async fn unmount(
+        &self,
+        ctx: &TtrpcContext,
+        req: UnmountRequest,
+    ) -> ::ttrpc::Result<UnmountResponse> {
+        debug!("get new Unmount request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "Unmount");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        reader
+            .secure_unmount(&req.mount_point)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: unmount failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        debug!("secure mount torn down");
+        Ok(UnmountResponse::new())
+    }
+
+    async fn remount(
+        &self,
+        ctx: &TtrpcContext,
+        req: RemountRequest,
+    ) -> ::ttrpc::Result<RemountResponse> {
+        debug!("get new Remount request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "Remount");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let mount_path = reader
+            .secure_remount(&req.mount_point)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: remount failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        let mut reply = RemountResponse::new();
+        reply.mount_path = mount_path;
+        debug!("send back the mount path");
+        Ok(reply)
+    }
+}
+
+#[async_trait]
+impl SecretInjectionService for Server {
+    async fn inject_secrets(
+        &self,
+        ctx: &TtrpcContext,
+        req: InjectSecretsRequest,
+    ) -> ::ttrpc::Result<InjectSecretsResponse> {
+        debug!("get new InjectSecrets request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "InjectSecrets");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let manifest: InjectionManifest = serde_json::from_slice(&req.manifest).map_err(|e| {
+            let mut status = Status::new();
+            status.set_code(Code::INVALID_ARGUMENT);
+            status.set_message(format!("[CDH] [ERROR]: illegal injection manifest: {e}"));
+            Error::RpcStatus(status)
+        })?;
+        let paths = reader
+            .inject_secrets(manifest, &req.rootfs)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Inject Secrets failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        let mut reply = InjectSecretsResponse::new();
+        reply.paths = paths;
+        debug!("send back the injected paths");
+        Ok(reply)
+    }
+}
+
+/// Get the uid of the peer of the ttrpc connection of `ctx`.
+#[cfg(feature = "key-service")]
+fn peer_uid(ctx: &TtrpcContext) -> ::ttrpc::Result<u32> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: `cred` and `len` are valid for the size given
+    let ret = unsafe {
+        libc::getsockopt(
+            ctx.fd,
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        let mut status = Status::new();
+        status.set_code(Code::PERMISSION_DENIED);
+        status.set_message(format!(
+            "[CDH] [ERROR]: get peer credentials failed: {}",
+            std::io::Error::last_os_error()
+        ));
+        return Err(Error::RpcStatus(status));
+    }
+
+    Ok(cred.uid)
+}
+
+#[cfg(feature = "key-service")]
+#[async_trait]
+impl KeyService for Server {
+    async fn generate_key(
+        &self,
+        ctx: &TtrpcContext,
+        req: GenerateKeyRequest,
+    ) -> ::ttrpc::Result<GenerateKeyResponse> {
+        debug!("get new GenerateKey request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "GenerateKey");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let invalid_argument = |e: String| {
+            let mut status = Status::new();
+            status.set_code(Code::INVALID_ARGUMENT);
+            status.set_message(format!("[CDH] [ERROR]: illegal key request: {e}"));
+            Error::RpcStatus(status)
+        };
+        let algorithm = req
+            .algorithm
+            .parse::<KeyAlgorithm>()
+            .map_err(|e| invalid_argument(format!("{e}")))?;
+        let ip_addresses = req
+            .ip_addresses
+            .iter()
+            .map(|ip| {
+                ip.parse::<IpAddr>()
+                    .map_err(|e| format!("ip address {ip:?}: {e}"))
+            })
+            .collect::<std::result::Result<_, _>>()
+            .map_err(invalid_argument)?;
+        let request = KeyRequest {
+            caller_uid: peer_uid(ctx)?,
+            algorithm,
+            common_name: req.common_name,
+            dns_names: req.dns_names,
+            ip_addresses,
+        };
+        let key = reader
+            .generate_key(request)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Generate Key failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        let mut reply = GenerateKeyResponse::new();
+        reply.key_id = key.key_id;
+        reply.csr = key.csr;
+        reply.evidence = key.evidence;
+        debug!("send back the CSR");
+        Ok(reply)
+    }
+
+    async fn install_certificate(
+        &self,
+        ctx: &TtrpcContext,
+        req: InstallCertificateRequest,
+    ) -> ::ttrpc::Result<InstallCertificateResponse> {
+        debug!("get new InstallCertificate request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "InstallCertificate");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let path = reader
+            .install_certificate(peer_uid(ctx)?, &req.key_id, &req.certificate)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Install Certificate failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        let mut reply = InstallCertificateResponse::new();
+        reply.path = path;
+        debug!("send back the key pair path");
+        Ok(reply)
+    }
+}
+
+#[cfg(feature = "image-pull")]
+#[async_trait]
+impl ImagePullService for Server {
+    async fn pull_image(
+        &self,
+        ctx: &TtrpcContext,
+        req: ImagePullRequest,
+    ) -> ::ttrpc::Result<ImagePullResponse> {
+        debug!("get new ImagePull request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "PullImage");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let non_empty = |s: String| Some(s).filter(|s| !s.is_empty());
+        let options = ImagePullOptions {
+            auth_info: non_empty(req.auth_info),
+            decrypt_config: non_empty(req.decrypt_config),
+            snapshotter: non_empty(req.snapshotter),
+            platform: non_empty(req.platform),
+            auth: req.auth,
+            auth_file: non_empty(req.auth_file),
+            security_validate: req.security_validate,
+            policy_path: non_empty(req.policy_path),
+            sigstore_config: non_empty(req.sigstore_config),
+            selinux_label: non_empty(req.selinux_label),
+            id_shift: non_empty(req.id_shift),
+        };
+        let image = reader
+            .pull_image(&req.image_url, &req.bundle_path, options)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Image Pull failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        let mut reply = ImagePullResponse::new();
+        reply.image_id = image.image_id;
+        reply.layer_group_mounts = image.layer_group_mounts;
+        if let Some(verification) = image.verification {
+            reply.verification_report = serde_json::to_string(&verification).map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!(
+                    "[CDH] [ERROR]: serialize verification report failed: {e}"
+                ));
+                Error::RpcStatus(status)
+            })?;
+        }
+        debug!("send back the image id");
+        Ok(reply)
+    }
+
+    async fn pin_image(
+        &self,
+        ctx: &TtrpcContext,
+        req: PinImageRequest,
+    ) -> ::ttrpc::Result<PinImageResponse> {
+        debug!("get new PinImage request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "PinImage");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        reader
+            .pin_image(&req.image_url)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Pin Image failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        debug!("image pinned");
+        Ok(PinImageResponse::new())
+    }
+
+    async fn unpin_image(
+        &self,
+        ctx: &TtrpcContext,
+        req: UnpinImageRequest,
+    ) -> ::ttrpc::Result<UnpinImageResponse> {
+        debug!("get new UnpinImage request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "UnpinImage");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let unpinned = reader
+            .unpin_image(&req.image_url)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Unpin Image failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        let mut reply = UnpinImageResponse::new();
+        reply.unpinned = unpinned;
+        debug!("send back whether the image was pinned");
+        Ok(reply)
+    }
+}
+
+#[cfg(feature = "secret-dirs")]
+#[async_trait]
+impl SecretDirService for Server {
+    async fn provision_secret_dir(
+        &self,
+        ctx: &TtrpcContext,
+        req: ProvisionSecretDirRequest,
+    ) -> ::ttrpc::Result<ProvisionSecretDirResponse> {
+        debug!("get new ProvisionSecretDir request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "ProvisionSecretDir");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        let manifest: InjectionManifest = serde_json::from_slice(&req.manifest).map_err(|e| {
+            let mut status = Status::new();
+            status.set_code(Code::INVALID_ARGUMENT);
+            status.set_message(format!("[CDH] [ERROR]: illegal injection manifest: {e}"));
+            Error::RpcStatus(status)
+        })?;
+        let request = SecretDirRequest {
+            container_id: req.container_id,
+            manifest,
+            mount_path: req.mount_path,
+            uid: req.uid,
+            gid: req.gid,
+            size: (req.size != 0).then_some(req.size),
+        };
+        let mount = reader
+            .provision_secret_dir(request)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Provision Secret Dir failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        let mut reply = ProvisionSecretDirResponse::new();
+        reply.source = mount.source;
+        reply.destination = mount.destination;
+        reply.options = mount.options;
+        debug!("send back the mount of the secret dir");
+        Ok(reply)
+    }
+
+    async fn remove_secret_dir(
+        &self,
+        ctx: &TtrpcContext,
+        req: RemoveSecretDirRequest,
+    ) -> ::ttrpc::Result<RemoveSecretDirResponse> {
+        debug!("get new RemoveSecretDir request");
+        let span = telemetry::ttrpc_server_span(&ctx.metadata, "RemoveSecretDir");
+        let reader = HUB.read().await;
+        let reader = reader.as_ref().expect("must be initialized");
+        reader
+            .remove_secret_dir(&req.container_id)
+            .with_context(span)
+            .await
+            .map_err(|e| {
+                let mut status = Status::new();
+                status.set_code(Code::INTERNAL);
+                status.set_message(format!("[CDH] [ERROR]: Remove Secret Dir failed: {e}"));
+                Error::RpcStatus(status)
+            })?;
+
+        debug!("secret dir removed");
+        Ok(RemoveSecretDirResponse::new())
+    }
 }
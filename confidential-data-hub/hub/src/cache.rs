@@ -0,0 +1,295 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! In-memory cache for resources fetched by `GetResource`.
+//!
+//! Hot resources (registry credentials, policy files, ...) are read many
+//! times during the lifetime of a pod. The cache keeps them for a bounded
+//! time so that not every request goes to the KBS. Failed fetches are
+//! cached as well, but with a much shorter TTL, so that a missing resource
+//! does not cause a request storm against the KBS.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Default TTL of a successfully fetched resource.
+pub const DEFAULT_RESOURCE_TTL_SECS: u64 = 60;
+
+/// Default TTL of a failed fetch.
+pub const DEFAULT_NEGATIVE_TTL_SECS: u64 = 5;
+
+/// Default max number of resources kept in the cache.
+pub const DEFAULT_MAX_ENTRIES: usize = 256;
+
+/// Default max total size of the resources kept in the cache.
+pub const DEFAULT_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ResourceCacheConfig {
+    /// TTL in seconds of a successfully fetched resource. `0` disables
+    /// caching of resources.
+    pub ttl_secs: u64,
+
+    /// TTL in seconds of a failed fetch. `0` disables negative caching.
+    pub negative_ttl_secs: u64,
+
+    /// Max number of entries (both positive and negative) in the cache.
+    pub max_entries: usize,
+
+    /// Max total size in bytes of the cached resources. A resource bigger
+    /// than this is never cached.
+    pub max_bytes: usize,
+
+    /// Per-resource TTLs in seconds, keyed by the KBS Resource URI. These
+    /// take precedence over `ttl_secs`.
+    pub ttl_overrides: HashMap<String, u64>,
+}
+
+impl Default for ResourceCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: DEFAULT_RESOURCE_TTL_SECS,
+            negative_ttl_secs: DEFAULT_NEGATIVE_TTL_SECS,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            max_bytes: DEFAULT_MAX_BYTES,
+            ttl_overrides: HashMap::new(),
+        }
+    }
+}
+
+enum CachedValue {
+    Resource(Zeroizing<Vec<u8>>),
+    Failure(String),
+}
+
+impl CachedValue {
+    fn size(&self) -> usize {
+        match self {
+            CachedValue::Resource(r) => r.len(),
+            CachedValue::Failure(_) => 0,
+        }
+    }
+}
+
+struct CacheEntry {
+    value: CachedValue,
+    expires_at: Instant,
+    last_used: Instant,
+}
+
+/// A size bounded cache of resources with TTL.
+pub struct ResourceCache {
+    config: ResourceCacheConfig,
+    entries: HashMap<String, CacheEntry>,
+    bytes: usize,
+}
+
+impl ResourceCache {
+    pub fn new(config: ResourceCacheConfig) -> Self {
+        Self {
+            config,
+            entries: HashMap::new(),
+            bytes: 0,
+        }
+    }
+
+    /// Look up the given uri. Returns `None` if the uri is not cached or
+    /// the entry has expired, `Some(Ok(..))` for a cached resource and
+    /// `Some(Err(..))` with the original error message for a cached failure.
+    pub fn get(&mut self, uri: &str) -> Option<Result<Vec<u8>, String>> {
+        let now = Instant::now();
+        let entry = self.entries.get_mut(uri)?;
+        if entry.expires_at <= now {
+            self.remove(uri);
+            return None;
+        }
+
+        entry.last_used = now;
+        match &entry.value {
+            CachedValue::Resource(r) => Some(Ok(r.to_vec())),
+            CachedValue::Failure(e) => Some(Err(e.clone())),
+        }
+    }
+
+    /// Cache a successfully fetched resource.
+    pub fn insert(&mut self, uri: &str, resource: &[u8]) {
+        let ttl = self
+            .config
+            .ttl_overrides
+            .get(uri)
+            .copied()
+            .unwrap_or(self.config.ttl_secs);
+        if resource.len() > self.config.max_bytes {
+            self.remove(uri);
+            return;
+        }
+
+        self.put(
+            uri,
+            CachedValue::Resource(Zeroizing::new(resource.to_vec())),
+            ttl,
+        );
+    }
+
+    /// Cache a failed fetch of the resource.
+    pub fn insert_failure(&mut self, uri: &str, error: String) {
+        self.put(
+            uri,
+            CachedValue::Failure(error),
+            self.config.negative_ttl_secs,
+        );
+    }
+
+    /// Drop the cached entry of the given uri, or all the entries if `uri`
+    /// is `None`. Returns the number of entries dropped.
+    pub fn invalidate(&mut self, uri: Option<&str>) -> usize {
+        match uri {
+            Some(uri) => self.remove(uri) as usize,
+            None => {
+                let count = self.entries.len();
+                self.entries.clear();
+                self.bytes = 0;
+                count
+            }
+        }
+    }
+
+    fn put(&mut self, uri: &str, value: CachedValue, ttl: u64) {
+        self.remove(uri);
+        if ttl == 0 || self.config.max_entries == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        self.make_room(value.size(), now);
+        self.bytes += value.size();
+        self.entries.insert(
+            uri.to_string(),
+            CacheEntry {
+                value,
+                expires_at: now + Duration::from_secs(ttl),
+                last_used: now,
+            },
+        );
+    }
+
+    /// Evict expired entries first, then the least recently used ones until
+    /// a new entry of `size` bytes fits within the bounds.
+    fn make_room(&mut self, size: usize, now: Instant) {
+        let expired: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.expires_at <= now)
+            .map(|(uri, _)| uri.clone())
+            .collect();
+        for uri in expired {
+            self.remove(&uri);
+        }
+
+        while self.entries.len() >= self.config.max_entries
+            || self.bytes + size > self.config.max_bytes
+        {
+            let Some(lru) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(uri, _)| uri.clone())
+            else {
+                break;
+            };
+            self.remove(&lru);
+        }
+    }
+
+    fn remove(&mut self, uri: &str) -> bool {
+        match self.entries.remove(uri) {
+            Some(entry) => {
+                self.bytes -= entry.value.size();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_hit_and_failure() {
+        let mut cache = ResourceCache::new(ResourceCacheConfig::default());
+        assert_eq!(cache.get("kbs:///a/b/c"), None);
+
+        cache.insert("kbs:///a/b/c", b"secret");
+        assert_eq!(cache.get("kbs:///a/b/c"), Some(Ok(b"secret".to_vec())));
+
+        cache.insert_failure("kbs:///a/b/d", "not found".into());
+        assert_eq!(cache.get("kbs:///a/b/d"), Some(Err("not found".into())));
+    }
+
+    #[test]
+    fn test_cache_ttl() {
+        let mut config = ResourceCacheConfig {
+            negative_ttl_secs: 0,
+            ..Default::default()
+        };
+        config.ttl_overrides.insert("kbs:///a/b/c".into(), 0);
+        let mut cache = ResourceCache::new(config);
+
+        cache.insert("kbs:///a/b/c", b"secret");
+        assert_eq!(cache.get("kbs:///a/b/c"), None);
+
+        cache.insert("kbs:///a/b/e", b"secret");
+        assert_eq!(cache.get("kbs:///a/b/e"), Some(Ok(b"secret".to_vec())));
+
+        cache.insert_failure("kbs:///a/b/d", "not found".into());
+        assert_eq!(cache.get("kbs:///a/b/d"), None);
+    }
+
+    #[test]
+    fn test_cache_bounds() {
+        let mut cache = ResourceCache::new(ResourceCacheConfig {
+            max_entries: 2,
+            max_bytes: 8,
+            ..Default::default()
+        });
+
+        cache.insert("kbs:///a/b/1", b"1234");
+        cache.insert("kbs:///a/b/2", b"1234");
+        // touch the first one so that the second one is the LRU
+        assert!(cache.get("kbs:///a/b/1").is_some());
+        cache.insert("kbs:///a/b/3", b"12");
+        assert!(cache.get("kbs:///a/b/2").is_none());
+        assert!(cache.get("kbs:///a/b/1").is_some());
+        assert!(cache.get("kbs:///a/b/3").is_some());
+
+        // too big to be cached at all
+        cache.insert("kbs:///a/b/4", b"123456789");
+        assert!(cache.get("kbs:///a/b/4").is_none());
+        assert_eq!(cache.bytes, 6);
+    }
+
+    #[test]
+    fn test_cache_invalidate() {
+        let mut cache = ResourceCache::new(ResourceCacheConfig::default());
+        cache.insert("kbs:///a/b/1", b"1");
+        cache.insert("kbs:///a/b/2", b"2");
+        cache.insert_failure("kbs:///a/b/3", "not found".into());
+
+        assert_eq!(cache.invalidate(Some("kbs:///a/b/1")), 1);
+        assert_eq!(cache.invalidate(Some("kbs:///a/b/1")), 0);
+        assert!(cache.get("kbs:///a/b/1").is_none());
+        assert_eq!(cache.invalidate(None), 2);
+        assert!(cache.get("kbs:///a/b/2").is_none());
+        assert_eq!(cache.bytes, 0);
+    }
+}
@@ -15,6 +15,9 @@ pub enum Error {
     #[error("decrypt image (unwrap key) failed: {0}")]
     ImageDecryption(String),
 
+    #[error("pull image failed: {0}")]
+    ImagePull(String),
+
     #[error("init Hub failed: {0}")]
     InitializationFailed(String),
 
@@ -23,4 +26,22 @@ pub enum Error {
 
     #[error("secure mount failed: {0}")]
     SecureMount(String),
+
+    #[error("secure unmount failed: {0}")]
+    SecureUnmount(String),
+
+    #[error("inject secrets failed: {0}")]
+    SecretInjection(String),
+
+    #[error("secret lease failed: {0}")]
+    SecretLease(String),
+
+    #[error("secret dir failed: {0}")]
+    SecretDir(String),
+
+    #[error("generate key failed: {0}")]
+    GenerateKey(String),
+
+    #[error("install certificate failed: {0}")]
+    InstallCertificate(String),
 }
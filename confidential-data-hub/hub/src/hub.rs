@@ -6,22 +6,190 @@
 use async_trait::async_trait;
 use base64::{engine::general_purpose::STANDARD, Engine};
 use image::AnnotationPacket;
-use kms::{Annotations, ProviderSettings};
-use log::info;
+#[cfg(feature = "image-pull")]
+use image_rs::image::ImageClient;
+use log::{debug, info};
 use secret::secret::Secret;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use storage::mounts::{MountTable, MOUNT_TABLE_PATH};
 use storage::volume_type::Storage;
+use tokio::sync::{broadcast, Mutex};
+use zeroize::Zeroizing;
 
-use crate::{DataHub, Error, Result};
+#[cfg(feature = "image-pull")]
+use crate::image_pull::{ImagePullOptions, PulledImage};
+#[cfg(feature = "key-service")]
+use crate::keys::{GeneratedKey, KeyRequest, KeyStore, DEFAULT_KEY_DIR};
+#[cfg(feature = "secret-dirs")]
+use crate::secret_dirs::{
+    bind_mount, SecretDirMount, SecretDirRequest, SecretDirs, DEFAULT_SECRET_DIRS_ROOT,
+};
+use crate::{
+    cache::{ResourceCache, ResourceCacheConfig},
+    inject::{confine_rootfs, InjectionManifest, DEFAULT_ROOTFS_BASE},
+    lease::{self, LeaseExpired, LeaseTable, Leases, LEASE_TABLE_PATH},
+    resource::ResourceResolver,
+    spool::{
+        check_digest, open_target_dir, ResourceChunk, ResourceInfo, ResourceSpool, SpooledResource,
+        DEFAULT_FETCH_TARGET_BASE, DEFAULT_IDLE_SECS, DEFAULT_SPOOL_DIR,
+    },
+    DataHub, Error, Result,
+};
 
-pub struct Hub {}
+pub struct Hub {
+    resource_cache: Mutex<ResourceCache>,
+
+    resolver: ResourceResolver,
+
+    spool: Mutex<ResourceSpool>,
+
+    secure_mounts: Mutex<MountTable>,
+
+    leases: Arc<Leases>,
+
+    /// Dir the rootfs secrets are injected into must be under, see
+    /// [`crate::inject`].
+    inject_rootfs_base: PathBuf,
+
+    /// Dir the targets of `fetch_resource_to_file` must be under, see
+    /// [`crate::spool`].
+    fetch_target_base: PathBuf,
+
+    #[cfg(feature = "key-service")]
+    keys: KeyStore,
+
+    #[cfg(feature = "secret-dirs")]
+    secret_dirs: SecretDirs,
+
+    #[cfg(feature = "image-pull")]
+    image_client: Arc<Mutex<ImageClient>>,
+}
 
 impl Hub {
     pub async fn new() -> Result<Self> {
-        let mut hub = Self {};
+        Self::new_with_cache_config(ResourceCacheConfig::default()).await
+    }
+
+    pub async fn new_with_cache_config(cache_config: ResourceCacheConfig) -> Result<Self> {
+        #[cfg(feature = "image-pull")]
+        let image_client = Arc::new(Mutex::new(
+            ImageClient::from_config_file()
+                .map_err(|e| Error::InitializationFailed(format!("image-rs config: {e:#}")))?,
+        ));
+
+        #[allow(unused_mut)]
+        let mut resolver = ResourceResolver::default();
+        #[cfg(feature = "image-pull")]
+        resolver.register(
+            "oci",
+            Box::new(crate::resource::OciProvider::new(image_client.clone())),
+        );
+
+        let secure_mounts = MountTable::load(Path::new(MOUNT_TABLE_PATH))
+            .map_err(|e| Error::InitializationFailed(e.to_string()))?;
+        let leases = Leases::new(
+            LeaseTable::load(Path::new(LEASE_TABLE_PATH))
+                .map_err(|e| Error::InitializationFailed(e.to_string()))?,
+        );
+        tokio::spawn(leases.clone().run());
+
+        let mut hub = Self {
+            resource_cache: Mutex::new(ResourceCache::new(cache_config)),
+            resolver,
+            spool: Mutex::new(ResourceSpool::new(DEFAULT_SPOOL_DIR, DEFAULT_IDLE_SECS)),
+            secure_mounts: Mutex::new(secure_mounts),
+            leases,
+            inject_rootfs_base: PathBuf::from(DEFAULT_ROOTFS_BASE),
+            fetch_target_base: PathBuf::from(DEFAULT_FETCH_TARGET_BASE),
+            #[cfg(feature = "key-service")]
+            keys: KeyStore::new(DEFAULT_KEY_DIR),
+            #[cfg(feature = "secret-dirs")]
+            secret_dirs: SecretDirs::new(DEFAULT_SECRET_DIRS_ROOT),
+            #[cfg(feature = "image-pull")]
+            image_client,
+        };
 
         hub.init().await?;
         Ok(hub)
     }
+
+    /// Only inject secrets into the rootfs under `base`, instead of
+    /// [`DEFAULT_ROOTFS_BASE`].
+    pub fn set_inject_rootfs_base(&mut self, base: PathBuf) {
+        self.inject_rootfs_base = base;
+    }
+
+    /// Only write the resources of `fetch_resource_to_file` under `base`,
+    /// instead of [`DEFAULT_FETCH_TARGET_BASE`].
+    pub fn set_fetch_target_base(&mut self, base: PathBuf) {
+        self.fetch_target_base = base;
+    }
+
+    /// Get notified every time the lease of an injected secret expires and
+    /// its file is shredded, see [`crate::lease`].
+    pub fn subscribe_leases(&self) -> broadcast::Receiver<LeaseExpired> {
+        self.leases.subscribe()
+    }
+
+    // unmount and remove the secret dir of the container, and drop the
+    // leases of its secrets
+    #[cfg(feature = "secret-dirs")]
+    async fn drop_secret_dir(&self, container_id: &str) -> Result<()> {
+        self.secret_dirs.remove(container_id).await?;
+        self.leases
+            .release(&self.secret_dirs.dir(container_id)?)
+            .await
+    }
+
+    /// Open the spooled resource of `uri`, fetching it into the spool
+    /// first if needed. Large resources bypass the resource cache.
+    async fn spooled_resource(&self, uri: &str) -> Result<SpooledResource> {
+        let spool_failed =
+            |e: std::io::Error| Error::GetResource(format!("spool resource {uri} failed: {e}"));
+        let mut pending = {
+            let mut spool = self.spool.lock().await;
+            if let Some(spooled) = spool.get(uri) {
+                debug!("get resource {uri} from spool");
+                return Ok(spooled);
+            }
+            spool.reserve(uri).map_err(spool_failed)?
+        };
+
+        // the spool is not locked while the resource is fetched
+        self.resolver
+            .write_resource(uri, &mut pending)
+            .await
+            .map_err(Error::GetResource)?;
+        self.spool
+            .lock()
+            .await
+            .commit(pending)
+            .map_err(spool_failed)
+    }
+
+    /// Resolve the sources of `manifest`, resource URIs and sealed secrets.
+    async fn resolve_secrets(
+        &self,
+        manifest: &InjectionManifest,
+    ) -> Result<HashMap<String, Zeroizing<Vec<u8>>>> {
+        let mut secrets = HashMap::new();
+        for source in manifest.sources()? {
+            let secret = if source.starts_with("sealed.") {
+                self.unseal_secret(source.clone().into_bytes()).await?
+            } else if self.resolver.supports(&source) {
+                self.get_resource(source.clone()).await?
+            } else {
+                return Err(Error::SecretInjection(format!(
+                    "unsupported secret source {source:?}"
+                )));
+            };
+            secrets.insert(source, Zeroizing::new(secret));
+        }
+
+        Ok(secrets)
+    }
 }
 
 #[async_trait]
@@ -66,25 +234,241 @@ impl DataHub for Hub {
 
     async fn get_resource(&self, uri: String) -> Result<Vec<u8>> {
         info!("get resource called: {uri}");
-        // to initialize a get_resource_provider client we do not need the ProviderSettings.
-        let mut client = kms::new_getter("kbs", ProviderSettings::default())
-            .await
-            .map_err(|e| Error::GetResource(format!("create kbs client failed: {e}")))?;
+        if let Some(cached) = self.resource_cache.lock().await.get(&uri) {
+            debug!("get resource {uri} from cache");
+            return cached.map_err(Error::GetResource);
+        }
 
-        // to get resource using a get_resource_provider client we do not need the Annotations.
-        let res = client
-            .get_secret(&uri, &Annotations::default())
-            .await
-            .map_err(|e| Error::GetResource(format!("get rersource failed: {e}")))?;
-        Ok(res)
+        let res = self.resolver.get_resource(&uri).await;
+        let mut cache = self.resource_cache.lock().await;
+        match res {
+            Ok(res) => {
+                cache.insert(&uri, &res);
+                Ok(res)
+            }
+            Err(e) => {
+                cache.insert_failure(&uri, e.clone());
+                Err(Error::GetResource(e))
+            }
+        }
+    }
+
+    async fn get_resource_chunk(
+        &self,
+        uri: String,
+        offset: u64,
+        length: u32,
+        digest: Option<String>,
+    ) -> Result<ResourceChunk> {
+        debug!("get resource chunk called: {uri} at {offset}");
+        let mut spooled = self.spooled_resource(&uri).await?;
+        check_digest(&spooled.info, digest.as_deref()).map_err(Error::GetResource)?;
+        let data = spooled
+            .read_chunk(offset, length)
+            .map_err(|e| Error::GetResource(format!("read chunk of {uri} failed: {e}")))?;
+        Ok(ResourceChunk {
+            data,
+            info: spooled.info,
+        })
+    }
+
+    async fn fetch_resource_to_file(
+        &self,
+        uri: String,
+        target: &str,
+        digest: Option<String>,
+    ) -> Result<ResourceInfo> {
+        info!("fetch resource to file called: {uri} to {target}");
+        let (dir, name) = open_target_dir(&self.fetch_target_base, Path::new(target))
+            .map_err(|e| Error::GetResource(format!("open target {target} failed: {e}")))?;
+
+        let mut spooled = self.spooled_resource(&uri).await?;
+        check_digest(&spooled.info, digest.as_deref()).map_err(Error::GetResource)?;
+        spooled
+            .copy_to(&dir, &name, 0o600)
+            .map_err(|e| Error::GetResource(format!("write {uri} to {target} failed: {e}")))?;
+        Ok(spooled.info)
+    }
+
+    async fn invalidate_resource(&self, uri: Option<String>) -> Result<usize> {
+        info!("invalidate resource called: {uri:?}");
+        let invalidated = self.resource_cache.lock().await.invalidate(uri.as_deref())
+            + self.spool.lock().await.invalidate(uri.as_deref());
+        Ok(invalidated)
     }
 
     async fn secure_mount(&self, storage: Storage) -> Result<String> {
         info!("secure mount called");
-        let res = storage
-            .mount()
+        let res = self
+            .secure_mounts
+            .lock()
+            .await
+            .mount(storage)
             .await
             .map_err(|e| Error::SecureMount(e.to_string()))?;
         Ok(res)
     }
+
+    async fn secure_unmount(&self, mount_point: &str) -> Result<()> {
+        info!("secure unmount called: {mount_point}");
+        self.secure_mounts
+            .lock()
+            .await
+            .unmount(mount_point)
+            .await
+            .map_err(|e| Error::SecureUnmount(e.to_string()))
+    }
+
+    async fn secure_remount(&self, mount_point: &str) -> Result<String> {
+        info!("secure remount called: {mount_point}");
+        self.secure_mounts
+            .lock()
+            .await
+            .remount(mount_point)
+            .await
+            .map_err(|e| Error::SecureMount(e.to_string()))
+    }
+
+    async fn inject_secrets(
+        &self,
+        manifest: InjectionManifest,
+        rootfs: &str,
+    ) -> Result<Vec<String>> {
+        info!("inject secrets called: {rootfs}");
+        let rootfs = confine_rootfs(&self.inject_rootfs_base, rootfs)?;
+        let secrets = self.resolve_secrets(&manifest).await?;
+        let leases = manifest.leases(&rootfs)?;
+        let injected = manifest.materialize(&rootfs, &secrets)?;
+
+        // no secret is left behind without its lease
+        if let Err(e) = self.leases.renew(&leases).await {
+            lease::discard(&leases);
+            return Err(e);
+        }
+
+        Ok(injected)
+    }
+
+    #[cfg(feature = "secret-dirs")]
+    async fn provision_secret_dir(&self, request: SecretDirRequest) -> Result<SecretDirMount> {
+        info!("provision secret dir called: {}", request.container_id);
+        let container_id = request.container_id.clone();
+        let provision = async {
+            let secrets = self.resolve_secrets(&request.manifest).await?;
+            let mount_path = request.mount_path.clone();
+
+            // the secrets of the dir provisioned before go with it
+            self.leases
+                .release(&self.secret_dirs.dir(&container_id)?)
+                .await?;
+            let (dir, manifest) = self.secret_dirs.prepare(request).await?;
+
+            // a dir missing some of its secrets or their leases is not
+            // handed out
+            let provisioned = async {
+                let leases = manifest.leases(&dir)?;
+                manifest.materialize(&dir, &secrets)?;
+                self.leases.renew(&leases).await
+            }
+            .await;
+            if let Err(e) = provisioned {
+                if let Err(e) = self.drop_secret_dir(&container_id).await {
+                    log::warn!("remove failed secret dir {container_id} failed: {e}");
+                }
+                return Err(e);
+            }
+
+            Ok(bind_mount(&dir, &mount_path))
+        };
+
+        self.secret_dirs.with_lock(&container_id, provision).await
+    }
+
+    #[cfg(feature = "secret-dirs")]
+    async fn remove_secret_dir(&self, container_id: &str) -> Result<()> {
+        info!("remove secret dir called: {container_id}");
+        self.secret_dirs
+            .with_lock(container_id, self.drop_secret_dir(container_id))
+            .await
+    }
+
+    #[cfg(feature = "key-service")]
+    async fn generate_key(&self, request: KeyRequest) -> Result<GeneratedKey> {
+        info!("generate key called: {:?}", request.algorithm);
+        self.keys.generate(&request).await
+    }
+
+    #[cfg(feature = "key-service")]
+    async fn install_certificate(
+        &self,
+        caller_uid: u32,
+        key_id: &str,
+        certificate: &[u8],
+    ) -> Result<String> {
+        info!("install certificate called: {key_id}");
+        let dir = self
+            .keys
+            .install_certificate(caller_uid, key_id, certificate)?;
+        Ok(dir.display().to_string())
+    }
+
+    #[cfg(feature = "image-pull")]
+    async fn pull_image(
+        &self,
+        image_url: &str,
+        bundle_path: &str,
+        options: ImagePullOptions,
+    ) -> Result<PulledImage> {
+        info!("pull image called: {image_url}");
+        // the options only apply to this pull, which runs on a client of its
+        // own sharing the layers and the snapshotters, so that the client is
+        // only locked to read its config
+        let client = {
+            let client = self.image_client.lock().await;
+            let mut config = client.config.clone();
+            options.apply(&mut config).map_err(Error::ImagePull)?;
+            client
+                .with_pull_config(config)
+                .map_err(|e| Error::ImagePull(format!("{e:?}")))?
+        };
+
+        client
+            .pull_image(
+                image_url,
+                std::path::Path::new(bundle_path),
+                &options.auth_info.as_deref(),
+                &options.decrypt_config.as_deref(),
+            )
+            .await
+            .map(|image| PulledImage {
+                image_id: image.meta.id,
+                verification: image.meta.verification,
+                layer_group_mounts: image
+                    .layer_groups
+                    .iter()
+                    .map(|group| group.mount_path().display().to_string())
+                    .collect(),
+            })
+            .map_err(|e| Error::ImagePull(format!("{e:?}")))
+    }
+
+    #[cfg(feature = "image-pull")]
+    async fn pin_image(&self, image_ref: &str) -> Result<()> {
+        info!("pin image called: {image_ref}");
+        let client = self.image_client.lock().await;
+        client
+            .pin_image(image_ref)
+            .await
+            .map_err(|e| Error::ImagePull(format!("{e:?}")))
+    }
+
+    #[cfg(feature = "image-pull")]
+    async fn unpin_image(&self, image_ref: &str) -> Result<bool> {
+        info!("unpin image called: {image_ref}");
+        let client = self.image_client.lock().await;
+        client
+            .unpin_image(image_ref)
+            .await
+            .map_err(|e| Error::ImagePull(format!("{e:?}")))
+    }
 }
@@ -0,0 +1,257 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Per-request options of the image pulling API.
+
+use image_rs::{
+    config::{IdShift, ImageConfig},
+    snapshots::SnapshotType,
+    verification::VerificationReport,
+};
+
+/// Options of a single image pull. Every `None` field falls back to the
+/// image-rs configuration of the CDH.
+///
+/// The security settings of the operator (registry credential source,
+/// signature verification, policy and sigstore config) cannot be relaxed by
+/// a request: they are only accepted if they keep or tighten the configured
+/// ones, see [`ImagePullOptions::apply`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImagePullOptions {
+    /// Registry credential as `username:password`.
+    pub auth_info: Option<String>,
+
+    /// Decrypt config of encrypted layers. This is also used to set up
+    /// the secure channel to the KBS, see `ImageClient::pull_image`.
+    pub decrypt_config: Option<String>,
+
+    /// Snapshotter the rootfs is mounted with, e.g. `overlay`.
+    pub snapshotter: Option<String>,
+
+    /// Platform (`os/arch[/variant]`) to select from multi-platform images.
+    pub platform: Option<String>,
+
+    /// Whether to look up registry credentials in `auth_file`. Must be the
+    /// configured value.
+    pub auth: Option<bool>,
+
+    /// KBS Resource URI of the registry auth file. Must be the configured
+    /// one.
+    pub auth_file: Option<String>,
+
+    /// Whether to verify the image against the security policy. It can
+    /// only be turned on.
+    pub security_validate: Option<bool>,
+
+    /// KBS Resource URI of the security policy. Must be the configured one.
+    pub policy_path: Option<String>,
+
+    /// KBS Resource URI of the sigstore config. Must be the configured one.
+    pub sigstore_config: Option<String>,
+
+    /// SELinux context the rootfs is labeled with. Must be the configured
+    /// or an allowed one, see `image_rs::config::RelabelConfig`.
+    pub selinux_label: Option<String>,
+
+    /// `<uid>:<gid>[:<size>]` range the rootfs owners are shifted to. Must
+    /// be the configured or an allowed one.
+    pub id_shift: Option<String>,
+}
+
+/// An image pulled by the image pulling API.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PulledImage {
+    /// Id of the image.
+    pub image_id: String,
+
+    /// Why the image passed the signature verification, or `None` if it was
+    /// pulled without `security_validate`.
+    pub verification: Option<VerificationReport>,
+
+    /// Where the layer groups of the image are mounted apart from the
+    /// rootfs, see `image_rs::layer_groups`.
+    pub layer_group_mounts: Vec<String>,
+}
+
+// fail if a request asks for another value of the security setting `name`
+// than the configured one
+fn keep_configured<T: PartialEq>(
+    name: &str,
+    requested: Option<&T>,
+    configured: &T,
+) -> Result<(), String> {
+    match requested {
+        Some(requested) if requested != configured => {
+            Err(format!("{name} cannot be overridden per request"))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl ImagePullOptions {
+    /// Override the fields of `config` that are set in the options. Fails
+    /// if the options relax the security settings of `config`.
+    pub fn apply(&self, config: &mut ImageConfig) -> Result<(), String> {
+        keep_configured("auth", self.auth.as_ref(), &config.auth)?;
+        keep_configured(
+            "auth_file",
+            self.auth_file.as_ref(),
+            &config.file_paths.auth_file,
+        )?;
+        keep_configured(
+            "policy_path",
+            self.policy_path.as_ref(),
+            &config.file_paths.policy_path,
+        )?;
+        keep_configured(
+            "sigstore_config",
+            self.sigstore_config.as_ref(),
+            &config.file_paths.sigstore_config,
+        )?;
+        if self.security_validate == Some(false) && config.security_validate {
+            return Err("security_validate cannot be turned off per request".into());
+        }
+
+        if let Some(snapshotter) = &self.snapshotter {
+            config.default_snapshot =
+                serde_json::from_value::<SnapshotType>(snapshotter.as_str().into())
+                    .map_err(|_| format!("unsupported snapshotter {snapshotter:?}"))?;
+        }
+
+        if let Some(platform) = &self.platform {
+            config.platform = Some(platform.clone());
+        }
+
+        if let Some(security_validate) = self.security_validate {
+            config.security_validate = security_validate;
+        }
+
+        // the relabeling is done as root, so only the operator sets what a
+        // request may ask for
+        if self.selinux_label.is_some() || self.id_shift.is_some() {
+            let relabel = config
+                .rootfs_relabel
+                .as_mut()
+                .ok_or("rootfs relabeling is not configured")?;
+            if let Some(selinux_label) = &self.selinux_label {
+                relabel
+                    .request_selinux_label(selinux_label)
+                    .map_err(|e| e.to_string())?;
+            }
+            if let Some(id_shift) = &self.id_shift {
+                let id_shift = id_shift.parse::<IdShift>().map_err(|e| e.to_string())?;
+                relabel
+                    .request_id_shift(&id_shift)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image_rs::config::RelabelConfig;
+
+    #[test]
+    fn test_apply_options() {
+        let mut config = ImageConfig::default();
+        let origin = config.clone();
+        ImagePullOptions::default().apply(&mut config).unwrap();
+        assert_eq!(config.default_snapshot, origin.default_snapshot);
+        assert_eq!(config.platform, None);
+
+        let options = ImagePullOptions {
+            snapshotter: Some("overlay".into()),
+            platform: Some("linux/arm64".into()),
+            auth: Some(origin.auth),
+            auth_file: Some(origin.file_paths.auth_file.clone()),
+            security_validate: Some(true),
+            ..Default::default()
+        };
+        options.apply(&mut config).unwrap();
+        assert_eq!(config.default_snapshot, SnapshotType::Overlay);
+        assert_eq!(config.platform.as_deref(), Some("linux/arm64"));
+        assert_eq!(config.auth, origin.auth);
+        assert!(config.security_validate);
+        assert_eq!(config.file_paths.auth_file, origin.file_paths.auth_file);
+        assert_eq!(config.file_paths.policy_path, origin.file_paths.policy_path);
+        assert!(config.rootfs_relabel.is_none());
+
+        // the security settings cannot be relaxed
+        for options in [
+            ImagePullOptions {
+                auth: Some(!origin.auth),
+                ..Default::default()
+            },
+            ImagePullOptions {
+                auth_file: Some("kbs:///default/credential/1".into()),
+                ..Default::default()
+            },
+            ImagePullOptions {
+                security_validate: Some(false),
+                ..Default::default()
+            },
+            ImagePullOptions {
+                policy_path: Some("kbs:///default/security-policy/test".into()),
+                ..Default::default()
+            },
+            ImagePullOptions {
+                sigstore_config: Some("kbs:///default/sigstore-config/test".into()),
+                ..Default::default()
+            },
+        ] {
+            let mut relaxed = config.clone();
+            assert!(options.apply(&mut relaxed).is_err(), "{options:?}");
+            assert!(relaxed.security_validate);
+        }
+
+        let options = ImagePullOptions {
+            selinux_label: Some("system_u:object_r:container_file_t:s0".into()),
+            id_shift: Some("100000:100000".into()),
+            ..Default::default()
+        };
+        assert!(options.apply(&mut config.clone()).is_err());
+
+        // only the relabeling allowed by the operator
+        config.rootfs_relabel = Some(RelabelConfig {
+            allowed_selinux_labels: vec!["system_u:object_r:container_file_t:s0".into()],
+            allowed_id_shifts: vec!["100000:100000".parse().unwrap()],
+            ..Default::default()
+        });
+        options.apply(&mut config).unwrap();
+        let relabel = config.rootfs_relabel.as_ref().unwrap();
+        assert_eq!(
+            relabel.selinux_label.as_deref(),
+            Some("system_u:object_r:container_file_t:s0")
+        );
+        assert_eq!(relabel.id_shift.as_ref().unwrap().uid, 100000);
+
+        for options in [
+            ImagePullOptions {
+                id_shift: Some("100000".into()),
+                ..Default::default()
+            },
+            ImagePullOptions {
+                id_shift: Some("0:0".into()),
+                ..Default::default()
+            },
+            ImagePullOptions {
+                selinux_label: Some("system_u:object_r:shadow_t:s0".into()),
+                ..Default::default()
+            },
+        ] {
+            assert!(options.apply(&mut config.clone()).is_err(), "{options:?}");
+        }
+
+        let options = ImagePullOptions {
+            snapshotter: Some("unknown-fs".into()),
+            ..Default::default()
+        };
+        assert!(options.apply(&mut config).is_err());
+    }
+}
@@ -0,0 +1,468 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Key pairs generated inside the TEE, e.g. for the mTLS identities of
+//! workloads.
+//!
+//! The private key of a key pair never leaves the guest. The caller gets a
+//! CSR to be signed by an external CA, together with attestation evidence
+//! whose runtime data is the sha256 digest of the DER encoded public key,
+//! so that the CA can issue the certificate only to a key held by an
+//! attested TEE. Once the certificate is installed, the key pair is written
+//! as `key.pem` and `cert.pem` to `<key dir>/<key id>/`, inside the guest,
+//! for the workloads to use.
+//!
+//! Only callers of the allowed uids, root by default, can generate key
+//! pairs, and only the caller which generated a key pair can install its
+//! certificate. At most [`MAX_KEYS`] key pairs are kept, and at most
+//! [`MAX_GENERATED_PER_MINUTE`] are generated a minute, as every one costs a
+//! key generation and an evidence.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::net::IpAddr;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use kbs_protocol::token_provider::AATokenProvider;
+use openssl::asn1::Asn1Time;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::stack::Stack;
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::{X509NameBuilder, X509ReqBuilder, X509VerifyResult, X509};
+use sha2::{Digest, Sha256};
+
+use crate::{Error, Result};
+
+/// Dir the key pairs with an installed certificate are written to.
+pub const DEFAULT_KEY_DIR: &str = "/run/confidential-containers/cdh/keys";
+
+/// Most key pairs kept in memory.
+pub const MAX_KEYS: usize = 256;
+
+/// Most key pairs generated a minute.
+pub const MAX_GENERATED_PER_MINUTE: usize = 32;
+
+const GENERATE_WINDOW: Duration = Duration::from_secs(60);
+
+const KEY_FILE: &str = "key.pem";
+const CERT_FILE: &str = "cert.pem";
+
+/// Algorithm of a generated key pair.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyAlgorithm {
+    #[default]
+    EcP256,
+    EcP384,
+    Rsa2048,
+    Rsa4096,
+}
+
+impl FromStr for KeyAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "" | "ec-p256" => Ok(Self::EcP256),
+            "ec-p384" => Ok(Self::EcP384),
+            "rsa-2048" => Ok(Self::Rsa2048),
+            "rsa-4096" => Ok(Self::Rsa4096),
+            other => Err(Error::GenerateKey(format!(
+                "unsupported key algorithm {other:?}"
+            ))),
+        }
+    }
+}
+
+/// Subject of the CSR of a generated key pair.
+#[derive(Clone, Debug, Default)]
+pub struct KeyRequest {
+    /// Uid of the caller, e.g. got with `SO_PEERCRED`.
+    pub caller_uid: u32,
+    pub algorithm: KeyAlgorithm,
+    pub common_name: String,
+    pub dns_names: Vec<String>,
+    pub ip_addresses: Vec<IpAddr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GeneratedKey {
+    /// Id of the key pair to install its certificate with.
+    pub key_id: String,
+
+    /// PEM CSR signed by the key pair.
+    pub csr: Vec<u8>,
+
+    /// Evidence of the TEE, bound to the public key of the key pair.
+    pub evidence: Vec<u8>,
+}
+
+struct StoredKey {
+    /// Uid of the caller which generated the key pair.
+    owner: u32,
+    key: PKey<Private>,
+}
+
+/// The key pairs generated by this CDH, kept in memory only.
+pub struct KeyStore {
+    dir: PathBuf,
+    keys: Mutex<HashMap<String, StoredKey>>,
+    allowed_uids: Vec<u32>,
+
+    /// When the key pairs of the last minute were generated, oldest first.
+    generated: Mutex<VecDeque<Instant>>,
+}
+
+impl KeyStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            keys: Mutex::new(HashMap::new()),
+            allowed_uids: vec![0],
+            generated: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Allow the callers of `uids` to generate key pairs, instead of root.
+    pub fn set_allowed_uids(&mut self, uids: Vec<u32>) {
+        self.allowed_uids = uids;
+    }
+
+    /// Generate a key pair, and get its CSR and the evidence binding its
+    /// public key to the TEE from the attestation-agent.
+    pub async fn generate(&self, request: &KeyRequest) -> Result<GeneratedKey> {
+        if !self.allowed_uids.contains(&request.caller_uid) {
+            return Err(Error::GenerateKey(format!(
+                "uid {} is not allowed to generate keys",
+                request.caller_uid
+            )));
+        }
+        self.throttle()?;
+        let (key_id, csr, public_key) = self.create_key(request)?;
+
+        let evidence = AATokenProvider::new()
+            .await
+            .map_err(|e| Error::GenerateKey(format!("connect attestation-agent failed: {e}")))?
+            .get_evidence(Sha256::digest(&public_key).to_vec())
+            .await
+            .map_err(|e| Error::GenerateKey(format!("get evidence failed: {e}")))?;
+
+        Ok(GeneratedKey {
+            key_id,
+            csr,
+            evidence,
+        })
+    }
+
+    // Count a generation, failing if too many were done in the last minute.
+    fn throttle(&self) -> Result<()> {
+        let mut generated = self
+            .generated
+            .lock()
+            .map_err(|_| Error::GenerateKey("key store lock poisoned".into()))?;
+        let now = Instant::now();
+        while generated
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= GENERATE_WINDOW)
+        {
+            generated.pop_front();
+        }
+        if generated.len() >= MAX_GENERATED_PER_MINUTE {
+            return Err(Error::GenerateKey(format!(
+                "more than {MAX_GENERATED_PER_MINUTE} keys generated in the last minute"
+            )));
+        }
+
+        generated.push_back(now);
+        Ok(())
+    }
+
+    // Generate a key pair and keep it. Returns its id, its CSR and its DER
+    // encoded public key.
+    fn create_key(&self, request: &KeyRequest) -> Result<(String, Vec<u8>, Vec<u8>)> {
+        if self.keys.lock().map_or(0, |keys| keys.len()) >= MAX_KEYS {
+            return Err(Error::GenerateKey(format!(
+                "{MAX_KEYS} keys are kept already"
+            )));
+        }
+
+        let key = new_key(request.algorithm)
+            .map_err(|e| Error::GenerateKey(format!("generate key pair failed: {e}")))?;
+        let csr = new_csr(&key, request)
+            .map_err(|e| Error::GenerateKey(format!("create CSR failed: {e}")))?;
+        let public_key = key
+            .public_key_to_der()
+            .map_err(|e| Error::GenerateKey(format!("encode public key failed: {e}")))?;
+
+        let key_id = hex(&Sha256::digest(&public_key)[..16]);
+        let mut keys = self
+            .keys
+            .lock()
+            .map_err(|_| Error::GenerateKey("key store lock poisoned".into()))?;
+        // checked again, other keys may have been generated meanwhile
+        if keys.len() >= MAX_KEYS {
+            return Err(Error::GenerateKey(format!(
+                "{MAX_KEYS} keys are kept already"
+            )));
+        }
+        keys.insert(
+            key_id.clone(),
+            StoredKey {
+                owner: request.caller_uid,
+                key,
+            },
+        );
+
+        Ok((key_id, csr, public_key))
+    }
+
+    /// Install the PEM `certificate` chain of the key pair `key_id`, leaf
+    /// first, and write the key pair to the key dir. Each certificate of the
+    /// chain must be signed by the next one. A certificate can be installed
+    /// again, e.g. when it is renewed, by the caller `caller_uid` which
+    /// generated the key pair. Returns the dir of the key pair.
+    pub fn install_certificate(
+        &self,
+        caller_uid: u32,
+        key_id: &str,
+        certificate: &[u8],
+    ) -> Result<PathBuf> {
+        let keys = self
+            .keys
+            .lock()
+            .map_err(|_| Error::InstallCertificate("key store lock poisoned".into()))?;
+        let key = keys
+            .get(key_id)
+            .filter(|stored| stored.owner == caller_uid)
+            .map(|stored| &stored.key)
+            .ok_or_else(|| Error::InstallCertificate(format!("unknown key id {key_id:?}")))?;
+
+        let chain = X509::stack_from_pem(certificate)
+            .map_err(|e| Error::InstallCertificate(format!("illegal certificate: {e}")))?;
+        let leaf = chain
+            .first()
+            .ok_or_else(|| Error::InstallCertificate("no certificate given".into()))?;
+        let matches = leaf
+            .public_key()
+            .map(|public_key| public_key.public_eq(key))
+            .unwrap_or(false);
+        if !matches {
+            return Err(Error::InstallCertificate(format!(
+                "certificate is not issued for key {key_id}"
+            )));
+        }
+        let now = Asn1Time::days_from_now(0)
+            .map_err(|e| Error::InstallCertificate(format!("get current time failed: {e}")))?;
+        if leaf.not_after() < now {
+            return Err(Error::InstallCertificate("certificate has expired".into()));
+        }
+        verify_chain(&chain)?;
+
+        let mut cert_pem = Vec::new();
+        for cert in &chain {
+            let pem = cert.to_pem().map_err(|e| {
+                Error::InstallCertificate(format!("encode certificate failed: {e}"))
+            })?;
+            cert_pem.extend_from_slice(&pem);
+        }
+        let key_pem = zeroize::Zeroizing::new(
+            key.private_key_to_pem_pkcs8()
+                .map_err(|e| Error::InstallCertificate(format!("encode key failed: {e}")))?,
+        );
+
+        let dir = self.dir.join(key_id);
+        write_private(&dir, KEY_FILE, &key_pem)
+            .and_then(|_| write_private(&dir, CERT_FILE, &cert_pem))
+            .map_err(|e| {
+                Error::InstallCertificate(format!("write key pair to {}: {e}", dir.display()))
+            })?;
+
+        Ok(dir)
+    }
+}
+
+// check that every certificate of `chain` is issued and signed by the next
+// one
+fn verify_chain(chain: &[X509]) -> Result<()> {
+    for (i, link) in chain.windows(2).enumerate() {
+        let [cert, issuer] = link else {
+            unreachable!("windows of two certificates");
+        };
+        let signed = issuer
+            .public_key()
+            .and_then(|issuer_key| cert.verify(&issuer_key))
+            .unwrap_or(false);
+        if issuer.issued(cert) != X509VerifyResult::OK || !signed {
+            return Err(Error::InstallCertificate(format!(
+                "certificate {i} of the chain is not issued by certificate {}",
+                i + 1
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn new_key(
+    algorithm: KeyAlgorithm,
+) -> std::result::Result<PKey<Private>, openssl::error::ErrorStack> {
+    let ec = |nid| EcGroup::from_curve_name(nid).and_then(|group| EcKey::generate(&group));
+    match algorithm {
+        KeyAlgorithm::EcP256 => PKey::from_ec_key(ec(Nid::X9_62_PRIME256V1)?),
+        KeyAlgorithm::EcP384 => PKey::from_ec_key(ec(Nid::SECP384R1)?),
+        KeyAlgorithm::Rsa2048 => PKey::from_rsa(Rsa::generate(2048)?),
+        KeyAlgorithm::Rsa4096 => PKey::from_rsa(Rsa::generate(4096)?),
+    }
+}
+
+fn new_csr(
+    key: &PKey<Private>,
+    request: &KeyRequest,
+) -> std::result::Result<Vec<u8>, openssl::error::ErrorStack> {
+    let mut name = X509NameBuilder::new()?;
+    if !request.common_name.is_empty() {
+        name.append_entry_by_nid(Nid::COMMONNAME, &request.common_name)?;
+    }
+    let name = name.build();
+
+    let mut builder = X509ReqBuilder::new()?;
+    builder.set_version(0)?;
+    builder.set_subject_name(&name)?;
+    builder.set_pubkey(key)?;
+
+    if !request.dns_names.is_empty() || !request.ip_addresses.is_empty() {
+        let mut san = SubjectAlternativeName::new();
+        for dns in &request.dns_names {
+            san.dns(dns);
+        }
+        for ip in &request.ip_addresses {
+            san.ip(&ip.to_string());
+        }
+        let san = san.build(&builder.x509v3_context(None))?;
+        let mut extensions = Stack::new()?;
+        extensions.push(san)?;
+        builder.add_extensions(&extensions)?;
+    }
+
+    builder.sign(key, MessageDigest::sha256())?;
+    builder.build().to_pem()
+}
+
+fn write_private(dir: &Path, name: &str, content: &[u8]) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    // write to a temporary file first, so that a renewed key pair is
+    // replaced at once
+    let tmp = dir.join(format!(".{name}.tmp"));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(tmp, dir.join(name))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::bn::BigNum;
+    use openssl::x509::{X509Builder, X509Req};
+    use std::os::unix::fs::PermissionsExt;
+
+    fn issue(csr: &[u8], not_after: &Asn1Time) -> Vec<u8> {
+        let ca_key = new_key(KeyAlgorithm::EcP256).unwrap();
+        let csr = X509Req::from_pem(csr).unwrap();
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+        builder.set_serial_number(&serial).unwrap();
+        builder.set_subject_name(csr.subject_name()).unwrap();
+        builder.set_issuer_name(csr.subject_name()).unwrap();
+        builder.set_pubkey(&csr.public_key().unwrap()).unwrap();
+        builder
+            .set_not_before(&Asn1Time::from_unix(0).unwrap())
+            .unwrap();
+        builder.set_not_after(not_after).unwrap();
+        builder.sign(&ca_key, MessageDigest::sha256()).unwrap();
+        builder.build().to_pem().unwrap()
+    }
+
+    #[test]
+    fn test_key_algorithm() {
+        assert_eq!(KeyAlgorithm::from_str("").unwrap(), KeyAlgorithm::EcP256);
+        assert_eq!(
+            KeyAlgorithm::from_str("rsa-2048").unwrap(),
+            KeyAlgorithm::Rsa2048
+        );
+        assert!(KeyAlgorithm::from_str("dsa").is_err());
+    }
+
+    #[test]
+    fn test_generate_and_install() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = KeyStore::new(dir.path());
+        let request = KeyRequest {
+            algorithm: KeyAlgorithm::EcP384,
+            common_name: "workload".into(),
+            dns_names: vec!["workload.default.svc".into()],
+            ip_addresses: vec!["10.0.0.1".parse().unwrap()],
+        };
+
+        let (key_id, csr, public_key) = store.create_key(&request).unwrap();
+        let req = X509Req::from_pem(&csr).unwrap();
+        let req_key = req.public_key().unwrap();
+        assert!(req.verify(&req_key).unwrap());
+        assert_eq!(req_key.public_key_to_der().unwrap(), public_key);
+        let cn = req
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .unwrap();
+        assert_eq!(cn.data().as_slice(), b"workload");
+
+        let valid = Asn1Time::days_from_now(1).unwrap();
+        let path = store
+            .install_certificate(0, &key_id, &issue(&csr, &valid))
+            .unwrap();
+        assert_eq!(path, dir.path().join(&key_id));
+        let key_pem = fs::read(path.join(KEY_FILE)).unwrap();
+        let key = PKey::private_key_from_pem(&key_pem).unwrap();
+        assert_eq!(key.public_key_to_der().unwrap(), public_key);
+        let mode = fs::metadata(path.join(KEY_FILE))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(X509::from_pem(&fs::read(path.join(CERT_FILE)).unwrap()).is_ok());
+
+        // certificates of other keys, expired ones and unknown keys
+        let (_, other_csr, _) = store.create_key(&KeyRequest::default()).unwrap();
+        assert!(store
+            .install_certificate(0, &key_id, &issue(&other_csr, &valid))
+            .is_err());
+        let expired = Asn1Time::from_unix(1).unwrap();
+        assert!(store
+            .install_certificate(0, &key_id, &issue(&csr, &expired))
+            .is_err());
+        assert!(store
+            .install_certificate(0, "unknown", &issue(&csr, &valid))
+            .is_err());
+        assert!(store.install_certificate(0, &key_id, b"not a pem").is_err());
+    }
+}
@@ -0,0 +1,464 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Leases of the secrets injected as files, see [`crate::inject`].
+//!
+//! A file injected with a `ttl` is leased: once the TTL is over, CDH shreds
+//! it, overwriting its content with zeros before removing it, and notifies
+//! the subscribers of [`Leases::subscribe`]. This bounds how long a
+//! credential stays readable inside a long-running pod, whether or not the
+//! workload cleans it up. Injecting the same path again renews or, without
+//! a `ttl`, drops its lease.
+//!
+//! The leases are persisted, so that the files whose lease expired while
+//! CDH was down are shredded when it starts again. A lease only shreds the
+//! file it was taken on: if the workload replaced the file at the path, the
+//! new file is left alone.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, Mutex, Notify};
+
+use crate::{Error, Result};
+
+/// Where the leases are persisted.
+pub const LEASE_TABLE_PATH: &str = "/run/confidential-containers/cdh/secret-leases.json";
+
+/// Expiry events kept for a subscriber lagging behind.
+const EVENT_CAPACITY: usize = 64;
+
+/// Size of the zeros written at once when shredding a file.
+const SHRED_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The lease of an injected file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Lease {
+    /// Unix time in seconds the lease expires at.
+    pub expires_at: u64,
+
+    // identity of the leased file, so that a replaced file is not shredded
+    dev: u64,
+    ino: u64,
+}
+
+/// Event sent to the subscribers when a lease expires.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeaseExpired {
+    /// Path of the leased file, outside of the rootfs.
+    pub path: PathBuf,
+
+    /// Whether the file was shredded. It is not if it was already removed
+    /// or replaced by the workload, or if shredding it failed.
+    pub shredded: bool,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// The leases taken so far, by path of the leased file, persisted at `path`.
+#[derive(Debug)]
+pub struct LeaseTable {
+    path: PathBuf,
+    leases: BTreeMap<PathBuf, Lease>,
+}
+
+impl LeaseTable {
+    /// Load the table persisted at `path`. A missing file is an empty table.
+    pub fn load(path: &Path) -> Result<Self> {
+        let leases = match fs::read(path) {
+            Ok(content) => serde_json::from_slice(&content).map_err(|e| {
+                Error::SecretLease(format!("parse lease table {} failed: {e}", path.display()))
+            })?,
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                return Err(Error::SecretLease(format!(
+                    "read lease table {} failed: {e}",
+                    path.display()
+                )))
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            leases,
+        })
+    }
+
+    /// Get the lease of the file at `path`.
+    pub fn get(&self, path: &Path) -> Option<&Lease> {
+        self.leases.get(path)
+    }
+
+    /// Lease the files at the paths for their TTLs, dropping the leases of
+    /// the files without one.
+    pub fn renew(&mut self, files: &[(PathBuf, Option<Duration>)]) -> Result<()> {
+        let now = unix_now();
+        for (path, ttl) in files {
+            let Some(ttl) = ttl else {
+                self.leases.remove(path);
+                continue;
+            };
+
+            let meta = fs::symlink_metadata(path).map_err(|e| {
+                Error::SecretLease(format!("stat leased file {} failed: {e}", path.display()))
+            })?;
+            let lease = Lease {
+                expires_at: now.saturating_add(ttl.as_secs()),
+                dev: meta.dev(),
+                ino: meta.ino(),
+            };
+            self.leases.insert(path.clone(), lease);
+        }
+
+        self.save()
+    }
+
+    /// Drop the leases of the files under `dir`, e.g. once it is removed.
+    pub fn release(&mut self, dir: &Path) -> Result<()> {
+        let leased = self.leases.len();
+        self.leases.retain(|path, _| !path.starts_with(dir));
+        if self.leases.len() == leased {
+            return Ok(());
+        }
+
+        self.save()
+    }
+
+    /// Unix time in seconds the next lease expires at.
+    pub fn next_expiry(&self) -> Option<u64> {
+        self.leases.values().map(|lease| lease.expires_at).min()
+    }
+
+    /// Shred the files whose lease is expired at unix time `now`, and drop
+    /// their leases.
+    pub fn expire(&mut self, now: u64) -> Result<Vec<LeaseExpired>> {
+        let expired: Vec<_> = self
+            .leases
+            .iter()
+            .filter(|(_, lease)| lease.expires_at <= now)
+            .map(|(path, _)| path.clone())
+            .collect();
+        if expired.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut events = Vec::new();
+        for path in expired {
+            let lease = self.leases.remove(&path).expect("listed above");
+            let shredded = match shred(&path, &lease) {
+                Ok(shredded) => shredded,
+                Err(e) => {
+                    warn!("shred leased file {} failed: {e}", path.display());
+                    false
+                }
+            };
+            events.push(LeaseExpired { path, shredded });
+        }
+
+        self.save()?;
+        Ok(events)
+    }
+
+    fn save(&self) -> Result<()> {
+        let save_err = |e: std::io::Error| {
+            Error::SecretLease(format!(
+                "save lease table {} failed: {e}",
+                self.path.display()
+            ))
+        };
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(save_err)?;
+        }
+
+        let content = serde_json::to_vec(&self.leases)
+            .map_err(|e| Error::SecretLease(format!("serialize lease table failed: {e}")))?;
+        let tmp = self.path.with_extension("tmp");
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp)
+            .map_err(save_err)?;
+        file.write_all(&content).map_err(save_err)?;
+        file.sync_all().map_err(save_err)?;
+        fs::rename(&tmp, &self.path).map_err(save_err)
+    }
+}
+
+/// Shred the files just injected at the paths, whose leases could not be
+/// taken, so that none is left on disk without one.
+pub fn discard(files: &[(PathBuf, Option<Duration>)]) {
+    for (path, _) in files {
+        if let Err(e) = shred_if(path, fs::Metadata::is_file) {
+            warn!("shred unleased file {} failed: {e}", path.display());
+        }
+    }
+}
+
+/// Overwrite the leased file at `path` with zeros and remove it. Returns
+/// false if the path no longer holds the leased file.
+fn shred(path: &Path, lease: &Lease) -> std::io::Result<bool> {
+    shred_if(path, |meta| {
+        meta.is_file() && meta.dev() == lease.dev && meta.ino() == lease.ino
+    })
+}
+
+// overwrite the file at `path` with zeros and remove it, if it is the
+// `expected` one
+fn shred_if(path: &Path, expected: impl Fn(&fs::Metadata) -> bool) -> std::io::Result<bool> {
+    // stat first not to open e.g. a fifo the workload put there
+    match fs::symlink_metadata(path) {
+        Ok(meta) if expected(&meta) => {}
+        Ok(_) => return Ok(false),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    }
+
+    // a symlink swapped in meanwhile is not followed
+    let mut file = match fs::OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)
+    {
+        Ok(file) => file,
+        Err(e) if e.raw_os_error() == Some(libc::ELOOP) => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let meta = file.metadata()?;
+    if !expected(&meta) {
+        return Ok(false);
+    }
+
+    let zeros = [0u8; SHRED_CHUNK_SIZE];
+    let mut left = meta.len();
+    while left > 0 {
+        let len = left.min(SHRED_CHUNK_SIZE as u64) as usize;
+        file.write_all(&zeros[..len])?;
+        left -= len as u64;
+    }
+    file.sync_all()?;
+    fs::remove_file(path)?;
+
+    Ok(true)
+}
+
+/// The lease table, with the subscribers of its expiry events.
+pub struct Leases {
+    table: Mutex<LeaseTable>,
+
+    renewed: Notify,
+
+    events: broadcast::Sender<LeaseExpired>,
+}
+
+impl Leases {
+    pub fn new(table: LeaseTable) -> Arc<Self> {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Arc::new(Self {
+            table: Mutex::new(table),
+            renewed: Notify::new(),
+            events,
+        })
+    }
+
+    /// Lease the injected files for their TTLs, see [`LeaseTable::renew`].
+    pub async fn renew(&self, files: &[(PathBuf, Option<Duration>)]) -> Result<()> {
+        self.table.lock().await.renew(files)?;
+        self.renewed.notify_one();
+        Ok(())
+    }
+
+    /// Drop the leases of the files under `dir`, see [`LeaseTable::release`].
+    pub async fn release(&self, dir: &Path) -> Result<()> {
+        self.table.lock().await.release(dir)
+    }
+
+    /// Get notified every time a lease expires.
+    pub fn subscribe(&self) -> broadcast::Receiver<LeaseExpired> {
+        self.events.subscribe()
+    }
+
+    /// Shred the files as their leases expire, until the leases are dropped.
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            let next_expiry = {
+                let mut table = self.table.lock().await;
+                match table.expire(unix_now()) {
+                    Ok(events) => {
+                        for event in events {
+                            info!(
+                                "lease of {} expired, shredded: {}",
+                                event.path.display(),
+                                event.shredded
+                            );
+                            // nobody may be subscribed
+                            let _ = self.events.send(event);
+                        }
+                    }
+                    Err(e) => warn!("expire secret leases failed: {e}"),
+                }
+                table.next_expiry()
+            };
+
+            match next_expiry {
+                Some(expires_at) => {
+                    let wait = Duration::from_secs(expires_at.saturating_sub(unix_now()));
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {}
+                        _ = self.renewed.notified() => {}
+                    }
+                }
+                None => self.renewed.notified().await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let table_path = dir.path().join("leases.json");
+        let key = dir.path().join("tls.key");
+        let env = dir.path().join("env");
+        fs::write(&key, b"secret key").unwrap();
+        fs::write(&env, b"DB_PASSWORD=hunter2\n").unwrap();
+
+        let mut table = LeaseTable::load(&table_path).unwrap();
+        table
+            .renew(&[
+                (key.clone(), Some(Duration::from_secs(60))),
+                (env.clone(), Some(Duration::from_secs(3600))),
+            ])
+            .unwrap();
+        let expires_at = table.get(&key).unwrap().expires_at;
+        assert_eq!(table.next_expiry(), Some(expires_at));
+
+        // the leases survive a restart
+        let mut table = LeaseTable::load(&table_path).unwrap();
+        assert!(table.expire(expires_at - 1).unwrap().is_empty());
+        assert_eq!(
+            table.expire(expires_at).unwrap(),
+            [LeaseExpired {
+                path: key.clone(),
+                shredded: true
+            }]
+        );
+        assert!(!key.exists());
+        assert!(table.get(&key).is_none());
+
+        // a file replaced by the workload is left alone
+        let replaced = dir.path().join("replaced");
+        fs::write(&replaced, b"DB_PASSWORD=hunter3\n").unwrap();
+        fs::rename(&replaced, &env).unwrap();
+        assert_eq!(
+            table.expire(u64::MAX).unwrap(),
+            [LeaseExpired {
+                path: env.clone(),
+                shredded: false
+            }]
+        );
+        assert_eq!(fs::read(&env).unwrap(), b"DB_PASSWORD=hunter3\n");
+        assert_eq!(table.next_expiry(), None);
+
+        // injecting again without a ttl drops the lease
+        table
+            .renew(&[(env.clone(), Some(Duration::from_secs(60)))])
+            .unwrap();
+        table.renew(&[(env.clone(), None)]).unwrap();
+        assert!(table.get(&env).is_none());
+
+        // the leases under a removed dir are dropped, and not the others
+        let secret_dir = dir.path().join("app");
+        fs::create_dir(&secret_dir).unwrap();
+        fs::write(secret_dir.join("token"), b"token").unwrap();
+        table
+            .renew(&[
+                (secret_dir.join("token"), Some(Duration::from_secs(60))),
+                (env.clone(), Some(Duration::from_secs(60))),
+            ])
+            .unwrap();
+        table.release(&secret_dir).unwrap();
+        assert!(table.get(&secret_dir.join("token")).is_none());
+        assert!(LeaseTable::load(&table_path)
+            .unwrap()
+            .get(&secret_dir.join("token"))
+            .is_none());
+        assert!(table.get(&env).is_some());
+
+        assert!(table
+            .renew(&[(dir.path().join("missing"), Some(Duration::from_secs(60)))])
+            .is_err());
+    }
+
+    #[test]
+    fn test_shred_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret = dir.path().join("secret");
+        let other = dir.path().join("other");
+        fs::write(&secret, b"hunter2").unwrap();
+        fs::write(&other, b"not leased").unwrap();
+
+        // the leased file swapped for a symlink to the same file
+        let mut table = LeaseTable::load(&dir.path().join("leases.json")).unwrap();
+        table
+            .renew(&[(other.clone(), Some(Duration::from_secs(60)))])
+            .unwrap();
+        let lease = table.get(&other).unwrap().clone();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&other, &link).unwrap();
+        assert!(!shred(&link, &lease).unwrap());
+        assert_eq!(fs::read(&other).unwrap(), b"not leased");
+
+        discard(&[(link.clone(), None), (secret.clone(), None)]);
+        assert!(link.exists());
+        assert_eq!(fs::read(&other).unwrap(), b"not leased");
+        assert!(!secret.exists());
+    }
+
+    #[tokio::test]
+    async fn test_leases_notify() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret = dir.path().join("secret");
+        fs::write(&secret, b"hunter2").unwrap();
+
+        let leases = Leases::new(LeaseTable::load(&dir.path().join("leases.json")).unwrap());
+        let mut expired = leases.subscribe();
+        tokio::spawn(leases.clone().run());
+        leases
+            .renew(&[(secret.clone(), Some(Duration::ZERO))])
+            .await
+            .unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(5), expired.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            LeaseExpired {
+                path: secret.clone(),
+                shredded: true
+            }
+        );
+        assert!(!secret.exists());
+    }
+}
@@ -9,6 +9,25 @@ pub use error::*;
 pub mod api;
 pub use api::*;
 
+pub mod cache;
+
 pub mod hub;
 
+pub mod inject;
+
+#[cfg(feature = "key-service")]
+pub mod keys;
+
+pub mod lease;
+
+pub mod resource;
+
+#[cfg(feature = "secret-dirs")]
+pub mod secret_dirs;
+
+pub mod spool;
+
+#[cfg(feature = "image-pull")]
+pub mod image_pull;
+
 pub mod auth;
@@ -0,0 +1,268 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Secret dirs provisioned for containers at creation time.
+//!
+//! Workloads getting their secrets from CDH at runtime need a CDH client in
+//! their image, or an init container running one. Instead, the agent asks
+//! CDH for the secret dir of a container while creating it: CDH mounts a
+//! tmpfs in guest memory under [`DEFAULT_SECRET_DIRS_ROOT`], writes the
+//! secrets of an [`InjectionManifest`] into it, resources and sealed secrets
+//! alike, owned by the user of the container, and returns the bind mount the
+//! agent adds to the OCI spec of the container. The secrets are never written
+//! to the virtio-fs share of the pod, which the host can read.
+//!
+//! The paths of the manifest are relative to the dir. Once the container is
+//! gone, the agent has the dir unmounted and removed. The requests for the
+//! dir of a container are served one at a time.
+
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use storage::mounts::{mount_tmpfs, unmount};
+use tokio::sync::Mutex;
+
+use crate::inject::InjectionManifest;
+use crate::{Error, Result};
+
+/// Dir the secret dirs of the containers are mounted under.
+pub const DEFAULT_SECRET_DIRS_ROOT: &str = "/run/confidential-containers/cdh/secret-dirs";
+
+/// Size in bytes of a secret dir if the request does not give one.
+pub const DEFAULT_SECRET_DIR_SIZE: u64 = 1024 * 1024;
+
+/// Options of the bind mount of a secret dir into its container.
+pub const SECRET_DIR_MOUNT_OPTIONS: [&str; 5] = ["rbind", "ro", "nosuid", "nodev", "noexec"];
+
+/// The secret dir of a container to provision.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SecretDirRequest {
+    /// Id of the container, which names its secret dir.
+    pub container_id: String,
+
+    /// Secrets written into the dir, with paths relative to it.
+    pub manifest: InjectionManifest,
+
+    /// Absolute path the dir is mounted at inside the container.
+    pub mount_path: String,
+
+    /// Owner of the dir, and of the secrets without an owner in the
+    /// manifest.
+    pub uid: u32,
+
+    pub gid: u32,
+
+    /// Max bytes of the secrets, [`DEFAULT_SECRET_DIR_SIZE`] if not given.
+    pub size: Option<u64>,
+}
+
+/// The bind mount of a secret dir, to add to the OCI spec of its container.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SecretDirMount {
+    /// The secret dir inside the guest.
+    pub source: String,
+
+    /// The path inside the container.
+    pub destination: String,
+
+    pub options: Vec<String>,
+}
+
+/// The secret dirs under a root dir.
+pub struct SecretDirs {
+    root: PathBuf,
+
+    /// Locks of the dirs being provisioned or removed, by container id.
+    busy: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl SecretDirs {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            busy: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Run `f` holding the lock of the secret dir of the container
+    /// `container_id`, so that concurrent requests for the dir don't race.
+    pub async fn with_lock<T>(&self, container_id: &str, f: impl Future<Output = T>) -> T {
+        let slot = self
+            .busy
+            .lock()
+            .await
+            .entry(container_id.to_string())
+            .or_default()
+            .clone();
+
+        let res = {
+            let _busy = slot.lock().await;
+            f.await
+        };
+
+        // the map and this call hold the slot if nobody else waits for it
+        let mut busy = self.busy.lock().await;
+        if Arc::strong_count(&slot) == 2 {
+            busy.remove(container_id);
+        }
+
+        res
+    }
+
+    /// The secret dir of the container `container_id`. The id must be a
+    /// single path component.
+    pub fn dir(&self, container_id: &str) -> Result<PathBuf> {
+        let legal = !container_id.is_empty()
+            && container_id != "."
+            && container_id != ".."
+            && container_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !legal {
+            return Err(Error::SecretDir(format!(
+                "illegal container id {container_id:?}"
+            )));
+        }
+
+        Ok(self.root.join(container_id))
+    }
+
+    /// Mount the empty tmpfs of the secret dir of `request`, replacing the
+    /// one provisioned before for the container if any. Returns the dir and
+    /// the manifest with the owner of the request set on its files.
+    pub async fn prepare(&self, request: SecretDirRequest) -> Result<(PathBuf, InjectionManifest)> {
+        if !request.mount_path.starts_with('/') {
+            return Err(Error::SecretDir(format!(
+                "mount path {:?} is not absolute",
+                request.mount_path
+            )));
+        }
+
+        let size = match request.size {
+            Some(0) => return Err(Error::SecretDir("illegal size 0".into())),
+            size => size.unwrap_or(DEFAULT_SECRET_DIR_SIZE),
+        };
+
+        let dir = self.dir(&request.container_id)?;
+        self.remove(&request.container_id).await?;
+        fs::create_dir_all(&dir)
+            .map_err(|e| Error::SecretDir(format!("create dir {} failed: {e}", dir.display())))?;
+        let options = tmpfs_options(size, request.uid, request.gid);
+        if let Err(e) = mount_tmpfs(&dir.to_string_lossy(), &options).await {
+            let _ = fs::remove_dir(&dir);
+            return Err(Error::SecretDir(e.to_string()));
+        }
+
+        let mut manifest = request.manifest;
+        let files = manifest.files.iter_mut().map(|file| &mut file.attrs);
+        let env_files = manifest.env_files.iter_mut().map(|file| &mut file.attrs);
+        for attrs in files.chain(env_files) {
+            attrs.uid.get_or_insert(request.uid);
+            attrs.gid.get_or_insert(request.gid);
+        }
+
+        Ok((dir, manifest))
+    }
+
+    /// Unmount and remove the secret dir of the container `container_id`,
+    /// if any.
+    pub async fn remove(&self, container_id: &str) -> Result<()> {
+        let dir = self.dir(container_id)?;
+        if !dir.exists() {
+            return Ok(());
+        }
+
+        unmount(&dir.to_string_lossy())
+            .await
+            .map_err(|e| Error::SecretDir(e.to_string()))?;
+        fs::remove_dir_all(&dir)
+            .map_err(|e| Error::SecretDir(format!("remove dir {} failed: {e}", dir.display())))
+    }
+}
+
+/// The bind mount of the secret `dir` at `mount_path` inside the container.
+pub fn bind_mount(dir: &Path, mount_path: &str) -> SecretDirMount {
+    SecretDirMount {
+        source: dir.display().to_string(),
+        destination: mount_path.to_string(),
+        options: SECRET_DIR_MOUNT_OPTIONS.map(String::from).to_vec(),
+    }
+}
+
+// the mount options of the tmpfs of a secret dir, only readable by its owner
+fn tmpfs_options(size: u64, uid: u32, gid: u32) -> String {
+    format!("size={size},mode=0500,uid={uid},gid={gid},nosuid,nodev,noexec")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_secret_dir() {
+        let dirs = SecretDirs::new("/run/secret-dirs");
+        assert_eq!(
+            dirs.dir("4bf92f3577b3-app_1.0").unwrap(),
+            Path::new("/run/secret-dirs/4bf92f3577b3-app_1.0")
+        );
+        for id in ["", ".", "..", "../etc", "a/b", "a b"] {
+            assert!(dirs.dir(id).is_err(), "{id:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prepare_checks() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dirs = SecretDirs::new(tempdir.path());
+        let request = |mount_path: &str, size| SecretDirRequest {
+            container_id: "app".into(),
+            mount_path: mount_path.into(),
+            size,
+            ..Default::default()
+        };
+        assert!(dirs.prepare(request("run/secrets", None)).await.is_err());
+        assert!(dirs
+            .prepare(request("/run/secrets", Some(0)))
+            .await
+            .is_err());
+        assert!(!tempdir.path().join("app").exists());
+
+        // nothing to remove
+        dirs.remove("app").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_with_lock() {
+        let dirs = SecretDirs::new("/run/secret-dirs");
+        let busy = AtomicBool::new(false);
+        let request = || async {
+            assert!(!busy.swap(true, Ordering::SeqCst));
+            tokio::task::yield_now().await;
+            busy.store(false, Ordering::SeqCst);
+        };
+        tokio::join!(
+            dirs.with_lock("app", request()),
+            dirs.with_lock("app", request())
+        );
+        assert!(dirs.busy.lock().await.is_empty());
+    }
+
+    #[test]
+    fn test_mount() {
+        assert_eq!(
+            tmpfs_options(4096, 1000, 1001),
+            "size=4096,mode=0500,uid=1000,gid=1001,nosuid,nodev,noexec"
+        );
+
+        let mount = bind_mount(Path::new("/run/secret-dirs/app"), "/run/secrets");
+        assert_eq!(mount.source, "/run/secret-dirs/app");
+        assert_eq!(mount.destination, "/run/secrets");
+        assert_eq!(mount.options, ["rbind", "ro", "nosuid", "nodev", "noexec"]);
+    }
+}
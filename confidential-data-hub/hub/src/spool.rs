@@ -0,0 +1,535 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Spool of the resources retrieved in chunks.
+//!
+//! `GetResource` returns a resource in a single message, which both the
+//! client and the ttRPC message size limit choke on for resources of
+//! hundreds of MB (model weights, big CA bundles, ...). Such resources are
+//! fetched once into a file of the spool dir, and then served in chunks by
+//! offset with `GetResourceChunk`, or copied to a target path with
+//! `FetchResourceToFile`. A client resumes an interrupted retrieval from
+//! the offset it got to, passing the digest of the first chunk so that it
+//! does not mix up two versions of the resource. Spooled resources that
+//! are not retrieved for [`DEFAULT_IDLE_SECS`] are removed.
+//!
+//! A resource is written to the spool as it is received, see
+//! [`crate::resource::ResourceProvider::write_resource`], and the spool is
+//! only locked to [`ResourceSpool::reserve`] its file and to
+//! [`ResourceSpool::commit`] it, not while it is fetched.
+//!
+//! `FetchResourceToFile` only writes under [`DEFAULT_FETCH_TARGET_BASE`],
+//! or the base CDH is configured with, see [`open_target_dir`].
+
+use std::{
+    collections::HashMap,
+    ffi::{CString, OsStr, OsString},
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt},
+        io::{AsRawFd, FromRawFd},
+    },
+    path::{Component, Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use sha2::{Digest, Sha256};
+
+/// Default dir of the spooled resources.
+pub const DEFAULT_SPOOL_DIR: &str = "/run/confidential-containers/cdh/spool";
+
+/// Default dir the targets of `FetchResourceToFile` must be under.
+pub const DEFAULT_FETCH_TARGET_BASE: &str = "/run/confidential-containers/cdh/resources";
+
+/// Default time in seconds after which a spooled resource that is not
+/// retrieved is removed.
+pub const DEFAULT_IDLE_SECS: u64 = 300;
+
+/// Size of a chunk if the request does not give one.
+pub const DEFAULT_CHUNK_SIZE: u32 = 1024 * 1024;
+
+/// Max size of a chunk, well below the 4 MiB message limit of ttRPC.
+pub const MAX_CHUNK_SIZE: u32 = 2 * 1024 * 1024;
+
+/// Size and digest of a resource.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceInfo {
+    pub size: u64,
+
+    /// `sha256:<hex>` of the resource.
+    pub digest: String,
+}
+
+/// A chunk of a resource.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ResourceChunk {
+    pub data: Vec<u8>,
+
+    /// The whole resource the chunk is of.
+    pub info: ResourceInfo,
+}
+
+/// `sha256:<hex>` of `content`.
+pub fn resource_digest(content: &[u8]) -> String {
+    let hex: String = Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!("sha256:{hex}")
+}
+
+/// Check that `info` has the `expected` digest, if any.
+pub fn check_digest(info: &ResourceInfo, expected: Option<&str>) -> Result<(), String> {
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(&info.digest) => Err(format!(
+            "resource digest is {}, expected {expected}",
+            info.digest
+        )),
+        _ => Ok(()),
+    }
+}
+
+struct SpoolEntry {
+    path: PathBuf,
+    info: ResourceInfo,
+    last_used: Instant,
+}
+
+/// A resource in the spool, opened so that it can be read even if it is
+/// removed from the spool meanwhile.
+pub struct SpooledResource {
+    file: File,
+    pub info: ResourceInfo,
+}
+
+impl SpooledResource {
+    /// Read at most `length` bytes at `offset`, [`DEFAULT_CHUNK_SIZE`] if
+    /// `length` is `0`. The chunk is empty at the end of the resource.
+    pub fn read_chunk(&mut self, offset: u64, length: u32) -> io::Result<Vec<u8>> {
+        if offset > self.info.size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "offset {offset} beyond the resource of {} bytes",
+                    self.info.size
+                ),
+            ));
+        }
+
+        let length = match length {
+            0 => DEFAULT_CHUNK_SIZE,
+            length => length.min(MAX_CHUNK_SIZE),
+        };
+        let length = (length as u64).min(self.info.size - offset);
+        let mut data = vec![0; length as usize];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// Copy the resource to the file `name` of the dir `dir`, see
+    /// [`open_target_dir`], with `mode`. The copy is written next to the
+    /// target with `O_NOFOLLOW` and renamed to it once synced, so that the
+    /// target is never seen incomplete.
+    pub fn copy_to(&mut self, dir: &File, name: &OsStr, mode: u32) -> io::Result<()> {
+        let target = CString::new(name.as_bytes())?;
+        let tmp = CString::new(format!(".{}.cdh-tmp", name.to_string_lossy()))?;
+
+        let res = (|| {
+            let flags =
+                libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+            // SAFETY: `tmp` is a valid C string and `dir` an open fd.
+            let fd = unsafe { libc::openat(dir.as_raw_fd(), tmp.as_ptr(), flags, mode) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: `fd` was just opened and is owned by nothing else.
+            let mut file = unsafe { File::from_raw_fd(fd) };
+            // the mode given at creation is masked by the umask
+            file.set_permissions(fs::Permissions::from_mode(mode))?;
+            self.file.seek(SeekFrom::Start(0))?;
+            let copied = io::copy(&mut self.file, &mut file)?;
+            if copied != self.info.size {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("copied {copied} of {} bytes", self.info.size),
+                ));
+            }
+            file.sync_all()?;
+
+            // the rename replaces a symlink at the target rather than following it
+            // SAFETY: the names are valid C strings and `dir` an open fd.
+            let renamed = unsafe {
+                libc::renameat(
+                    dir.as_raw_fd(),
+                    tmp.as_ptr(),
+                    dir.as_raw_fd(),
+                    target.as_ptr(),
+                )
+            };
+            if renamed != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(())
+        })();
+
+        if res.is_err() {
+            // SAFETY: as above
+            unsafe { libc::unlinkat(dir.as_raw_fd(), tmp.as_ptr(), 0) };
+        }
+
+        res
+    }
+}
+
+/// Open the parent dir of the absolute `target`, which must be below
+/// `base`, and return it with the name of the file. The dirs below `base`
+/// are opened with `O_NOFOLLOW`, and created if missing, so that a symlink
+/// cannot redirect the target out of `base`.
+pub fn open_target_dir(base: &Path, target: &Path) -> io::Result<(File, OsString)> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "target {} is not a file under {}",
+                target.display(),
+                base.display()
+            ),
+        )
+    };
+    let relative = target.strip_prefix(base).map_err(|_| invalid())?;
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return Err(invalid());
+    }
+    let name = relative.file_name().ok_or_else(invalid)?.to_os_string();
+
+    fs::DirBuilder::new().recursive(true).create(base)?;
+    let mut dir = File::open(base)?;
+    if let Some(parent) = relative.parent() {
+        for component in parent.components() {
+            dir = crate::inject::open_dir(&dir, component.as_os_str())?;
+        }
+    }
+
+    Ok((dir, name))
+}
+
+/// A resource being written to the spool, see [`ResourceSpool::reserve`].
+/// Its file is removed if it is dropped before it is committed.
+pub struct PendingResource {
+    uri: String,
+    path: Option<PathBuf>,
+    file: File,
+    generation: u64,
+}
+
+impl Write for PendingResource {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for PendingResource {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// The spooled resources by their URI.
+pub struct ResourceSpool {
+    dir: PathBuf,
+    idle: Duration,
+    entries: HashMap<String, SpoolEntry>,
+
+    /// Number of the next file of the spool.
+    next_file: u64,
+
+    /// Bumped by [`Self::invalidate`], so that the resources fetched before
+    /// are not committed to the spool.
+    generation: u64,
+}
+
+impl ResourceSpool {
+    /// Spool under `dir`, which is emptied of the resources left by a
+    /// previous run.
+    pub fn new(dir: impl Into<PathBuf>, idle_secs: u64) -> Self {
+        let dir = dir.into();
+        let _ = fs::remove_dir_all(&dir);
+        Self {
+            dir,
+            idle: Duration::from_secs(idle_secs),
+            entries: HashMap::new(),
+            next_file: 0,
+            generation: 0,
+        }
+    }
+
+    /// Open the spooled resource of `uri`, if any.
+    pub fn get(&mut self, uri: &str) -> Option<SpooledResource> {
+        let now = Instant::now();
+        self.sweep(now);
+
+        let entry = self.entries.get_mut(uri)?;
+        entry.last_used = now;
+        match File::open(&entry.path) {
+            Ok(file) => Some(SpooledResource {
+                file,
+                info: entry.info.clone(),
+            }),
+            Err(_) => {
+                self.remove(uri);
+                None
+            }
+        }
+    }
+
+    /// Create the file of the resource of `uri` to be written, without
+    /// holding the spool meanwhile.
+    pub fn reserve(&mut self, uri: &str) -> io::Result<PendingResource> {
+        self.sweep(Instant::now());
+
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&self.dir)?;
+        let path = self.dir.join(format!(
+            "{}.{}",
+            &resource_digest(uri.as_bytes())["sha256:".len()..],
+            self.next_file
+        ));
+        self.next_file += 1;
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)?;
+
+        Ok(PendingResource {
+            uri: uri.to_string(),
+            path: Some(path),
+            file,
+            generation: self.generation,
+        })
+    }
+
+    /// Spool the `pending` resource once written, replacing the spooled one
+    /// of its URI if any. It is only read, and not spooled, if the spool
+    /// was invalidated since it was reserved.
+    pub fn commit(&mut self, mut pending: PendingResource) -> io::Result<SpooledResource> {
+        let mut file = pending.file.try_clone()?;
+        file.seek(SeekFrom::Start(0))?;
+        let mut hasher = Sha256::new();
+        let size = io::copy(&mut file, &mut hasher)?;
+        let hex: String = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        let info = ResourceInfo {
+            size,
+            digest: format!("sha256:{hex}"),
+        };
+
+        if pending.generation == self.generation {
+            self.remove(&pending.uri);
+            let path = pending.path.take().expect("only taken once committed");
+            self.entries.insert(
+                pending.uri.clone(),
+                SpoolEntry {
+                    path,
+                    info: info.clone(),
+                    last_used: Instant::now(),
+                },
+            );
+        }
+
+        Ok(SpooledResource { file, info })
+    }
+
+    /// Spool the `resource` of `uri`, replacing the spooled one if any.
+    pub fn insert(&mut self, uri: &str, resource: &[u8]) -> io::Result<SpooledResource> {
+        let mut pending = self.reserve(uri)?;
+        pending.write_all(resource)?;
+        self.commit(pending)
+    }
+
+    /// Remove the spooled resource of `uri`, or all of them if `uri` is
+    /// `None`. Returns the number of removed resources.
+    pub fn invalidate(&mut self, uri: Option<&str>) -> usize {
+        self.generation += 1;
+        match uri {
+            Some(uri) => self.remove(uri) as usize,
+            None => {
+                let uris: Vec<String> = self.entries.keys().cloned().collect();
+                uris.iter().filter(|uri| self.remove(uri)).count()
+            }
+        }
+    }
+
+    fn sweep(&mut self, now: Instant) {
+        let idle: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| now.duration_since(entry.last_used) >= self.idle)
+            .map(|(uri, _)| uri.clone())
+            .collect();
+        for uri in idle {
+            self.remove(&uri);
+        }
+    }
+
+    fn remove(&mut self, uri: &str) -> bool {
+        match self.entries.remove(uri) {
+            Some(entry) => {
+                let _ = fs::remove_file(entry.path);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spool_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = ResourceSpool::new(dir.path().join("spool"), DEFAULT_IDLE_SECS);
+        assert!(spool.get("kbs:///a/b/c").is_none());
+
+        let resource: Vec<u8> = (0..=255).cycle().take(3000).collect();
+        let spooled = spool.insert("kbs:///a/b/c", &resource).unwrap();
+        assert_eq!(spooled.info.size, 3000);
+        assert_eq!(spooled.info.digest, resource_digest(&resource));
+
+        let mut spooled = spool.get("kbs:///a/b/c").unwrap();
+        let mut retrieved = Vec::new();
+        loop {
+            let chunk = spooled.read_chunk(retrieved.len() as u64, 1024).unwrap();
+            if chunk.is_empty() {
+                break;
+            }
+            retrieved.extend(chunk);
+        }
+        assert_eq!(retrieved, resource);
+        assert_eq!(spooled.read_chunk(0, 0).unwrap(), resource);
+        assert!(spooled.read_chunk(3001, 0).is_err());
+
+        // an opened resource can still be read once removed from the spool
+        assert_eq!(spool.invalidate(None), 1);
+        assert!(spool.get("kbs:///a/b/c").is_none());
+        assert_eq!(spooled.read_chunk(2990, 0).unwrap(), &resource[2990..]);
+        assert_eq!(fs::read_dir(dir.path().join("spool")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_spool_idle() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = ResourceSpool::new(dir.path(), 0);
+        spool.insert("kbs:///a/b/c", b"resource").unwrap();
+        assert!(spool.get("kbs:///a/b/c").is_none());
+        assert_eq!(spool.invalidate(Some("kbs:///a/b/c")), 0);
+    }
+
+    #[test]
+    fn test_spool_pending() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = ResourceSpool::new(dir.path(), DEFAULT_IDLE_SECS);
+
+        // dropped before it is committed
+        let mut pending = spool.reserve("kbs:///a/b/c").unwrap();
+        pending.write_all(b"partial").unwrap();
+        drop(pending);
+        assert!(spool.get("kbs:///a/b/c").is_none());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let mut pending = spool.reserve("kbs:///a/b/c").unwrap();
+        pending.write_all(b"resource").unwrap();
+        let spooled = spool.commit(pending).unwrap();
+        assert_eq!(spooled.info.digest, resource_digest(b"resource"));
+        assert_eq!(spool.get("kbs:///a/b/c").unwrap().info, spooled.info);
+
+        // invalidated while it was fetched
+        let mut pending = spool.reserve("kbs:///a/b/c").unwrap();
+        assert_eq!(spool.invalidate(None), 1);
+        pending.write_all(b"stale").unwrap();
+        let mut spooled = spool.commit(pending).unwrap();
+        assert_eq!(spooled.read_chunk(0, 0).unwrap(), b"stale");
+        assert!(spool.get("kbs:///a/b/c").is_none());
+        drop(spooled);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_spool_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut spool = ResourceSpool::new(dir.path().join("spool"), DEFAULT_IDLE_SECS);
+        let mut spooled = spool.insert("kbs:///a/b/c", b"resource").unwrap();
+
+        let base = dir.path().join("resources");
+        let target = base.join("models/model.bin");
+        let (target_dir, name) = open_target_dir(&base, &target).unwrap();
+        fs::write(&target, b"old").unwrap();
+        spooled.copy_to(&target_dir, &name, 0o640).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"resource");
+        assert_eq!(
+            fs::metadata(&target).unwrap().permissions().mode() & 0o7777,
+            0o640
+        );
+        assert!(!base.join("models/.model.bin.cdh-tmp").exists());
+
+        // a symlink at the target is replaced, not followed
+        let outside = dir.path().join("passwd");
+        fs::write(&outside, b"root").unwrap();
+        fs::remove_file(&target).unwrap();
+        std::os::unix::fs::symlink(&outside, &target).unwrap();
+        spooled.copy_to(&target_dir, &name, 0o600).unwrap();
+        assert_eq!(fs::read(&outside).unwrap(), b"root");
+        assert!(!fs::symlink_metadata(&target).unwrap().is_symlink());
+    }
+
+    #[test]
+    fn test_open_target_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("resources");
+        let (_, name) = open_target_dir(&base, &base.join("a/b/model.bin")).unwrap();
+        assert_eq!(name, "model.bin");
+        assert!(base.join("a/b").is_dir());
+
+        assert!(open_target_dir(&base, Path::new("/etc/passwd")).is_err());
+        assert!(open_target_dir(&base, &base.join("../passwd")).is_err());
+        assert!(open_target_dir(&base, &base).is_err());
+
+        // a symlink below the base is not followed
+        std::os::unix::fs::symlink("/etc", base.join("etc")).unwrap();
+        assert!(open_target_dir(&base, &base.join("etc/passwd")).is_err());
+    }
+
+    #[test]
+    fn test_check_digest() {
+        let info = ResourceInfo {
+            size: 8,
+            digest: resource_digest(b"resource"),
+        };
+        assert!(check_digest(&info, None).is_ok());
+        assert!(check_digest(&info, Some(&info.digest.to_uppercase())).is_ok());
+        assert!(check_digest(&info, Some(&resource_digest(b"other"))).is_err());
+    }
+}
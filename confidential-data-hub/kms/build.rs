@@ -14,5 +14,25 @@ fn main() -> Result<()> {
         .out_dir("./src/plugins/kbs/sev")
         .compile(&["./src/plugins/kbs/sev/protos/getsecret.proto"], &[""])?;
 
+    #[cfg(feature = "external")]
+    {
+        ttrpc_codegen::Codegen::new()
+            .out_dir("./src/plugins/external")
+            .include("./src/plugins/external/protos")
+            .inputs(["./src/plugins/external/protos/secret_store.proto"])
+            .rust_protobuf()
+            .customize(ttrpc_codegen::Customize {
+                async_all: true,
+                ..Default::default()
+            })
+            .rust_protobuf_customize(ttrpc_codegen::ProtobufCustomize::default().gen_mod_rs(false))
+            .run()?;
+
+        // Fix clippy warnings of code generated from ttrpc_codegen
+        let client = "./src/plugins/external/secret_store_ttrpc.rs";
+        let code = std::fs::read_to_string(client)?;
+        std::fs::write(client, code.replace("client: client", "client"))?;
+    }
+
     Ok(())
 }
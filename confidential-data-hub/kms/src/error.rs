@@ -20,6 +20,14 @@ pub enum Error {
     #[error("eHSM-KMS client error: {0}")]
     EhsmKmsError(String),
 
+    #[cfg(feature = "sealed")]
+    #[error("Sealed key error: {0}")]
+    SealedKeyError(String),
+
+    #[cfg(feature = "external")]
+    #[error("External secret store error: {0}")]
+    ExternalSecretStoreError(String),
+
     #[error("Unsupported provider: {0}")]
     UnsupportedProvider(String),
 }
@@ -0,0 +1,294 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Secret stores served by external plugins.
+//!
+//! Secret stores without a built-in driver (CyberArk Conjur, in-house
+//! vaults, ...) are served by plugins: processes listening on a unix socket
+//! for ttrpc and implementing the `SecretStoreService` of
+//! [secret_store.proto](protos/secret_store.proto). The plugins are
+//! registered by [`register_plugins`] from the config of CDH, and a vault
+//! secret whose `provider` is the name of a plugin is fetched from it.
+//!
+//! A plugin can get the attestation token of the guest with every request,
+//! if its config says so, to present it to its store.
+
+mod secret_store;
+mod secret_store_ttrpc;
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+use kbs_protocol::token_provider::{AATokenProvider, TokenProvider};
+use serde::{Deserialize, Serialize};
+use ttrpc::context;
+
+use self::{secret_store::GetSecretRequest, secret_store_ttrpc::SecretStoreServiceClient};
+use super::VaultProvider;
+use crate::{Annotations, Error, Getter, Result};
+
+/// Timeout of the requests to a plugin if not configured, in seconds.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// The registered plugins by their name.
+static PLUGINS: RwLock<BTreeMap<String, PluginConfig>> = RwLock::new(BTreeMap::new());
+
+/// Config of the plugins, e.g.
+///
+/// ```json
+/// {
+///     "plugins": [
+///         {
+///             "name": "conjur",
+///             "socket": "unix:///run/confidential-containers/cdh/plugins/conjur.sock",
+///             "forward_token": true
+///         }
+///     ]
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PluginsConfig {
+    #[serde(default)]
+    pub plugins: Vec<PluginConfig>,
+}
+
+/// Config of a plugin.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PluginConfig {
+    /// Name the vault secrets select the plugin by as their `provider`.
+    pub name: String,
+
+    /// ttrpc address of the plugin, `unix://<path>`.
+    pub socket: String,
+
+    /// Send the attestation token of the guest with the requests.
+    #[serde(default)]
+    pub forward_token: bool,
+
+    /// Timeout of the requests, in seconds.
+    ///
+    /// This defaults to [`DEFAULT_TIMEOUT_SECS`].
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+/// Register the plugins of `config`, replacing the ones registered before.
+/// The names must be unique, and must not be the ones of built-in
+/// providers.
+pub fn register_plugins(config: PluginsConfig) -> Result<()> {
+    let mut plugins = BTreeMap::new();
+    for plugin in config.plugins {
+        if VaultProvider::from_str(&plugin.name).is_ok() {
+            return Err(Error::ExternalSecretStoreError(format!(
+                "plugin name {} is taken by a built-in provider",
+                plugin.name
+            )));
+        }
+
+        if !plugin.socket.starts_with("unix://") {
+            return Err(Error::ExternalSecretStoreError(format!(
+                "socket {} of plugin {} is not a unix socket",
+                plugin.socket, plugin.name
+            )));
+        }
+
+        if let Some(plugin) = plugins.insert(plugin.name.clone(), plugin) {
+            return Err(Error::ExternalSecretStoreError(format!(
+                "plugin {} is configured twice",
+                plugin.name
+            )));
+        }
+    }
+
+    *PLUGINS.write().expect("plugins poisoned") = plugins;
+    Ok(())
+}
+
+/// The config of the plugin `name`, if registered.
+pub fn plugin(name: &str) -> Option<PluginConfig> {
+    PLUGINS.read().expect("plugins poisoned").get(name).cloned()
+}
+
+/// Client of a secret store plugin.
+pub struct ExternalClient {
+    config: PluginConfig,
+    client: SecretStoreServiceClient,
+}
+
+impl ExternalClient {
+    pub fn new(config: PluginConfig) -> Result<Self> {
+        let c = ttrpc::r#async::Client::connect(&config.socket).map_err(|e| {
+            Error::ExternalSecretStoreError(format!("connect plugin {} failed: {e}", config.name))
+        })?;
+        let client = SecretStoreServiceClient::new(c);
+        Ok(Self { config, client })
+    }
+}
+
+#[async_trait]
+impl Getter for ExternalClient {
+    async fn get_secret(&mut self, name: &str, annotations: &Annotations) -> Result<Vec<u8>> {
+        let token = match self.config.forward_token {
+            true => {
+                let (token, _) = AATokenProvider::new()
+                    .await
+                    .map_err(|e| {
+                        Error::ExternalSecretStoreError(format!(
+                            "connect attestation-agent failed: {e}"
+                        ))
+                    })?
+                    .get_token()
+                    .await
+                    .map_err(|e| {
+                        Error::ExternalSecretStoreError(format!(
+                            "get attestation token failed: {e}"
+                        ))
+                    })?;
+                token.content
+            }
+            false => String::new(),
+        };
+
+        let req = GetSecretRequest {
+            Name: name.to_string(),
+            Annotations: serde_json::to_string(annotations).map_err(|e| {
+                Error::ExternalSecretStoreError(format!("serialize annotations failed: {e}"))
+            })?,
+            Token: token,
+            ..Default::default()
+        };
+        let timeout = self.config.timeout_secs * 1000 * 1000 * 1000;
+        let res = self
+            .client
+            .get_secret(context::with_timeout(timeout as i64), &req)
+            .await
+            .map_err(|e| {
+                Error::ExternalSecretStoreError(format!(
+                    "plugin {} failed to get secret {name}: {e}",
+                    self.config.name
+                ))
+            })?;
+        Ok(res.Secret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ttrpc::r#async::TtrpcContext;
+
+    use super::secret_store::GetSecretResponse;
+    use super::secret_store_ttrpc::{create_secret_store_service, SecretStoreService};
+    use super::*;
+
+    // A plugin returning `<Name>:<Annotations>:<Token>` as the secret, and
+    // NOT_FOUND for the secret `missing`.
+    struct MockPlugin;
+
+    #[async_trait]
+    impl SecretStoreService for MockPlugin {
+        async fn get_secret(
+            &self,
+            _ctx: &TtrpcContext,
+            req: GetSecretRequest,
+        ) -> ttrpc::Result<GetSecretResponse> {
+            if req.Name == "missing" {
+                return Err(ttrpc::Error::RpcStatus(ttrpc::get_status(
+                    ttrpc::Code::NOT_FOUND,
+                    "no such secret".to_string(),
+                )));
+            }
+
+            Ok(GetSecretResponse {
+                Secret: format!("{}:{}:{}", req.Name, req.Annotations, req.Token).into_bytes(),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_secret() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = format!("unix://{}", dir.path().join("plugin.sock").display());
+        let service = Box::new(MockPlugin) as Box<dyn SecretStoreService + Send + Sync>;
+        let mut server = ttrpc::r#async::Server::new()
+            .bind(&socket)
+            .unwrap()
+            .register_service(create_secret_store_service(Arc::new(service)));
+        server.start().await.unwrap();
+
+        let mut client = ExternalClient::new(PluginConfig {
+            name: "mock".into(),
+            socket,
+            forward_token: false,
+            timeout_secs: 5,
+        })
+        .unwrap();
+        let mut annotations = Annotations::new();
+        annotations.insert("path".into(), "db/password".into());
+        let secret = client.get_secret("db", &annotations).await.unwrap();
+        assert_eq!(secret, br#"db:{"path":"db/password"}:"#);
+
+        let e = client
+            .get_secret("missing", &annotations)
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("no such secret"), "{e}");
+
+        server.shutdown().await.unwrap();
+    }
+
+    #[test]
+    fn test_register_plugins() {
+        let config: PluginsConfig = serde_json::from_str(
+            r#"{
+                "plugins": [
+                    {
+                        "name": "conjur",
+                        "socket": "unix:///run/conjur.sock",
+                        "forward_token": true
+                    },
+                    {
+                        "name": "in-house",
+                        "socket": "unix:///run/in-house.sock",
+                        "timeout_secs": 5
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.plugins[0].timeout_secs, DEFAULT_TIMEOUT_SECS);
+        assert!(!config.plugins[1].forward_token);
+
+        register_plugins(config.clone()).unwrap();
+        assert_eq!(plugin("conjur"), Some(config.plugins[0].clone()));
+        assert_eq!(plugin("in-house").unwrap().timeout_secs, 5);
+        assert_eq!(plugin("vault"), None);
+
+        let mut taken = config.clone();
+        taken.plugins[1].name = "kbs".into();
+        assert!(register_plugins(taken).is_err());
+
+        let mut twice = config.clone();
+        twice.plugins[1].name = "conjur".into();
+        assert!(register_plugins(twice).is_err());
+
+        let mut tcp = config;
+        tcp.plugins[0].socket = "tcp://127.0.0.1:8080".into();
+        assert!(register_plugins(tcp).is_err());
+
+        // failed registrations keep the plugins registered before
+        assert!(plugin("in-house").is_some());
+        register_plugins(PluginsConfig::default()).unwrap();
+        assert_eq!(plugin("conjur"), None);
+    }
+}
@@ -0,0 +1,345 @@
+// This file is generated by rust-protobuf 3.7.2. Do not edit
+// .proto file is parsed by pure
+// @generated
+
+// https://github.com/rust-lang/rust-clippy/issues/702
+#![allow(unknown_lints)]
+#![allow(clippy::all)]
+
+#![allow(unused_attributes)]
+#![cfg_attr(rustfmt, rustfmt::skip)]
+
+#![allow(dead_code)]
+#![allow(missing_docs)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(trivial_casts)]
+#![allow(unused_results)]
+#![allow(unused_mut)]
+
+//! Generated file from `secret_store.proto`
+
+/// Generated files are compatible only with the same version
+/// of protobuf runtime.
+const _PROTOBUF_VERSION_CHECK: () = ::protobuf::VERSION_3_7_2;
+
+// @@protoc_insertion_point(message:secret_store.GetSecretRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GetSecretRequest {
+    // message fields
+    // @@protoc_insertion_point(field:secret_store.GetSecretRequest.Name)
+    pub Name: ::std::string::String,
+    // @@protoc_insertion_point(field:secret_store.GetSecretRequest.Annotations)
+    pub Annotations: ::std::string::String,
+    // @@protoc_insertion_point(field:secret_store.GetSecretRequest.Token)
+    pub Token: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:secret_store.GetSecretRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GetSecretRequest {
+    fn default() -> &'a GetSecretRequest {
+        <GetSecretRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GetSecretRequest {
+    pub fn new() -> GetSecretRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(3);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Name",
+            |m: &GetSecretRequest| { &m.Name },
+            |m: &mut GetSecretRequest| { &mut m.Name },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Annotations",
+            |m: &GetSecretRequest| { &m.Annotations },
+            |m: &mut GetSecretRequest| { &mut m.Annotations },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Token",
+            |m: &GetSecretRequest| { &m.Token },
+            |m: &mut GetSecretRequest| { &mut m.Token },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetSecretRequest>(
+            "GetSecretRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GetSecretRequest {
+    const NAME: &'static str = "GetSecretRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.Name = is.read_string()?;
+                },
+                18 => {
+                    self.Annotations = is.read_string()?;
+                },
+                26 => {
+                    self.Token = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.Name.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.Name);
+        }
+        if !self.Annotations.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.Annotations);
+        }
+        if !self.Token.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.Token);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.Name.is_empty() {
+            os.write_string(1, &self.Name)?;
+        }
+        if !self.Annotations.is_empty() {
+            os.write_string(2, &self.Annotations)?;
+        }
+        if !self.Token.is_empty() {
+            os.write_string(3, &self.Token)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GetSecretRequest {
+        GetSecretRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.Name.clear();
+        self.Annotations.clear();
+        self.Token.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GetSecretRequest {
+        static instance: GetSecretRequest = GetSecretRequest {
+            Name: ::std::string::String::new(),
+            Annotations: ::std::string::String::new(),
+            Token: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for GetSecretRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GetSecretRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GetSecretRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GetSecretRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:secret_store.GetSecretResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GetSecretResponse {
+    // message fields
+    // @@protoc_insertion_point(field:secret_store.GetSecretResponse.Secret)
+    pub Secret: ::std::vec::Vec<u8>,
+    // special fields
+    // @@protoc_insertion_point(special_field:secret_store.GetSecretResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GetSecretResponse {
+    fn default() -> &'a GetSecretResponse {
+        <GetSecretResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GetSecretResponse {
+    pub fn new() -> GetSecretResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Secret",
+            |m: &GetSecretResponse| { &m.Secret },
+            |m: &mut GetSecretResponse| { &mut m.Secret },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetSecretResponse>(
+            "GetSecretResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GetSecretResponse {
+    const NAME: &'static str = "GetSecretResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.Secret = is.read_bytes()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.Secret.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(1, &self.Secret);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.Secret.is_empty() {
+            os.write_bytes(1, &self.Secret)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GetSecretResponse {
+        GetSecretResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.Secret.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GetSecretResponse {
+        static instance: GetSecretResponse = GetSecretResponse {
+            Secret: ::std::vec::Vec::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for GetSecretResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GetSecretResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GetSecretResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GetSecretResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+static file_descriptor_proto_data: &'static [u8] = b"\
+    \n\x12secret_store.proto\x12\x0csecret_store\"^\n\x10GetSecretRequest\
+    \x12\x12\n\x04Name\x18\x01\x20\x01(\tR\x04Name\x12\x20\n\x0bAnnotations\
+    \x18\x02\x20\x01(\tR\x0bAnnotations\x12\x14\n\x05Token\x18\x03\x20\x01(\
+    \tR\x05Token\"+\n\x11GetSecretResponse\x12\x16\n\x06Secret\x18\x01\x20\
+    \x01(\x0cR\x06Secret2b\n\x12SecretStoreService\x12L\n\tGetSecret\x12\x1e\
+    .secret_store.GetSecretRequest\x1a\x1f.secret_store.GetSecretResponseb\
+    \x06proto3\
+";
+
+/// `FileDescriptorProto` object which was a source for this generated file
+fn file_descriptor_proto() -> &'static ::protobuf::descriptor::FileDescriptorProto {
+    static file_descriptor_proto_lazy: ::protobuf::rt::Lazy<::protobuf::descriptor::FileDescriptorProto> = ::protobuf::rt::Lazy::new();
+    file_descriptor_proto_lazy.get(|| {
+        ::protobuf::Message::parse_from_bytes(file_descriptor_proto_data).unwrap()
+    })
+}
+
+/// `FileDescriptor` object which allows dynamic access to files
+pub fn file_descriptor() -> &'static ::protobuf::reflect::FileDescriptor {
+    static generated_file_descriptor_lazy: ::protobuf::rt::Lazy<::protobuf::reflect::GeneratedFileDescriptor> = ::protobuf::rt::Lazy::new();
+    static file_descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::FileDescriptor> = ::protobuf::rt::Lazy::new();
+    file_descriptor.get(|| {
+        let generated_file_descriptor = generated_file_descriptor_lazy.get(|| {
+            let mut deps = ::std::vec::Vec::with_capacity(0);
+            let mut messages = ::std::vec::Vec::with_capacity(2);
+            messages.push(GetSecretRequest::generated_message_descriptor_data());
+            messages.push(GetSecretResponse::generated_message_descriptor_data());
+            let mut enums = ::std::vec::Vec::with_capacity(0);
+            ::protobuf::reflect::GeneratedFileDescriptor::new_generated(
+                file_descriptor_proto(),
+                deps,
+                messages,
+                enums,
+            )
+        });
+        ::protobuf::reflect::FileDescriptor::new_generated_2(generated_file_descriptor)
+    })
+}
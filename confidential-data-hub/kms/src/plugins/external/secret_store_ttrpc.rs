@@ -0,0 +1,69 @@
+// This file is generated by ttrpc-compiler 0.6.2. Do not edit
+// @generated
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+#![allow(unknown_lints)]
+#![allow(clipto_camel_casepy)]
+#![allow(box_pointers)]
+#![allow(dead_code)]
+#![allow(missing_docs)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(trivial_casts)]
+#![allow(unsafe_code)]
+#![allow(unused_imports)]
+#![allow(unused_results)]
+#![allow(clippy::all)]
+use protobuf::{CodedInputStream, CodedOutputStream, Message};
+use std::collections::HashMap;
+use std::sync::Arc;
+use async_trait::async_trait;
+
+#[derive(Clone)]
+pub struct SecretStoreServiceClient {
+    client: ::ttrpc::r#async::Client,
+}
+
+impl SecretStoreServiceClient {
+    pub fn new(client: ::ttrpc::r#async::Client) -> Self {
+        SecretStoreServiceClient {
+            client,
+        }
+    }
+
+    pub async fn get_secret(&self, ctx: ttrpc::context::Context, req: &super::secret_store::GetSecretRequest) -> ::ttrpc::Result<super::secret_store::GetSecretResponse> {
+        let mut cres = super::secret_store::GetSecretResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "secret_store.SecretStoreService", "GetSecret", cres);
+    }
+}
+
+struct GetSecretMethod {
+    service: Arc<Box<dyn SecretStoreService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for GetSecretMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, secret_store, GetSecretRequest, get_secret);
+    }
+}
+
+#[async_trait]
+pub trait SecretStoreService: Sync {
+    async fn get_secret(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::secret_store::GetSecretRequest) -> ::ttrpc::Result<super::secret_store::GetSecretResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/secret_store.SecretStoreService/GetSecret is not supported".to_string())))
+    }
+}
+
+pub fn create_secret_store_service(service: Arc<Box<dyn SecretStoreService + Send + Sync>>) -> HashMap<String, ::ttrpc::r#async::Service> {
+    let mut ret = HashMap::new();
+    let mut methods = HashMap::new();
+    let streams = HashMap::new();
+
+    methods.insert("GetSecret".to_string(),
+                    Box::new(GetSecretMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    ret.insert("secret_store.SecretStoreService".to_string(), ::ttrpc::r#async::Service{ methods, streams });
+    ret
+}
@@ -41,12 +41,7 @@ enum RealClient {
 
 impl RealClient {
     async fn new() -> Result<Self> {
-        // Check for /run/peerpod/daemon.json to see if we are in a peer pod
-        // If so we need to read from the agent-config file, not /proc/cmdline
-        let (kbc, _kbs_host) = match Path::new(PEER_POD_CONFIG_PATH).exists() {
-            true => get_aa_params_from_config_file().await?,
-            false => get_aa_params_from_cmdline().await?,
-        };
+        let (kbc, _kbs_host) = get_aa_params().await?;
 
         let c = match &kbc[..] {
             #[cfg(feature = "kbs")]
@@ -117,6 +112,21 @@ impl KbcClient {
     }
 }
 
+/// The URL of the KBS the KBC talks to, as given by `aa_kbc_params`.
+pub async fn kbs_host_url() -> Result<String> {
+    let (_, kbs_host) = get_aa_params().await?;
+    Ok(kbs_host)
+}
+
+async fn get_aa_params() -> Result<(String, String)> {
+    // Check for /run/peerpod/daemon.json to see if we are in a peer pod
+    // If so we need to read from the agent-config file, not /proc/cmdline
+    match Path::new(PEER_POD_CONFIG_PATH).exists() {
+        true => get_aa_params_from_config_file().await,
+        false => get_aa_params_from_cmdline().await,
+    }
+}
+
 async fn get_aa_params_from_cmdline() -> Result<(String, String)> {
     use tokio::fs;
     debug!("get aa_kbc_params from kernel cmdline");
@@ -19,6 +19,12 @@ pub mod kbs;
 #[cfg(feature = "ehsm")]
 pub mod ehsm;
 
+#[cfg(feature = "sealed")]
+pub mod sealed;
+
+#[cfg(feature = "external")]
+pub mod external;
+
 #[derive(AsRefStr, EnumString)]
 pub enum DecryptorProvider {
     #[cfg(feature = "aliyun")]
@@ -28,6 +34,10 @@ pub enum DecryptorProvider {
     #[strum(ascii_case_insensitive)]
     #[cfg(feature = "ehsm")]
     Ehsm,
+
+    #[strum(ascii_case_insensitive)]
+    #[cfg(feature = "sealed")]
+    Sealed,
 }
 
 /// Create a new [`Decrypter`] by given provider name and [`ProviderSettings`]
@@ -47,6 +57,11 @@ pub async fn new_decryptor(
         DecryptorProvider::Ehsm => Ok(Box::new(
             ehsm::EhsmKmsClient::from_provider_settings(&_provider_settings).await?,
         ) as Box<dyn Decrypter>),
+
+        #[cfg(feature = "sealed")]
+        DecryptorProvider::Sealed => Ok(Box::new(
+            sealed::SealedKmsClient::from_provider_settings(&_provider_settings).await?,
+        ) as Box<dyn Decrypter>),
     }
 }
 
@@ -56,11 +71,18 @@ pub enum VaultProvider {
     Kbs,
 }
 
-/// Create a new [`Getter`] by given provider name and [`ProviderSettings`]
+/// Create a new [`Getter`] by given provider name and [`ProviderSettings`].
+/// The names of the plugins registered by `external::register_plugins`
+/// are providers as well.
 pub async fn new_getter(
     provider_name: &str,
     _provider_settings: ProviderSettings,
 ) -> Result<Box<dyn Getter>> {
+    #[cfg(feature = "external")]
+    if let Some(plugin) = external::plugin(provider_name) {
+        return Ok(Box::new(external::ExternalClient::new(plugin)?) as Box<dyn Getter>);
+    }
+
     let provider = VaultProvider::from_str(provider_name)
         .map_err(|_| Error::UnsupportedProvider(provider_name.to_string()))?;
     match provider {
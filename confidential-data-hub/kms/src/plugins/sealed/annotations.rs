@@ -0,0 +1,144 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// Serialized [`crate::Annotations`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SealedAnnotations {
+    pub iv: String,
+}
+
+/// TEE deriving the sealing key.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SealingBackend {
+    #[default]
+    Snp,
+}
+
+/// Root key the SEV-SNP firmware derives the sealing key from.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RootKey {
+    /// The VCEK, unique to the chip.
+    #[default]
+    Vcek,
+
+    /// The VM root key, shared by the guests provisioned by the same
+    /// migration agent.
+    Vmrk,
+}
+
+/// Field of the guest mixed into the sealing key, so that guests differing
+/// in it derive other keys.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GuestField {
+    Policy,
+    ImageId,
+    FamilyId,
+    Measurement,
+    GuestSvn,
+    TcbVersion,
+}
+
+impl GuestField {
+    /// Bit of the field in the `GUEST_FIELD_SELECT` of the key request.
+    pub fn bit(self) -> u64 {
+        match self {
+            Self::Policy => 1 << 0,
+            Self::ImageId => 1 << 1,
+            Self::FamilyId => 1 << 2,
+            Self::Measurement => 1 << 3,
+            Self::GuestSvn => 1 << 4,
+            Self::TcbVersion => 1 << 5,
+        }
+    }
+}
+
+/// Fields every sealing key is bound to, so that only guests launched with
+/// the same measurement and policy derive it.
+pub const REQUIRED_FIELDS: [GuestField; 2] = [GuestField::Policy, GuestField::Measurement];
+
+/// Serialized [`crate::ProviderSettings`]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SealedProviderSettings {
+    #[serde(default)]
+    pub backend: SealingBackend,
+
+    #[serde(default)]
+    pub root_key: RootKey,
+
+    #[serde(default = "default_fields")]
+    pub fields: Vec<GuestField>,
+
+    /// VMPL the key is derived for, which has to be the one of the guest.
+    #[serde(default)]
+    pub vmpl: u32,
+
+    /// Guest SVN mixed in if [`GuestField::GuestSvn`] is selected, not
+    /// greater than the one of the guest.
+    #[serde(default)]
+    pub guest_svn: u32,
+
+    /// TCB version mixed in if [`GuestField::TcbVersion`] is selected, not
+    /// greater than the one of the platform.
+    #[serde(default)]
+    pub tcb_version: u64,
+}
+
+impl Default for SealedProviderSettings {
+    fn default() -> Self {
+        Self {
+            backend: SealingBackend::default(),
+            root_key: RootKey::default(),
+            fields: default_fields(),
+            vmpl: 0,
+            guest_svn: 0,
+            tcb_version: 0,
+        }
+    }
+}
+
+impl SealedProviderSettings {
+    /// `GUEST_FIELD_SELECT` of the key request.
+    pub fn field_select(&self) -> u64 {
+        self.fields.iter().fold(0, |mask, field| mask | field.bit())
+    }
+
+    /// Check that the settings bind the key to the guest at least as much
+    /// as the defaults do, i.e. they select all the [`REQUIRED_FIELDS`], and
+    /// the key is derived for `guest_vmpl`, the VMPL of the guest. The
+    /// settings travel with the sealed data, so weaker ones would have the
+    /// guest accept data sealed by other guests, or by less privileged
+    /// software of the same one.
+    pub fn check(&self, guest_vmpl: u32) -> Result<()> {
+        if let Some(field) = REQUIRED_FIELDS
+            .iter()
+            .find(|field| !self.fields.contains(field))
+        {
+            return Err(Error::SealedKeyError(format!(
+                "the sealing key must be bound to the guest field {field:?}"
+            )));
+        }
+
+        if self.vmpl != guest_vmpl {
+            return Err(Error::SealedKeyError(format!(
+                "the sealing key must be derived for VMPL {guest_vmpl} of the guest, not {}",
+                self.vmpl
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn default_fields() -> Vec<GuestField> {
+    REQUIRED_FIELDS.to_vec()
+}
@@ -0,0 +1,272 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use crypto::WrapType;
+use rand::RngCore;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::{Annotations, Decrypter, Encrypter, ProviderSettings};
+use crate::{Error, Result};
+
+use super::annotations::{RootKey, SealedAnnotations, SealedProviderSettings, SealingBackend};
+
+/// Domain of the keys derived from the sealing key.
+const KEY_CONTEXT: &[u8] = b"coco-cdh-sealed-kms";
+
+/// IV length of A256GCM.
+const IV_LEN: usize = 12;
+
+/// The least privileged VMPL of SEV-SNP.
+const MAX_VMPL: u32 = 3;
+
+pub struct SealedKmsClient {
+    settings: SealedProviderSettings,
+    sealing_key: Zeroizing<Vec<u8>>,
+}
+
+impl SealedKmsClient {
+    /// Derive the sealing key of `settings` from the TEE. Settings binding
+    /// the key less than the defaults are rejected, see
+    /// [`SealedProviderSettings::check`].
+    pub fn new(settings: SealedProviderSettings) -> Result<Self> {
+        let sealing_key = match settings.backend {
+            SealingBackend::Snp => snp_sealing_key(&settings)?,
+        };
+
+        Ok(Self {
+            settings,
+            sealing_key,
+        })
+    }
+
+    /// This new function is used by a in-pod client. The settings are the
+    /// ones the data was encrypted with, see
+    /// [`SealedKmsClient::export_provider_settings`].
+    pub async fn from_provider_settings(provider_settings: &ProviderSettings) -> Result<Self> {
+        let settings: SealedProviderSettings =
+            serde_json::from_value(Value::Object(provider_settings.clone())).map_err(|e| {
+                Error::SealedKeyError(format!("parse provider setting failed: {e}"))
+            })?;
+
+        Self::new(settings)
+    }
+
+    /// Export the [`ProviderSettings`] of the current client. This function is to be used
+    /// in the encryptor side. The [`ProviderSettings`] will be used to initial a client
+    /// in the decryptor side.
+    pub fn export_provider_settings(&self) -> Result<ProviderSettings> {
+        let provider_settings = serde_json::to_value(&self.settings)
+            .map_err(|e| Error::SealedKeyError(format!("serialize ProviderSettings failed: {e}")))?
+            .as_object()
+            .expect("must be an object")
+            .to_owned();
+
+        Ok(provider_settings)
+    }
+}
+
+// get the key derived by the SNP firmware for this guest. The root key
+// is the choice of the settings: the VMRK is given by the migration agent
+// the policy of the guest allows, and both are mixed with the measurement
+// and the policy.
+fn snp_sealing_key(settings: &SealedProviderSettings) -> Result<Zeroizing<Vec<u8>>> {
+    use sev_firmware::firmware::guest::{DerivedKey, Firmware, GuestFieldSelect};
+
+    let mut firmware = Firmware::open()
+        .map_err(|e| Error::SealedKeyError(format!("open SNP guest device failed: {e}")))?;
+
+    // the firmware refuses reports for VMPLs more privileged than the one
+    // of the guest, so the first one it accepts is the guest's
+    let guest_vmpl = (0..=MAX_VMPL)
+        .find(|vmpl| firmware.get_report(None, None, Some(*vmpl)).is_ok())
+        .ok_or_else(|| Error::SealedKeyError("get the VMPL of the guest failed".into()))?;
+    settings.check(guest_vmpl)?;
+
+    let request = DerivedKey::new(
+        settings.root_key == RootKey::Vmrk,
+        GuestFieldSelect(settings.field_select()),
+        settings.vmpl,
+        settings.guest_svn,
+        settings.tcb_version,
+    );
+    let key = firmware
+        .get_derived_key(None, request)
+        .map_err(|e| Error::SealedKeyError(format!("derive SNP sealing key failed: {e}")))?;
+
+    Ok(Zeroizing::new(key.to_vec()))
+}
+
+// the key of `key_id`, so that every key id gets its own
+fn wrapping_key(sealing_key: &[u8], key_id: &str) -> Zeroizing<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    hasher.update(KEY_CONTEXT);
+    hasher.update((key_id.len() as u64).to_be_bytes());
+    hasher.update(key_id);
+    hasher.update(sealing_key);
+    Zeroizing::new(hasher.finalize().to_vec())
+}
+
+// encrypt `data` with the key of `key_id`, returning the ciphertext and
+// the IV
+fn seal(sealing_key: &[u8], key_id: &str, data: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut iv = vec![0; IV_LEN];
+    rand::thread_rng().fill_bytes(&mut iv);
+    let ciphertext = crypto::encrypt(
+        wrapping_key(sealing_key, key_id),
+        data.to_vec(),
+        iv.clone(),
+        WrapType::Aes256Gcm,
+    )
+    .map_err(|e| Error::SealedKeyError(format!("seal data failed: {e}")))?;
+
+    Ok((ciphertext, iv))
+}
+
+fn unseal(sealing_key: &[u8], key_id: &str, ciphertext: &[u8], iv: &[u8]) -> Result<Vec<u8>> {
+    crypto::decrypt(
+        wrapping_key(sealing_key, key_id),
+        ciphertext.to_vec(),
+        iv.to_vec(),
+        WrapType::Aes256Gcm,
+    )
+    .map_err(|e| {
+        Error::SealedKeyError(format!(
+            "unseal data failed, it was not sealed to this TEE: {e}"
+        ))
+    })
+}
+
+#[async_trait]
+impl Encrypter for SealedKmsClient {
+    async fn encrypt(&mut self, data: &[u8], key_id: &str) -> Result<(Vec<u8>, Annotations)> {
+        let (ciphertext, iv) = seal(&self.sealing_key, key_id, data)?;
+        let annotations = SealedAnnotations {
+            iv: STANDARD.encode(iv),
+        };
+
+        let annotations = serde_json::to_value(annotations)
+            .map_err(|e| Error::SealedKeyError(format!("serialize annotations failed: {e}")))?
+            .as_object()
+            .expect("must be an object")
+            .to_owned();
+
+        Ok((ciphertext, annotations))
+    }
+}
+
+#[async_trait]
+impl Decrypter for SealedKmsClient {
+    async fn decrypt(
+        &mut self,
+        ciphertext: &[u8],
+        key_id: &str,
+        annotations: &Annotations,
+    ) -> Result<Vec<u8>> {
+        let annotations: SealedAnnotations =
+            serde_json::from_value(Value::Object(annotations.clone()))
+                .map_err(|e| Error::SealedKeyError(format!("parse annotations failed: {e}")))?;
+        let iv = STANDARD
+            .decode(annotations.iv)
+            .map_err(|e| Error::SealedKeyError(format!("base64 decode iv failed: {e}")))?;
+
+        unseal(&self.sealing_key, key_id, ciphertext, &iv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::plugins::sealed::annotations::REQUIRED_FIELDS;
+    use crate::plugins::sealed::GuestField;
+
+    #[test]
+    fn test_seal() {
+        let sealing_key = [7u8; 32];
+        let (ciphertext, iv) = seal(&sealing_key, "layer-key", b"plaintext").unwrap();
+        assert_eq!(iv.len(), IV_LEN);
+        assert_ne!(ciphertext, b"plaintext");
+        assert_eq!(
+            unseal(&sealing_key, "layer-key", &ciphertext, &iv).unwrap(),
+            b"plaintext"
+        );
+
+        // other TEEs and other key ids derive other keys
+        assert!(unseal(&[8u8; 32], "layer-key", &ciphertext, &iv).is_err());
+        assert!(unseal(&sealing_key, "other-key", &ciphertext, &iv).is_err());
+    }
+
+    #[test]
+    fn test_provider_settings() {
+        let settings: SealedProviderSettings = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(settings, SealedProviderSettings::default());
+        assert_eq!(settings.field_select(), 0b1001);
+
+        let settings: SealedProviderSettings = serde_json::from_value(json!({
+            "backend": "snp",
+            "root_key": "vmrk",
+            "fields": ["measurement", "tcb_version"],
+            "tcb_version": 42,
+        }))
+        .unwrap();
+        assert_eq!(settings.root_key, RootKey::Vmrk);
+        assert_eq!(
+            settings.fields,
+            [GuestField::Measurement, GuestField::TcbVersion]
+        );
+        assert_eq!(settings.field_select(), 0b101000);
+        assert_eq!(settings.tcb_version, 42);
+
+        assert!(
+            serde_json::from_value::<SealedProviderSettings>(json!({"backend": "tpm"})).is_err()
+        );
+        assert!(serde_json::from_value::<SealedProviderSettings>(json!({"vmpll": 1})).is_err());
+    }
+
+    #[test]
+    fn test_check_provider_settings() {
+        let settings = SealedProviderSettings::default();
+        assert_eq!(settings.fields, REQUIRED_FIELDS);
+        settings.check(0).unwrap();
+
+        // a key of another VMPL than the guest's
+        assert!(settings.check(1).is_err());
+        let settings = SealedProviderSettings {
+            vmpl: 2,
+            ..Default::default()
+        };
+        assert!(settings.check(0).is_err());
+        settings.check(2).unwrap();
+
+        // a key not bound to the measurement or the policy
+        for fields in [
+            vec![],
+            vec![GuestField::Measurement],
+            vec![GuestField::Policy, GuestField::TcbVersion],
+        ] {
+            let settings = SealedProviderSettings {
+                fields,
+                ..Default::default()
+            };
+            assert!(settings.check(0).is_err(), "{:?}", settings.fields);
+        }
+
+        let settings = SealedProviderSettings {
+            fields: vec![
+                GuestField::Measurement,
+                GuestField::Policy,
+                GuestField::TcbVersion,
+            ],
+            ..Default::default()
+        };
+        settings.check(0).unwrap();
+    }
+}
@@ -0,0 +1,24 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! This is a KMS implementation sealing keys to the local TEE.
+//!
+//! Data is encrypted with a key derived by the TEE itself, instead of one
+//! held by a remote KMS, so that it can be decrypted without connectivity,
+//! but only by a guest with the same identity on the same platform. On
+//! SEV-SNP the key is derived by the firmware from the VCEK, bound to the
+//! chip, or from the VMRK, shared by all the guests the migration agent
+//! gave it to, mixed with the selected fields of the guest, e.g. its launch
+//! measurement.
+//!
+//! As the sealing key never leaves the TEE, the data has to be encrypted
+//! inside a guest with the same identity, e.g. the layer keys of an image
+//! once during the provisioning of an edge machine.
+
+mod annotations;
+mod client;
+
+pub use annotations::{GuestField, RootKey, SealedProviderSettings, SealingBackend};
+pub use client::SealedKmsClient;
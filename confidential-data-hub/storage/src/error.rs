@@ -12,6 +12,9 @@ pub enum Error {
     #[error("secure mount failed: {0}")]
     SecureMountFailed(String),
 
+    #[error("secure unmount failed: {0}")]
+    SecureUnmountFailed(String),
+
     #[error("file error: {0}")]
     FileError(String),
 
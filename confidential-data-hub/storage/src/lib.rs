@@ -4,6 +4,7 @@
 //
 
 pub mod error;
+pub mod mounts;
 pub mod volume_type;
 
 pub use error::*;
@@ -0,0 +1,413 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Lifecycle of the secure mounts.
+//!
+//! Every secure mount is recorded in a [`MountTable`] together with the
+//! steps that tear it down, i.e. the filesystems to unmount, the device
+//! mapper mappings to close and the ephemeral key files to wipe. This lets
+//! `Unmount` undo a mount once its pod is gone, and `Remount` redo it. The
+//! table is persisted, so that the mounts made before a restart of CDH can
+//! still be torn down.
+//!
+//! The records hold the [`Storage`] requests to remount them, so the table
+//! is only readable by its owner.
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::volume_type::Storage;
+use crate::{Error, Result};
+
+/// Default path of the persisted [`MountTable`].
+pub const MOUNT_TABLE_PATH: &str = "/run/confidential-containers/cdh/secure-mounts.json";
+
+const MOUNT_BIN: &str = "/bin/mount";
+
+pub(crate) const UMOUNT_BIN: &str = "/bin/umount";
+
+/// dm-verity setup binary of cryptsetup
+pub(crate) const VERITYSETUP_BIN: &str = "/usr/sbin/veritysetup";
+
+const CRYPTSETUP_BIN: &str = "/usr/sbin/cryptsetup";
+
+const PROC_MOUNTS: &str = "/proc/self/mounts";
+
+const DEVICE_MAPPER_DIR: &str = "/dev/mapper";
+
+/// A step of the teardown of a secure mount. Steps whose object is already
+/// gone are skipped, so that an interrupted teardown can be retried.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum TeardownStep {
+    /// Unmount the filesystem mounted at `target`.
+    Umount { target: String },
+
+    /// Close the dm-verity mapping `name`.
+    CloseVerity { name: String },
+
+    /// Close the dm-crypt mapping `name`.
+    CloseCrypt { name: String },
+
+    /// Overwrite the key file at `path` with zeroes, then remove it.
+    WipeKey { path: String },
+
+    /// Remove the dir at `path` created by the mount, with its content.
+    RemoveDir { path: String },
+}
+
+impl TeardownStep {
+    async fn run(&self) -> Result<()> {
+        match self {
+            TeardownStep::Umount { target } => {
+                if is_mounted(target).await? {
+                    run(UMOUNT_BIN, &[target], Error::SecureUnmountFailed).await?;
+                }
+            }
+            TeardownStep::CloseVerity { name } => {
+                if mapping_exists(name) {
+                    run(
+                        VERITYSETUP_BIN,
+                        &["close", name],
+                        Error::SecureUnmountFailed,
+                    )
+                    .await?;
+                }
+            }
+            TeardownStep::CloseCrypt { name } => {
+                if mapping_exists(name) {
+                    run(CRYPTSETUP_BIN, &["close", name], Error::SecureUnmountFailed).await?;
+                }
+            }
+            TeardownStep::WipeKey { path } => wipe_file(Path::new(path)).await?,
+            TeardownStep::RemoveDir { path } => match tokio::fs::remove_dir_all(path).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => {
+                    return Err(Error::SecureUnmountFailed(format!(
+                        "remove {path} failed: {e}"
+                    )))
+                }
+                _ => {}
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// Mount a tmpfs with the mount `options` at `target`, e.g. to keep files
+/// in guest memory only.
+pub async fn mount_tmpfs(target: &str, options: &str) -> Result<()> {
+    let args = ["-t", "tmpfs", "-o", options, "tmpfs", target];
+    run(MOUNT_BIN, &args, Error::SecureMountFailed).await
+}
+
+/// Unmount the filesystem mounted at `target`, if any.
+pub async fn unmount(target: &str) -> Result<()> {
+    let target = target.to_string();
+    TeardownStep::Umount { target }.run().await
+}
+
+async fn run(bin: &str, args: &[&str], error: fn(String) -> Error) -> Result<()> {
+    let output = Command::new(bin)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| error(format!("failed to run {bin}: {e}")))?;
+
+    if !output.status.success() {
+        return Err(error(format!(
+            "{bin} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+fn mapping_exists(name: &str) -> bool {
+    Path::new(DEVICE_MAPPER_DIR).join(name).exists()
+}
+
+async fn is_mounted(target: &str) -> Result<bool> {
+    let mounts = tokio::fs::read_to_string(PROC_MOUNTS)
+        .await
+        .map_err(|e| Error::SecureUnmountFailed(format!("read {PROC_MOUNTS} failed: {e}")))?;
+    Ok(mounted_in(&mounts, target))
+}
+
+// Whether `target` is a mount point in the `mounts` table of the format of
+// `/proc/self/mounts`, where whitespaces of paths are octal escaped.
+fn mounted_in(mounts: &str, target: &str) -> bool {
+    let target = target
+        .replace('\\', "\\134")
+        .replace(' ', "\\040")
+        .replace('\t', "\\011")
+        .replace('\n', "\\012");
+    mounts
+        .lines()
+        .filter_map(|line| line.split(' ').nth(1))
+        .any(|mount_point| mount_point == target)
+}
+
+/// Overwrite the file at `path` with zeroes and remove it, so that the key
+/// it holds does not outlive the mount. A missing file is ignored.
+pub(crate) async fn wipe_file(path: &Path) -> Result<()> {
+    let wiped = async {
+        let len = tokio::fs::metadata(path).await?.len();
+        let mut file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+        file.write_all(&vec![0; len as usize]).await?;
+        file.sync_all().await?;
+        tokio::fs::remove_file(path).await
+    }
+    .await;
+
+    match wiped {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(Error::FileError(format!(
+            "wipe {} failed: {e}",
+            path.display()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// A secure mount and how to tear it down.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct MountRecord {
+    /// The request the mount was made with.
+    pub storage: Storage,
+
+    /// The steps left to tear the mount down, in order.
+    pub teardown: Vec<TeardownStep>,
+}
+
+impl MountRecord {
+    /// Run the teardown steps in order. The steps done are removed, so that
+    /// a failed teardown resumes from the failed step.
+    pub async fn teardown(&mut self) -> Result<()> {
+        while let Some(step) = self.teardown.first() {
+            debug!("secure unmount step: {step:?}");
+            step.run().await?;
+            self.teardown.remove(0);
+        }
+
+        Ok(())
+    }
+}
+
+/// The secure mounts made so far, by mount point, persisted at `path`.
+#[derive(Debug)]
+pub struct MountTable {
+    path: PathBuf,
+    mounts: BTreeMap<String, MountRecord>,
+}
+
+impl MountTable {
+    /// Load the table persisted at `path`. A missing file is an empty table.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mounts = match std::fs::read(path) {
+            Ok(content) => serde_json::from_slice(&content).map_err(|e| {
+                Error::FileError(format!("parse mount table {} failed: {e}", path.display()))
+            })?,
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                return Err(Error::FileError(format!(
+                    "read mount table {} failed: {e}",
+                    path.display()
+                )))
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            mounts,
+        })
+    }
+
+    /// Get the record of the secure mount at `mount_point`.
+    pub fn get(&self, mount_point: &str) -> Option<&MountRecord> {
+        self.mounts.get(mount_point)
+    }
+
+    /// Mount `storage` and record it. Returns the mount point.
+    pub async fn mount(&mut self, storage: Storage) -> Result<String> {
+        let mount_point = storage.mount_point.clone();
+        if self.mounts.contains_key(&mount_point) {
+            return Err(Error::SecureMountFailed(format!(
+                "{mount_point} is already a secure mount"
+            )));
+        }
+
+        let teardown = storage.mount().await?;
+        self.mounts
+            .insert(mount_point.clone(), MountRecord { storage, teardown });
+        self.save()?;
+        info!("secure mount recorded: {mount_point}");
+
+        Ok(mount_point)
+    }
+
+    /// Tear the secure mount at `mount_point` down and forget it. If the
+    /// teardown fails, the steps left are kept for a retry.
+    pub async fn unmount(&mut self, mount_point: &str) -> Result<()> {
+        let record = self.mounts.get_mut(mount_point).ok_or_else(|| {
+            Error::SecureUnmountFailed(format!("{mount_point} is not a secure mount"))
+        })?;
+
+        let res = record.teardown().await;
+        if res.is_ok() {
+            self.mounts.remove(mount_point);
+            info!("secure mount torn down: {mount_point}");
+        }
+        self.save()?;
+
+        res
+    }
+
+    /// Tear the secure mount at `mount_point` down and mount it again with
+    /// the request it was made with. Returns the mount point.
+    pub async fn remount(&mut self, mount_point: &str) -> Result<String> {
+        let storage = self
+            .get(mount_point)
+            .ok_or_else(|| {
+                Error::SecureMountFailed(format!("{mount_point} is not a secure mount"))
+            })?
+            .storage
+            .clone();
+        self.unmount(mount_point).await?;
+        self.mount(storage).await
+    }
+
+    fn save(&self) -> Result<()> {
+        let save_err = |e: std::io::Error| {
+            Error::FileError(format!(
+                "save mount table {} failed: {e}",
+                self.path.display()
+            ))
+        };
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).map_err(save_err)?;
+        }
+
+        let content = serde_json::to_vec(&self.mounts)
+            .map_err(|e| Error::FileError(format!("serialize mount table failed: {e}")))?;
+        let tmp = self.path.with_extension("tmp");
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp)
+            .map_err(save_err)?;
+        std::io::Write::write_all(&mut file, &content).map_err(save_err)?;
+        file.sync_all().map_err(save_err)?;
+        std::fs::rename(&tmp, &self.path).map_err(save_err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage(mount_point: &str) -> Storage {
+        Storage {
+            driver: "".into(),
+            driver_options: vec![],
+            source: "".into(),
+            fstype: "".into(),
+            options: vec![],
+            mount_point: mount_point.into(),
+        }
+    }
+
+    #[test]
+    fn test_mounted_in() {
+        let mounts = "proc /proc proc rw 0 0\n\
+            /dev/mapper/cdh-verity-1 /run/models\\040a ext4 ro 0 0\n";
+        assert!(mounted_in(mounts, "/proc"));
+        assert!(mounted_in(mounts, "/run/models a"));
+        assert!(!mounted_in(mounts, "/run/models"));
+    }
+
+    #[tokio::test]
+    async fn test_teardown() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path().join("meta");
+        let key = dir.join("passwd");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(&key, "secret").unwrap();
+
+        let mut record = MountRecord {
+            storage: storage("/run/volume"),
+            teardown: vec![
+                TeardownStep::Umount {
+                    target: tempdir.path().join("unmounted").display().to_string(),
+                },
+                TeardownStep::CloseCrypt {
+                    name: "cdh-test-missing".into(),
+                },
+                TeardownStep::WipeKey {
+                    path: key.display().to_string(),
+                },
+                TeardownStep::RemoveDir {
+                    path: dir.display().to_string(),
+                },
+            ],
+        };
+        record.teardown().await.unwrap();
+        assert!(record.teardown.is_empty());
+        assert!(!dir.exists());
+
+        // everything is gone already
+        record.teardown = vec![
+            TeardownStep::WipeKey {
+                path: key.display().to_string(),
+            },
+            TeardownStep::RemoveDir {
+                path: dir.display().to_string(),
+            },
+        ];
+        record.teardown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mount_table() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("cdh/secure-mounts.json");
+        let mut table = MountTable::load(&path).unwrap();
+        assert!(table.get("/run/volume").is_none());
+        assert!(table.unmount("/run/volume").await.is_err());
+        assert!(table.remount("/run/volume").await.is_err());
+
+        // a mount without a supported driver option fails and is not recorded
+        assert!(table.mount(storage("/run/volume")).await.is_err());
+        assert!(table.get("/run/volume").is_none());
+
+        let record = MountRecord {
+            storage: storage("/run/volume"),
+            teardown: vec![TeardownStep::RemoveDir {
+                path: tempdir.path().join("volume-meta").display().to_string(),
+            }],
+        };
+        table.mounts.insert("/run/volume".into(), record.clone());
+        table.save().unwrap();
+
+        // the records survive a restart
+        let mut table = MountTable::load(&path).unwrap();
+        assert_eq!(table.get("/run/volume"), Some(&record));
+        assert!(table.mount(storage("/run/volume")).await.is_err());
+
+        table.unmount("/run/volume").await.unwrap();
+        assert!(table.get("/run/volume").is_none());
+        let table = MountTable::load(&path).unwrap();
+        assert!(table.get("/run/volume").is_none());
+    }
+}
@@ -4,12 +4,13 @@
 //
 
 use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 
-use base64::{engine::general_purpose::STANDARD, Engine};
-use secret::secret::Secret;
 use serde::{Deserialize, Serialize};
 use tokio::{fs, io::AsyncWriteExt, process::Command};
 
+use crate::mounts::{wipe_file, TeardownStep};
+use crate::volume_type::sealed_secret::get_plaintext_secret;
 use crate::{Error, Result};
 
 /// Name of the file that contains ossfs password
@@ -50,48 +51,6 @@ pub struct Oss {
     pub volume_id: String,
 }
 
-async fn unseal_secret(secret: Vec<u8>) -> Result<Vec<u8>> {
-    // TODO: verify the jws signature using the key specified by `kid`
-    // in header. Here we directly get the JWS payload
-    let payload = secret.split(|c| *c == b'.').nth(1).ok_or_else(|| {
-        Error::SecureMountFailed("illegal input sealed secret (not a JWS)".into())
-    })?;
-
-    let secret_json = STANDARD.decode(payload).map_err(|e| {
-        Error::SecureMountFailed(format!(
-            "illegal input sealed secret (JWS body is not standard base64 encoded): {e}"
-        ))
-    })?;
-    let secret: Secret = serde_json::from_slice(&secret_json).map_err(|e| {
-        Error::SecureMountFailed(format!(
-            "illegal input sealed secret format (json deseralization failed): {e}"
-        ))
-    })?;
-
-    let res = secret
-        .unseal()
-        .await
-        .map_err(|e| Error::UnsealSecretFailed(format!("unseal failed: {e}")))?;
-    Ok(res)
-}
-
-async fn get_plaintext_secret(secret: &str) -> Result<String> {
-    if secret.starts_with("sealed.") {
-        let tmp = secret
-            .strip_prefix("sealed.")
-            .ok_or(Error::SecureMountFailed(
-                "strip_prefix \"sealed.\" failed".to_string(),
-            ))?;
-        let unsealed = unseal_secret(tmp.into()).await?;
-
-        return String::from_utf8(unsealed)
-            .map_err(|e| Error::SecureMountFailed(format!("convert to String failed: {e}")));
-    }
-    Err(Error::SecureMountFailed(
-        "sealed secret format error!".to_string(),
-    ))
-}
-
 impl Oss {
     /// Mount the Aliyun OSS storage to the given `mount_point``.
     ///
@@ -101,7 +60,13 @@ impl Oss {
     /// This function will create a temp directory, which is used to mount OSS. Then
     /// use gocryptfs to mount the `mount_point` as plaintext and the temp directory
     /// as ciphertext.
-    pub(crate) async fn mount(&self, _source: String, mount_point: String) -> Result<String> {
+    ///
+    /// The password files are wiped once the FUSE daemons have read them.
+    pub(crate) async fn mount(
+        &self,
+        _source: String,
+        mount_point: String,
+    ) -> Result<Vec<TeardownStep>> {
         // unseal secret
         let plain_ak_id = get_plaintext_secret(&self.ak_id).await?;
         let plain_ak_secret = get_plaintext_secret(&self.ak_secret).await?;
@@ -142,12 +107,22 @@ impl Oss {
             .map(str::to_string)
             .collect();
 
+        let mut teardown = vec![TeardownStep::Umount {
+            target: mount_point.clone(),
+        }];
         if self.encrypted == "gocryptfs" {
-            let gocryptfs_dir = tempfile::tempdir().map_err(|e| {
-                Error::FileError(format!("create gocryptfs mount dir failed: {e:?}"))
-            })?;
-
-            let gocryptfs_dir_path = gocryptfs_dir.path().to_string_lossy().to_string();
+            // kept until the teardown, the ciphertext is mounted there
+            let gocryptfs_dir = tempfile::tempdir()
+                .map_err(|e| Error::FileError(format!("create gocryptfs mount dir failed: {e:?}")))?
+                .into_path();
+
+            let gocryptfs_dir_path = gocryptfs_dir.to_string_lossy().to_string();
+            teardown.push(TeardownStep::Umount {
+                target: gocryptfs_dir_path.clone(),
+            });
+            teardown.push(TeardownStep::RemoveDir {
+                path: gocryptfs_dir_path.clone(),
+            });
             let mut parameters = vec![
                 format!("{}:{}", self.bucket, self.path),
                 gocryptfs_dir_path.clone(),
@@ -185,7 +160,7 @@ impl Oss {
                 gocryptfs_dir_path,
                 mount_point.clone(),
                 "-passfile".to_string(),
-                gocryptfs_passwd_path,
+                gocryptfs_passwd_path.clone(),
                 "-nosyslog".to_string(),
             ];
             Command::new(GOCRYPTFS_BIN)
@@ -193,6 +168,7 @@ impl Oss {
                 .spawn()
                 .map_err(|e| Error::SecureMountFailed(format!("failed to decrypt oss: {e:?}")))?;
             tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+            wipe_file(Path::new(&gocryptfs_passwd_path)).await?;
         } else {
             let mut parameters = vec![
                 format!("{}:{}", self.bucket, self.path),
@@ -208,7 +184,8 @@ impl Oss {
                 .map_err(|e| Error::SecureMountFailed(format!("failed to mount oss: {e:?}")))?;
             tokio::time::sleep(std::time::Duration::from_secs(3)).await;
         };
+        wipe_file(Path::new(&ossfs_passwd_path)).await?;
 
-        Ok(mount_point)
+        Ok(teardown)
     }
 }
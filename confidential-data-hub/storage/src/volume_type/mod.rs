@@ -5,13 +5,21 @@
 
 #[cfg(feature = "aliyun")]
 pub mod alibaba_cloud_oss;
+#[cfg(any(feature = "aliyun", feature = "shared-fs"))]
+mod sealed_secret;
+#[cfg(feature = "shared-fs")]
+pub mod shared_fs;
 
 #[cfg(feature = "aliyun")]
 use self::alibaba_cloud_oss::oss::Oss;
+#[cfg(feature = "shared-fs")]
+use self::shared_fs::integrity::IntegritySharedFs;
+use crate::mounts::TeardownStep;
 use crate::{Error, Result};
 use log::warn;
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct Storage {
     pub driver: String,
     pub driver_options: Vec<String>,
@@ -22,7 +30,9 @@ pub struct Storage {
 }
 
 impl Storage {
-    pub async fn mount(&self) -> Result<String> {
+    /// Mount the storage to its mount point. Returns the steps that tear the
+    /// mount down, see [`crate::mounts`].
+    pub async fn mount(&self) -> Result<Vec<TeardownStep>> {
         for driver_option in &self.driver_options {
             let (volume_type, metadata) =
                 driver_option
@@ -43,6 +53,23 @@ impl Storage {
                         .mount(self.source.clone(), self.mount_point.clone())
                         .await;
                 }
+                #[cfg(feature = "shared-fs")]
+                "integrity-shared-fs" => {
+                    let shared_fs: IntegritySharedFs =
+                        serde_json::from_str(metadata).map_err(|e| {
+                            Error::SecureMountFailed(format!(
+                                "illegal mount info format (json deseralization failed): {e}"
+                            ))
+                        })?;
+                    return shared_fs
+                        .mount(
+                            &self.source,
+                            &self.fstype,
+                            &self.options,
+                            self.mount_point.clone(),
+                        )
+                        .await;
+                }
                 other => {
                     warn!("skip mount info with unsupported volume_type: {other}");
                 }
@@ -0,0 +1,51 @@
+// Copyright (c) 2023 Intel
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use secret::secret::Secret;
+
+use crate::{Error, Result};
+
+async fn unseal_secret(secret: Vec<u8>) -> Result<Vec<u8>> {
+    // TODO: verify the jws signature using the key specified by `kid`
+    // in header. Here we directly get the JWS payload
+    let payload = secret.split(|c| *c == b'.').nth(1).ok_or_else(|| {
+        Error::SecureMountFailed("illegal input sealed secret (not a JWS)".into())
+    })?;
+
+    let secret_json = STANDARD.decode(payload).map_err(|e| {
+        Error::SecureMountFailed(format!(
+            "illegal input sealed secret (JWS body is not standard base64 encoded): {e}"
+        ))
+    })?;
+    let secret: Secret = serde_json::from_slice(&secret_json).map_err(|e| {
+        Error::SecureMountFailed(format!(
+            "illegal input sealed secret format (json deseralization failed): {e}"
+        ))
+    })?;
+
+    let res = secret
+        .unseal()
+        .await
+        .map_err(|e| Error::UnsealSecretFailed(format!("unseal failed: {e}")))?;
+    Ok(res)
+}
+
+pub(crate) async fn get_plaintext_secret(secret: &str) -> Result<String> {
+    if secret.starts_with("sealed.") {
+        let tmp = secret
+            .strip_prefix("sealed.")
+            .ok_or(Error::SecureMountFailed(
+                "strip_prefix \"sealed.\" failed".to_string(),
+            ))?;
+        let unsealed = unseal_secret(tmp.into()).await?;
+
+        return String::from_utf8(unsealed)
+            .map_err(|e| Error::SecureMountFailed(format!("convert to String failed: {e}")));
+    }
+    Err(Error::SecureMountFailed(
+        "sealed secret format error!".to_string(),
+    ))
+}
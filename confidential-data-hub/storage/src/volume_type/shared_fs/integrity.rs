@@ -0,0 +1,348 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::mounts::{TeardownStep, UMOUNT_BIN, VERITYSETUP_BIN};
+use crate::volume_type::sealed_secret::get_plaintext_secret;
+use crate::{Error, Result};
+
+const MOUNT_BIN: &str = "/bin/mount";
+
+/// Filesystems the shared data can be on
+const SHARED_FSTYPES: [&str; 3] = ["virtiofs", "nfs", "nfs4"];
+
+static NEXT_VERITY_DEVICE: AtomicUsize = AtomicUsize::new(0);
+
+/// Integrity layer over the image on the shared filesystem.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Integrity {
+    /// A filesystem image with a dm-verity hash tree.
+    DmVerity,
+
+    /// An integrity-only eccfs image.
+    Eccfs,
+}
+
+fn default_image_fstype() -> String {
+    "ext4".to_string()
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct IntegritySharedFs {
+    /// Path of the image, relative to the root of the shared filesystem.
+    pub image: String,
+    pub integrity: Integrity,
+    /// Root hash the image is checked against, as a sealed secret. The
+    /// storage metadata comes from the host, so a plaintext one would not
+    /// protect anything.
+    #[serde(rename = "rootHash")]
+    pub root_hash: String,
+    /// dm-verity: path of the hash tree image, relative to the root of the
+    /// shared filesystem. Defaults to `image`.
+    #[serde(rename = "hashImage", default)]
+    pub hash_image: Option<String>,
+    /// dm-verity: offset in bytes of the hash tree in the hash tree image.
+    #[serde(rename = "hashOffset", default)]
+    pub hash_offset: u64,
+    /// dm-verity: filesystem of the image.
+    #[serde(rename = "imageFsType", default = "default_image_fstype")]
+    pub image_fstype: String,
+}
+
+async fn run(bin: &str, args: Vec<String>) -> Result<()> {
+    let output = Command::new(bin)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| Error::SecureMountFailed(format!("failed to run {bin}: {e}")))?;
+
+    if !output.status.success() {
+        return Err(Error::SecureMountFailed(format!(
+            "{bin} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Path of `path` under the mounted shared filesystem. It must stay below
+/// the root of the shared filesystem.
+fn image_path(share_dir: &Path, path: &str) -> Result<PathBuf> {
+    let relative = Path::new(path);
+    if relative.as_os_str().is_empty()
+        || relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return Err(Error::SecureMountFailed(format!(
+            "illegal image path {path:?} on the shared filesystem"
+        )));
+    }
+
+    Ok(share_dir.join(relative))
+}
+
+/// Options the shared filesystem is mounted with: `options`, but always
+/// read-only, whatever they say.
+fn share_mount_options(options: &[String]) -> String {
+    let mut share_options: Vec<&str> = options
+        .iter()
+        .map(String::as_str)
+        .filter(|option| !matches!(*option, "ro" | "rw"))
+        .collect();
+    share_options.push("ro");
+
+    share_options.join(",")
+}
+
+fn check_root_hash(root_hash: &str) -> Result<()> {
+    let even = root_hash.len() & 1 == 0;
+    if root_hash.is_empty() || !even || !root_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::SecureMountFailed(
+            "root hash must be a hex string".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+impl IntegritySharedFs {
+    /// Mount the image on the shared filesystem `source` to `mount_point`
+    /// through the integrity layer.
+    ///
+    /// The shared filesystem is mounted read-only to a temp directory first,
+    /// with the given `fstype` and `options` (e.g. `vers=4.2` for NFS), a
+    /// `rw` among them being dropped. Then
+    /// the image is checked against the root hash whenever it is read, so
+    /// the host cannot tamper with the data it serves.
+    pub(crate) async fn mount(
+        &self,
+        source: &str,
+        fstype: &str,
+        options: &[String],
+        mount_point: String,
+    ) -> Result<Vec<TeardownStep>> {
+        if !SHARED_FSTYPES.contains(&fstype) {
+            return Err(Error::SecureMountFailed(format!(
+                "unsupported shared filesystem {fstype:?}"
+            )));
+        }
+
+        if !self.root_hash.starts_with("sealed.") {
+            return Err(Error::SecureMountFailed(
+                "root hash must be given as a sealed secret".to_string(),
+            ));
+        }
+        let root_hash = get_plaintext_secret(&self.root_hash).await?;
+        check_root_hash(&root_hash)?;
+
+        let share_dir = tempfile::tempdir()
+            .map_err(|e| Error::FileError(format!("create shared fs mount dir failed: {e:?}")))?
+            .into_path();
+        run(
+            MOUNT_BIN,
+            vec![
+                "-t".into(),
+                fstype.into(),
+                "-o".into(),
+                share_mount_options(options),
+                source.into(),
+                share_dir.to_string_lossy().to_string(),
+            ],
+        )
+        .await?;
+
+        let mut teardown = match self.mount_image(&share_dir, &root_hash, &mount_point).await {
+            Ok(teardown) => teardown,
+            Err(e) => {
+                let _ = run(UMOUNT_BIN, vec![share_dir.to_string_lossy().to_string()]).await;
+                return Err(e);
+            }
+        };
+        let share_dir = share_dir.to_string_lossy().to_string();
+        teardown.push(TeardownStep::Umount {
+            target: share_dir.clone(),
+        });
+        teardown.push(TeardownStep::RemoveDir { path: share_dir });
+
+        Ok(teardown)
+    }
+
+    // Returns the steps that tear the integrity layer down.
+    async fn mount_image(
+        &self,
+        share_dir: &Path,
+        root_hash: &str,
+        mount_point: &str,
+    ) -> Result<Vec<TeardownStep>> {
+        let image = image_path(share_dir, &self.image)?;
+
+        match self.integrity {
+            Integrity::DmVerity => {
+                let hash_image = match &self.hash_image {
+                    Some(hash_image) => image_path(share_dir, hash_image)?,
+                    None => image.clone(),
+                };
+                let name = format!(
+                    "cdh-verity-{}-{}",
+                    std::process::id(),
+                    NEXT_VERITY_DEVICE.fetch_add(1, Ordering::Relaxed)
+                );
+                run(
+                    VERITYSETUP_BIN,
+                    self.verity_open_args(&image, &name, &hash_image, root_hash),
+                )
+                .await?;
+
+                let mounted = run(
+                    MOUNT_BIN,
+                    vec![
+                        "-t".into(),
+                        self.image_fstype.clone(),
+                        "-o".into(),
+                        "ro".into(),
+                        format!("/dev/mapper/{name}"),
+                        mount_point.into(),
+                    ],
+                )
+                .await;
+                if let Err(e) = mounted {
+                    let _ = run(VERITYSETUP_BIN, vec!["close".into(), name]).await;
+                    return Err(e);
+                }
+
+                Ok(vec![
+                    TeardownStep::Umount {
+                        target: mount_point.into(),
+                    },
+                    TeardownStep::CloseVerity { name },
+                ])
+            }
+            Integrity::Eccfs => {
+                run(
+                    MOUNT_BIN,
+                    vec![
+                        "-t".into(),
+                        "eccfs".into(),
+                        "-o".into(),
+                        format!(
+                            "ro,dir={},mode=int-{}",
+                            image.display(),
+                            root_hash.to_uppercase()
+                        ),
+                        "eccfs".into(),
+                        mount_point.into(),
+                    ],
+                )
+                .await?;
+
+                Ok(vec![TeardownStep::Umount {
+                    target: mount_point.into(),
+                }])
+            }
+        }
+    }
+
+    fn verity_open_args(
+        &self,
+        image: &Path,
+        name: &str,
+        hash_image: &Path,
+        root_hash: &str,
+    ) -> Vec<String> {
+        let mut args = vec![
+            "open".to_string(),
+            image.to_string_lossy().to_string(),
+            name.to_string(),
+            hash_image.to_string_lossy().to_string(),
+            root_hash.to_string(),
+        ];
+        if self.hash_offset != 0 {
+            args.push(format!("--hash-offset={}", self.hash_offset));
+        }
+
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT_HASH: &str = "4392bd5a5d503b48ff75e51c7bea56e774b7ec1b59bd0e2d5c0b1bd0c5243770";
+
+    #[test]
+    fn test_deserialize() {
+        let metadata = format!(
+            r#"{{"image": "models/llm.img", "integrity": "dm-verity", "rootHash": "{ROOT_HASH}", "hashOffset": 4096}}"#
+        );
+        let shared_fs: IntegritySharedFs = serde_json::from_str(&metadata).unwrap();
+        assert_eq!(
+            shared_fs,
+            IntegritySharedFs {
+                image: "models/llm.img".into(),
+                integrity: Integrity::DmVerity,
+                root_hash: ROOT_HASH.into(),
+                hash_image: None,
+                hash_offset: 4096,
+                image_fstype: "ext4".into(),
+            }
+        );
+
+        let args = shared_fs.verity_open_args(
+            Path::new("/share/models/llm.img"),
+            "cdh-verity-0",
+            Path::new("/share/models/llm.img"),
+            ROOT_HASH,
+        );
+        assert_eq!(
+            args,
+            [
+                "open",
+                "/share/models/llm.img",
+                "cdh-verity-0",
+                "/share/models/llm.img",
+                ROOT_HASH,
+                "--hash-offset=4096"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_image_path() {
+        let share = Path::new("/share");
+        assert_eq!(
+            image_path(share, "models/llm.img").unwrap(),
+            Path::new("/share/models/llm.img")
+        );
+        assert!(image_path(share, "").is_err());
+        assert!(image_path(share, "/etc/shadow").is_err());
+        assert!(image_path(share, "models/../../etc/shadow").is_err());
+    }
+
+    #[test]
+    fn test_share_mount_options() {
+        assert_eq!(share_mount_options(&[]), "ro");
+        assert_eq!(
+            share_mount_options(&["rw".into(), "vers=4.2".into(), "ro".into()]),
+            "vers=4.2,ro"
+        );
+    }
+
+    #[test]
+    fn test_check_root_hash() {
+        assert!(check_root_hash(ROOT_HASH).is_ok());
+        assert!(check_root_hash("").is_err());
+        assert!(check_root_hash("abc").is_err());
+        assert!(check_root_hash("zz").is_err());
+    }
+}
@@ -0,0 +1,519 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! A small CLI to exercise image-rs without kata-agent, e.g. to try the
+//! snapshotters on a development machine. Every command prints its result
+//! as JSON to stdout, and logs to stderr as filtered by `RUST_LOG`.
+//!
+//! image-rs keeps its metadata in memory, so the CLI saves the metadata of
+//! the pulled images and the bundles it mounted to a state file in the work
+//! dir, for later commands to pick up.
+//!
+//! With `serve`, the CLI keeps running and takes the commands of other
+//! processes of the VM on a unix socket instead, one JSON array of command
+//! arguments per connection, e.g. `["pull", "busybox", "/run/bundle"]`,
+//! answered with the JSON result. The commands share the manifest and blob
+//! caches of the config, and run one at a time.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand};
+use image_rs::blob_cache::BlobCache;
+use image_rs::bundle::{
+    create_runtime_config, BUNDLE_CONFIG, BUNDLE_CONFIG_FRAGMENT, BUNDLE_ROOTFS,
+};
+use image_rs::config::{ImageConfig, PullPolicy};
+use image_rs::disk_space::DiskReservations;
+use image_rs::image::{ImageClient, ImageMeta};
+use image_rs::layer_cache::LayerPins;
+use image_rs::manifest_cache::ManifestCache;
+use image_rs::meta_store::MetaStore;
+use image_rs::snapshots::MountPoint;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::Mutex;
+
+/// Name of the state file under the work dir.
+const STATE_FILE: &str = "image-rs-cli.json";
+
+#[derive(Parser)]
+#[command(name = "image-rs-cli")]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Path of the image-rs configuration file. The defaults are used if not given
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Work dir of image-rs, overriding the one of the configuration file
+    #[arg(short, long)]
+    work_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Pull an image, and mount its rootfs to the bundle dir
+    Pull {
+        /// Reference of the image, e.g. `docker.io/library/busybox:latest`
+        image: String,
+
+        /// Bundle dir to create the rootfs and config.json in
+        bundle: PathBuf,
+
+        /// Registry credential, as `username:password`
+        #[arg(long)]
+        auth_info: Option<String>,
+
+        /// Decrypt config of encrypted images, e.g. `provider:attestation-agent:...`
+        #[arg(long)]
+        decrypt_config: Option<String>,
+
+        /// Whether the registry is contacted for images pulled before: `Always`,
+        /// `IfNotPresent` or `Never`. The one of the configuration if not given
+        #[arg(long)]
+        pull_policy: Option<PullPolicy>,
+    },
+
+    /// Mount the rootfs of a pulled image to the bundle dir
+    Mount {
+        /// ID or reference of the pulled image
+        image: String,
+
+        /// Bundle dir to create the rootfs and config.json in
+        bundle: PathBuf,
+    },
+
+    /// Unmount the rootfs of a bundle dir
+    Unmount {
+        /// Bundle dir mounted by `pull` or `mount`
+        bundle: PathBuf,
+    },
+
+    /// Remove the pulled images and layers not used by any mounted bundle
+    Gc,
+
+    /// Show the pulled images and the mounted bundles
+    Status,
+
+    /// Keep running, and run the commands sent to a unix socket
+    Serve {
+        /// Path of the unix socket
+        socket: PathBuf,
+    },
+}
+
+/// Command sent to `serve`, as its arguments.
+#[derive(Parser)]
+#[command(no_binary_name = true)]
+struct Request {
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Caches of the config, and the disk space reserved by the pulls, shared
+/// by the commands run by `serve`.
+struct Caches {
+    manifest: Option<Arc<ManifestCache>>,
+    blob: Option<Arc<BlobCache>>,
+    disk_reservations: Arc<DiskReservations>,
+    layer_pins: Arc<LayerPins>,
+}
+
+impl Caches {
+    fn new(config: &ImageConfig) -> Self {
+        Self {
+            manifest: ImageClient::init_manifest_cache(config),
+            blob: ImageClient::init_blob_cache(config),
+            disk_reservations: Arc::default(),
+            layer_pins: Arc::default(),
+        }
+    }
+}
+
+/// A bundle mounted by the CLI.
+#[derive(Serialize, Deserialize)]
+struct Bundle {
+    image_id: String,
+    snapshotter: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    meta_store: MetaStore,
+    bundles: BTreeMap<PathBuf, Bundle>,
+}
+
+impl State {
+    fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read(path).with_context(|| format!("read {}", path.display()))?;
+        serde_json::from_slice(&content).with_context(|| format!("parse {}", path.display()))
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("write {}", path.display()))
+    }
+
+    fn find_image(&self, image: &str) -> Option<&ImageMeta> {
+        let images = &self.meta_store.image_db;
+        images
+            .get(image)
+            .or_else(|| images.values().find(|meta| meta.reference == image))
+    }
+}
+
+fn image_json(meta: &ImageMeta) -> serde_json::Value {
+    json!({
+        "id": meta.id,
+        "digest": meta.digest,
+        "reference": meta.reference,
+        "signed": meta.signed,
+        "layers": meta.layer_metas.len(),
+        "labels": meta.labels(),
+        "exposed_ports": meta.exposed_ports(),
+    })
+}
+
+/// The bundle dir is the key of the mounted bundles, so it is made absolute.
+fn bundle_key(bundle: &Path) -> Result<PathBuf> {
+    fs::create_dir_all(bundle).with_context(|| format!("create {}", bundle.display()))?;
+    bundle
+        .canonicalize()
+        .with_context(|| format!("resolve {}", bundle.display()))
+}
+
+/// Take the work dir index of the next snapshot, so that snapshots mounted by
+/// different runs of the CLI do not share a work dir.
+fn take_snapshot_index(state: &mut State, config: &ImageConfig) {
+    *state
+        .meta_store
+        .snapshot_db
+        .entry(config.default_snapshot.to_string())
+        .or_default() += 1;
+}
+
+fn client(config: ImageConfig, state: &State, caches: &Caches) -> ImageClient {
+    let snapshots = ImageClient::init_snapshots(&config, &state.meta_store);
+    let pull_budget = ImageClient::init_pull_budget(&config);
+    ImageClient {
+        config,
+        meta_store: Arc::new(Mutex::new(state.meta_store.clone())),
+        snapshots: Arc::new(Mutex::new(snapshots)),
+        measurement_hook: None,
+        manifest_cache: caches.manifest.clone(),
+        blob_cache: caches.blob.clone(),
+        pull_budget,
+        layer_locks: Arc::default(),
+        layer_pins: caches.layer_pins.clone(),
+        disk_reservations: caches.disk_reservations.clone(),
+        registry_clients: Arc::default(),
+        events: Arc::default(),
+        #[cfg(feature = "fault-injection")]
+        faults: Arc::default(),
+    }
+}
+
+async fn run(cli: Cli) -> Result<serde_json::Value> {
+    let mut config = match &cli.config {
+        Some(path) => ImageConfig::load(path)?,
+        None => ImageConfig::from_env()?,
+    };
+    if let Some(work_dir) = cli.work_dir {
+        config.work_dir = work_dir;
+    }
+    fs::create_dir_all(&config.work_dir)
+        .with_context(|| format!("create work dir {}", config.work_dir.display()))?;
+    let state_path = config.work_dir.join(STATE_FILE);
+    let caches = Caches::new(&config);
+    if let Command::Serve { socket } = &cli.command {
+        serve(socket, &config, &state_path, &caches).await?;
+        return Ok(json!({}));
+    }
+
+    let mut state = State::load(&state_path)?;
+    let read_only = matches!(cli.command, Command::Status);
+    let res = execute(cli.command, &config, &mut state, &caches).await?;
+    if !read_only {
+        state.save(&state_path)?;
+    }
+    Ok(res)
+}
+
+/// Run the commands sent to `socket` until the process is killed.
+async fn serve(
+    socket: &Path,
+    config: &ImageConfig,
+    state_path: &Path,
+    caches: &Caches,
+) -> Result<()> {
+    if socket.exists() {
+        fs::remove_file(socket).with_context(|| format!("remove {}", socket.display()))?;
+    }
+    let listener =
+        UnixListener::bind(socket).with_context(|| format!("bind {}", socket.display()))?;
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+    info!("serving on {}", socket.display());
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        if let Err(e) = handle_request(stream, config, state_path, caches).await {
+            warn!("failed to answer request: {e:#}");
+        }
+    }
+}
+
+async fn handle_request(
+    stream: UnixStream,
+    config: &ImageConfig,
+    state_path: &Path,
+    caches: &Caches,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+
+    let res = async {
+        let args: Vec<String> = serde_json::from_str(&line).context("parse request")?;
+        let request = Request::try_parse_from(args)?;
+        let mut state = State::load(state_path)?;
+        let res = execute(request.command, config, &mut state, caches).await?;
+        state.save(state_path)?;
+        Ok(res)
+    }
+    .await;
+
+    let res = res.unwrap_or_else(|e| json!({ "error": format!("{e:#}") }));
+    writer.write_all(format!("{res}\n").as_bytes()).await?;
+    Ok(())
+}
+
+async fn execute(
+    command: Command,
+    config: &ImageConfig,
+    state: &mut State,
+    caches: &Caches,
+) -> Result<serde_json::Value> {
+    let snapshotter = config.default_snapshot.to_string();
+    let res = match command {
+        Command::Pull {
+            image,
+            bundle,
+            auth_info,
+            decrypt_config,
+            pull_policy,
+        } => {
+            let bundle = bundle_key(&bundle)?;
+            if state.bundles.contains_key(&bundle) {
+                bail!("bundle {} is mounted already", bundle.display());
+            }
+
+            let client = client(config.clone(), state, caches);
+            let pulled = client
+                .pull_image_with_policy(
+                    &image,
+                    &bundle,
+                    &auth_info.as_deref(),
+                    &decrypt_config.as_deref(),
+                    pull_policy.unwrap_or(config.pull_policy),
+                )
+                .await?;
+            state.meta_store = client.meta_store.lock().await.clone();
+            take_snapshot_index(state, config);
+            state.bundles.insert(
+                bundle.clone(),
+                Bundle {
+                    image_id: pulled.meta.id.clone(),
+                    snapshotter,
+                },
+            );
+
+            let layer_groups: Vec<_> = pulled
+                .layer_groups
+                .iter()
+                .map(|group| {
+                    json!({
+                        "snapshotter": group.snapshot.to_string(),
+                        "mount_path": group.mount_path(),
+                    })
+                })
+                .collect();
+            json!({
+                "image": image_json(&pulled.meta),
+                "bundle": bundle,
+                "layer_groups": layer_groups,
+            })
+        }
+        Command::Mount { image, bundle } => {
+            let bundle = bundle_key(&bundle)?;
+            if state.bundles.contains_key(&bundle) {
+                bail!("bundle {} is mounted already", bundle.display());
+            }
+
+            let meta = state
+                .find_image(&image)
+                .ok_or_else(|| anyhow!("image {image} has not been pulled"))?
+                .clone();
+            let layer_path = meta
+                .layer_metas
+                .iter()
+                .rev()
+                .map(|l| l.store_path.as_str())
+                .collect::<Vec<&str>>();
+
+            let client = client(config.clone(), state, caches);
+            let mut snapshots = client.snapshots.lock().await;
+            let snapshot = snapshots
+                .get_mut(&config.default_snapshot)
+                .ok_or_else(|| anyhow!("default snapshot {snapshotter} not found"))?;
+            snapshot.mount(&layer_path, &bundle.join(BUNDLE_ROOTFS))?;
+            take_snapshot_index(state, config);
+            create_runtime_config(&meta.image_config, &bundle)?;
+            state.bundles.insert(
+                bundle.clone(),
+                Bundle {
+                    image_id: meta.id.clone(),
+                    snapshotter,
+                },
+            );
+
+            json!({
+                "image": image_json(&meta),
+                "bundle": bundle,
+            })
+        }
+        Command::Unmount { bundle } => {
+            let bundle = bundle
+                .canonicalize()
+                .with_context(|| format!("resolve {}", bundle.display()))?;
+            let mounted = state
+                .bundles
+                .get(&bundle)
+                .ok_or_else(|| anyhow!("bundle {} is not mounted", bundle.display()))?;
+            if mounted.snapshotter != snapshotter {
+                bail!(
+                    "bundle {} is mounted by snapshotter {}, not {snapshotter}",
+                    bundle.display(),
+                    mounted.snapshotter
+                );
+            }
+
+            let client = client(config.clone(), state, caches);
+            let groups = client.unmount_layer_groups(&bundle).await;
+            state.meta_store = client.meta_store.lock().await.clone();
+            groups?;
+
+            let snapshots = client.snapshots.lock().await;
+            let snapshot = snapshots
+                .get(&config.default_snapshot)
+                .ok_or_else(|| anyhow!("default snapshot {snapshotter} not found"))?;
+            snapshot.unmount(&MountPoint {
+                r#type: snapshotter,
+                mount_path: bundle.join(BUNDLE_ROOTFS),
+                work_dir: PathBuf::new(),
+                aux_resources: Vec::new(),
+            })?;
+            for file in [BUNDLE_CONFIG, BUNDLE_CONFIG_FRAGMENT] {
+                let _ = fs::remove_file(bundle.join(file));
+            }
+            let mounted = state.bundles.remove(&bundle).expect("bundle is mounted");
+
+            json!({
+                "image_id": mounted.image_id,
+                "bundle": bundle,
+            })
+        }
+        Command::Gc => {
+            let in_use: HashSet<&String> = state.bundles.values().map(|b| &b.image_id).collect();
+            let removed_images: Vec<String> = state
+                .meta_store
+                .image_db
+                .keys()
+                .filter(|id| !in_use.contains(id))
+                .cloned()
+                .collect();
+            for id in &removed_images {
+                state.meta_store.image_db.remove(id);
+            }
+
+            let used_layers: HashSet<String> = state
+                .meta_store
+                .image_db
+                .values()
+                .flat_map(|meta| meta.layer_metas.iter())
+                .map(|layer| layer.compressed_digest.clone())
+                .chain(state.meta_store.customization_db.values().cloned())
+                .collect();
+            let mut removed_layers = vec![];
+            state.meta_store.layer_db.retain(|digest, layer| {
+                if used_layers.contains(digest) {
+                    return true;
+                }
+
+                if let Err(e) = fs::remove_dir_all(&layer.store_path) {
+                    warn!("failed to remove layer {}: {e}", layer.store_path);
+                }
+                removed_layers.push(digest.clone());
+                false
+            });
+
+            json!({
+                "removed_images": removed_images,
+                "removed_layers": removed_layers,
+            })
+        }
+        Command::Status => {
+            let images: Vec<_> = state.meta_store.image_db.values().map(image_json).collect();
+            let bundles: Vec<_> = state
+                .bundles
+                .iter()
+                .map(|(bundle, mounted)| {
+                    json!({
+                        "bundle": bundle,
+                        "image_id": mounted.image_id,
+                        "snapshotter": mounted.snapshotter,
+                    })
+                })
+                .collect();
+
+            json!({
+                "work_dir": config.work_dir,
+                "snapshotter": snapshotter,
+                "images": images,
+                "bundles": bundles,
+            })
+        }
+        Command::Serve { .. } => bail!("serve is not a command to run once"),
+    };
+
+    Ok(res)
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(res) => println!("{res:#}"),
+        Err(e) => {
+            println!("{:#}", json!({ "error": format!("{e:#}") }));
+            std::process::exit(1);
+        }
+    }
+}
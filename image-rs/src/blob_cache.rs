@@ -0,0 +1,389 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pull-through cache of layer blobs.
+//!
+//! Every pull downloads the layers missing from its own layer store, so the
+//! containers of a VM pulling the same image with other snapshotters, by
+//! other image-rs instances or after the layers were evicted download the
+//! same blobs again. With a [`BlobCacheConfig`], blobs are downloaded into
+//! a cache dir first, usually on a volume shared by the image-rs instances
+//! of the VM, and unpacked from there. Later pulls of a blob read it from
+//! the cache instead of the registry, once the registry served them a
+//! manifest listing it.
+//!
+//! Blobs are cached as the registry serves them, so the ones of encrypted
+//! layers stay encrypted and every pull decrypts them anew with the keys of
+//! its own decrypt config. With `encrypted_only`, no other blob is cached.
+//!
+//! The blobs are cached per scope, i.e. per repository of the image and
+//! credential of the pull, see
+//! [`PullClient::blob_scope`](crate::pull::PullClient::blob_scope). A pull only
+//! reads the blobs pulled from the same repository with the same
+//! credential, so that a manifest listing the digest of a blob of another
+//! image doesn't get it from the cache without the registry authorizing it.
+//!
+//! Blobs are verified against their digest before they are added, and
+//! written to a temporary file renamed into place, so that the instances
+//! sharing the dir never see partial blobs. The cache is kept below
+//! `max_bytes` by evicting the least recently used blobs, whose last use is
+//! their mtime.
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use oci_distribution::manifest::OciDescriptor;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs::{self, File};
+use tokio::io::AsyncRead;
+use tokio::sync::Mutex;
+
+use crate::config::BlobCacheConfig;
+use crate::decrypt::Decryptor;
+use crate::digest::{hasher_for, HashingReader};
+use crate::disk_space::tree_size;
+use crate::pull::{blob_id, BlobDigestMismatch};
+
+/// Dir under the cache dir the blobs are stored in.
+const BLOBS_DIR: &str = "blobs";
+
+/// Dir under the cache dir the blobs are downloaded to.
+const TMP_DIR: &str = "tmp";
+
+/// Pull-through cache of layer blobs, which can be shared by several
+/// clients.
+pub struct BlobCache {
+    config: BlobCacheConfig,
+
+    /// Blobs being downloaded, by scope and digest, so that concurrent pulls
+    /// of a blob download it once.
+    fetching: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl BlobCache {
+    /// Create the cache, with its dir if missing.
+    pub fn new(config: &BlobCacheConfig) -> Result<Self> {
+        for dir in [BLOBS_DIR, TMP_DIR] {
+            let dir = config.dir.join(dir);
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("create blob cache dir {}", dir.display()))?;
+        }
+
+        Ok(Self {
+            config: config.clone(),
+            fetching: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Whether the blob of `layer` is kept in the cache.
+    pub fn caches(&self, layer: &OciDescriptor) -> bool {
+        let encrypted = Decryptor::from_descriptor(layer).is_encrypted();
+        (encrypted || !self.config.encrypted_only)
+            && layer.size.max(0) as u64 <= self.config.max_bytes
+    }
+
+    fn blob_path(&self, scope: &str, digest: &str) -> PathBuf {
+        self.config
+            .dir
+            .join(BLOBS_DIR)
+            .join(scope)
+            .join(blob_id(digest))
+    }
+
+    /// Open the blob with `digest` cached in `scope`, marking it as used.
+    pub async fn open(&self, scope: &str, digest: &str) -> Result<Option<File>> {
+        let path = self.blob_path(scope, digest);
+        let file = match File::open(&path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("open {}", path.display())),
+        };
+
+        // times of null set the atime and mtime to now
+        if unsafe { libc::futimens(file.as_raw_fd(), std::ptr::null()) } != 0 {
+            warn!(
+                "failed to mark cached blob {} as used: {}",
+                digest,
+                io::Error::last_os_error()
+            );
+        }
+
+        Ok(Some(file))
+    }
+
+    /// Open the blob of `layer` cached in `scope`. If it is not cached yet,
+    /// it is read from the reader returned by `fetch`, e.g. the registry,
+    /// and added first. Concurrent calls for the same blob wait for the
+    /// first one.
+    pub async fn get_or_fetch<F, Fut, R>(
+        &self,
+        scope: &str,
+        layer: &OciDescriptor,
+        fetch: F,
+    ) -> Result<File>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<R>>,
+        R: AsyncRead + Unpin,
+    {
+        let key = format!("{scope}/{}", layer.digest);
+        let slot = self
+            .fetching
+            .lock()
+            .await
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        let res = async {
+            let _fetching = slot.lock().await;
+            if let Some(file) = self.open(scope, &layer.digest).await? {
+                return Ok(file);
+            }
+
+            self.insert(scope, layer, fetch().await?).await?;
+            self.open(scope, &layer.digest)
+                .await?
+                .ok_or_else(|| anyhow!("cached blob {} is gone", layer.digest))
+        }
+        .await;
+
+        // the map and this call hold the slot if nobody else waits for it
+        let mut fetching = self.fetching.lock().await;
+        if Arc::strong_count(&slot) == 2 {
+            fetching.remove(&key);
+        }
+
+        res
+    }
+
+    /// Add the blob of `layer` read from `reader` to `scope`, after checking
+    /// it against the digest of `layer`, and evict the least recently used
+    /// blobs if the cache grew too large.
+    pub async fn insert(
+        &self,
+        scope: &str,
+        layer: &OciDescriptor,
+        reader: impl AsyncRead + Unpin,
+    ) -> Result<()> {
+        let tmp = self.config.dir.join(TMP_DIR).join(format!(
+            "{scope}.{}.{}",
+            blob_id(&layer.digest),
+            std::process::id()
+        ));
+        let mut blob = HashingReader::new(reader, hasher_for(&layer.digest)?);
+        let written = async {
+            let mut file = File::create(&tmp).await?;
+            tokio::io::copy(&mut blob, &mut file).await?;
+            file.sync_all().await
+        }
+        .await;
+        if let Err(e) = written {
+            let _ = fs::remove_file(&tmp).await;
+            return Err(anyhow!("failed to cache blob {}: {}", layer.digest, e));
+        }
+
+        let digest = blob.digest_finalize();
+        if digest != layer.digest {
+            let _ = fs::remove_file(&tmp).await;
+            return Err(anyhow::Error::new(BlobDigestMismatch {
+                digest,
+                expected: layer.digest.clone(),
+            }));
+        }
+
+        let path = self.blob_path(scope, &layer.digest);
+        let moved = async {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).await?;
+            }
+            fs::rename(&tmp, &path).await
+        }
+        .await;
+        if let Err(e) = moved {
+            let _ = fs::remove_file(&tmp).await;
+            return Err(e).with_context(|| format!("move cached blob to {}", path.display()));
+        }
+        let freed = self.evict(&path).await?;
+        if freed > 0 {
+            info!("{} bytes freed by evicting cached blobs", freed);
+        }
+
+        Ok(())
+    }
+
+    /// Remove the blob with `digest` cached in `scope`, e.g. because
+    /// unpacking it failed verification.
+    pub async fn remove(&self, scope: &str, digest: &str) {
+        match fs::remove_file(self.blob_path(scope, digest)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                warn!("failed to remove cached blob {}: {}", digest, e)
+            }
+            _ => {}
+        }
+    }
+
+    /// Bytes of the blobs cached, counted in the budget of the layer store,
+    /// see [`crate::layer_cache`].
+    pub fn size(&self) -> Result<u64> {
+        tree_size(&self.config.dir.join(BLOBS_DIR))
+    }
+
+    /// Remove the least recently used blobs but `keep` until the cache fits
+    /// into `max_bytes`. Returns the bytes freed.
+    async fn evict(&self, keep: &Path) -> Result<u64> {
+        let mut blobs = Vec::new();
+        let mut total = 0;
+        let mut scopes = fs::read_dir(self.config.dir.join(BLOBS_DIR)).await?;
+        while let Some(scope) = scopes.next_entry().await? {
+            let mut entries = match fs::read_dir(scope.path()).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) if e.kind() == io::ErrorKind::NotADirectory => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                // blobs evicted by another instance meanwhile
+                let meta = match entry.metadata().await {
+                    Ok(meta) => meta,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e.into()),
+                };
+                if meta.is_file() {
+                    total += meta.len();
+                    blobs.push((meta.modified()?, meta.len(), entry.path()));
+                }
+            }
+        }
+
+        // open blobs stay readable by the pulls using them once removed
+        blobs.sort();
+        let mut freed = 0;
+        for (_, size, path) in blobs {
+            if total <= self.config.max_bytes {
+                break;
+            }
+            if path == keep {
+                continue;
+            }
+
+            match fs::remove_file(&path).await {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+            total -= size;
+            freed += size;
+            info!("evicted cached blob {}", path.display());
+        }
+
+        Ok(freed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+
+    fn layer(data: &[u8], media_type: &str) -> OciDescriptor {
+        OciDescriptor {
+            media_type: media_type.to_string(),
+            digest: format!("sha256:{:x}", Sha256::digest(data)),
+            size: data.len() as i64,
+            ..Default::default()
+        }
+    }
+
+    async fn read(mut file: File) -> Vec<u8> {
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).await.unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn test_blob_cache() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let cache = BlobCache::new(&BlobCacheConfig {
+            dir: tempdir.path().to_path_buf(),
+            max_bytes: 8,
+            encrypted_only: false,
+        })
+        .unwrap();
+        let media_type = "application/vnd.oci.image.layer.v1.tar";
+
+        let scope = "image-a";
+        let a = layer(b"aaaa", media_type);
+        assert!(cache.open(scope, &a.digest).await.unwrap().is_none());
+        let file = cache
+            .get_or_fetch(scope, &a, || async { Ok(&b"aaaa"[..]) })
+            .await
+            .unwrap();
+        assert_eq!(read(file).await, b"aaaa");
+
+        // hits don't fetch
+        let file = cache
+            .get_or_fetch(scope, &a, || async {
+                Err::<&[u8], _>(anyhow!("not cached"))
+            })
+            .await
+            .unwrap();
+        assert_eq!(read(file).await, b"aaaa");
+        assert!(cache.fetching.lock().await.is_empty());
+
+        // other scopes don't get the blob
+        assert!(cache.open("image-b", &a.digest).await.unwrap().is_none());
+        let e = cache
+            .get_or_fetch("image-b", &a, || async {
+                Err::<&[u8], _>(anyhow!("not authorized"))
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), "not authorized");
+
+        // blobs not matching their digest are not added
+        let e = cache.insert(scope, &a, &b"bbbb"[..]).await.unwrap_err();
+        assert!(e.is::<BlobDigestMismatch>());
+        assert_eq!(
+            read(cache.open(scope, &a.digest).await.unwrap().unwrap()).await,
+            b"aaaa"
+        );
+
+        // the least recently used blob is evicted
+        let b = layer(b"bbbb", media_type);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        cache.insert(scope, &b, &b"bbbb"[..]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        cache.open(scope, &a.digest).await.unwrap().unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let c = layer(b"cc", media_type);
+        cache.insert("image-c", &c, &b"cc"[..]).await.unwrap();
+        assert!(cache.open(scope, &a.digest).await.unwrap().is_some());
+        assert!(cache.open(scope, &b.digest).await.unwrap().is_none());
+        assert!(cache.open("image-c", &c.digest).await.unwrap().is_some());
+
+        cache.remove(scope, &a.digest).await;
+        assert!(cache.open(scope, &a.digest).await.unwrap().is_none());
+        assert!(std::fs::read_dir(tempdir.path().join(TMP_DIR))
+            .unwrap()
+            .next()
+            .is_none());
+
+        assert!(cache.caches(&c));
+        assert!(!cache.caches(&layer(b"too large", media_type)));
+        let encrypted_only = BlobCache::new(&BlobCacheConfig {
+            dir: tempdir.path().to_path_buf(),
+            max_bytes: 8,
+            encrypted_only: true,
+        })
+        .unwrap();
+        assert!(!encrypted_only.caches(&c));
+        assert!(encrypted_only.caches(&layer(b"cc", &format!("{media_type}+gzip+encrypted"))));
+    }
+}
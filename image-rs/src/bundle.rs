@@ -2,14 +2,18 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
 use std::path::{Path, PathBuf};
 
 use oci_spec::image::ImageConfiguration;
-use oci_spec::runtime::{Mount, Process, Spec};
+use oci_spec::runtime::{Mount, Process, Spec, User};
 
 pub const BUNDLE_CONFIG: &str = "config.json";
+pub const BUNDLE_CONFIG_FRAGMENT: &str = "config.fragment.json";
 pub const BUNDLE_ROOTFS: &str = "rootfs";
 pub const BUNDLE_HOSTNAME: &str = "image-rs";
 
@@ -23,195 +27,366 @@ const ANNOTATION_CREATED: &str = "org.opencontainers.image.created";
 const ANNOTATION_STOP_SIGNAL: &str = "org.opencontainers.image.stopSignal";
 const ANNOTATION_EXPOSED_PORTS: &str = "org.opencontainers.image.exposedPorts";
 
-/// Convert an `application/vnd.oci.image.config.v1+json` object into an OCI runtime configuration
-/// blob and write to `config.json`.
-///
-/// [OCI Image Spec: Conversion to OCI Runtime Configuration](https://github.com/opencontainers/image-spec/blob/main/conversion.md)
-/// states:
+/// The part of the OCI runtime configuration converted from the image
+/// configuration, laid out as in `config.json`.
 ///
-/// The "default generated runtime configuration" MAY be overridden or combined with externally
-/// provided inputs from the caller. In addition, a converter MAY have its own
-/// implementation-defined defaults and extensions which MAY be combined with the "default generated
-/// runtime configuration".
-pub fn create_runtime_config(
-    image_config: &ImageConfiguration,
-    bundle_path: &Path,
-) -> Result<PathBuf> {
-    let mut spec = Spec::default();
-    let mut annotations: HashMap<String, String> = HashMap::new();
-    let mut labels = HashMap::new();
+/// It is written to [`BUNDLE_CONFIG_FRAGMENT`] beside `config.json`, so that
+/// callers with their own runtime configuration (e.g. from the pod spec) can
+/// merge in what the image asks for, without the defaults of `config.json`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuntimeConfigFragment {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process: Option<ProcessFragment>,
 
-    // Update the default hostname
-    spec.set_hostname(Some(BUNDLE_HOSTNAME.to_string()));
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub annotations: HashMap<String, String>,
 
-    if let Some(config) = image_config.config() {
-        let mut process = Process::default();
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mounts: Vec<Mount>,
+}
+
+/// The process fields converted from the image configuration.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessFragment {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<Vec<String>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<User>,
+}
+
+impl RuntimeConfigFragment {
+    /// Convert an `application/vnd.oci.image.config.v1+json` object, which
+    /// is the configuration of the image unpacked to `rootfs`.
+    ///
+    /// [OCI Image Spec: Conversion to OCI Runtime Configuration](https://github.com/opencontainers/image-spec/blob/main/conversion.md)
+    pub fn from_image_config(image_config: &ImageConfiguration, rootfs: &Path) -> Result<Self> {
+        let mut fragment = RuntimeConfigFragment::default();
+        let mut labels = HashMap::new();
+
+        if let Some(config) = image_config.config() {
+            let mut process = ProcessFragment::default();
+
+            // Verbatim Fields:
+            //
+            // A compliant configuration converter MUST extract the following fields verbatim to the
+            // corresponding field in the generated runtime configuration:
+            // - WorkingDir
+            // - Env
+            // - EntryPoint
+            // - Cmd
+            if let Some(working_dir) = config.working_dir() {
+                process.cwd = Some(PathBuf::from(working_dir));
+            }
+            // The converter MAY add additional entries to process.env but it SHOULD NOT add entries
+            // that have variable names present in Config.Env.
+            if let Some(env) = config.env() {
+                process.env = Some(env.to_vec());
+            }
+            // If both Config.Entrypoint and Config.Cmd are specified, the converter MUST append the
+            // value of Config.Cmd to the value of Config.Entrypoint and set process.args to that
+            // combined value.
+            let mut args: Vec<String> = vec![];
+            if let Some(entrypoint) = config.entrypoint() {
+                args.extend(entrypoint.clone());
+            }
+            if let Some(cmd) = config.cmd() {
+                args.extend(cmd.clone());
+            }
+            if !args.is_empty() {
+                process.args = Some(args);
+            }
 
-        // Verbatim Fields:
-        //
-        // A compliant configuration converter MUST extract the following fields verbatim to the
-        // corresponding field in the generated runtime configuration:
-        // - WorkingDir
-        // - Env
-        // - EntryPoint
-        // - Cmd
-        if let Some(working_dir) = config.working_dir() {
-            process.set_cwd(PathBuf::from(working_dir));
+            // Parsed Fields:
+            //
+            // Certain image configuration fields have a counterpart that must first be translated.
+            // A compliant configuration converter SHOULD parse all of these fields and set the
+            // corresponding fields in the generated runtime configuration:
+            // - User
+            //
+            // A user the rootfs does not know is left to the runtime, as
+            // it may be created when the container starts.
+            if let Some(user) = config.user() {
+                if !user.is_empty() {
+                    match resolve_user(user, rootfs) {
+                        Ok(resolved) => process.user = Some(resolved),
+                        Err(e) => warn!("image user {user:?} is not converted: {e:#}"),
+                    }
+                }
+            }
+            fragment.process = Some(process);
+
+            // Annotation Fields:
+            //
+            // These fields all affect the annotations of the runtime configuration, and are thus
+            // subject to precedence: if there is a conflict (same key but different value) between an
+            // implicit annotation and an explicitly specified annotation in Config.Labels, the value
+            // specified in Config.Labels MUST take precedence.
+            // - os: org.opencontainers.image.os
+            // - os.version: org.opencontainers.image.os.version
+            // - os.features: org.opencontainers.image.os.features
+            // - architecture: org.opencontainers.image.architecture
+            // - variant: org.opencontainers.image.variant
+            // - author: org.opencontainers.image.author
+            // - created: org.opencontainers.image.created
+            // - Config.StopSignal: org.opencontainers.image.stopSignal
+            // - Config.Labels
+            if let Some(labels2) = config.labels() {
+                fragment.annotations.extend(labels2.clone());
+                labels = labels2.clone();
+            }
+            if !labels.contains_key(ANNOTATION_STOP_SIGNAL) {
+                if let Some(stop_signal) = config.stop_signal() {
+                    fragment
+                        .annotations
+                        .insert(ANNOTATION_STOP_SIGNAL.to_string(), stop_signal.to_string());
+                }
+            }
+
+            // Optional Fields:
+            //
+            // Certain image configuration fields are not applicable to all conversion use cases, and
+            // thus are optional for configuration converters to implement. A compliant configuration
+            // converter SHOULD provide a way for users to extract these fields into the generated
+            // runtime configuration:
+            // - ExposedPorts
+            // - Volumes
+
+            // The runtime configuration does not have a corresponding field for this image field.
+            // However, converters SHOULD set the org.opencontainers.image.exposedPorts annotation.
+            if let Some(exposed_ports) = config.exposed_ports() {
+                fragment.annotations.insert(
+                    ANNOTATION_EXPOSED_PORTS.to_string(),
+                    exposed_ports.join(","),
+                );
+            }
+
+            // Implementations SHOULD provide mounts for these locations such that application data is
+            // not written to the container's root filesystem. If a converter implements conversion for
+            // this field using mountpoints, it SHOULD set the destination of the mountpoint to the
+            // value specified in Config.Volumes. An implementation MAY seed the contents of the mount
+            // with data in the image at the same location. If a new image is created from a container
+            // based on the image described by this configuration, data in these paths SHOULD NOT be
+            // included in the new image. The other mounts fields are platform and context dependent,
+            // and thus are implementation-defined.
+            //
+            // Note that the implementation of Config.Volumes need not use mountpoints, as it is
+            // effectively a mask of the filesystem.
+            if let Some(volumes) = config.volumes() {
+                for v in volumes.iter() {
+                    let mut m = Mount::default();
+                    m.set_destination(PathBuf::from(v))
+                        .set_typ("tmpfs".to_string().into())
+                        .set_source(None)
+                        .set_options(Some(vec![
+                            "nosuid".into(),
+                            "noexec".into(),
+                            "nodev".into(),
+                            "relatime".into(),
+                            "rw".into(),
+                        ]));
+                    fragment.mounts.push(m);
+                }
+            }
         }
-        // The converter MAY add additional entries to process.env but it SHOULD NOT add entries
-        // that have variable names present in Config.Env.
-        if let Some(env) = config.env() {
-            process.set_env(Some(env.to_vec()));
+
+        let annotations = &mut fragment.annotations;
+        if !labels.contains_key(ANNOTATION_OS) {
+            annotations.insert(ANNOTATION_OS.to_string(), image_config.os().to_string());
         }
-        // If both Config.Entrypoint and Config.Cmd are specified, the converter MUST append the
-        // value of Config.Cmd to the value of Config.Entrypoint and set process.args to that
-        // combined value.
-        let mut args: Vec<String> = vec![];
-        if let Some(entrypoint) = config.entrypoint() {
-            args.extend(entrypoint.clone());
+        if !labels.contains_key(ANNOTATION_OS_VERSION) {
+            if let Some(version) = image_config.os_version() {
+                annotations.insert(ANNOTATION_OS_VERSION.to_string(), version.to_string());
+            }
         }
-        if let Some(cmd) = config.cmd() {
-            args.extend(cmd.clone());
+        if !labels.contains_key(ANNOTATION_OS_FEATURES) {
+            if let Some(features) = image_config.os_features() {
+                if let Ok(v) = serde_json::to_string(features) {
+                    annotations.insert(ANNOTATION_OS_FEATURES.to_string(), v);
+                }
+            }
         }
-        if !args.is_empty() {
-            process.set_args(Some(args));
+        if !labels.contains_key(ANNOTATION_ARCH) {
+            annotations.insert(
+                ANNOTATION_ARCH.to_string(),
+                image_config.architecture().to_string(),
+            );
         }
-        spec.set_process(Some(process));
-
-        // Annotation Fields:
-        //
-        // These fields all affect the annotations of the runtime configuration, and are thus
-        // subject to precedence: if there is a conflict (same key but different value) between an
-        // implicit annotation and an explicitly specified annotation in Config.Labels, the value
-        // specified in Config.Labels MUST take precedence.
-        // - os: org.opencontainers.image.os
-        // - os.version: org.opencontainers.image.os.version
-        // - os.features: org.opencontainers.image.os.features
-        // - architecture: org.opencontainers.image.architecture
-        // - variant: org.opencontainers.image.variant
-        // - author: org.opencontainers.image.author
-        // - created: org.opencontainers.image.created
-        // - Config.StopSignal: org.opencontainers.image.stopSignal
-        // - Config.Labels
-        if let Some(labels2) = config.labels() {
-            annotations.extend(labels2.clone());
-            labels = labels2.clone();
+        if !labels.contains_key(ANNOTATION_VARIANT) {
+            if let Some(variant) = image_config.variant() {
+                annotations.insert(ANNOTATION_VARIANT.to_string(), variant.to_string());
+            }
+        }
+        if !labels.contains_key(ANNOTATION_AUTHOR) {
+            if let Some(author) = image_config.author() {
+                annotations.insert(ANNOTATION_AUTHOR.to_string(), author.to_string());
+            }
         }
-        if !labels.contains_key(ANNOTATION_STOP_SIGNAL) {
-            if let Some(stop_signal) = config.stop_signal() {
-                annotations.insert(ANNOTATION_STOP_SIGNAL.to_string(), stop_signal.to_string());
+        if !labels.contains_key(ANNOTATION_CREATED) {
+            if let Some(created) = image_config.created() {
+                annotations.insert(ANNOTATION_CREATED.to_string(), created.to_string());
             }
         }
 
-        // Parsed Fields:
-        //
-        // Certain image configuration fields have a counterpart that must first be translated.
-        // A compliant configuration converter SHOULD parse all of these fields and set the
-        // corresponding fields in the generated runtime configuration:
-        // - User
-        // TODO: parse image config user info and extract uid from rootfs passwd file
-        // github issue: https://github.com/confidential-containers/image-rs/issues/8
-
-        // Optional Fields:
-        //
-        // Certain image configuration fields are not applicable to all conversion use cases, and
-        // thus are optional for configuration converters to implement. A compliant configuration
-        // converter SHOULD provide a way for users to extract these fields into the generated
-        // runtime configuration:
-        // - ExposedPorts
-        // - Volumes
-
-        // The runtime configuration does not have a corresponding field for this image field.
-        // However, converters SHOULD set the org.opencontainers.image.exposedPorts annotation.
-        if let Some(exposed_ports) = config.exposed_ports() {
-            annotations.insert(
-                ANNOTATION_EXPOSED_PORTS.to_string(),
-                exposed_ports.join(","),
-            );
+        Ok(fragment)
+    }
+}
+
+/// Resolve the `User` of the image configuration, i.e. `user`, `uid`,
+/// `user:group`, `uid:gid`, `uid:group` or `user:gid`, against the
+/// `/etc/passwd` and `/etc/group` of the rootfs.
+///
+/// Without a group, the primary group of the user and the groups the user
+/// is a member of are used.
+fn resolve_user(user: &str, rootfs: &Path) -> Result<User> {
+    let passwd = read_db(&rootfs.join("etc/passwd"))?;
+    let (name, group) = match user.split_once(':') {
+        Some((name, group)) => (name, Some(group)),
+        None => (user, None),
+    };
+
+    // passwd entries are name:password:uid:gid:...
+    let entry = passwd.iter().find(|e| match name.parse::<u32>() {
+        Ok(uid) => e.get(2).and_then(|u| u.parse::<u32>().ok()) == Some(uid),
+        Err(_) => e[0] == name,
+    });
+    let (uid, primary_gid) = match (name.parse::<u32>(), entry) {
+        (_, Some(entry)) => (db_id(entry, 2)?, db_id(entry, 3)?),
+        (Ok(uid), None) => (uid, 0),
+        (Err(_), None) => bail!("user {name:?} not found in the /etc/passwd of the image"),
+    };
+
+    let mut res = User::default();
+    res.set_uid(uid);
+    match group {
+        Some(group) => {
+            let gid = match group.parse::<u32>() {
+                Ok(gid) => gid,
+                Err(_) => {
+                    let groups = read_db(&rootfs.join("etc/group"))?;
+                    let entry = groups.iter().find(|e| e[0] == group).ok_or_else(|| {
+                        anyhow!("group {group:?} not found in the /etc/group of the image")
+                    })?;
+                    db_id(entry, 2)?
+                }
+            };
+            res.set_gid(gid);
         }
+        None => {
+            res.set_gid(primary_gid);
 
-        // Implementations SHOULD provide mounts for these locations such that application data is
-        // not written to the container's root filesystem. If a converter implements conversion for
-        // this field using mountpoints, it SHOULD set the destination of the mountpoint to the
-        // value specified in Config.Volumes. An implementation MAY seed the contents of the mount
-        // with data in the image at the same location. If a new image is created from a container
-        // based on the image described by this configuration, data in these paths SHOULD NOT be
-        // included in the new image. The other mounts fields are platform and context dependent,
-        // and thus are implementation-defined.
-        //
-        // Note that the implementation of Config.Volumes need not use mountpoints, as it is
-        // effectively a mask of the filesystem.
-        if let Some(volumes) = config.volumes() {
-            let mut mounts: Vec<Mount> = vec![];
-            for v in volumes.iter() {
-                let mut m = Mount::default();
-                m.set_destination(PathBuf::from(v))
-                    .set_typ("tmpfs".to_string().into())
-                    .set_source(None)
-                    .set_options(Some(vec![
-                        "nosuid".into(),
-                        "noexec".into(),
-                        "nodev".into(),
-                        "relatime".into(),
-                        "rw".into(),
-                    ]));
-                mounts.push(m.clone());
-            }
-            if !mounts.is_empty() {
-                if let Some(default_mounts) = spec.mounts() {
-                    mounts.extend(default_mounts.clone());
+            // group entries are name:password:gid:member,...
+            let username = entry.map(|e| e[0].as_str()).unwrap_or(name);
+            let mut additional_gids = vec![];
+            for entry in read_db(&rootfs.join("etc/group"))? {
+                let member = entry
+                    .get(3)
+                    .is_some_and(|members| members.split(',').any(|m| m == username));
+                if member {
+                    let gid = db_id(&entry, 2)?;
+                    if gid != primary_gid && !additional_gids.contains(&gid) {
+                        additional_gids.push(gid);
+                    }
                 }
-                spec.set_mounts(Some(mounts));
+            }
+            if !additional_gids.is_empty() {
+                res.set_additional_gids(Some(additional_gids));
             }
         }
     }
 
-    if !labels.contains_key(ANNOTATION_OS) {
-        annotations.insert(ANNOTATION_OS.to_string(), image_config.os().to_string());
+    Ok(res)
+}
+
+/// Read the entries of a passwd(5) or group(5) file. A missing file, or one
+/// that is not a regular file (which could point out of the rootfs), has no
+/// entries.
+fn read_db(path: &Path) -> Result<Vec<Vec<String>>> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_file() => {}
+        _ => return Ok(vec![]),
     }
-    if !labels.contains_key(ANNOTATION_OS_VERSION) {
-        if let Some(version) = image_config.os_version() {
-            annotations.insert(ANNOTATION_OS_VERSION.to_string(), version.to_string());
-        }
+
+    let content = fs::read_to_string(path).with_context(|| format!("read {path:?}"))?;
+    Ok(content
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.split(':').map(str::to_string).collect())
+        .collect())
+}
+
+fn db_id(entry: &[String], index: usize) -> Result<u32> {
+    entry
+        .get(index)
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| anyhow!("malformed entry {:?}", entry.join(":")))
+}
+
+/// Convert an `application/vnd.oci.image.config.v1+json` object into an OCI runtime configuration
+/// blob and write to `config.json`. The converted fields are written to
+/// [`BUNDLE_CONFIG_FRAGMENT`] as well, see [`RuntimeConfigFragment`].
+///
+/// [OCI Image Spec: Conversion to OCI Runtime Configuration](https://github.com/opencontainers/image-spec/blob/main/conversion.md)
+/// states:
+///
+/// The "default generated runtime configuration" MAY be overridden or combined with externally
+/// provided inputs from the caller. In addition, a converter MAY have its own
+/// implementation-defined defaults and extensions which MAY be combined with the "default generated
+/// runtime configuration".
+pub fn create_runtime_config(
+    image_config: &ImageConfiguration,
+    bundle_path: &Path,
+) -> Result<PathBuf> {
+    let bundle_config = bundle_path.join(BUNDLE_CONFIG);
+    if bundle_config.exists() {
+        bail!("OCI config file already exists: {:?}", bundle_config);
     }
-    if !labels.contains_key(ANNOTATION_OS_FEATURES) {
-        if let Some(features) = image_config.os_features() {
-            if let Ok(v) = serde_json::to_string(features) {
-                annotations.insert(ANNOTATION_OS_FEATURES.to_string(), v);
-            }
+
+    let fragment =
+        RuntimeConfigFragment::from_image_config(image_config, &bundle_path.join(BUNDLE_ROOTFS))?;
+    let mut spec = Spec::default();
+
+    // Update the default hostname
+    spec.set_hostname(Some(BUNDLE_HOSTNAME.to_string()));
+
+    if let Some(converted) = &fragment.process {
+        let mut process = Process::default();
+        if let Some(cwd) = &converted.cwd {
+            process.set_cwd(cwd.clone());
         }
-    }
-    if !labels.contains_key(ANNOTATION_ARCH) {
-        annotations.insert(
-            ANNOTATION_ARCH.to_string(),
-            image_config.architecture().to_string(),
-        );
-    }
-    if !labels.contains_key(ANNOTATION_VARIANT) {
-        if let Some(variant) = image_config.variant() {
-            annotations.insert(ANNOTATION_VARIANT.to_string(), variant.to_string());
+        if converted.env.is_some() {
+            process.set_env(converted.env.clone());
         }
-    }
-    if !labels.contains_key(ANNOTATION_AUTHOR) {
-        if let Some(author) = image_config.author() {
-            annotations.insert(ANNOTATION_AUTHOR.to_string(), author.to_string());
+        if converted.args.is_some() {
+            process.set_args(converted.args.clone());
         }
-    }
-    if !labels.contains_key(ANNOTATION_CREATED) {
-        if let Some(created) = image_config.created() {
-            annotations.insert(ANNOTATION_CREATED.to_string(), created.to_string());
+        if let Some(user) = &converted.user {
+            process.set_user(user.clone());
         }
+        spec.set_process(Some(process));
     }
 
-    spec.set_annotations(Some(annotations));
-    let bundle_config = bundle_path.join(BUNDLE_CONFIG);
-
-    if bundle_config.exists() {
-        bail!("OCI config file already exists: {:?}", bundle_config);
+    if !fragment.mounts.is_empty() {
+        let mut mounts = fragment.mounts.clone();
+        if let Some(default_mounts) = spec.mounts() {
+            mounts.extend(default_mounts.clone());
+        }
+        spec.set_mounts(Some(mounts));
     }
 
+    spec.set_annotations(Some(fragment.annotations.clone()));
     spec.save(&bundle_config)?;
 
+    let fragment_path = bundle_path.join(BUNDLE_CONFIG_FRAGMENT);
+    fs::write(&fragment_path, serde_json::to_vec_pretty(&fragment)?)
+        .with_context(|| format!("write {fragment_path:?}"))?;
+
     Ok(bundle_config)
 }
 
@@ -234,5 +409,54 @@ mod tests {
 
         assert!(create_runtime_config(&image_config, tempdir.path()).is_err());
         assert!(filename.exists());
+
+        let fragment: RuntimeConfigFragment =
+            serde_json::from_slice(&fs::read(tempdir.path().join(BUNDLE_CONFIG_FRAGMENT)).unwrap())
+                .unwrap();
+        assert_eq!(
+            fragment.annotations.get(ANNOTATION_OS).map(String::as_str),
+            Some("linux")
+        );
+    }
+
+    #[test]
+    fn test_resolve_user() {
+        let rootfs = tempfile::tempdir().unwrap();
+        fs::create_dir(rootfs.path().join("etc")).unwrap();
+        fs::write(
+            rootfs.path().join("etc/passwd"),
+            "root:x:0:0:root:/root:/bin/sh\nnginx:x:101:101:nginx:/var/cache/nginx:/sbin/nologin\n",
+        )
+        .unwrap();
+        fs::write(
+            rootfs.path().join("etc/group"),
+            "root:x:0:\nnginx:x:101:\nwww-data:x:33:nginx,root\n",
+        )
+        .unwrap();
+
+        let user = resolve_user("nginx", rootfs.path()).unwrap();
+        assert_eq!((user.uid(), user.gid()), (101, 101));
+        assert_eq!(user.additional_gids(), &Some(vec![33]));
+
+        let user = resolve_user("nginx:root", rootfs.path()).unwrap();
+        assert_eq!((user.uid(), user.gid()), (101, 0));
+        assert_eq!(user.additional_gids(), &None);
+
+        let user = resolve_user("1000:1000", rootfs.path()).unwrap();
+        assert_eq!((user.uid(), user.gid()), (1000, 1000));
+
+        let user = resolve_user("101", rootfs.path()).unwrap();
+        assert_eq!((user.uid(), user.gid()), (101, 101));
+
+        assert!(resolve_user("nobody", rootfs.path()).is_err());
+        assert!(resolve_user("nginx:nogroup", rootfs.path()).is_err());
+
+        // the pull does not fail on them
+        let image_config: ImageConfiguration =
+            serde_json::from_str(r#"{"architecture": "amd64", "os": "linux", "rootfs": {"type": "layers", "diff_ids": []}, "config": {"User": "nobody"}}"#)
+                .unwrap();
+        let fragment =
+            RuntimeConfigFragment::from_image_config(&image_config, rootfs.path()).unwrap();
+        assert_eq!(fragment.process.unwrap().user, None);
     }
 }
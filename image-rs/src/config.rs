@@ -2,12 +2,17 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use log::warn;
 use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use crate::freshness::FreshnessPolicy;
+use crate::reference_policy::ReferencePolicy;
 use crate::snapshots::SnapshotType;
 
 const DEFAULT_WORK_DIR: &str = "/var/lib/image-rs/";
@@ -33,11 +38,31 @@ pub const AUTH_FILE_PATH: &str = "kbs:///default/credential/test";
 /// Default max concurrent download.
 pub const DEFAULT_MAX_CONCURRENT_DOWNLOAD: usize = 3;
 
+/// Default number of times a layer failing digest verification is re-downloaded.
+pub const DEFAULT_MAX_LAYER_RETRIES: usize = 2;
+
+/// Default max number of layers applied concurrently to a flattened rootfs.
+pub const DEFAULT_MAX_CONCURRENT_UNPACK: usize = 1;
+
+/// Name of the dir under `work_dir` where corrupt layers are recorded for diagnostics.
+pub const DEFAULT_QUARANTINE_DIR: &str = "quarantine";
+
+/// Default max number of corrupt layers recorded in the quarantine dir.
+pub const DEFAULT_MAX_QUARANTINED_LAYERS: usize = 32;
+
 /// Path to the configuration file to generate ImageConfiguration
 pub const CONFIGURATION_FILE_PATH: &str = "/var/lib/image-rs/config.json";
 
+/// Prefix of the environment variables overriding the configuration, see
+/// [`ImageConfig::load`].
+pub const ENV_PREFIX: &str = "IMAGE_RS_";
+
 /// `image-rs` configuration information.
+///
+/// Fields missing from a configuration file take the values of the profile
+/// of its `default_snapshot`, see [`ImageConfig::default_for`].
 #[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ImageConfig {
     /// The location for `image-rs` to store data.
     pub work_dir: PathBuf,
@@ -63,9 +88,148 @@ pub struct ImageConfig {
     /// This defaults to [`DEFAULT_MAX_CONCURRENT_DOWNLOAD`].
     pub max_concurrent_download: usize,
 
+    /// Maximum number of times a single layer is re-downloaded after it
+    /// failed digest verification. Only the failing layer is retried.
+    ///
+    /// This defaults to [`DEFAULT_MAX_LAYER_RETRIES`].
+    #[serde(default = "default_max_layer_retries")]
+    pub max_layer_retries: usize,
+
+    /// Maximum number of layers applied concurrently by the snapshotters
+    /// flattening the layers into one rootfs, e.g. `occlum_unionfs`. Only
+    /// layers touching disjoint paths are applied concurrently, see
+    /// [`crate::flatten`].
+    ///
+    /// This defaults to [`DEFAULT_MAX_CONCURRENT_UNPACK`].
+    #[serde(default = "default_max_concurrent_unpack")]
+    pub max_concurrent_unpack: usize,
+
+    /// Dir where the layers failing digest verification are recorded, to
+    /// help debugging flaky registries. Only the digests and the error are
+    /// recorded, the unpacked layer is deleted.
+    ///
+    /// This defaults to `<work_dir>/`[`DEFAULT_QUARANTINE_DIR`].
+    #[serde(default)]
+    pub quarantine_dir: Option<PathBuf>,
+
+    /// Maximum number of layers recorded in the quarantine dir, the oldest
+    /// records are removed first.
+    ///
+    /// This defaults to [`DEFAULT_MAX_QUARANTINED_LAYERS`].
+    #[serde(default = "default_max_quarantined_layers")]
+    pub max_quarantined_layers: usize,
+
+    /// Platform (`os/arch[/variant]`, e.g. `linux/arm64/v8`) whose manifest
+    /// is selected from multi-platform images.
+    ///
+    /// This defaults to the platform image-rs runs on.
+    #[serde(default)]
+    pub platform: Option<String>,
+
+    /// Which image references are accepted, and the digests tags are
+    /// pinned to.
+    #[serde(default)]
+    pub reference_policy: ReferencePolicy,
+
+    /// Which images are too old or revoked, see [`crate::freshness`].
+    ///
+    /// Images are not checked if not set.
+    #[serde(default)]
+    pub freshness: Option<FreshnessPolicy>,
+
+    /// Whether the pulls of images pulled before contact the registry, see
+    /// [`PullPolicy`].
+    #[serde(default)]
+    pub pull_policy: PullPolicy,
+
+    /// Forward proxy on the host registry traffic is tunneled through, for
+    /// guests without IP networking.
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+
+    /// TLS policy of registries, by their `host[:port]` as in the image
+    /// references, e.g. `localhost:5000`, see [`RegistryConfig`].
+    ///
+    /// Registries not listed are reached over https, verifying their
+    /// certificates.
+    #[serde(default)]
+    pub registries: HashMap<String, RegistryConfig>,
+
+    /// Cache of the manifests and configs of the pulled references, shared
+    /// by bursts of pulls of the same image, see [`crate::manifest_cache`].
+    ///
+    /// The cache is disabled if not set.
+    #[serde(default)]
+    pub manifest_cache: Option<ManifestCacheConfig>,
+
+    /// Cache of the layer keys unwrapped by ocicrypt, shared by the pulls of
+    /// the images encrypted with the same keys, see
+    /// `ocicrypt_rs::unwrap_cache`. The cache is global to the process, so
+    /// the config of the client created last applies.
+    ///
+    /// The cache is disabled if not set.
+    #[serde(default)]
+    pub layer_key_cache: Option<LayerKeyCacheConfig>,
+
+    /// Pull-through cache of the layer blobs, shared by the pulls of the
+    /// image-rs instances using the same dir from the same repository with
+    /// the same credential, see [`crate::blob_cache`].
+    ///
+    /// The cache is disabled if not set.
+    #[serde(default)]
+    pub blob_cache: Option<BlobCacheConfig>,
+
+    /// Download budget shared by the concurrent pulls of the client, see
+    /// [`crate::pull_budget`].
+    ///
+    /// Only `max_concurrent_download` bounds the downloads of every pull if
+    /// not set.
+    #[serde(default)]
+    pub pull_budget: Option<PullBudgetConfig>,
+
+    /// Storage the layers of a snapshotter are unpacked to, instead of
+    /// `<work_dir>/layers`, see [`crate::layer_storage`].
+    #[serde(default)]
+    pub layer_storage: HashMap<SnapshotType, LayerStorageConfig>,
+
+    /// Disk space checks of the pulls, see [`crate::disk_space`].
+    ///
+    /// Pulls are not checked if not set.
+    #[serde(default)]
+    pub disk_space: Option<DiskSpaceConfig>,
+
+    /// Size budget of the layer store, kept by evicting the layers of the
+    /// images not in use, see [`crate::layer_cache`].
+    ///
+    /// The layer store is not bounded if not set.
+    #[serde(default)]
+    pub layer_cache: Option<LayerCacheConfig>,
+
     /// Nydus services configuration
-    #[serde(rename = "nydus")]
+    #[serde(rename = "nydus", default)]
     pub nydus_config: Option<NydusConfig>,
+
+    /// Eccfs snapshotter configuration
+    #[serde(rename = "eccfs", default)]
+    pub eccfs_config: Option<EccfsConfig>,
+
+    /// Priority the layers are unpacked and the snapshots are built at, so
+    /// that pulls don't starve the running containers.
+    ///
+    /// The work runs at the priority of the process if not set.
+    #[serde(default)]
+    pub background_priority: Option<BackgroundPriority>,
+
+    /// Relabeling of the rootfs once mounted, e.g. set per pull for the
+    /// container the image is pulled for, see [`crate::relabel`].
+    ///
+    /// The rootfs keeps the owners and labels of the image if not set.
+    #[serde(default)]
+    pub rootfs_relabel: Option<RelabelConfig>,
+
+    /// Image volumes configuration, see [`crate::volume`].
+    #[serde(default)]
+    pub image_volume: Option<ImageVolumeConfig>,
 }
 
 /// This function used to parse from string. When it is an
@@ -80,6 +244,64 @@ where
     Ok(opt.unwrap_or_default())
 }
 
+fn default_max_layer_retries() -> usize {
+    DEFAULT_MAX_LAYER_RETRIES
+}
+
+fn default_max_concurrent_unpack() -> usize {
+    DEFAULT_MAX_CONCURRENT_UNPACK
+}
+
+fn default_max_quarantined_layers() -> usize {
+    DEFAULT_MAX_QUARANTINED_LAYERS
+}
+
+/// Set the fields of `config` named by the variables of `vars` starting
+/// with [`ENV_PREFIX`], see [`ImageConfig::load`].
+fn apply_env_overrides(
+    config: &mut Value,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<()> {
+    // tables are set before the fields within them
+    let mut overrides: Vec<_> = vars
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+        .collect();
+    overrides.sort();
+
+    for (name, raw) in overrides {
+        let path: Vec<_> = name[ENV_PREFIX.len()..]
+            .split("__")
+            .map(str::to_lowercase)
+            .collect();
+        if path.iter().any(String::is_empty) {
+            bail!("config override {name} names an empty field");
+        }
+
+        let value = serde_json::from_str(&raw).unwrap_or(Value::String(raw));
+        let (field, tables) = path.split_last().expect("split yields a field");
+        let mut table = &mut *config;
+        for key in tables {
+            let child = table
+                .as_object_mut()
+                .ok_or_else(|| anyhow!("config override {name}: parent of {key} is not a table"))?
+                .entry(key.clone())
+                .or_insert(Value::Null);
+            if child.is_null() {
+                *child = Value::Object(Map::new());
+            }
+            table = child;
+        }
+
+        table
+            .as_object_mut()
+            .ok_or_else(|| anyhow!("config override {name}: parent of {field} is not a table"))?
+            .insert(field.clone(), value);
+    }
+
+    Ok(())
+}
+
 impl Default for ImageConfig {
     // Construct a default instance of `ImageConfig`
     fn default() -> ImageConfig {
@@ -93,10 +315,31 @@ impl Default for ImageConfig {
             auth: false,
             file_paths: Paths::default(),
             max_concurrent_download: DEFAULT_MAX_CONCURRENT_DOWNLOAD,
+            max_layer_retries: DEFAULT_MAX_LAYER_RETRIES,
+            max_concurrent_unpack: DEFAULT_MAX_CONCURRENT_UNPACK,
+            quarantine_dir: None,
+            max_quarantined_layers: DEFAULT_MAX_QUARANTINED_LAYERS,
+            platform: None,
+            reference_policy: ReferencePolicy::default(),
+            freshness: None,
+            pull_policy: PullPolicy::default(),
+            proxy: None,
+            registries: HashMap::new(),
+            manifest_cache: None,
+            layer_key_cache: None,
+            blob_cache: None,
+            pull_budget: None,
+            layer_storage: HashMap::new(),
+            disk_space: None,
+            layer_cache: None,
             #[cfg(feature = "nydus")]
             nydus_config: Some(NydusConfig::default()),
             #[cfg(not(feature = "nydus"))]
             nydus_config: None,
+            eccfs_config: None,
+            background_priority: None,
+            rootfs_relabel: None,
+            image_volume: None,
         }
     }
 }
@@ -107,21 +350,11 @@ impl TryFrom<&Path> for ImageConfig {
     ///        "work_dir": "/var/lib/image-rs/",
     ///        "default_snapshot": "overlay"
     ///    }
+    ///
+    /// See [`ImageConfig::load`].
     type Error = anyhow::Error;
     fn try_from(config_path: &Path) -> Result<Self, Self::Error> {
-        let file = File::open(config_path)
-            .map_err(|e| anyhow!("failed to open config file {}", e.to_string()))?;
-
-        match serde_json::from_reader::<File, ImageConfig>(file) {
-            Ok(image_config) => {
-                if image_config.validate() {
-                    Ok(image_config)
-                } else {
-                    Err(anyhow!("invalid configuration"))
-                }
-            }
-            Err(e) => Err(anyhow!("failed to parse config file {}", e.to_string())),
-        }
+        Self::load(config_path)
     }
 }
 
@@ -133,15 +366,181 @@ impl ImageConfig {
             ..Default::default()
         }
     }
-    /// Validate the configuration object.
-    pub fn validate(&self) -> bool {
+
+    /// The default configuration of `snapshot`. It differs from
+    /// [`ImageConfig::default`] in the fields specific to the snapshotter,
+    /// e.g. the eccfs one comes with an [`EccfsConfig`].
+    pub fn default_for(snapshot: SnapshotType) -> Self {
+        Self {
+            default_snapshot: snapshot,
+            #[cfg(feature = "snapshot-eccfs")]
+            eccfs_config: (snapshot == SnapshotType::Eccfs).then(EccfsConfig::default),
+            ..Default::default()
+        }
+    }
+
+    /// Load the configuration file at `path`, TOML if it ends with `.toml`
+    /// and JSON otherwise.
+    ///
+    /// The environment variables starting with [`ENV_PREFIX`] override the
+    /// fields of the file: the rest of the name is the lowercase path to the
+    /// field, with `__` between the nested fields, e.g.
+    /// `IMAGE_RS_ECCFS__DETERMINISTIC=true`. Values are parsed as JSON, or
+    /// taken as strings if they are not JSON, e.g. `IMAGE_RS_WORK_DIR=/run`.
+    ///
+    /// The result is checked with [`ImageConfig::validate`].
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        let mut value: Value = if path.extension().map_or(false, |ext| ext == "toml") {
+            toml::from_str(&data)
+                .with_context(|| format!("failed to parse config file {}", path.display()))?
+        } else {
+            serde_json::from_str(&data)
+                .with_context(|| format!("failed to parse config file {}", path.display()))?
+        };
+
+        apply_env_overrides(&mut value, std::env::vars())?;
+        Self::from_value(value).with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    /// Like [`ImageConfig::load`], but a missing file is the default
+    /// configuration with the environment overrides.
+    pub fn load_or_default(path: &Path) -> Result<Self> {
+        match path.try_exists() {
+            Ok(false) => Self::from_env(),
+            _ => Self::load(path),
+        }
+    }
+
+    /// The default configuration with the environment overrides of
+    /// [`ImageConfig::load`].
+    pub fn from_env() -> Result<Self> {
+        let mut value = Value::Object(Map::new());
+        apply_env_overrides(&mut value, std::env::vars())?;
+        Self::from_value(value).context("invalid config overrides")
+    }
+
+    fn from_value(value: Value) -> Result<Self> {
+        let mut config: Self = serde_json::from_value(value)?;
+        let profile = Self::default_for(config.default_snapshot);
+        if config.eccfs_config.is_none() {
+            config.eccfs_config = profile.eccfs_config;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Validate the configuration object. Callers building the
+    /// configuration themselves should run it at startup, the loaders of
+    /// files do.
+    pub fn validate(&self) -> Result<()> {
+        if self.max_concurrent_download == 0 {
+            bail!("max_concurrent_download must be at least 1");
+        }
+
+        if self.max_concurrent_unpack == 0 {
+            bail!("max_concurrent_unpack must be at least 1");
+        }
+
+        if let Some(platform) = &self.platform {
+            let parts: Vec<_> = platform.split('/').collect();
+            if !(2..=3).contains(&parts.len()) || parts.iter().any(|part| part.is_empty()) {
+                bail!("platform {platform:?} is not of the form os/arch[/variant]");
+            }
+        }
+
+        if let Some(proxy) = &self.proxy {
+            if !proxy.address.starts_with("vsock://") {
+                bail!(
+                    "proxy.address {:?} is not of the form vsock://<cid>:<port>",
+                    proxy.address
+                );
+            }
+        }
+
+        for registry in self.registries.keys() {
+            if registry.is_empty()
+                || registry.contains(|c: char| c == '/' || c == '*' || c.is_whitespace())
+            {
+                bail!("registries.{registry:?} is not of the form host[:port]");
+            }
+        }
+
+        if let Some(blob_cache) = &self.blob_cache {
+            if blob_cache.max_bytes == 0 {
+                bail!("blob_cache.max_bytes must be at least 1");
+            }
+        }
+
+        if let Some(pull_budget) = &self.pull_budget {
+            if pull_budget.max_concurrent_downloads == 0 {
+                bail!("pull_budget.max_concurrent_downloads must be at least 1");
+            }
+            if pull_budget.max_bytes_per_second == Some(0) {
+                bail!("pull_budget.max_bytes_per_second must be at least 1");
+            }
+        }
+
+        for (snapshot, storage) in &self.layer_storage {
+            if storage.tmpfs_size == 0 {
+                bail!("layer_storage.{snapshot}.tmpfs_size must be at least 1");
+            }
+        }
+
         if let Some(nydus_cfg) = self.nydus_config.as_ref() {
-            if !nydus_cfg.validate() {
-                return false;
+            nydus_cfg.validate().context("invalid nydus config")?;
+        }
+
+        if let Some(eccfs_cfg) = self.eccfs_config.as_ref() {
+            eccfs_cfg.validate().context("invalid eccfs config")?;
+        }
+
+        if let Some(priority) = self.background_priority.as_ref() {
+            priority
+                .validate()
+                .context("invalid background_priority config")?;
+        }
+
+        if let Some(disk_space) = self.disk_space.as_ref() {
+            disk_space.validate().context("invalid disk_space config")?;
+        }
+
+        if let Some(layer_cache) = &self.layer_cache {
+            if layer_cache.max_bytes == 0 {
+                bail!("layer_cache.max_bytes must be at least 1");
             }
         }
 
-        true
+        if let Some(relabel) = self.rootfs_relabel.as_ref() {
+            relabel
+                .validate()
+                .context("invalid rootfs_relabel config")?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the dir where corrupt layers are recorded.
+    pub fn quarantine_dir(&self) -> PathBuf {
+        self.quarantine_dir
+            .clone()
+            .unwrap_or_else(|| self.work_dir.join(DEFAULT_QUARANTINE_DIR))
+    }
+
+    /// Get the snapshot the image volumes are mounted with: the one of
+    /// `image_volume` if set, eccfs if built in, the default snapshot
+    /// otherwise.
+    pub fn volume_snapshot(&self) -> SnapshotType {
+        if let Some(snapshot) = self.image_volume.as_ref().and_then(|v| v.snapshot) {
+            return snapshot;
+        }
+
+        #[cfg(feature = "snapshot-eccfs")]
+        return SnapshotType::Eccfs;
+        #[cfg(not(feature = "snapshot-eccfs"))]
+        return self.default_snapshot;
     }
 
     pub fn get_nydus_config(&self) -> Result<&NydusConfig> {
@@ -152,6 +551,7 @@ impl ImageConfig {
 }
 
 #[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Paths {
     /// sigstore config file for simple signing
     pub sigstore_config: String,
@@ -161,6 +561,12 @@ pub struct Paths {
 
     /// Path to the auth file
     pub auth_file: String,
+
+    /// Path to the file of the client certificates registries are
+    /// authenticated to with, see [`crate::mtls`]. Like the auth file, it
+    /// is only read if `auth` is enabled.
+    #[serde(default)]
+    pub client_certs_file: Option<String>,
 }
 
 impl Default for Paths {
@@ -169,142 +575,912 @@ impl Default for Paths {
             sigstore_config: SIG_STORE_CONFIG_DEFAULT_FILE.into(),
             policy_path: POLICY_FILE_PATH.into(),
             auth_file: AUTH_FILE_PATH.into(),
+            client_certs_file: None,
         }
     }
 }
 
-/// Nydus daemon service configuration
-/// support fs driver including fusedev and fscache.
-#[derive(Clone, Debug, Deserialize)]
-pub struct NydusConfig {
-    /// Type of daemon service
-    #[serde(rename = "type")]
-    pub driver_type: String,
+/// Whether a pull contacts the registry, like the `imagePullPolicy` of a
+/// Kubernetes container. Images preloaded inside the guest, see
+/// [`crate::local`], are always read from their source.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub enum PullPolicy {
+    /// Resolve the reference with the registry on every pull. The image and
+    /// its layers are still reused by digest if pulled before.
+    #[default]
+    Always,
+
+    /// Reuse the image pulled before with the same reference without
+    /// contacting the registry, and pull it if there is none. The registry
+    /// is still asked for the manifest if the image was pulled with other
+    /// credentials, and not anonymously.
+    IfNotPresent,
+
+    /// Only use the images pulled before, failing the pulls of the others
+    /// and of the ones pulled with other credentials, and not anonymously.
+    Never,
+}
 
-    /// Service instance identifier
-    pub id: Option<String>,
+impl FromStr for PullPolicy {
+    type Err = anyhow::Error;
 
-    /// Fuse service configuration
-    #[serde(rename = "fuse")]
-    pub fuse_config: Option<FuseConfig>,
+    fn from_str(policy: &str) -> Result<Self> {
+        serde_json::from_value(Value::String(policy.to_string())).map_err(|_| {
+            anyhow!("unknown pull policy {policy:?}, expected Always, IfNotPresent or Never")
+        })
+    }
+}
 
-    /// Fscache service configuration
-    #[serde(rename = "fscache")]
-    pub fscache_config: Option<FscacheConfig>,
+/// Forward proxy on the host, reached over vsock.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ProxyConfig {
+    /// vsock address of the proxy, `vsock://<cid>:<port>`.
+    pub address: String,
+
+    /// Credential `username:password` the proxy authenticates the guest
+    /// with.
+    #[serde(default)]
+    pub credential: Option<String>,
+
+    /// Hosts reached directly, in the format of `NO_PROXY`.
+    #[serde(default)]
+    pub no_proxy: Option<String>,
 }
 
-impl Default for NydusConfig {
+/// TLS policy of a registry listed in [`ImageConfig::registries`].
+///
+/// It applies to the pulls from that registry alone, so that a test
+/// registry or a mirror on loopback doesn't weaken the pulls from the
+/// others. The http client of such a pull is shared with the token realm of
+/// the registry, which gets the same policy.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RegistryConfig {
+    /// Accept any certificate of the registry, e.g. a self-signed one.
+    #[serde(default)]
+    pub insecure_skip_tls_verify: bool,
+
+    /// Talk plain http to the registry, e.g. to an in-VM mirror. Hosts are
+    /// only reached in plaintext when listed by their exact `host[:port]`,
+    /// and the pulls in plaintext from hosts other than loopback ones are
+    /// logged as warnings.
+    #[serde(default)]
+    pub plain_http: bool,
+}
+
+impl RegistryConfig {
+    /// Whether `registry` (`host[:port]`) is a loopback host, where plain
+    /// http doesn't leave the VM.
+    pub fn is_loopback(registry: &str) -> bool {
+        let host = match registry.strip_prefix('[') {
+            Some(rest) => rest.split(']').next().unwrap_or_default(),
+            None => registry.split(':').next().unwrap_or_default(),
+        };
+        host == "localhost"
+            || host
+                .parse::<std::net::IpAddr>()
+                .map_or(false, |ip| ip.is_loopback())
+    }
+}
+
+/// Default number of seconds a cached manifest of a tag is used before it
+/// is revalidated against the registry.
+pub const DEFAULT_MANIFEST_CACHE_TTL_SECS: u64 = 60;
+
+/// Default max number of references with a cached manifest.
+pub const DEFAULT_MANIFEST_CACHE_MAX_ENTRIES: usize = 64;
+
+/// Manifest cache configuration.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestCacheConfig {
+    /// Seconds a cached manifest of a tag is used before the registry is
+    /// asked whether the tag still points to it. Manifests of digest
+    /// references never change, so they are not revalidated.
+    #[serde(default = "default_manifest_cache_ttl_secs")]
+    pub ttl_secs: u64,
+
+    /// Max number of references with a cached manifest.
+    #[serde(default = "default_manifest_cache_max_entries")]
+    pub max_entries: usize,
+}
+
+impl Default for ManifestCacheConfig {
     fn default() -> Self {
         Self {
-            driver_type: "fuse".to_string(),
-            id: None,
-            fuse_config: Some(FuseConfig::default()),
-            fscache_config: None,
+            ttl_secs: DEFAULT_MANIFEST_CACHE_TTL_SECS,
+            max_entries: DEFAULT_MANIFEST_CACHE_MAX_ENTRIES,
         }
     }
 }
 
-impl NydusConfig {
-    /// Check whether the service type is `fuse`
-    pub fn is_fuse(&self) -> bool {
-        self.driver_type == "fuse"
-    }
+fn default_manifest_cache_ttl_secs() -> u64 {
+    DEFAULT_MANIFEST_CACHE_TTL_SECS
+}
 
-    /// Check whether the service type is `fscache`
-    pub fn is_fscache(&self) -> bool {
-        self.driver_type == "fscache"
-    }
+fn default_manifest_cache_max_entries() -> usize {
+    DEFAULT_MANIFEST_CACHE_MAX_ENTRIES
+}
 
-    pub fn get_fuse_config(&self) -> Result<&FuseConfig> {
-        self.fuse_config
-            .as_ref()
-            .ok_or_else(|| anyhow!("no configuration information for fuse"))
-    }
+/// Default number of seconds an unwrapped layer key is cached.
+pub const DEFAULT_LAYER_KEY_CACHE_TTL_SECS: u64 = 300;
 
-    pub fn get_fscache_config(&self) -> Result<&FscacheConfig> {
-        self.fscache_config
-            .as_ref()
-            .ok_or_else(|| anyhow!("no configuration information for fscache"))
-    }
+/// Default max number of layers with a cached key.
+pub const DEFAULT_LAYER_KEY_CACHE_MAX_ENTRIES: usize = 64;
 
-    /// Validate the configuration object.
-    pub fn validate(&self) -> bool {
-        if self.driver_type != "fuse" && self.driver_type != "fscache" {
-            return false;
+/// Layer key cache configuration.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LayerKeyCacheConfig {
+    /// Seconds an unwrapped key is used before it is unwrapped again, so
+    /// that a key revoked on the KBS is not served longer.
+    #[serde(default = "default_layer_key_cache_ttl_secs")]
+    pub ttl_secs: u64,
+
+    /// Max number of layers with a cached key.
+    #[serde(default = "default_layer_key_cache_max_entries")]
+    pub max_entries: usize,
+}
+
+impl Default for LayerKeyCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: DEFAULT_LAYER_KEY_CACHE_TTL_SECS,
+            max_entries: DEFAULT_LAYER_KEY_CACHE_MAX_ENTRIES,
         }
+    }
+}
 
-        if self.is_fuse() && self.fuse_config.is_none() {
-            return false;
+fn default_layer_key_cache_ttl_secs() -> u64 {
+    DEFAULT_LAYER_KEY_CACHE_TTL_SECS
+}
+
+fn default_layer_key_cache_max_entries() -> usize {
+    DEFAULT_LAYER_KEY_CACHE_MAX_ENTRIES
+}
+
+/// Default max bytes of the blobs of the blob cache.
+pub const DEFAULT_BLOB_CACHE_MAX_BYTES: u64 = 4 << 30;
+
+/// Blob cache configuration, see [`crate::blob_cache`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct BlobCacheConfig {
+    /// Dir the blobs are cached in, usually on a volume shared by the
+    /// image-rs instances of the VM.
+    pub dir: PathBuf,
+
+    /// Max bytes of the cached blobs. The least recently used ones are
+    /// evicted to stay below it, and larger blobs are not cached.
+    ///
+    /// This defaults to [`DEFAULT_BLOB_CACHE_MAX_BYTES`].
+    #[serde(default = "default_blob_cache_max_bytes")]
+    pub max_bytes: u64,
+
+    /// Only cache the blobs of encrypted layers, so that the cache never
+    /// holds layers readable without the keys.
+    #[serde(default)]
+    pub encrypted_only: bool,
+}
+
+fn default_blob_cache_max_bytes() -> u64 {
+    DEFAULT_BLOB_CACHE_MAX_BYTES
+}
+
+/// Default max number of layers downloaded at once by all the pulls of a
+/// client.
+pub const DEFAULT_BUDGET_MAX_CONCURRENT_DOWNLOADS: usize = 8;
+
+/// Download budget configuration, see [`crate::pull_budget`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PullBudgetConfig {
+    /// Max number of layers downloaded at once by all the pulls.
+    ///
+    /// This defaults to [`DEFAULT_BUDGET_MAX_CONCURRENT_DOWNLOADS`].
+    #[serde(default = "default_budget_max_concurrent_downloads")]
+    pub max_concurrent_downloads: usize,
+
+    /// Max bytes per second read from the registries by all the pulls.
+    ///
+    /// The bandwidth is not limited if not set.
+    #[serde(default)]
+    pub max_bytes_per_second: Option<u64>,
+}
+
+fn default_budget_max_concurrent_downloads() -> usize {
+    DEFAULT_BUDGET_MAX_CONCURRENT_DOWNLOADS
+}
+
+/// Layer storage of a snapshotter.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LayerStorageConfig {
+    /// Size cap in bytes of the tmpfs layers are unpacked into.
+    pub tmpfs_size: u64,
+
+    /// Dir on encrypted scratch storage, where layers not fitting into the
+    /// tmpfs are unpacked.
+    pub spill_dir: PathBuf,
+}
+
+/// Default ratio of the unpacked size of a layer to its compressed size.
+pub const DEFAULT_EXPANSION_FACTOR: f64 = 3.0;
+
+/// Default share of the unpacked size of the layers the eccfs roimages
+/// take in addition.
+pub const DEFAULT_ECCFS_OVERHEAD: f64 = 1.1;
+
+/// Disk space checks of pulls, see [`crate::disk_space`].
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DiskSpaceConfig {
+    /// Ratio of the unpacked size of a layer to its compressed size the
+    /// estimate of a pull assumes.
+    ///
+    /// This defaults to [`DEFAULT_EXPANSION_FACTOR`].
+    #[serde(default = "default_expansion_factor")]
+    pub expansion_factor: f64,
+
+    /// Share of the unpacked size of the layers the eccfs snapshotter needs
+    /// in addition for the roimages.
+    ///
+    /// This defaults to [`DEFAULT_ECCFS_OVERHEAD`].
+    #[serde(default = "default_eccfs_overhead")]
+    pub eccfs_overhead: f64,
+
+    /// Bytes always kept free on the layer storage. Pulls which would eat
+    /// into it fail.
+    #[serde(default)]
+    pub reserve: u64,
+
+    /// Remove the layers no pulled image uses anymore when a pull does not
+    /// fit otherwise.
+    #[serde(default)]
+    pub evict_unused_layers: bool,
+}
+
+impl DiskSpaceConfig {
+    /// Layers don't shrink when unpacked, and eccfs only adds to them.
+    pub fn validate(&self) -> Result<()> {
+        if self.expansion_factor < 1.0 {
+            bail!(
+                "expansion_factor is {}, set it to 1.0 or more",
+                self.expansion_factor
+            );
         }
 
-        if self.is_fscache() && self.fscache_config.is_none() {
-            return false;
+        if self.eccfs_overhead < 0.0 {
+            bail!(
+                "eccfs_overhead is {}, set it to 0.0 or more",
+                self.eccfs_overhead
+            );
         }
 
-        true
+        Ok(())
     }
 }
 
-/// Nydus daemon fs backend configuration for fusedev
-#[derive(Clone, Debug, Deserialize)]
-pub struct FuseConfig {
-    /// FUSE server failover policy
-    pub fail_over_policy: String,
+fn default_expansion_factor() -> f64 {
+    DEFAULT_EXPANSION_FACTOR
+}
 
-    /// Number of worker threads to serve FUSE I/O requests
-    pub fuse_threads: u32,
+fn default_eccfs_overhead() -> f64 {
+    DEFAULT_ECCFS_OVERHEAD
+}
 
-    /// Path to the `localfs` working directory, which also enables the `localfs` storage backend
-    pub localfs_dir: Option<PathBuf>,
+/// Order the layers of the layer store are evicted in.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EvictionPolicy {
+    /// The least recently used layers first.
+    #[default]
+    Lru,
+
+    /// The least frequently used layers first, and the least recently used
+    /// of the ones used as often.
+    Lfu,
+}
 
-    /// Path to the prefetch configuration file
-    pub prefetch_files: Option<Vec<String>>,
+/// Layer store budget configuration, see [`crate::layer_cache`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LayerCacheConfig {
+    /// Max bytes of the unpacked layers and of the blobs of the blob cache
+    /// if any. Layers are evicted after every pull until they fit, or only
+    /// the layers which cannot be evicted are left.
+    pub max_bytes: u64,
 
-    /// Mountpoint within the FUSE/virtiofs device to mount the RAFS/passthroughfs filesystem
-    pub virtual_mountpoint: Option<PathBuf>,
+    /// Order the layers are evicted in.
+    ///
+    /// This defaults to [`EvictionPolicy::Lru`].
+    #[serde(default)]
+    pub policy: EvictionPolicy,
+
+    /// References or ids of the images whose layers are never evicted, in
+    /// addition to the ones pinned with
+    /// [`crate::image::ImageClient::pin_image`].
+    #[serde(default)]
+    pub pinned_images: Vec<String>,
 }
 
-impl Default for FuseConfig {
-    fn default() -> Self {
-        Self {
-            fail_over_policy: "flush".to_string(),
-            fuse_threads: 4,
-            localfs_dir: None,
-            prefetch_files: None,
-            virtual_mountpoint: Some(PathBuf::from("/")),
-        }
-    }
+/// Default KBS resource holding the key used by deterministic eccfs builds.
+pub const ECCFS_BUILD_KEY_URI: &str = "kbs:///default/eccfs-key/test";
+
+/// Eccfs snapshotter configuration.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct EccfsConfig {
+    /// Build the roimages reproducibly: the keys are derived from a fixed
+    /// key fetched from the KBS instead of being random, and the timestamps
+    /// of the layer files are normalized before conversion. The same image
+    /// then always results in the same roimages, whose digests can be
+    /// referenced in attestation evidence.
+    #[serde(default)]
+    pub deterministic: bool,
+
+    /// KBS resource uri of the 16 bytes build key.
+    ///
+    /// This defaults to [`ECCFS_BUILD_KEY_URI`].
+    #[serde(default)]
+    pub key_uri: Option<String>,
+
+    /// Unix timestamp all the file times are set to in deterministic mode.
+    #[serde(default)]
+    pub source_date_epoch: i64,
+
+    /// Resource uri of the keys to build the images with instead of the
+    /// generated ones, see [`SuppliedKeys`](crate::snapshots::eccfs::SuppliedKeys).
+    /// The `io.confidential-containers.eccfs.keys` annotation of an image
+    /// manifest takes precedence over this if it names one of
+    /// `allowed_keys_uris`.
+    #[serde(default)]
+    pub keys_uri: Option<String>,
+
+    /// KBS resource uris the `io.confidential-containers.eccfs.keys`
+    /// annotation of an image manifest may name. The manifest is not
+    /// trusted, so the annotation is ignored unless its uri is listed here.
+    #[serde(default)]
+    pub allowed_keys_uris: Vec<String>,
+
+    /// Keep the roimages of a container when it is unmounted, so that they
+    /// can be reused. The keys of the roimages are removed anyway, so this
+    /// is meant for deterministic builds, whose keys can be derived again,
+    /// or for supplied keys, which can be fetched again.
+    #[serde(default)]
+    pub retain_artifacts: bool,
+
+    /// Build the roimage of a layer once, and share it read-only among the
+    /// containers using the layer, e.g. the sidecars of a pod. The shared
+    /// roimage keeps its key, and is removed with the last container using
+    /// it, as recorded in the meta store. Only the roimages whose keys are
+    /// derived in `deterministic` mode, or supplied, are shared, and layers
+    /// supplied with another key than the shared one still get their own
+    /// roimage.
+    #[serde(default)]
+    pub share_layers: bool,
+
+    /// Convert the small layers in memory, and only the large ones on the
+    /// scratch storage. Every layer goes through the scratch storage if not
+    /// set.
+    #[serde(default)]
+    pub hybrid: Option<HybridPolicy>,
+
+    /// Storage backing the work dir the layers are converted in, which
+    /// holds their plaintext content during the conversion.
+    #[serde(default)]
+    pub scratch: ScratchBacking,
+
+    /// Read back the roimages built by a mount and check them against
+    /// their layers before the mount succeeds. Not checked if not set.
+    #[serde(default)]
+    pub verify: Option<BuildVerification>,
 }
 
-/// Nydus daemon fs backend configuration for fusedev
-#[derive(Clone, Debug, Deserialize)]
-pub struct FscacheConfig {
-    /// Working directory for Linux fscache driver to store cache files
-    pub fscache: Option<PathBuf>,
+/// Storage backing the eccfs work dir. A tmpfs by default: the disk dir may
+/// be visible to the host, the others keep the plaintext layer content off
+/// untrusted storage.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum ScratchBacking {
+    /// A plain dir on the root filesystem, which may be visible to the host.
+    Disk,
+
+    /// A tmpfs per container, in guest memory. The default.
+    Tmpfs {
+        /// Size cap in bytes of the tmpfs. Half of the memory if not set.
+        #[serde(default)]
+        size: Option<u64>,
+    },
+
+    /// A dm-crypt device opened with a random key when the first container
+    /// is mounted, so its content is lost with the process.
+    DmCrypt {
+        /// Block device the encrypted scratch is created on. Its content
+        /// is overwritten.
+        device: PathBuf,
+    },
+
+    /// A sefs per container, encrypted by the enclave, inside Occlum. Its
+    /// lower dir on the host is created by each mount and removed with the
+    /// scratch dir.
+    Sefs,
+}
 
-    /// Working directory for Linux fscache driver to store cache files
-    pub fscache_tag: Option<String>,
+impl Default for ScratchBacking {
+    fn default() -> Self {
+        ScratchBacking::Tmpfs { size: None }
+    }
+}
 
-    /// Number of working threads to serve fscache requests
-    pub fscache_threads: u32,
+/// Default number of entries of a layer compared with its roimage by
+/// [`BuildVerification`].
+pub const DEFAULT_VERIFY_SAMPLES: usize = 64;
+
+/// Verification of the roimages built by the eccfs snapshotter, to catch
+/// builder or key regressions before the workload starts rather than in
+/// the enclave. Each roimage is reopened with its key, which checks its
+/// root MAC, and a sample of the entries of its layer is compared with the
+/// ones read from the roimage.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct BuildVerification {
+    /// Number of entries of a layer compared, spread evenly over the
+    /// layer. With 0, only the root MAC is checked.
+    #[serde(default = "default_verify_samples")]
+    pub samples: usize,
 }
 
-impl Default for FscacheConfig {
+impl Default for BuildVerification {
     fn default() -> Self {
         Self {
-            fscache: None,
-            fscache_tag: None,
-            fscache_threads: 4,
+            samples: DEFAULT_VERIFY_SAMPLES,
         }
     }
 }
 
-#[cfg(feature = "snapshot-overlayfs")]
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs::File;
-    use std::io::prelude::*;
+fn default_verify_samples() -> usize {
+    DEFAULT_VERIFY_SAMPLES
+}
+
+/// Policy of the hybrid mode of the eccfs snapshotter. The enclave only
+/// reads roimages, so every layer is converted, but going through the
+/// scratch storage is hardly worth it for small layers (config files,
+/// entrypoint scripts): they are converted in a tmpfs instead.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct HybridPolicy {
+    /// Layers whose unpacked size in bytes is below this are converted in a
+    /// tmpfs of the container instead of the scratch storage.
+    pub small_layer_threshold: u64,
+
+    /// Max bytes of the layers of a container converted in memory. Small
+    /// layers not fitting in anymore go through the scratch storage as
+    /// well. Unlimited if not set.
+    #[serde(default)]
+    pub memory_budget: Option<u64>,
+}
+
+impl EccfsConfig {
+    /// Get the KBS resource uri of the build key.
+    pub fn key_uri(&self) -> &str {
+        self.key_uri.as_deref().unwrap_or(ECCFS_BUILD_KEY_URI)
+    }
+
+    /// Get the resource uri of the supplied keys of an image annotated with
+    /// `annotated`: the annotated one if it is allowed, the configured one
+    /// otherwise.
+    pub fn keys_uri_for<'a>(&'a self, annotated: Option<&'a str>) -> Option<&'a str> {
+        match annotated {
+            Some(uri) if self.allowed_keys_uris.iter().any(|allowed| allowed == uri) => Some(uri),
+            Some(uri) => {
+                warn!("ignoring eccfs keys annotation {uri:?}, which is not allowed");
+                self.keys_uri.as_deref()
+            }
+            None => self.keys_uri.as_deref(),
+        }
+    }
+
+    /// Validate the configuration object.
+    pub fn validate(&self) -> Result<()> {
+        if self.deterministic && self.source_date_epoch < 0 {
+            bail!(
+                "source_date_epoch is {}, set it to a unix timestamp of 0 or later",
+                self.source_date_epoch
+            );
+        }
+
+        if let Some(hybrid) = &self.hybrid {
+            if hybrid.memory_budget == Some(0) {
+                bail!("hybrid.memory_budget is 0, leave it unset for no limit");
+            }
+        }
+
+        if let ScratchBacking::Tmpfs { size: Some(0) } = self.scratch {
+            bail!("scratch.size is 0, leave it unset for half of the memory");
+        }
+
+        for uri in &self.allowed_keys_uris {
+            if !uri.starts_with("kbs://") {
+                bail!("allowed_keys_uris has {uri:?}, only KBS resource uris are allowed");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// IO scheduling class, see `ioprio_set(2)`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoClass {
+    /// Served by priority level, alongside the other processes.
+    BestEffort,
+
+    /// Only served when no other process needs the disk.
+    Idle,
+}
+
+/// Priority of the threads unpacking layers and building snapshots, see
+/// [`crate::priority`].
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct BackgroundPriority {
+    /// Nice value of the threads, from -20 (highest) to 19 (lowest).
+    #[serde(default)]
+    pub nice: Option<i32>,
+
+    /// IO scheduling class of the threads.
+    #[serde(default)]
+    pub io_class: Option<IoClass>,
+
+    /// Level within the best-effort IO class, from 0 (highest) to 7
+    /// (lowest). Ignored for the idle class.
+    #[serde(default)]
+    pub io_level: u8,
+}
+
+impl BackgroundPriority {
+    /// Validate the configuration object.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(nice) = self.nice {
+            if !(-20..=19).contains(&nice) {
+                bail!("nice is {nice}, set it from -20 to 19");
+            }
+        }
+
+        if self.io_level > 7 {
+            bail!("io_level is {}, set it from 0 to 7", self.io_level);
+        }
+
+        Ok(())
+    }
+}
+
+/// Default number of ids shifted by an [`IdShift`], the 16 bit ids of a
+/// regular image.
+pub const DEFAULT_ID_SHIFT_SIZE: u32 = 65536;
+
+/// How the owners of the rootfs are shifted.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdShiftMode {
+    /// An idmapped mount, or chown of the files if the kernel or the
+    /// filesystem of the snapshotter can't idmap it.
+    #[default]
+    Auto,
+
+    /// An idmapped mount only.
+    Idmap,
+
+    /// Chown of the files only.
+    Chown,
+}
+
+/// Shift of the owners of the rootfs, for a container running in a user
+/// namespace. The ids `0..size` of the image are shifted to
+/// `uid..uid + size` and `gid..gid + size`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct IdShift {
+    /// UID the UID 0 of the image is shifted to.
+    pub uid: u32,
+
+    /// GID the GID 0 of the image is shifted to.
+    pub gid: u32,
+
+    /// Number of ids shifted.
+    ///
+    /// This defaults to [`DEFAULT_ID_SHIFT_SIZE`].
+    #[serde(default = "default_id_shift_size")]
+    pub size: u32,
+
+    #[serde(default)]
+    pub mode: IdShiftMode,
+}
+
+fn default_id_shift_size() -> u32 {
+    DEFAULT_ID_SHIFT_SIZE
+}
+
+impl FromStr for IdShift {
+    type Err = anyhow::Error;
+
+    /// Parse `<uid>:<gid>[:<size>]`, shifted in the [`IdShiftMode::Auto`]
+    /// mode.
+    fn from_str(shift: &str) -> Result<Self> {
+        let parts: Vec<_> = shift.split(':').collect();
+        let parse = |part: &str| {
+            part.parse::<u32>()
+                .map_err(|_| anyhow!("id shift {shift:?} is not of the form <uid>:<gid>[:<size>]"))
+        };
+        let (uid, gid, size) = match parts[..] {
+            [uid, gid] => (parse(uid)?, parse(gid)?, DEFAULT_ID_SHIFT_SIZE),
+            [uid, gid, size] => (parse(uid)?, parse(gid)?, parse(size)?),
+            _ => bail!("id shift {shift:?} is not of the form <uid>:<gid>[:<size>]"),
+        };
+
+        Ok(Self {
+            uid,
+            gid,
+            size,
+            mode: IdShiftMode::Auto,
+        })
+    }
+}
+
+/// Relabeling of the rootfs once mounted, see [`crate::relabel`].
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RelabelConfig {
+    /// SELinux context of the files of the rootfs, e.g.
+    /// `system_u:object_r:container_file_t:s0:c1,c2`.
+    #[serde(default)]
+    pub selinux_label: Option<String>,
+
+    /// Shift of the owners of the files of the rootfs.
+    #[serde(default)]
+    pub id_shift: Option<IdShift>,
+
+    /// SELinux contexts a pull request may ask for instead of
+    /// `selinux_label`. A request may only ask for `selinux_label` itself
+    /// if empty.
+    #[serde(default)]
+    pub allowed_selinux_labels: Vec<String>,
+
+    /// Shifts a pull request may ask for instead of `id_shift`, matched by
+    /// their ids, and done in their mode. A request may only ask for
+    /// `id_shift` itself if empty.
+    #[serde(default)]
+    pub allowed_id_shifts: Vec<IdShift>,
+}
+
+impl RelabelConfig {
+    /// Validate the configuration object.
+    pub fn validate(&self) -> Result<()> {
+        for label in self
+            .selinux_label
+            .iter()
+            .chain(&self.allowed_selinux_labels)
+        {
+            if label.split(':').count() < 3 || label.contains('\0') {
+                bail!("selinux_label {label:?} is not of the form user:role:type[:level]");
+            }
+        }
+
+        for shift in self.id_shift.iter().chain(&self.allowed_id_shifts) {
+            if shift.size == 0 {
+                bail!("id_shift.size is 0, set it to 1 or more");
+            }
+            if shift.uid.checked_add(shift.size - 1).is_none()
+                || shift.gid.checked_add(shift.size - 1).is_none()
+            {
+                bail!(
+                    "id_shift of {} ids from {}:{} is beyond the 32 bit ids",
+                    shift.size,
+                    shift.uid,
+                    shift.gid
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Relabel with the SELinux context `label` a pull request asks for,
+    /// which must be the configured or an allowed one.
+    pub fn request_selinux_label(&mut self, label: &str) -> Result<()> {
+        if self.selinux_label.as_deref() != Some(label)
+            && !self.allowed_selinux_labels.iter().any(|l| l == label)
+        {
+            bail!("selinux_label {label:?} is not allowed");
+        }
+
+        self.selinux_label = Some(label.to_string());
+        Ok(())
+    }
+
+    /// Shift the ids as `shift`, which a pull request asks for, does. It must
+    /// have the ids of the configured or an allowed shift, whose mode is
+    /// taken.
+    pub fn request_id_shift(&mut self, shift: &IdShift) -> Result<()> {
+        let ids = |s: &IdShift| (s.uid, s.gid, s.size);
+        let allowed = self
+            .id_shift
+            .iter()
+            .chain(&self.allowed_id_shifts)
+            .find(|allowed| ids(allowed) == ids(shift))
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "id_shift of {} ids from {}:{} is not allowed",
+                    shift.size,
+                    shift.uid,
+                    shift.gid
+                )
+            })?;
+
+        self.id_shift = Some(allowed);
+        Ok(())
+    }
+}
+
+/// Image volumes configuration, see [`crate::volume`].
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ImageVolumeConfig {
+    /// Snapshot the volumes are mounted with.
+    ///
+    /// This defaults to eccfs if built in, to `default_snapshot` otherwise.
+    #[serde(default)]
+    pub snapshot: Option<SnapshotType>,
+
+    /// Build the eccfs roimages of every volume encrypted. The roimages of
+    /// the volumes of images without encrypted layers are only integrity
+    /// protected otherwise.
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+/// Nydus daemon service configuration
+/// support fs driver including fusedev and fscache.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NydusConfig {
+    /// Type of daemon service
+    #[serde(rename = "type")]
+    pub driver_type: String,
+
+    /// Service instance identifier
+    pub id: Option<String>,
+
+    /// Fuse service configuration
+    #[serde(rename = "fuse")]
+    pub fuse_config: Option<FuseConfig>,
+
+    /// Fscache service configuration
+    #[serde(rename = "fscache")]
+    pub fscache_config: Option<FscacheConfig>,
+}
+
+impl Default for NydusConfig {
+    fn default() -> Self {
+        Self {
+            driver_type: "fuse".to_string(),
+            id: None,
+            fuse_config: Some(FuseConfig::default()),
+            fscache_config: None,
+        }
+    }
+}
+
+impl NydusConfig {
+    /// Check whether the service type is `fuse`
+    pub fn is_fuse(&self) -> bool {
+        self.driver_type == "fuse"
+    }
+
+    /// Check whether the service type is `fscache`
+    pub fn is_fscache(&self) -> bool {
+        self.driver_type == "fscache"
+    }
+
+    pub fn get_fuse_config(&self) -> Result<&FuseConfig> {
+        self.fuse_config
+            .as_ref()
+            .ok_or_else(|| anyhow!("no configuration information for fuse"))
+    }
+
+    pub fn get_fscache_config(&self) -> Result<&FscacheConfig> {
+        self.fscache_config
+            .as_ref()
+            .ok_or_else(|| anyhow!("no configuration information for fscache"))
+    }
+
+    /// Validate the configuration object.
+    pub fn validate(&self) -> Result<()> {
+        if !self.is_fuse() && !self.is_fscache() {
+            bail!(
+                "type is {:?}, set it to \"fuse\" or \"fscache\"",
+                self.driver_type
+            );
+        }
+
+        if self.is_fuse() && self.fuse_config.is_none() {
+            bail!("type is \"fuse\", but the fuse config is missing");
+        }
+
+        if self.is_fscache() && self.fscache_config.is_none() {
+            bail!("type is \"fscache\", but the fscache config is missing");
+        }
+
+        Ok(())
+    }
+}
+
+/// Nydus daemon fs backend configuration for fusedev
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FuseConfig {
+    /// FUSE server failover policy
+    pub fail_over_policy: String,
+
+    /// Number of worker threads to serve FUSE I/O requests
+    pub fuse_threads: u32,
+
+    /// Path to the `localfs` working directory, which also enables the `localfs` storage backend
+    pub localfs_dir: Option<PathBuf>,
+
+    /// Path to the prefetch configuration file
+    pub prefetch_files: Option<Vec<String>>,
+
+    /// Mountpoint within the FUSE/virtiofs device to mount the RAFS/passthroughfs filesystem
+    pub virtual_mountpoint: Option<PathBuf>,
+}
+
+impl Default for FuseConfig {
+    fn default() -> Self {
+        Self {
+            fail_over_policy: "flush".to_string(),
+            fuse_threads: 4,
+            localfs_dir: None,
+            prefetch_files: None,
+            virtual_mountpoint: Some(PathBuf::from("/")),
+        }
+    }
+}
+
+/// Nydus daemon fs backend configuration for fusedev
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FscacheConfig {
+    /// Working directory for Linux fscache driver to store cache files
+    pub fscache: Option<PathBuf>,
+
+    /// Working directory for Linux fscache driver to store cache files
+    pub fscache_tag: Option<String>,
+
+    /// Number of working threads to serve fscache requests
+    pub fscache_threads: u32,
+}
+
+impl Default for FscacheConfig {
+    fn default() -> Self {
+        Self {
+            fscache: None,
+            fscache_tag: None,
+            fscache_threads: 4,
+        }
+    }
+}
+
+#[cfg(feature = "snapshot-overlayfs")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::prelude::*;
     use tempfile;
 
     #[test]
@@ -349,6 +1525,16 @@ mod tests {
         assert_eq!(config.work_dir, work_dir);
         assert_eq!(config.default_snapshot, SnapshotType::Overlay);
         assert_eq!(config.max_concurrent_download, 1);
+        assert_eq!(config.max_layer_retries, DEFAULT_MAX_LAYER_RETRIES);
+        assert_eq!(config.max_concurrent_unpack, DEFAULT_MAX_CONCURRENT_UNPACK);
+        assert_eq!(
+            config.quarantine_dir(),
+            work_dir.join(DEFAULT_QUARANTINE_DIR)
+        );
+        assert_eq!(
+            config.max_quarantined_layers,
+            DEFAULT_MAX_QUARANTINED_LAYERS
+        );
 
         let invalid_config_file = tempdir.path().join("does-not-exist");
         assert!(!invalid_config_file.exists());
@@ -405,4 +1591,655 @@ mod tests {
         let _ = ImageConfig::try_from(invalid_config_file.as_path()).is_err();
         assert!(!invalid_config_file.exists());
     }
+
+    #[test]
+    fn test_proxy_config_from_file() {
+        let data = r#"{
+            "work_dir": "/var/lib/image-rs/",
+            "default_snapshot": "overlay",
+            "security_validate": false,
+            "auth": false,
+            "max_concurrent_download": 1,
+            "proxy": {
+                "address": "vsock://2:4443",
+                "credential": "pod:secret"
+            }
+        }"#;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let config_file = tempdir.path().join("config.json");
+        std::fs::write(&config_file, data).unwrap();
+
+        let config = ImageConfig::try_from(config_file.as_path()).unwrap();
+        assert_eq!(
+            config.proxy,
+            Some(ProxyConfig {
+                address: "vsock://2:4443".into(),
+                credential: Some("pod:secret".into()),
+                no_proxy: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_registries_config_from_file() {
+        let data = r#"{
+            "registries": {
+                "localhost:5000": {"plain_http": true},
+                "registry.test": {"insecure_skip_tls_verify": true}
+            }
+        }"#;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let config_file = tempdir.path().join("config.json");
+        std::fs::write(&config_file, data).unwrap();
+
+        let config = ImageConfig::try_from(config_file.as_path()).unwrap();
+        assert_eq!(
+            config.registries["localhost:5000"],
+            RegistryConfig {
+                insecure_skip_tls_verify: false,
+                plain_http: true,
+            }
+        );
+        assert!(config.registries["registry.test"].insecure_skip_tls_verify);
+        assert!(!config.registries["registry.test"].plain_http);
+
+        for registry in ["*", "http://registry.test", "registry.test/library", ""] {
+            let mut config = ImageConfig::default();
+            config
+                .registries
+                .insert(registry.to_string(), RegistryConfig::default());
+            assert!(config.validate().is_err(), "{registry}");
+        }
+
+        for registry in [
+            "localhost",
+            "localhost:5000",
+            "127.0.0.1:5000",
+            "[::1]:5000",
+        ] {
+            assert!(RegistryConfig::is_loopback(registry), "{registry}");
+        }
+        for registry in ["registry.test", "10.0.0.1:5000", "localhost.example.com"] {
+            assert!(!RegistryConfig::is_loopback(registry), "{registry}");
+        }
+    }
+
+    #[cfg(feature = "snapshot-overlayfs")]
+    #[test]
+    fn test_layer_storage_config_from_file() {
+        let data = r#"{
+            "work_dir": "/var/lib/image-rs/",
+            "default_snapshot": "overlay",
+            "security_validate": false,
+            "auth": false,
+            "max_concurrent_download": 1,
+            "layer_storage": {
+                "overlay": {
+                    "tmpfs_size": 1073741824,
+                    "spill_dir": "/run/scratch/layers"
+                }
+            }
+        }"#;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let config_file = tempdir.path().join("config.json");
+        std::fs::write(&config_file, data).unwrap();
+
+        let config = ImageConfig::try_from(config_file.as_path()).unwrap();
+        assert_eq!(
+            config.layer_storage.get(&SnapshotType::Overlay),
+            Some(&LayerStorageConfig {
+                tmpfs_size: 1 << 30,
+                spill_dir: PathBuf::from("/run/scratch/layers"),
+            })
+        );
+    }
+
+    #[test]
+    fn test_blob_cache_config_from_file() {
+        let data = r#"{
+            "work_dir": "/var/lib/image-rs/",
+            "default_snapshot": "unknown",
+            "security_validate": false,
+            "auth": false,
+            "max_concurrent_download": 1,
+            "blob_cache": {
+                "dir": "/run/image-rs/blobs",
+                "encrypted_only": true
+            }
+        }"#;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let config_file = tempdir.path().join("config.json");
+        std::fs::write(&config_file, data).unwrap();
+
+        let config = ImageConfig::try_from(config_file.as_path()).unwrap();
+        assert_eq!(
+            config.blob_cache,
+            Some(BlobCacheConfig {
+                dir: PathBuf::from("/run/image-rs/blobs"),
+                max_bytes: DEFAULT_BLOB_CACHE_MAX_BYTES,
+                encrypted_only: true,
+            })
+        );
+        assert_eq!(ImageConfig::default().blob_cache, None);
+    }
+
+    #[test]
+    fn test_pull_budget_config_from_file() {
+        let data = r#"{
+            "work_dir": "/var/lib/image-rs/",
+            "default_snapshot": "unknown",
+            "security_validate": false,
+            "auth": false,
+            "max_concurrent_download": 3,
+            "pull_budget": {
+                "max_bytes_per_second": 10485760
+            }
+        }"#;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let config_file = tempdir.path().join("config.json");
+        std::fs::write(&config_file, data).unwrap();
+
+        let mut config = ImageConfig::try_from(config_file.as_path()).unwrap();
+        assert_eq!(
+            config.pull_budget,
+            Some(PullBudgetConfig {
+                max_concurrent_downloads: DEFAULT_BUDGET_MAX_CONCURRENT_DOWNLOADS,
+                max_bytes_per_second: Some(10 << 20),
+            })
+        );
+        assert_eq!(ImageConfig::default().pull_budget, None);
+
+        config.pull_budget = Some(PullBudgetConfig {
+            max_concurrent_downloads: 0,
+            max_bytes_per_second: None,
+        });
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_freshness_config_from_file() {
+        let data = r#"{
+            "work_dir": "/var/lib/image-rs/",
+            "default_snapshot": "unknown",
+            "freshness": {
+                "max_age_secs": 2592000,
+                "revocation_uri": "kbs:///default/image-revocations/test"
+            }
+        }"#;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let config_file = tempdir.path().join("config.json");
+        std::fs::write(&config_file, data).unwrap();
+
+        let config = ImageConfig::try_from(config_file.as_path()).unwrap();
+        assert_eq!(
+            config.freshness,
+            Some(FreshnessPolicy {
+                max_age_secs: Some(30 * 24 * 3600),
+                revocation_uri: Some("kbs:///default/image-revocations/test".into()),
+            })
+        );
+        assert_eq!(ImageConfig::default().freshness, None);
+    }
+
+    #[test]
+    fn test_disk_space_config_from_file() {
+        let data = r#"{
+            "work_dir": "/var/lib/image-rs/",
+            "default_snapshot": "unknown",
+            "security_validate": false,
+            "auth": false,
+            "max_concurrent_download": 1,
+            "disk_space": {
+                "reserve": 134217728,
+                "evict_unused_layers": true
+            }
+        }"#;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let config_file = tempdir.path().join("config.json");
+        std::fs::write(&config_file, data).unwrap();
+
+        let config = ImageConfig::try_from(config_file.as_path()).unwrap();
+        assert_eq!(
+            config.disk_space,
+            Some(DiskSpaceConfig {
+                expansion_factor: DEFAULT_EXPANSION_FACTOR,
+                eccfs_overhead: DEFAULT_ECCFS_OVERHEAD,
+                reserve: 128 << 20,
+                evict_unused_layers: true,
+            })
+        );
+        assert_eq!(ImageConfig::default().disk_space, None);
+
+        let invalid = data.replace(r#""reserve""#, r#""expansion_factor": 0.5, "reserve""#);
+        std::fs::write(&config_file, invalid).unwrap();
+        assert!(ImageConfig::try_from(config_file.as_path()).is_err());
+    }
+
+    #[test]
+    fn test_eccfs_config_from_file() {
+        let data = r#"{
+            "work_dir": "/var/lib/image-rs/",
+            "default_snapshot": "overlay",
+            "security_validate": false,
+            "auth": false,
+            "max_concurrent_download": 1,
+            "eccfs": {
+                "deterministic": true,
+                "source_date_epoch": 1,
+                "share_layers": true,
+                "hybrid": {
+                    "small_layer_threshold": 1048576
+                },
+                "scratch": {
+                    "type": "tmpfs",
+                    "size": 1073741824
+                },
+                "verify": {}
+            }
+        }"#;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let config_file = tempdir.path().join("config.json");
+
+        File::create(&config_file)
+            .unwrap()
+            .write_all(data.as_bytes())
+            .unwrap();
+
+        let config = ImageConfig::try_from(config_file.as_path()).unwrap();
+        let eccfs_config = config.eccfs_config.unwrap();
+        assert!(eccfs_config.deterministic);
+        assert_eq!(eccfs_config.source_date_epoch, 1);
+        assert_eq!(eccfs_config.key_uri(), ECCFS_BUILD_KEY_URI);
+        assert!(eccfs_config.share_layers);
+        assert!(!eccfs_config.retain_artifacts);
+        assert_eq!(
+            eccfs_config.hybrid,
+            Some(HybridPolicy {
+                small_layer_threshold: 1048576,
+                memory_budget: None,
+            })
+        );
+        assert_eq!(
+            eccfs_config.scratch,
+            ScratchBacking::Tmpfs {
+                size: Some(1073741824)
+            }
+        );
+        assert_eq!(eccfs_config.verify, Some(BuildVerification::default()));
+    }
+
+    #[test]
+    fn test_eccfs_keys_uri_for() {
+        let eccfs_config = EccfsConfig {
+            keys_uri: Some("kbs:///default/eccfs-keys/all".into()),
+            allowed_keys_uris: vec!["kbs:///default/eccfs-keys/app".into()],
+            ..Default::default()
+        };
+        eccfs_config.validate().unwrap();
+        assert_eq!(
+            eccfs_config.keys_uri_for(Some("kbs:///default/eccfs-keys/app")),
+            Some("kbs:///default/eccfs-keys/app")
+        );
+        assert_eq!(
+            eccfs_config.keys_uri_for(Some("file:///tmp/keys.json")),
+            Some("kbs:///default/eccfs-keys/all")
+        );
+        assert_eq!(
+            eccfs_config.keys_uri_for(None),
+            Some("kbs:///default/eccfs-keys/all")
+        );
+
+        let eccfs_config = EccfsConfig {
+            allowed_keys_uris: vec!["file:///tmp/keys.json".into()],
+            ..Default::default()
+        };
+        assert!(eccfs_config.validate().is_err());
+    }
+
+    #[test]
+    fn test_background_priority_config_from_file() {
+        let data = r#"{
+            "work_dir": "/var/lib/image-rs/",
+            "default_snapshot": "overlay",
+            "security_validate": false,
+            "auth": false,
+            "max_concurrent_download": 1,
+            "background_priority": {
+                "nice": 10,
+                "io_class": "best-effort",
+                "io_level": 7
+            }
+        }"#;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let config_file = tempdir.path().join("config.json");
+        std::fs::write(&config_file, data).unwrap();
+
+        let config = ImageConfig::try_from(config_file.as_path()).unwrap();
+        assert_eq!(
+            config.background_priority,
+            Some(BackgroundPriority {
+                nice: Some(10),
+                io_class: Some(IoClass::BestEffort),
+                io_level: 7,
+            })
+        );
+
+        std::fs::write(&config_file, data.replace("\"nice\": 10", "\"nice\": 20")).unwrap();
+        assert!(ImageConfig::try_from(config_file.as_path()).is_err());
+    }
+
+    #[test]
+    fn test_rootfs_relabel_config_from_file() {
+        let data = r#"{
+            "work_dir": "/var/lib/image-rs/",
+            "default_snapshot": "overlay",
+            "rootfs_relabel": {
+                "selinux_label": "system_u:object_r:container_file_t:s0:c1,c2",
+                "id_shift": {
+                    "uid": 100000,
+                    "gid": 200000,
+                    "mode": "chown"
+                }
+            }
+        }"#;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let config_file = tempdir.path().join("config.json");
+        std::fs::write(&config_file, data).unwrap();
+
+        let config = ImageConfig::try_from(config_file.as_path()).unwrap();
+        assert_eq!(
+            config.rootfs_relabel,
+            Some(RelabelConfig {
+                selinux_label: Some("system_u:object_r:container_file_t:s0:c1,c2".into()),
+                id_shift: Some(IdShift {
+                    uid: 100000,
+                    gid: 200000,
+                    size: DEFAULT_ID_SHIFT_SIZE,
+                    mode: IdShiftMode::Chown,
+                }),
+                ..Default::default()
+            })
+        );
+
+        let shift: IdShift = "1000:2000:100".parse().unwrap();
+        assert_eq!((shift.uid, shift.gid, shift.size), (1000, 2000, 100));
+        assert_eq!(shift.mode, IdShiftMode::Auto);
+        assert_eq!(
+            "1000:2000".parse::<IdShift>().unwrap().size,
+            DEFAULT_ID_SHIFT_SIZE
+        );
+        for shift in ["1000", "1000:x", "1000:2000:100:1", "-1:0"] {
+            assert!(shift.parse::<IdShift>().is_err(), "{shift}");
+        }
+    }
+
+    #[test]
+    fn test_relabel_requests() {
+        let allowed = IdShift {
+            uid: 300000,
+            gid: 300000,
+            size: 65536,
+            mode: IdShiftMode::Idmap,
+        };
+        let mut relabel = RelabelConfig {
+            selinux_label: Some("system_u:object_r:container_file_t:s0".into()),
+            allowed_selinux_labels: vec!["system_u:object_r:container_file_t:s0:c1,c2".into()],
+            allowed_id_shifts: vec![allowed.clone()],
+            ..Default::default()
+        };
+        relabel.validate().unwrap();
+
+        relabel
+            .request_selinux_label("system_u:object_r:container_file_t:s0")
+            .unwrap();
+        relabel
+            .request_selinux_label("system_u:object_r:container_file_t:s0:c1,c2")
+            .unwrap();
+        assert!(relabel
+            .request_selinux_label("system_u:object_r:shadow_t:s0")
+            .is_err());
+        assert_eq!(
+            relabel.selinux_label.as_deref(),
+            Some("system_u:object_r:container_file_t:s0:c1,c2")
+        );
+
+        // the mode of the allowed shift is kept
+        relabel
+            .request_id_shift(&"300000:300000".parse().unwrap())
+            .unwrap();
+        assert_eq!(relabel.id_shift, Some(allowed));
+        assert!(relabel.request_id_shift(&"0:0".parse().unwrap()).is_err());
+        assert!(relabel
+            .request_id_shift(&"300000:300000:100".parse().unwrap())
+            .is_err());
+
+        relabel
+            .allowed_selinux_labels
+            .push("container_file_t".into());
+        assert!(relabel.validate().is_err());
+    }
+
+    #[test]
+    fn test_image_config_from_toml() {
+        let data = r#"
+            work_dir = "/var/lib/image-rs/"
+            default_snapshot = "overlay"
+            max_concurrent_download = 2
+
+            [manifest_cache]
+            ttl_secs = 10
+
+            [layer_key_cache]
+            max_entries = 8
+        "#;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let config_file = tempdir.path().join("config.toml");
+        std::fs::write(&config_file, data).unwrap();
+
+        let config = ImageConfig::load(&config_file).unwrap();
+        assert_eq!(config.default_snapshot, SnapshotType::Overlay);
+        assert_eq!(config.max_concurrent_download, 2);
+        assert!(!config.security_validate);
+        assert_eq!(
+            config.manifest_cache,
+            Some(ManifestCacheConfig {
+                ttl_secs: 10,
+                max_entries: DEFAULT_MANIFEST_CACHE_MAX_ENTRIES,
+            })
+        );
+        assert_eq!(
+            config.layer_key_cache,
+            Some(LayerKeyCacheConfig {
+                ttl_secs: DEFAULT_LAYER_KEY_CACHE_TTL_SECS,
+                max_entries: 8,
+            })
+        );
+        assert_eq!(ImageConfig::default().layer_key_cache, None);
+
+        // a TOML file is no JSON file
+        let json_file = tempdir.path().join("config.json");
+        std::fs::write(&json_file, data).unwrap();
+        assert!(ImageConfig::load(&json_file).is_err());
+
+        let missing = tempdir.path().join("missing.toml");
+        assert!(ImageConfig::load(&missing).is_err());
+        assert_eq!(
+            ImageConfig::load_or_default(&missing).unwrap().work_dir,
+            PathBuf::from(DEFAULT_WORK_DIR)
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        for value in [
+            serde_json::json!({"work_dirr": "/run/image-rs"}),
+            serde_json::json!({"manifest_cache": {"ttl": 10}}),
+            serde_json::json!({"freshness": {"max_age": 60}}),
+            serde_json::json!({"reference_policy": {"pinned_tag": {}}}),
+        ] {
+            assert!(ImageConfig::from_value(value.clone()).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let mut config = serde_json::json!({
+            "work_dir": "/var/lib/image-rs/",
+            "max_concurrent_download": 1,
+            "eccfs": {"deterministic": false},
+        });
+        let vars = [
+            ("IMAGE_RS_MAX_CONCURRENT_DOWNLOAD", "4"),
+            ("IMAGE_RS_WORK_DIR", "/run/image-rs"),
+            ("IMAGE_RS_ECCFS__DETERMINISTIC", "true"),
+            ("IMAGE_RS_ECCFS__SCRATCH__TYPE", "tmpfs"),
+            ("IMAGE_RS_SECURITY_VALIDATE", "true"),
+            ("PATH", "/usr/bin"),
+        ];
+        apply_env_overrides(
+            &mut config,
+            vars.map(|(name, value)| (name.to_string(), value.to_string())),
+        )
+        .unwrap();
+
+        let config = ImageConfig::from_value(config).unwrap();
+        assert_eq!(config.work_dir, PathBuf::from("/run/image-rs"));
+        assert_eq!(config.max_concurrent_download, 4);
+        assert!(config.security_validate);
+        let eccfs = config.eccfs_config.unwrap();
+        assert!(eccfs.deterministic);
+        assert_eq!(eccfs.scratch, ScratchBacking::Tmpfs { size: None });
+
+        let mut config = serde_json::json!({"work_dir": "/var/lib/image-rs/"});
+        let vars = [("IMAGE_RS_WORK_DIR__NESTED".to_string(), "1".to_string())];
+        assert!(apply_env_overrides(&mut config, vars).is_err());
+        let vars = [("IMAGE_RS_PROXY____ADDRESS".to_string(), "1".to_string())];
+        assert!(apply_env_overrides(&mut config, vars).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(ImageConfig::default().validate().is_ok());
+        assert_eq!(
+            ImageConfig::default_for(SnapshotType::Overlay).default_snapshot,
+            SnapshotType::Overlay
+        );
+
+        let cases = [
+            (
+                r#"{"max_concurrent_download": 0}"#,
+                "max_concurrent_download must be at least 1",
+            ),
+            (
+                r#"{"platform": "linux"}"#,
+                r#"platform "linux" is not of the form os/arch[/variant]"#,
+            ),
+            (
+                r#"{"proxy": {"address": "http://proxy:3128"}}"#,
+                r#"proxy.address "http://proxy:3128" is not of the form vsock://<cid>:<port>"#,
+            ),
+            (
+                r#"{"eccfs": {"hybrid": {"small_layer_threshold": 1, "memory_budget": 0}}}"#,
+                "invalid eccfs config: hybrid.memory_budget is 0, leave it unset for no limit",
+            ),
+            (
+                r#"{"disk_space": {"expansion_factor": 0.5}}"#,
+                "invalid disk_space config: expansion_factor is 0.5, set it to 1.0 or more",
+            ),
+            (
+                r#"{"layer_cache": {"max_bytes": 0}}"#,
+                "layer_cache.max_bytes must be at least 1",
+            ),
+            (
+                r#"{"rootfs_relabel": {"id_shift": {"uid": 4294967295, "gid": 0}}}"#,
+                "invalid rootfs_relabel config: id_shift of 65536 ids from 4294967295:0 is beyond the 32 bit ids",
+            ),
+            (
+                r#"{"rootfs_relabel": {"selinux_label": "container_file_t"}}"#,
+                r#"invalid rootfs_relabel config: selinux_label "container_file_t" is not of the form user:role:type[:level]"#,
+            ),
+            (
+                r#"{"nydus": {"type": "fscache"}}"#,
+                r#"invalid nydus config: type is "fscache", but the fscache config is missing"#,
+            ),
+        ];
+        for (data, error) in cases {
+            let e = ImageConfig::from_value(serde_json::from_str(data).unwrap()).unwrap_err();
+            assert_eq!(format!("{e:#}"), error);
+        }
+    }
+
+    #[test]
+    fn test_layer_cache_config() {
+        let config = ImageConfig::from_value(serde_json::json!({
+            "layer_cache": {
+                "max_bytes": 1073741824,
+                "policy": "lfu",
+                "pinned_images": ["docker.io/library/busybox:1.36"]
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            config.layer_cache,
+            Some(LayerCacheConfig {
+                max_bytes: 1 << 30,
+                policy: EvictionPolicy::Lfu,
+                pinned_images: vec!["docker.io/library/busybox:1.36".into()],
+            })
+        );
+        assert_eq!(ImageConfig::default().layer_cache, None);
+
+        let config =
+            ImageConfig::from_value(serde_json::json!({"layer_cache": {"max_bytes": 1}})).unwrap();
+        assert_eq!(config.layer_cache.unwrap().policy, EvictionPolicy::Lru);
+    }
+
+    #[test]
+    fn test_image_volume_config() {
+        let config = ImageConfig::from_value(serde_json::json!({
+            "image_volume": {"snapshot": "overlay", "encrypted": true}
+        }))
+        .unwrap();
+        assert_eq!(
+            config.image_volume,
+            Some(ImageVolumeConfig {
+                snapshot: Some(SnapshotType::Overlay),
+                encrypted: true,
+            })
+        );
+        assert_eq!(config.volume_snapshot(), SnapshotType::Overlay);
+
+        #[cfg(not(feature = "snapshot-eccfs"))]
+        assert_eq!(
+            ImageConfig::default().volume_snapshot(),
+            ImageConfig::default().default_snapshot
+        );
+        #[cfg(feature = "snapshot-eccfs")]
+        assert_eq!(
+            ImageConfig::default().volume_snapshot(),
+            SnapshotType::Eccfs
+        );
+    }
+
+    #[test]
+    fn test_pull_policy() {
+        assert_eq!(ImageConfig::default().pull_policy, PullPolicy::Always);
+        let config =
+            ImageConfig::from_value(serde_json::json!({"pull_policy": "IfNotPresent"})).unwrap();
+        assert_eq!(config.pull_policy, PullPolicy::IfNotPresent);
+
+        assert_eq!("Never".parse::<PullPolicy>().unwrap(), PullPolicy::Never);
+        assert!("never".parse::<PullPolicy>().is_err());
+    }
 }
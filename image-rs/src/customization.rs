@@ -0,0 +1,175 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Files of the caller overlaid on top of a pulled image, e.g. the
+//! certificates of the cluster or a wrapper of the entrypoint, without
+//! rebuilding the image.
+//!
+//! The files, a dir or a tarball inside the guest, are copied into a layer
+//! of the layer store, which the snapshotter mounts as the top-most layer
+//! of the rootfs. So they shadow the files of the image, and writes of the
+//! container go to the writable layer above them. The layer is named by the
+//! measurement of its tree, under [`CUSTOMIZATION_DIGEST_PREFIX`], so that
+//! the same files give the same layer, and the measurement of the rootfs
+//! names it as well, see [`crate::measure::RootfsMeasurement`].
+
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::{lchown, MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::decoder::Compression;
+use crate::image::LayerMeta;
+use crate::measure::measure_tree;
+use crate::pull::blob_id;
+
+/// Prefix of the digests of the customization layers in the layer db, so
+/// that they can't be mistaken for the layers of an image.
+pub const CUSTOMIZATION_DIGEST_PREFIX: &str = "customization:";
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// tells apart the staging dirs of concurrent pulls
+static STAGING_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Copy the files of `source`, a dir or a (gzip compressed) tarball, into a
+/// customization layer under `layer_dir`. A layer with the same files is
+/// reused.
+pub fn prepare(source: &Path, layer_dir: &Path) -> Result<LayerMeta> {
+    let meta = fs::metadata(source)
+        .with_context(|| format!("customization {} not found", source.display()))?;
+    fs::create_dir_all(layer_dir)?;
+    let staged = layer_dir.join(format!(
+        ".customization-{}-{}",
+        std::process::id(),
+        STAGING_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+    let res = stage(source, meta.is_dir(), &staged).and_then(|_| {
+        let digest = measure_tree(&staged)?;
+        let compressed_digest = format!("{CUSTOMIZATION_DIGEST_PREFIX}{digest}");
+        let destination = layer_dir.join(blob_id(&compressed_digest));
+        if !destination.exists() {
+            fs::rename(&staged, &destination)?;
+        }
+
+        Ok(LayerMeta {
+            decoder: Compression::Uncompressed,
+            encrypted: false,
+            compressed_digest,
+            uncompressed_digest: digest,
+            store_path: destination.display().to_string(),
+        })
+    });
+
+    if staged.exists() {
+        let _ = fs::remove_dir_all(&staged);
+    }
+
+    res
+}
+
+fn stage(source: &Path, is_dir: bool, staged: &Path) -> Result<()> {
+    if is_dir {
+        return copy_tree(source, staged);
+    }
+
+    let mut magic = [0u8; 2];
+    let gzip = fs::File::open(source)?.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC;
+    let tarball = fs::File::open(source)?;
+    match gzip {
+        true => crate::unpack::unpack(GzDecoder::new(tarball), staged),
+        false => crate::unpack::unpack(tarball, staged),
+    }
+    .with_context(|| format!("unpack customization {}", source.display()))
+}
+
+// copy the tree under `from` to `to`, keeping the modes and the ownership,
+// and the symlinks as they are
+fn copy_tree(from: &Path, to: &Path) -> Result<()> {
+    let meta = fs::symlink_metadata(from)?;
+    let file_type = meta.file_type();
+    if file_type.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else if file_type.is_symlink() {
+        std::os::unix::fs::symlink(fs::read_link(from)?, to)?;
+    } else if file_type.is_file() {
+        fs::copy(from, to)?;
+    } else {
+        bail!(
+            "customization {} is not a file, a dir or a symlink",
+            from.display()
+        );
+    }
+
+    lchown(to, Some(meta.uid()), Some(meta.gid()))
+        .with_context(|| format!("chown {}", to.display()))?;
+    if !file_type.is_symlink() {
+        fs::set_permissions(to, fs::Permissions::from_mode(meta.mode()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepare() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let source = tempdir.path().join("source");
+        fs::create_dir_all(source.join("etc/ssl/certs")).unwrap();
+        fs::write(source.join("etc/ssl/certs/cluster-ca.pem"), b"ca").unwrap();
+        std::os::unix::fs::symlink("cluster-ca.pem", source.join("etc/ssl/certs/ca.pem")).unwrap();
+
+        let layer_dir = tempdir.path().join("layers");
+        let layer = prepare(&source, &layer_dir).unwrap();
+        assert!(layer
+            .compressed_digest
+            .starts_with(CUSTOMIZATION_DIGEST_PREFIX));
+        let store_path = Path::new(&layer.store_path);
+        assert_eq!(
+            fs::read(store_path.join("etc/ssl/certs/cluster-ca.pem")).unwrap(),
+            b"ca"
+        );
+        assert_eq!(
+            fs::read_link(store_path.join("etc/ssl/certs/ca.pem")).unwrap(),
+            Path::new("cluster-ca.pem")
+        );
+        assert_eq!(measure_tree(store_path).unwrap(), layer.uncompressed_digest);
+
+        // the same files give the same layer
+        assert_eq!(prepare(&source, &layer_dir).unwrap(), layer);
+        assert_eq!(fs::read_dir(&layer_dir).unwrap().count(), 1);
+
+        let tarball = tempdir.path().join("source.tar.gz");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            fs::File::create(&tarball).unwrap(),
+            flate2::Compression::default(),
+        ));
+        builder.follow_symlinks(false);
+        builder.append_dir_all("etc", source.join("etc")).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        let unpacked = prepare(&tarball, &layer_dir).unwrap();
+        assert_eq!(
+            fs::read(Path::new(&unpacked.store_path).join("etc/ssl/certs/cluster-ca.pem")).unwrap(),
+            b"ca"
+        );
+
+        fs::write(source.join("entrypoint.sh"), b"#!/bin/sh\n").unwrap();
+        assert_ne!(
+            prepare(&source, &layer_dir).unwrap().compressed_digest,
+            layer.compressed_digest
+        );
+
+        assert!(prepare(&tempdir.path().join("missing"), &layer_dir).is_err());
+    }
+}
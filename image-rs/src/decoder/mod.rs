@@ -9,7 +9,7 @@ use std::io;
 use anyhow::{bail, Result};
 use oci_distribution::manifest;
 use oci_spec::image::MediaType;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncRead, BufReader};
 
 /// Error message for unhandled media type.
@@ -17,7 +17,7 @@ pub const ERR_BAD_MEDIA_TYPE: &str = "unhandled media type";
 
 /// Represents the layer compression algorithm type,
 /// and allows to decompress corresponding compressed data.
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Compression {
     Uncompressed,
     #[default]
@@ -115,6 +115,12 @@ impl TryFrom<&str> for Compression {
             media_type_str = manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE;
         }
 
+        // WASM modules are not tarballs, and are stored as they are
+        #[cfg(feature = "snapshot-wasm")]
+        if crate::snapshots::wasm::is_wasm_media_type(media_type_str) {
+            return Ok(Compression::Uncompressed);
+        }
+
         let media_type = MediaType::from(media_type_str);
 
         let decoder = match media_type {
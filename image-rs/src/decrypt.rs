@@ -3,9 +3,20 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::{bail, Result};
-use oci_distribution::manifest::{self, OciDescriptor};
+use oci_distribution::manifest::OciDescriptor;
 use tokio::io::AsyncRead;
 
+use crate::decoder::Compression;
+
+/// Suffix of the media type of an encrypted layer, appended by ocicrypt to
+/// the media type of the plaintext layer.
+const ENCRYPTED_SUFFIX: &str = "+encrypted";
+
+/// Annotation of an encrypted layer with the media type of the plaintext
+/// layer. It takes precedence over the media type of the encrypted layer,
+/// for tools which always use `tar+encrypted` whatever the compression.
+pub const ANNOTATION_PLAINTEXT_MEDIA_TYPE: &str = "org.opencontainers.image.enc.mediatype";
+
 /// Image layer encryption type information and associated methods to decrypt image layers.
 #[derive(Default, Clone, Debug)]
 pub struct Decryptor {
@@ -21,6 +32,43 @@ impl Decryptor {
     pub fn is_encrypted(&self) -> bool {
         self.encrypted
     }
+
+    /// Construct Decryptor from media_type. The plaintext media type is the
+    /// encrypted one without `+encrypted`, e.g. `tar+zstd` for
+    /// `tar+zstd+encrypted`, and non distributable layers are handled as
+    /// distributable ones.
+    pub fn from_media_type(media_type: &str) -> Self {
+        let Some(plaintext) = media_type.strip_suffix(ENCRYPTED_SUFFIX) else {
+            return Self::default();
+        };
+
+        let plaintext = plaintext.replace(".layer.nondistributable.v1.", ".layer.v1.");
+        if Compression::try_from(plaintext.as_str()).is_err() {
+            return Self::default();
+        }
+
+        Decryptor {
+            media_type: plaintext,
+            encrypted: true,
+        }
+    }
+
+    /// Construct Decryptor from the layer descriptor, also honoring
+    /// [`ANNOTATION_PLAINTEXT_MEDIA_TYPE`] of encrypted layers.
+    pub fn from_descriptor(layer: &OciDescriptor) -> Self {
+        let mut decryptor = Self::from_media_type(&layer.media_type);
+        if let Some(media_type) = layer
+            .annotations
+            .as_ref()
+            .and_then(|annotations| annotations.get(ANNOTATION_PLAINTEXT_MEDIA_TYPE))
+            .filter(|_| decryptor.is_encrypted())
+        {
+            // an unknown media type is rejected by the decompressor
+            decryptor.media_type = media_type.clone();
+        }
+
+        decryptor
+    }
 }
 
 #[cfg(feature = "encryption")]
@@ -32,34 +80,12 @@ mod encryption {
         async_decrypt_layer, decrypt_layer, decrypt_layer_key_opts_data,
     };
     use ocicrypt_rs::helpers::create_decrypt_config;
-    use ocicrypt_rs::spec::{
-        MEDIA_TYPE_LAYER_ENC, MEDIA_TYPE_LAYER_GZIP_ENC, MEDIA_TYPE_LAYER_NON_DISTRIBUTABLE_ENC,
-        MEDIA_TYPE_LAYER_NON_DISTRIBUTABLE_GZIP_ENC,
-    };
     use std::io::Read;
 
     impl Decryptor {
         const ERR_EMPTY_CFG: &'static str = "decrypt_config is empty";
         const ERR_UNENCRYPTED_MEDIA_TYPE: &'static str = "unencrypted media type";
 
-        /// Construct Decryptor from media_type.
-        pub fn from_media_type(media_type: &str) -> Self {
-            let (media_type, encrypted) = match media_type {
-                MEDIA_TYPE_LAYER_ENC | MEDIA_TYPE_LAYER_NON_DISTRIBUTABLE_ENC => {
-                    (manifest::IMAGE_LAYER_MEDIA_TYPE.to_string(), true)
-                }
-                MEDIA_TYPE_LAYER_GZIP_ENC | MEDIA_TYPE_LAYER_NON_DISTRIBUTABLE_GZIP_ENC => {
-                    (manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE.to_string(), true)
-                }
-                _ => ("".to_string(), false),
-            };
-
-            Decryptor {
-                media_type,
-                encrypted,
-            }
-        }
-
         /// get_plaintext_layer decrypts encrypted_layer data and return the plaintext_layer data.
         ///
         /// `descriptor` and `decrypt_config` are required for layer data decryption process.
@@ -153,6 +179,13 @@ mod encryption {
     #[cfg(test)]
     mod tests {
         use super::*;
+        use oci_distribution::manifest;
+        use ocicrypt_rs::spec::{
+            MEDIA_TYPE_LAYER_ENC, MEDIA_TYPE_LAYER_GZIP_ENC,
+            MEDIA_TYPE_LAYER_NON_DISTRIBUTABLE_ENC, MEDIA_TYPE_LAYER_NON_DISTRIBUTABLE_GZIP_ENC,
+            MEDIA_TYPE_LAYER_NON_DISTRIBUTABLE_ZSTD_ENC, MEDIA_TYPE_LAYER_ZSTD_ENC,
+        };
+        use std::collections::HashMap;
 
         #[tokio::test]
         async fn test_from_media_type() {
@@ -212,6 +245,27 @@ mod encryption {
                         encrypted: true,
                     },
                 },
+                TestData {
+                    media_type: MEDIA_TYPE_LAYER_ZSTD_ENC,
+                    result: Decryptor {
+                        media_type: "application/vnd.oci.image.layer.v1.tar+zstd".into(),
+                        encrypted: true,
+                    },
+                },
+                TestData {
+                    media_type: MEDIA_TYPE_LAYER_NON_DISTRIBUTABLE_ZSTD_ENC,
+                    result: Decryptor {
+                        media_type: "application/vnd.oci.image.layer.v1.tar+zstd".into(),
+                        encrypted: true,
+                    },
+                },
+                TestData {
+                    media_type: "application/vnd.oci.image.layer.v1.tar+lz4+encrypted",
+                    result: Decryptor {
+                        media_type: "".into(),
+                        encrypted: false,
+                    },
+                },
             ];
 
             for (i, d) in tests.iter().enumerate() {
@@ -226,6 +280,41 @@ mod encryption {
             }
         }
 
+        #[test]
+        fn test_from_descriptor() {
+            let annotations = HashMap::from([(
+                ANNOTATION_PLAINTEXT_MEDIA_TYPE.to_string(),
+                "application/vnd.oci.image.layer.v1.tar+zstd".to_string(),
+            )]);
+
+            let layer = OciDescriptor {
+                media_type: MEDIA_TYPE_LAYER_ENC.to_string(),
+                annotations: Some(annotations.clone()),
+                ..Default::default()
+            };
+            let decryptor = Decryptor::from_descriptor(&layer);
+            assert!(decryptor.is_encrypted());
+            assert_eq!(
+                decryptor.media_type,
+                "application/vnd.oci.image.layer.v1.tar+zstd"
+            );
+
+            let layer = OciDescriptor {
+                media_type: MEDIA_TYPE_LAYER_GZIP_ENC.to_string(),
+                ..Default::default()
+            };
+            let decryptor = Decryptor::from_descriptor(&layer);
+            assert_eq!(decryptor.media_type, manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE);
+
+            // the annotation does not make a plaintext layer encrypted
+            let layer = OciDescriptor {
+                media_type: manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE.to_string(),
+                annotations: Some(annotations),
+                ..Default::default()
+            };
+            assert!(!Decryptor::from_descriptor(&layer).is_encrypted());
+        }
+
         #[tokio::test]
         async fn test_get_plaintext_layer() {
             use std::io::Write;
@@ -324,26 +413,6 @@ mod encryption {
 
 #[cfg(not(feature = "encryption"))]
 impl Decryptor {
-    /// Construct Decryptor from media_type.
-    pub fn from_media_type(media_type: &str) -> Self {
-        let (media_type, encrypted) = match media_type {
-            "application/vnd.oci.image.layer.v1.tar+encrypted"
-            | "application/vnd.oci.image.layer.nondistributable.v1.tar+encrypted" => {
-                (manifest::IMAGE_LAYER_MEDIA_TYPE.to_string(), true)
-            }
-            "application/vnd.oci.image.layer.v1.tar+gzip+encrypted"
-            | "application/vnd.oci.image.layer.nondistributable.v1.tar+gzip+encrypted" => {
-                (manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE.to_string(), true)
-            }
-            _ => ("".to_string(), false),
-        };
-
-        Decryptor {
-            media_type,
-            encrypted,
-        }
-    }
-
     pub fn get_plaintext_layer(
         &self,
         _descriptor: &OciDescriptor,
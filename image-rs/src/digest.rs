@@ -2,7 +2,11 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use anyhow::{bail, Result};
 use sha2::Digest;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
 
 pub const DIGEST_SHA256_PREFIX: &str = "sha256:";
 pub const DIGEST_SHA512_PREFIX: &str = "sha512:";
@@ -41,3 +45,65 @@ impl DigestHasher for LayerDigestHasher {
         }
     }
 }
+
+/// Get the hasher of the algorithm of `digest`, e.g. `sha256:...`.
+pub(crate) fn hasher_for(digest: &str) -> Result<LayerDigestHasher> {
+    if digest.starts_with(DIGEST_SHA256_PREFIX) {
+        Ok(LayerDigestHasher::Sha256(sha2::Sha256::new()))
+    } else if digest.starts_with(DIGEST_SHA512_PREFIX) {
+        Ok(LayerDigestHasher::Sha512(sha2::Sha512::new()))
+    } else {
+        bail!("unsupported digest {:?}", digest)
+    }
+}
+
+/// [`AsyncRead`] wrapper calculating the digest of the data read through it.
+pub(crate) struct HashingReader<R> {
+    inner: R,
+    hasher: LayerDigestHasher,
+}
+
+impl<R> HashingReader<R> {
+    pub(crate) fn new(inner: R, hasher: LayerDigestHasher) -> Self {
+        Self { inner, hasher }
+    }
+
+    /// The digest of the data read so far.
+    pub(crate) fn digest_finalize(self) -> String {
+        self.hasher.digest_finalize()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let filled = buf.filled().len();
+        let this = &mut *self;
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.hasher.digest_update(&buf.filled()[filled..]);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_hashing_reader() {
+        let data = b"some layer data".to_vec();
+        let mut reader = HashingReader::new(&data[..], hasher_for("sha256:").unwrap());
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).await.unwrap();
+        assert_eq!(out, data);
+        assert_eq!(
+            reader.digest_finalize(),
+            format!("sha256:{:x}", sha2::Sha256::digest(&data))
+        );
+        assert!(hasher_for("md5:1234").is_err());
+    }
+}
@@ -0,0 +1,620 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Disk space checks of pulls.
+//!
+//! The image volume of a guest is often small, and a pull running out of
+//! space fails midway with an opaque `ENOSPC`, after most of the image has
+//! been downloaded. With a [`DiskSpaceConfig`], the space a pull needs is
+//! estimated from the sizes of the layers missing locally before any of
+//! them is downloaded, and every layer is checked again before it is
+//! downloaded, so that pulls not fitting fail early with
+//! [`InsufficientDiskSpace`]. The layers no pulled image uses anymore can
+//! be evicted to make room.
+//!
+//! The unpacked layers are checked against the filesystem of the layer
+//! store, and the space the snapshotter needs on top, e.g. the eccfs
+//! roimages, against the one of its work dir. The space a pull needs is
+//! reserved in the [`DiskReservations`] of the client until the pull is
+//! over, or its layers are stored, so that concurrent pulls do not count on
+//! the same free space.
+
+use anyhow::{bail, Result};
+use log::info;
+use oci_distribution::manifest::OciDescriptor;
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::config::DiskSpaceConfig;
+use crate::meta_store::MetaStore;
+use crate::snapshots::SnapshotType;
+
+/// Error returned when a pull does not fit into the free space left.
+#[derive(Debug)]
+pub struct InsufficientDiskSpace {
+    /// Dir the layers are stored in.
+    pub path: PathBuf,
+
+    /// Estimated bytes needed, including the reserve.
+    pub required: u64,
+
+    /// Bytes available, less the ones reserved by the other pulls.
+    pub available: u64,
+}
+
+impl fmt::Display for InsufficientDiskSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "not enough disk space on {}: {} bytes needed, {} available",
+            self.path.display(),
+            self.required,
+            self.available
+        )
+    }
+}
+
+impl std::error::Error for InsufficientDiskSpace {}
+
+/// Bytes reserved by the pulls in flight, per filesystem, shared by the
+/// clients with the same work dir.
+#[derive(Debug, Default)]
+pub struct DiskReservations {
+    // by device id of the filesystem
+    reserved: Mutex<HashMap<u64, u64>>,
+}
+
+impl DiskReservations {
+    /// Bytes reserved on the filesystem of `path`.
+    pub fn reserved(&self, path: &Path) -> Result<u64> {
+        let (_, dev) = device(path)?;
+        Ok(self.lock().get(&dev).copied().unwrap_or_default())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, u64>> {
+        self.reserved.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // reserve the bytes of `needs` on the filesystems of their paths, if
+    // they all fit
+    fn reserve(
+        self: &Arc<Self>,
+        needs: &[(&Path, u64)],
+        config: &DiskSpaceConfig,
+    ) -> Result<Reservation> {
+        let mut by_device: Vec<(&Path, u64, u64)> = Vec::new();
+        for (path, bytes) in needs {
+            let (_, dev) = device(path)?;
+            match by_device.iter_mut().find(|(_, d, _)| *d == dev) {
+                Some((_, _, total)) => *total += bytes,
+                None => by_device.push((path, dev, *bytes)),
+            }
+        }
+
+        let mut reserved = self.lock();
+        for (path, dev, bytes) in &by_device {
+            let others = reserved.get(dev).copied().unwrap_or_default();
+            check_reserved(path, *bytes, others, config)?;
+        }
+
+        let mut held = HashMap::new();
+        for (_, dev, bytes) in by_device {
+            *reserved.entry(dev).or_default() += bytes;
+            held.insert(dev, bytes);
+        }
+
+        Ok(Reservation {
+            reservations: self.clone(),
+            held: Mutex::new(held),
+        })
+    }
+}
+
+/// Space reserved by a pull, given back as its layers are stored, and
+/// altogether once dropped.
+#[derive(Debug)]
+pub struct Reservation {
+    reservations: Arc<DiskReservations>,
+
+    // bytes still held, by device id of the filesystem
+    held: Mutex<HashMap<u64, u64>>,
+}
+
+impl Reservation {
+    /// Give back up to `bytes` held on the filesystem of `path`, once they
+    /// are taken from its free space.
+    pub fn release(&self, path: &Path, bytes: u64) -> Result<()> {
+        let (_, dev) = device(path)?;
+        let mut held = self.lock();
+        let Some(held) = held.get_mut(&dev) else {
+            return Ok(());
+        };
+        let released = bytes.min(*held);
+        *held -= released;
+        if let Some(reserved) = self.reservations.lock().get_mut(&dev) {
+            *reserved = reserved.saturating_sub(released);
+        }
+
+        Ok(())
+    }
+
+    /// Bytes still held on the filesystem of `path`.
+    pub fn held(&self, path: &Path) -> Result<u64> {
+        let (_, dev) = device(path)?;
+        Ok(self.lock().get(&dev).copied().unwrap_or_default())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, u64>> {
+        self.held.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        let held: Vec<(u64, u64)> = self.lock().drain().collect();
+        let mut reserved = self.reservations.lock();
+        for (dev, bytes) in held {
+            if let Some(reserved) = reserved.get_mut(&dev) {
+                *reserved = reserved.saturating_sub(bytes);
+            }
+        }
+        reserved.retain(|_, bytes| *bytes > 0);
+    }
+}
+
+/// Estimate the bytes needed to unpack layers of `compressed_sizes` and
+/// to mount them with the `snapshot`.
+#[cfg_attr(not(feature = "snapshot-eccfs"), allow(unused_variables))]
+pub fn estimate(
+    compressed_sizes: impl IntoIterator<Item = u64>,
+    snapshot: SnapshotType,
+    config: &DiskSpaceConfig,
+) -> u64 {
+    let compressed: u64 = compressed_sizes.into_iter().sum();
+    let mut total = unpacked_size(compressed, config);
+
+    // the roimages are built next to the unpacked layers
+    #[cfg(feature = "snapshot-eccfs")]
+    if snapshot == SnapshotType::Eccfs {
+        total *= 1.0 + config.eccfs_overhead;
+    }
+
+    total.ceil() as u64
+}
+
+/// Estimate the unpacked size of layers of `compressed` bytes.
+pub fn unpacked_size(compressed: u64, config: &DiskSpaceConfig) -> f64 {
+    compressed as f64 * config.expansion_factor
+}
+
+fn layer_size(layer: &OciDescriptor) -> u64 {
+    layer.size.max(0) as u64
+}
+
+/// Check that the `layers` of an image missing from `meta_store` fit into
+/// the layer store `layer_dir` once unpacked, and into the work dir
+/// `snapshot_dir` of the `snapshot` once mounted, besides the space
+/// reserved by the other pulls, and reserve it. If they don't and `config`
+/// allows it, the layers no image uses are evicted first, except the ones
+/// `pinned` by the pulls in progress.
+#[allow(clippy::too_many_arguments)]
+pub fn preflight(
+    meta_store: &mut MetaStore,
+    reservations: &Arc<DiskReservations>,
+    layer_dir: &Path,
+    snapshot_dir: &Path,
+    layers: &[OciDescriptor],
+    snapshot: SnapshotType,
+    pinned: &BTreeSet<String>,
+    config: &DiskSpaceConfig,
+) -> Result<Reservation> {
+    let missing: Vec<u64> = layers
+        .iter()
+        .filter(|layer| !meta_store.layer_db.contains_key(&layer.digest))
+        .map(layer_size)
+        .collect();
+    let unpacked = unpacked_size(missing.iter().sum(), config).ceil() as u64;
+    let overhead = estimate(missing, snapshot, config).saturating_sub(unpacked);
+    let needs = [(layer_dir, unpacked), (snapshot_dir, overhead)];
+    match reservations.reserve(&needs, config) {
+        Err(e) if e.is::<InsufficientDiskSpace>() && config.evict_unused_layers => {
+            let keep = layers
+                .iter()
+                .map(|layer| layer.digest.as_str())
+                .chain(pinned.iter().map(String::as_str))
+                .collect();
+            let freed = evict_unused_layers(meta_store, &keep)?;
+            info!("{} bytes freed by evicting unused layers", freed);
+            reservations.reserve(&needs, config)
+        }
+        res => res,
+    }
+}
+
+/// Check that `layer` fits into `data_dir` once unpacked, besides the space
+/// reserved by the other pulls than the one of `reservation`.
+pub fn check_layer(
+    data_dir: &Path,
+    layer: &OciDescriptor,
+    config: &DiskSpaceConfig,
+    reservations: &DiskReservations,
+    reservation: Option<&Reservation>,
+) -> Result<()> {
+    let required = layer_unpacked_size(layer, config);
+    let own = match reservation {
+        Some(reservation) => reservation.held(data_dir)?,
+        None => 0,
+    };
+    let others = reservations.reserved(data_dir)?.saturating_sub(own);
+    check_reserved(data_dir, required, others, config)
+}
+
+/// Estimate the unpacked size of `layer`.
+pub fn layer_unpacked_size(layer: &OciDescriptor, config: &DiskSpaceConfig) -> u64 {
+    unpacked_size(layer_size(layer), config).ceil() as u64
+}
+
+// the nearest existing dir of `path`, and the device id of its filesystem
+fn device(path: &Path) -> Result<(&Path, u64)> {
+    let existing = path
+        .ancestors()
+        .find(|dir| dir.exists())
+        .unwrap_or(Path::new("/"));
+    Ok((existing, fs::metadata(existing)?.dev()))
+}
+
+/// Bytes available to unprivileged users on the filesystem of `path`, or
+/// of its nearest existing parent.
+pub fn available(path: &Path) -> Result<u64> {
+    let (existing, _) = device(path)?;
+    let c_path = CString::new(existing.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        bail!(
+            "failed to get the free space of {}: {}",
+            existing.display(),
+            io::Error::last_os_error()
+        );
+    }
+
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Check that `required` bytes fit on the filesystem of `path`, keeping
+/// the reserve of `config` free.
+pub fn check(path: &Path, required: u64, config: &DiskSpaceConfig) -> Result<()> {
+    check_reserved(path, required, 0, config)
+}
+
+// check that `required` bytes fit on the filesystem of `path` besides the
+// `reserved` ones, keeping the reserve of `config` free
+fn check_reserved(
+    path: &Path,
+    required: u64,
+    reserved: u64,
+    config: &DiskSpaceConfig,
+) -> Result<()> {
+    let available = available(path)?.saturating_sub(reserved);
+    let required = required.saturating_add(config.reserve);
+    if required > available {
+        return Err(InsufficientDiskSpace {
+            path: path.to_path_buf(),
+            required,
+            available,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Remove the layers of `meta_store` no image or customized bundle uses,
+/// except the ones whose digest is in `keep`. Returns the bytes freed.
+pub fn evict_unused_layers(meta_store: &mut MetaStore, keep: &BTreeSet<&str>) -> Result<u64> {
+    let used: BTreeSet<&str> = meta_store
+        .image_db
+        .values()
+        .flat_map(|image| &image.layer_metas)
+        .map(|layer| layer.compressed_digest.as_str())
+        .chain(meta_store.customization_db.values().map(String::as_str))
+        .collect();
+    let unused: Vec<String> = meta_store
+        .layer_db
+        .keys()
+        .filter(|digest| !used.contains(digest.as_str()) && !keep.contains(digest.as_str()))
+        .cloned()
+        .collect();
+
+    evict_layers(meta_store, &unused)
+}
+
+/// Forget the layers with `digests` and the images using them, and remove
+/// the layers from the layer store. The meta store is pruned even if some
+/// layers cannot be removed, which are reported once all were tried.
+/// Returns the bytes freed.
+pub(crate) fn evict_layers(meta_store: &mut MetaStore, digests: &[String]) -> Result<u64> {
+    let mut evicted = Vec::new();
+    for digest in digests {
+        meta_store.layer_usage.remove(digest);
+        if let Some(layer) = meta_store.layer_db.remove(digest) {
+            evicted.push(layer);
+        }
+    }
+    meta_store.image_db.retain(|id, image| {
+        let complete = !image
+            .layer_metas
+            .iter()
+            .any(|layer| digests.contains(&layer.compressed_digest));
+        if !complete {
+            info!("forgot image {} whose layers were evicted", id);
+        }
+        complete
+    });
+
+    let mut freed = 0;
+    let mut failed = Vec::new();
+    for layer in evicted {
+        let path = Path::new(&layer.store_path);
+        if !path.exists() {
+            continue;
+        }
+        let size = tree_size(path).unwrap_or_default();
+        match fs::remove_dir_all(path) {
+            Ok(()) => {
+                freed += size;
+                info!("evicted layer {}", layer.compressed_digest);
+            }
+            Err(e) => failed.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    if !failed.is_empty() {
+        bail!("failed to remove evicted layers: {}", failed.join(", "));
+    }
+
+    Ok(freed)
+}
+
+// total size of the files under path
+pub(crate) fn tree_size(path: &Path) -> Result<u64> {
+    let meta = fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += tree_size(&entry?.path())?;
+    }
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{ImageMeta, LayerMeta};
+
+    #[test]
+    fn test_estimate_and_check() {
+        let config = DiskSpaceConfig {
+            expansion_factor: 2.0,
+            eccfs_overhead: 1.0,
+            reserve: 0,
+            evict_unused_layers: false,
+        };
+        assert_eq!(estimate([100, 50], SnapshotType::Unknown, &config), 300);
+        #[cfg(feature = "snapshot-eccfs")]
+        assert_eq!(estimate([100, 50], SnapshotType::Eccfs, &config), 600);
+
+        let tempdir = tempfile::tempdir().unwrap();
+        assert!(available(tempdir.path()).unwrap() > 0);
+        assert!(available(&tempdir.path().join("missing/dir")).unwrap() > 0);
+
+        check(tempdir.path(), 0, &config).unwrap();
+        let e = check(tempdir.path(), u64::MAX, &config).unwrap_err();
+        let e = e.downcast_ref::<InsufficientDiskSpace>().unwrap();
+        assert_eq!(e.required, u64::MAX);
+        let reserve = DiskSpaceConfig {
+            reserve: u64::MAX,
+            ..config
+        };
+        assert!(check(tempdir.path(), 1, &reserve).is_err());
+    }
+
+    #[test]
+    fn test_preflight() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let stale = tempdir.path().join("stale");
+        fs::create_dir(&stale).unwrap();
+        let mut meta_store = MetaStore::default();
+        meta_store.layer_db.insert(
+            "sha256:stale".into(),
+            LayerMeta {
+                compressed_digest: "sha256:stale".into(),
+                store_path: stale.display().to_string(),
+                ..Default::default()
+            },
+        );
+        let layers = [OciDescriptor {
+            digest: "sha256:new".into(),
+            size: 1024,
+            ..Default::default()
+        }];
+        let config = DiskSpaceConfig {
+            expansion_factor: 3.0,
+            eccfs_overhead: 0.0,
+            reserve: 0,
+            evict_unused_layers: false,
+        };
+
+        let reservations = Arc::new(DiskReservations::default());
+        let reservation = preflight(
+            &mut meta_store,
+            &reservations,
+            tempdir.path(),
+            tempdir.path(),
+            &layers,
+            SnapshotType::Unknown,
+            &BTreeSet::new(),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(reservations.reserved(tempdir.path()).unwrap(), 3072);
+        check_layer(
+            tempdir.path(),
+            &layers[0],
+            &config,
+            &reservations,
+            Some(&reservation),
+        )
+        .unwrap();
+        reservation.release(tempdir.path(), 3072).unwrap();
+        assert_eq!(reservation.held(tempdir.path()).unwrap(), 0);
+        assert_eq!(reservations.reserved(tempdir.path()).unwrap(), 0);
+        drop(reservation);
+
+        let full = DiskSpaceConfig {
+            reserve: u64::MAX,
+            evict_unused_layers: true,
+            ..config
+        };
+
+        // a layer pinned by another pull is kept
+        let pinned = BTreeSet::from(["sha256:stale".to_string()]);
+        assert!(preflight(
+            &mut meta_store,
+            &reservations,
+            tempdir.path(),
+            tempdir.path(),
+            &layers,
+            SnapshotType::Unknown,
+            &pinned,
+            &full,
+        )
+        .is_err());
+        assert!(stale.exists());
+
+        let e = preflight(
+            &mut meta_store,
+            &reservations,
+            tempdir.path(),
+            tempdir.path(),
+            &layers,
+            SnapshotType::Unknown,
+            &BTreeSet::new(),
+            &full,
+        )
+        .unwrap_err();
+        assert!(e.is::<InsufficientDiskSpace>());
+        assert!(check_layer(tempdir.path(), &layers[0], &full, &reservations, None).is_err());
+
+        // the stale layer was evicted before giving up
+        assert!(meta_store.layer_db.is_empty());
+        assert!(!stale.exists());
+    }
+
+    #[test]
+    fn test_concurrent_reservations() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let config = DiskSpaceConfig {
+            expansion_factor: 1.0,
+            eccfs_overhead: 0.0,
+            reserve: 0,
+            evict_unused_layers: false,
+        };
+        // two pulls which only fit one at a time
+        let size = available(tempdir.path()).unwrap() / 3 * 2;
+        let layers = |digest: &str| {
+            [OciDescriptor {
+                digest: digest.into(),
+                size: size as i64,
+                ..Default::default()
+            }]
+        };
+        let mut meta_store = MetaStore::default();
+        let reservations = Arc::new(DiskReservations::default());
+        let mut pull = |digest: &str| {
+            preflight(
+                &mut meta_store,
+                &reservations,
+                tempdir.path(),
+                &tempdir.path().join("overlay"),
+                &layers(digest),
+                SnapshotType::Unknown,
+                &BTreeSet::new(),
+                &config,
+            )
+        };
+
+        let first = pull("sha256:first").unwrap();
+        let e = pull("sha256:second").unwrap_err();
+        assert!(e.is::<InsufficientDiskSpace>());
+        assert!(check_layer(
+            tempdir.path(),
+            &layers("sha256:second")[0],
+            &config,
+            &reservations,
+            None
+        )
+        .is_err());
+
+        // the space is given back once the first pull is over
+        drop(first);
+        assert_eq!(reservations.reserved(tempdir.path()).unwrap(), 0);
+        let second = pull("sha256:second").unwrap();
+        assert_eq!(second.held(tempdir.path()).unwrap(), size);
+    }
+
+    #[test]
+    fn test_evict_unused_layers() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let layer = |digest: &str| {
+            let path = tempdir.path().join(digest);
+            fs::create_dir_all(path.join("etc")).unwrap();
+            fs::write(path.join("etc/data"), digest).unwrap();
+            LayerMeta {
+                compressed_digest: digest.to_string(),
+                store_path: path.display().to_string(),
+                ..Default::default()
+            }
+        };
+
+        let mut meta_store = MetaStore::default();
+        for digest in ["used", "unused", "kept", "customization"] {
+            meta_store.layer_db.insert(digest.into(), layer(digest));
+        }
+        meta_store
+            .customization_db
+            .insert("/run/bundle".into(), "customization".into());
+        meta_store.image_db.insert(
+            "image".into(),
+            ImageMeta {
+                layer_metas: vec![meta_store.layer_db["used"].clone()],
+                ..Default::default()
+            },
+        );
+
+        let freed = evict_unused_layers(&mut meta_store, &BTreeSet::from(["kept"])).unwrap();
+        assert_eq!(freed, "unused".len() as u64);
+        assert!(!tempdir.path().join("unused").exists());
+        assert!(tempdir.path().join("used").exists());
+        assert!(tempdir.path().join("kept").exists());
+        assert!(tempdir.path().join("customization").exists());
+        assert_eq!(
+            meta_store.layer_db.keys().collect::<BTreeSet<_>>(),
+            BTreeSet::from([
+                &"customization".to_string(),
+                &"kept".to_string(),
+                &"used".to_string()
+            ])
+        );
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Events of the image pulls of a client.
+//!
+//! Callers like kata-agent or test harnesses can follow the lifecycle of
+//! the pulls by subscribing to the [`PullEvents`] of an
+//! [`ImageClient`](crate::image::ImageClient), e.g. to assert on the order
+//! of the phases of a pull, or to run a hook exactly when the rootfs of a
+//! container is mounted.
+//!
+//! Every subscriber gets all the events emitted after it subscribed, in
+//! order and without loss, so a subscriber not reading its events keeps
+//! them in memory until it is dropped. The events of concurrent pulls are
+//! interleaved, and are told apart by their `image_url`.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::disk_space::InsufficientDiskSpace;
+use crate::pull::{BlobDigestMismatch, LayerDigestMismatch};
+use crate::stream::LayerStorageFull;
+use crate::verification::SignatureRejected;
+
+/// An event of a pull. `image_url` is the image as given to the pull.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum PullEvent {
+    /// The pull of the image into `bundle_dir` started.
+    PullStarted {
+        image_url: String,
+        bundle_dir: PathBuf,
+    },
+
+    /// A layer was downloaded and its digests were checked.
+    LayerVerified { image_url: String, digest: String },
+
+    /// The image manifest with `digest` was allowed by the signature
+    /// policy, with a verified signature if `signed`.
+    SignatureChecked {
+        image_url: String,
+        digest: String,
+        signed: bool,
+    },
+
+    /// The rootfs, or a layer group, of the image was mounted at
+    /// `mount_path` by the `snapshot` snapshotter.
+    SnapshotMounted {
+        image_url: String,
+        image_id: String,
+        snapshot: String,
+        mount_path: PathBuf,
+    },
+
+    /// The pull failed, after `PullStarted`.
+    PullFailed {
+        image_url: String,
+        code: PullErrorCode,
+        message: String,
+    },
+}
+
+/// Why a pull failed.
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PullErrorCode {
+    /// The pull was cancelled.
+    Cancelled,
+
+    /// The image does not fit into the layer storage.
+    DiskSpace,
+
+    /// A layer did not match its digest.
+    LayerVerification,
+
+    /// The signature policy rejected the image.
+    SignatureRejected,
+
+    /// Any other error, e.g. of the registry.
+    Other,
+}
+
+impl PullErrorCode {
+    /// The code of `error`, failing a pull which was `cancelled` or not.
+    pub fn of(error: &anyhow::Error, cancelled: bool) -> Self {
+        if cancelled {
+            return Self::Cancelled;
+        }
+
+        for cause in error.chain() {
+            if cause.is::<InsufficientDiskSpace>() || cause.is::<LayerStorageFull>() {
+                return Self::DiskSpace;
+            }
+            if cause.is::<LayerDigestMismatch>() || cause.is::<BlobDigestMismatch>() {
+                return Self::LayerVerification;
+            }
+            if cause.is::<SignatureRejected>() {
+                return Self::SignatureRejected;
+            }
+        }
+
+        Self::Other
+    }
+}
+
+/// Emitter of the events of the pulls of a client, which can be shared by
+/// several clients.
+#[derive(Default)]
+pub struct PullEvents {
+    subscribers: Mutex<Vec<UnboundedSender<PullEvent>>>,
+}
+
+impl PullEvents {
+    /// Get the events emitted from now on.
+    pub fn subscribe(&self) -> UnboundedReceiver<PullEvent> {
+        let (sender, receiver) = unbounded_channel();
+        self.subscribers
+            .lock()
+            .expect("poisoned pull events")
+            .push(sender);
+        receiver
+    }
+
+    /// Send `event` to the subscribers, dropping the ones which are gone.
+    pub fn emit(&self, event: PullEvent) {
+        self.subscribers
+            .lock()
+            .expect("poisoned pull events")
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn started(image_url: &str) -> PullEvent {
+        PullEvent::PullStarted {
+            image_url: image_url.into(),
+            bundle_dir: "/run/bundle".into(),
+        }
+    }
+
+    #[test]
+    fn test_pull_events() {
+        let events = PullEvents::default();
+        events.emit(started("unseen"));
+
+        let mut first = events.subscribe();
+        let second = events.subscribe();
+        drop(second);
+        events.emit(started("busybox"));
+        events.emit(PullEvent::LayerVerified {
+            image_url: "busybox".into(),
+            digest: "sha256:1234".into(),
+        });
+
+        assert_eq!(first.try_recv().unwrap(), started("busybox"));
+        assert!(matches!(
+            first.try_recv().unwrap(),
+            PullEvent::LayerVerified { .. }
+        ));
+        assert!(first.try_recv().is_err());
+        assert_eq!(events.subscribers.lock().unwrap().len(), 1);
+
+        assert_eq!(
+            serde_json::to_value(started("busybox")).unwrap(),
+            serde_json::json!({
+                "event": "pull_started",
+                "image_url": "busybox",
+                "bundle_dir": "/run/bundle",
+            })
+        );
+    }
+
+    #[test]
+    fn test_pull_error_code() {
+        let rejected = anyhow!(SignatureRejected("no signature".into()));
+        assert_eq!(
+            PullErrorCode::of(&rejected, false),
+            PullErrorCode::SignatureRejected
+        );
+        assert_eq!(PullErrorCode::of(&rejected, true), PullErrorCode::Cancelled);
+        let full = anyhow!(LayerStorageFull {
+            source: anyhow!("no space left on device"),
+        })
+        .context("failed to handle layer");
+        assert_eq!(PullErrorCode::of(&full, false), PullErrorCode::DiskSpace);
+        assert_eq!(
+            PullErrorCode::of(&anyhow!("unauthorized"), false),
+            PullErrorCode::Other
+        );
+    }
+}
@@ -0,0 +1,408 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Export of pulled images as [OCI image layouts](https://github.com/opencontainers/image-spec/blob/main/image-layout.md).
+//!
+//! image-rs only keeps the unpacked layers of an image, so the blobs are
+//! fetched again from the source the image was pulled from. The manifest
+//! digest is checked against the one recorded (and verified) at pull time,
+//! and every blob is checked against its digest in the manifest, so the
+//! exported layout holds exactly the content the guest pulled.
+//!
+//! The manifest itself is re-encoded from its parsed form, so its digest
+//! may differ from the one at the source. The source digest is kept in the
+//! `io.confidential-containers.image.source-digest` annotation of the
+//! index entry.
+
+use anyhow::{anyhow, bail, Context, Result};
+use oci_distribution::manifest::{OciDescriptor, OCI_IMAGE_MEDIA_TYPE};
+use serde_json::json;
+use sha2::Digest;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use crate::decrypt::Decryptor;
+use crate::digest::{
+    hasher_for, DigestHasher, HashingReader, LayerDigestHasher, DIGEST_SHA256_PREFIX,
+};
+use crate::pull::PullClient;
+
+/// Error returned when the image at its source is not the one that was
+/// pulled before.
+pub const ERR_EXPORT_MANIFEST_CHANGED: &str = "image manifest changed since it was pulled";
+
+const ANNOTATION_REF_NAME: &str = "org.opencontainers.image.ref.name";
+const ANNOTATION_CONTAINERD_IMAGE_NAME: &str = "io.containerd.image.name";
+const ANNOTATION_SOURCE_DIGEST: &str = "io.confidential-containers.image.source-digest";
+const ENCRYPTION_ANNOTATION_PREFIX: &str = "org.opencontainers.image.enc.";
+const OCI_LAYOUT_VERSION: &str = "1.0.0";
+const CAPACITY: usize = 32768;
+
+/// A blob written to a temporary file of the layout, not committed yet.
+struct StagedBlob {
+    tmp: PathBuf,
+    digest: String,
+    size: i64,
+}
+
+impl StagedBlob {
+    /// Remove the blob, e.g. because it failed verification.
+    async fn discard(self) {
+        let _ = tokio::fs::remove_file(&self.tmp).await;
+    }
+}
+
+/// The blob store of an OCI image layout under construction.
+struct LayoutWriter {
+    dir: PathBuf,
+    tmp_index: usize,
+}
+
+impl LayoutWriter {
+    async fn new(dir: &Path) -> Result<Self> {
+        tokio::fs::create_dir_all(dir.join("blobs").join("sha256"))
+            .await
+            .with_context(|| format!("create OCI layout {}", dir.display()))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            tmp_index: 0,
+        })
+    }
+
+    /// Write all data of `reader` as a blob, returns its sha256 digest and size.
+    async fn write_blob(&mut self, reader: impl AsyncRead + Unpin) -> Result<(String, i64)> {
+        let blob = self.stage_blob(reader).await?;
+        self.commit_blob(blob).await
+    }
+
+    /// Write all data of `reader` to a temporary file, which only becomes a
+    /// blob of the layout once committed with [`Self::commit_blob`], e.g.
+    /// after the data was verified.
+    async fn stage_blob(&mut self, mut reader: impl AsyncRead + Unpin) -> Result<StagedBlob> {
+        self.tmp_index += 1;
+        let tmp = self
+            .dir
+            .join("blobs")
+            .join(format!(".tmp-{}", self.tmp_index));
+        let mut file = tokio::fs::File::create(&tmp).await?;
+        let mut hasher = LayerDigestHasher::Sha256(sha2::Sha256::new());
+        let mut size = 0;
+        let mut buffer = vec![0u8; CAPACITY];
+
+        let written: Result<()> = async {
+            loop {
+                let n = reader.read(&mut buffer).await?;
+                if n == 0 {
+                    break;
+                }
+                hasher.digest_update(&buffer[..n]);
+                file.write_all(&buffer[..n]).await?;
+                size += n;
+            }
+            file.flush().await?;
+            Ok(())
+        }
+        .await;
+        if let Err(e) = written {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(e);
+        }
+
+        Ok(StagedBlob {
+            tmp,
+            digest: hasher.digest_finalize(),
+            size: size as i64,
+        })
+    }
+
+    /// Move a staged blob to its place in the layout, returns its sha256
+    /// digest and size.
+    async fn commit_blob(&self, blob: StagedBlob) -> Result<(String, i64)> {
+        let target = self
+            .dir
+            .join("blobs")
+            .join("sha256")
+            .join(blob.digest.trim_start_matches(DIGEST_SHA256_PREFIX));
+        if let Err(e) = tokio::fs::rename(&blob.tmp, &target).await {
+            blob.discard().await;
+            return Err(e.into());
+        }
+        Ok((blob.digest, blob.size))
+    }
+
+    async fn finish(
+        &self,
+        manifest: &OciDescriptor,
+        name: &str,
+        source_digest: &str,
+    ) -> Result<()> {
+        let layout = json!({ "imageLayoutVersion": OCI_LAYOUT_VERSION });
+        tokio::fs::write(self.dir.join("oci-layout"), serde_json::to_vec(&layout)?).await?;
+
+        let index = json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.oci.image.index.v1+json",
+            "manifests": [{
+                "mediaType": manifest.media_type,
+                "digest": manifest.digest,
+                "size": manifest.size,
+                "annotations": {
+                    (ANNOTATION_REF_NAME): name,
+                    (ANNOTATION_CONTAINERD_IMAGE_NAME): name,
+                    (ANNOTATION_SOURCE_DIGEST): source_digest,
+                },
+            }],
+        });
+        tokio::fs::write(self.dir.join("index.json"), serde_json::to_vec(&index)?).await?;
+        Ok(())
+    }
+}
+
+/// Export the image `client` points to as an OCI layout at `path`.
+///
+/// `expected_digest` is the manifest digest recorded when the image was
+/// pulled. If `decrypt_config` is given, encrypted layers are exported
+/// decrypted, otherwise they are kept encrypted as they were pulled.
+/// Returns the digest of the exported manifest.
+pub(crate) async fn export_image(
+    client: &mut PullClient<'_>,
+    name: &str,
+    expected_digest: &str,
+    path: &Path,
+    decrypt_config: &Option<&str>,
+) -> Result<String> {
+    let (mut manifest, digest, config) = client.pull_manifest().await?;
+    if digest != expected_digest {
+        bail!(
+            "{}: pulled {}, now {}",
+            ERR_EXPORT_MANIFEST_CHANGED,
+            expected_digest,
+            digest
+        );
+    }
+
+    let mut writer = LayoutWriter::new(path).await?;
+    let config_blob = writer.stage_blob(config.as_bytes()).await?;
+    if config_blob.digest != manifest.config.digest {
+        let config_digest = config_blob.digest.clone();
+        config_blob.discard().await;
+        bail!(
+            "config digest {} mismatch with manifest {}",
+            config_digest,
+            manifest.config.digest
+        );
+    }
+    writer.commit_blob(config_blob).await?;
+
+    let mut layers = Vec::with_capacity(manifest.layers.len());
+    for layer in &manifest.layers {
+        layers.push(export_layer(client, &mut writer, layer, decrypt_config).await?);
+    }
+    manifest.layers = layers;
+
+    let media_type = manifest
+        .media_type
+        .clone()
+        .unwrap_or_else(|| OCI_IMAGE_MEDIA_TYPE.to_string());
+    let manifest = serde_json::to_vec(&manifest)?;
+    let (manifest_digest, size) = writer.write_blob(&manifest[..]).await?;
+    let descriptor = OciDescriptor {
+        media_type,
+        digest: manifest_digest.clone(),
+        size,
+        ..Default::default()
+    };
+    writer.finish(&descriptor, name, &digest).await?;
+
+    Ok(manifest_digest)
+}
+
+/// Export a layer blob, returns the descriptor of the exported blob. The
+/// blob only lands in the layout once the layer matches its digest in the
+/// manifest, and is removed otherwise.
+async fn export_layer(
+    client: &PullClient<'_>,
+    writer: &mut LayoutWriter,
+    layer: &OciDescriptor,
+    decrypt_config: &Option<&str>,
+) -> Result<OciDescriptor> {
+    let mut reader = HashingReader::new(
+        client.layer_reader(layer).await?,
+        hasher_for(&layer.digest)?,
+    );
+
+    let decryptor = Decryptor::from_descriptor(layer);
+    let (blob, exported) = match decrypt_config {
+        Some(dc) if decryptor.is_encrypted() => {
+            let decrypt_key = decryptor
+                .get_decrypt_key(layer, dc)
+                .map_err(|e| anyhow!("failed to get decrypt key {}", e))?;
+            let plaintext_layer = decryptor
+                .async_get_plaintext_layer(&mut reader, layer, &decrypt_key)
+                .map_err(|e| anyhow!("failed to async_get_plaintext_layer: {:?}", e))?;
+            let blob = writer.stage_blob(Box::pin(plaintext_layer)).await?;
+
+            let annotations: HashMap<String, String> = layer
+                .annotations
+                .iter()
+                .flatten()
+                .filter(|(k, _)| !k.starts_with(ENCRYPTION_ANNOTATION_PREFIX))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            let exported = OciDescriptor {
+                media_type: decryptor.media_type.clone(),
+                digest: blob.digest.clone(),
+                size: blob.size,
+                urls: layer.urls.clone(),
+                annotations: (!annotations.is_empty()).then_some(annotations),
+            };
+            (blob, exported)
+        }
+        _ => (writer.stage_blob(&mut reader).await?, layer.clone()),
+    };
+
+    let calculated = reader.digest_finalize();
+    if calculated != layer.digest {
+        blob.discard().await;
+        bail!(
+            "layer digest {} mismatch with manifest {}",
+            calculated,
+            layer.digest
+        );
+    }
+    writer.commit_blob(blob).await?;
+
+    Ok(exported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local::LocalSource;
+    use oci_distribution::secrets::RegistryAuth;
+    use oci_distribution::Reference;
+    use std::io::Write;
+
+    #[tokio::test]
+    async fn test_layout_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = LayoutWriter::new(dir.path()).await.unwrap();
+        let (digest, size) = writer.write_blob(&b"blob"[..]).await.unwrap();
+        assert_eq!(
+            digest,
+            format!("sha256:{:x}", sha2::Sha256::digest(b"blob"))
+        );
+        assert_eq!(size, 4);
+        let blob = dir
+            .path()
+            .join("blobs/sha256")
+            .join(digest.trim_start_matches("sha256:"));
+        assert_eq!(std::fs::read(blob).unwrap(), b"blob");
+
+        let manifest = OciDescriptor {
+            media_type: OCI_IMAGE_MEDIA_TYPE.to_string(),
+            digest: digest.clone(),
+            size,
+            ..Default::default()
+        };
+        writer
+            .finish(&manifest, "docker.io/library/busybox:latest", "sha256:1234")
+            .await
+            .unwrap();
+        assert!(dir.path().join("oci-layout").exists());
+        let index: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.path().join("index.json")).unwrap()).unwrap();
+        assert_eq!(index["manifests"][0]["digest"], digest.as_str());
+        assert_eq!(
+            index["manifests"][0]["annotations"][ANNOTATION_SOURCE_DIGEST],
+            "sha256:1234"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_staged_blob_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = LayoutWriter::new(dir.path()).await.unwrap();
+        let blob = writer.stage_blob(&b"blob"[..]).await.unwrap();
+
+        // nothing is in the layout before the blob is committed
+        let blobs = dir.path().join("blobs/sha256");
+        assert_eq!(std::fs::read_dir(&blobs).unwrap().count(), 0);
+
+        blob.discard().await;
+        assert_eq!(std::fs::read_dir(&blobs).unwrap().count(), 0);
+        assert_eq!(
+            std::fs::read_dir(dir.path().join("blobs")).unwrap().count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_image() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "file", &b"data"[..])
+            .unwrap();
+        let layer = builder.into_inner().unwrap();
+        let diff_id = format!("sha256:{:x}", sha2::Sha256::digest(&layer));
+        let config = format!(
+            r#"{{"architecture":"amd64","os":"linux","rootfs":{{"type":"layers","diff_ids":["{}"]}}}}"#,
+            diff_id
+        );
+        let manifest =
+            r#"[{"Config":"config.json","RepoTags":["busybox:latest"],"Layers":["layer.tar"]}]"#;
+
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in [
+            ("layer.tar", &layer[..]),
+            ("config.json", config.as_bytes()),
+            ("manifest.json", manifest.as_bytes()),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data).unwrap();
+        }
+        let mut archive = tempfile::NamedTempFile::new().unwrap();
+        archive.write_all(&builder.into_inner().unwrap()).unwrap();
+        let url = format!("docker-archive:{}", archive.path().display());
+
+        let work_dir = tempfile::tempdir().unwrap();
+        let export_dir = tempfile::tempdir().unwrap();
+        let source = LocalSource::from_url(&url).await.unwrap().unwrap();
+        let (_, source_digest, _) = source.manifest_and_config();
+        let mut client = PullClient::new(
+            Reference::try_from("busybox").unwrap(),
+            work_dir.path(),
+            &RegistryAuth::Anonymous,
+            1,
+        )
+        .unwrap();
+        client.local_source = Some(source);
+
+        let name = "docker.io/library/busybox:latest";
+        assert!(
+            export_image(&mut client, name, "sha256:1234", export_dir.path(), &None)
+                .await
+                .is_err()
+        );
+        let digest = export_image(&mut client, name, &source_digest, export_dir.path(), &None)
+            .await
+            .unwrap();
+
+        let exported = LocalSource::from_url(&format!("oci:{}", export_dir.path().display()))
+            .await
+            .unwrap()
+            .unwrap();
+        let (m, d, c) = exported.manifest_and_config();
+        assert_eq!(d, digest);
+        assert_eq!(c, config);
+        assert_eq!(m.layers[0].digest, diff_id);
+        assert_eq!(exported.reference().whole(), name);
+    }
+}
@@ -0,0 +1,461 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Extraction of single files of an image.
+//!
+//! Policy engines often only need a few files of an image to decide on it,
+//! e.g. `/etc/passwd` or a license file. Instead of unpacking the whole
+//! rootfs, the layers are streamed from the top one down, and only the
+//! entries of the requested paths are kept in memory. A path is resolved
+//! by the highest layer that has it, or that hides it with a whiteout, an
+//! opaque directory or a non-directory entry at one of its parents. Lower
+//! layers are only fetched while some requested path is unresolved.
+//!
+//! Every streamed layer is checked against its digest in the manifest and
+//! its diff ID in the image config, so the extracted content is the one a
+//! pull of the image would unpack.
+//! The manifest is given by the caller, which checks it against the
+//! signature policy before, see [`crate::image::ImageClient::extract_files`].
+
+use anyhow::{anyhow, bail, Context, Result};
+use oci_distribution::manifest::{OciDescriptor, OciImageManifest};
+use oci_spec::image::ImageConfiguration;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::channel;
+use tokio::io::AsyncReadExt;
+
+use crate::digest::{hasher_for, DigestHasher, HashingReader};
+use crate::pull::{BlobDigestMismatch, LayerDigestMismatch, PullClient};
+use crate::stream::{ChannelRead, LayerDecoding};
+
+/// Max size of an extracted regular file.
+pub const MAX_EXTRACTED_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+const WHITEOUT_PREFIX: &str = ".wh.";
+const WHITEOUT_OPAQUE_DIR: &str = ".wh..wh..opq";
+const CAPACITY: usize = 32768;
+
+/// An extracted entry of the image rootfs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExtractedFile {
+    /// A regular file, with its permission bits and its content.
+    File { mode: u32, data: Vec<u8> },
+
+    /// A directory, with its permission bits. Its content is not extracted.
+    Directory { mode: u32 },
+
+    /// A symbolic link, with its target, which is not followed.
+    Symlink(PathBuf),
+
+    /// A hard link, with the absolute path of the file it links to.
+    Hardlink(PathBuf),
+}
+
+/// The files extracted from an image.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExtractedFiles {
+    /// Digest of the manifest the files were extracted from.
+    pub manifest_digest: String,
+
+    /// The requested paths found in the image rootfs, by their absolute
+    /// path. Devices and fifos are not extracted.
+    pub files: HashMap<PathBuf, ExtractedFile>,
+}
+
+/// The entries of a layer relevant to the requested paths.
+#[derive(Debug, Default)]
+struct LayerScan {
+    found: HashMap<PathBuf, ExtractedFile>,
+
+    /// Paths removed by a whiteout.
+    whiteouts: HashSet<PathBuf>,
+
+    /// Directories whose content in the lower layers is hidden.
+    opaque_dirs: HashSet<PathBuf>,
+
+    /// Non-directory entries replacing a directory of the lower layers.
+    non_dirs: HashSet<PathBuf>,
+}
+
+impl LayerScan {
+    /// Resolve the `pending` paths this layer has or hides.
+    fn resolve(
+        mut self,
+        pending: &mut HashSet<PathBuf>,
+        extracted: &mut HashMap<PathBuf, ExtractedFile>,
+    ) {
+        pending.retain(|path| {
+            if let Some(file) = self.found.remove(path) {
+                extracted.insert(Path::new("/").join(path), file);
+                return false;
+            }
+
+            let hidden = path.ancestors().any(|p| self.whiteouts.contains(p))
+                || path
+                    .ancestors()
+                    .skip(1)
+                    .any(|p| self.opaque_dirs.contains(p) || self.non_dirs.contains(p));
+            !hidden
+        });
+    }
+}
+
+/// Path of a layer entry or a requested path, relative to the rootfs.
+fn normalize(path: &Path) -> Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(c) => normalized.push(c),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir | Component::Prefix(_) => {
+                bail!("invalid path {}", path.display())
+            }
+        }
+    }
+
+    Ok(normalized)
+}
+
+fn read_entry(entry: &mut tar::Entry<impl Read>) -> Result<Option<ExtractedFile>> {
+    let entry_type = entry.header().entry_type();
+    let mode = entry.header().mode()?;
+
+    let file = if entry_type.is_file() {
+        if entry.size() > MAX_EXTRACTED_FILE_SIZE {
+            bail!(
+                "file of {} bytes exceeds the max of {} bytes",
+                entry.size(),
+                MAX_EXTRACTED_FILE_SIZE
+            );
+        }
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data)?;
+        ExtractedFile::File { mode, data }
+    } else if entry_type.is_dir() {
+        ExtractedFile::Directory { mode }
+    } else if entry_type.is_symlink() || entry_type.is_hard_link() {
+        let target = entry
+            .link_name()?
+            .ok_or_else(|| anyhow!("link without target"))?
+            .into_owned();
+        if entry_type.is_symlink() {
+            ExtractedFile::Symlink(target)
+        } else {
+            ExtractedFile::Hardlink(Path::new("/").join(normalize(&target)?))
+        }
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(file))
+}
+
+/// Scan the uncompressed tar of a layer for the `wanted` paths. The whole
+/// tar is read, so that its digest can be checked.
+fn scan_tar(input: impl Read, wanted: &HashSet<PathBuf>) -> Result<LayerScan> {
+    let is_parent = |dir: &Path| wanted.iter().any(|p| p != dir && p.starts_with(dir));
+    let mut scan = LayerScan::default();
+
+    let mut archive = tar::Archive::new(input);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = normalize(&entry.path()?)?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let parent = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        if name == WHITEOUT_OPAQUE_DIR {
+            if is_parent(&parent) {
+                scan.opaque_dirs.insert(parent);
+            }
+            continue;
+        }
+
+        if let Some(hidden) = name.strip_prefix(WHITEOUT_PREFIX) {
+            let hidden = parent.join(hidden);
+            if wanted.contains(&hidden) || is_parent(&hidden) {
+                scan.whiteouts.insert(hidden);
+            }
+            continue;
+        }
+
+        if !entry.header().entry_type().is_dir() && is_parent(&path) {
+            scan.non_dirs.insert(path.clone());
+        }
+
+        if wanted.contains(&path) {
+            let file =
+                read_entry(&mut entry).with_context(|| format!("extract {}", path.display()))?;
+            if let Some(file) = file {
+                scan.found.insert(path, file);
+            }
+        }
+    }
+
+    io::copy(&mut archive.into_inner(), &mut io::sink())?;
+    Ok(scan)
+}
+
+/// Stream a layer through [`scan_tar`], checking its digest and `diff_id`.
+async fn scan_layer(
+    client: &PullClient<'_>,
+    layer: &OciDescriptor,
+    diff_id: &str,
+    decrypt_config: &Option<&str>,
+    wanted: HashSet<PathBuf>,
+) -> Result<LayerScan> {
+    let mut hasher = hasher_for(diff_id)?;
+    let decoding = LayerDecoding::new(layer, decrypt_config)?;
+    let mut blob = HashingReader::new(
+        client.layer_reader(layer).await?,
+        hasher_for(&layer.digest)?,
+    );
+    let mut reader = decoding.tar_reader(&mut blob)?;
+
+    let (tx, rx) = channel();
+    let scan_thread = std::thread::spawn(move || scan_tar(ChannelRead::new(rx), &wanted));
+
+    let mut send_failed = false;
+    loop {
+        let mut buffer = vec![0u8; CAPACITY];
+        let n = reader.read(&mut buffer).await?;
+        if n == 0 {
+            break;
+        }
+
+        buffer.truncate(n);
+        hasher.digest_update(&buffer);
+        if tx.send(buffer).is_err() {
+            // The scan thread failed, the error is returned below.
+            send_failed = true;
+            break;
+        }
+    }
+
+    // Close the channel to signal EOF.
+    drop(tx);
+
+    let scan = tokio::task::spawn_blocking(|| scan_thread.join())
+        .await?
+        .map_err(|e| anyhow!("scan thread failed {:?}", e))??;
+    if send_failed {
+        bail!("scan finished before the end of the layer");
+    }
+
+    let uncompressed_digest = hasher.digest_finalize();
+    if uncompressed_digest != diff_id {
+        return Err(anyhow::Error::new(LayerDigestMismatch {
+            uncompressed_digest,
+            diff_id: diff_id.to_string(),
+        }));
+    }
+
+    drop(reader);
+    tokio::io::copy(&mut blob, &mut tokio::io::sink()).await?;
+    let digest = blob.digest_finalize();
+    if digest != layer.digest {
+        return Err(anyhow::Error::new(BlobDigestMismatch {
+            digest,
+            expected: layer.digest.clone(),
+        }));
+    }
+
+    Ok(scan)
+}
+
+/// Extract the `paths` of the rootfs of the image `client` points to.
+pub(crate) async fn extract_files(
+    client: &mut PullClient<'_>,
+    manifest: (OciImageManifest, String, String),
+    paths: &[&str],
+    decrypt_config: &Option<&str>,
+) -> Result<ExtractedFiles> {
+    let mut pending = HashSet::new();
+    for path in paths {
+        let normalized = normalize(Path::new(path))?;
+        if normalized.as_os_str().is_empty() {
+            bail!("cannot extract the root directory");
+        }
+        pending.insert(normalized);
+    }
+
+    let (manifest, manifest_digest, config) = manifest;
+    let image_config = ImageConfiguration::from_reader(config.as_bytes())?;
+    let diff_ids = image_config.rootfs().diff_ids();
+    if diff_ids.len() != manifest.layers.len() {
+        bail!(
+            "{} layers in the manifest, {} diff IDs in the config",
+            manifest.layers.len(),
+            diff_ids.len()
+        );
+    }
+
+    let mut files = HashMap::new();
+    for (layer, diff_id) in manifest.layers.iter().zip(diff_ids).rev() {
+        if pending.is_empty() {
+            break;
+        }
+
+        let scan = scan_layer(client, layer, diff_id, decrypt_config, pending.clone())
+            .await
+            .with_context(|| format!("scan layer {}", layer.digest))?;
+        scan.resolve(&mut pending, &mut files);
+    }
+
+    Ok(ExtractedFiles {
+        manifest_digest,
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local::LocalSource;
+    use oci_distribution::secrets::RegistryAuth;
+    use oci_distribution::Reference;
+    use sha2::Digest;
+    use std::io::Write;
+
+    fn layer(entries: &[(&str, tar::EntryType, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, entry_type, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(*entry_type);
+            header.set_mode(0o644);
+            if entry_type.is_symlink() {
+                header.set_size(0);
+                header
+                    .set_link_name(std::str::from_utf8(data).unwrap())
+                    .unwrap();
+                header.set_cksum();
+                builder.append_data(&mut header, path, io::empty()).unwrap();
+            } else {
+                header.set_size(data.len() as u64);
+                header.set_cksum();
+                builder.append_data(&mut header, path, *data).unwrap();
+            }
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_extract_files() {
+        use tar::EntryType::{Directory, Regular, Symlink};
+
+        let layers = [
+            layer(&[
+                ("etc/", Directory, b""),
+                ("etc/passwd", Regular, b"root:x:0:0"),
+                ("etc/shadow", Regular, b"root:*"),
+                ("opt/app/LICENSE", Regular, b"MIT"),
+                ("var/log/", Directory, b""),
+                ("var/log/messages", Regular, b"boot"),
+            ]),
+            layer(&[
+                ("etc/passwd", Regular, b"root:x:0:0\nuser:x:1000:1000"),
+                ("etc/.wh.shadow", Regular, b""),
+                ("opt/app/.wh..wh..opq", Regular, b""),
+                ("opt/app/README", Regular, b"app"),
+                ("var/log", Symlink, b"/tmp"),
+            ]),
+        ];
+
+        let diff_ids: Vec<String> = layers
+            .iter()
+            .map(|l| format!("sha256:{:x}", sha2::Sha256::digest(l)))
+            .collect();
+        let config = serde_json::json!({
+            "architecture": "amd64",
+            "os": "linux",
+            "rootfs": {"type": "layers", "diff_ids": diff_ids},
+        })
+        .to_string();
+        let manifest =
+            r#"[{"Config":"config.json","RepoTags":["app:latest"],"Layers":["0.tar","1.tar"]}]"#;
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in [
+            ("0.tar", &layers[0][..]),
+            ("1.tar", &layers[1][..]),
+            ("config.json", config.as_bytes()),
+            ("manifest.json", manifest.as_bytes()),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data).unwrap();
+        }
+        let mut archive = tempfile::NamedTempFile::new().unwrap();
+        archive.write_all(&builder.into_inner().unwrap()).unwrap();
+
+        let work_dir = tempfile::tempdir().unwrap();
+        let url = format!("docker-archive:{}", archive.path().display());
+        let source = LocalSource::from_url(&url).await.unwrap().unwrap();
+        let (_, source_digest, _) = source.manifest_and_config();
+        let mut client = PullClient::new(
+            Reference::try_from("app").unwrap(),
+            work_dir.path(),
+            &RegistryAuth::Anonymous,
+            1,
+        )
+        .unwrap();
+        client.local_source = Some(source);
+
+        let paths = [
+            "/etc/passwd",
+            "/etc/shadow",
+            "/etc",
+            "opt/app/LICENSE",
+            "./opt/app/README",
+            "/var/log/messages",
+            "/var/log",
+            "/missing",
+        ];
+        let manifest = client.pull_manifest().await.unwrap();
+        let extracted = extract_files(&mut client, manifest.clone(), &paths, &None)
+            .await
+            .unwrap();
+        assert_eq!(extracted.manifest_digest, source_digest);
+
+        let files = extracted.files;
+        assert_eq!(
+            files[Path::new("/etc/passwd")],
+            ExtractedFile::File {
+                mode: 0o644,
+                data: b"root:x:0:0\nuser:x:1000:1000".to_vec()
+            }
+        );
+        assert_eq!(
+            files[Path::new("/etc")],
+            ExtractedFile::Directory { mode: 0o644 }
+        );
+        assert!(matches!(
+            &files[Path::new("/opt/app/README")],
+            ExtractedFile::File { data, .. } if data == b"app"
+        ));
+        assert_eq!(
+            files[Path::new("/var/log")],
+            ExtractedFile::Symlink("/tmp".into())
+        );
+        // removed by a whiteout, an opaque directory and a symlink
+        assert!(!files.contains_key(Path::new("/etc/shadow")));
+        assert!(!files.contains_key(Path::new("/opt/app/LICENSE")));
+        assert!(!files.contains_key(Path::new("/var/log/messages")));
+        assert!(!files.contains_key(Path::new("/missing")));
+        assert_eq!(files.len(), 4);
+
+        assert!(extract_files(&mut client, manifest.clone(), &["/"], &None)
+            .await
+            .is_err());
+        assert!(
+            extract_files(&mut client, manifest, &["/etc/../root"], &None)
+                .await
+                .is_err()
+        );
+    }
+}
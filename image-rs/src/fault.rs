@@ -0,0 +1,388 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Faults injected into the pull pipeline, for chaos tests.
+//!
+//! A pull failing halfway must leave nothing behind: no mount, no layer
+//! partially unpacked into the layer store, no plaintext of a decrypted
+//! layer. With the `fault-injection` feature, every
+//! [`crate::image::ImageClient`] has a [`FaultInjector`] the tests arm with
+//! [`Fault`]s, which fire once each:
+//!
+//! - the blob requests to the registry are counted, and
+//!   [`Fault::DropBlobRequest`] fails the one it is due at,
+//! - [`Fault::CorruptLayerDigest`] has the next layer unpacked fail its
+//!   digest verification,
+//! - [`Fault::DelayDecrypt`] stalls the decrypted and decompressed stream of
+//!   the next layer after its first chunk, i.e. once unpacking has started,
+//!   so that the pull can be cancelled meanwhile,
+//! - the mounts of the snapshotters wrapped with
+//!   [`FaultInjector::wrap_snapshots`], and of the eccfs snapshotters built
+//!   per pull in place of a wrapped one, are counted, and
+//!   [`Fault::FailMount`] fails the one it is due at,
+//! - [`Fault::FailRelabel`] fails the relabeling of the next bundle mounted,
+//!   see [`crate::relabel`].
+//!
+//! The feature is meant for tests only, and not to be built into a guest.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::time::Sleep;
+use tokio_util::sync::CancellationToken;
+
+use crate::digest::DIGEST_SHA256_PREFIX;
+use crate::snapshots::{MountPoint, SnapshotType, Snapshotter};
+
+/// A fault to inject, see the [module docs](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Fail the `n`th blob request to the registry from now, `0` being the
+    /// next one.
+    DropBlobRequest(usize),
+
+    /// Expect a wrong uncompressed digest of the next layer unpacked.
+    CorruptLayerDigest,
+
+    /// Stall the decrypted stream of the next layer for the duration.
+    DelayDecrypt(Duration),
+
+    /// Fail the `n`th mount of the wrapped snapshotters from now, `0` being
+    /// the next one.
+    FailMount(usize),
+
+    /// Fail the relabeling of the next bundle mounted.
+    FailRelabel,
+}
+
+/// Error of a fault fired.
+#[derive(Debug)]
+pub struct InjectedFault(pub Fault);
+
+impl fmt::Display for InjectedFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "injected fault {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InjectedFault {}
+
+struct Armed {
+    fault: Fault,
+
+    /// The count of the blob requests or mounts the fault is due at.
+    at: usize,
+}
+
+#[derive(Default)]
+struct State {
+    armed: Vec<Armed>,
+
+    blob_requests: usize,
+
+    mounts: usize,
+
+    /// Whether the snapshotters of the client are wrapped.
+    wrapping: bool,
+
+    fired: Vec<Fault>,
+}
+
+impl State {
+    // take the armed fault of a kind due at `at`, if any
+    fn take(&mut self, is_kind: impl Fn(&Fault) -> bool, at: usize) -> Option<Fault> {
+        let i = self
+            .armed
+            .iter()
+            .position(|armed| is_kind(&armed.fault) && armed.at == at)?;
+        let fault = self.armed.remove(i).fault;
+        self.fired.push(fault.clone());
+        Some(fault)
+    }
+}
+
+/// Faults armed for the pulls of a client, see the [module docs](self).
+#[derive(Default)]
+pub struct FaultInjector {
+    state: Mutex<State>,
+}
+
+impl FaultInjector {
+    /// Arm `fault`.
+    pub fn inject(&self, fault: Fault) {
+        let mut state = self.lock();
+        let at = match fault {
+            Fault::DropBlobRequest(n) => state.blob_requests + n,
+            Fault::FailMount(n) => state.mounts + n,
+            _ => 0,
+        };
+        state.armed.push(Armed { fault, at });
+    }
+
+    /// Disarm the faults not fired yet, and forget the fired ones.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.armed.clear();
+        state.fired.clear();
+    }
+
+    /// The faults fired, in order.
+    pub fn fired(&self) -> Vec<Fault> {
+        self.lock().fired.clone()
+    }
+
+    /// Have [`Fault::FailMount`] fail the mounts of `snapshots`.
+    pub fn wrap_snapshots(
+        self: &Arc<Self>,
+        snapshots: &mut HashMap<SnapshotType, Box<dyn Snapshotter>>,
+    ) {
+        self.lock().wrapping = true;
+        *snapshots = snapshots
+            .drain()
+            .map(|(snapshot_type, inner)| (snapshot_type, self.wrap(inner)))
+            .collect();
+    }
+
+    /// `snapshot`, built for a pull in place of one of the snapshotters of
+    /// the client, wrapped like them if they are, see
+    /// [`FaultInjector::wrap_snapshots`].
+    pub(crate) fn wrap_replacement(
+        self: &Arc<Self>,
+        snapshot: Box<dyn Snapshotter>,
+    ) -> Box<dyn Snapshotter> {
+        let wrapping = self.lock().wrapping;
+        match wrapping {
+            true => self.wrap(snapshot),
+            false => snapshot,
+        }
+    }
+
+    /// Count a blob request of the layer `digest` to the registry, failing
+    /// it if a [`Fault::DropBlobRequest`] is due.
+    pub(crate) fn blob_request(&self, digest: &str) -> Result<()> {
+        let mut state = self.lock();
+        let at = state.blob_requests;
+        state.blob_requests += 1;
+        match state.take(|f| matches!(f, Fault::DropBlobRequest(_)), at) {
+            Some(fault) => Err(anyhow::Error::new(InjectedFault(fault))
+                .context(format!("blob request of layer {digest} dropped"))),
+            None => Ok(()),
+        }
+    }
+
+    /// The `diff_id` the next layer is verified against, a wrong one if a
+    /// [`Fault::CorruptLayerDigest`] is armed.
+    pub(crate) fn layer_diff_id(&self, diff_id: String) -> String {
+        match self.lock().take(|f| *f == Fault::CorruptLayerDigest, 0) {
+            Some(_) => format!(
+                "{}{:x}",
+                DIGEST_SHA256_PREFIX,
+                Sha256::digest(diff_id.as_bytes())
+            ),
+            None => diff_id,
+        }
+    }
+
+    /// The decrypted stream of the next layer, stalled after its first
+    /// chunk if a [`Fault::DelayDecrypt`] is armed.
+    pub(crate) fn delay_decrypt<'a>(
+        &self,
+        reader: Box<dyn AsyncRead + Unpin + Send + 'a>,
+    ) -> Box<dyn AsyncRead + Unpin + Send + 'a> {
+        match self.lock().take(|f| matches!(f, Fault::DelayDecrypt(_)), 0) {
+            Some(Fault::DelayDecrypt(delay)) => Box::new(Delayed {
+                inner: reader,
+                delay: Some(delay),
+                sleep: None,
+            }),
+            _ => reader,
+        }
+    }
+
+    /// Fail the relabeling of the mount at `mount_path` if a
+    /// [`Fault::FailRelabel`] is armed.
+    pub(crate) fn relabel(&self, mount_path: &Path) -> Result<()> {
+        match self.lock().take(|f| *f == Fault::FailRelabel, 0) {
+            Some(fault) => Err(anyhow::Error::new(InjectedFault(fault))
+                .context(format!("relabel of {} failed", mount_path.display()))),
+            None => Ok(()),
+        }
+    }
+
+    // count a mount of a wrapped snapshotter, failing it if a
+    // `Fault::FailMount` is due
+    fn mount(&self, mount_path: &Path) -> Result<()> {
+        let mut state = self.lock();
+        let at = state.mounts;
+        state.mounts += 1;
+        match state.take(|f| matches!(f, Fault::FailMount(_)), at) {
+            Some(fault) => Err(anyhow::Error::new(InjectedFault(fault))
+                .context(format!("mount at {} failed", mount_path.display()))),
+            None => Ok(()),
+        }
+    }
+
+    fn wrap(self: &Arc<Self>, inner: Box<dyn Snapshotter>) -> Box<dyn Snapshotter> {
+        Box::new(FaultySnapshotter {
+            inner,
+            faults: self.clone(),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // a failed assertion of a test must not poison the other ones
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+struct Delayed<'a> {
+    inner: Box<dyn AsyncRead + Unpin + Send + 'a>,
+
+    delay: Option<Duration>,
+
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl AsyncRead for Delayed<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(sleep) = &mut this.sleep {
+            ready!(sleep.as_mut().poll(cx));
+            this.sleep = None;
+        }
+
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        if buf.filled().len() > filled {
+            if let Some(delay) = this.delay.take() {
+                this.sleep = Some(Box::pin(tokio::time::sleep(delay)));
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+struct FaultySnapshotter {
+    inner: Box<dyn Snapshotter>,
+
+    faults: Arc<FaultInjector>,
+}
+
+impl Snapshotter for FaultySnapshotter {
+    fn mount(&mut self, layer_path: &[&str], mount_path: &Path) -> Result<MountPoint> {
+        self.faults.mount(mount_path)?;
+        self.inner.mount(layer_path, mount_path)
+    }
+
+    fn mount_with_cancellation(
+        &mut self,
+        layer_path: &[&str],
+        mount_path: &Path,
+        cancel: &CancellationToken,
+    ) -> Result<MountPoint> {
+        self.faults.mount(mount_path)?;
+        self.inner
+            .mount_with_cancellation(layer_path, mount_path, cancel)
+    }
+
+    fn unmount(&self, mount_point: &MountPoint) -> Result<()> {
+        self.inner.unmount(mount_point)
+    }
+
+    fn layer_digests(&self) -> Option<Vec<String>> {
+        self.inner.layer_digests()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use tokio::io::AsyncReadExt;
+
+    #[test]
+    fn test_drop_blob_request() {
+        let faults = FaultInjector::default();
+        faults.blob_request("sha256:0").unwrap();
+        faults.inject(Fault::DropBlobRequest(1));
+        faults.blob_request("sha256:1").unwrap();
+        let e = faults.blob_request("sha256:2").unwrap_err();
+        assert!(e.is::<InjectedFault>());
+
+        // fired once
+        faults.blob_request("sha256:2").unwrap();
+        assert_eq!(faults.fired(), [Fault::DropBlobRequest(1)]);
+
+        faults.inject(Fault::DropBlobRequest(0));
+        faults.clear();
+        faults.blob_request("sha256:3").unwrap();
+        assert!(faults.fired().is_empty());
+    }
+
+    #[test]
+    fn test_corrupt_layer_digest() {
+        let faults = FaultInjector::default();
+        let diff_id = format!("{DIGEST_SHA256_PREFIX}{}", "1".repeat(64));
+        assert_eq!(faults.layer_diff_id(diff_id.clone()), diff_id);
+
+        faults.inject(Fault::CorruptLayerDigest);
+        let corrupt = faults.layer_diff_id(diff_id.clone());
+        assert_ne!(corrupt, diff_id);
+        assert!(corrupt.starts_with(DIGEST_SHA256_PREFIX));
+        assert_eq!(faults.layer_diff_id(diff_id.clone()), diff_id);
+    }
+
+    #[test]
+    fn test_fail_mount() {
+        let faults = FaultInjector::default();
+        faults.inject(Fault::FailMount(0));
+        assert!(faults.mount(Path::new("/rootfs")).is_err());
+        faults.mount(Path::new("/rootfs")).unwrap();
+        assert_eq!(faults.fired(), [Fault::FailMount(0)]);
+    }
+
+    #[test]
+    fn test_fail_relabel() {
+        let faults = FaultInjector::default();
+        faults.relabel(Path::new("/rootfs")).unwrap();
+        faults.inject(Fault::FailRelabel);
+        assert!(faults.relabel(Path::new("/rootfs")).is_err());
+        faults.relabel(Path::new("/rootfs")).unwrap();
+        assert_eq!(faults.fired(), [Fault::FailRelabel]);
+    }
+
+    #[tokio::test]
+    async fn test_delay_decrypt() {
+        let faults = FaultInjector::default();
+        faults.inject(Fault::DelayDecrypt(Duration::from_millis(50)));
+        let mut reader = faults.delay_decrypt(Box::new(&[7u8; 1500][..]));
+
+        let start = Instant::now();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, [7u8; 1500]);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        // not armed any more
+        let start = Instant::now();
+        let mut reader = faults.delay_decrypt(Box::new(&[7u8; 1500][..]));
+        reader.read_to_end(&mut data).await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,434 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Flattening of unpacked layers into a single rootfs.
+//!
+//! Snapshotters without a union filesystem, e.g. `occlum_unionfs`, copy the
+//! unpacked layers one over another into the rootfs. Copying them strictly
+//! bottom up is slow for images with many layers, although most layers
+//! touch disjoint paths. The layers are grouped into rounds instead: a
+//! layer depends on every lower layer it overwrites, hides with a whiteout
+//! or an opaque directory, or whose directory it replaces by a file or the
+//! other way around. A layer is applied in the round after the last one it
+//! depends on, and the layers of a round are applied in parallel.
+//!
+//! Directories shared by layers are not a dependency, their content is
+//! merged. Their permissions are set to the ones of the highest layer
+//! having them once all the layers are applied.
+
+use anyhow::{Context, Result};
+use log::warn;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::ops::Bound;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+const WHITEOUT_PREFIX: &str = ".wh.";
+const WHITEOUT_OPAQUE_DIR: &str = ".wh..wh..opq";
+
+/// What a layer does to a path of the rootfs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Change {
+    /// Creates the directory or merges into it.
+    Dir,
+
+    /// Creates the directory, hiding its content in the lower layers.
+    OpaqueDir,
+
+    /// Creates or replaces a regular file, a symlink or a special file.
+    NonDir,
+
+    /// Removes the path.
+    Whiteout,
+}
+
+/// The paths of a layer relative to the rootfs, with its changes to them.
+#[derive(Debug, Default)]
+struct LayerChanges {
+    changes: BTreeMap<PathBuf, Change>,
+}
+
+impl LayerChanges {
+    fn scan(layer: &Path) -> Result<Self> {
+        let mut scan = Self::default();
+        scan.scan_dir(layer, Path::new(""))?;
+        Ok(scan)
+    }
+
+    fn scan_dir(&mut self, dir: &Path, relative: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir).with_context(|| format!("read {}", dir.display()))? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name_str = name.to_string_lossy();
+            if name_str == WHITEOUT_OPAQUE_DIR {
+                self.changes
+                    .insert(relative.to_path_buf(), Change::OpaqueDir);
+                continue;
+            }
+            if let Some(hidden) = name_str.strip_prefix(WHITEOUT_PREFIX) {
+                self.changes.insert(relative.join(hidden), Change::Whiteout);
+                continue;
+            }
+
+            let path = relative.join(&name);
+            if entry.file_type()?.is_dir() {
+                self.changes.entry(path.clone()).or_insert(Change::Dir);
+                self.scan_dir(&entry.path(), &path)?;
+            } else {
+                self.changes.insert(path, Change::NonDir);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the changes of `self` have to be applied after the ones of
+    /// the lower layer `lower`.
+    fn depends_on(&self, lower: &LayerChanges) -> bool {
+        self.changes.iter().any(|(path, change)| {
+            match lower.changes.get(path) {
+                Some(Change::Dir | Change::OpaqueDir) if *change == Change::Dir => {}
+                Some(_) => return true,
+                None => {}
+            }
+
+            // the path replaces or hides what the lower layer has below it
+            if *change != Change::Dir && lower.has_below(path) {
+                return true;
+            }
+
+            // a directory of the path is a file in the lower layer, or is
+            // removed or emptied by it
+            path.ancestors()
+                .skip(1)
+                .any(|p| matches!(lower.changes.get(p), Some(c) if *c != Change::Dir))
+        })
+    }
+
+    fn has_below(&self, path: &Path) -> bool {
+        self.changes
+            .range::<Path, _>((Bound::Excluded(path), Bound::Unbounded))
+            .next()
+            .is_some_and(|(p, _)| p.starts_with(path))
+    }
+}
+
+/// The round each layer of `layers`, bottom first, is applied in.
+fn rounds(layers: &[LayerChanges]) -> Vec<usize> {
+    let mut rounds: Vec<usize> = Vec::with_capacity(layers.len());
+    for (i, layer) in layers.iter().enumerate() {
+        let round = (0..i)
+            .filter(|&j| layer.depends_on(&layers[j]))
+            .map(|j| rounds[j] + 1)
+            .max()
+            .unwrap_or_default();
+        rounds.push(round);
+    }
+
+    rounds
+}
+
+/// Copy the unpacked `layers`, top first as they are given to
+/// [`Snapshotter::mount`](crate::snapshots::Snapshotter::mount), into
+/// `destination`, applying whiteouts and opaque directories. Up to
+/// `concurrency` independent layers are copied in parallel.
+pub fn apply_layers(layers: &[&str], destination: &Path, concurrency: usize) -> Result<()> {
+    let layers: Vec<&Path> = layers.iter().rev().map(Path::new).collect();
+    let changes = layers
+        .iter()
+        .map(|layer| LayerChanges::scan(layer))
+        .collect::<Result<Vec<_>>>()?;
+    let rounds = rounds(&changes);
+
+    fs::create_dir_all(destination)?;
+    let last_round = rounds.iter().copied().max().unwrap_or_default();
+    for round in 0..=last_round {
+        let batch: Vec<&Path> = layers
+            .iter()
+            .zip(&rounds)
+            .filter(|(_, r)| **r == round)
+            .map(|(layer, _)| *layer)
+            .collect();
+        apply_batch(&batch, destination, concurrency.max(1))?;
+    }
+
+    // the permissions of a shared directory are the ones of the highest
+    // layer having it
+    let mut done = HashSet::new();
+    for (layer, changes) in layers.iter().zip(&changes).rev() {
+        for (path, change) in &changes.changes {
+            if !done.insert(path) || !matches!(change, Change::Dir | Change::OpaqueDir) {
+                continue;
+            }
+            // the directory may be removed by a whiteout of a parent
+            let target = destination.join(path);
+            if fs::symlink_metadata(&target).is_ok_and(|m| m.is_dir()) {
+                let permissions = fs::metadata(layer.join(path))?.permissions();
+                fs::set_permissions(target, permissions)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_batch(batch: &[&Path], destination: &Path, concurrency: usize) -> Result<()> {
+    let next = AtomicUsize::new(0);
+    let failed = Mutex::new(None);
+    thread::scope(|s| {
+        for _ in 0..concurrency.min(batch.len()) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some(layer) = batch.get(i) else {
+                    break;
+                };
+                if let Err(e) = apply_dir(layer, destination) {
+                    let e = e.context(format!("apply layer {}", layer.display()));
+                    failed
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .get_or_insert(e);
+                    break;
+                }
+            });
+        }
+    });
+
+    match failed.into_inner().unwrap_or_else(PoisonError::into_inner) {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Apply the content of the layer dir `source` to `destination`.
+fn apply_dir(source: &Path, destination: &Path) -> Result<()> {
+    let mut entries = fs::read_dir(source)?.collect::<std::io::Result<Vec<_>>>()?;
+    // the content of the lower layers is removed before the one of this
+    // layer is copied
+    if entries
+        .iter()
+        .any(|entry| entry.file_name() == WHITEOUT_OPAQUE_DIR)
+    {
+        for entry in fs::read_dir(destination)? {
+            remove(&entry?.path())?;
+        }
+    }
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name();
+        let name_str = name.to_string_lossy();
+        if name_str == WHITEOUT_OPAQUE_DIR {
+            continue;
+        }
+        if let Some(hidden) = name_str.strip_prefix(WHITEOUT_PREFIX) {
+            remove(&destination.join(hidden))?;
+            continue;
+        }
+
+        let from = entry.path();
+        let to = destination.join(&name);
+        let file_type = entry.file_type()?;
+        let existing = fs::symlink_metadata(&to).ok();
+        if file_type.is_dir() {
+            if existing.is_some_and(|m| !m.is_dir()) {
+                remove(&to)?;
+            }
+            // the layers of a round may create the same directory, its
+            // permissions are set once all the layers are applied
+            if let Err(e) = fs::create_dir(&to) {
+                if e.kind() != std::io::ErrorKind::AlreadyExists {
+                    return Err(e).with_context(|| format!("create {}", to.display()));
+                }
+            }
+            apply_dir(&from, &to)?;
+            continue;
+        }
+
+        if existing.is_some() {
+            remove(&to)?;
+        }
+        if file_type.is_symlink() {
+            symlink(fs::read_link(&from)?, &to)?;
+        } else if file_type.is_file() {
+            fs::copy(&from, &to).with_context(|| format!("copy {}", from.display()))?;
+        } else {
+            warn!("skip special file {}", from.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn remove(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(m) if m.is_dir() => fs::remove_dir_all(path)?,
+        Ok(_) => fs::remove_file(path)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Create a layer dir holding `files`, directories end with `/`.
+    fn layer(root: &Path, name: &str, files: &[(&str, &str)]) -> String {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        for (path, content) in files {
+            match path.strip_suffix('/') {
+                Some(path) => fs::create_dir_all(dir.join(path)).unwrap(),
+                None => {
+                    let path = dir.join(path);
+                    fs::create_dir_all(path.parent().unwrap()).unwrap();
+                    fs::write(path, content).unwrap();
+                }
+            }
+        }
+        dir.display().to_string()
+    }
+
+    /// The files of `dir` with their content, directories end with `/`.
+    fn tree(dir: &Path) -> Vec<(String, String)> {
+        let mut tree: Vec<_> = walkdir::WalkDir::new(dir)
+            .min_depth(1)
+            .into_iter()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let path = entry.path().strip_prefix(dir).unwrap().display();
+                if entry.file_type().is_dir() {
+                    (format!("{path}/"), String::new())
+                } else {
+                    (path.to_string(), fs::read_to_string(entry.path()).unwrap())
+                }
+            })
+            .collect();
+        tree.sort();
+        tree
+    }
+
+    fn changes(files: &[(&str, Change)]) -> LayerChanges {
+        LayerChanges {
+            changes: files
+                .iter()
+                .map(|(path, change)| (PathBuf::from(path), *change))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_rounds() {
+        use Change::*;
+
+        let base = changes(&[("etc", Dir), ("etc/passwd", NonDir), ("usr", Dir)]);
+        // merges into the shared directories only
+        let independent = changes(&[("usr", Dir), ("usr/bin", Dir), ("usr/bin/app", NonDir)]);
+        let overwrite = changes(&[("etc", Dir), ("etc/passwd", NonDir)]);
+        let whiteout = changes(&[("etc", Dir), ("etc/passwd", Whiteout)]);
+        let opaque = changes(&[("usr", OpaqueDir)]);
+        let replace_dir = changes(&[("usr", NonDir)]);
+        let below_file = changes(&[("etc", Dir), ("etc/passwd", Dir), ("etc/passwd/a", NonDir)]);
+
+        assert!(!independent.depends_on(&base));
+        assert!(overwrite.depends_on(&base));
+        assert!(whiteout.depends_on(&base));
+        assert!(opaque.depends_on(&independent));
+        assert!(!opaque.depends_on(&overwrite));
+        assert!(replace_dir.depends_on(&independent));
+        assert!(below_file.depends_on(&base));
+        // the content is created in the directory the lower layer empties
+        assert!(independent.depends_on(&opaque));
+
+        assert_eq!(
+            rounds(&[base, independent, overwrite, whiteout, opaque]),
+            vec![0, 0, 1, 2, 1]
+        );
+    }
+
+    #[test]
+    fn test_apply_layers() {
+        let root = tempfile::tempdir().unwrap();
+        let layers = [
+            layer(
+                root.path(),
+                "0",
+                &[
+                    ("etc/passwd", "root"),
+                    ("etc/hosts", "localhost"),
+                    ("opt/app/lib/a.so", "a"),
+                    ("opt/app/lib/b.so", "b"),
+                    ("var/log/", ""),
+                    ("data", "file"),
+                ],
+            ),
+            layer(
+                root.path(),
+                "1",
+                &[("usr/bin/sh", "sh"), ("etc/passwd", "user")],
+            ),
+            layer(
+                root.path(),
+                "2",
+                &[
+                    ("etc/.wh.hosts", ""),
+                    ("opt/app/.wh..wh..opq", ""),
+                    ("opt/app/lib/c.so", "c"),
+                    ("data/", ""),
+                    ("data/new", "new"),
+                ],
+            ),
+            layer(
+                root.path(),
+                "3",
+                &[("var/.wh.log", ""), ("etc/passwd", "top")],
+            ),
+        ];
+        fs::set_permissions(
+            Path::new(&layers[3]).join("etc"),
+            fs::Permissions::from_mode(0o700),
+        )
+        .unwrap();
+        // top first, as given to the snapshotters
+        let layers: Vec<&str> = layers.iter().rev().map(|l| l.as_str()).collect();
+
+        let expected: Vec<(String, String)> = [
+            ("data/", ""),
+            ("data/new", "new"),
+            ("etc/", ""),
+            ("etc/passwd", "top"),
+            ("opt/", ""),
+            ("opt/app/", ""),
+            ("opt/app/lib/", ""),
+            ("opt/app/lib/c.so", "c"),
+            ("usr/", ""),
+            ("usr/bin/", ""),
+            ("usr/bin/sh", "sh"),
+            ("var/", ""),
+        ]
+        .iter()
+        .map(|(p, c)| (p.to_string(), c.to_string()))
+        .collect();
+
+        for concurrency in [1, 4] {
+            let destination = root.path().join(format!("rootfs-{concurrency}"));
+            apply_layers(&layers, &destination, concurrency).unwrap();
+            assert_eq!(tree(&destination), expected);
+
+            let mode = fs::metadata(destination.join("etc"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Policy on how old the pulled images may be.
+//!
+//! Registries keep serving vulnerable images long after they were rebuilt.
+//! The policy rejects images created longer ago than a max age, by the
+//! `created` time of their config, and images revoked by a list fetched
+//! from the KBS for every pull, e.g.
+//!
+//! ```json
+//! {
+//!     "revoked": ["sha256:<manifest or config digest>"],
+//!     "not_before": "2024-01-01T00:00:00Z"
+//! }
+//! ```
+//!
+//! where the images created before `not_before`, or without a creation
+//! time, are revoked as well. The images pulled before are checked again,
+//! so that a revoked image is refused even if it is present in the guest.
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// Image freshness policy, see the [module docs](self).
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct FreshnessPolicy {
+    /// Max age in seconds of the images. Images without a creation time are
+    /// rejected then.
+    ///
+    /// The age is not checked if not set.
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+
+    /// Resource uri of the revocation list, e.g.
+    /// `kbs:///default/image-revocations/test`.
+    ///
+    /// No images are revoked if not set.
+    #[serde(default)]
+    pub revocation_uri: Option<String>,
+}
+
+/// Images revoked by the KBS.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct RevocationList {
+    /// Digests of the revoked manifests or configs.
+    #[serde(default)]
+    pub revoked: Vec<String>,
+
+    /// Images created before are revoked, as well as the images without
+    /// a creation time.
+    #[serde(default)]
+    pub not_before: Option<DateTime<Utc>>,
+}
+
+// The fields of the image config the policy needs.
+#[derive(Deserialize)]
+struct Created {
+    #[serde(default)]
+    created: Option<String>,
+}
+
+impl FreshnessPolicy {
+    /// Check the image with the manifest and config `digests`, and the
+    /// JSON `image_config`, fetching the revocation list if any.
+    pub async fn check(&self, digests: &[&str], image_config: &str) -> Result<()> {
+        let revocations = match &self.revocation_uri {
+            Some(uri) => {
+                let list = crate::resource::get_resource(uri)
+                    .await
+                    .map_err(|e| anyhow!("failed to get image revocation list: {:?}", e))?;
+                Some(
+                    serde_json::from_slice(&list)
+                        .map_err(|e| anyhow!("invalid image revocation list: {}", e))?,
+                )
+            }
+            None => None,
+        };
+
+        let created: Created = serde_json::from_str(image_config)?;
+        let created = created
+            .created
+            .map(|created| {
+                DateTime::parse_from_rfc3339(&created)
+                    .map(|created| created.with_timezone(&Utc))
+                    .map_err(|e| anyhow!("invalid image creation time {:?}: {}", created, e))
+            })
+            .transpose()?;
+        self.check_at(digests, created, revocations.as_ref(), Utc::now())
+    }
+
+    fn check_at(
+        &self,
+        digests: &[&str],
+        created: Option<DateTime<Utc>>,
+        revocations: Option<&RevocationList>,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        if let Some(revocations) = revocations {
+            if let Some(digest) = digests
+                .iter()
+                .find(|digest| revocations.revoked.iter().any(|r| r == *digest))
+            {
+                bail!("image {} is revoked", digest);
+            }
+
+            if let Some(not_before) = revocations.not_before {
+                let created = created.ok_or_else(|| anyhow!("image has no creation time"))?;
+                if created < not_before {
+                    bail!(
+                        "image created at {} is revoked, images must be created after {}",
+                        created,
+                        not_before
+                    );
+                }
+            }
+        }
+
+        if let Some(max_age) = self.max_age_secs {
+            let created = created.ok_or_else(|| anyhow!("image has no creation time"))?;
+            let age = (now - created).num_seconds();
+            if age > 0 && age as u64 > max_age {
+                bail!(
+                    "image created at {} is older than {} seconds",
+                    created,
+                    max_age
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str =
+        "sha256:e1c082e3d3c45cccac829840a25941e679c25d438cc8412c2fa221cf1a824e6a";
+    const CONFIG: &str = "sha256:0000000000000000000000000000000000000000000000000000000000000000";
+
+    fn time(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_check_at() {
+        let now = time("2024-06-01T00:00:00Z");
+        let created = Some(time("2024-05-01T00:00:00Z"));
+        let digests = [MANIFEST, CONFIG];
+
+        let policy = FreshnessPolicy::default();
+        assert!(policy.check_at(&digests, None, None, now).is_ok());
+
+        let policy = FreshnessPolicy {
+            max_age_secs: Some(60 * 24 * 3600),
+            revocation_uri: None,
+        };
+        assert!(policy.check_at(&digests, created, None, now).is_ok());
+        assert!(policy.check_at(&digests, None, None, now).is_err());
+        let old = Some(time("2024-01-01T00:00:00Z"));
+        assert!(policy.check_at(&digests, old, None, now).is_err());
+
+        let revocations = RevocationList {
+            revoked: vec![CONFIG.to_string()],
+            not_before: None,
+        };
+        let policy = FreshnessPolicy::default();
+        assert!(policy
+            .check_at(&digests, created, Some(&revocations), now)
+            .is_err());
+        assert!(policy
+            .check_at(&[MANIFEST], created, Some(&revocations), now)
+            .is_ok());
+
+        let revocations = RevocationList {
+            revoked: vec![],
+            not_before: Some(time("2024-05-15T00:00:00Z")),
+        };
+        assert!(policy
+            .check_at(&digests, created, Some(&revocations), now)
+            .is_err());
+        assert!(policy
+            .check_at(&digests, None, Some(&revocations), now)
+            .is_err());
+    }
+
+    #[test]
+    fn test_revocation_list_from_json() {
+        let data =
+            format!(r#"{{"revoked": ["{MANIFEST}"], "not_before": "2024-01-01T00:00:00Z"}}"#);
+        let parsed: RevocationList = serde_json::from_str(&data).unwrap();
+        assert_eq!(parsed.revoked, vec![MANIFEST.to_string()]);
+        assert_eq!(parsed.not_before, Some(time("2024-01-01T00:00:00Z")));
+
+        let parsed: FreshnessPolicy = serde_json::from_str("{}").unwrap();
+        assert_eq!(parsed, FreshnessPolicy::default());
+    }
+}
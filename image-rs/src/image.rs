@@ -2,33 +2,57 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::{anyhow, bail, Result};
-use log::warn;
+use anyhow::{anyhow, bail, Context, Result};
+use log::{info, warn};
 use oci_distribution::manifest::{OciDescriptor, OciImageManifest};
 use oci_distribution::secrets::RegistryAuth;
 use oci_distribution::Reference;
 use oci_spec::image::{ImageConfiguration, Os};
-use serde::Deserialize;
-use std::collections::{BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
+use std::collections::{hash_map::Entry, BTreeSet, HashMap};
 use std::convert::TryFrom;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 
+use crate::blob_cache::BlobCache;
 use crate::bundle::{create_runtime_config, BUNDLE_ROOTFS};
-use crate::config::{ImageConfig, CONFIGURATION_FILE_PATH};
+use crate::config::{
+    BackgroundPriority, ImageConfig, LayerCacheConfig, PullPolicy, CONFIGURATION_FILE_PATH,
+};
+use crate::customization;
 use crate::decoder::Compression;
+use crate::disk_space::DiskReservations;
+use crate::events::{PullErrorCode, PullEvent, PullEvents};
+use crate::extract::ExtractedFiles;
+use crate::layer_cache::{self, LayerPin, LayerPins};
+use crate::layer_groups::{layer_groups, GroupMount, LayerGroup};
+use crate::local::LocalSource;
+use crate::manifest_cache::ManifestCache;
+use crate::measure::{MeasurementHook, RootfsMeasurement};
 use crate::meta_store::{MetaStore, METAFILE};
-use crate::pull::PullClient;
-use crate::snapshots::{SnapshotType, Snapshotter};
+use crate::pull::{credential_key, LayerLocks, PullClient, RegistryClients, ANONYMOUS_CREDENTIAL};
+use crate::pull_budget::PullBudget;
+use crate::snapshots::{MountPoint, SnapshotType, Snapshotter};
+use crate::trace;
+use crate::verification::VerificationReport;
+use crate::volume::{self, ImageVolume};
+use crate::ERR_PULL_CANCELLED;
+
+#[cfg(feature = "signature")]
+use crate::verification::SignatureRejected;
 
 #[cfg(feature = "snapshot-eccfs")]
-use crate::snapshots::eccfs::EccOvlFs;
+use crate::snapshots::eccfs::{EccOvlFs, PreflightReport, SuppliedKeys, ANNOTATION_ECCFS_KEYS};
 #[cfg(feature = "snapshot-unionfs")]
 use crate::snapshots::occlum::unionfs::Unionfs;
 #[cfg(feature = "snapshot-overlayfs")]
 use crate::snapshots::overlay::OverlayFs;
+#[cfg(feature = "snapshot-wasm")]
+use crate::snapshots::wasm::{self, WasmSnapshot};
 
 #[cfg(feature = "nydus")]
 use crate::nydus::{service, utils};
@@ -42,7 +66,7 @@ use crate::nydus::{service, utils};
 pub const IMAGE_SECURITY_CONFIG_DIR: &str = "/run/image-security";
 
 /// The metadata info for container image layer.
-#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
 pub struct LayerMeta {
     /// Image layer compression algorithm type.
     pub decoder: Compression,
@@ -61,7 +85,7 @@ pub struct LayerMeta {
 }
 
 /// The metadata info for container image.
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct ImageMeta {
     /// The digest of the image configuration.
     pub id: String,
@@ -78,13 +102,75 @@ pub struct ImageMeta {
     /// Whether image is signed.
     pub signed: bool,
 
+    /// Why the image passed the signature verification, if
+    /// `security_validate` is enabled.
+    #[serde(default)]
+    pub verification: Option<VerificationReport>,
+
     /// The metadata of image layers.
     pub layer_metas: Vec<LayerMeta>,
+
+    /// The manifest of the image, so that pulls with a [`PullPolicy`]
+    /// reusing the image don't fetch it again.
+    #[serde(default)]
+    pub manifest: Option<OciImageManifest>,
+
+    /// The credentials the image was pulled with, or checked to be allowed
+    /// with, each only kept as a hash. Pulls with a
+    /// [`PullPolicy`] reusing the image with other credentials need the
+    /// registry to allow them first, unless it was pulled anonymously.
+    #[serde(default)]
+    pub pulled_with: BTreeSet<String>,
+}
+
+impl ImageMeta {
+    /// The labels of the image configuration.
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.image_config
+            .config()
+            .as_ref()
+            .and_then(|config| config.labels().clone())
+            .unwrap_or_default()
+    }
+
+    /// The exposed ports of the image configuration, e.g. `80/tcp`.
+    pub fn exposed_ports(&self) -> Vec<String> {
+        self.image_config
+            .config()
+            .as_ref()
+            .and_then(|config| config.exposed_ports().clone())
+            .unwrap_or_default()
+    }
+}
+
+/// An image pulled by [`ImageClient::pull_image`].
+#[derive(Clone, Debug)]
+pub struct PulledImage {
+    /// The bundle dir passed by the caller.
+    pub bundle_dir: PathBuf,
+
+    /// The metadata of the image, including the parsed image configuration
+    /// (Entrypoint, Cmd, Env, User, ...).
+    pub meta: ImageMeta,
+
+    /// The layers mounted apart from the rootfs, each into its own dir of
+    /// the bundle, see [`crate::layer_groups`]. They are unmounted by
+    /// [`ImageClient::unmount_layer_groups`].
+    pub layer_groups: Vec<GroupMount>,
+
+    /// The customization layer overlaid on top of the rootfs, if any, see
+    /// [`ImageClient::pull_image_with_customization`].
+    pub customization: Option<LayerMeta>,
 }
 
 /// The`image-rs` client will support OCI image
 /// pulling, image signing verfication, image layer
 /// decryption/unpack/store and management.
+///
+/// Pulls take `&self`, so that the images of the containers of a pod can be
+/// pulled at the same time by one client. They share the layers, the
+/// registry tokens and the [`crate::pull_budget`]; only the mounts of the
+/// snapshotters are serialized.
 pub struct ImageClient {
     /// The config for `image-rs` client.
     pub config: ImageConfig,
@@ -92,23 +178,47 @@ pub struct ImageClient {
     /// The metadata database for `image-rs` client.
     pub meta_store: Arc<Mutex<MetaStore>>,
 
-    /// The supported snapshots for `image-rs` client.
-    pub snapshots: HashMap<SnapshotType, Box<dyn Snapshotter>>,
-}
+    /// The supported snapshots for `image-rs` client, locked by the pull
+    /// mounting with them, and shared with the clients of
+    /// [`ImageClient::with_pull_config`].
+    pub snapshots: Arc<Mutex<HashMap<SnapshotType, Box<dyn Snapshotter>>>>,
 
-impl Default for ImageClient {
-    // construct a default instance of `ImageClient`
-    fn default() -> ImageClient {
-        let config = ImageConfig::try_from(Path::new(CONFIGURATION_FILE_PATH)).unwrap_or_default();
-        let meta_store = MetaStore::try_from(Path::new(METAFILE)).unwrap_or_default();
-        let snapshots = Self::init_snapshots(&config, &meta_store);
+    /// Hook called with the measurement of every mounted rootfs.
+    pub measurement_hook: Option<Arc<dyn MeasurementHook>>,
 
-        ImageClient {
-            config,
-            meta_store: Arc::new(Mutex::new(meta_store)),
-            snapshots,
-        }
-    }
+    /// Cache of the manifests of the pulled references, which can be shared
+    /// by several clients.
+    pub manifest_cache: Option<Arc<ManifestCache>>,
+
+    /// Pull-through cache of the layer blobs, which can be shared by
+    /// several clients.
+    pub blob_cache: Option<Arc<BlobCache>>,
+
+    /// Download budget of the concurrent pulls, which can be shared by
+    /// several clients.
+    pub pull_budget: Option<Arc<PullBudget>>,
+
+    /// Locks of the layers being pulled, shared by the clients with the
+    /// same layer store.
+    pub layer_locks: Arc<LayerLocks>,
+
+    /// Layers held by the pulls in progress, shared by the clients with the
+    /// same layer store, see [`crate::layer_cache`].
+    pub layer_pins: Arc<LayerPins>,
+
+    /// Disk space reserved by the pulls in flight, shared by the clients
+    /// with the same work dir, see [`crate::disk_space`].
+    pub disk_reservations: Arc<DiskReservations>,
+
+    /// Registry clients of the pulls, holding their tokens.
+    pub registry_clients: Arc<RegistryClients>,
+
+    /// Events of the pulls, see [`crate::events`].
+    pub events: Arc<PullEvents>,
+
+    /// Faults injected into the pulls, see [`crate::fault`].
+    #[cfg(feature = "fault-injection")]
+    pub faults: Arc<crate::fault::FaultInjector>,
 }
 
 impl ImageClient {
@@ -146,6 +256,7 @@ impl ImageClient {
                     .work_dir
                     .join(SnapshotType::OcclumUnionfs.to_string()),
                 index: std::sync::atomic::AtomicUsize::new(*occlum_unionfs_index),
+                concurrency: config.max_concurrent_unpack,
             };
             snapshots.insert(
                 SnapshotType::OcclumUnionfs,
@@ -155,37 +266,156 @@ impl ImageClient {
 
         #[cfg(feature = "snapshot-eccfs")]
         {
-            let eccfs = EccOvlFs {
-                data_dir: config
+            let mut eccfs = EccOvlFs::new(
+                config
                     .work_dir
                     .join(SnapshotType::Eccfs.to_string()),
-            };
+            );
+            if let Some(eccfs_config) = &config.eccfs_config {
+                eccfs.configure(eccfs_config);
+            }
+            eccfs.shared_layers = meta_store.shared_layer_db.clone();
             snapshots.insert(
                 SnapshotType::Eccfs,
                 Box::new(eccfs) as Box<dyn Snapshotter>,
             );
         }
 
+        #[cfg(feature = "snapshot-wasm")]
+        snapshots.insert(
+            SnapshotType::Wasm,
+            Box::<WasmSnapshot>::default() as Box<dyn Snapshotter>,
+        );
+
         snapshots
     }
 
     /// Create an ImageClient instance with specific work directory.
     pub fn new(image_work_dir: PathBuf) -> Self {
-        let config = ImageConfig::new(image_work_dir);
+        Self::with_config(ImageConfig::new(image_work_dir))
+    }
+
+    /// Create an ImageClient instance with the config file at
+    /// [`CONFIGURATION_FILE_PATH`], or the defaults if there is none, see
+    /// [`ImageConfig::load_or_default`]. A config file failing to load or
+    /// validate is an error.
+    pub fn from_config_file() -> Result<Self> {
+        let path = Path::new(CONFIGURATION_FILE_PATH);
+        let config = ImageConfig::load_or_default(path)
+            .with_context(|| format!("load image-rs config {}", path.display()))?;
+        Ok(Self::with_config(config))
+    }
+
+    /// Create an ImageClient instance with `config`, once it is validated.
+    pub fn from_config(config: ImageConfig) -> Result<Self> {
+        config.validate().context("invalid image-rs config")?;
+        Ok(Self::with_config(config))
+    }
+
+    fn with_config(config: ImageConfig) -> Self {
         let meta_store = MetaStore::try_from(Path::new(METAFILE)).unwrap_or_default();
         let snapshots = Self::init_snapshots(&config, &meta_store);
+        let manifest_cache = Self::init_manifest_cache(&config);
+        let blob_cache = Self::init_blob_cache(&config);
+        let pull_budget = Self::init_pull_budget(&config);
+        #[cfg(feature = "encryption")]
+        Self::init_layer_key_cache(&config);
 
         Self {
             config,
             meta_store: Arc::new(Mutex::new(meta_store)),
-            snapshots,
+            snapshots: Arc::new(Mutex::new(snapshots)),
+            measurement_hook: None,
+            manifest_cache,
+            blob_cache,
+            pull_budget,
+            layer_locks: Arc::default(),
+            layer_pins: Arc::default(),
+            disk_reservations: Arc::default(),
+            registry_clients: Arc::default(),
+            events: Arc::default(),
+            #[cfg(feature = "fault-injection")]
+            faults: Arc::default(),
+        }
+    }
+
+    /// A client pulling with `config` instead of the config of this one,
+    /// e.g. with the options of a request applied, sharing everything else
+    /// with this one: the layers, the snapshotters, the caches and the pulls
+    /// in progress. Unlike changing the config of this client, it only
+    /// applies to the pulls of the client returned, so that they can run at
+    /// the same time as the other pulls. `config` must keep the work dir.
+    pub fn with_pull_config(&self, config: ImageConfig) -> Result<Self> {
+        if config.work_dir != self.config.work_dir {
+            bail!("the work dir cannot be changed per pull");
+        }
+        config.validate().context("invalid image-rs config")?;
+
+        Ok(Self {
+            config,
+            meta_store: self.meta_store.clone(),
+            snapshots: self.snapshots.clone(),
+            measurement_hook: self.measurement_hook.clone(),
+            manifest_cache: self.manifest_cache.clone(),
+            blob_cache: self.blob_cache.clone(),
+            pull_budget: self.pull_budget.clone(),
+            layer_locks: self.layer_locks.clone(),
+            layer_pins: self.layer_pins.clone(),
+            disk_reservations: self.disk_reservations.clone(),
+            registry_clients: self.registry_clients.clone(),
+            events: self.events.clone(),
+            #[cfg(feature = "fault-injection")]
+            faults: self.faults.clone(),
+        })
+    }
+
+    /// Create the manifest cache, if enabled by the config.
+    pub fn init_manifest_cache(config: &ImageConfig) -> Option<Arc<ManifestCache>> {
+        config
+            .manifest_cache
+            .as_ref()
+            .map(|cache_config| Arc::new(ManifestCache::new(cache_config)))
+    }
+
+    /// Configure the cache of the unwrapped layer keys of ocicrypt, disabling
+    /// it if not enabled by the config.
+    #[cfg(feature = "encryption")]
+    pub fn init_layer_key_cache(config: &ImageConfig) {
+        let (max_entries, ttl_secs) = config
+            .layer_key_cache
+            .as_ref()
+            .map_or((0, 0), |cache| (cache.max_entries, cache.ttl_secs));
+        let ttl = std::time::Duration::from_secs(ttl_secs);
+        ocicrypt_rs::unwrap_cache::configure(max_entries, ttl);
+    }
+
+    /// Create the blob cache, if enabled by the config. Pulls go to the
+    /// registry if its dir cannot be created.
+    pub fn init_blob_cache(config: &ImageConfig) -> Option<Arc<BlobCache>> {
+        let cache_config = config.blob_cache.as_ref()?;
+        match BlobCache::new(cache_config) {
+            Ok(cache) => Some(Arc::new(cache)),
+            Err(e) => {
+                warn!("blob cache disabled: {e:?}");
+                None
+            }
         }
     }
 
+    /// Create the download budget, if enabled by the config.
+    pub fn init_pull_budget(config: &ImageConfig) -> Option<Arc<PullBudget>> {
+        config
+            .pull_budget
+            .as_ref()
+            .map(|budget_config| Arc::new(PullBudget::new(budget_config)))
+    }
+
     /// pull_image pulls an image with optional auth info and decrypt config
     /// and store the pulled data under user defined work_dir/layers.
-    /// It will return the image ID with prepeared bundle: a rootfs directory,
-    /// and config.json will be ready in the bundle_dir passed by user.
+    /// It will return the [`PulledImage`] with prepeared bundle: a rootfs directory,
+    /// and config.json will be ready in the bundle_dir passed by user. What
+    /// config.json took from the image config is also written to
+    /// [`crate::bundle::BUNDLE_CONFIG_FRAGMENT`], for callers to merge into their own one.
     ///
     /// If at least one of `security_validate` and `auth` in self.config is
     /// enabled, `auth_info` **must** be given. There will establish a SecureChannel
@@ -194,14 +424,223 @@ impl ImageClient {
     /// When `auth_info` parameter is given and `auth` in self.config is also enabled,
     /// this function will only try to get auth from `auth_info`, and if fails then
     /// then returns an error.
+    ///
+    /// Besides registry references, `image_url` can be an `oci:` layout dir or a
+    /// `docker-archive:` tarball inside the guest, see [`crate::local`].
     pub async fn pull_image(
-        &mut self,
+        &self,
         image_url: &str,
         bundle_dir: &Path,
         auth_info: &Option<&str>,
         decrypt_config: &Option<&str>,
-    ) -> Result<String> {
-        let reference = Reference::try_from(image_url)?;
+    ) -> Result<PulledImage> {
+        self.pull_image_with_cancellation(
+            image_url,
+            bundle_dir,
+            auth_info,
+            decrypt_config,
+            &CancellationToken::new(),
+        )
+        .await
+    }
+
+    /// pull_image_with_cancellation behaves like [`ImageClient::pull_image`],
+    /// but aborts as soon as `cancel` is cancelled (e.g. the pod was deleted
+    /// while the image was still being pulled).
+    ///
+    /// Cancellation is checked in the download, decrypt, unpack and snapshot
+    /// phases. Partially unpacked layers and partially built snapshot
+    /// artifacts are removed before [`ERR_PULL_CANCELLED`] is returned.
+    pub async fn pull_image_with_cancellation(
+        &self,
+        image_url: &str,
+        bundle_dir: &Path,
+        auth_info: &Option<&str>,
+        decrypt_config: &Option<&str>,
+        cancel: &CancellationToken,
+    ) -> Result<PulledImage> {
+        let policy = self.config.pull_policy;
+        self.pull(
+            image_url,
+            bundle_dir,
+            auth_info,
+            decrypt_config,
+            policy,
+            None,
+            cancel,
+        )
+        .await
+    }
+
+    /// pull_image_with_policy behaves like [`ImageClient::pull_image`], but
+    /// with `policy` instead of the configured
+    /// [`ImageConfig::pull_policy`], e.g. the `imagePullPolicy` of the
+    /// container the image is pulled for.
+    pub async fn pull_image_with_policy(
+        &self,
+        image_url: &str,
+        bundle_dir: &Path,
+        auth_info: &Option<&str>,
+        decrypt_config: &Option<&str>,
+        policy: PullPolicy,
+    ) -> Result<PulledImage> {
+        self.pull(
+            image_url,
+            bundle_dir,
+            auth_info,
+            decrypt_config,
+            policy,
+            None,
+            &CancellationToken::new(),
+        )
+        .await
+    }
+
+    /// pull_image_with_customization behaves like [`ImageClient::pull_image`],
+    /// but overlays the files of `customization`, a dir or a tarball, as the
+    /// top-most read-only layer of the rootfs, see [`crate::customization`].
+    /// The layer is recorded in the metadata store with the bundle, and is
+    /// named by the rootfs measurement.
+    pub async fn pull_image_with_customization(
+        &self,
+        image_url: &str,
+        bundle_dir: &Path,
+        auth_info: &Option<&str>,
+        decrypt_config: &Option<&str>,
+        customization: &Path,
+    ) -> Result<PulledImage> {
+        let policy = self.config.pull_policy;
+        self.pull(
+            image_url,
+            bundle_dir,
+            auth_info,
+            decrypt_config,
+            policy,
+            Some(customization),
+            &CancellationToken::new(),
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn pull(
+        &self,
+        image_url: &str,
+        bundle_dir: &Path,
+        auth_info: &Option<&str>,
+        decrypt_config: &Option<&str>,
+        policy: PullPolicy,
+        customization: Option<&Path>,
+        cancel: &CancellationToken,
+    ) -> Result<PulledImage> {
+        self.events.emit(PullEvent::PullStarted {
+            image_url: image_url.to_string(),
+            bundle_dir: bundle_dir.to_path_buf(),
+        });
+
+        let res: Result<PulledImage> = async {
+            let customization = match customization {
+                Some(source) => {
+                    let layer_dir = self.layer_dir()?;
+                    let source = source.to_path_buf();
+                    let layer = tokio::task::spawn_blocking(move || {
+                        customization::prepare(&source, &layer_dir)
+                    })
+                    .await
+                    .map_err(|e| anyhow!("customization task failed: {:?}", e))?
+                    .context("failed to prepare the customization layer")?;
+                    Some(layer)
+                }
+                None => None,
+            };
+
+            let (id, _pin) = trace::in_span_with(
+                "pull_image",
+                [("image.url", image_url.to_string())],
+                self.pull_bundle(
+                    image_url,
+                    bundle_dir,
+                    auth_info,
+                    decrypt_config,
+                    policy,
+                    customization.as_ref(),
+                    false,
+                    cancel,
+                ),
+            )
+            .await?;
+            let mut m = self.meta_store.lock().await;
+            let meta = m
+                .image_db
+                .get(&id)
+                .cloned()
+                .ok_or_else(|| anyhow!("image {id} pulled without metadata"))?;
+            if let Some(layer) = &customization {
+                m.layer_db
+                    .insert(layer.compressed_digest.clone(), layer.clone());
+                m.customization_db.insert(
+                    bundle_dir.display().to_string(),
+                    layer.compressed_digest.clone(),
+                );
+            }
+            m.bundle_db
+                .insert(bundle_dir.display().to_string(), id.clone());
+            let layer_groups = m
+                .group_db
+                .get(&bundle_dir.display().to_string())
+                .cloned()
+                .unwrap_or_default();
+            self.keep_layer_budget(&mut m, &meta.layer_metas);
+
+            Ok(PulledImage {
+                bundle_dir: bundle_dir.to_path_buf(),
+                meta,
+                layer_groups,
+                customization,
+            })
+        }
+        .await;
+        if let Err(e) = &res {
+            self.events.emit(PullEvent::PullFailed {
+                image_url: image_url.to_string(),
+                code: PullErrorCode::of(e, cancel.is_cancelled()),
+                message: format!("{e:#}"),
+            });
+        }
+        res
+    }
+
+    /// Pull the image and prepare the bundle, with the `customization`
+    /// layer on top of the rootfs, returning the image ID and the pin of its
+    /// layers, to be held until the bundle is recorded. The layer groups
+    /// mounted apart from the rootfs are recorded in the meta store. If
+    /// `volume` is set, the image is mounted read-only at `bundle_dir` as an
+    /// image volume instead, see [`crate::volume`].
+    #[allow(clippy::too_many_arguments)]
+    async fn pull_bundle(
+        &self,
+        image_url: &str,
+        bundle_dir: &Path,
+        auth_info: &Option<&str>,
+        decrypt_config: &Option<&str>,
+        policy: PullPolicy,
+        customization: Option<&LayerMeta>,
+        volume: bool,
+        cancel: &CancellationToken,
+    ) -> Result<(String, LayerPin)> {
+        // Images preloaded inside the guest are named by their docker
+        // reference for signature verification, see [`crate::local`].
+        let local_source = LocalSource::from_url(image_url).await?;
+        let reference = match &local_source {
+            Some(source) => source.reference().clone(),
+            None => Reference::try_from(image_url)?,
+        };
+        self.config.reference_policy.check_reference(&reference)?;
+        #[cfg(feature = "signature")]
+        let verified_reference = match &local_source {
+            Some(_) => reference.whole(),
+            None => image_url.to_string(),
+        };
 
         // Try to get auth using input param.
         let auth = if let Some(auth_info) = auth_info {
@@ -247,6 +686,7 @@ impl ImageClient {
         // If a proper auth is given, use this auth.
         // If no valid auth is given and config.auth is disabled, use Anonymous auth.
         let auth = match (self.config.auth, auth.is_none()) {
+            _ if local_source.is_some() => RegistryAuth::Anonymous,
             (true, true) => {
                 match crate::auth::credential_for_reference(
                     &reference,
@@ -270,54 +710,244 @@ impl ImageClient {
 
         let mut client = PullClient::new(
             reference,
-            &self.config.work_dir.join("layers"),
+            &self.layer_dir()?,
             &auth,
             self.config.max_concurrent_download,
         )?;
-        let (image_manifest, image_digest, image_config) = client.pull_manifest().await?;
+        client.max_layer_retries = self.config.max_layer_retries;
+        client.quarantine_dir = self.config.quarantine_dir();
+        client.max_quarantined_layers = self.config.max_quarantined_layers;
+        client.spill_dir = self
+            .config
+            .layer_storage
+            .get(&self.config.default_snapshot)
+            .map(|storage| storage.spill_dir.clone());
+        client.cancel = cancel.clone();
+        client.local_source = local_source;
+        client.background_priority = self.config.background_priority.clone();
+        client.disk_space = self.config.disk_space.clone();
+        client.disk_reservations = self.disk_reservations.clone();
+        client.blob_cache = self.blob_cache.clone();
+        client.budget = self.pull_budget.clone();
+        client.layer_locks = self.layer_locks.clone();
+        client.image_url = image_url.to_string();
+        client.events = self.events.clone();
+        #[cfg(feature = "fault-injection")]
+        client.faults = self.faults.clone();
+        if let Some(platform) = &self.config.platform {
+            client.set_platform(platform)?;
+        }
+        let registry = client.reference.resolve_registry();
+        if let Some(registry_config) = self.config.registries.get(registry) {
+            client.set_registry_config(registry_config)?;
+        }
+        #[cfg(feature = "vsock-proxy")]
+        self.set_proxy(&mut client)?;
+        #[cfg(feature = "registry-mtls")]
+        self.set_client_cert(&mut client).await?;
+        client.share_client(&self.registry_clients);
+        // Images preloaded inside the guest are read from their source anyway.
+        let credential = credential_key(&auth);
+        let present = match policy {
+            _ if client.local_source.is_some() => None,
+            PullPolicy::Always => None,
+            PullPolicy::IfNotPresent | PullPolicy::Never => {
+                self.present_image(&client.reference).await
+            }
+        };
+        let present = match present {
+            Some((image, pulled_with)) if !may_reuse(&pulled_with, &credential) => {
+                // another caller pulled the image, this one has to be
+                // allowed to pull it as well
+                if policy == PullPolicy::Never {
+                    bail!("image {image_url} was pulled with other credentials, which the pull policy Never cannot check");
+                }
+                client.authenticate().await?;
+                client
+                    .verify_manifest_digest(&image.1)
+                    .await
+                    .with_context(|| {
+                        format!("image {image_url} was pulled with other credentials, and the registry refuses these")
+                    })?;
+                let mut m = self.meta_store.lock().await;
+                if let Some(meta) = m.image_db.get_mut(&image.0.config.digest) {
+                    meta.pulled_with.insert(credential.clone());
+                }
+                Some(image)
+            }
+            present => present.map(|(image, _)| image),
+        };
+        let reused = present.is_some();
+        let (image_manifest, image_digest, image_config) = match (present, &self.manifest_cache) {
+            (Some(present), _) => {
+                info!("image {image_url} is present, not contacting the registry");
+                present
+            }
+            _ if policy == PullPolicy::Never && client.local_source.is_none() => {
+                bail!("image {image_url} is not present, and the pull policy is Never");
+            }
+            (None, Some(cache)) => {
+                let pull = cache.pull_manifest(&mut client, self.config.platform.as_deref());
+                trace::in_span("pull_manifest", pull).await?
+            }
+            (None, None) => trace::in_span("pull_manifest", client.pull_manifest()).await?,
+        };
+        if self
+            .config
+            .reference_policy
+            .needs_resolved_check(&client.reference)
+        {
+            // both digests are computed from the manifests served, the
+            // ones the registry reports are not trusted
+            let manifest_digest = match reused {
+                true => image_digest.clone(),
+                false => {
+                    client.verify_manifest_digest(&image_digest).await?;
+                    client.fetch_manifest_digest().await?
+                }
+            };
+            self.config
+                .reference_policy
+                .check_resolved(&client.reference, &[&manifest_digest, &image_digest])?;
+        }
+        if let Some(freshness) = &self.config.freshness {
+            freshness
+                .check(&[&image_digest, &image_manifest.config.digest], &image_config)
+                .await?;
+        }
 
         let id = image_manifest.config.digest.clone();
 
-        let snapshot = match self.snapshots.get_mut(&self.config.default_snapshot) {
-            Some(s) => s,
-            _ => {
+        // the layers reused or pulled are not evicted by the other pulls
+        // meanwhile
+        let layers = image_manifest.layers.iter();
+        let pin = self
+            .layer_pins
+            .pin(layers.map(|layer| layer.digest.clone()));
+
+        // WASM artifacts have no Linux rootfs, their modules are placed in
+        // the bundle by the wasm snapshot whatever the default snapshot.
+        #[cfg(feature = "snapshot-wasm")]
+        let (snapshot_type, image_config) = match wasm::is_wasm_artifact(&image_manifest) {
+            true => (
+                SnapshotType::Wasm,
+                wasm::image_config(&image_config, &image_manifest)?,
+            ),
+            false => (self.config.default_snapshot, image_config),
+        };
+        #[cfg(not(feature = "snapshot-wasm"))]
+        let snapshot_type = self.config.default_snapshot;
+
+        // image volumes are mounted whole, with the snapshot of the volumes
+        let snapshot_type = match volume {
+            true => self.config.volume_snapshot(),
+            false => snapshot_type,
+        };
+
+        // e.g. model weights mounted with another snapshotter than the code
+        let groups = match volume {
+            true => Vec::new(),
+            false => layer_groups(&image_manifest, snapshot_type)?,
+        };
+        {
+            let snapshots = self.snapshots.lock().await;
+            if let Some(group) = groups
+                .iter()
+                .find(|group| !snapshots.contains_key(&group.snapshot))
+            {
                 bail!(
-                    "default snapshot {} not found",
-                    &self.config.default_snapshot
+                    "snapshot {} of the layers mounted at {} not found",
+                    group.snapshot,
+                    group.mount_dir
                 );
             }
+            if !snapshots.contains_key(&snapshot_type) {
+                bail!("snapshot {} not found", &snapshot_type);
+            }
+        }
+
+        // put in place by the mount of this pull, not to mount with the
+        // keys of another one
+        #[cfg(feature = "snapshot-eccfs")]
+        let eccfs = match snapshot_type == SnapshotType::Eccfs
+            || groups
+                .iter()
+                .any(|group| group.snapshot == SnapshotType::Eccfs)
+        {
+            true => Some(
+                self.load_eccfs_keys(&image_manifest, volume.then_some(bundle_dir))
+                    .await?,
+            ),
+            false => None,
         };
+        #[cfg(not(feature = "snapshot-eccfs"))]
+        let eccfs = None;
 
         #[cfg(feature = "nydus")]
         if utils::is_nydus_image(&image_manifest) {
-            {
-                let m = self.meta_store.lock().await;
-                if let Some(image_data) = &m.image_db.get(&id) {
-                    return service::create_nydus_bundle(image_data, bundle_dir, snapshot);
-                }
+            if client.local_source.is_some() {
+                bail!("nydus images from local sources are not supported");
+            }
+            if volume {
+                bail!("nydus images as image volumes are not supported");
+            }
+            if !groups.is_empty() {
+                bail!("nydus images with layer groups are not supported");
+            }
+            if customization.is_some() {
+                bail!("nydus images with a customization layer are not supported");
+            }
+            if self.config.rootfs_relabel.is_some() {
+                bail!("nydus images with rootfs relabeling are not supported");
             }
 
-            #[cfg(feature = "signature")]
-            if self.config.security_validate {
-                crate::signature::allows_image(
-                    image_url,
-                    &image_digest,
-                    &auth,
-                    &self.config.file_paths,
-                )
-                .await
-                .map_err(|e| anyhow!("Security validate failed: {:?}", e))?;
+            let present = self.meta_store.lock().await.image_db.get(&id).cloned();
+            if let Some(image_data) = present {
+                let mut snapshots = self.snapshots.lock().await;
+                let snapshot = snapshots
+                    .get_mut(&snapshot_type)
+                    .ok_or_else(|| anyhow!("snapshot {} not found", &snapshot_type))?;
+                let image_id = service::create_nydus_bundle(&image_data, bundle_dir, snapshot)?;
+                drop(snapshots);
+                self.emit_mounted(image_url, &image_id, bundle_dir, snapshot_type, &groups);
+                return Ok((image_id, pin));
             }
 
+            #[cfg(feature = "signature")]
+            let verification = match self.config.security_validate {
+                true => Some(
+                    trace::in_span(
+                        "verify_signature",
+                        crate::signature::allows_image(
+                            &verified_reference,
+                            &image_digest,
+                            &auth,
+                            &self.config.file_paths,
+                            client.registry_access(),
+                        ),
+                    )
+                    .await
+                    .map_err(|e| anyhow!(SignatureRejected(format!("{e:?}"))))?,
+                ),
+                false => None,
+            };
+            #[cfg(not(feature = "signature"))]
+            let verification = None;
+            #[cfg(feature = "signature")]
+            self.emit_signature_checked(image_url, &image_digest, verification.as_ref());
+
             let (mut image_data, _, _) = create_image_meta(
                 &id,
                 image_url,
                 &image_manifest,
                 &image_digest,
                 &image_config,
+                verification,
             )?;
+            image_data.pulled_with.insert(credential);
 
-            return self
+            client.authenticate().await?;
+            let image_id = self
                 .do_pull_image_with_nydus(
                     &mut client,
                     &mut image_data,
@@ -325,28 +955,68 @@ impl ImageClient {
                     decrypt_config,
                     bundle_dir,
                 )
-                .await;
+                .await?;
+            self.emit_mounted(image_url, &image_id, bundle_dir, snapshot_type, &groups);
+            return Ok((image_id, pin));
         }
 
         // If image has already been populated, just create the bundle.
-        {
-            let m = self.meta_store.lock().await;
-            if let Some(image_data) = &m.image_db.get(&id) {
-                return create_bundle(image_data, bundle_dir, snapshot);
-            }
+        let present = self.meta_store.lock().await.image_db.get(&id).cloned();
+        if let Some(image_data) = present {
+            let image_id = match volume {
+                true => {
+                    let mount = self.mount_volume(
+                        image_url,
+                        &image_data,
+                        bundle_dir,
+                        snapshot_type,
+                        eccfs,
+                        cancel,
+                    );
+                    trace::in_span("mount_volume", mount).await?
+                }
+                false => {
+                    trace::in_span(
+                        "mount_bundle",
+                        self.mount_bundle(
+                            image_url,
+                            &image_data,
+                            bundle_dir,
+                            snapshot_type,
+                            &groups,
+                            customization,
+                            eccfs,
+                            cancel,
+                        ),
+                    )
+                    .await?
+                }
+            };
+            return Ok((image_id, pin));
         }
 
         #[cfg(feature = "signature")]
-        if self.config.security_validate {
-            crate::signature::allows_image(
-                image_url,
-                &image_digest,
-                &auth,
-                &self.config.file_paths,
-            )
-            .await
-            .map_err(|e| anyhow!("Security validate failed: {:?}", e))?;
-        }
+        let verification = match self.config.security_validate {
+            true => Some(
+                trace::in_span(
+                    "verify_signature",
+                    crate::signature::allows_image(
+                        &verified_reference,
+                        &image_digest,
+                        &auth,
+                        &self.config.file_paths,
+                        client.registry_access(),
+                    ),
+                )
+                .await
+                .map_err(|e| anyhow!(SignatureRejected(format!("{e:?}"))))?,
+            ),
+            false => None,
+        };
+        #[cfg(not(feature = "signature"))]
+        let verification = None;
+        #[cfg(feature = "signature")]
+        self.emit_signature_checked(image_url, &image_digest, verification.as_ref());
 
         let (mut image_data, unique_layers, unique_diff_ids) = create_image_meta(
             &id,
@@ -354,17 +1024,40 @@ impl ImageClient {
             &image_manifest,
             &image_digest,
             &image_config,
+            verification,
         )?;
+        image_data.pulled_with.insert(credential);
+
+        // Fail before downloading anything if the image does not fit, and
+        // keep the space it needs from the other pulls until it is mounted.
+        if let Some(disk_space) = &self.config.disk_space {
+            let reservation = crate::disk_space::preflight(
+                &mut *self.meta_store.lock().await,
+                &self.disk_reservations,
+                &client.data_dir,
+                &self.config.work_dir.join(snapshot_type.to_string()),
+                &unique_layers,
+                snapshot_type,
+                &self.layer_pins.pinned(),
+                disk_space,
+            )?;
+            client.disk_reservation = Some(Arc::new(reservation));
+        }
 
+        // The manifest may have been taken from the cache without talking
+        // to the registry.
+        client.authenticate().await?;
         let unique_layers_len = unique_layers.len();
-        let layer_metas = client
-            .async_pull_layers(
+        let layer_metas = trace::in_span(
+            "pull_layers",
+            client.async_pull_layers(
                 unique_layers,
                 &unique_diff_ids,
                 decrypt_config,
                 self.meta_store.clone(),
-            )
-            .await?;
+            ),
+        )
+        .await?;
 
         image_data.layer_metas = layer_metas;
         let layer_db: HashMap<String, LayerMeta> = image_data
@@ -381,7 +1074,35 @@ impl ImageClient {
             );
         }
 
-        let image_id = create_bundle(&image_data, bundle_dir, snapshot)?;
+        let image_id = match volume {
+            true => {
+                let mount = self.mount_volume(
+                    image_url,
+                    &image_data,
+                    bundle_dir,
+                    snapshot_type,
+                    eccfs,
+                    cancel,
+                );
+                trace::in_span("mount_volume", mount).await?
+            }
+            false => {
+                trace::in_span(
+                    "mount_bundle",
+                    self.mount_bundle(
+                        image_url,
+                        &image_data,
+                        bundle_dir,
+                        snapshot_type,
+                        &groups,
+                        customization,
+                        eccfs,
+                        cancel,
+                    ),
+                )
+                .await?
+            }
+        };
 
         self.meta_store
             .lock()
@@ -389,55 +1110,960 @@ impl ImageClient {
             .image_db
             .insert(image_data.id.clone(), image_data.clone());
 
-        Ok(image_id)
+        Ok((image_id, pin))
     }
 
-    #[cfg(feature = "nydus")]
-    async fn do_pull_image_with_nydus<'a>(
-        &mut self,
-        client: &mut PullClient<'_>,
-        image_data: &mut ImageMeta,
-        image_manifest: &OciImageManifest,
-        decrypt_config: &Option<&str>,
+    /// Mount the rootfs of the image with the `customization` layer on top,
+    /// and the layer groups, into `bundle_dir`, relabel them as configured,
+    /// measure them, and record the mounts of the groups. The snapshots are
+    /// locked for the whole mount, so that the measured layer digests are
+    /// the ones of this mount. `eccfs`, if given, replaces the eccfs
+    /// snapshotter first. If any step fails, what was mounted is unmounted
+    /// again.
+    #[allow(clippy::too_many_arguments)]
+    async fn mount_bundle(
+        &self,
+        image_url: &str,
+        image_data: &ImageMeta,
         bundle_dir: &Path,
+        snapshot_type: SnapshotType,
+        groups: &[LayerGroup],
+        customization: Option<&LayerMeta>,
+        eccfs: Option<Box<dyn Snapshotter>>,
+        cancel: &CancellationToken,
     ) -> Result<String> {
-        let diff_ids = image_data.image_config.rootfs().diff_ids();
-        let bootstrap_id = if !diff_ids.is_empty() {
-            diff_ids[diff_ids.len() - 1].to_string()
-        } else {
-            bail!("Failed to get bootstrap id, diff_ids is empty");
-        };
-
-        let bootstrap = utils::get_nydus_bootstrap_desc(image_manifest)
-            .ok_or_else(|| anyhow!("Faild to get bootstrap oci descriptor"))?;
-        let layer_metas = client
-            .pull_bootstrap(
-                bootstrap,
-                bootstrap_id.to_string(),
-                decrypt_config,
-                self.meta_store.clone(),
+        let mut mounts = Vec::new();
+        let res = self
+            .do_mount_bundle(
+                image_url,
+                image_data,
+                bundle_dir,
+                snapshot_type,
+                groups,
+                customization,
+                eccfs,
+                cancel,
+                &mut mounts,
             )
-            .await?;
-        image_data.layer_metas = vec![layer_metas];
-        let layer_db: HashMap<String, LayerMeta> = image_data
-            .layer_metas
+            .await;
+        if res.is_err() {
+            self.unmount_all(&mounts).await;
+            return res;
+        }
+
+        // the rootfs is the first mount, the groups follow in order
+        let group_mounts: Vec<GroupMount> = groups
             .iter()
-            .map(|layer| (layer.compressed_digest.clone(), layer.clone()))
+            .zip(mounts.into_iter().skip(1))
+            .map(|(group, (_, mount_point))| GroupMount {
+                snapshot: group.snapshot,
+                mount_dir: group.mount_dir.clone(),
+                mount_point,
+            })
             .collect();
+        if !group_mounts.is_empty() {
+            self.meta_store
+                .lock()
+                .await
+                .group_db
+                .insert(bundle_dir.display().to_string(), group_mounts);
+        }
+        res
+    }
 
-        self.meta_store.lock().await.layer_db.extend(layer_db);
+    #[allow(clippy::too_many_arguments)]
+    async fn do_mount_bundle(
+        &self,
+        image_url: &str,
+        image_data: &ImageMeta,
+        bundle_dir: &Path,
+        snapshot_type: SnapshotType,
+        groups: &[LayerGroup],
+        customization: Option<&LayerMeta>,
+        eccfs: Option<Box<dyn Snapshotter>>,
+        cancel: &CancellationToken,
+        mounts: &mut Vec<(SnapshotType, MountPoint)>,
+    ) -> Result<String> {
+        let mut snapshots = self.snapshots.lock().await;
+        if let Some(eccfs) = eccfs {
+            snapshots.insert(SnapshotType::Eccfs, eccfs);
+        }
 
-        if image_data.layer_metas.is_empty() {
-            bail!("Failed to pull the bootstrap");
+        let snapshot = snapshots
+            .get_mut(&snapshot_type)
+            .ok_or_else(|| anyhow!("snapshot {} not found", &snapshot_type))?;
+        let rootfs = mount_rootfs(
+            image_data,
+            bundle_dir,
+            snapshot,
+            groups,
+            customization,
+            cancel,
+            self.config.background_priority.as_ref(),
+        )?;
+        mounts.push((snapshot_type, rootfs));
+        let image_id = create_bundle(image_data, bundle_dir)?;
+        mount_layer_groups(
+            image_data,
+            bundle_dir,
+            groups,
+            &mut snapshots,
+            cancel,
+            self.config.background_priority.as_ref(),
+            mounts,
+        )?;
+        let layers = snapshots
+            .get(&self.config.default_snapshot)
+            .and_then(|s| s.layer_digests());
+        // every group has a snapshotter of its own, whose last mount it is
+        let group_layers: Vec<_> = groups
+            .iter()
+            .map(|group| {
+                snapshots
+                    .get(&group.snapshot)
+                    .and_then(|s| s.layer_digests())
+            })
+            .collect();
+        drop(snapshots);
+
+        if let Some(relabel) = &self.config.rootfs_relabel {
+            let mount_paths = std::iter::once(bundle_dir.join(BUNDLE_ROOTFS))
+                .chain(groups.iter().map(|group| group.mount_path(bundle_dir)));
+            // what was mounted is unmounted by `mount_bundle` if it fails
+            for mount_path in mount_paths {
+                #[cfg(feature = "fault-injection")]
+                self.faults.relabel(&mount_path)?;
+                crate::priority::run_with(self.config.background_priority.as_ref(), || {
+                    crate::relabel::relabel(&mount_path, relabel)
+                })
+                .with_context(|| format!("failed to relabel {}", mount_path.display()))?;
+            }
         }
 
-        let reference = Reference::try_from(image_data.reference.clone())?;
-        let nydus_config = self
+        self.emit_mounted(image_url, &image_id, bundle_dir, snapshot_type, groups);
+        self.measure_rootfs(&image_id, bundle_dir, layers, customization)
+            .await?;
+        for (group, layers) in groups.iter().zip(group_layers) {
+            self.measure_layer_group(&image_id, bundle_dir, group, layers)
+                .await?;
+        }
+        Ok(image_id)
+    }
+
+    // unmount the mounts of a bundle failing to mount, the last one first
+    async fn unmount_all(&self, mounts: &[(SnapshotType, MountPoint)]) {
+        let snapshots = self.snapshots.lock().await;
+        for (snapshot_type, mount_point) in mounts.iter().rev() {
+            let Some(snapshot) = snapshots.get(snapshot_type) else {
+                continue;
+            };
+            if let Err(e) = snapshot.unmount(mount_point) {
+                warn!(
+                    "failed to unmount {}: {e:?}",
+                    mount_point.mount_path.display()
+                );
+            }
+        }
+    }
+
+    /// Mount all the layers of the image read-only at `target` as an image
+    /// volume, and record it. `eccfs`, if given, is the snapshotter mounting
+    /// it, built for this volume.
+    async fn mount_volume(
+        &self,
+        image_url: &str,
+        image_data: &ImageMeta,
+        target: &Path,
+        snapshot_type: SnapshotType,
+        mut eccfs: Option<Box<dyn Snapshotter>>,
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        if cancel.is_cancelled() {
+            bail!(ERR_PULL_CANCELLED);
+        }
+
+        // the volumes are mounted with the snapshots locked, so no other one
+        // gets mounted at `target` meanwhile
+        let key = target.display().to_string();
+        let mut snapshots = self.snapshots.lock().await;
+        if self.meta_store.lock().await.volume_db.contains_key(&key) {
+            bail!("an image volume is mounted at {} already", target.display());
+        }
+
+        // eccfs builds the roimages of the volume without a rw image
+        let read_only = eccfs.is_some();
+        let snapshot = match eccfs.as_mut() {
+            Some(eccfs) => eccfs,
+            None => snapshots
+                .get_mut(&snapshot_type)
+                .ok_or_else(|| anyhow!("snapshot {} not found", &snapshot_type))?,
+        };
+        let layer_path = image_data
+            .layer_metas
+            .iter()
+            .rev()
+            .map(|l| l.store_path.as_str())
+            .collect::<Vec<&str>>();
+        let priority = self.config.background_priority.as_ref();
+        let mount_point = crate::priority::run_with(priority, || {
+            snapshot.mount_with_cancellation(&layer_path, target, cancel)
+        })?;
+        let remounted = match read_only {
+            true => Ok(()),
+            false => volume::remount_read_only(target),
+        };
+        let mut m = self.meta_store.lock().await;
+        let entry = match (remounted, m.volume_db.entry(key)) {
+            (Ok(()), Entry::Vacant(entry)) => entry,
+            (remounted, _) => {
+                if let Err(ue) = snapshot.unmount(&mount_point) {
+                    warn!("failed to unmount {}: {ue:?}", target.display());
+                }
+                remounted?;
+                bail!("an image volume is mounted at {} already", target.display());
+            }
+        };
+        entry.insert(ImageVolume {
+            image_url: image_url.to_string(),
+            image_id: image_data.id.clone(),
+            snapshot: snapshot_type,
+            mount_point,
+        });
+        drop(m);
+        drop(snapshots);
+        self.events.emit(PullEvent::SnapshotMounted {
+            image_url: image_url.to_string(),
+            image_id: image_data.id.clone(),
+            snapshot: snapshot_type.to_string(),
+            mount_path: target.to_path_buf(),
+        });
+        Ok(image_data.id.clone())
+    }
+
+    // the rootfs and the layer groups of the bundle are mounted
+    fn emit_mounted(
+        &self,
+        image_url: &str,
+        image_id: &str,
+        bundle_dir: &Path,
+        snapshot_type: SnapshotType,
+        groups: &[LayerGroup],
+    ) {
+        let rootfs = (snapshot_type, bundle_dir.join(BUNDLE_ROOTFS));
+        let groups = groups
+            .iter()
+            .map(|group| (group.snapshot, group.mount_path(bundle_dir)));
+        for (snapshot, mount_path) in std::iter::once(rootfs).chain(groups) {
+            self.events.emit(PullEvent::SnapshotMounted {
+                image_url: image_url.to_string(),
+                image_id: image_id.to_string(),
+                snapshot: snapshot.to_string(),
+                mount_path,
+            });
+        }
+    }
+
+    // the image was allowed by the signature policy
+    #[cfg(feature = "signature")]
+    fn emit_signature_checked(
+        &self,
+        image_url: &str,
+        digest: &str,
+        verification: Option<&VerificationReport>,
+    ) {
+        if let Some(report) = verification {
+            self.events.emit(PullEvent::SignatureChecked {
+                image_url: image_url.to_string(),
+                digest: digest.to_string(),
+                signed: report.is_signed(),
+            });
+        }
+    }
+
+    /// present_image returns the manifest, the digest and the config of the
+    /// image pulled before with `reference`, if any, with the credentials it
+    /// was pulled with. A tag pulled again after it moved matches several
+    /// images, and none of them is taken. With `security_validate`, only the
+    /// images verified when pulled are.
+    #[allow(clippy::type_complexity)]
+    async fn present_image(
+        &self,
+        reference: &Reference,
+    ) -> Option<((OciImageManifest, String, String), BTreeSet<String>)> {
+        let m = self.meta_store.lock().await;
+        let mut present = m
+            .image_db
+            .values()
+            .filter(|meta| meta.verification.is_some() || !self.config.security_validate)
+            .filter(|meta| is_pulled_as(meta, reference))
+            .filter_map(|meta| {
+                let config = serde_json::to_string(&meta.image_config).ok()?;
+                let image = (meta.manifest.clone()?, meta.digest.clone(), config);
+                Some((image, meta.pulled_with.clone()))
+            });
+        let image = present.next()?;
+        if present.next().is_some() {
+            warn!("several images were pulled as {}", reference.whole());
+            return None;
+        }
+
+        Some(image)
+    }
+
+    /// verification_report returns why a pulled image passed the signature
+    /// verification, or None if it was pulled without `security_validate`.
+    /// `image_ref` is the reference the image was pulled with, or the image
+    /// ID.
+    pub async fn verification_report(&self, image_ref: &str) -> Result<Option<VerificationReport>> {
+        let m = self.meta_store.lock().await;
+        m.image_db
+            .values()
+            .find(|image| image.reference == image_ref || image.id == image_ref)
+            .map(|image| image.verification.clone())
+            .ok_or_else(|| anyhow!("image {} has not been pulled", image_ref))
+    }
+
+    /// pin_image keeps the layers of the image pulled with `image_ref`, or
+    /// of the image ID, from being evicted to keep the budget of the layer
+    /// store, see [`crate::layer_cache`]. Images can be pinned before they
+    /// are pulled.
+    pub async fn pin_image(&self, image_ref: &str) -> Result<()> {
+        if image_ref.is_empty() {
+            bail!("no image to pin");
+        }
+
+        self.meta_store
+            .lock()
+            .await
+            .pinned_images
+            .insert(image_ref.to_string());
+        Ok(())
+    }
+
+    /// unpin_image undoes [`ImageClient::pin_image`], evicting the layers of
+    /// the image if the layer store is over its budget. Returns whether the
+    /// image was pinned.
+    pub async fn unpin_image(&self, image_ref: &str) -> Result<bool> {
+        let mut m = self.meta_store.lock().await;
+        if !m.pinned_images.remove(image_ref) {
+            return Ok(false);
+        }
+
+        if let Some(budget) = &self.config.layer_cache {
+            let freed = self.enforce_layer_budget(&mut m, budget, &BTreeSet::new())?;
+            if freed > 0 {
+                info!("{} bytes freed by evicting layers", freed);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// pinned_images returns the images pinned with
+    /// [`ImageClient::pin_image`].
+    pub async fn pinned_images(&self) -> Vec<String> {
+        let m = self.meta_store.lock().await;
+        m.pinned_images.iter().cloned().collect()
+    }
+
+    /// mount_image_volume mounts the image `image_ref` read-only at `target`
+    /// as an image volume of a container, see [`crate::volume`]. The image
+    /// is pulled like [`ImageClient::pull_image`] does, with the same
+    /// `auth_info` and `decrypt_config`, the signature verification and the
+    /// decryption of its layers, but all its layers are mounted at `target`
+    /// with the snapshot of [`ImageConfig::volume_snapshot`], without a
+    /// bundle around them.
+    pub async fn mount_image_volume(
+        &self,
+        image_ref: &str,
+        target: &Path,
+        auth_info: &Option<&str>,
+        decrypt_config: &Option<&str>,
+    ) -> Result<ImageVolume> {
+        let key = target.display().to_string();
+        self.events.emit(PullEvent::PullStarted {
+            image_url: image_ref.to_string(),
+            bundle_dir: target.to_path_buf(),
+        });
+        let cancel = CancellationToken::new();
+        let res: Result<ImageVolume> = async {
+            let (_, _pin) = trace::in_span_with(
+                "mount_image_volume",
+                [("image.url", image_ref.to_string())],
+                self.pull_bundle(
+                    image_ref,
+                    target,
+                    auth_info,
+                    decrypt_config,
+                    self.config.pull_policy,
+                    None,
+                    true,
+                    &cancel,
+                ),
+            )
+            .await?;
+
+            let mut m = self.meta_store.lock().await;
+            let volume = m
+                .volume_db
+                .get(&key)
+                .cloned()
+                .ok_or_else(|| anyhow!("image volume at {key} mounted without record"))?;
+            let layers = m
+                .image_db
+                .get(&volume.image_id)
+                .map(|image| image.layer_metas.clone())
+                .unwrap_or_default();
+            self.keep_layer_budget(&mut m, &layers);
+            Ok(volume)
+        }
+        .await;
+        if let Err(e) = &res {
+            self.events.emit(PullEvent::PullFailed {
+                image_url: image_ref.to_string(),
+                code: PullErrorCode::of(e, cancel.is_cancelled()),
+                message: format!("{e:#}"),
+            });
+        }
+        res
+    }
+
+    /// unmount_image_volume unmounts the image volume mounted at `target` by
+    /// [`ImageClient::mount_image_volume`], and forgets it, so that the
+    /// layers of its image can be evicted. Returns whether a volume was
+    /// mounted there.
+    pub async fn unmount_image_volume(&self, target: &Path) -> Result<bool> {
+        let key = target.display().to_string();
+        let Some(volume) = self.meta_store.lock().await.volume_db.get(&key).cloned() else {
+            return Ok(false);
+        };
+
+        {
+            let snapshots = self.snapshots.lock().await;
+            let snapshot = snapshots
+                .get(&volume.snapshot)
+                .ok_or_else(|| anyhow!("snapshot {} not found", volume.snapshot))?;
+            snapshot
+                .unmount(&volume.mount_point)
+                .with_context(|| format!("failed to unmount image volume {key}"))?;
+        }
+
+        let mut m = self.meta_store.lock().await;
+        m.volume_db.remove(&key);
+        if let Some(budget) = &self.config.layer_cache {
+            let freed = self.enforce_layer_budget(&mut m, budget, &BTreeSet::new())?;
+            if freed > 0 {
+                info!("{} bytes freed by evicting layers", freed);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// unmount_layer_groups unmounts the layer groups mounted into the
+    /// bundle at `bundle_dir` apart from its rootfs, the last one first, and
+    /// forgets them, see [`crate::layer_groups`]. The rootfs is left to the
+    /// runtime. Returns whether groups were mounted there.
+    pub async fn unmount_layer_groups(&self, bundle_dir: &Path) -> Result<bool> {
+        let key = bundle_dir.display().to_string();
+        let Some(mut group_mounts) = self.meta_store.lock().await.group_db.get(&key).cloned()
+        else {
+            return Ok(false);
+        };
+
+        // the groups unmounted are forgotten even if a later one fails
+        let mut res = Ok(true);
+        {
+            let snapshots = self.snapshots.lock().await;
+            while let Some(group) = group_mounts.last() {
+                res = snapshots
+                    .get(&group.snapshot)
+                    .ok_or_else(|| anyhow!("snapshot {} not found", group.snapshot))
+                    .and_then(|snapshot| snapshot.unmount(&group.mount_point))
+                    .map(|()| true)
+                    .with_context(|| {
+                        format!("failed to unmount the layers at {}", group.mount_dir)
+                    });
+                if res.is_err() {
+                    break;
+                }
+                group_mounts.pop();
+            }
+        }
+
+        let mut m = self.meta_store.lock().await;
+        if group_mounts.is_empty() {
+            m.group_db.remove(&key);
+        } else {
+            m.group_db.insert(key, group_mounts);
+        }
+        res
+    }
+
+    /// image_volumes returns the image volumes mounted by
+    /// [`ImageClient::mount_image_volume`].
+    pub async fn image_volumes(&self) -> Vec<ImageVolume> {
+        let m = self.meta_store.lock().await;
+        m.volume_db.values().cloned().collect()
+    }
+
+    // record that the pull used `layers`, and evict layers until the layer
+    // store fits into its budget if any; the pull succeeded even if the
+    // budget cannot be kept
+    fn keep_layer_budget(&self, m: &mut MetaStore, layers: &[LayerMeta]) {
+        let Some(budget) = &self.config.layer_cache else {
+            return;
+        };
+
+        layer_cache::record_use(m, layers, layer_cache::now());
+        let keep = layers
+            .iter()
+            .map(|layer| layer.compressed_digest.as_str())
+            .collect();
+        match self.enforce_layer_budget(m, budget, &keep) {
+            Ok(0) => {}
+            Ok(freed) => info!("{} bytes freed by evicting layers", freed),
+            Err(e) => warn!("failed to evict layers: {e:?}"),
+        }
+    }
+
+    // evict layers until the layer store and the blob cache fit into
+    // `budget`, but the ones in `keep` and the ones pinned by the pulls in
+    // progress
+    fn enforce_layer_budget(
+        &self,
+        m: &mut MetaStore,
+        budget: &LayerCacheConfig,
+        keep: &BTreeSet<&str>,
+    ) -> Result<u64> {
+        let pinned = self.layer_pins.pinned();
+        let keep = keep
+            .iter()
+            .copied()
+            .chain(pinned.iter().map(String::as_str))
+            .collect();
+        let cached = match &self.blob_cache {
+            Some(cache) => cache.size()?,
+            None => 0,
+        };
+        layer_cache::enforce(m, budget, &keep, cached)
+    }
+
+    /// export writes a pulled image as an OCI image layout under `path`, so
+    /// that exactly what the guest pulled can be audited or transferred to
+    /// air-gapped nodes. `image_ref` is the reference the image was pulled
+    /// with, or the image ID.
+    ///
+    /// The blobs are fetched again from the source of the image, and checked
+    /// against the manifest digest recorded at pull time. Encrypted layers are
+    /// written as they were pulled, unless `decrypt_config` is given, in which
+    /// case they are decrypted. Returns the digest of the exported manifest.
+    pub async fn export(
+        &self,
+        image_ref: &str,
+        path: &Path,
+        decrypt_config: &Option<&str>,
+    ) -> Result<String> {
+        let image_data = {
+            let m = self.meta_store.lock().await;
+            m.image_db
+                .values()
+                .find(|image| image.reference == image_ref || image.id == image_ref)
+                .cloned()
+                .ok_or_else(|| anyhow!("image {} has not been pulled", image_ref))?
+        };
+
+        let local_source = LocalSource::from_url(&image_data.reference).await?;
+        let (reference, auth) = match &local_source {
+            Some(source) => (source.reference().clone(), RegistryAuth::Anonymous),
+            None => {
+                let reference = Reference::try_from(image_data.reference.as_str())?;
+                let auth = self.registry_auth(&reference, &None).await?;
+                (reference, auth)
+            }
+        };
+
+        let mut client = PullClient::new(
+            reference.clone(),
+            &self.config.work_dir.join("layers"),
+            &auth,
+            self.config.max_concurrent_download,
+        )?;
+        client.local_source = local_source;
+        client.blob_cache = self.blob_cache.clone();
+        if let Some(platform) = &self.config.platform {
+            client.set_platform(platform)?;
+        }
+        let registry = client.reference.resolve_registry();
+        if let Some(registry_config) = self.config.registries.get(registry) {
+            client.set_registry_config(registry_config)?;
+        }
+        #[cfg(feature = "vsock-proxy")]
+        self.set_proxy(&mut client)?;
+        #[cfg(feature = "registry-mtls")]
+        self.set_client_cert(&mut client).await?;
+
+        crate::export::export_image(
+            &mut client,
+            &reference.whole(),
+            &image_data.digest,
+            path,
+            decrypt_config,
+        )
+        .await
+    }
+
+    /// extract_files reads the `paths` of the rootfs of an image, e.g.
+    /// `/etc/passwd`, without pulling it, see [`crate::extract`]. Only the
+    /// layers needed to resolve the paths are fetched, and none is unpacked.
+    ///
+    /// `image_url`, `auth_info` and `decrypt_config` are as for
+    /// [`ImageClient::pull_image`]. With `security_validate`, the manifest
+    /// is checked against the signature policy before any layer is read, as
+    /// for a pull.
+    pub async fn extract_files(
+        &self,
+        image_url: &str,
+        paths: &[&str],
+        auth_info: &Option<&str>,
+        decrypt_config: &Option<&str>,
+    ) -> Result<ExtractedFiles> {
+        let local_source = LocalSource::from_url(image_url).await?;
+        let (reference, auth) = match &local_source {
+            Some(source) => (source.reference().clone(), RegistryAuth::Anonymous),
+            None => {
+                let reference = Reference::try_from(image_url)?;
+                let auth = self.registry_auth(&reference, auth_info).await?;
+                (reference, auth)
+            }
+        };
+        self.config.reference_policy.check_reference(&reference)?;
+
+        let mut client = PullClient::new(
+            reference,
+            &self.config.work_dir.join("layers"),
+            &auth,
+            self.config.max_concurrent_download,
+        )?;
+        client.local_source = local_source;
+        client.blob_cache = self.blob_cache.clone();
+        if let Some(platform) = &self.config.platform {
+            client.set_platform(platform)?;
+        }
+        let registry = client.reference.resolve_registry();
+        if let Some(registry_config) = self.config.registries.get(registry) {
+            client.set_registry_config(registry_config)?;
+        }
+        #[cfg(feature = "vsock-proxy")]
+        self.set_proxy(&mut client)?;
+        #[cfg(feature = "registry-mtls")]
+        self.set_client_cert(&mut client).await?;
+
+        let manifest = client.pull_manifest().await?;
+        #[cfg(feature = "signature")]
+        if self.config.security_validate {
+            let verified_reference = match &client.local_source {
+                Some(source) => source.reference().whole(),
+                None => image_url.to_string(),
+            };
+            crate::signature::allows_image(
+                &verified_reference,
+                &manifest.1,
+                &auth,
+                &self.config.file_paths,
+                client.registry_access(),
+            )
+            .await
+            .map_err(|e| anyhow!(SignatureRejected(format!("{e:?}"))))?;
+        }
+
+        crate::extract::extract_files(&mut client, manifest, paths, decrypt_config).await
+    }
+
+    /// The credential to access the registry of `reference` with: the one
+    /// in `auth_info`, or the one of the auth file if `auth` is enabled.
+    async fn registry_auth(
+        &self,
+        reference: &Reference,
+        auth_info: &Option<&str>,
+    ) -> Result<RegistryAuth> {
+        if let Some(auth_info) = auth_info {
+            let Some((username, password)) = auth_info.split_once(':') else {
+                bail!("Invalid authentication info ({:?})", auth_info);
+            };
+            return Ok(RegistryAuth::Basic(
+                username.to_string(),
+                password.to_string(),
+            ));
+        }
+
+        if !self.config.auth {
+            return Ok(RegistryAuth::Anonymous);
+        }
+
+        let auth =
+            crate::auth::credential_for_reference(reference, &self.config.file_paths.auth_file)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("get credential failed, use Anonymous auth instead: {}", e);
+                    RegistryAuth::Anonymous
+                });
+        Ok(auth)
+    }
+
+    /// Make `client` present the client certificate configured for its
+    /// registry, if any, see [`crate::mtls`].
+    #[cfg(feature = "registry-mtls")]
+    async fn set_client_cert(&self, client: &mut PullClient<'_>) -> Result<()> {
+        let Some(certs_file) = &self.config.file_paths.client_certs_file else {
+            return Ok(());
+        };
+        if !self.config.auth || client.local_source.is_some() {
+            return Ok(());
+        }
+
+        if let Some(cert) =
+            crate::mtls::client_cert_for_reference(&client.reference, certs_file).await?
+        {
+            client.set_client_cert(&cert)?;
+        }
+        Ok(())
+    }
+
+    /// Make `client` reach its registry through the proxy configured, if
+    /// any, see [`crate::proxy`].
+    #[cfg(feature = "vsock-proxy")]
+    fn set_proxy(&self, client: &mut PullClient<'_>) -> Result<()> {
+        let Some(proxy) = &self.config.proxy else {
+            return Ok(());
+        };
+        if client.local_source.is_some() {
+            return Ok(());
+        }
+
+        let proxy = crate::proxy::setup(proxy).context("set up registry proxy")?;
+        client.set_proxy(&proxy)
+    }
+
+    /// Register a hook to be called with the measurement of every rootfs
+    /// mounted by this client, e.g. to extend it into a runtime measurement
+    /// register through the attestation-agent. Nydus rootfs are fetched
+    /// lazily and are not measured.
+    pub fn set_measurement_hook(&mut self, hook: Arc<dyn MeasurementHook>) {
+        self.measurement_hook = Some(hook);
+    }
+
+    /// Get the events of the pulls of this client from now on, see
+    /// [`crate::events`].
+    pub fn subscribe(&self) -> UnboundedReceiver<PullEvent> {
+        self.events.subscribe()
+    }
+
+    async fn measure_rootfs(
+        &self,
+        image_id: &str,
+        bundle_dir: &Path,
+        layers: Option<Vec<String>>,
+        customization: Option<&LayerMeta>,
+    ) -> Result<()> {
+        let Some(hook) = &self.measurement_hook else {
+            return Ok(());
+        };
+
+        let measurement = RootfsMeasurement::new(
+            image_id,
+            &self.config.default_snapshot.to_string(),
+            &bundle_dir.join(BUNDLE_ROOTFS),
+            layers,
+        )
+        .await?;
+        let measurement = match customization {
+            Some(layer) => measurement.with_customization(&layer.compressed_digest),
+            None => measurement,
+        };
+        hook.rootfs_mounted(&measurement)
+            .await
+            .map_err(|e| anyhow!("rootfs measurement hook failed: {:?}", e))
+    }
+
+    async fn measure_layer_group(
+        &self,
+        image_id: &str,
+        bundle_dir: &Path,
+        group: &LayerGroup,
+        layers: Option<Vec<String>>,
+    ) -> Result<()> {
+        let Some(hook) = &self.measurement_hook else {
+            return Ok(());
+        };
+
+        let measurement = RootfsMeasurement::new(
+            image_id,
+            &group.snapshot.to_string(),
+            &group.mount_path(bundle_dir),
+            layers,
+        )
+        .await?
+        .with_layer_group(&group.mount_dir);
+        hook.rootfs_mounted(&measurement).await.map_err(|e| {
+            anyhow!(
+                "measurement hook of the layers at {} failed: {:?}",
+                group.mount_dir,
+                e
+            )
+        })
+    }
+
+    /// Check that the eccfs snapshotter of this client can mount, see
+    /// [`EccOvlFs::preflight`], e.g. to fail the startup of the agent with
+    /// the missing pieces instead of the first pull.
+    #[cfg(feature = "snapshot-eccfs")]
+    pub fn eccfs_preflight(&self) -> PreflightReport {
+        let eccfs_dir = self.config.work_dir.join(SnapshotType::Eccfs.to_string());
+        let mut eccfs = EccOvlFs::new(eccfs_dir);
+        if let Some(eccfs_config) = &self.config.eccfs_config {
+            eccfs.configure(eccfs_config);
+        }
+        eccfs.preflight()
+    }
+
+    /// Build an eccfs snapshotter having the keys to build the images of
+    /// `manifest` with, to replace the one of the client. If deterministic
+    /// builds are configured, the build key is fetched from the KBS. If
+    /// supplied keys are configured, or given by the [`ANNOTATION_ECCFS_KEYS`]
+    /// annotation of the manifest naming one of the allowed uris, see
+    /// [`crate::config::EccfsConfig::keys_uri_for`], they are fetched as well. The keys are
+    /// fetched through the secure channel, so `auth` or `security_validate`
+    /// needs to be enabled unless `file://` uris are used.
+    ///
+    /// `volume` is the target of the image volume the snapshotter is built
+    /// for, whose roimages are mounted read-only, and named after it. If
+    /// they are only integrity protected, see [`volume::integrity_only`], no
+    /// keys are fetched.
+    #[cfg(feature = "snapshot-eccfs")]
+    async fn load_eccfs_keys(
+        &self,
+        manifest: &OciImageManifest,
+        volume: Option<&Path>,
+    ) -> Result<Box<dyn Snapshotter>> {
+        let eccfs_dir = self.config.work_dir.join(SnapshotType::Eccfs.to_string());
+        let eccfs_config = self.config.eccfs_config.as_ref();
+        let integrity_only =
+            volume.is_some() && volume::integrity_only(self.config.image_volume.as_ref(), manifest);
+
+        // integrity-only roimages are built without keys, none are fetched
+        let mut eccfs = match eccfs_config.filter(|c| c.deterministic && !integrity_only) {
+            Some(eccfs_config) => {
+                let key = crate::resource::get_resource(eccfs_config.key_uri())
+                    .await
+                    .map_err(|e| anyhow!("failed to get eccfs build key: {:?}", e))?;
+                let key: [u8; 16] = key
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| anyhow!("eccfs build key must be 16 bytes, got {}", key.len()))?;
+                EccOvlFs::new_deterministic(eccfs_dir, key, eccfs_config.source_date_epoch)
+            }
+            None => EccOvlFs::new(eccfs_dir),
+        };
+        if let Some(eccfs_config) = eccfs_config {
+            eccfs.configure(eccfs_config);
+        }
+        eccfs.integrity_only = integrity_only;
+        if let Some(target) = volume {
+            eccfs.read_only = true;
+            eccfs.name = Some(volume::eccfs_name(target));
+        }
+        eccfs.shared_layers = self.meta_store.lock().await.shared_layer_db.clone();
+
+        let annotated = manifest
+            .annotations
+            .as_ref()
+            .and_then(|a| a.get(ANNOTATION_ECCFS_KEYS))
+            .map(String::as_str);
+        let keys_uri = match eccfs_config {
+            _ if integrity_only => None,
+            Some(eccfs_config) => eccfs_config.keys_uri_for(annotated),
+            None => {
+                if let Some(uri) = annotated {
+                    warn!("ignoring eccfs keys annotation {uri:?}, which is not allowed");
+                }
+                None
+            }
+        };
+        if let Some(keys_uri) = keys_uri {
+            let keys = crate::resource::get_resource(keys_uri)
+                .await
+                .map_err(|e| anyhow!("failed to get eccfs keys: {:?}", e))?;
+            eccfs.supplied_keys = Some(SuppliedKeys::from_json(&keys)?);
+        }
+
+        let eccfs: Box<dyn Snapshotter> = Box::new(eccfs);
+        #[cfg(feature = "fault-injection")]
+        let eccfs = self.faults.wrap_replacement(eccfs);
+        Ok(eccfs)
+    }
+
+    // Dir the layers of the default snapshotter are unpacked to, a tmpfs if
+    // it has a layer storage configured.
+    fn layer_dir(&self) -> Result<PathBuf> {
+        let snapshot = &self.config.default_snapshot;
+        match self.config.layer_storage.get(snapshot) {
+            Some(storage) => {
+                let dir = self.config.work_dir.join(format!("layers-{snapshot}"));
+                crate::layer_storage::prepare(storage, &dir)?;
+                Ok(dir)
+            }
+            None => Ok(self.config.work_dir.join("layers")),
+        }
+    }
+
+    #[cfg(feature = "nydus")]
+    async fn do_pull_image_with_nydus<'a>(
+        &self,
+        client: &mut PullClient<'_>,
+        image_data: &mut ImageMeta,
+        image_manifest: &OciImageManifest,
+        decrypt_config: &Option<&str>,
+        bundle_dir: &Path,
+    ) -> Result<String> {
+        let diff_ids = image_data.image_config.rootfs().diff_ids();
+        let bootstrap_id = if !diff_ids.is_empty() {
+            diff_ids[diff_ids.len() - 1].to_string()
+        } else {
+            bail!("Failed to get bootstrap id, diff_ids is empty");
+        };
+
+        let bootstrap = utils::get_nydus_bootstrap_desc(image_manifest)
+            .ok_or_else(|| anyhow!("Faild to get bootstrap oci descriptor"))?;
+        let layer_metas = client
+            .pull_bootstrap(
+                bootstrap,
+                bootstrap_id.to_string(),
+                decrypt_config,
+                self.meta_store.clone(),
+            )
+            .await?;
+        image_data.layer_metas = vec![layer_metas];
+        let layer_db: HashMap<String, LayerMeta> = image_data
+            .layer_metas
+            .iter()
+            .map(|layer| (layer.compressed_digest.clone(), layer.clone()))
+            .collect();
+
+        self.meta_store.lock().await.layer_db.extend(layer_db);
+
+        if image_data.layer_metas.is_empty() {
+            bail!("Failed to pull the bootstrap");
+        }
+
+        let reference = Reference::try_from(image_data.reference.clone())?;
+        let nydus_config = self
             .config
             .get_nydus_config()
             .expect("Nydus configuration not found");
         let work_dir = self.config.work_dir.clone();
-        let snapshot = match self.snapshots.get_mut(&self.config.default_snapshot) {
+        let mut snapshots = self.snapshots.lock().await;
+        let snapshot = match snapshots.get_mut(&self.config.default_snapshot) {
             Some(s) => s,
             _ => {
                 bail!(
@@ -466,6 +2092,29 @@ impl ImageClient {
     }
 }
 
+/// Whether the image of `meta` was pulled with `reference`, or with another
+/// tag of the same digest reference.
+pub(crate) fn is_pulled_as(meta: &ImageMeta, reference: &Reference) -> bool {
+    let Ok(pulled) = Reference::try_from(meta.reference.as_str()) else {
+        return false;
+    };
+    if pulled.registry() != reference.registry() || pulled.repository() != reference.repository() {
+        return false;
+    }
+
+    match reference.digest() {
+        Some(digest) => meta.digest == digest || pulled.digest() == Some(digest),
+        None => pulled.tag() == reference.tag(),
+    }
+}
+
+/// Whether an image pulled with the credentials `pulled_with` is reused by a
+/// pull with `credential` without asking the registry. Anyone can pull what
+/// was pulled anonymously.
+fn may_reuse(pulled_with: &BTreeSet<String>, credential: &str) -> bool {
+    pulled_with.contains(credential) || pulled_with.contains(ANONYMOUS_CREDENTIAL)
+}
+
 /// Create image meta object with the image info
 /// Return the image meta object, oci descriptors of the unique layers, and unique diff ids.
 fn create_image_meta(
@@ -474,12 +2123,18 @@ fn create_image_meta(
     image_manifest: &OciImageManifest,
     image_digest: &str,
     image_config: &str,
+    verification: Option<VerificationReport>,
 ) -> Result<(ImageMeta, Vec<OciDescriptor>, Vec<String>)> {
     let image_data = ImageMeta {
         id: id.to_string(),
         digest: image_digest.to_string(),
         reference: image_url.to_string(),
         image_config: ImageConfiguration::from_reader(image_config.to_string().as_bytes())?,
+        signed: verification
+            .as_ref()
+            .is_some_and(VerificationReport::is_signed),
+        verification,
+        manifest: Some(image_manifest.clone()),
         ..Default::default()
     };
 
@@ -513,22 +2168,50 @@ fn create_image_meta(
     Ok((image_data, unique_layers, unique_diff_ids))
 }
 
-fn create_bundle(
+// mount the rootfs of the bundle
+fn mount_rootfs(
     image_data: &ImageMeta,
     bundle_dir: &Path,
     snapshot: &mut Box<dyn Snapshotter>,
-) -> Result<String> {
-    let layer_path = image_data
-        .layer_metas
-        .iter()
-        .rev()
+    groups: &[LayerGroup],
+    customization: Option<&LayerMeta>,
+    cancel: &CancellationToken,
+    priority: Option<&BackgroundPriority>,
+) -> Result<MountPoint> {
+    if cancel.is_cancelled() {
+        bail!(ERR_PULL_CANCELLED);
+    }
+
+    // the layers of the groups are mounted apart from the rootfs, and the
+    // customization layer is the top-most one
+    let layer_path = customization
+        .into_iter()
+        .chain(
+            image_data
+                .layer_metas
+                .iter()
+                .rev()
+                .filter(|l| !groups.iter().any(|g| g.contains(&l.compressed_digest))),
+        )
         .map(|l| l.store_path.as_str())
         .collect::<Vec<&str>>();
 
-    snapshot.mount(&layer_path, &bundle_dir.join(BUNDLE_ROOTFS))?;
+    // converting the layers, e.g. into eccfs roimages, runs at the
+    // background priority as well
+    let rootfs = bundle_dir.join(BUNDLE_ROOTFS);
+    crate::priority::run_with(priority, || {
+        snapshot.mount_with_cancellation(&layer_path, &rootfs, cancel)
+    })
+}
 
+// write the runtime config of the bundle, once its rootfs is mounted
+fn create_bundle(image_data: &ImageMeta, bundle_dir: &Path) -> Result<String> {
     let image_config = image_data.image_config.clone();
-    if image_config.os() != &Os::Linux {
+    #[cfg(feature = "snapshot-wasm")]
+    let supported_os = image_config.os() == &Os::Linux || wasm::is_wasm_config(&image_config);
+    #[cfg(not(feature = "snapshot-wasm"))]
+    let supported_os = image_config.os() == &Os::Linux;
+    if !supported_os {
         bail!("unsupport OS image {:?}", image_config.os());
     }
 
@@ -537,11 +2220,49 @@ fn create_bundle(
     Ok(image_id)
 }
 
+// mount every layer group with its snapshotter into its dir of the bundle,
+// adding the mounts to `mounts`
+fn mount_layer_groups(
+    image_data: &ImageMeta,
+    bundle_dir: &Path,
+    groups: &[LayerGroup],
+    snapshots: &mut HashMap<SnapshotType, Box<dyn Snapshotter>>,
+    cancel: &CancellationToken,
+    priority: Option<&BackgroundPriority>,
+    mounts: &mut Vec<(SnapshotType, MountPoint)>,
+) -> Result<()> {
+    for group in groups {
+        if cancel.is_cancelled() {
+            bail!(ERR_PULL_CANCELLED);
+        }
+
+        let snapshot = snapshots
+            .get_mut(&group.snapshot)
+            .ok_or_else(|| anyhow!("snapshot {} not found", group.snapshot))?;
+        let layer_path = image_data
+            .layer_metas
+            .iter()
+            .rev()
+            .filter(|l| group.contains(&l.compressed_digest))
+            .map(|l| l.store_path.as_str())
+            .collect::<Vec<&str>>();
+        let mount_path = group.mount_path(bundle_dir);
+        let mount_point = crate::priority::run_with(priority, || {
+            snapshot.mount_with_cancellation(&layer_path, &mount_path, cancel)
+        })
+        .with_context(|| format!("failed to mount the layers at {}", group.mount_dir))?;
+        mounts.push((group.snapshot, mount_point));
+    }
+
+    Ok(())
+}
+
 #[cfg(feature = "snapshot-overlayfs")]
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::config::{ImageVolumeConfig, PullBudgetConfig};
     use test_utils::assert_retry;
 
     #[tokio::test]
@@ -567,7 +2288,7 @@ mod tests {
             // "releases-docker.jfrog.io/reg2/busybox:1.33.1"
         ];
 
-        let mut image_client = ImageClient::new(work_dir.path().to_path_buf());
+        let image_client = ImageClient::new(work_dir.path().to_path_buf());
         for image in oci_images.iter() {
             let bundle_dir = tempfile::tempdir().unwrap();
 
@@ -589,6 +2310,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_from_config() {
+        let work_dir = tempfile::tempdir().unwrap();
+        let mut config = ImageConfig::new(work_dir.path().to_path_buf());
+        config.max_concurrent_download = 0;
+        assert!(ImageClient::from_config(config).is_err());
+
+        let config = ImageConfig::new(work_dir.path().to_path_buf());
+        let client = ImageClient::from_config(config).unwrap();
+        assert_eq!(client.config.work_dir, work_dir.path());
+    }
+
+    #[test]
+    fn test_with_pull_config() {
+        let work_dir = tempfile::tempdir().unwrap();
+        let client = ImageClient::new(work_dir.path().to_path_buf());
+        let mut config = client.config.clone();
+        config.platform = Some("linux/arm64".into());
+        let pull = client.with_pull_config(config).unwrap();
+        assert_eq!(pull.config.platform.as_deref(), Some("linux/arm64"));
+        assert_eq!(client.config.platform, None);
+        assert!(Arc::ptr_eq(&pull.meta_store, &client.meta_store));
+        assert!(Arc::ptr_eq(&pull.snapshots, &client.snapshots));
+        assert!(Arc::ptr_eq(&pull.layer_locks, &client.layer_locks));
+
+        let other = tempfile::tempdir().unwrap();
+        let config = ImageConfig::new(other.path().to_path_buf());
+        assert!(client.with_pull_config(config).is_err());
+    }
+
     #[cfg(feature = "nydus")]
     #[tokio::test]
     async fn test_nydus_image() {
@@ -600,7 +2351,7 @@ mod tests {
             //"eci-nydus-registry.cn-hangzhou.cr.aliyuncs.com/test/python:latest_nydus",
         ];
 
-        let mut image_client = ImageClient::new(work_dir.path().to_path_buf());
+        let image_client = ImageClient::new(work_dir.path().to_path_buf());
 
         for image in nydus_images.iter() {
             let bundle_dir = tempfile::tempdir().unwrap();
@@ -629,7 +2380,7 @@ mod tests {
 
         let image = "mcr.microsoft.com/hello-world";
 
-        let mut image_client = ImageClient::new(work_dir.path().to_path_buf());
+        let image_client = ImageClient::new(work_dir.path().to_path_buf());
 
         let bundle1_dir = tempfile::tempdir().unwrap();
         if let Err(e) = image_client
@@ -651,6 +2402,7 @@ mod tests {
         // Assert that config is written out.
         assert!(bundle1_dir.path().join("config.json").exists());
         assert!(bundle2_dir.path().join("config.json").exists());
+        assert!(bundle1_dir.path().join("config.fragment.json").exists());
 
         // Assert that rootfs is populated.
         assert!(bundle1_dir.path().join("rootfs").join("hello").exists());
@@ -659,4 +2411,243 @@ mod tests {
         // Assert that image is pulled only once.
         assert_eq!(image_client.meta_store.lock().await.image_db.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_concurrent_pulls() {
+        let work_dir = tempfile::tempdir().unwrap();
+        let image = "mcr.microsoft.com/hello-world";
+        let mut image_client = ImageClient::new(work_dir.path().to_path_buf());
+        image_client.pull_budget = Some(Arc::new(PullBudget::new(&PullBudgetConfig {
+            max_concurrent_downloads: 1,
+            max_bytes_per_second: None,
+        })));
+
+        let bundle1_dir = tempfile::tempdir().unwrap();
+        let bundle2_dir = tempfile::tempdir().unwrap();
+        let (res1, res2) = tokio::join!(
+            image_client.pull_image(image, bundle1_dir.path(), &None, &None),
+            image_client.pull_image(image, bundle2_dir.path(), &None, &None),
+        );
+        assert_eq!(res1.unwrap().meta.id, res2.unwrap().meta.id);
+
+        assert!(bundle1_dir.path().join("rootfs").join("hello").exists());
+        assert!(bundle2_dir.path().join("rootfs").join("hello").exists());
+        assert_eq!(image_client.meta_store.lock().await.layer_db.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pull_events() {
+        let work_dir = tempfile::tempdir().unwrap();
+        let image = "mcr.microsoft.com/hello-world";
+        let image_client = ImageClient::new(work_dir.path().to_path_buf());
+        let mut events = image_client.subscribe();
+
+        let bundle_dir = tempfile::tempdir().unwrap();
+        let pulled = image_client
+            .pull_image(image, bundle_dir.path(), &None, &None)
+            .await
+            .unwrap();
+
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+        let started = PullEvent::PullStarted {
+            image_url: image.into(),
+            bundle_dir: bundle_dir.path().to_path_buf(),
+        };
+        assert_eq!(received[0], started);
+        assert!(matches!(&received[1], PullEvent::LayerVerified { .. }));
+        assert_eq!(
+            received[2],
+            PullEvent::SnapshotMounted {
+                image_url: image.into(),
+                image_id: pulled.meta.id,
+                snapshot: "overlay".into(),
+                mount_path: bundle_dir.path().join(BUNDLE_ROOTFS),
+            }
+        );
+        assert_eq!(received.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_pull_policy() {
+        let work_dir = tempfile::tempdir().unwrap();
+        let image = "mcr.microsoft.com/hello-world";
+        let image_client = ImageClient::new(work_dir.path().to_path_buf());
+
+        let bundle1_dir = tempfile::tempdir().unwrap();
+        assert!(image_client
+            .pull_image_with_policy(image, bundle1_dir.path(), &None, &None, PullPolicy::Never)
+            .await
+            .is_err());
+        image_client
+            .pull_image_with_policy(
+                image,
+                bundle1_dir.path(),
+                &None,
+                &None,
+                PullPolicy::IfNotPresent,
+            )
+            .await
+            .unwrap();
+
+        // the image pulled before is used without the registry
+        let bundle2_dir = tempfile::tempdir().unwrap();
+        let pulled = image_client
+            .pull_image_with_policy(image, bundle2_dir.path(), &None, &None, PullPolicy::Never)
+            .await
+            .unwrap();
+        assert!(pulled.meta.manifest.is_some());
+        assert!(bundle2_dir.path().join("rootfs").join("hello").exists());
+        assert_eq!(image_client.meta_store.lock().await.image_db.len(), 1);
+
+        // pulled with other credentials, it is only reused once the
+        // registry allows the ones of the pull
+        let id = pulled.meta.id;
+        let other = BTreeSet::from(["basic:other".to_string()]);
+        image_client
+            .meta_store
+            .lock()
+            .await
+            .image_db
+            .get_mut(&id)
+            .unwrap()
+            .pulled_with = other;
+        let bundle3_dir = tempfile::tempdir().unwrap();
+        assert!(image_client
+            .pull_image_with_policy(image, bundle3_dir.path(), &None, &None, PullPolicy::Never)
+            .await
+            .is_err());
+        image_client
+            .pull_image_with_policy(
+                image,
+                bundle3_dir.path(),
+                &None,
+                &None,
+                PullPolicy::IfNotPresent,
+            )
+            .await
+            .unwrap();
+        assert!(image_client.meta_store.lock().await.image_db[&id]
+            .pulled_with
+            .contains(ANONYMOUS_CREDENTIAL));
+    }
+
+    #[tokio::test]
+    async fn test_image_volume() {
+        // mounting needs root
+        if !nix::unistd::Uid::effective().is_root() {
+            return;
+        }
+
+        let work_dir = tempfile::tempdir().unwrap();
+        let image = "mcr.microsoft.com/hello-world";
+        let mut image_client = ImageClient::new(work_dir.path().to_path_buf());
+        image_client.config.image_volume = Some(ImageVolumeConfig {
+            snapshot: Some(SnapshotType::Overlay),
+            ..Default::default()
+        });
+
+        let volumes_dir = tempfile::tempdir().unwrap();
+        let target = volumes_dir.path().join("hello");
+        let volume = image_client
+            .mount_image_volume(image, &target, &None, &None)
+            .await
+            .unwrap();
+        assert_eq!(volume.target(), target);
+        assert_eq!(volume.snapshot, SnapshotType::Overlay);
+        assert!(std::fs::read_dir(&target).unwrap().next().is_some());
+        assert!(std::fs::write(target.join("file"), b"").is_err());
+        assert!(image_client
+            .mount_image_volume(image, &target, &None, &None)
+            .await
+            .is_err());
+
+        // volumes are not bundles
+        let m = image_client.meta_store.lock().await;
+        assert!(m.bundle_db.is_empty());
+        assert!(m.image_db.contains_key(&volume.image_id));
+        drop(m);
+        assert_eq!(image_client.image_volumes().await.len(), 1);
+
+        assert!(image_client.unmount_image_volume(&target).await.unwrap());
+        assert!(!image_client.unmount_image_volume(&target).await.unwrap());
+        assert!(image_client.image_volumes().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unmount_layer_groups() {
+        let work_dir = tempfile::tempdir().unwrap();
+        let client = ImageClient::new(work_dir.path().to_path_buf());
+        let bundle_dir = work_dir.path().join("bundle");
+        assert!(!client.unmount_layer_groups(&bundle_dir).await.unwrap());
+
+        // a group failing to unmount is kept
+        let group = GroupMount {
+            snapshot: SnapshotType::Unknown,
+            mount_dir: "weights".to_string(),
+            mount_point: MountPoint {
+                r#type: "unknown".to_string(),
+                mount_path: bundle_dir.join("weights"),
+                work_dir: PathBuf::new(),
+                aux_resources: Vec::new(),
+            },
+        };
+        client
+            .meta_store
+            .lock()
+            .await
+            .group_db
+            .insert(bundle_dir.display().to_string(), vec![group]);
+        assert!(client.unmount_layer_groups(&bundle_dir).await.is_err());
+        assert_eq!(client.meta_store.lock().await.group_db.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pin_image() {
+        let work_dir = tempfile::tempdir().unwrap();
+        let image_client = ImageClient::new(work_dir.path().to_path_buf());
+        assert!(image_client.pin_image("").await.is_err());
+
+        image_client.pin_image("busybox:1.36").await.unwrap();
+        assert_eq!(image_client.pinned_images().await, ["busybox:1.36"]);
+        assert!(image_client.unpin_image("busybox:1.36").await.unwrap());
+        assert!(!image_client.unpin_image("busybox:1.36").await.unwrap());
+        assert!(image_client.pinned_images().await.is_empty());
+    }
+
+    #[test]
+    fn test_may_reuse() {
+        let pulled_with = BTreeSet::from(["basic:a".to_string()]);
+        assert!(may_reuse(&pulled_with, "basic:a"));
+        assert!(!may_reuse(&pulled_with, "basic:b"));
+        assert!(!may_reuse(&pulled_with, ANONYMOUS_CREDENTIAL));
+        assert!(!may_reuse(&BTreeSet::new(), ANONYMOUS_CREDENTIAL));
+
+        let public = BTreeSet::from([ANONYMOUS_CREDENTIAL.to_string()]);
+        assert!(may_reuse(&public, "basic:b"));
+    }
+
+    #[test]
+    fn test_is_pulled_as() {
+        let meta = ImageMeta {
+            digest: "sha256:1111111111111111111111111111111111111111111111111111111111111111"
+                .into(),
+            reference: "busybox:1.36".into(),
+            ..Default::default()
+        };
+        let pulled_as =
+            |reference: &str| is_pulled_as(&meta, &Reference::try_from(reference).unwrap());
+        assert!(pulled_as("busybox:1.36"));
+        assert!(pulled_as("docker.io/library/busybox:1.36"));
+        assert!(pulled_as(
+            "busybox@sha256:1111111111111111111111111111111111111111111111111111111111111111"
+        ));
+        assert!(!pulled_as("busybox:1.37"));
+        assert!(!pulled_as("quay.io/library/busybox:1.36"));
+        assert!(!pulled_as(
+            "busybox@sha256:2222222222222222222222222222222222222222222222222222222222222222"
+        ));
+    }
 }
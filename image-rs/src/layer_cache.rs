@@ -0,0 +1,465 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Size budget of the layer store.
+//!
+//! Layers are kept once pulled, so that the next pulls of their images
+//! reuse them, and the layer store of a long-running guest grows with every
+//! image it ever pulled. With a [`LayerCacheConfig`], the size and the uses
+//! of the layers are recorded, and after every pull the layers are evicted
+//! in the order of the [`EvictionPolicy`] until the store, together with
+//! the blobs of the [`BlobCache`](crate::blob_cache::BlobCache) if any, fits
+//! into `max_bytes`. The images whose layers are evicted are forgotten, so
+//! that their next pull downloads them again.
+//!
+//! The layers of the pinned images, of the images whose bundle still has
+//! its rootfs mounted, and of the images mounted as volumes, are never
+//! evicted. Neither are the layers of the pulls in progress, reused or
+//! pulled, which the pulls hold in the [`LayerPins`] of the client until
+//! their image is mounted.
+
+use anyhow::Result;
+use oci_distribution::Reference;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::bundle::BUNDLE_ROOTFS;
+use crate::config::{EvictionPolicy, LayerCacheConfig};
+use crate::disk_space::{evict_layers, tree_size};
+use crate::image::{is_pulled_as, ImageMeta, LayerMeta};
+use crate::layer_storage::is_mount_point;
+use crate::meta_store::MetaStore;
+
+/// Size and uses of a layer of the layer store.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct LayerUsage {
+    /// Bytes of the unpacked layer.
+    pub size: u64,
+
+    /// Seconds since the epoch the layer was last used by a pull at.
+    pub last_used: u64,
+
+    /// Number of pulls which used the layer.
+    pub uses: u64,
+}
+
+/// Layers held by the pulls in progress, shared by the clients with the
+/// same layer store, so that the eviction run by a pull doesn't remove the
+/// layers another one reused or pulled before its image is mounted.
+#[derive(Default)]
+pub struct LayerPins {
+    pinned: Mutex<HashMap<String, usize>>,
+}
+
+impl LayerPins {
+    /// Pin the layers with `digests` until the returned [`LayerPin`] is
+    /// dropped.
+    pub fn pin(self: &Arc<Self>, digests: impl IntoIterator<Item = String>) -> LayerPin {
+        let digests: Vec<String> = digests.into_iter().collect();
+        let mut pinned = self.pinned.lock().unwrap_or_else(|e| e.into_inner());
+        for digest in &digests {
+            *pinned.entry(digest.clone()).or_default() += 1;
+        }
+
+        LayerPin {
+            pins: self.clone(),
+            digests,
+        }
+    }
+
+    /// Digests of the layers pinned.
+    pub fn pinned(&self) -> BTreeSet<String> {
+        let pinned = self.pinned.lock().unwrap_or_else(|e| e.into_inner());
+        pinned.keys().cloned().collect()
+    }
+}
+
+/// Layers pinned by a pull, see [`LayerPins::pin`].
+pub struct LayerPin {
+    pins: Arc<LayerPins>,
+    digests: Vec<String>,
+}
+
+impl Drop for LayerPin {
+    fn drop(&mut self) {
+        let mut pinned = self.pins.pinned.lock().unwrap_or_else(|e| e.into_inner());
+        for digest in &self.digests {
+            if let Some(count) = pinned.get_mut(digest) {
+                *count -= 1;
+                if *count == 0 {
+                    pinned.remove(digest);
+                }
+            }
+        }
+    }
+}
+
+/// Seconds since the epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Record that `layers` were used by a pull at `now`.
+pub fn record_use(meta_store: &mut MetaStore, layers: &[LayerMeta], now: u64) {
+    for layer in layers {
+        let usage = meta_store
+            .layer_usage
+            .entry(layer.compressed_digest.clone())
+            .or_insert_with(|| LayerUsage {
+                size: tree_size(Path::new(&layer.store_path)).unwrap_or_default(),
+                ..Default::default()
+            });
+        usage.last_used = now;
+        usage.uses += 1;
+    }
+}
+
+/// Whether the image of `meta` is pinned by one of `pins`, given as the
+/// reference the image was pulled with, or as its ID.
+pub fn is_pinned<'a>(meta: &ImageMeta, pins: impl IntoIterator<Item = &'a String>) -> bool {
+    pins.into_iter().any(|pin| {
+        *pin == meta.reference
+            || *pin == meta.id
+            || Reference::try_from(pin.as_str()).is_ok_and(|pin| is_pulled_as(meta, &pin))
+    })
+}
+
+// whether the rootfs of the bundle dir is still mounted
+fn is_mounted(bundle_dir: &Path) -> bool {
+    let rootfs = bundle_dir.join(BUNDLE_ROOTFS);
+    rootfs.exists() && is_mount_point(&rootfs).unwrap_or(false)
+}
+
+/// Evict the layers of `meta_store` in the order of the policy of `config`
+/// until the store and the `cached` bytes of the blob cache fit into its
+/// `max_bytes`, except the ones whose digest is in `keep`. Returns the bytes
+/// freed.
+pub fn enforce(
+    meta_store: &mut MetaStore,
+    config: &LayerCacheConfig,
+    keep: &BTreeSet<&str>,
+    cached: u64,
+) -> Result<u64> {
+    // the bundles unmounted since don't hold their layers anymore
+    meta_store
+        .bundle_db
+        .retain(|bundle, _| is_mounted(Path::new(bundle)));
+    meta_store
+        .customization_db
+        .retain(|bundle, _| is_mounted(Path::new(bundle)));
+
+    let volumes = meta_store.volume_db.values().map(|volume| &volume.image_id);
+    let in_use: BTreeSet<&String> = meta_store.bundle_db.values().chain(volumes).collect();
+    let mut protected: BTreeSet<String> = meta_store
+        .image_db
+        .values()
+        .filter(|image| {
+            in_use.contains(&image.id)
+                || is_pinned(
+                    image,
+                    config.pinned_images.iter().chain(&meta_store.pinned_images),
+                )
+        })
+        .flat_map(|image| &image.layer_metas)
+        .map(|layer| layer.compressed_digest.clone())
+        .chain(meta_store.customization_db.values().cloned())
+        .collect();
+    protected.extend(keep.iter().map(|digest| digest.to_string()));
+
+    // the layers pulled before the budget was set have not been recorded
+    meta_store
+        .layer_usage
+        .retain(|digest, _| meta_store.layer_db.contains_key(digest));
+    for image in meta_store.image_db.values() {
+        for layer in &image.layer_metas {
+            meta_store
+                .layer_usage
+                .entry(layer.compressed_digest.clone())
+                .or_insert_with(|| LayerUsage {
+                    size: tree_size(Path::new(&layer.store_path)).unwrap_or_default(),
+                    ..Default::default()
+                });
+        }
+    }
+
+    let mut total: u64 = meta_store
+        .layer_usage
+        .values()
+        .map(|usage| usage.size)
+        .sum::<u64>()
+        .saturating_add(cached);
+    if total <= config.max_bytes {
+        return Ok(0);
+    }
+
+    let mut candidates: Vec<(&String, &LayerUsage)> = meta_store
+        .layer_usage
+        .iter()
+        .filter(|(digest, _)| !protected.contains(*digest))
+        .collect();
+    candidates.sort_by_key(|(digest, usage)| match config.policy {
+        EvictionPolicy::Lru => (usage.last_used, usage.uses, *digest),
+        EvictionPolicy::Lfu => (usage.uses, usage.last_used, *digest),
+    });
+
+    let mut evicted = Vec::new();
+    for (digest, usage) in candidates {
+        if total <= config.max_bytes {
+            break;
+        }
+        total -= usage.size;
+        evicted.push(digest.clone());
+    }
+
+    evict_layers(meta_store, &evicted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::snapshots::{MountPoint, SnapshotType};
+    use crate::volume::ImageVolume;
+
+    // an image of the layers of the given sizes, stored under dir
+    fn image(meta_store: &mut MetaStore, dir: &Path, name: &str, layers: &[(&str, usize)]) {
+        let layer_metas: Vec<LayerMeta> = layers
+            .iter()
+            .map(|(digest, size)| {
+                let path = dir.join(digest);
+                fs::create_dir_all(&path).unwrap();
+                fs::write(path.join("data"), vec![0; *size]).unwrap();
+                LayerMeta {
+                    compressed_digest: digest.to_string(),
+                    store_path: path.display().to_string(),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        for layer in &layer_metas {
+            meta_store
+                .layer_db
+                .insert(layer.compressed_digest.clone(), layer.clone());
+        }
+        meta_store.image_db.insert(
+            name.to_string(),
+            ImageMeta {
+                id: name.to_string(),
+                reference: format!("docker.io/library/{name}:latest"),
+                layer_metas,
+                ..Default::default()
+            },
+        );
+    }
+
+    fn record(meta_store: &mut MetaStore, name: &str, now: u64) {
+        let layers = meta_store.image_db[name].layer_metas.clone();
+        record_use(meta_store, &layers, now);
+    }
+
+    #[test]
+    fn test_enforce_lru() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut meta_store = MetaStore::default();
+        image(
+            &mut meta_store,
+            tempdir.path(),
+            "a",
+            &[("base", 4), ("a", 4)],
+        );
+        image(
+            &mut meta_store,
+            tempdir.path(),
+            "b",
+            &[("base", 4), ("b", 4)],
+        );
+        image(&mut meta_store, tempdir.path(), "c", &[("c", 4)]);
+        record(&mut meta_store, "a", 1);
+        record(&mut meta_store, "a", 2);
+        record(&mut meta_store, "b", 3);
+        record(&mut meta_store, "c", 4);
+        assert_eq!(meta_store.layer_usage["base"].uses, 3);
+        assert_eq!(meta_store.layer_usage["a"].size, 4);
+
+        let config = LayerCacheConfig {
+            max_bytes: 16,
+            policy: EvictionPolicy::Lru,
+            pinned_images: Vec::new(),
+        };
+        assert_eq!(
+            enforce(&mut meta_store, &config, &BTreeSet::new(), 0).unwrap(),
+            0
+        );
+
+        // a is the least recently used, and c is kept
+        let config = LayerCacheConfig {
+            max_bytes: 12,
+            ..config
+        };
+        assert_eq!(
+            enforce(&mut meta_store, &config, &BTreeSet::from(["c"]), 0).unwrap(),
+            4
+        );
+        assert!(!tempdir.path().join("a").exists());
+        assert!(!meta_store.layer_db.contains_key("a"));
+        assert!(!meta_store.image_db.contains_key("a"));
+        assert!(meta_store.image_db.contains_key("b"));
+
+        // b's layers are the only ones left to evict
+        let config = LayerCacheConfig {
+            max_bytes: 1,
+            ..config
+        };
+        assert_eq!(
+            enforce(&mut meta_store, &config, &BTreeSet::from(["c"]), 0).unwrap(),
+            8
+        );
+        assert_eq!(
+            meta_store.layer_db.keys().collect::<Vec<_>>(),
+            vec![&"c".to_string()]
+        );
+        assert_eq!(meta_store.image_db.len(), 1);
+    }
+
+    #[test]
+    fn test_enforce_lfu_and_pins() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut meta_store = MetaStore::default();
+        image(&mut meta_store, tempdir.path(), "a", &[("a", 4)]);
+        image(&mut meta_store, tempdir.path(), "b", &[("b", 4)]);
+        image(&mut meta_store, tempdir.path(), "c", &[("c", 4)]);
+        record(&mut meta_store, "a", 1);
+        record(&mut meta_store, "a", 2);
+        record(&mut meta_store, "b", 3);
+        record(&mut meta_store, "c", 4);
+        record(&mut meta_store, "c", 5);
+
+        // b is the least frequently used, but pinned
+        let config = LayerCacheConfig {
+            max_bytes: 8,
+            policy: EvictionPolicy::Lfu,
+            pinned_images: vec!["docker.io/library/b:latest".into()],
+        };
+        enforce(&mut meta_store, &config, &BTreeSet::new(), 0).unwrap();
+        assert!(meta_store.image_db.contains_key("b"));
+        assert!(!meta_store.image_db.contains_key("a"));
+
+        meta_store.pinned_images.insert("busybox".into());
+        image(&mut meta_store, tempdir.path(), "busybox", &[("d", 4)]);
+        let config = LayerCacheConfig {
+            max_bytes: 1,
+            ..config
+        };
+        enforce(&mut meta_store, &config, &BTreeSet::new(), 0).unwrap();
+        assert_eq!(
+            meta_store.image_db.keys().collect::<BTreeSet<_>>(),
+            BTreeSet::from([&"b".to_string(), &"busybox".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_enforce_volumes() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut meta_store = MetaStore::default();
+        image(&mut meta_store, tempdir.path(), "a", &[("a", 4)]);
+        image(&mut meta_store, tempdir.path(), "b", &[("b", 4)]);
+        meta_store.volume_db.insert(
+            "/run/volumes/a".into(),
+            ImageVolume {
+                image_url: "docker.io/library/a:latest".into(),
+                image_id: "a".into(),
+                snapshot: SnapshotType::Unknown,
+                mount_point: MountPoint {
+                    r#type: "unknown".into(),
+                    mount_path: "/run/volumes/a".into(),
+                    work_dir: PathBuf::new(),
+                    aux_resources: Vec::new(),
+                },
+            },
+        );
+
+        // the layers of a are mounted as a volume
+        let config = LayerCacheConfig {
+            max_bytes: 1,
+            policy: EvictionPolicy::Lru,
+            pinned_images: Vec::new(),
+        };
+        assert_eq!(
+            enforce(&mut meta_store, &config, &BTreeSet::new(), 0).unwrap(),
+            4
+        );
+        assert!(meta_store.image_db.contains_key("a"));
+        assert!(!meta_store.image_db.contains_key("b"));
+    }
+
+    #[test]
+    fn test_enforce_blob_cache() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut meta_store = MetaStore::default();
+        image(&mut meta_store, tempdir.path(), "a", &[("a", 4)]);
+        image(&mut meta_store, tempdir.path(), "b", &[("b", 4)]);
+        record(&mut meta_store, "a", 1);
+        record(&mut meta_store, "b", 2);
+
+        // the layers fit, but not with the cached blobs
+        let config = LayerCacheConfig {
+            max_bytes: 8,
+            policy: EvictionPolicy::Lru,
+            pinned_images: Vec::new(),
+        };
+        assert_eq!(
+            enforce(&mut meta_store, &config, &BTreeSet::new(), 0).unwrap(),
+            0
+        );
+        assert_eq!(
+            enforce(&mut meta_store, &config, &BTreeSet::new(), 4).unwrap(),
+            4
+        );
+        assert!(!meta_store.image_db.contains_key("a"));
+        assert!(!meta_store.layer_usage.contains_key("a"));
+        assert!(meta_store.image_db.contains_key("b"));
+    }
+
+    #[test]
+    fn test_layer_pins() {
+        let pins = Arc::new(LayerPins::default());
+        let first = pins.pin(["a".to_string(), "b".to_string()]);
+        let second = pins.pin(["b".to_string()]);
+        assert_eq!(
+            pins.pinned(),
+            BTreeSet::from(["a".to_string(), "b".to_string()])
+        );
+
+        drop(first);
+        assert_eq!(pins.pinned(), BTreeSet::from(["b".to_string()]));
+        drop(second);
+        assert!(pins.pinned().is_empty());
+    }
+
+    #[test]
+    fn test_is_pinned() {
+        let meta = ImageMeta {
+            id: "sha256:config".into(),
+            reference: "busybox:1.36".into(),
+            ..Default::default()
+        };
+        assert!(is_pinned(&meta, &["busybox:1.36".to_string()]));
+        assert!(is_pinned(&meta, &["sha256:config".to_string()]));
+        assert!(is_pinned(
+            &meta,
+            &["docker.io/library/busybox:1.36".to_string()]
+        ));
+        assert!(!is_pinned(&meta, &["busybox:1.37".to_string()]));
+        assert!(!is_pinned(&meta, None));
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Layers of an image mounted apart from its rootfs.
+//!
+//! Some images ship data next to the code, e.g. model-serving images whose
+//! weights take most of the layers. The layers annotated with
+//! [`ANNOTATION_LAYER_SNAPSHOTTER`] are left out of the rootfs and mounted
+//! by the snapshotter named, e.g. the weights as read-only eccfs roimages
+//! next to an overlay rootfs with the code. They are mounted into their own
+//! dir of the bundle, from where the runtime mounts them into the container
+//! wherever it needs them.
+//!
+//! The layers of a snapshotter form one group, stacked in layer order into
+//! the dir named by [`ANNOTATION_LAYER_MOUNT`]. Snapshotters keep the state
+//! of a mount by the name of the bundle dir, so every group needs its own
+//! snapshotter, other than the one of the rootfs.
+//!
+//! The mounts of the groups are returned with the pulled image, measured
+//! like the rootfs, see [`crate::measure`], and recorded in the
+//! [`crate::meta_store::MetaStore`] by bundle dir. The runtime unmounts the
+//! rootfs, but only image-rs knows how the groups were mounted, so they are
+//! unmounted by [`crate::image::ImageClient::unmount_layer_groups`] once the
+//! container is gone.
+
+use anyhow::{anyhow, bail, Result};
+use oci_distribution::manifest::OciImageManifest;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use crate::bundle::{BUNDLE_CONFIG, BUNDLE_CONFIG_FRAGMENT, BUNDLE_ROOTFS};
+use crate::snapshots::{MountPoint, SnapshotType};
+
+/// Annotation of a layer with the snapshotter it is mounted with instead of
+/// being part of the rootfs, e.g. `eccfs`.
+pub const ANNOTATION_LAYER_SNAPSHOTTER: &str = "io.confidential-containers.image.layer.snapshotter";
+
+/// Annotation of a layer with the dir of the bundle its group is mounted
+/// at. It defaults to the name of the snapshotter.
+pub const ANNOTATION_LAYER_MOUNT: &str = "io.confidential-containers.image.layer.mount";
+
+/// Layers of an image mounted apart from the rootfs by one snapshotter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LayerGroup {
+    /// Snapshotter the layers are mounted with.
+    pub snapshot: SnapshotType,
+
+    /// Dir of the bundle the layers are mounted at.
+    pub mount_dir: String,
+
+    /// Digests of the layers, in layer order.
+    pub digests: Vec<String>,
+}
+
+impl LayerGroup {
+    /// Where the group is mounted in the bundle at `bundle_dir`.
+    pub fn mount_path(&self, bundle_dir: &Path) -> PathBuf {
+        bundle_dir.join(&self.mount_dir)
+    }
+
+    /// Whether the layer with `digest` belongs to the group.
+    pub fn contains(&self, digest: &str) -> bool {
+        self.digests.iter().any(|d| d == digest)
+    }
+}
+
+/// A layer group mounted into a bundle.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GroupMount {
+    /// Snapshotter the layers are mounted with.
+    pub snapshot: SnapshotType,
+
+    /// Dir of the bundle the layers are mounted at.
+    pub mount_dir: String,
+
+    /// The mount of the group, at its dir of the bundle.
+    pub mount_point: MountPoint,
+}
+
+impl GroupMount {
+    /// Where the group is mounted.
+    pub fn mount_path(&self) -> &Path {
+        &self.mount_point.mount_path
+    }
+}
+
+/// Get the layer groups of `manifest`, whose rootfs is mounted with
+/// `rootfs_snapshot`. Images without annotated layers have none.
+pub fn layer_groups(
+    manifest: &OciImageManifest,
+    rootfs_snapshot: SnapshotType,
+) -> Result<Vec<LayerGroup>> {
+    let mut groups: Vec<LayerGroup> = Vec::new();
+    let mut seen = HashMap::new();
+    let mut rootfs_layers = 0;
+    for layer in &manifest.layers {
+        let annotation = |key: &str| layer.annotations.as_ref().and_then(|a| a.get(key));
+        let snapshot = annotation(ANNOTATION_LAYER_SNAPSHOTTER)
+            .map(|name| {
+                serde_json::from_value::<SnapshotType>(Value::String(name.clone()))
+                    .map_err(|_| anyhow!("layer {}: unknown snapshotter {name:?}", layer.digest))
+            })
+            .transpose()?;
+
+        // duplicated layers are unpacked once, so they can't be split
+        if let Some(first) = seen.insert(layer.digest.as_str(), snapshot) {
+            if first != snapshot {
+                bail!(
+                    "layer {} is annotated with different snapshotters",
+                    layer.digest
+                );
+            }
+            continue;
+        }
+
+        let Some(snapshot) = snapshot else {
+            rootfs_layers += 1;
+            continue;
+        };
+        if snapshot == rootfs_snapshot {
+            bail!(
+                "layer {}: {snapshot} is the snapshotter of the rootfs, drop {ANNOTATION_LAYER_SNAPSHOTTER} to keep the layer in the rootfs",
+                layer.digest
+            );
+        }
+
+        let mount_dir = annotation(ANNOTATION_LAYER_MOUNT)
+            .cloned()
+            .unwrap_or_else(|| snapshot.to_string());
+        check_mount_dir(&mount_dir)?;
+        match groups.iter_mut().find(|group| group.snapshot == snapshot) {
+            Some(group) if group.mount_dir != mount_dir => bail!(
+                "layers of snapshotter {snapshot} are mounted at both {} and {mount_dir}, give them the same {ANNOTATION_LAYER_MOUNT}",
+                group.mount_dir
+            ),
+            Some(group) => group.digests.push(layer.digest.clone()),
+            None => {
+                if groups.iter().any(|group| group.mount_dir == mount_dir) {
+                    bail!("layers of different snapshotters are mounted at {mount_dir}");
+                }
+                groups.push(LayerGroup {
+                    snapshot,
+                    mount_dir,
+                    digests: vec![layer.digest.clone()],
+                });
+            }
+        }
+    }
+
+    if !groups.is_empty() && rootfs_layers == 0 {
+        bail!("all the layers are mounted apart from the rootfs, which needs one at least");
+    }
+
+    Ok(groups)
+}
+
+// groups are mounted at a dir right under the bundle dir, next to the
+// rootfs and the runtime configs
+fn check_mount_dir(dir: &str) -> Result<()> {
+    let mut components = Path::new(dir).components();
+    let single = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    );
+    if !single
+        || dir.contains('/')
+        || [BUNDLE_ROOTFS, BUNDLE_CONFIG, BUNDLE_CONFIG_FRAGMENT].contains(&dir)
+    {
+        bail!("{ANNOTATION_LAYER_MOUNT} {dir:?} is not the name of a free dir of the bundle");
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "snapshot-overlayfs")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oci_distribution::manifest::OciDescriptor;
+
+    fn manifest(layers: &[(&str, &[(&str, &str)])]) -> OciImageManifest {
+        OciImageManifest {
+            layers: layers
+                .iter()
+                .map(|(digest, annotations)| OciDescriptor {
+                    digest: digest.to_string(),
+                    annotations: (!annotations.is_empty()).then(|| {
+                        annotations
+                            .iter()
+                            .map(|(k, v)| (k.to_string(), v.to_string()))
+                            .collect()
+                    }),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_layer_groups() {
+        let overlay = [(ANNOTATION_LAYER_SNAPSHOTTER, "overlay")];
+        let weights = [
+            (ANNOTATION_LAYER_SNAPSHOTTER, "overlay"),
+            (ANNOTATION_LAYER_MOUNT, "weights"),
+        ];
+
+        let plain = manifest(&[("sha256:a", &[]), ("sha256:b", &[])]);
+        assert!(layer_groups(&plain, SnapshotType::Unknown)
+            .unwrap()
+            .is_empty());
+
+        let split = manifest(&[
+            ("sha256:a", &[]),
+            ("sha256:b", &weights),
+            ("sha256:c", &weights),
+            ("sha256:b", &weights),
+        ]);
+        let groups = layer_groups(&split, SnapshotType::Unknown).unwrap();
+        assert_eq!(
+            groups,
+            vec![LayerGroup {
+                snapshot: SnapshotType::Overlay,
+                mount_dir: "weights".into(),
+                digests: vec!["sha256:b".into(), "sha256:c".into()],
+            }]
+        );
+        assert!(groups[0].contains("sha256:c"));
+        assert!(!groups[0].contains("sha256:a"));
+        assert_eq!(
+            groups[0].mount_path(Path::new("/run/bundle")),
+            PathBuf::from("/run/bundle/weights")
+        );
+
+        let default_dir = manifest(&[("sha256:a", &[]), ("sha256:b", &overlay)]);
+        let groups = layer_groups(&default_dir, SnapshotType::Unknown).unwrap();
+        assert_eq!(groups[0].mount_dir, "overlay");
+
+        let invalid = [
+            // the snapshotter of the rootfs
+            (
+                manifest(&[("sha256:a", &[]), ("sha256:b", &overlay)]),
+                SnapshotType::Overlay,
+            ),
+            // no rootfs layer left
+            (manifest(&[("sha256:b", &weights)]), SnapshotType::Unknown),
+            // a duplicated layer in and out of the rootfs
+            (
+                manifest(&[("sha256:a", &[]), ("sha256:b", &weights), ("sha256:b", &[])]),
+                SnapshotType::Unknown,
+            ),
+            // one snapshotter at two dirs
+            (
+                manifest(&[
+                    ("sha256:a", &[]),
+                    ("sha256:b", &weights),
+                    ("sha256:c", &overlay),
+                ]),
+                SnapshotType::Unknown,
+            ),
+            (
+                manifest(&[
+                    ("sha256:a", &[]),
+                    ("sha256:b", &[(ANNOTATION_LAYER_SNAPSHOTTER, "zfs")]),
+                ]),
+                SnapshotType::Unknown,
+            ),
+        ];
+        for (manifest, rootfs_snapshot) in invalid {
+            assert!(layer_groups(&manifest, rootfs_snapshot).is_err());
+        }
+
+        for dir in [
+            "",
+            "rootfs",
+            "config.json",
+            "../etc",
+            "a/b",
+            "/weights",
+            ".",
+        ] {
+            assert!(check_mount_dir(dir).is_err(), "{dir}");
+        }
+        check_mount_dir("weights").unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Memory-backed storage for unpacked layers.
+//!
+//! Decrypted layers are unpacked in plaintext, which leaks them where the
+//! work dir is visible to the host. A snapshotter can instead have its
+//! layers unpacked into a size-capped tmpfs in guest memory. Layers not
+//! fitting into it are unpacked to a spill dir, which the deployment puts on
+//! encrypted scratch storage (e.g. sefs or dm-crypt).
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::config::LayerStorageConfig;
+
+/// Mount the tmpfs of `config` on `dir`, unless it is mounted already, and
+/// create the spill dir.
+pub fn prepare(config: &LayerStorageConfig, dir: &Path) -> Result<()> {
+    fs::create_dir_all(&config.spill_dir)
+        .with_context(|| format!("create spill dir {}", config.spill_dir.display()))?;
+    fs::create_dir_all(dir)?;
+    if is_mount_point(dir)? {
+        return Ok(());
+    }
+
+    mount_tmpfs(dir, config.tmpfs_size)
+}
+
+pub(crate) fn is_mount_point(dir: &Path) -> Result<bool> {
+    let parent = match dir.parent() {
+        Some(parent) => fs::metadata(parent)?,
+        None => return Ok(true),
+    };
+
+    Ok(fs::metadata(dir)?.dev() != parent.dev())
+}
+
+#[cfg(feature = "nix")]
+fn mount_tmpfs(dir: &Path, size: u64) -> Result<()> {
+    use nix::mount::MsFlags;
+
+    let options = format!("size={size},mode=0700");
+    nix::mount::mount(
+        Some("tmpfs"),
+        dir,
+        Some("tmpfs"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some(options.as_str()),
+    )
+    .with_context(|| format!("mount layer tmpfs on {}", dir.display()))
+}
+
+#[cfg(not(feature = "nix"))]
+fn mount_tmpfs(_dir: &Path, _size: u64) -> Result<()> {
+    anyhow::bail!("layer tmpfs is not supported in this build")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_mount_point() {
+        let tempdir = tempfile::tempdir().unwrap();
+        assert!(!is_mount_point(tempdir.path()).unwrap());
+        assert!(is_mount_point(Path::new("/")).unwrap());
+    }
+}
@@ -4,22 +4,53 @@
 
 pub const ERR_BAD_UNCOMPRESSED_DIGEST: &str = "unsupported uncompressed digest format";
 
+/// Error message returned when an in-flight pull is aborted by its
+/// [`CancellationToken`](tokio_util::sync::CancellationToken).
+pub const ERR_PULL_CANCELLED: &str = "image pull cancelled";
+
 pub mod auth;
+pub mod blob_cache;
 pub mod bundle;
 pub mod config;
+pub mod customization;
 pub mod decoder;
 pub mod decrypt;
 pub mod digest;
+pub mod disk_space;
+pub mod events;
+pub mod export;
+pub mod extract;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+pub mod flatten;
+pub mod freshness;
 pub mod image;
+pub mod layer_cache;
+pub mod layer_groups;
+pub mod layer_storage;
+pub mod local;
+pub mod manifest_cache;
+pub mod measure;
 pub mod meta_store;
+#[cfg(feature = "registry-mtls")]
+pub mod mtls;
 #[cfg(feature = "nydus")]
 pub mod nydus;
+#[cfg(feature = "vsock-proxy")]
+pub mod proxy;
+pub mod priority;
 pub mod pull;
+pub mod pull_budget;
+pub mod reference_policy;
+pub mod relabel;
 pub mod resource;
 #[cfg(feature = "signature")]
 pub mod signature;
 pub mod snapshots;
 pub mod stream;
+mod trace;
 pub mod unpack;
+pub mod verification;
 #[cfg(feature = "verity")]
 pub mod verity;
+pub mod volume;
@@ -0,0 +1,616 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Image sources already present inside the guest, e.g. preloaded via a
+//! measured volume. Two formats are supported, following the naming of
+//! [containers-transports(5)](https://github.com/containers/image/blob/main/docs/containers-transports.5.md):
+//!
+//! - `oci:<path>[:<reference>]`: an [OCI image layout](https://github.com/opencontainers/image-spec/blob/main/image-layout.md)
+//!   directory. `reference` selects the manifest by its
+//!   `org.opencontainers.image.ref.name` annotation, and can be omitted if
+//!   the layout contains only one manifest.
+//! - `docker-archive:<path>[:<docker-reference>]`: a tarball produced by
+//!   `docker save`. `docker-reference` selects the image by its tag, and can
+//!   be omitted if the archive contains only one image.
+//!
+//! Local images go through the same signature verification, decryption
+//! and unpacking as images pulled from a registry. Signature policies are
+//! matched against the docker reference of the image, which is taken from
+//! the URL if given there, otherwise from the metadata of the image
+//! (`io.containerd.image.name` or a fully qualified
+//! `org.opencontainers.image.ref.name` annotation, or `RepoTags`). An image
+//! without any such name is verified as `localhost/<path>`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use oci_distribution::client::current_platform_resolver;
+use oci_distribution::manifest::{
+    OciDescriptor, OciImageIndex, OciImageManifest, IMAGE_CONFIG_MEDIA_TYPE,
+    IMAGE_LAYER_GZIP_MEDIA_TYPE, IMAGE_LAYER_MEDIA_TYPE, OCI_IMAGE_MEDIA_TYPE,
+};
+use oci_distribution::Reference;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+
+/// URL prefix of OCI image layout sources.
+pub const OCI_LAYOUT_PREFIX: &str = "oci:";
+
+/// URL prefix of `docker save` tarball sources.
+pub const DOCKER_ARCHIVE_PREFIX: &str = "docker-archive:";
+
+const ANNOTATION_REF_NAME: &str = "org.opencontainers.image.ref.name";
+const ANNOTATION_CONTAINERD_IMAGE_NAME: &str = "io.containerd.image.name";
+const OCI_IMAGE_INDEX_MEDIA_TYPE: &str = "application/vnd.oci.image.index.v1+json";
+const DOCKER_MANIFEST_LIST_MEDIA_TYPE: &str =
+    "application/vnd.docker.distribution.manifest.list.v2+json";
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const IMAGE_LAYER_ZSTD_MEDIA_TYPE: &str = "application/vnd.oci.image.layer.v1.tar+zstd";
+
+/// Returns whether the image url refers to a local image source.
+pub fn is_local_image(image_url: &str) -> bool {
+    image_url.starts_with(OCI_LAYOUT_PREFIX) || image_url.starts_with(DOCKER_ARCHIVE_PREFIX)
+}
+
+/// Location of a blob inside the local source.
+#[derive(Clone, Debug)]
+enum BlobLocation {
+    /// A standalone file.
+    File(PathBuf),
+
+    /// A range of a (docker-archive) tarball.
+    ArchiveEntry { offset: u64, size: u64 },
+}
+
+/// A local image, with its manifest and config already loaded.
+#[derive(Debug)]
+pub struct LocalSource {
+    /// Path of the OCI layout dir or the docker-archive tarball.
+    path: PathBuf,
+
+    /// Docker reference the image is verified as.
+    reference: Reference,
+
+    manifest: OciImageManifest,
+    manifest_digest: String,
+    config: String,
+
+    /// Blobs of the image keyed by the digests in the manifest.
+    blobs: HashMap<String, BlobLocation>,
+}
+
+impl LocalSource {
+    /// Load the local image referred to by `image_url`. Returns `None` if
+    /// `image_url` is not a local image url.
+    pub async fn from_url(image_url: &str) -> Result<Option<Self>> {
+        if let Some(rest) = image_url.strip_prefix(OCI_LAYOUT_PREFIX) {
+            let (path, name) = split_path_and_name(rest);
+            let path = PathBuf::from(path);
+            return tokio::task::spawn_blocking(move || Self::open_oci_layout(path, name))
+                .await?
+                .map(Some);
+        }
+
+        if let Some(rest) = image_url.strip_prefix(DOCKER_ARCHIVE_PREFIX) {
+            let (path, name) = split_path_and_name(rest);
+            let path = PathBuf::from(path);
+            return tokio::task::spawn_blocking(move || Self::open_docker_archive(path, name))
+                .await?
+                .map(Some);
+        }
+
+        Ok(None)
+    }
+
+    /// The docker reference signature policies are matched against.
+    pub fn reference(&self) -> &Reference {
+        &self.reference
+    }
+
+    /// Returns the image manifest, manifest digest and image config,
+    /// in the same form as [`oci_distribution::Client::pull_manifest_and_config`].
+    pub fn manifest_and_config(&self) -> (OciImageManifest, String, String) {
+        (
+            self.manifest.clone(),
+            self.manifest_digest.clone(),
+            self.config.clone(),
+        )
+    }
+
+    /// Open the blob with the given digest for reading.
+    pub async fn blob_reader(&self, digest: &str) -> Result<impl AsyncRead + Unpin + Send> {
+        let location = self
+            .blobs
+            .get(digest)
+            .ok_or_else(|| anyhow!("blob {} not found in {}", digest, self.path.display()))?;
+
+        let reader = match location {
+            BlobLocation::File(path) => {
+                let file = tokio::fs::File::open(path).await?;
+                let size = file.metadata().await?.len();
+                file.take(size)
+            }
+            BlobLocation::ArchiveEntry { offset, size } => {
+                let mut file = tokio::fs::File::open(&self.path).await?;
+                file.seek(SeekFrom::Start(*offset)).await?;
+                file.take(*size)
+            }
+        };
+
+        Ok(reader)
+    }
+
+    fn open_oci_layout(dir: PathBuf, name: Option<String>) -> Result<Self> {
+        let blob_path = |digest: &str| -> Result<PathBuf> {
+            let (algorithm, encoded) = digest
+                .split_once(':')
+                .ok_or_else(|| anyhow!("invalid digest {}", digest))?;
+            if encoded.is_empty() || encoded.contains('/') || algorithm.contains('/') {
+                bail!("invalid digest {}", digest);
+            }
+            Ok(dir.join("blobs").join(algorithm).join(encoded))
+        };
+
+        let index = std::fs::read(dir.join("index.json"))
+            .with_context(|| format!("read index.json of OCI layout {}", dir.display()))?;
+        let index: OciImageIndex = serde_json::from_slice(&index)?;
+
+        let entry = match &name {
+            Some(name) => index
+                .manifests
+                .iter()
+                .find(|m| annotation(&m.annotations, ANNOTATION_REF_NAME) == Some(name))
+                .ok_or_else(|| anyhow!("no manifest named {} in {}", name, dir.display()))?,
+            None => match &index.manifests[..] {
+                [entry] => entry,
+                _ => bail!(
+                    "OCI layout {} contains {} manifests, a reference must be given",
+                    dir.display(),
+                    index.manifests.len()
+                ),
+            },
+        };
+
+        // Name the image after the index entry that was selected, even if
+        // it points to a nested (multi-platform) index.
+        let identity = annotation(&entry.annotations, ANNOTATION_CONTAINERD_IMAGE_NAME)
+            .or_else(|| {
+                annotation(&entry.annotations, ANNOTATION_REF_NAME).filter(|n| n.contains('/'))
+            })
+            .cloned();
+
+        let mut entry = entry.clone();
+        if entry.media_type == OCI_IMAGE_INDEX_MEDIA_TYPE
+            || entry.media_type == DOCKER_MANIFEST_LIST_MEDIA_TYPE
+        {
+            let nested = read_verified(&blob_path(&entry.digest)?, &entry.digest)?;
+            let nested: OciImageIndex = serde_json::from_slice(&nested)?;
+            let digest = current_platform_resolver(&nested.manifests)
+                .ok_or_else(|| anyhow!("no manifest for the current platform"))?;
+            entry = nested
+                .manifests
+                .into_iter()
+                .find(|m| m.digest == digest)
+                .expect("resolved digest must be in the index");
+        }
+
+        let manifest = read_verified(&blob_path(&entry.digest)?, &entry.digest)?;
+        let manifest: OciImageManifest = serde_json::from_slice(&manifest)?;
+        let config = read_verified(
+            &blob_path(&manifest.config.digest)?,
+            &manifest.config.digest,
+        )?;
+        let config = String::from_utf8(config).context("image config is not valid UTF-8")?;
+
+        let blobs = manifest
+            .layers
+            .iter()
+            .map(|l| Ok((l.digest.clone(), BlobLocation::File(blob_path(&l.digest)?))))
+            .collect::<Result<_>>()?;
+
+        let reference = local_reference(identity.as_deref(), &dir)?;
+
+        Ok(Self {
+            path: dir,
+            reference,
+            manifest,
+            manifest_digest: entry.digest,
+            config,
+            blobs,
+        })
+    }
+
+    fn open_docker_archive(path: PathBuf, name: Option<String>) -> Result<Self> {
+        let mut archive = tar::Archive::new(
+            std::fs::File::open(&path)
+                .with_context(|| format!("open docker archive {}", path.display()))?,
+        );
+
+        let mut entries = HashMap::new();
+        let mut archive_manifest = None;
+        for entry in archive.entries_with_seek()? {
+            let mut entry = entry?;
+            let entry_path = entry
+                .path()?
+                .to_string_lossy()
+                .trim_start_matches("./")
+                .to_string();
+            if entry_path == "manifest.json" {
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content)?;
+                archive_manifest = Some(content);
+            }
+            entries.insert(entry_path, (entry.raw_file_position(), entry.size()));
+        }
+
+        let archive_manifest = archive_manifest
+            .ok_or_else(|| anyhow!("no manifest.json in docker archive {}", path.display()))?;
+        let archive_manifest: Vec<DockerArchiveManifest> =
+            serde_json::from_slice(&archive_manifest)?;
+
+        let item = match &name {
+            Some(name) => {
+                let wanted = Reference::try_from(name.as_str())?.whole();
+                archive_manifest
+                    .iter()
+                    .find(|m| {
+                        m.repo_tags.iter().any(|tag| {
+                            Reference::try_from(tag.as_str()).map(|r| r.whole()).ok()
+                                == Some(wanted.clone())
+                        })
+                    })
+                    .ok_or_else(|| anyhow!("no image tagged {} in {}", name, path.display()))?
+            }
+            None => match &archive_manifest[..] {
+                [item] => item,
+                _ => bail!(
+                    "docker archive {} contains {} images, a reference must be given",
+                    path.display(),
+                    archive_manifest.len()
+                ),
+            },
+        };
+
+        let mut file = std::fs::File::open(&path)?;
+        let mut read_entry = |entry_path: &str| -> Result<Vec<u8>> {
+            let (offset, size) = entries
+                .get(entry_path)
+                .ok_or_else(|| anyhow!("{} not found in docker archive", entry_path))?;
+            file.seek(SeekFrom::Start(*offset))?;
+            let mut content = Vec::new();
+            (&mut file).take(*size).read_to_end(&mut content)?;
+            Ok(content)
+        };
+
+        let config = read_entry(&item.config)?;
+        let config_digest = format!("sha256:{:x}", Sha256::digest(&config));
+        let diff_ids: Vec<String> = serde_json::from_slice::<DockerImageConfig>(&config)?
+            .rootfs
+            .diff_ids;
+        if diff_ids.len() != item.layers.len() {
+            bail!("number of layers in docker archive mismatch with image config diff_ids");
+        }
+
+        // `docker save` does not record the digests of the layer tarballs,
+        // they are identified by their diff_ids instead. The diff_ids are
+        // checked against the unpacked layers later as usual.
+        let mut blobs = HashMap::new();
+        let mut layers = Vec::new();
+        for (layer, diff_id) in item.layers.iter().zip(diff_ids) {
+            let (offset, size) = *entries
+                .get(layer.trim_start_matches("./"))
+                .ok_or_else(|| anyhow!("layer {} not found in docker archive", layer))?;
+
+            let mut magic = [0u8; 4];
+            file.seek(SeekFrom::Start(offset))?;
+            let read = (&mut file).take(size).read(&mut magic)?;
+            let media_type = if read >= 4 && magic == ZSTD_MAGIC {
+                IMAGE_LAYER_ZSTD_MEDIA_TYPE
+            } else if read >= 2 && magic[..2] == GZIP_MAGIC {
+                IMAGE_LAYER_GZIP_MEDIA_TYPE
+            } else {
+                IMAGE_LAYER_MEDIA_TYPE
+            };
+
+            layers.push(OciDescriptor {
+                media_type: media_type.to_string(),
+                digest: diff_id.clone(),
+                size: size as i64,
+                ..Default::default()
+            });
+            blobs.insert(diff_id, BlobLocation::ArchiveEntry { offset, size });
+        }
+
+        let manifest = OciImageManifest {
+            media_type: Some(OCI_IMAGE_MEDIA_TYPE.to_string()),
+            config: OciDescriptor {
+                media_type: IMAGE_CONFIG_MEDIA_TYPE.to_string(),
+                digest: config_digest,
+                size: config.len() as i64,
+                ..Default::default()
+            },
+            layers,
+            ..Default::default()
+        };
+        let manifest_digest = format!(
+            "sha256:{:x}",
+            Sha256::digest(serde_json::to_vec(&manifest)?)
+        );
+        let config = String::from_utf8(config).context("image config is not valid UTF-8")?;
+
+        let identity = name.or_else(|| item.repo_tags.first().cloned());
+        let reference = local_reference(identity.as_deref(), &path)?;
+
+        Ok(Self {
+            path,
+            reference,
+            manifest,
+            manifest_digest,
+            config,
+            blobs,
+        })
+    }
+}
+
+/// An item of the `manifest.json` of a docker archive.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct DockerArchiveManifest {
+    config: String,
+    #[serde(default)]
+    repo_tags: Vec<String>,
+    layers: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct DockerImageConfig {
+    rootfs: DockerRootfs,
+}
+
+#[derive(Deserialize, Debug)]
+struct DockerRootfs {
+    diff_ids: Vec<String>,
+}
+
+/// Split `<path>[:<name>]`. As in containers-transports(5), the path
+/// cannot contain a `:`.
+fn split_path_and_name(s: &str) -> (&str, Option<String>) {
+    match s.split_once(':') {
+        Some((path, name)) if !name.is_empty() => (path, Some(name.to_string())),
+        Some((path, _)) => (path, None),
+        None => (s, None),
+    }
+}
+
+fn annotation<'a>(
+    annotations: &'a Option<HashMap<String, String>>,
+    key: &str,
+) -> Option<&'a String> {
+    annotations.as_ref().and_then(|a| a.get(key))
+}
+
+/// Read a blob and check it against the given digest.
+fn read_verified(path: &Path, digest: &str) -> Result<Vec<u8>> {
+    let content = std::fs::read(path).with_context(|| format!("read blob {}", digest))?;
+    let calculated = match digest.split_once(':') {
+        Some(("sha256", _)) => format!("sha256:{:x}", Sha256::digest(&content)),
+        Some(("sha512", _)) => format!("sha512:{:x}", sha2::Sha512::digest(&content)),
+        _ => bail!("unsupported digest {}", digest),
+    };
+    if calculated != digest {
+        bail!("blob digest {} mismatch with {}", calculated, digest);
+    }
+
+    Ok(content)
+}
+
+/// The reference a local image is verified as. Images without a name are
+/// named after their path under `localhost`.
+fn local_reference(identity: Option<&str>, path: &Path) -> Result<Reference> {
+    if let Some(identity) = identity {
+        return Reference::try_from(identity)
+            .map_err(|e| anyhow!("invalid local image name {}: {}", identity, e));
+    }
+
+    let repository: String = path
+        .to_string_lossy()
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '/' => c,
+            _ => '-',
+        })
+        .collect();
+    let repository = repository
+        .split('/')
+        .map(|c| c.trim_matches('-'))
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    Reference::try_from(format!("localhost/{}", repository))
+        .map_err(|e| anyhow!("cannot name local image {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const LAYER: &[u8] = b"layer";
+
+    fn tar_layer() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(LAYER.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "file", LAYER).unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    fn sha256(data: &[u8]) -> String {
+        format!("sha256:{:x}", Sha256::digest(data))
+    }
+
+    fn config(diff_id: &str) -> Vec<u8> {
+        format!(
+            r#"{{"architecture":"amd64","os":"linux","rootfs":{{"type":"layers","diff_ids":["{}"]}}}}"#,
+            diff_id
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_split_path_and_name() {
+        assert_eq!(split_path_and_name("/a/b"), ("/a/b", None));
+        assert_eq!(split_path_and_name("/a/b:"), ("/a/b", None));
+        assert_eq!(
+            split_path_and_name("/a/b:docker.io/library/busybox:latest"),
+            ("/a/b", Some("docker.io/library/busybox:latest".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_local_reference() {
+        let reference = local_reference(None, Path::new("/run/Images/busy_box")).unwrap();
+        assert_eq!(reference.whole(), "localhost/run/images/busy-box:latest");
+
+        let reference = local_reference(Some("busybox"), Path::new("/a")).unwrap();
+        assert_eq!(reference.whole(), "docker.io/library/busybox:latest");
+    }
+
+    #[tokio::test]
+    async fn test_oci_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = dir.path().join("blobs").join("sha256");
+        std::fs::create_dir_all(&blobs).unwrap();
+        let write_blob = |data: &[u8]| -> String {
+            let digest = sha256(data);
+            std::fs::write(blobs.join(digest.trim_start_matches("sha256:")), data).unwrap();
+            digest
+        };
+
+        let layer = tar_layer();
+        let layer_digest = write_blob(&layer);
+        let config = config(&layer_digest);
+        let config_digest = write_blob(&config);
+        let manifest = format!(
+            r#"{{"schemaVersion":2,"mediaType":"{}","config":{{"mediaType":"{}","digest":"{}","size":{}}},"layers":[{{"mediaType":"{}","digest":"{}","size":{}}}]}}"#,
+            OCI_IMAGE_MEDIA_TYPE,
+            IMAGE_CONFIG_MEDIA_TYPE,
+            config_digest,
+            config.len(),
+            IMAGE_LAYER_MEDIA_TYPE,
+            layer_digest,
+            layer.len()
+        );
+        let manifest_digest = write_blob(manifest.as_bytes());
+        let index = format!(
+            r#"{{"schemaVersion":2,"manifests":[{{"mediaType":"{}","digest":"{}","size":{},"annotations":{{"{}":"v1"}}}}]}}"#,
+            OCI_IMAGE_MEDIA_TYPE,
+            manifest_digest,
+            manifest.len(),
+            ANNOTATION_REF_NAME
+        );
+        std::fs::write(dir.path().join("index.json"), index).unwrap();
+
+        let url = format!("{}{}", OCI_LAYOUT_PREFIX, dir.path().display());
+        assert!(is_local_image(&url));
+        let source = LocalSource::from_url(&format!("{}:v1", url))
+            .await
+            .unwrap()
+            .unwrap();
+        let (m, digest, c) = source.manifest_and_config();
+        assert_eq!(digest, manifest_digest);
+        assert_eq!(c.as_bytes(), &config[..]);
+        assert_eq!(m.layers[0].digest, layer_digest);
+        assert!(source.reference().whole().starts_with("localhost/"));
+
+        let mut content = Vec::new();
+        source
+            .blob_reader(&layer_digest)
+            .await
+            .unwrap()
+            .read_to_end(&mut content)
+            .await
+            .unwrap();
+        assert_eq!(content, layer);
+
+        assert!(LocalSource::from_url(&format!("{}:v2", url)).await.is_err());
+
+        // tampered manifest
+        std::fs::write(
+            blobs.join(manifest_digest.trim_start_matches("sha256:")),
+            manifest.replace("schemaVersion\":2", "schemaVersion\":3"),
+        )
+        .unwrap();
+        assert!(LocalSource::from_url(&url).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_docker_archive() {
+        let layer = tar_layer();
+        let layer_diff_id = sha256(&layer);
+        let config = config(&layer_diff_id);
+        let config_name = format!("{}.json", sha256(&config).trim_start_matches("sha256:"));
+        let manifest = format!(
+            r#"[{{"Config":"{}","RepoTags":["busybox:latest"],"Layers":["abc/layer.tar"]}}]"#,
+            config_name
+        );
+
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in [
+            ("abc/layer.tar", &layer[..]),
+            (config_name.as_str(), &config[..]),
+            ("manifest.json", manifest.as_bytes()),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data).unwrap();
+        }
+        let mut archive = tempfile::NamedTempFile::new().unwrap();
+        archive.write_all(&builder.into_inner().unwrap()).unwrap();
+
+        let url = format!("{}{}", DOCKER_ARCHIVE_PREFIX, archive.path().display());
+        let source = LocalSource::from_url(&url).await.unwrap().unwrap();
+        let (m, _, c) = source.manifest_and_config();
+        assert_eq!(c.as_bytes(), &config[..]);
+        assert_eq!(m.config.digest, sha256(&config));
+        assert_eq!(m.layers[0].digest, layer_diff_id);
+        assert_eq!(m.layers[0].media_type, IMAGE_LAYER_MEDIA_TYPE);
+        assert_eq!(
+            source.reference().whole(),
+            "docker.io/library/busybox:latest"
+        );
+
+        let mut content = Vec::new();
+        source
+            .blob_reader(&layer_diff_id)
+            .await
+            .unwrap()
+            .read_to_end(&mut content)
+            .await
+            .unwrap();
+        assert_eq!(content, layer);
+
+        let source = LocalSource::from_url(&format!("{}:docker.io/library/busybox", url))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            source.reference().whole(),
+            "docker.io/library/busybox:latest"
+        );
+        assert!(LocalSource::from_url(&format!("{}:alpine", url))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Cache of the manifests and configs of the pulled references.
+//!
+//! When many pods are started from the same image at once, every pull
+//! resolves the same reference, authenticating to the registry and fetching
+//! the manifest and the config again. With the cache, the pulls of a
+//! reference wait for the first one to resolve it and reuse its manifest
+//! and config.
+//!
+//! Manifests of digest references never change, so they are cached until
+//! evicted. For tags, the manifest digest the registry serves the tag with
+//! (`Docker-Content-Digest`, which registries use as the `ETag` of the
+//! manifest) is recorded. Once the TTL passes, the next pull of the tag asks
+//! the registry for the digest with a `HEAD` request, and only fetches the
+//! manifest and config again if the digest changed.
+//!
+//! Entries are keyed by the reference, the platform and the credential of
+//! the pull, so that a pull never gets a manifest it could not fetch.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use log::warn;
+use oci_distribution::manifest::OciImageManifest;
+use oci_distribution::secrets::RegistryAuth;
+use tokio::sync::Mutex;
+
+use crate::config::ManifestCacheConfig;
+use crate::pull::{credential_key, PullClient};
+
+/// The manifest, the digest of the manifest and the config of an image, as
+/// returned by [`PullClient::pull_manifest`].
+type Resolved = (OciImageManifest, String, String);
+
+struct Entry {
+    resolved: Resolved,
+
+    /// Digest the registry serves the reference with, i.e. the one of the
+    /// image index for multi-platform images. Not set for digest references.
+    served_digest: Option<String>,
+
+    /// When the entry was fetched or last revalidated.
+    validated: Instant,
+}
+
+impl Entry {
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        self.served_digest.is_none() || self.validated.elapsed() < ttl
+    }
+}
+
+type Slot = Arc<Mutex<Option<Entry>>>;
+
+/// Cache of the manifests and configs of the pulled references.
+pub struct ManifestCache {
+    ttl: Duration,
+    max_entries: usize,
+    slots: std::sync::Mutex<HashMap<String, Slot>>,
+}
+
+impl ManifestCache {
+    pub fn new(config: &ManifestCacheConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.ttl_secs),
+            max_entries: config.max_entries,
+            slots: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// pull_manifest gets the manifest and config of the reference of
+    /// `client` from the cache, or pulls them with `client` and caches them.
+    /// `platform` is the one `client` selects manifests of multi-platform
+    /// images for.
+    ///
+    /// Concurrent pulls of the same reference wait for the first one, so the
+    /// registry is only asked once.
+    pub async fn pull_manifest(
+        &self,
+        client: &mut PullClient<'_>,
+        platform: Option<&str>,
+    ) -> Result<Resolved> {
+        if client.local_source.is_some() {
+            return client.pull_manifest().await;
+        }
+
+        let key = cache_key(&client.reference.whole(), platform, client.auth);
+        let slot = self.slot(&key);
+        let mut entry = slot.lock().await;
+
+        let is_tag = client.reference.digest().is_none();
+        if let Some(cached) = entry.as_mut() {
+            if cached.is_fresh(self.ttl) {
+                return Ok(cached.resolved.clone());
+            }
+
+            // A failed revalidation falls back to pulling the manifest, which
+            // reports the error if the registry is really unreachable.
+            match client.fetch_manifest_digest().await {
+                Ok(digest) if cached.served_digest.as_deref() == Some(digest.as_str()) => {
+                    cached.validated = Instant::now();
+                    return Ok(cached.resolved.clone());
+                }
+                Ok(_) => {}
+                Err(e) => warn!("failed to revalidate cached manifest of {key}: {e:?}"),
+            }
+        }
+
+        *entry = None;
+        let served_digest = if is_tag {
+            Some(client.fetch_manifest_digest().await?)
+        } else {
+            None
+        };
+        let resolved = client.pull_manifest().await?;
+        *entry = Some(Entry {
+            resolved: resolved.clone(),
+            served_digest,
+            validated: Instant::now(),
+        });
+
+        Ok(resolved)
+    }
+
+    /// Get the slot of `key`, evicting the least recently validated entry
+    /// not being resolved if the cache is full.
+    fn slot(&self, key: &str) -> Slot {
+        let mut slots = self.slots.lock().expect("poisoned manifest cache");
+        if let Some(slot) = slots.get(key) {
+            return slot.clone();
+        }
+
+        if slots.len() >= self.max_entries {
+            let oldest = slots
+                .iter()
+                .filter_map(|(key, slot)| {
+                    let validated = slot.try_lock().ok()?.as_ref().map(|e| e.validated);
+                    Some((key.clone(), validated))
+                })
+                .min_by_key(|(_, validated)| *validated)
+                .map(|(key, _)| key);
+            if let Some(oldest) = oldest {
+                slots.remove(&oldest);
+            }
+        }
+
+        slots.entry(key.to_string()).or_default().clone()
+    }
+}
+
+/// The credential is only kept as a hash in the key.
+fn cache_key(reference: &str, platform: Option<&str>, auth: &RegistryAuth) -> String {
+    format!(
+        "{reference}#{}#{}",
+        platform.unwrap_or_default(),
+        credential_key(auth)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oci_distribution::Reference;
+
+    fn entry(served_digest: Option<&str>) -> Entry {
+        Entry {
+            resolved: (
+                OciImageManifest::default(),
+                "sha256:1234".into(),
+                "{}".into(),
+            ),
+            served_digest: served_digest.map(String::from),
+            validated: Instant::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_manifest_cache() {
+        assert!(entry(None).is_fresh(Duration::ZERO));
+        assert!(entry(Some("sha256:abcd")).is_fresh(Duration::from_secs(60)));
+        assert!(!entry(Some("sha256:abcd")).is_fresh(Duration::ZERO));
+
+        let reference = "docker.io/library/busybox:latest";
+        let anonymous = cache_key(reference, None, &RegistryAuth::Anonymous);
+        let basic = cache_key(
+            reference,
+            None,
+            &RegistryAuth::Basic("user".into(), "password".into()),
+        );
+        assert_ne!(anonymous, basic);
+        assert!(!basic.contains("password"));
+        assert_ne!(
+            anonymous,
+            cache_key(reference, Some("linux/arm64"), &RegistryAuth::Anonymous)
+        );
+
+        // A cached manifest is returned without asking the registry.
+        let cache = ManifestCache::new(&ManifestCacheConfig {
+            ttl_secs: 60,
+            max_entries: 2,
+        });
+        *cache.slot(&basic).lock().await = Some(entry(None));
+        *cache.slot(&anonymous).lock().await = Some(entry(Some("sha256:abcd")));
+        let work_dir = tempfile::tempdir().unwrap();
+        let mut client = PullClient::new(
+            Reference::try_from(reference).unwrap(),
+            work_dir.path(),
+            &RegistryAuth::Anonymous,
+            1,
+        )
+        .unwrap();
+        let (_, digest, config) = cache.pull_manifest(&mut client, None).await.unwrap();
+        assert_eq!(digest, "sha256:1234");
+        assert_eq!(config, "{}");
+
+        // The least recently validated entry is evicted once the cache is full.
+        let _ = cache.slot("busybox@sha256:abcd#");
+        let slots = cache.slots.lock().unwrap();
+        assert_eq!(slots.len(), 2);
+        assert!(slots.contains_key(&anonymous));
+        assert!(!slots.contains_key(&basic));
+    }
+}
@@ -0,0 +1,276 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Measurement of the mounted container rootfs.
+//!
+//! Once a snapshotter has mounted the rootfs of a container, image-rs can
+//! compute a measurement of it and hand it to a registered
+//! [`MeasurementHook`], e.g. one forwarding it to the attestation-agent's
+//! `ExtendRuntimeMeasurement` API. This binds the rootfs that is actually
+//! used by the container to the attestation evidence.
+//!
+//! The measurement is either a merkle hash of the whole mounted tree, or,
+//! for snapshotters whose rootfs can't be walked from image-rs (eccfs), a
+//! hash over the digests of the per-layer images they built.
+//!
+//! The layer groups mounted apart from the rootfs, see
+//! [`crate::layer_groups`], are measured the same way, each on its own.
+
+use std::fs::{self, Metadata};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::digest::DIGEST_SHA256_PREFIX;
+
+/// Domain of the events produced by [`RootfsMeasurement::to_event`].
+pub const ROOTFS_EVENT_DOMAIN: &str = "image-rs rootfs";
+
+/// Domain of the events of the layer groups, see
+/// [`RootfsMeasurement::with_layer_group`].
+pub const LAYER_GROUP_EVENT_DOMAIN: &str = "image-rs layer group";
+
+/// Measurement of a mounted container rootfs.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct RootfsMeasurement {
+    /// The id of the image the rootfs is created from.
+    pub image_id: String,
+
+    /// The type of the snapshotter which mounted the rootfs.
+    pub snapshot: String,
+
+    /// The mount path of the rootfs.
+    pub mount_path: PathBuf,
+
+    /// The measurement of the rootfs, as `sha256:<hex>`.
+    pub digest: String,
+
+    /// The digests of the per-layer images, if the snapshotter provides
+    /// them. `digest` is computed over these in that case.
+    pub layers: Vec<String>,
+
+    /// The digest of the customization layer overlaid on top of the image,
+    /// if any, see [`crate::customization`].
+    pub customization: Option<String>,
+
+    /// The dir of the bundle the layer group is mounted at, if this is the
+    /// measurement of a layer group rather than of the rootfs.
+    pub layer_group: Option<String>,
+}
+
+impl RootfsMeasurement {
+    /// Measure the rootfs mounted at `mount_path`. If `layers` is given,
+    /// the measurement is computed over the layer digests, otherwise over
+    /// the whole tree under `mount_path`.
+    pub async fn new(
+        image_id: &str,
+        snapshot: &str,
+        mount_path: &Path,
+        layers: Option<Vec<String>>,
+    ) -> Result<Self> {
+        let (digest, layers) = match layers {
+            Some(layers) => (measure_layers(&layers), layers),
+            None => {
+                let path = mount_path.to_path_buf();
+                let digest = tokio::task::spawn_blocking(move || measure_tree(&path))
+                    .await
+                    .map_err(|e| anyhow!("rootfs measurement task failed: {:?}", e))??;
+                (digest, Vec::new())
+            }
+        };
+
+        Ok(Self {
+            image_id: image_id.to_string(),
+            snapshot: snapshot.to_string(),
+            mount_path: mount_path.to_path_buf(),
+            digest,
+            layers,
+            customization: None,
+            layer_group: None,
+        })
+    }
+
+    /// Name the customization layer overlaid on top of the image by its
+    /// `digest`. Its files are measured with the rest of the tree, or by
+    /// the layer digests of the snapshotter, and the event names it too.
+    pub fn with_customization(mut self, digest: &str) -> Self {
+        self.customization = Some(digest.to_string());
+        self
+    }
+
+    /// Make this the measurement of the layer group mounted at `mount_dir`
+    /// of the bundle. Its event is of its own domain and names the dir.
+    pub fn with_layer_group(mut self, mount_dir: &str) -> Self {
+        self.layer_group = Some(mount_dir.to_string());
+        self
+    }
+
+    /// Encode the measurement as an event to be extended into a runtime
+    /// measurement register.
+    pub fn to_event(&self) -> Vec<u8> {
+        let domain = match self.layer_group {
+            Some(_) => LAYER_GROUP_EVENT_DOMAIN,
+            None => ROOTFS_EVENT_DOMAIN,
+        };
+        let mut event = format!(
+            "{} {} {} {}",
+            domain, self.snapshot, self.image_id, self.digest
+        );
+        if let Some(customization) = &self.customization {
+            event.push_str(&format!(" {customization}"));
+        }
+        if let Some(mount_dir) = &self.layer_group {
+            event.push_str(&format!(" {mount_dir}"));
+        }
+        event.into_bytes()
+    }
+}
+
+/// Callback invoked with the measurement of every rootfs and layer group
+/// mounted by [`ImageClient`](crate::image::ImageClient). An error fails the
+/// pull.
+#[async_trait]
+pub trait MeasurementHook: Send + Sync {
+    async fn rootfs_mounted(&self, measurement: &RootfsMeasurement) -> Result<()>;
+}
+
+/// Measure the digests of the rootfs layers, in order.
+pub fn measure_layers(layers: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    for layer in layers {
+        hasher.update((layer.len() as u64).to_le_bytes());
+        hasher.update(layer.as_bytes());
+    }
+
+    format!("{}{:x}", DIGEST_SHA256_PREFIX, hasher.finalize())
+}
+
+/// Compute a merkle hash of the tree under `root`. File contents, symlink
+/// targets, modes and ownership are covered; timestamps are not, so that
+/// the same image always gives the same measurement.
+pub fn measure_tree(root: &Path) -> Result<String> {
+    let meta = fs::symlink_metadata(root)?;
+    let hash = hash_entry(root, &meta)?;
+
+    Ok(format!("{}{:x}", DIGEST_SHA256_PREFIX, hash))
+}
+
+fn hash_entry(path: &Path, meta: &Metadata) -> Result<sha2::digest::Output<Sha256>> {
+    let mut hasher = Sha256::new();
+    hasher.update(meta.mode().to_le_bytes());
+    hasher.update(meta.uid().to_le_bytes());
+    hasher.update(meta.gid().to_le_bytes());
+
+    let file_type = meta.file_type();
+    if file_type.is_dir() {
+        hasher.update(b"d");
+        let mut children = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.file_name()))
+            .collect::<io::Result<Vec<_>>>()?;
+        children.sort();
+        for name in children {
+            let child = path.join(&name);
+            let child_meta = fs::symlink_metadata(&child)?;
+            let name = name.as_bytes();
+            hasher.update((name.len() as u64).to_le_bytes());
+            hasher.update(name);
+            hasher.update(hash_entry(&child, &child_meta)?);
+        }
+    } else if file_type.is_symlink() {
+        hasher.update(b"l");
+        hasher.update(fs::read_link(path)?.as_os_str().as_bytes());
+    } else if file_type.is_file() {
+        hasher.update(b"f");
+        let mut content = Sha256::new();
+        io::copy(&mut fs::File::open(path)?, &mut content)?;
+        hasher.update(content.finalize());
+    } else {
+        // devices, fifos and sockets
+        hasher.update(b"o");
+        hasher.update(meta.rdev().to_le_bytes());
+    }
+
+    Ok(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure_tree() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path();
+        fs::create_dir(root.join("etc")).unwrap();
+        fs::write(root.join("etc/hostname"), b"guest").unwrap();
+        std::os::unix::fs::symlink("etc/hostname", root.join("hostname")).unwrap();
+
+        let digest = measure_tree(root).unwrap();
+        assert!(digest.starts_with(DIGEST_SHA256_PREFIX));
+
+        // timestamps are not measured
+        set_mtime(&root.join("etc/hostname"));
+        assert_eq!(measure_tree(root).unwrap(), digest);
+
+        fs::write(root.join("etc/hostname"), b"host").unwrap();
+        assert_ne!(measure_tree(root).unwrap(), digest);
+    }
+
+    #[test]
+    fn test_measure_layers() {
+        let a = measure_layers(&["sha256:aa".into(), "sha256:bb".into()]);
+        let b = measure_layers(&["sha256:bb".into(), "sha256:aa".into()]);
+        assert_ne!(a, b);
+        assert_eq!(a, measure_layers(&["sha256:aa".into(), "sha256:bb".into()]));
+    }
+
+    #[tokio::test]
+    async fn test_event() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let layers = vec!["sha256:aa".to_string()];
+        let measurement =
+            RootfsMeasurement::new("sha256:id", "eccfs", tempdir.path(), Some(layers))
+                .await
+                .unwrap();
+        let event = String::from_utf8(measurement.to_event()).unwrap();
+        assert_eq!(
+            event,
+            format!(
+                "{ROOTFS_EVENT_DOMAIN} eccfs sha256:id {}",
+                measurement.digest
+            )
+        );
+
+        let customized = measurement.with_customization("customization:sha256:bb");
+        assert_eq!(
+            String::from_utf8(customized.to_event()).unwrap(),
+            format!("{event} customization:sha256:bb")
+        );
+
+        let group = RootfsMeasurement::new("sha256:id", "eccfs", tempdir.path(), None)
+            .await
+            .unwrap()
+            .with_layer_group("weights");
+        assert_eq!(
+            String::from_utf8(group.to_event()).unwrap(),
+            format!(
+                "{LAYER_GROUP_EVENT_DOMAIN} eccfs sha256:id {} weights",
+                group.digest
+            )
+        );
+    }
+
+    fn set_mtime(path: &Path) {
+        let file = fs::OpenOptions::new().append(true).open(path).unwrap();
+        file.set_modified(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap();
+    }
+}
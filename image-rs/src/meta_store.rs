@@ -1,16 +1,21 @@
 use anyhow::{anyhow, Result};
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
 use std::convert::TryFrom;
 use std::fs::File;
 use std::path::Path;
 
 use crate::image::{ImageMeta, LayerMeta};
+use crate::layer_cache::LayerUsage;
+use crate::layer_groups::GroupMount;
+#[cfg(feature = "snapshot-eccfs")]
+use crate::snapshots::eccfs::SharedLayers;
+use crate::volume::ImageVolume;
 
 pub const METAFILE: &str = "meta_store.json";
 
 /// `image-rs` container metadata storage database.
-#[derive(Clone, Default, Deserialize, Debug)]
+#[derive(Clone, Default, Deserialize, Serialize, Debug)]
 pub struct MetaStore {
     // image_db holds map of image ID with image data.
     pub image_db: HashMap<String, ImageMeta>,
@@ -20,6 +25,43 @@ pub struct MetaStore {
 
     // snapshot_db holds map of snapshot with work dir index.
     pub snapshot_db: HashMap<String, usize>,
+
+    // customization_db holds map of bundle dir with the digest of the
+    // customization layer of its rootfs, whose meta is in layer_db.
+    #[serde(default)]
+    pub customization_db: HashMap<String, String>,
+
+    // bundle_db holds map of bundle dir with the ID of the image its
+    // rootfs is mounted from.
+    #[serde(default)]
+    pub bundle_db: HashMap<String, String>,
+
+    // group_db holds map of bundle dir with the layer groups mounted into
+    // it apart from its rootfs, see crate::layer_groups.
+    #[serde(default)]
+    pub group_db: HashMap<String, Vec<GroupMount>>,
+
+    // layer_usage holds map of layer digest with its size and uses, see
+    // crate::layer_cache.
+    #[serde(default)]
+    pub layer_usage: HashMap<String, LayerUsage>,
+
+    // pinned_images holds the references or IDs of the images whose
+    // layers are never evicted.
+    #[serde(default)]
+    pub pinned_images: BTreeSet<String>,
+
+    // volume_db holds map of the target dir of an image volume with the
+    // image mounted there, see crate::volume.
+    #[serde(default)]
+    pub volume_db: HashMap<String, ImageVolume>,
+
+    // shared_layer_db holds the eccfs layer roimages shared by the
+    // containers, with the containers using them, see
+    // crate::snapshots::eccfs.
+    #[cfg(feature = "snapshot-eccfs")]
+    #[serde(default)]
+    pub shared_layer_db: SharedLayers,
 }
 
 impl TryFrom<&Path> for MetaStore {
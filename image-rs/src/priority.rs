@@ -0,0 +1,112 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! CPU and IO priority of the background work of a pull.
+//!
+//! Unpacking layers and converting them into snapshots saturates the few
+//! vCPUs and the disk of a small TEE, which stalls the containers already
+//! running in it, e.g. while the image of a sidecar is pulled. The threads
+//! doing this work are given the nice value and the IO scheduling class of
+//! the [`BackgroundPriority`] configured. On Linux both are attributes of a
+//! thread rather than of the process, so the rest of the process is left
+//! alone.
+
+use anyhow::{bail, Result};
+use log::warn;
+use std::io;
+
+use crate::config::{BackgroundPriority, IoClass};
+
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+const IOPRIO_CLASS_BE: libc::c_int = 2;
+const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+
+fn ioprio(class: IoClass, level: u8) -> libc::c_int {
+    match class {
+        IoClass::BestEffort => (IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | libc::c_int::from(level),
+        IoClass::Idle => IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+    }
+}
+
+/// Apply `priority` to the calling thread.
+pub fn set_current_thread(priority: &BackgroundPriority) -> Result<()> {
+    if let Some(nice) = priority.nice {
+        // `who` 0 is the calling thread, not the whole process
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+            bail!(
+                "failed to set nice value {}: {}",
+                nice,
+                io::Error::last_os_error()
+            );
+        }
+    }
+
+    if let Some(class) = priority.io_class {
+        let ioprio = ioprio(class, priority.io_level);
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) } != 0 {
+            bail!(
+                "failed to set IO priority {:?}/{}: {}",
+                class,
+                priority.io_level,
+                io::Error::last_os_error()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Run `f` at `priority`. Without priority, `f` runs on the calling thread.
+/// Otherwise it runs on a thread of its own, so that the calling thread,
+/// e.g. a worker of the async runtime, keeps its priority.
+///
+/// Failing to apply the priority is not fatal, `f` then runs anyway.
+pub fn run_with<T: Send>(priority: Option<&BackgroundPriority>, f: impl FnOnce() -> T + Send) -> T {
+    let Some(priority) = priority else {
+        return f();
+    };
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            apply_or_warn(priority);
+            f()
+        })
+        .join()
+        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+    })
+}
+
+/// Apply `priority` to the calling thread, only warning if it fails.
+pub fn apply_or_warn(priority: &BackgroundPriority) {
+    if let Err(e) = set_current_thread(priority) {
+        warn!("background work runs at normal priority: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_with() {
+        let nice = || unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+        let io_priority = || unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, 0) };
+        let before = (nice(), io_priority());
+
+        let priority = BackgroundPriority {
+            nice: Some(before.0.max(10)),
+            io_class: Some(IoClass::Idle),
+            io_level: 0,
+        };
+        let (background_nice, background_ioprio) =
+            run_with(Some(&priority), || (nice(), io_priority()));
+        assert_eq!(background_nice, before.0.max(10));
+        assert_eq!(background_ioprio, ioprio(IoClass::Idle, 0) as libc::c_long);
+
+        // the calling thread keeps its priority
+        assert_eq!((nice(), io_priority()), before);
+        assert_eq!(run_with(None, nice), before.0);
+    }
+}
@@ -3,23 +3,222 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::{anyhow, bail, Result};
-use futures_util::stream::{self, StreamExt, TryStreamExt};
-use oci_distribution::manifest::{OciDescriptor, OciImageManifest};
+use futures_util::stream::{self, StreamExt};
+use log::warn;
+use oci_distribution::client::{ClientConfig, ClientProtocol};
+use oci_distribution::manifest::{
+    ImageIndexEntry, OciDescriptor, OciImageManifest, IMAGE_MANIFEST_LIST_MEDIA_TYPE,
+    IMAGE_MANIFEST_MEDIA_TYPE, OCI_IMAGE_INDEX_MEDIA_TYPE, OCI_IMAGE_MEDIA_TYPE,
+};
+use oci_distribution::RegistryOperation;
 use oci_distribution::{secrets::RegistryAuth, Client, Reference};
-use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
+use std::fmt;
+use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use tokio::io::AsyncRead;
 use tokio::sync::Mutex;
 use tokio_util::io::StreamReader;
+use tokio_util::sync::CancellationToken;
 
-use crate::decoder::Compression;
-use crate::decrypt::Decryptor;
+use crate::blob_cache::BlobCache;
+use crate::config::{
+    BackgroundPriority, DiskSpaceConfig, RegistryConfig, DEFAULT_MAX_LAYER_RETRIES,
+    DEFAULT_MAX_QUARANTINED_LAYERS, DEFAULT_QUARANTINE_DIR,
+};
+use crate::digest::{hasher_for, DigestHasher, HashingReader, DIGEST_SHA256_PREFIX};
+use crate::disk_space::{self, DiskReservations, InsufficientDiskSpace, Reservation};
+use crate::events::{PullEvent, PullEvents};
 use crate::image::LayerMeta;
+use crate::local::LocalSource;
 use crate::meta_store::MetaStore;
-use crate::stream::stream_processing;
+use crate::pull_budget::PullBudget;
+use crate::stream::{stream_processing, LayerDecoding, LayerStorageFull};
+use crate::trace;
+use crate::ERR_PULL_CANCELLED;
 
-const ERR_NO_DECRYPT_CFG: &str = "decrypt_config is None";
+pub(crate) const ERR_NO_DECRYPT_CFG: &str = "decrypt_config is None";
+
+/// Media types of the manifests and image indexes a reference may resolve to.
+const MANIFEST_MEDIA_TYPES: [&str; 4] = [
+    OCI_IMAGE_MEDIA_TYPE,
+    IMAGE_MANIFEST_MEDIA_TYPE,
+    OCI_IMAGE_INDEX_MEDIA_TYPE,
+    IMAGE_MANIFEST_LIST_MEDIA_TYPE,
+];
+
+/// Error returned when the unpacked layer digest does not match the
+/// `diff_id` recorded in the image config. This is the only layer error
+/// that triggers a re-download of the layer.
+#[derive(Debug)]
+pub struct LayerDigestMismatch {
+    /// The digest computed from the unpacked layer data.
+    pub uncompressed_digest: String,
+
+    /// The expected digest from the image config.
+    pub diff_id: String,
+}
+
+impl fmt::Display for LayerDigestMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unequal uncompressed digest {:?} config diff_id {:?}",
+            self.uncompressed_digest, self.diff_id
+        )
+    }
+}
+
+impl std::error::Error for LayerDigestMismatch {}
+
+/// Error returned when the digest of a layer blob does not match the one of
+/// its descriptor in the manifest. The layer is re-downloaded as on
+/// [`LayerDigestMismatch`].
+#[derive(Debug)]
+pub struct BlobDigestMismatch {
+    /// The digest computed from the layer blob.
+    pub digest: String,
+
+    /// The expected digest from the manifest.
+    pub expected: String,
+}
+
+impl fmt::Display for BlobDigestMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unequal blob digest {:?} manifest digest {:?}",
+            self.digest, self.expected
+        )
+    }
+}
+
+impl std::error::Error for BlobDigestMismatch {}
+
+/// Name of the dir the layer with `digest` is unpacked to.
+pub(crate) fn blob_id(digest: &str) -> String {
+    digest.replace(':', "_")
+}
+
+/// The [`credential_key`] of anonymous pulls.
+pub(crate) const ANONYMOUS_CREDENTIAL: &str = "anonymous";
+
+/// The credential `auth` stands for in a cache key, only kept as a hash.
+pub(crate) fn credential_key(auth: &RegistryAuth) -> String {
+    match auth {
+        RegistryAuth::Anonymous => ANONYMOUS_CREDENTIAL.to_string(),
+        RegistryAuth::Basic(username, password) => {
+            let hash = Sha256::digest(format!("{username}:{password}"));
+            format!("basic:{hash:x}")
+        }
+    }
+}
+
+/// Locks of the layers being pulled into a layer store, shared by the
+/// clients pulling into it, so that a layer of several images pulled at the
+/// same time is pulled once.
+#[derive(Default)]
+pub struct LayerLocks {
+    pulling: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl LayerLocks {
+    /// Run `f` holding the lock of the layer with `digest`.
+    pub async fn with_lock<T>(&self, digest: &str, f: impl Future<Output = T>) -> T {
+        let slot = self
+            .pulling
+            .lock()
+            .await
+            .entry(digest.to_string())
+            .or_default()
+            .clone();
+
+        let res = {
+            let _pulling = slot.lock().await;
+            f.await
+        };
+
+        // the map and this call hold the slot if nobody else waits for it
+        let mut pulling = self.pulling.lock().await;
+        if Arc::strong_count(&slot) == 2 {
+            pulling.remove(digest);
+        }
+
+        res
+    }
+}
+
+/// `oci-distribution` clients shared by the concurrent pulls of a client,
+/// so that the pulls of a repository with the same credential reuse the
+/// tokens and the connections of one another instead of authenticating
+/// again. A client is kept for every repository, credential and TLS policy
+/// pulled with.
+#[derive(Default)]
+pub struct RegistryClients {
+    clients: std::sync::Mutex<HashMap<String, Client>>,
+}
+
+/// Picks the manifest digest of a platform from the entries of an image index.
+pub type PlatformResolver = Box<dyn Fn(&[ImageIndexEntry]) -> Option<String> + Send + Sync>;
+
+/// Build a resolver selecting the given platform (`os/arch[/variant]`) from
+/// multi-platform images. Without a variant, the first entry matching `os`
+/// and `arch` is selected.
+pub fn platform_resolver(platform: &str) -> Result<PlatformResolver> {
+    let parts: Vec<&str> = platform.split('/').collect();
+    let (os, arch, variant) = match parts[..] {
+        [os, arch] if !os.is_empty() && !arch.is_empty() => (os, arch, None),
+        [os, arch, variant] if !os.is_empty() && !arch.is_empty() && !variant.is_empty() => {
+            (os, arch, Some(variant.to_string()))
+        }
+        _ => bail!(
+            "invalid platform {:?}, expected os/arch[/variant]",
+            platform
+        ),
+    };
+    let (os, arch) = (os.to_string(), arch.to_string());
+
+    Ok(Box::new(move |manifests: &[ImageIndexEntry]| {
+        manifests
+            .iter()
+            .find(|entry| {
+                entry.platform.as_ref().map_or(false, |p| {
+                    p.os == os
+                        && p.architecture == arch
+                        && (variant.is_none() || p.variant == variant)
+                })
+            })
+            .map(|entry| entry.digest.clone())
+    }))
+}
+
+/// How a pull reaches the registry of its image, for the requests made
+/// besides the pull, e.g. to find the signatures of the image, see
+/// [`PullClient::registry_access`].
+#[derive(Clone)]
+pub struct RegistryAccess {
+    /// Client set up with the TLS policy, the proxy and the client
+    /// certificate of the pull.
+    pub client: Client,
+
+    /// Reference the image is reached at, e.g. through the relay of
+    /// [`crate::mtls`].
+    pub reference: Reference,
+
+    /// Registries reached over plain http.
+    pub plain_http: Vec<String>,
+
+    /// Whether the certificates of the registry are not verified.
+    pub insecure_skip_tls_verify: bool,
+
+    /// Whether the registry is reached through a proxy.
+    pub proxied: bool,
+}
 
 /// The PullClient connects to remote OCI registry, pulls the container image,
 /// and save the image layers under data_dir and return the layer meta info.
@@ -38,6 +237,79 @@ pub struct PullClient<'a> {
 
     /// Max number of concurrent downloads.
     pub max_concurrent_download: usize,
+
+    /// Max number of re-downloads of a layer failing digest verification.
+    pub max_layer_retries: usize,
+
+    /// Dir where layers failing digest verification are recorded.
+    pub quarantine_dir: PathBuf,
+
+    /// Max number of layers recorded in `quarantine_dir`.
+    pub max_quarantined_layers: usize,
+
+    /// Dir layers not fitting into `data_dir` are unpacked to instead.
+    pub spill_dir: Option<PathBuf>,
+
+    /// Token checked by every download/decrypt/unpack phase. Once it is
+    /// cancelled, in-flight layers are aborted and their partially unpacked
+    /// data is removed.
+    pub cancel: CancellationToken,
+
+    /// Local image source. If set, the manifest, config and layers are read
+    /// from it instead of the registry of `reference`.
+    pub local_source: Option<LocalSource>,
+
+    /// Priority the layers are unpacked at, see [`crate::priority`].
+    pub background_priority: Option<BackgroundPriority>,
+
+    /// If set, every layer is checked to fit into its data dir before it is
+    /// unpacked, see [`crate::disk_space`].
+    pub disk_space: Option<DiskSpaceConfig>,
+
+    /// Disk space reserved by the pulls in flight.
+    pub disk_reservations: Arc<DiskReservations>,
+
+    /// Disk space reserved by this pull, given back as its layers are
+    /// stored.
+    pub disk_reservation: Option<Arc<Reservation>>,
+
+    /// Cache the layer blobs are pulled through, see [`crate::blob_cache`].
+    pub blob_cache: Option<Arc<BlobCache>>,
+
+    /// Download budget shared with the other pulls, see
+    /// [`crate::pull_budget`].
+    pub budget: Option<Arc<PullBudget>>,
+
+    /// Locks of the layers pulled into `data_dir`, shared with the other
+    /// pulls into it.
+    pub layer_locks: Arc<LayerLocks>,
+
+    /// The image as given to the pull, for its events.
+    pub image_url: String,
+
+    /// Events of the pull, see [`crate::events`].
+    pub events: Arc<PullEvents>,
+
+    /// Faults injected into the pull, see [`crate::fault`].
+    #[cfg(feature = "fault-injection")]
+    pub faults: Arc<crate::fault::FaultInjector>,
+
+    /// Platform selected by [`PullClient::set_platform`].
+    platform: Option<String>,
+
+    /// TLS policy set by [`PullClient::set_registry_config`].
+    registry_config: Option<RegistryConfig>,
+
+    /// Reference `client` pulls `reference` with, if it is pulled through
+    /// a relay presenting a client certificate, see [`crate::mtls`].
+    relayed_reference: Option<Reference>,
+
+    /// Proxy set by [`PullClient::set_proxy`].
+    #[cfg(feature = "vsock-proxy")]
+    proxy: Option<crate::proxy::RegistryProxy>,
+
+    /// Whether `client` holds a token to pull from the registry.
+    authenticated: bool,
 }
 
 impl<'a> PullClient<'a> {
@@ -50,6 +322,7 @@ impl<'a> PullClient<'a> {
         max_concurrent_download: usize,
     ) -> Result<PullClient<'a>> {
         let client = Client::default();
+        let image_url = reference.whole();
 
         Ok(PullClient {
             client,
@@ -57,15 +330,264 @@ impl<'a> PullClient<'a> {
             reference,
             data_dir: data_dir.to_path_buf(),
             max_concurrent_download,
+            max_layer_retries: DEFAULT_MAX_LAYER_RETRIES,
+            quarantine_dir: data_dir.join(DEFAULT_QUARANTINE_DIR),
+            max_quarantined_layers: DEFAULT_MAX_QUARANTINED_LAYERS,
+            spill_dir: None,
+            cancel: CancellationToken::new(),
+            local_source: None,
+            background_priority: None,
+            disk_space: None,
+            disk_reservations: Arc::default(),
+            disk_reservation: None,
+            blob_cache: None,
+            budget: None,
+            layer_locks: Arc::default(),
+            image_url,
+            events: Arc::default(),
+            #[cfg(feature = "fault-injection")]
+            faults: Arc::default(),
+            platform: None,
+            registry_config: None,
+            relayed_reference: None,
+            #[cfg(feature = "vsock-proxy")]
+            proxy: None,
+            authenticated: false,
+        })
+    }
+
+    /// Select the manifest of `platform` (`os/arch[/variant]`) from
+    /// multi-platform images instead of the one of the current platform.
+    pub fn set_platform(&mut self, platform: &str) -> Result<()> {
+        platform_resolver(platform)?;
+        self.platform = Some(platform.to_string());
+        self.rebuild_client()
+    }
+
+    /// Reach the registry of `reference` with the TLS policy `config`
+    /// instead of https with verified certificates.
+    pub fn set_registry_config(&mut self, config: &RegistryConfig) -> Result<()> {
+        let registry = self.reference.resolve_registry();
+        if config.plain_http && !RegistryConfig::is_loopback(registry) {
+            warn!("registry {registry} is reached over plain http");
+        }
+        if config.insecure_skip_tls_verify {
+            warn!("certificates of registry {registry} are not verified");
+        }
+        self.registry_config = Some(config.clone());
+        self.rebuild_client()
+    }
+
+    /// Authenticate to the registry with the client certificate `cert`,
+    /// on top of [`PullClient::auth`].
+    #[cfg(feature = "registry-mtls")]
+    pub fn set_client_cert(&mut self, cert: &crate::mtls::ClientCert) -> Result<()> {
+        self.relayed_reference = Some(crate::mtls::relayed_reference(&self.reference, cert)?);
+        self.rebuild_client()
+    }
+
+    /// Reach the registry through `proxy`, see [`crate::proxy`].
+    #[cfg(feature = "vsock-proxy")]
+    pub fn set_proxy(&mut self, proxy: &crate::proxy::RegistryProxy) -> Result<()> {
+        self.proxy = Some(proxy.clone());
+        self.rebuild_client()
+    }
+
+    /// How the registry of the image is reached, `None` for a local
+    /// source.
+    pub fn registry_access(&self) -> Option<RegistryAccess> {
+        if self.local_source.is_some() {
+            return None;
+        }
+
+        #[cfg(feature = "vsock-proxy")]
+        let proxied = self.proxy.is_some();
+        #[cfg(not(feature = "vsock-proxy"))]
+        let proxied = false;
+        Some(RegistryAccess {
+            client: self.client.clone(),
+            reference: self
+                .relayed_reference
+                .as_ref()
+                .unwrap_or(&self.reference)
+                .clone(),
+            plain_http: self.plain_http(),
+            insecure_skip_tls_verify: self
+                .registry_config
+                .as_ref()
+                .is_some_and(|config| config.insecure_skip_tls_verify),
+            proxied,
         })
     }
 
+    // the registries reached over plain http
+    fn plain_http(&self) -> Vec<String> {
+        let mut plain_http = Vec::new();
+        if self
+            .registry_config
+            .as_ref()
+            .is_some_and(|config| config.plain_http)
+        {
+            plain_http.push(self.reference.resolve_registry().to_string());
+        }
+        if let Some(relayed) = &self.relayed_reference {
+            // the relay is on loopback, it speaks TLS to the registry
+            plain_http.push(relayed.registry().to_string());
+        }
+        plain_http
+    }
+
+    fn rebuild_client(&mut self) -> Result<()> {
+        let mut config = ClientConfig::default();
+        if let Some(platform) = &self.platform {
+            config.platform_resolver = Some(platform_resolver(platform)?);
+        }
+        if let Some(registry_config) = &self.registry_config {
+            config.accept_invalid_certificates = registry_config.insecure_skip_tls_verify;
+        }
+        let plain_http = self.plain_http();
+        // only the hosts listed, never the token realms or other registries
+        if !plain_http.is_empty() {
+            config.protocol = ClientProtocol::HttpsExcept(plain_http);
+        }
+        #[cfg(feature = "vsock-proxy")]
+        if let Some(proxy) = &self.proxy {
+            config.https_proxy = Some(proxy.url.clone());
+            config.http_proxy = Some(proxy.url.clone());
+            config.no_proxy = Some(proxy.no_proxy.clone());
+        }
+        self.client = Client::new(config);
+        self.authenticated = false;
+
+        Ok(())
+    }
+
+    /// Pull with the client of `clients` set up as this one, see
+    /// [`RegistryClients`]. The setters build a client of their own, so
+    /// this is called after them.
+    pub fn share_client(&mut self, clients: &RegistryClients) {
+        if self.local_source.is_some() {
+            return;
+        }
+
+        let key = format!(
+            "{}/{}#{:?}#{:?}#{}#{}",
+            self.reference.resolve_registry(),
+            self.reference.repository(),
+            self.platform,
+            self.registry_config,
+            self.relayed_reference
+                .as_ref()
+                .map(Reference::whole)
+                .unwrap_or_default(),
+            credential_key(self.auth)
+        );
+        let mut clients = clients.clients.lock().expect("poisoned registry clients");
+        self.client = clients
+            .entry(key)
+            .or_insert_with(|| self.client.clone())
+            .clone();
+    }
+
     /// pull_manifest pulls an image manifest and config data.
     pub async fn pull_manifest(&mut self) -> Result<(OciImageManifest, String, String)> {
-        self.client
-            .pull_manifest_and_config(&self.reference, self.auth)
-            .await
-            .map_err(|e| anyhow!("failed to pull manifest {}", e.to_string()))
+        if let Some(source) = &self.local_source {
+            return Ok(source.manifest_and_config());
+        }
+
+        let reference = self.relayed_reference.as_ref().unwrap_or(&self.reference);
+        tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => bail!(ERR_PULL_CANCELLED),
+            res = self.client.pull_manifest_and_config(reference, self.auth) => {
+                let res = res.map_err(|e| anyhow!("failed to pull manifest {}", e.to_string()))?;
+                self.authenticated = true;
+                Ok(res)
+            }
+        }
+    }
+
+    /// fetch_manifest_digest gets the digest of the manifest the registry
+    /// serves the reference with, i.e. the one of the image index for
+    /// multi-platform images. The digest is computed from the manifest, not
+    /// taken from what the registry reports. Must be called after
+    /// [`PullClient::pull_manifest`], which authenticates to the registry.
+    pub async fn fetch_manifest_digest(&mut self) -> Result<String> {
+        if let Some(source) = &self.local_source {
+            return Ok(source.manifest_and_config().1);
+        }
+
+        let reference = self
+            .relayed_reference
+            .as_ref()
+            .unwrap_or(&self.reference)
+            .clone();
+        let manifest = self.pull_raw_manifest(&reference).await?;
+        Ok(format!(
+            "{}{:x}",
+            DIGEST_SHA256_PREFIX,
+            Sha256::digest(&manifest)
+        ))
+    }
+
+    /// verify_manifest_digest checks that the manifest `digest`, e.g. the
+    /// one returned by [`PullClient::pull_manifest`] as reported by the
+    /// registry, is the digest of the manifest the registry serves for it.
+    pub async fn verify_manifest_digest(&mut self, digest: &str) -> Result<()> {
+        if self.local_source.is_some() {
+            return Ok(());
+        }
+
+        let reference = self
+            .relayed_reference
+            .as_ref()
+            .unwrap_or(&self.reference)
+            .clone_with_digest(digest.to_string());
+        let manifest = self.pull_raw_manifest(&reference).await?;
+        let mut hasher = hasher_for(digest)?;
+        hasher.digest_update(&manifest);
+        let calculated = hasher.digest_finalize();
+        if calculated != digest {
+            bail!("registry served a manifest of digest {calculated} for {digest}");
+        }
+
+        Ok(())
+    }
+
+    // pull the manifest or image index `reference` resolves to as it is
+    // served, to compute its digest
+    async fn pull_raw_manifest(&mut self, reference: &Reference) -> Result<Vec<u8>> {
+        tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => bail!(ERR_PULL_CANCELLED),
+            res = self.client.pull_manifest_raw(reference, self.auth, &MANIFEST_MEDIA_TYPES) => {
+                let (manifest, _) =
+                    res.map_err(|e| anyhow!("failed to pull manifest {}", e.to_string()))?;
+                self.authenticated = true;
+                Ok(manifest)
+            }
+        }
+    }
+
+    /// authenticate gets a token to pull from the registry, if neither
+    /// [`PullClient::pull_manifest`] nor [`PullClient::fetch_manifest_digest`]
+    /// have been called, e.g. because the manifest was taken from a
+    /// [`ManifestCache`](crate::manifest_cache::ManifestCache).
+    pub async fn authenticate(&mut self) -> Result<()> {
+        if self.authenticated || self.local_source.is_some() {
+            return Ok(());
+        }
+
+        let reference = self.relayed_reference.as_ref().unwrap_or(&self.reference);
+        tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => bail!(ERR_PULL_CANCELLED),
+            res = self.client.auth(reference, self.auth, RegistryOperation::Pull) => {
+                res.map_err(|e| anyhow!("failed to authenticate to registry {}", e.to_string()))?;
+                self.authenticated = true;
+                Ok(())
+            }
+        }
     }
 
     /// pull_bootstrap pulls a nydus image's bootstrap layer.
@@ -87,6 +609,10 @@ impl<'a> PullClient<'a> {
 
     /// async_pull_layers pulls an image layers and do ondemand decrypt/decompress.
     /// It returns the layer metadata for layer db to track.
+    ///
+    /// Once a layer fails, no more layers are started, and the error is
+    /// returned when the ones in flight are over, so that none is left
+    /// partially unpacked.
     pub async fn async_pull_layers(
         &self,
         layer_descs: Vec<OciDescriptor>,
@@ -95,112 +621,406 @@ impl<'a> PullClient<'a> {
         meta_store: Arc<Mutex<MetaStore>>,
     ) -> Result<Vec<LayerMeta>> {
         let meta_store = &meta_store;
-        let layer_metas: Vec<(usize, LayerMeta)> = stream::iter(layer_descs)
+        let failed = &AtomicBool::new(false);
+        let mut pulls = stream::iter(layer_descs)
             .enumerate()
             .map(|(i, layer)| async move {
-                let layer_stream = self
-                    .client
-                    .pull_blob_stream(&self.reference, &layer.digest)
-                    .await
-                    .map_err(|e| anyhow!("failed to async pull blob stream {}", e.to_string()))?;
-                let layer_reader = StreamReader::new(layer_stream);
-                self.async_handle_layer(
-                    layer,
-                    diff_ids[i].clone(),
-                    decrypt_config,
-                    layer_reader,
-                    meta_store.clone(),
+                if failed.load(Ordering::SeqCst) {
+                    return Ok(None);
+                }
+                let digest = layer.digest.clone();
+                let pull = self.pull_layer(i, layer, diff_ids, decrypt_config, meta_store);
+                trace::in_span_with(
+                    "pull_layer",
+                    [("layer.digest", digest.clone())],
+                    self.layer_locks.with_lock(&digest, pull),
                 )
                 .await
-                .map_err(|e| anyhow!("failed to handle layer: {:?}", e))
-                .map(|layer_meta| (i, layer_meta))
+                .map(Some)
             })
-            .buffer_unordered(self.max_concurrent_download)
-            .try_collect()
-            .await?;
+            .buffer_unordered(self.max_concurrent_download);
+
+        let mut layer_metas = Vec::new();
+        let mut failure = None;
+        while let Some(res) = pulls.next().await {
+            match res {
+                Ok(Some(layer_meta)) => layer_metas.push(layer_meta),
+                Ok(None) => {}
+                Err(e) if failure.is_none() => {
+                    failed.store(true, Ordering::SeqCst);
+                    failure = Some(e);
+                }
+                Err(e) => warn!("layer pull failed after another one: {e:#}"),
+            }
+        }
+        if let Some(e) = failure {
+            return Err(e);
+        }
+
         let meta_map: BTreeMap<usize, _> = layer_metas.into_iter().collect();
         let sorted_layer_metas = meta_map.into_values().collect();
         Ok(sorted_layer_metas)
     }
 
+    // pull the `i`th layer, unless a concurrent pull did, and record it in
+    // the layer db
+    async fn pull_layer(
+        &self,
+        i: usize,
+        layer: OciDescriptor,
+        diff_ids: &[String],
+        decrypt_config: &Option<&str>,
+        meta_store: &Arc<Mutex<MetaStore>>,
+    ) -> Result<(usize, LayerMeta)> {
+        if let Some(layer_meta) = meta_store.lock().await.layer_db.get(&layer.digest).cloned() {
+            return Ok((i, layer_meta));
+        }
+
+        let _download = match (&self.budget, &self.local_source) {
+            (Some(budget), None) => Some(tokio::select! {
+                biased;
+                _ = self.cancel.cancelled() => bail!(ERR_PULL_CANCELLED),
+                permit = budget.acquire() => permit,
+            }),
+            _ => None,
+        };
+
+        let mut attempt = 0;
+        let mut spilled = false;
+        loop {
+            let data_dir = match (&self.spill_dir, spilled) {
+                (Some(spill_dir), true) => spill_dir,
+                _ => &self.data_dir,
+            };
+            let layer_reader = match self.layer_reader(&layer).await {
+                Err(e) if e.is::<BlobDigestMismatch>() && attempt < self.max_layer_retries => {
+                    attempt += 1;
+                    warn!(
+                        "layer {} failed verification, retrying ({}/{}): {}",
+                        layer.digest, attempt, self.max_layer_retries, e
+                    );
+                    continue;
+                }
+                res => res?,
+            };
+            let diff_id = diff_ids[i].clone();
+            #[cfg(feature = "fault-injection")]
+            let diff_id = self.faults.layer_diff_id(diff_id);
+            match self
+                .async_handle_layer(
+                    layer.clone(),
+                    diff_id,
+                    decrypt_config,
+                    layer_reader,
+                    data_dir,
+                    meta_store.clone(),
+                )
+                .await
+            {
+                Ok(layer_meta) => {
+                    meta_store
+                        .lock()
+                        .await
+                        .layer_db
+                        .insert(layer_meta.compressed_digest.clone(), layer_meta.clone());
+                    self.events.emit(PullEvent::LayerVerified {
+                        image_url: self.image_url.clone(),
+                        digest: layer.digest.clone(),
+                    });
+                    return Ok((i, layer_meta));
+                }
+                Err(e)
+                    if (e.is::<LayerStorageFull>() || e.is::<InsufficientDiskSpace>())
+                        && !spilled
+                        && self.spill_dir.is_some() =>
+                {
+                    spilled = true;
+                    warn!(
+                        "layer {} does not fit into {}, unpacking it to the spill dir",
+                        layer.digest,
+                        self.data_dir.display()
+                    );
+                }
+                Err(e)
+                    if (e.is::<LayerDigestMismatch>() || e.is::<BlobDigestMismatch>())
+                        && attempt < self.max_layer_retries =>
+                {
+                    attempt += 1;
+                    warn!(
+                        "layer {} failed verification, retrying ({}/{}): {}",
+                        layer.digest, attempt, self.max_layer_retries, e
+                    );
+                    if let Some(cache) = &self.blob_cache {
+                        cache.remove(&self.blob_scope(), &layer.digest).await;
+                    }
+                }
+                Err(e) if e.is::<InsufficientDiskSpace>() => return Err(e),
+                Err(e) => return Err(e.context("failed to handle layer")),
+            }
+        }
+    }
+
+    /// The scope the blobs of the pull are cached in, see
+    /// [`crate::blob_cache`]: the repository of the image and the
+    /// credential of the pull, hashed.
+    pub fn blob_scope(&self) -> String {
+        let scope = format!(
+            "{}/{}#{}",
+            self.reference.resolve_registry(),
+            self.reference.repository(),
+            credential_key(self.auth)
+        );
+        format!("{:x}", Sha256::digest(scope))
+    }
+
+    /// Open the layer blob, either from the registry or the local source.
+    /// Blobs of the registry are read through the blob cache, if any.
+    pub(crate) async fn layer_reader(
+        &self,
+        layer: &OciDescriptor,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
+        if let Some(source) = &self.local_source {
+            return Ok(Box::new(source.blob_reader(&layer.digest).await?));
+        }
+
+        match &self.blob_cache {
+            Some(cache) if cache.caches(layer) => {
+                let blob = tokio::select! {
+                    biased;
+                    _ = self.cancel.cancelled() => bail!(ERR_PULL_CANCELLED),
+                    res = cache.get_or_fetch(
+                        &self.blob_scope(),
+                        layer,
+                        || self.registry_blob_reader(layer),
+                    ) => res?,
+                };
+                Ok(Box::new(blob))
+            }
+            _ => self.registry_blob_reader(layer).await,
+        }
+    }
+
+    async fn registry_blob_reader(
+        &self,
+        layer: &OciDescriptor,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
+        #[cfg(feature = "fault-injection")]
+        self.faults.blob_request(&layer.digest)?;
+
+        let reference = self.relayed_reference.as_ref().unwrap_or(&self.reference);
+        let layer_stream = tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => bail!(ERR_PULL_CANCELLED),
+            res = self.client.pull_blob_stream(reference, &layer.digest) => {
+                res.map_err(|e| anyhow!("failed to async pull blob stream {}", e.to_string()))?
+            }
+        };
+        let reader: Box<dyn AsyncRead + Unpin + Send> = Box::new(StreamReader::new(layer_stream));
+        match &self.budget {
+            Some(budget) => Ok(budget.throttle(reader)),
+            None => Ok(reader),
+        }
+    }
+
     async fn async_handle_layer(
         &self,
         layer: OciDescriptor,
         diff_id: String,
         decrypt_config: &Option<&str>,
         layer_reader: (impl tokio::io::AsyncRead + Unpin + Send),
+        data_dir: &Path,
         ms: Arc<Mutex<MetaStore>>,
     ) -> Result<LayerMeta> {
-        let layer_db = &ms.lock().await.layer_db;
-        if let Some(layer_meta) = layer_db.get(&layer.digest) {
-            return Ok(layer_meta.clone());
+        if let Some(layer_meta) = ms.lock().await.layer_db.get(&layer.digest).cloned() {
+            return Ok(layer_meta);
         }
 
-        let blob_id = layer.digest.to_string().replace(':', "_");
-        let destination = self.data_dir.join(blob_id);
+        let destination = data_dir.join(blob_id(&layer.digest));
         let mut layer_meta = LayerMeta {
             compressed_digest: layer.digest.clone(),
             store_path: destination.display().to_string(),
             ..Default::default()
         };
 
-        let decryptor = Decryptor::from_media_type(&layer.media_type);
-        if decryptor.is_encrypted() {
-            if let Some(dc) = decrypt_config {
-                let decrypt_key = decryptor
-                    .get_decrypt_key(&layer, dc)
-                    .map_err(|e| anyhow!("failed to get decrypt key {}", e.to_string()))?;
-                let plaintext_layer = decryptor
-                    .async_get_plaintext_layer(layer_reader, &layer, &decrypt_key)
-                    .map_err(|e| anyhow!("failed to async_get_plaintext_layer: {:?}", e))?;
-                layer_meta.uncompressed_digest = self
-                    .async_decompress_unpack_layer(
-                        plaintext_layer,
-                        &diff_id,
-                        &decryptor.media_type,
-                        &destination,
-                    )
-                    .await?;
-                layer_meta.encrypted = true;
-            } else {
-                bail!(ERR_NO_DECRYPT_CFG);
+        if self.cancel.is_cancelled() {
+            bail!(ERR_PULL_CANCELLED);
+        }
+
+        if let Some(disk_space) = &self.disk_space {
+            disk_space::check_layer(
+                data_dir,
+                &layer,
+                disk_space,
+                &self.disk_reservations,
+                self.disk_reservation.as_deref(),
+            )?;
+        }
+
+        // The blob is hashed, decrypted, decompressed, hashed again and
+        // unpacked in one pass while it is downloaded.
+        let decoding = LayerDecoding::new(&layer, decrypt_config)?;
+        let mut blob = HashingReader::new(layer_reader, hasher_for(&layer.digest)?);
+        let tar_reader = decoding.tar_reader(&mut blob)?;
+        #[cfg(feature = "fault-injection")]
+        let tar_reader = self.faults.delay_decrypt(tar_reader);
+        layer_meta.uncompressed_digest = match decoding.media_type() {
+            #[cfg(feature = "snapshot-wasm")]
+            media_type if crate::snapshots::wasm::is_wasm_media_type(media_type) => {
+                crate::snapshots::wasm::store_module(
+                    tar_reader,
+                    &diff_id,
+                    &destination,
+                    &self.cancel,
+                )
+                .await?
             }
-        } else {
-            layer_meta.uncompressed_digest = self
-                .async_decompress_unpack_layer(
-                    layer_reader,
+            _ => {
+                stream_processing(
+                    tar_reader,
                     &diff_id,
-                    &layer.media_type,
                     &destination,
+                    &self.cancel,
+                    self.background_priority.as_ref(),
                 )
-                .await?;
+                .await?
+            }
+        };
+        layer_meta.encrypted = decoding.is_encrypted();
+
+        // Hash the end of the blob the decompressor left unread, e.g.
+        // padding after the compressed stream.
+        tokio::io::copy(&mut blob, &mut tokio::io::sink())
+            .await
+            .map_err(|e| anyhow!("failed to read the end of layer {}: {}", layer.digest, e))?;
+        let compressed_digest = blob.digest_finalize();
+
+        // uncompressed digest should equal to the diff_ids in image_config,
+        // and the blob digest to the one of its descriptor in the manifest.
+        let mismatch = if layer_meta.uncompressed_digest != diff_id {
+            Some(anyhow::Error::new(LayerDigestMismatch {
+                uncompressed_digest: layer_meta.uncompressed_digest.clone(),
+                diff_id,
+            }))
+        } else if compressed_digest != layer.digest {
+            Some(anyhow::Error::new(BlobDigestMismatch {
+                digest: compressed_digest,
+                expected: layer.digest.clone(),
+            }))
+        } else {
+            None
+        };
+        if let Some(e) = mismatch {
+            self.quarantine_layer(&destination, &layer, layer_meta.encrypted, &e)
+                .await;
+            return Err(e);
         }
 
-        // uncompressed digest should equal to the diff_ids in image_config.
-        if layer_meta.uncompressed_digest != diff_id {
-            bail!(
-                "unequal uncompressed digest {:?} config diff_id {:?}",
-                layer_meta.uncompressed_digest,
-                diff_id
-            );
+        // the layer takes its space from the free one now
+        if let (Some(disk_space), Some(reservation)) = (&self.disk_space, &self.disk_reservation) {
+            let unpacked = disk_space::layer_unpacked_size(&layer, disk_space);
+            reservation.release(data_dir, unpacked)?;
         }
 
         Ok(layer_meta)
     }
 
-    async fn async_decompress_unpack_layer(
+    /// Remove a layer that failed verification from the layer store, and
+    /// record it in the quarantine dir for diagnostics. The unpacked layer,
+    /// plaintext if it was encrypted, is never kept.
+    async fn quarantine_layer(
         &self,
-        input_reader: (impl tokio::io::AsyncRead + Unpin + Send),
-        diff_id: &str,
-        media_type: &str,
         destination: &Path,
-    ) -> Result<String> {
-        let decoder = Compression::try_from(media_type)?;
-        let async_decoder = decoder.async_decompress(input_reader);
-        stream_processing(async_decoder, diff_id, destination).await
+        layer: &OciDescriptor,
+        encrypted: bool,
+        error: &anyhow::Error,
+    ) {
+        if let Err(e) = tokio::fs::remove_dir_all(destination).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!(
+                    "failed to remove corrupt layer {}: {}",
+                    destination.display(),
+                    e
+                );
+            }
+        }
+
+        let quarantined_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let record = QuarantinedLayer {
+            digest: layer.digest.clone(),
+            media_type: layer.media_type.clone(),
+            size: layer.size,
+            encrypted,
+            error: error.to_string(),
+            quarantined_at: quarantined_at.as_secs(),
+        };
+        let target = self.quarantine_dir.join(format!(
+            "{}.{}.json",
+            blob_id(&layer.digest),
+            quarantined_at.as_nanos()
+        ));
+        let recorded = serde_json::to_vec_pretty(&record)
+            .map_err(std::io::Error::from)
+            .and_then(|record| {
+                std::fs::create_dir_all(&self.quarantine_dir)?;
+                std::fs::write(&target, record)?;
+                prune_quarantine(&self.quarantine_dir, self.max_quarantined_layers)
+            });
+        match recorded {
+            Ok(()) => warn!("corrupt layer recorded in {}", target.display()),
+            Err(e) => warn!("failed to record corrupt layer {}: {}", layer.digest, e),
+        }
     }
 }
 
+/// Record of a layer that failed verification, kept in the quarantine dir.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct QuarantinedLayer {
+    /// Digest of the layer blob in the manifest.
+    pub digest: String,
+
+    pub media_type: String,
+
+    /// Size of the layer blob in the manifest.
+    pub size: i64,
+
+    /// Whether the layer blob was encrypted.
+    pub encrypted: bool,
+
+    /// The verification error.
+    pub error: String,
+
+    /// Seconds since the epoch.
+    pub quarantined_at: u64,
+}
+
+// keep the `max` newest records in the quarantine `dir`, and scrub anything
+// else, e.g. the unpacked layers quarantined by former versions
+fn prune_quarantine(dir: &Path, max: usize) -> std::io::Result<()> {
+    let mut records = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            std::fs::remove_dir_all(&path)?;
+        } else if file_type.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+            records.push((entry.metadata()?.modified()?, path));
+        } else {
+            std::fs::remove_file(&path)?;
+        }
+    }
+
+    records.sort();
+    let excess = records.len().saturating_sub(max);
+    for (_, path) in records.into_iter().take(excess) {
+        std::fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -210,11 +1030,93 @@ mod tests {
     use flate2::write::GzEncoder;
     use oci_distribution::manifest::IMAGE_CONFIG_MEDIA_TYPE;
     use oci_spec::image::{ImageConfiguration, MediaType};
+    use sha2::Digest;
     use std::io::Write;
     use tempfile;
 
     use test_utils::{assert_result, assert_retry};
 
+    #[test]
+    fn test_platform_resolver() {
+        let manifests: Vec<ImageIndexEntry> = serde_json::from_str(
+            r#"[
+                {"mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": "sha256:amd64", "size": 1,
+                 "platform": {"architecture": "amd64", "os": "linux"}},
+                {"mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": "sha256:armv7", "size": 1,
+                 "platform": {"architecture": "arm", "os": "linux", "variant": "v7"}},
+                {"mediaType": "application/vnd.oci.image.manifest.v1+json", "digest": "sha256:arm64", "size": 1,
+                 "platform": {"architecture": "arm64", "os": "linux", "variant": "v8"}}
+            ]"#,
+        )
+        .unwrap();
+
+        let resolve = |platform: &str| platform_resolver(platform).unwrap()(&manifests);
+        assert_eq!(resolve("linux/amd64"), Some("sha256:amd64".into()));
+        assert_eq!(resolve("linux/arm64"), Some("sha256:arm64".into()));
+        assert_eq!(resolve("linux/arm/v7"), Some("sha256:armv7".into()));
+        assert_eq!(resolve("linux/arm/v6"), None);
+        assert_eq!(resolve("windows/amd64"), None);
+
+        assert!(platform_resolver("linux").is_err());
+        assert!(platform_resolver("linux/").is_err());
+        assert!(platform_resolver("linux/arm/v7/x").is_err());
+    }
+
+    #[test]
+    fn test_registry_access() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let reference = Reference::try_from("mirror.local:5000/library/busybox:latest").unwrap();
+        let mut client = PullClient::new(
+            reference.clone(),
+            tempdir.path(),
+            &RegistryAuth::Anonymous,
+            DEFAULT_MAX_CONCURRENT_DOWNLOAD,
+        )
+        .unwrap();
+
+        let access = client.registry_access().unwrap();
+        assert_eq!(access.reference, reference);
+        assert!(access.plain_http.is_empty());
+        assert!(!access.insecure_skip_tls_verify);
+        assert!(!access.proxied);
+
+        client
+            .set_registry_config(&RegistryConfig {
+                insecure_skip_tls_verify: true,
+                plain_http: true,
+            })
+            .unwrap();
+        let access = client.registry_access().unwrap();
+        assert_eq!(access.plain_http, ["mirror.local:5000"]);
+        assert!(access.insecure_skip_tls_verify);
+    }
+
+    #[test]
+    fn test_blob_scope() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let anonymous = RegistryAuth::Anonymous;
+        let basic = RegistryAuth::Basic("user".into(), "password".into());
+        let scope = |image: &str, auth| {
+            let reference = Reference::try_from(image).unwrap();
+            PullClient::new(reference, tempdir.path(), auth, 1)
+                .unwrap()
+                .blob_scope()
+        };
+
+        assert_eq!(
+            scope("quay.io/app/a:v1", &anonymous),
+            scope("quay.io/app/a:v2", &anonymous)
+        );
+        assert_ne!(
+            scope("quay.io/app/a:v1", &anonymous),
+            scope("quay.io/app/b:v1", &anonymous)
+        );
+        assert_ne!(
+            scope("quay.io/app/a:v1", &anonymous),
+            scope("quay.io/app/a:v1", &basic)
+        );
+    }
+
     #[ignore]
     #[tokio::test]
     async fn image_layer_order() {
@@ -296,6 +1198,30 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_manifest_digests() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let image = Reference::try_from(
+            "ghcr.io/confidential-containers/test-container-image-rs:busybox-gzip",
+        )
+        .unwrap();
+        let mut client = PullClient::new(
+            image,
+            tempdir.path(),
+            &RegistryAuth::Anonymous,
+            DEFAULT_MAX_CONCURRENT_DOWNLOAD,
+        )
+        .unwrap();
+        let (_, image_digest, _) = client.pull_manifest().await.unwrap();
+
+        client.verify_manifest_digest(&image_digest).await.unwrap();
+        let other = format!("{DIGEST_SHA256_PREFIX}{}", "0".repeat(64));
+        assert!(client.verify_manifest_digest(&other).await.is_err());
+
+        let served = client.fetch_manifest_digest().await.unwrap();
+        assert!(served.starts_with(DIGEST_SHA256_PREFIX));
+    }
+
     #[cfg(all(feature = "encryption", feature = "keywrap-jwe"))]
     #[tokio::test]
     async fn test_async_pull_client_encrypted() {
@@ -356,6 +1282,7 @@ mod tests {
 
         let uncompressed_layer = OciDescriptor {
             media_type: MediaType::ImageLayer.to_string(),
+            digest: "sha256:0000".to_string(),
             ..Default::default()
         };
 
@@ -367,6 +1294,7 @@ mod tests {
 
         let compressed_layer = OciDescriptor {
             media_type: MediaType::ImageLayerGzip.to_string(),
+            digest: "sha256:0000".to_string(),
             ..Default::default()
         };
 
@@ -452,6 +1380,7 @@ mod tests {
                     d.diff_id.to_string(),
                     &d.decrypt_config,
                     d.layer_data.clone().as_slice(),
+                    &client.data_dir,
                     ms.clone(),
                 )
                 .await;
@@ -462,6 +1391,198 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_async_handle_layer_cancelled() {
+        let oci_image = Reference::try_from(
+            "ghcr.io/confidential-containers/test-container-image-rs:busybox-gzip",
+        )
+        .expect("create reference failed");
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let client = PullClient::new(
+            oci_image,
+            tempdir.path(),
+            &RegistryAuth::Anonymous,
+            DEFAULT_MAX_CONCURRENT_DOWNLOAD,
+        )
+        .unwrap();
+        client.cancel.cancel();
+
+        let layer = OciDescriptor {
+            media_type: MediaType::ImageLayer.to_string(),
+            digest: "sha256:0000".to_string(),
+            ..Default::default()
+        };
+
+        let result = client
+            .async_handle_layer(
+                layer,
+                "sha256:0000".to_string(),
+                &None,
+                Vec::<u8>::new().as_slice(),
+                &client.data_dir,
+                Arc::new(Mutex::new(MetaStore::default())),
+            )
+            .await;
+
+        let expected: Result<LayerMeta> = Err(anyhow!(ERR_PULL_CANCELLED));
+        assert_result!(expected, result, "cancelled pull");
+        assert!(!tempdir.path().join("sha256_0000").exists());
+    }
+
+    #[tokio::test]
+    async fn test_async_handle_layer_quarantine() {
+        let oci_image = Reference::try_from(
+            "ghcr.io/confidential-containers/test-container-image-rs:busybox-gzip",
+        )
+        .expect("create reference failed");
+
+        let mut ar = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_cksum();
+        ar.append_data(&mut header, "file.txt", b"data".as_slice())
+            .unwrap();
+        let layer_data = ar.into_inner().unwrap();
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let client = PullClient::new(
+            oci_image,
+            tempdir.path(),
+            &RegistryAuth::Anonymous,
+            DEFAULT_MAX_CONCURRENT_DOWNLOAD,
+        )
+        .unwrap();
+
+        let layer = OciDescriptor {
+            media_type: MediaType::ImageLayer.to_string(),
+            digest: "sha256:0000".to_string(),
+            ..Default::default()
+        };
+        let bad_diff_id = format!("sha256:{}", "0".repeat(64));
+
+        let err = client
+            .async_handle_layer(
+                layer,
+                bad_diff_id,
+                &None,
+                layer_data.as_slice(),
+                &client.data_dir,
+                Arc::new(Mutex::new(MetaStore::default())),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.is::<LayerDigestMismatch>());
+
+        // The corrupt layer is only recorded in the quarantine dir, and
+        // removed from the store.
+        assert!(!tempdir.path().join("sha256_0000").exists());
+        let quarantined: Vec<_> = std::fs::read_dir(&client.quarantine_dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        assert_eq!(quarantined.len(), 1);
+        assert!(quarantined[0].is_file());
+        let record: QuarantinedLayer =
+            serde_json::from_slice(&std::fs::read(&quarantined[0]).unwrap()).unwrap();
+        assert_eq!(record.digest, "sha256:0000");
+        assert!(!record.encrypted);
+    }
+
+    #[test]
+    fn test_prune_quarantine() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path();
+        for i in 0..4 {
+            std::fs::write(dir.join(format!("sha256_{i}.{i}.json")), b"{}").unwrap();
+            // modification times are ordered
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        std::fs::create_dir(dir.join("sha256_0.0")).unwrap();
+        std::fs::write(dir.join("sha256_0.0").join("file.txt"), b"plaintext").unwrap();
+
+        prune_quarantine(dir, 2).unwrap();
+        let mut left: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["sha256_2.2.json", "sha256_3.3.json"]);
+    }
+
+    #[tokio::test]
+    async fn test_async_handle_layer_blob_digest() {
+        let oci_image = Reference::try_from(
+            "ghcr.io/confidential-containers/test-container-image-rs:busybox-gzip",
+        )
+        .expect("create reference failed");
+
+        let mut ar = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_cksum();
+        ar.append_data(&mut header, "file.txt", b"data".as_slice())
+            .unwrap();
+        let tar_data = ar.into_inner().unwrap();
+        let diff_id = format!("sha256:{:x}", sha2::Sha256::digest(&tar_data));
+
+        let mut gzip_encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip_encoder.write_all(&tar_data).unwrap();
+        let mut blob = gzip_encoder.finish().unwrap();
+        // the decompressor stops before the padding, it is hashed anyway
+        blob.extend_from_slice(&[0; 512]);
+        let digest = format!("sha256:{:x}", sha2::Sha256::digest(&blob));
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let client = PullClient::new(
+            oci_image,
+            tempdir.path(),
+            &RegistryAuth::Anonymous,
+            DEFAULT_MAX_CONCURRENT_DOWNLOAD,
+        )
+        .unwrap();
+
+        let layer = OciDescriptor {
+            media_type: MediaType::ImageLayerGzip.to_string(),
+            digest: digest.clone(),
+            ..Default::default()
+        };
+        let layer_meta = client
+            .async_handle_layer(
+                layer.clone(),
+                diff_id.clone(),
+                &None,
+                blob.as_slice(),
+                &client.data_dir,
+                Arc::new(Mutex::new(MetaStore::default())),
+            )
+            .await
+            .unwrap();
+        assert_eq!(layer_meta.compressed_digest, digest);
+        assert_eq!(layer_meta.uncompressed_digest, diff_id);
+
+        let corrupt = OciDescriptor {
+            digest: format!("sha256:{}", "0".repeat(64)),
+            ..layer
+        };
+        let err = client
+            .async_handle_layer(
+                corrupt,
+                diff_id,
+                &None,
+                blob.as_slice(),
+                &client.data_dir,
+                Arc::new(Mutex::new(MetaStore::default())),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.is::<BlobDigestMismatch>());
+        assert_eq!(
+            std::fs::read_dir(&client.quarantine_dir).unwrap().count(),
+            1
+        );
+    }
+
     #[cfg(feature = "nydus")]
     #[tokio::test]
     async fn test_pull_nydus_bootstrap() {
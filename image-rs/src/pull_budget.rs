@@ -0,0 +1,181 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Download budget shared by the concurrent pulls of a client.
+//!
+//! The images of the containers of a pod are pulled at the same time, each
+//! pull downloading up to `max_concurrent_download` layers at once. With a
+//! [`PullBudgetConfig`], all the pulls of the client together download at
+//! most `max_concurrent_downloads` layers at once, and read at most
+//! `max_bytes_per_second` from the registries, so that a pod with many
+//! containers doesn't starve the network of the guest.
+//!
+//! Layers read from a local source or from the blob cache don't take from
+//! the bandwidth budget.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Sleep;
+
+use crate::config::PullBudgetConfig;
+
+/// Download budget, which can be shared by several clients.
+pub struct PullBudget {
+    downloads: Arc<Semaphore>,
+
+    bandwidth: Option<Arc<Bandwidth>>,
+}
+
+impl PullBudget {
+    pub fn new(config: &PullBudgetConfig) -> Self {
+        Self {
+            downloads: Arc::new(Semaphore::new(config.max_concurrent_downloads)),
+            bandwidth: config
+                .max_bytes_per_second
+                .map(|rate| Arc::new(Bandwidth::new(rate))),
+        }
+    }
+
+    /// Wait for a layer download slot, kept until the permit is dropped.
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.downloads
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("pull budget semaphore is never closed")
+    }
+
+    /// Make the reads of `reader` take from the bandwidth budget.
+    pub fn throttle(
+        &self,
+        reader: Box<dyn AsyncRead + Unpin + Send>,
+    ) -> Box<dyn AsyncRead + Unpin + Send> {
+        match &self.bandwidth {
+            Some(bandwidth) => Box::new(Throttled {
+                inner: reader,
+                bandwidth: bandwidth.clone(),
+                delay: None,
+            }),
+            None => reader,
+        }
+    }
+}
+
+/// Token bucket holding up to a second of bytes. Reads go into debt, and
+/// the reader waits for the debt to be paid back before reading again.
+struct Bandwidth {
+    rate: f64,
+
+    state: std::sync::Mutex<(f64, Instant)>,
+}
+
+impl Bandwidth {
+    fn new(rate: u64) -> Self {
+        Self {
+            rate: rate as f64,
+            state: std::sync::Mutex::new((rate as f64, Instant::now())),
+        }
+    }
+
+    /// Take `bytes` from the bucket, returning how long to wait until it is
+    /// no longer in debt.
+    fn take(&self, bytes: usize) -> Duration {
+        let mut state = self.state.lock().expect("poisoned bandwidth budget");
+        let (available, refilled) = *state;
+        let now = Instant::now();
+        let refill = now.duration_since(refilled).as_secs_f64() * self.rate;
+        let available = (available + refill).min(self.rate) - bytes as f64;
+        *state = (available, now);
+
+        match available < 0.0 {
+            true => Duration::from_secs_f64(-available / self.rate),
+            false => Duration::ZERO,
+        }
+    }
+}
+
+struct Throttled {
+    inner: Box<dyn AsyncRead + Unpin + Send>,
+
+    bandwidth: Arc<Bandwidth>,
+
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl AsyncRead for Throttled {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(delay) = &mut this.delay {
+            ready!(delay.as_mut().poll(cx));
+            this.delay = None;
+        }
+
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let wait = this.bandwidth.take(buf.filled().len() - filled);
+        if !wait.is_zero() {
+            this.delay = Some(Box::pin(tokio::time::sleep(wait)));
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[test]
+    fn test_bandwidth() {
+        let bandwidth = Bandwidth::new(1000);
+        // a second of bytes is available at once
+        assert_eq!(bandwidth.take(1000), Duration::ZERO);
+        let wait = bandwidth.take(500);
+        assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_pull_budget() {
+        let budget = PullBudget::new(&PullBudgetConfig {
+            max_concurrent_downloads: 1,
+            max_bytes_per_second: Some(1000),
+        });
+
+        let permit = budget.acquire().await;
+        let waiting = tokio::time::timeout(Duration::from_millis(50), budget.acquire()).await;
+        assert!(waiting.is_err());
+        drop(permit);
+        tokio::time::timeout(Duration::from_secs(5), budget.acquire())
+            .await
+            .unwrap();
+
+        let mut reader = budget.throttle(Box::new(&[7u8; 1500][..]));
+        let start = Instant::now();
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).await.unwrap();
+        assert_eq!(read, [7u8; 1500]);
+        assert!(start.elapsed() >= Duration::from_millis(400));
+
+        let unlimited = PullBudget::new(&PullBudgetConfig {
+            max_concurrent_downloads: 1,
+            max_bytes_per_second: None,
+        });
+        let mut reader = unlimited.throttle(Box::new(&[7u8; 1500][..]));
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).await.unwrap();
+        assert_eq!(read.len(), 1500);
+    }
+}
@@ -0,0 +1,187 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Policy on how image references name the images they pull.
+//!
+//! Tags are mutable: the registry can serve a different image under the same
+//! tag at any time. The policy can pin tags to allow-listed digests, and in
+//! high-assurance deployments only accept references pinned by digest
+//! (`name@sha256:...`).
+
+use anyhow::{bail, Result};
+use oci_distribution::Reference;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Which image references are accepted.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReferenceMode {
+    /// Any reference. Tags listed in `pinned_tags` must still resolve to
+    /// one of their digests.
+    #[default]
+    Any,
+
+    /// Digest references, and tag references listed in `pinned_tags`.
+    Pinned,
+
+    /// Digest references only.
+    Digest,
+}
+
+/// Image reference policy, see the [module docs](self).
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ReferencePolicy {
+    #[serde(default)]
+    pub mode: ReferenceMode,
+
+    /// Allow-listed manifest digests of tag references, e.g.
+    /// `"docker.io/library/busybox:1.36": ["sha256:..."]`. A digest of the
+    /// image index of multi-platform images pins all its platforms.
+    #[serde(default)]
+    pub pinned_tags: HashMap<String, Vec<String>>,
+}
+
+impl ReferencePolicy {
+    /// Check `reference` before anything is pulled.
+    pub fn check_reference(&self, reference: &Reference) -> Result<()> {
+        if reference.digest().is_some() {
+            return Ok(());
+        }
+
+        match self.mode {
+            ReferenceMode::Any => Ok(()),
+            ReferenceMode::Digest => bail!(
+                "image reference {} is not pinned by digest",
+                reference.whole()
+            ),
+            ReferenceMode::Pinned => match self.pinned_digests(reference)? {
+                Some(_) => Ok(()),
+                None => bail!(
+                    "image tag {} is not pinned to a digest by the reference policy",
+                    reference.whole()
+                ),
+            },
+        }
+    }
+
+    /// Check the digests `reference` resolved to: the one of the manifest
+    /// the registry serves the reference with and the one of the selected
+    /// image manifest (equal unless the image has multiple platforms). Both
+    /// must be computed from the manifests, not taken from the registry.
+    pub fn check_resolved(&self, reference: &Reference, digests: &[&str]) -> Result<()> {
+        if let Some(digest) = reference.digest() {
+            if !digests.contains(&digest) {
+                bail!("registry resolved {} to {:?}", reference.whole(), digests);
+            }
+
+            return Ok(());
+        }
+
+        if let Some(allowed) = self.pinned_digests(reference)? {
+            if !digests.iter().any(|d| allowed.iter().any(|a| a == d)) {
+                bail!(
+                    "image tag {} resolved to {:?}, which is not allow-listed",
+                    reference.whole(),
+                    digests
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the digests `reference` resolves to need to be checked.
+    pub fn needs_resolved_check(&self, reference: &Reference) -> bool {
+        reference.digest().is_some()
+            || self
+                .pinned_digests(reference)
+                .map_or(true, |digests| digests.is_some())
+    }
+
+    // Keys are matched as parsed references, so that `busybox:1.36` and
+    // `docker.io/library/busybox:1.36` are the same.
+    fn pinned_digests(&self, reference: &Reference) -> Result<Option<&Vec<String>>> {
+        for (key, digests) in &self.pinned_tags {
+            let pinned = match Reference::try_from(key.as_str()) {
+                Ok(pinned) => pinned,
+                Err(e) => bail!("illegal image tag {key:?} in reference policy: {e}"),
+            };
+
+            if pinned.registry() == reference.registry()
+                && pinned.repository() == reference.repository()
+                && pinned.tag() == reference.tag()
+            {
+                return Ok(Some(digests));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "sha256:e1c082e3d3c45cccac829840a25941e679c25d438cc8412c2fa221cf1a824e6a";
+
+    fn policy(mode: ReferenceMode) -> ReferencePolicy {
+        ReferencePolicy {
+            mode,
+            pinned_tags: HashMap::from([("busybox:1.36".to_string(), vec![DIGEST.to_string()])]),
+        }
+    }
+
+    #[test]
+    fn test_check_reference() {
+        let tag = Reference::try_from("docker.io/library/busybox:1.36").unwrap();
+        let unpinned = Reference::try_from("docker.io/library/busybox:latest").unwrap();
+        let digest = Reference::try_from(format!("busybox@{DIGEST}")).unwrap();
+
+        let any = policy(ReferenceMode::Any);
+        assert!(any.check_reference(&unpinned).is_ok());
+
+        let pinned = policy(ReferenceMode::Pinned);
+        assert!(pinned.check_reference(&tag).is_ok());
+        assert!(pinned.check_reference(&digest).is_ok());
+        assert!(pinned.check_reference(&unpinned).is_err());
+
+        let digest_only = policy(ReferenceMode::Digest);
+        assert!(digest_only.check_reference(&digest).is_ok());
+        assert!(digest_only.check_reference(&tag).is_err());
+    }
+
+    #[test]
+    fn test_check_resolved() {
+        let tag = Reference::try_from("busybox:1.36").unwrap();
+        let unpinned = Reference::try_from("busybox:latest").unwrap();
+        let digest = Reference::try_from(format!("busybox@{DIGEST}")).unwrap();
+        let other = "sha256:0000000000000000000000000000000000000000000000000000000000000000";
+
+        let any = policy(ReferenceMode::Any);
+        assert!(any.check_resolved(&tag, &[other, DIGEST]).is_ok());
+        assert!(any.check_resolved(&tag, &[other]).is_err());
+        assert!(any.check_resolved(&unpinned, &[other]).is_ok());
+        assert!(any.check_resolved(&digest, &[DIGEST, other]).is_ok());
+        assert!(any.check_resolved(&digest, &[other]).is_err());
+
+        assert!(any.needs_resolved_check(&digest));
+        assert!(any.needs_resolved_check(&tag));
+        assert!(!any.needs_resolved_check(&unpinned));
+        assert!(!ReferencePolicy::default().needs_resolved_check(&tag));
+    }
+
+    #[test]
+    fn test_reference_policy_from_json() {
+        let data =
+            format!(r#"{{"mode": "pinned", "pinned_tags": {{"busybox:1.36": ["{DIGEST}"]}}}}"#);
+        let parsed: ReferencePolicy = serde_json::from_str(&data).unwrap();
+        assert_eq!(parsed, policy(ReferenceMode::Pinned));
+
+        let parsed: ReferencePolicy = serde_json::from_str("{}").unwrap();
+        assert_eq!(parsed, ReferencePolicy::default());
+    }
+}
@@ -0,0 +1,382 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Relabeling of the rootfs once mounted by a snapshotter.
+//!
+//! The files of the layers keep the owners and the SELinux labels of the
+//! image, which don't suit a container running in a user namespace, where
+//! root of the image is `nobody`, or confined by SELinux, whose context
+//! can't access them. The rootfs, and the layer groups mounted apart from
+//! it, are relabeled as set by [`RelabelConfig`] before the bundle is
+//! handed over, so that such containers need no manual pass:
+//!
+//! - the SELinux label is set on every file,
+//! - the owners are shifted by an idmapped mount, which leaves the files
+//!   alone, or by chown of every file if the kernel or the filesystem of
+//!   the snapshotter can't idmap the mount. Chown copies every file up to
+//!   the writable layer of overlay snapshotters and drops the file
+//!   capabilities, so the idmapped mount is tried first. The setuid and
+//!   setgid bits are dropped as well, rather than granting the shifted ids.
+//!
+//! The rootfs is measured once relabeled, so the shifted owners are part
+//! of its measurement, see [`crate::measure::RootfsMeasurement`].
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{lchown, MetadataExt, PermissionsExt};
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::config::{IdShift, IdShiftMode, RelabelConfig};
+
+const SELINUX_XATTR: &str = "security.selinux";
+
+/// Mounted if SELinux is enabled in the guest.
+const SELINUX_FS: &str = "/sys/fs/selinux";
+
+// the new mount API, which the libc crate lacks some bits of
+const OPEN_TREE_CLONE: libc::c_uint = 1;
+const MOVE_MOUNT_F_EMPTY_PATH: libc::c_uint = 0x4;
+const MOUNT_ATTR_IDMAP: u64 = 0x0010_0000;
+
+#[repr(C)]
+struct MountAttr {
+    attr_set: u64,
+    attr_clr: u64,
+    propagation: u64,
+    userns_fd: u64,
+}
+
+/// Relabel the rootfs, or the layer group, mounted at `mount_path` as set
+/// by `config`.
+pub fn relabel(mount_path: &Path, config: &RelabelConfig) -> Result<()> {
+    if let Some(label) = &config.selinux_label {
+        set_selinux_label(mount_path, label)?;
+    }
+
+    if let Some(shift) = &config.id_shift {
+        shift_ids(mount_path, shift)?;
+    }
+
+    Ok(())
+}
+
+fn set_selinux_label(mount_path: &Path, label: &str) -> Result<()> {
+    if !Path::new(SELINUX_FS).exists() {
+        warn!(
+            "SELinux is disabled, {} is not labeled",
+            mount_path.display()
+        );
+        return Ok(());
+    }
+
+    let name = CString::new(SELINUX_XATTR).expect("no NUL in the xattr name");
+    let value = CString::new(label).context("invalid selinux label")?;
+    let value = value.as_bytes_with_nul();
+    for entry in WalkDir::new(mount_path).same_file_system(true) {
+        let entry = entry?;
+        let path = CString::new(entry.path().as_os_str().as_bytes())?;
+        let res = unsafe {
+            libc::lsetxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr().cast(),
+                value.len(),
+                0,
+            )
+        };
+        if res != 0 {
+            bail!(
+                "failed to label {} with {label}: {}",
+                entry.path().display(),
+                io::Error::last_os_error()
+            );
+        }
+    }
+
+    info!("labeled {} with {label}", mount_path.display());
+    Ok(())
+}
+
+fn shift_ids(mount_path: &Path, shift: &IdShift) -> Result<()> {
+    match shift.mode {
+        IdShiftMode::Chown => chown_tree(mount_path, shift),
+        IdShiftMode::Idmap => {
+            let clone = idmapped_clone(mount_path, shift)?;
+            replace_mount(mount_path, clone)
+        }
+        IdShiftMode::Auto => match idmapped_clone(mount_path, shift) {
+            Ok(clone) => replace_mount(mount_path, clone),
+            Err(e) => {
+                warn!(
+                    "idmapped mount of {} failed, chown the files instead: {e:#}",
+                    mount_path.display()
+                );
+                chown_tree(mount_path, shift)
+            }
+        },
+    }
+}
+
+/// The id `id` of the image shifted to start at `base`, if it is one of
+/// the `size` shifted ids.
+fn shifted(id: u32, base: u32, size: u32) -> Option<u32> {
+    (id < size).then(|| base + id)
+}
+
+fn chown_tree(mount_path: &Path, shift: &IdShift) -> Result<()> {
+    // the paths of a hard linked file are shifted once
+    let mut shifted_inodes = HashSet::new();
+    for entry in WalkDir::new(mount_path).same_file_system(true) {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        if !meta.is_dir() && meta.nlink() > 1 && !shifted_inodes.insert(meta.ino()) {
+            continue;
+        }
+
+        let (Some(uid), Some(gid)) = (
+            shifted(meta.uid(), shift.uid, shift.size),
+            shifted(meta.gid(), shift.gid, shift.size),
+        ) else {
+            bail!(
+                "owner {}:{} of {} is beyond the {} shifted ids",
+                meta.uid(),
+                meta.gid(),
+                entry.path().display(),
+                shift.size
+            );
+        };
+        lchown(entry.path(), Some(uid), Some(gid))
+            .with_context(|| format!("failed to chown {}", entry.path().display()))?;
+
+        // chown may keep the setgid bit of files not executable by the group
+        if !entry.path_is_symlink() && meta.mode() & 0o6000 != 0 {
+            let mode = meta.mode() & !0o6000;
+            fs::set_permissions(entry.path(), fs::Permissions::from_mode(mode))?;
+        }
+    }
+
+    info!(
+        "shifted the owners of {} to {}:{}",
+        mount_path.display(),
+        shift.uid,
+        shift.gid
+    );
+    Ok(())
+}
+
+/// A detached clone of the mount at `mount_path`, with the ids of its files
+/// shifted by `shift`.
+fn idmapped_clone(mount_path: &Path, shift: &IdShift) -> Result<OwnedFd> {
+    let userns = user_namespace(shift).context("failed to create the user namespace")?;
+    let path = CString::new(mount_path.as_os_str().as_bytes())?;
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_open_tree,
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            OPEN_TREE_CLONE | libc::O_CLOEXEC as libc::c_uint,
+        )
+    };
+    if fd < 0 {
+        bail!(
+            "failed to clone the mount of {}: {}",
+            mount_path.display(),
+            io::Error::last_os_error()
+        );
+    }
+    let clone = unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) };
+
+    let attr = MountAttr {
+        attr_set: MOUNT_ATTR_IDMAP,
+        attr_clr: 0,
+        propagation: 0,
+        userns_fd: userns.as_raw_fd() as u64,
+    };
+    let empty = CString::default();
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_mount_setattr,
+            clone.as_raw_fd(),
+            empty.as_ptr(),
+            libc::AT_EMPTY_PATH,
+            &attr as *const MountAttr,
+            std::mem::size_of::<MountAttr>(),
+        )
+    };
+    if res != 0 {
+        bail!(
+            "failed to idmap the mount of {}: {}",
+            mount_path.display(),
+            io::Error::last_os_error()
+        );
+    }
+
+    Ok(clone)
+}
+
+/// Put the mount `clone` in place of the mount at `mount_path`, so that the
+/// mount path still holds a single mount to unmount.
+fn replace_mount(mount_path: &Path, clone: OwnedFd) -> Result<()> {
+    let path = CString::new(mount_path.as_os_str().as_bytes())?;
+
+    // the clone keeps the filesystem of the detached mount alive
+    if unsafe { libc::umount2(path.as_ptr(), libc::MNT_DETACH) } != 0 {
+        bail!(
+            "failed to unmount {}: {}",
+            mount_path.display(),
+            io::Error::last_os_error()
+        );
+    }
+
+    let empty = CString::default();
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_move_mount,
+            clone.as_raw_fd(),
+            empty.as_ptr(),
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            MOVE_MOUNT_F_EMPTY_PATH,
+        )
+    };
+    if res != 0 {
+        bail!(
+            "failed to mount the idmapped {}: {}",
+            mount_path.display(),
+            io::Error::last_os_error()
+        );
+    }
+
+    info!("idmapped the mount of {}", mount_path.display());
+    Ok(())
+}
+
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/// A user namespace mapping the ids of the image as `shift` does, held by
+/// the returned fd. It is made by a child process which unshares it and
+/// waits until it is opened.
+fn user_namespace(shift: &IdShift) -> Result<OwnedFd> {
+    let (ready_read, ready_write) = pipe()?;
+    let (done_read, done_write) = pipe()?;
+
+    let pid = unsafe { libc::fork() };
+    if pid < 0 {
+        bail!("failed to fork: {}", io::Error::last_os_error());
+    }
+
+    if pid == 0 {
+        // the process is multithreaded, so the child only makes
+        // async-signal-safe calls
+        unsafe {
+            libc::close(ready_read.as_raw_fd());
+            libc::close(done_write.as_raw_fd());
+            let unshared = u8::from(libc::unshare(libc::CLONE_NEWUSER) == 0);
+            libc::write(ready_write.as_raw_fd(), (&unshared as *const u8).cast(), 1);
+            let mut done = 0u8;
+            libc::read(done_read.as_raw_fd(), (&mut done as *mut u8).cast(), 1);
+            libc::_exit(0);
+        }
+    }
+
+    drop(ready_write);
+    drop(done_read);
+    let res = (|| {
+        let mut unshared = [0u8];
+        File::from(ready_read)
+            .read_exact(&mut unshared)
+            .context("the child exited early")?;
+        if unshared[0] != 1 {
+            bail!("the child failed to unshare its user namespace");
+        }
+
+        for (map, base) in [("uid_map", shift.uid), ("gid_map", shift.gid)] {
+            fs::write(
+                format!("/proc/{pid}/{map}"),
+                format!("0 {base} {}\n", shift.size),
+            )
+            .with_context(|| format!("failed to write {map}"))?;
+        }
+
+        let userns = File::open(format!("/proc/{pid}/ns/user"))?;
+        Ok(OwnedFd::from(userns))
+    })();
+
+    // the child exits once the pipe is closed
+    drop(done_write);
+    unsafe { libc::waitpid(pid, std::ptr::null_mut(), 0) };
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_ID_SHIFT_SIZE;
+
+    #[test]
+    fn test_shifted() {
+        assert_eq!(shifted(0, 100000, 65536), Some(100000));
+        assert_eq!(shifted(65535, 100000, 65536), Some(165535));
+        assert_eq!(shifted(65536, 100000, 65536), None);
+    }
+
+    #[test]
+    fn test_chown_tree() {
+        // chown needs root
+        if !nix::unistd::Uid::effective().is_root() {
+            return;
+        }
+
+        let rootfs = tempfile::tempdir().unwrap();
+        let bin = rootfs.path().join("bin");
+        fs::create_dir(&bin).unwrap();
+        fs::write(bin.join("su"), b"su").unwrap();
+        fs::set_permissions(bin.join("su"), fs::Permissions::from_mode(0o6755)).unwrap();
+        fs::hard_link(bin.join("su"), bin.join("su-link")).unwrap();
+        std::os::unix::fs::symlink("su", bin.join("su-symlink")).unwrap();
+        lchown(bin.join("su-symlink"), Some(1000), Some(1000)).unwrap();
+
+        let shift = IdShift {
+            uid: 100000,
+            gid: 200000,
+            size: DEFAULT_ID_SHIFT_SIZE,
+            mode: IdShiftMode::Chown,
+        };
+        relabel(
+            rootfs.path(),
+            &RelabelConfig {
+                id_shift: Some(shift.clone()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let owner = |path: &Path| {
+            let meta = fs::symlink_metadata(path).unwrap();
+            (meta.uid(), meta.gid())
+        };
+        assert_eq!(owner(rootfs.path()), (100000, 200000));
+        assert_eq!(owner(&bin.join("su")), (100000, 200000));
+        assert_eq!(owner(&bin.join("su-symlink")), (101000, 201000));
+        let su = fs::metadata(bin.join("su")).unwrap();
+        assert_eq!(su.mode() & 0o7777, 0o755);
+
+        // ids beyond the shifted ones
+        assert!(chown_tree(rootfs.path(), &shift).is_err());
+    }
+}
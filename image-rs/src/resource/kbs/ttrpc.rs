@@ -36,9 +36,13 @@ impl Client for Ttrpc {
             ResourcePath: resource_path.to_string(),
             ..Default::default()
         };
+        #[cfg_attr(not(feature = "telemetry"), allow(unused_mut))]
+        let mut ctx = context::with_timeout(50 * 1000 * 1000 * 1000);
+        #[cfg(feature = "telemetry")]
+        telemetry::inject_ttrpc(&mut ctx.metadata);
         let res = self
             .gtclient
-            .get_resource(context::with_timeout(50 * 1000 * 1000 * 1000), &req)
+            .get_resource(ctx, &req)
             .await
             .context("ttrpc request error")?;
         Ok(res.Resource)
@@ -8,6 +8,8 @@ use std::convert::TryFrom;
 use anyhow::*;
 use oci_distribution::Reference;
 
+use crate::pull::RegistryAccess;
+
 pub mod digest;
 
 use digest::Digest;
@@ -26,6 +28,8 @@ pub struct Image {
     pub reference: Reference,
     // digest format: "digest-algorithm:digest-value"
     pub manifest_digest: Digest,
+    // how the registry is reached by the pull verifying the image, if any
+    pub registry: Option<RegistryAccess>,
 }
 
 impl Image {
@@ -33,6 +37,7 @@ impl Image {
         Image {
             reference: image_ref,
             manifest_digest: Digest::default(),
+            registry: None,
         }
     }
 
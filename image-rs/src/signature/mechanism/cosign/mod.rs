@@ -10,15 +10,26 @@ use async_trait::async_trait;
 use oci_distribution::secrets::RegistryAuth;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "signature-cosign")]
+use crate::pull::RegistryAccess;
+#[cfg(feature = "signature-cosign")]
+use crate::verification::CertificateIdentity;
+#[cfg(feature = "signature-cosign")]
+use log::warn;
+#[cfg(feature = "signature-cosign")]
+use oci_distribution::{manifest::OciImageIndex, Client, Reference, RegistryOperation};
+#[cfg(feature = "signature-cosign")]
+use sha2::Digest;
 #[cfg(feature = "signature-cosign")]
 use sigstore::{
     cosign::{
+        signature_layers::{CertificateSubject, SignatureLayer},
         verification_constraint::{PublicKeyVerifier, VerificationConstraintVec},
         verify_constraints, ClientBuilder, CosignCapabilities,
     },
     crypto::SigningScheme,
     errors::SigstoreVerifyConstraintsError,
-    registry::{Auth, OciReference},
+    registry::{Auth, ClientConfig, ClientProtocol, OciReference},
 };
 use std::str::FromStr;
 
@@ -28,10 +39,15 @@ use crate::signature::{
     image::Image, mechanism::Paths, payload::simple_signing::SigPayload,
     policy::ref_match::PolicyReqMatchType,
 };
+use crate::verification::VerifiedSignature;
 
 /// The name of resource to request cosign verification key from kbs
 pub const COSIGN_KEY_KBS: &str = "Cosign Key";
 
+/// Artifact type of the cosign signatures stored as OCI 1.1 referrers of
+/// the signed image.
+pub const COSIGN_SIGNATURE_ARTIFACT_TYPE: &str = "application/vnd.dev.cosign.artifact.sig.v1+json";
+
 #[derive(Deserialize, Debug, Eq, PartialEq, Serialize, Default)]
 pub struct CosignParameters {
     // KeyPath is a pathname to a local file containing the trusted key(s).
@@ -71,27 +87,45 @@ impl SignScheme for CosignParameters {
 
     /// Judge whether an image is allowed by this SignScheme.
     #[cfg(feature = "signature-cosign")]
-    async fn allows_image(&self, image: &mut Image, auth: &RegistryAuth) -> Result<()> {
+    async fn allows_image(
+        &self,
+        image: &mut Image,
+        auth: &RegistryAuth,
+    ) -> Result<Vec<VerifiedSignature>> {
         // Check before we access the network
         self.check_reference_rule_types()?;
 
         // Verification, will access the network
-        let payloads = self.verify_signature_and_get_payload(image, auth).await?;
+        let (key_id, payloads) = self.verify_signature_and_get_payload(image, auth).await?;
 
         // check the reference rules (signed identity)
-        for payload in payloads {
+        let mut signatures = Vec::with_capacity(payloads.len());
+        for (payload, certificate_identity) in payloads {
             if let Some(rule) = &self.signed_identity {
                 payload.validate_signed_docker_reference(&image.reference, rule)?;
             }
 
             payload.validate_signed_docker_manifest_digest(&image.manifest_digest.to_string())?;
+            signatures.push(VerifiedSignature {
+                key_id: key_id.clone(),
+                signed_reference: payload.docker_reference(),
+                signed_identity: self
+                    .signed_identity
+                    .as_ref()
+                    .map(|rule| rule.type_name().to_string()),
+                certificate_identity,
+            });
         }
 
-        Ok(())
+        Ok(signatures)
     }
 
     #[cfg(not(feature = "signature-cosign"))]
-    async fn allows_image(&self, image: &mut Image, auth: &RegistryAuth) -> Result<()> {
+    async fn allows_image(
+        &self,
+        image: &mut Image,
+        auth: &RegistryAuth,
+    ) -> Result<Vec<VerifiedSignature>> {
         bail!("feature \"signature-cosign\" not enabled.")
     }
 }
@@ -117,16 +151,19 @@ impl CosignParameters {
 
     /// Verify the cosign-signed image. There will be three steps:
     /// * Get the pub key.
-    /// * Download the cosign-signed image's manifest and its digest. Calculate its
-    /// signature's image.
-    /// * Download the signature image, gather the signatures and verify them
+    /// * Find the signature images: the referrers of the signed image with
+    /// the cosign signature artifact type, or, if the registry has none or
+    /// does not support the OCI 1.1 referrers API, the image of the
+    /// `sha256-<digest>.sig` tag.
+    /// * Download the signature images, gather the signatures and verify them
     /// using the pubkey.
-    /// If succeeds, the payloads of the signature will be returned.
+    /// If succeeds, the digest of the pubkey and the payloads of the signatures,
+    /// with the identity of their certificate if any, will be returned.
     async fn verify_signature_and_get_payload(
         &self,
         image: &Image,
         auth: &RegistryAuth,
-    ) -> Result<Vec<SigPayload>> {
+    ) -> Result<(String, Vec<(SigPayload, Option<CertificateIdentity>)>)> {
         // Get the pubkey
         let key = match (&self.key_data, &self.key_path) {
             (None, None) => bail!("Neither keyPath nor keyData is specified."),
@@ -134,18 +171,45 @@ impl CosignParameters {
             (Some(key_data), None) => key_data.as_bytes().to_vec(),
             (Some(_), Some(_)) => bail!("Both keyPath and keyData are specified."),
         };
-
-        let image_ref = OciReference::from_str(&image.reference.whole())?;
+        let key_id = format!("sha256:{:x}", sha2::Sha256::digest(&key));
+
+        // the registry is reached as by the pull, e.g. through the relay
+        // presenting the client certificate
+        let registry = image.registry.as_ref();
+        let reference = registry.map_or(&image.reference, |registry| &registry.reference);
+        let image_ref = OciReference::from_str(&reference.whole())?;
+        let mut client = signature_client(registry)?;
+        let source_image_digest = image.manifest_digest.to_string();
+        let referrers = match signature_referrers(image, &source_image_digest, auth).await {
+            Ok(referrers) => referrers,
+            Err(e) => {
+                warn!(
+                    "cannot list the signature referrers, falling back to the signature tag: {e:#}"
+                );
+                Vec::new()
+            }
+        };
         let auth = &Auth::from(auth);
 
-        let mut client = ClientBuilder::default().build()?;
+        let mut signature_layers: Vec<SignatureLayer> = Vec::new();
+        for referrer in referrers {
+            match client
+                .trusted_signature_layers(auth, &source_image_digest, &referrer)
+                .await
+            {
+                Ok(layers) => signature_layers.extend(layers),
+                Err(e) => warn!("skip signature referrer {referrer}: {e}"),
+            }
+        }
 
-        // Get the cosign signature "image"'s uri and the signed image's digest
-        let (cosign_image, source_image_digest) = client.triangulate(&image_ref, auth).await?;
+        if signature_layers.is_empty() {
+            // Get the cosign signature "image"'s uri and the signed image's digest
+            let (cosign_image, source_image_digest) = client.triangulate(&image_ref, auth).await?;
 
-        let signature_layers = client
-            .trusted_signature_layers(auth, &source_image_digest, &cosign_image)
-            .await?;
+            signature_layers = client
+                .trusted_signature_layers(auth, &source_image_digest, &cosign_image)
+                .await?;
+        }
 
         // By default, the hashing algorithm is SHA256
         let pub_key_verifier =
@@ -160,9 +224,25 @@ impl CosignParameters {
                 // gather the payloads
                 let payloads = signature_layers
                     .iter()
-                    .map(|layer| SigPayload::from(layer.simple_signing.clone()))
+                    .map(|layer| {
+                        let certificate_identity =
+                            layer.certificate_signature.as_ref().map(|cert| {
+                                let subject = match &cert.subject {
+                                    CertificateSubject::Email(email) => email.clone(),
+                                    CertificateSubject::Uri(uri) => uri.clone(),
+                                };
+                                CertificateIdentity {
+                                    subject,
+                                    issuer: cert.issuer.clone(),
+                                }
+                            });
+                        (
+                            SigPayload::from(layer.simple_signing.clone()),
+                            certificate_identity,
+                        )
+                    })
                     .collect();
-                Ok(payloads)
+                Ok((key_id, payloads))
             }
             Err(SigstoreVerifyConstraintsError {
                 unsatisfied_constraints,
@@ -171,10 +251,86 @@ impl CosignParameters {
     }
 }
 
+/// The client the signature images are pulled with, set up with the TLS
+/// policy of the pull reaching the registry as `registry`, if any. Its
+/// requests can't be sent through a proxy, so the signatures of images
+/// pulled through one are not looked up around it.
+#[cfg(feature = "signature-cosign")]
+fn signature_client(registry: Option<&RegistryAccess>) -> Result<sigstore::cosign::Client> {
+    let mut builder = ClientBuilder::default();
+    if let Some(registry) = registry {
+        if registry.proxied {
+            bail!("cosign signatures cannot be pulled through the registry proxy");
+        }
+
+        let protocol = match registry.plain_http.is_empty() {
+            true => ClientProtocol::Https,
+            false => ClientProtocol::HttpsExcept(registry.plain_http.clone()),
+        };
+        builder = builder.with_oci_client_config(ClientConfig {
+            protocol,
+            accept_invalid_certificates: registry.insecure_skip_tls_verify,
+            ..Default::default()
+        });
+    }
+
+    Ok(builder.build()?)
+}
+
+/// List the cosign signature images attached to the manifest `digest` of
+/// `image` with the OCI 1.1 referrers API (`/v2/<name>/referrers/<digest>`),
+/// with the registry client of the pull.
+#[cfg(feature = "signature-cosign")]
+async fn signature_referrers(
+    image: &Image,
+    digest: &str,
+    auth: &RegistryAuth,
+) -> Result<Vec<OciReference>> {
+    let (mut client, reference) = match &image.registry {
+        Some(registry) => (registry.client.clone(), &registry.reference),
+        None => (Client::default(), &image.reference),
+    };
+    let subject = Reference::with_digest(
+        reference.registry().to_string(),
+        reference.repository().to_string(),
+        digest.to_string(),
+    );
+    client
+        .auth(&subject, auth, RegistryOperation::Pull)
+        .await
+        .map_err(|e| anyhow!("failed to authenticate to registry: {e}"))?;
+    let index = client
+        .pull_referrers(&subject, Some(COSIGN_SIGNATURE_ARTIFACT_TYPE))
+        .await
+        .map_err(|e| anyhow!("failed to pull referrers: {e}"))?;
+
+    referrer_references(&subject, &index)
+}
+
+/// The references of the images listed in the referrers `index` of
+/// `subject`, in its repository.
+#[cfg(feature = "signature-cosign")]
+fn referrer_references(subject: &Reference, index: &OciImageIndex) -> Result<Vec<OciReference>> {
+    index
+        .manifests
+        .iter()
+        .map(|manifest| {
+            let signature = Reference::with_digest(
+                subject.registry().to_string(),
+                subject.repository().to_string(),
+                manifest.digest.clone(),
+            );
+            OciReference::from_str(&signature.whole())
+                .map_err(|e| anyhow!("invalid signature referrer {}: {e}", manifest.digest))
+        })
+        .collect()
+}
+
 #[cfg(feature = "signature-cosign")]
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pull::PullClient;
     use crate::signature::{
         mechanism::SignScheme,
         policy::{policy_requirement::PolicyReqType, ref_match::PolicyReqMatchType},
@@ -267,6 +423,64 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn verify_signature_with_registry_access_test() {
+        let reference =
+            Reference::try_from("quay.io/kata-containers/confidential-containers:cosign-signed")
+                .unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+        let auth = oci_distribution::secrets::RegistryAuth::Anonymous;
+        let client = PullClient::new(reference.clone(), data_dir.path(), &auth, 1).unwrap();
+        let mut image = Image::default_with_reference(reference);
+        image.set_manifest_digest(IMAGE_DIGEST).unwrap();
+        image.registry = client.registry_access();
+
+        let parameter = CosignParameters {
+            key_path: Some(format!(
+                "{}/test_data/signature/cosign/cosign1.pub",
+                std::env::current_dir().unwrap().display(),
+            )),
+            key_data: None,
+            signed_identity: None,
+        };
+        parameter
+            .verify_signature_and_get_payload(&image, &auth)
+            .await
+            .unwrap();
+
+        // the signatures are never looked up around the proxy of the pull
+        image.registry.as_mut().unwrap().proxied = true;
+        assert!(parameter
+            .verify_signature_and_get_payload(&image, &auth)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_referrer_references() {
+        let subject =
+            Reference::try_from(format!("registry.local:5000/app/server@{IMAGE_DIGEST}")).unwrap();
+        let index: OciImageIndex = serde_json::from_str(
+            r#"{
+                "schemaVersion": 2,
+                "mediaType": "application/vnd.oci.image.index.v1+json",
+                "manifests": [
+                    {"mediaType": "application/vnd.oci.image.manifest.v1+json",
+                     "digest": "sha256:1111111111111111111111111111111111111111111111111111111111111111",
+                     "size": 1}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let references = referrer_references(&subject, &index).unwrap();
+        assert_eq!(references.len(), 1);
+        assert_eq!(
+            references[0].to_string(),
+            "registry.local:5000/app/server@sha256:1111111111111111111111111111111111111111111111111111111111111111"
+        );
+    }
+
     #[rstest]
     #[case(PolicyReqMatchType::MatchExact, false)]
     #[case(PolicyReqMatchType::MatchRepoDigestOrExact, false)]
@@ -19,6 +19,7 @@ use async_trait::async_trait;
 use oci_distribution::secrets::RegistryAuth;
 
 use crate::config::Paths;
+use crate::verification::VerifiedSignature;
 
 use super::image::Image;
 
@@ -33,6 +34,11 @@ pub trait SignScheme: Send + Sync {
     /// * gathering necessary files.
     async fn init(&mut self, config: &Paths) -> Result<()>;
 
-    /// Judge whether an image is allowed by this SignScheme.
-    async fn allows_image(&self, image: &mut Image, auth: &RegistryAuth) -> Result<()>;
+    /// Judge whether an image is allowed by this SignScheme, returning
+    /// the signatures which allowed it.
+    async fn allows_image(
+        &self,
+        image: &mut Image,
+        auth: &RegistryAuth,
+    ) -> Result<Vec<VerifiedSignature>>;
 }
@@ -21,6 +21,7 @@ mod verify;
 mod xrss;
 
 use crate::signature::{image::Image, mechanism::Paths, policy::ref_match::PolicyReqMatchType};
+use crate::verification::VerifiedSignature;
 
 use super::SignScheme;
 
@@ -97,7 +98,11 @@ impl SignScheme for SimpleParameters {
     }
 
     #[cfg(feature = "signature-simple")]
-    async fn allows_image(&self, image: &mut Image, _auth: &RegistryAuth) -> Result<()> {
+    async fn allows_image(
+        &self,
+        image: &mut Image,
+        _auth: &RegistryAuth,
+    ) -> Result<Vec<VerifiedSignature>> {
         // FIXME: only support "GPGKeys" type now.
         //
         // refer to https://github.com/confidential-containers/image-rs/issues/14
@@ -130,8 +135,8 @@ impl SignScheme for SimpleParameters {
                 sig.to_vec(),
             ) {
                 // One accepted signature is enough.
-                Result::Ok(()) => {
-                    return Ok(());
+                Result::Ok(signature) => {
+                    return Ok(vec![signature]);
                 }
                 Result::Err(e) => {
                     reject_reasons.push(e);
@@ -150,7 +155,11 @@ impl SignScheme for SimpleParameters {
     }
 
     #[cfg(not(feature = "signature-simple"))]
-    async fn allows_image(&self, _image: &mut Image, _auth: &RegistryAuth) -> Result<()> {
+    async fn allows_image(
+        &self,
+        _image: &mut Image,
+        _auth: &RegistryAuth,
+    ) -> Result<Vec<VerifiedSignature>> {
         bail!("feature \"signature-simple\" not enabled.")
     }
 }
@@ -167,9 +176,9 @@ pub fn judge_single_signature(
     signed_identity: Option<&PolicyReqMatchType>,
     pubkey_ring: &[u8],
     sig: Vec<u8>,
-) -> Result<()> {
+) -> Result<VerifiedSignature> {
     // Verify the signature with the pubkey ring.
-    let sig_payload = verify::verify_sig_and_extract_payload(pubkey_ring, sig)?;
+    let (sig_payload, fingerprint) = verify::verify_sig_and_extract_payload(pubkey_ring, sig)?;
 
     // Verify whether the information recorded in signature payload
     // is consistent with the real information of the image.
@@ -185,7 +194,12 @@ pub fn judge_single_signature(
     sig_payload.validate_signed_docker_reference(&image.reference, signed_identity)?;
     sig_payload.validate_signed_docker_manifest_digest(&image.manifest_digest.to_string())?;
 
-    Ok(())
+    Ok(VerifiedSignature {
+        key_id: fingerprint,
+        signed_reference: sig_payload.docker_reference(),
+        signed_identity: Some(signed_identity.type_name().to_string()),
+        certificate_identity: None,
+    })
 }
 
 #[cfg(feature = "signature-simple")]
@@ -50,8 +50,12 @@ impl SigKeyIDs {
 }
 
 // Verifies the input signature, and verifies its principal components match expected
-// values, both as specified by rules, and returns the signature payload.
-pub fn verify_sig_and_extract_payload(pubkey_ring: &[u8], sig: Vec<u8>) -> Result<SigPayload> {
+// values, both as specified by rules, and returns the signature payload with the
+// fingerprint of the key which verified it.
+pub fn verify_sig_and_extract_payload(
+    pubkey_ring: &[u8],
+    sig: Vec<u8>,
+) -> Result<(SigPayload, String)> {
     // Parse the gpg pubkey ring.
     let keyring_packet = PacketPile::from_bytes(pubkey_ring)?;
     let keyring_iter = keyring_packet.descendants();
@@ -59,6 +63,7 @@ pub fn verify_sig_and_extract_payload(pubkey_ring: &[u8], sig: Vec<u8>) -> Resul
     let mut sig_packet = PacketPile::from_bytes(&sig)?;
 
     let mut validate_key_id = SigKeyIDs::default();
+    let mut fingerprint = String::new();
 
     // Dump the keyID which recorded in the signature itself from the OnePassSig of the sig claim file.
     // OnePassSig: https://docs.rs/sequoia-openpgp/1.7.0/sequoia_openpgp/packet/enum.OnePassSig.html
@@ -104,6 +109,7 @@ pub fn verify_sig_and_extract_payload(pubkey_ring: &[u8], sig: Vec<u8>) -> Resul
                     // If the cryptography verification passes, but the key IDs are inconsistent,
                     // the verification failure is returned directly.
                     validate_key_id.validate()?;
+                    fingerprint = pubkey.fingerprint().to_hex();
                 }
             }
         }
@@ -117,7 +123,7 @@ pub fn verify_sig_and_extract_payload(pubkey_ring: &[u8], sig: Vec<u8>) -> Resul
     if let Some(openpgp::Packet::Literal(ref literal)) = sig_packet.path_ref(&[0, 1]) {
         let body_message = String::from_utf8(literal.body().to_vec())?;
         let sig_payload = serde_json::from_str::<SigPayload>(&body_message)?;
-        Ok(sig_payload)
+        Ok((sig_payload, fingerprint))
     } else {
         Err(anyhow!("Signature format error: no literal field in it!"))
     }
@@ -252,8 +258,9 @@ mod tests {
         let sig_bytes_case_1 =
             ::std::fs::read("./test_data/signature/signatures/signature-1").unwrap();
 
-        let sig_payload_verified =
+        let (sig_payload_verified, fingerprint) =
             verify_sig_and_extract_payload(&keyring_bytes_case_1, sig_bytes_case_1).unwrap();
+        assert_eq!(fingerprint.len(), 40);
 
         let sig_payload_verified = serde_json::to_value(sig_payload_verified).unwrap();
 
@@ -16,19 +16,23 @@ use oci_distribution::secrets::RegistryAuth;
 
 /// `allows_image` will check all the `PolicyRequirements` suitable for
 /// the given image. The `PolicyRequirements` is defined in
-/// [`policy_path`] and may include signature verification.
+/// [`policy_path`] and may include signature verification. If the image
+/// is allowed, the requirements it satisfied are reported. The signatures
+/// are looked up in the registry as reached by the pull, `registry`.
 #[cfg(feature = "signature")]
 pub async fn allows_image(
     image_reference: &str,
     image_digest: &str,
     auth: &RegistryAuth,
     file_paths: &Paths,
-) -> Result<()> {
+    registry: Option<crate::pull::RegistryAccess>,
+) -> Result<crate::verification::VerificationReport> {
     use crate::{resource, signature::image::Image};
 
     let reference = oci_distribution::Reference::try_from(image_reference)?;
     let mut image = Image::default_with_reference(reference);
     image.set_manifest_digest(image_digest)?;
+    image.registry = registry;
 
     // Read the set of signature schemes that need to be verified
     // of the image from the policy configuration.
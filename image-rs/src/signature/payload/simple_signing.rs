@@ -49,7 +49,7 @@ impl SigPayload {
         self.critical.image.docker_manifest_digest.clone()
     }
 
-    fn docker_reference(&self) -> String {
+    pub(crate) fn docker_reference(&self) -> String {
         self.critical.identity.docker_reference.clone()
     }
 }
@@ -14,6 +14,7 @@ use self::policy_requirement::PolicyReqType;
 
 use super::image;
 use super::mechanism::SignScheme;
+use crate::verification::VerificationReport;
 
 pub mod policy_requirement;
 pub mod ref_match;
@@ -43,14 +44,21 @@ pub struct Policy {
 pub type PolicyTransportScopes = HashMap<String, Vec<PolicyReqType>>;
 
 impl Policy {
-    // Returns Ok(()) if the requirement allows running an image.
+    // Returns a report of the satisfied requirements if the policy allows running an image.
     // WARNING: This validates signatures and the manifest, but does not download or validate the
     // layers. Users must validate that the layers match their expected digests.
     pub async fn is_image_allowed(
         &mut self,
         mut image: image::Image,
         auth: &RegistryAuth,
-    ) -> Result<()> {
+    ) -> Result<VerificationReport> {
+        let mut report = VerificationReport {
+            transport: image.transport_name(),
+            scope: self.scope_for_image(&image),
+            manifest_digest: image.manifest_digest.to_string(),
+            requirements: Vec::new(),
+        };
+
         // Get the policy set that matches the image.
         let reqs = self.requirements_for_image(&image);
         if reqs.is_empty() {
@@ -59,10 +67,11 @@ impl Policy {
 
         // The image must meet the requirements of each policy in the policy set.
         for req in reqs.iter() {
-            req.allows_image(&mut image, auth).await?;
+            let requirement = req.allows_image(&mut image, auth).await?;
+            report.requirements.push(requirement);
         }
 
-        Ok(())
+        Ok(report)
     }
 
     // Get the set of signature schemes that need to be verified of the image.
@@ -75,31 +84,127 @@ impl Policy {
 
     // selects the appropriate requirements for the image from Policy.
     fn requirements_for_image(&mut self, image: &image::Image) -> &mut Vec<PolicyReqType> {
+        match self.scope_for_image(image) {
+            Some(scope) => self
+                .transports
+                .get_mut(&image.transport_name())
+                .and_then(|transport_scopes| transport_scopes.get_mut(&scope))
+                .expect("Unexpected scope"),
+            None => &mut self.default,
+        }
+    }
+
+    // selects the scope of the image's transport whose requirements apply to the image,
+    // or None if the default requirements apply.
+    fn scope_for_image(&self, image: &image::Image) -> Option<String> {
         // Get transport name of the image
         let transport_name = image.transport_name();
+        let transport_scopes = self.transports.get(&transport_name)?;
 
-        if let Some(transport_scopes) = self.transports.get_mut(&transport_name) {
-            // Look for a full match.
-            let identity = image.reference.whole();
-            if transport_scopes.contains_key(&identity) {
-                return transport_scopes
-                    .get_mut(&identity)
-                    .expect("Unexpected contains");
-            }
+        // Look for a full match.
+        let identity = image.reference.whole();
+        if transport_scopes.contains_key(&identity) {
+            return Some(identity);
+        }
 
-            // Look for a match of the possible parent namespaces.
-            for name in image::get_image_namespaces(&image.reference).iter() {
-                if transport_scopes.contains_key(name) {
-                    return transport_scopes.get_mut(name).expect("Unexpected contains");
-                }
+        // Look for a match of the possible parent namespaces.
+        for name in image::get_image_namespaces(&image.reference) {
+            if transport_scopes.contains_key(&name) {
+                return Some(name);
             }
+        }
 
-            // Look for a default match for the transport.
-            if let Some(reqs) = transport_scopes.get_mut("") {
-                return reqs;
-            }
+        // Look for a default match for the transport.
+        if transport_scopes.contains_key("") {
+            return Some(String::new());
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oci_distribution::Reference;
+
+    use super::*;
+
+    fn image(reference: &str) -> image::Image {
+        let reference = Reference::try_from(reference).unwrap();
+        let mut image = image::Image::default_with_reference(reference);
+        image
+            .set_manifest_digest(
+                "sha256:0000000000000000000000000000000000000000000000000000000000000000",
+            )
+            .unwrap();
+        image
+    }
+
+    #[test]
+    fn test_scope_for_image() {
+        let policy: Policy = serde_json::from_str(
+            r#"{
+                "default": [{"type": "reject"}],
+                "transports": {
+                    "docker": {
+                        "quay.io/example/busybox:latest": [{"type": "insecureAcceptAnything"}],
+                        "quay.io/example": [{"type": "insecureAcceptAnything"}],
+                        "": [{"type": "reject"}]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let vectors = [
+            ("quay.io/example/busybox:latest", "quay.io/example/busybox:latest"),
+            ("quay.io/example/busybox:1.36", "quay.io/example"),
+            ("docker.io/library/busybox:latest", ""),
+        ];
+        for (reference, scope) in vectors {
+            assert_eq!(
+                policy.scope_for_image(&image(reference)).as_deref(),
+                Some(scope)
+            );
         }
 
-        &mut self.default
+        let policy: Policy =
+            serde_json::from_str(r#"{"default": [{"type": "reject"}], "transports": {}}"#)
+                .unwrap();
+        assert_eq!(policy.scope_for_image(&image("quay.io/example/busybox")), None);
+    }
+
+    #[tokio::test]
+    async fn test_is_image_allowed_report() {
+        let mut policy: Policy = serde_json::from_str(
+            r#"{
+                "default": [{"type": "reject"}],
+                "transports": {
+                    "docker": {
+                        "quay.io/example": [{"type": "insecureAcceptAnything"}]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let report = policy
+            .is_image_allowed(image("quay.io/example/busybox"), &RegistryAuth::Anonymous)
+            .await
+            .unwrap();
+        assert_eq!(report.transport, "docker");
+        assert_eq!(report.scope.as_deref(), Some("quay.io/example"));
+        assert_eq!(
+            report.manifest_digest,
+            "sha256:0000000000000000000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(report.requirements.len(), 1);
+        assert_eq!(report.requirements[0].requirement_type, "insecureAcceptAnything");
+        assert!(!report.is_signed());
+
+        assert!(policy
+            .is_image_allowed(image("docker.io/library/busybox"), &RegistryAuth::Anonymous)
+            .await
+            .is_err());
     }
 }
@@ -9,6 +9,7 @@ use serde::*;
 
 use crate::signature::image::Image;
 use crate::signature::mechanism::{cosign::CosignParameters, simple::SimpleParameters, SignScheme};
+use crate::verification::RequirementReport;
 
 /// Policy Requirement Types.
 /// * `Accept`: s.t. `insecureAcceptAnything`, skip signature verification, accept the image unconditionally.
@@ -40,12 +41,32 @@ pub enum PolicyReqType {
 
 impl PolicyReqType {
     /// Check whether an image is allowed by a given policy requirement.
-    pub async fn allows_image(&self, image: &mut Image, auth: &RegistryAuth) -> Result<()> {
+    /// If so, the signatures which satisfied the requirement are reported.
+    pub async fn allows_image(
+        &self,
+        image: &mut Image,
+        auth: &RegistryAuth,
+    ) -> Result<RequirementReport> {
+        let signatures = match self {
+            PolicyReqType::Accept => Vec::new(),
+            PolicyReqType::Reject => return Err(anyhow!(r#"The policy is "reject""#)),
+            PolicyReqType::SimpleSigning(inner) => inner.allows_image(image, auth).await?,
+            PolicyReqType::Cosign(inner) => inner.allows_image(image, auth).await?,
+        };
+
+        Ok(RequirementReport {
+            requirement_type: self.type_name().to_string(),
+            signatures,
+        })
+    }
+
+    /// Name of the requirement type in the policy file.
+    pub fn type_name(&self) -> &'static str {
         match self {
-            PolicyReqType::Accept => Ok(()),
-            PolicyReqType::Reject => Err(anyhow!(r#"The policy is "reject""#)),
-            PolicyReqType::SimpleSigning(inner) => inner.allows_image(image, auth).await,
-            PolicyReqType::Cosign(inner) => inner.allows_image(image, auth).await,
+            PolicyReqType::Accept => "insecureAcceptAnything",
+            PolicyReqType::Reject => "reject",
+            PolicyReqType::SimpleSigning(_) => "signedBy",
+            PolicyReqType::Cosign(_) => "sigstoreSigned",
         }
     }
 
@@ -62,6 +62,18 @@ impl PolicyReqMatchType {
         PolicyReqMatchType::MatchExact
     }
 
+    /// The `type` of the match policy in the policy file.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            PolicyReqMatchType::MatchExact => "matchExact",
+            PolicyReqMatchType::MatchRepoDigestOrExact => "matchRepoDigestOrExact",
+            PolicyReqMatchType::MatchRepository => "matchRepository",
+            PolicyReqMatchType::ExactReference { .. } => "exactReference",
+            PolicyReqMatchType::ExactRepository { .. } => "exactRepository",
+            PolicyReqMatchType::RemapIdentity { .. } => "remapIdentity",
+        }
+    }
+
     /// Check whether matches reference
     pub fn matches_docker_reference(
         &self,
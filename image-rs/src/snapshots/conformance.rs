@@ -0,0 +1,655 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conformance suite of the [`Snapshotter`]s.
+//!
+//! Every snapshotter merges the same unpacked layers into a rootfs, but
+//! each does it its own way, e.g. overlayfs in the kernel, eccfs through
+//! roimages, or by copying the layers. The suite gives a snapshotter layer
+//! dirs of synthetic images covering the corner cases of the OCI layer
+//! format (whiteouts, opaque dirs, hardlinks, symlinks pointing out of the
+//! rootfs, xattrs, huge files and unicode names), mounts them, and checks
+//! the mounted rootfs against what the image spec expects:
+//!
+//! ```ignore
+//! let reports = Suite::default().run(&mut snapshotter, work_dir);
+//! ```
+//!
+//! The layer dirs are laid out as [`crate::unpack::unpack`] leaves them,
+//! with whiteouts as `.wh.` files. Cases whose layers can't be built on the
+//! filesystem of the work dir, e.g. xattrs on a filesystem without them,
+//! are skipped.
+//!
+//! Snapshotters in other crates get the suite with the
+//! `snapshot-conformance` feature.
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use std::ffi::CString;
+use std::fs;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{symlink, MetadataExt};
+use std::path::{Path, PathBuf};
+
+use crate::snapshots::Snapshotter;
+
+/// Size of the huge file of the suite if not set, beyond 4 GiB so that
+/// 32-bit sizes and offsets are caught. The file is sparse in the layer.
+pub const DEFAULT_HUGE_FILE_SIZE: u64 = (4 << 30) + 4096;
+
+const HUGE_FILE_TAIL: &[u8] = b"end of huge file";
+
+/// An entry of a synthetic layer, with its path relative to the layer.
+#[derive(Clone, Debug)]
+enum Entry {
+    Dir(&'static str),
+    File(&'static str, &'static [u8]),
+    /// A sparse file of the huge file size, ending with [`HUGE_FILE_TAIL`].
+    Huge(&'static str),
+    Symlink(&'static str, &'static str),
+    /// A symlink to the `outside` dir of the case, out of the rootfs.
+    SymlinkOutside(&'static str),
+    /// A hardlink to a file of the same layer.
+    Hardlink(&'static str, &'static str),
+    Whiteout(&'static str),
+    OpaqueDir(&'static str),
+    Xattr(&'static str, &'static str, &'static [u8]),
+}
+
+/// What the mounted rootfs must hold, with paths relative to it.
+#[derive(Clone, Debug)]
+enum Expect {
+    Dir(&'static str),
+    File(&'static str, &'static [u8]),
+    /// A file of the huge file size, ending with [`HUGE_FILE_TAIL`].
+    Huge(&'static str),
+    Symlink(&'static str, &'static str),
+    /// The two paths are the same file.
+    SameFile(&'static str, &'static str),
+    Absent(&'static str),
+    Xattr(&'static str, &'static str, &'static [u8]),
+    /// Nothing was written to the `outside` dir of the case.
+    NoEscape,
+}
+
+struct Case {
+    name: &'static str,
+    /// Bottom layer first.
+    layers: Vec<Vec<Entry>>,
+    expects: Vec<Expect>,
+}
+
+fn cases() -> Vec<Case> {
+    use Entry as E;
+    use Expect as X;
+
+    vec![
+        Case {
+            name: "regular_files",
+            layers: vec![
+                vec![
+                    E::Dir("etc"),
+                    E::File("etc/hostname", b"lower"),
+                    E::Dir("usr/bin"),
+                    E::File("usr/bin/sh", b"#!sh"),
+                    E::File("empty", b""),
+                ],
+                vec![E::Dir("etc"), E::File("etc/hostname", b"upper")],
+            ],
+            expects: vec![
+                X::File("etc/hostname", b"upper"),
+                X::File("usr/bin/sh", b"#!sh"),
+                X::File("empty", b""),
+            ],
+        },
+        Case {
+            name: "whiteout",
+            layers: vec![
+                vec![
+                    E::File("etc/passwd", b"root"),
+                    E::File("etc/shadow", b"secret"),
+                    E::File("var/log/messages", b"log"),
+                ],
+                vec![
+                    E::Whiteout("etc/shadow"),
+                    E::Whiteout("var/log"),
+                    E::Whiteout("never/existed"),
+                ],
+            ],
+            expects: vec![
+                X::File("etc/passwd", b"root"),
+                X::Absent("etc/shadow"),
+                X::Absent("etc/.wh.shadow"),
+                X::Absent("var/log"),
+                X::Dir("var"),
+            ],
+        },
+        Case {
+            name: "opaque_dir",
+            layers: vec![
+                vec![
+                    E::File("opt/app/old", b"old"),
+                    E::File("opt/other", b"other"),
+                ],
+                vec![E::OpaqueDir("opt/app"), E::File("opt/app/new", b"new")],
+            ],
+            expects: vec![
+                X::Absent("opt/app/old"),
+                X::Absent("opt/app/.wh..wh..opq"),
+                X::File("opt/app/new", b"new"),
+                X::File("opt/other", b"other"),
+            ],
+        },
+        Case {
+            name: "file_replaces_dir",
+            layers: vec![
+                vec![E::File("srv/data/file", b"file"), E::File("run", b"file")],
+                vec![E::File("srv/data", b"now a file"), E::Dir("run")],
+            ],
+            expects: vec![X::File("srv/data", b"now a file"), X::Dir("run")],
+        },
+        Case {
+            name: "hardlinks",
+            layers: vec![vec![
+                E::File("bin/busybox", b"busybox"),
+                E::Hardlink("bin/ls", "bin/busybox"),
+                E::Hardlink("usr/bin/cat", "bin/busybox"),
+            ]],
+            expects: vec![
+                X::File("bin/ls", b"busybox"),
+                X::File("usr/bin/cat", b"busybox"),
+                X::SameFile("bin/ls", "bin/busybox"),
+                X::SameFile("usr/bin/cat", "bin/busybox"),
+            ],
+        },
+        Case {
+            name: "symlinks",
+            layers: vec![vec![
+                E::File("bin/busybox", b"busybox"),
+                E::Symlink("bin/sh", "busybox"),
+                E::Symlink("etc/mtab", "/proc/mounts"),
+                E::Symlink("dangling", "../../nowhere"),
+            ]],
+            expects: vec![
+                X::Symlink("bin/sh", "busybox"),
+                X::Symlink("etc/mtab", "/proc/mounts"),
+                X::Symlink("dangling", "../../nowhere"),
+            ],
+        },
+        Case {
+            name: "symlink_escape",
+            layers: vec![
+                vec![E::SymlinkOutside("escape"), E::SymlinkOutside("etc")],
+                vec![E::File("escape/pwned", b"pwned"), E::Dir("etc")],
+                vec![E::File("etc/pwned", b"pwned")],
+            ],
+            expects: vec![
+                X::Dir("escape"),
+                X::File("escape/pwned", b"pwned"),
+                X::File("etc/pwned", b"pwned"),
+                X::NoEscape,
+            ],
+        },
+        Case {
+            name: "xattrs",
+            layers: vec![
+                vec![
+                    E::File("etc/signed", b"signed"),
+                    E::Xattr("etc/signed", "user.coco.test", b"lower"),
+                    E::Dir("data"),
+                    E::Xattr("data", "user.coco.dir", b"dir"),
+                ],
+                vec![
+                    E::File("etc/signed", b"signed again"),
+                    E::Xattr("etc/signed", "user.coco.test", b"upper"),
+                ],
+            ],
+            expects: vec![
+                X::Xattr("etc/signed", "user.coco.test", b"upper"),
+                X::Xattr("data", "user.coco.dir", b"dir"),
+            ],
+        },
+        Case {
+            name: "huge_file",
+            layers: vec![vec![E::Huge("var/lib/huge.img")]],
+            expects: vec![X::Huge("var/lib/huge.img")],
+        },
+        Case {
+            name: "unicode_names",
+            layers: vec![vec![
+                E::File("日本語/ファイル.txt", b"ja"),
+                // the same name composed and decomposed are two files
+                E::File("caf\u{e9}", b"nfc"),
+                E::File("cafe\u{301}", b"nfd"),
+                E::File("\u{1f980}", b"crab"),
+                E::File("with space\tand tab", b"blank"),
+            ]],
+            expects: vec![
+                X::File("日本語/ファイル.txt", b"ja"),
+                X::File("caf\u{e9}", b"nfc"),
+                X::File("cafe\u{301}", b"nfd"),
+                X::File("\u{1f980}", b"crab"),
+                X::File("with space\tand tab", b"blank"),
+            ],
+        },
+    ]
+}
+
+/// Outcome of a case of the suite.
+#[derive(Debug)]
+pub enum Outcome {
+    Passed,
+
+    /// The layers of the case can't be built in the work dir, for the
+    /// given reason.
+    Skipped(String),
+
+    Failed(anyhow::Error),
+}
+
+/// Outcome of the case `case`.
+#[derive(Debug)]
+pub struct CaseReport {
+    pub case: &'static str,
+    pub outcome: Outcome,
+}
+
+/// The conformance suite, see the [module docs](self).
+#[derive(Clone, Debug)]
+pub struct Suite {
+    /// Size of the huge file, [`DEFAULT_HUGE_FILE_SIZE`] by default.
+    pub huge_file_size: u64,
+}
+
+impl Default for Suite {
+    fn default() -> Self {
+        Self {
+            huge_file_size: DEFAULT_HUGE_FILE_SIZE,
+        }
+    }
+}
+
+// why building the layers of a case failed
+enum BuildError {
+    Unsupported(String),
+    Failed(anyhow::Error),
+}
+
+impl From<anyhow::Error> for BuildError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Failed(e)
+    }
+}
+
+impl From<io::Error> for BuildError {
+    fn from(e: io::Error) -> Self {
+        Self::Failed(e.into())
+    }
+}
+
+impl Suite {
+    /// Names of the cases of the suite.
+    pub fn cases(&self) -> Vec<&'static str> {
+        cases().iter().map(|case| case.name).collect()
+    }
+
+    /// Run the cases against `snapshotter`, with their layers and rootfs
+    /// under `work_dir`, which must be empty or missing.
+    pub fn run(&self, snapshotter: &mut dyn Snapshotter, work_dir: &Path) -> Vec<CaseReport> {
+        cases()
+            .iter()
+            .map(|case| CaseReport {
+                case: case.name,
+                outcome: self.run_case(snapshotter, case, &work_dir.join(case.name)),
+            })
+            .collect()
+    }
+
+    /// Build the layer dirs of the case `name` under `dir`, bottom first,
+    /// for snapshotters which can't be driven through [`Snapshotter`] only,
+    /// e.g. to check the roimages eccfs builds of them. `None` if the layers
+    /// can't be built in `dir`.
+    pub fn build(&self, name: &str, dir: &Path) -> Result<Option<Vec<PathBuf>>> {
+        let case = cases()
+            .into_iter()
+            .find(|case| case.name == name)
+            .ok_or_else(|| anyhow!("no case {name}"))?;
+        match self.build_layers(&case, dir, &dir.join("outside")) {
+            Ok(layers) => Ok(Some(layers)),
+            Err(BuildError::Unsupported(_)) => Ok(None),
+            Err(BuildError::Failed(e)) => Err(e),
+        }
+    }
+
+    fn run_case(&self, snapshotter: &mut dyn Snapshotter, case: &Case, dir: &Path) -> Outcome {
+        let outside = dir.join("outside");
+        let layers = match self.build_layers(case, dir, &outside) {
+            Ok(layers) => layers,
+            Err(BuildError::Unsupported(reason)) => return Outcome::Skipped(reason),
+            Err(BuildError::Failed(e)) => {
+                return Outcome::Failed(e.context("build layers"));
+            }
+        };
+
+        // snapshotters take the top layer first
+        let layer_paths: Vec<String> = layers
+            .iter()
+            .rev()
+            .map(|p| p.display().to_string())
+            .collect();
+        let layer_paths: Vec<&str> = layer_paths.iter().map(String::as_str).collect();
+        let rootfs = dir.join("rootfs");
+        let mount_point = match snapshotter.mount(&layer_paths, &rootfs) {
+            Ok(mount_point) => mount_point,
+            Err(e) => return Outcome::Failed(e.context("mount")),
+        };
+
+        let checked = case
+            .expects
+            .iter()
+            .try_for_each(|expect| self.check(expect, &rootfs, &outside));
+        let unmounted = snapshotter.unmount(&mount_point);
+        match (checked, unmounted) {
+            (Ok(()), Ok(())) => Outcome::Passed,
+            (Err(e), _) => Outcome::Failed(e),
+            (Ok(()), Err(e)) => Outcome::Failed(e.context("unmount")),
+        }
+    }
+
+    // build the layer dirs of `case` under `dir`, bottom first
+    fn build_layers(
+        &self,
+        case: &Case,
+        dir: &Path,
+        outside: &Path,
+    ) -> std::result::Result<Vec<PathBuf>, BuildError> {
+        fs::create_dir_all(outside)?;
+        let mut layers = Vec::new();
+        for (i, entries) in case.layers.iter().enumerate() {
+            let layer = dir.join("layers").join(i.to_string());
+            fs::create_dir_all(&layer)?;
+            for entry in entries {
+                self.build_entry(&layer, entry, outside)?;
+            }
+            layers.push(layer);
+        }
+
+        Ok(layers)
+    }
+
+    fn build_entry(
+        &self,
+        layer: &Path,
+        entry: &Entry,
+        outside: &Path,
+    ) -> std::result::Result<(), BuildError> {
+        let create_parent = |path: &str| -> io::Result<PathBuf> {
+            let path = layer.join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            Ok(path)
+        };
+
+        match entry {
+            Entry::Dir(path) => fs::create_dir_all(layer.join(path))?,
+            Entry::File(path, data) => fs::write(create_parent(path)?, data)?,
+            Entry::Huge(path) => {
+                let mut file = fs::File::create(create_parent(path)?)?;
+                let tail_offset = self
+                    .huge_file_size
+                    .checked_sub(HUGE_FILE_TAIL.len() as u64)
+                    .ok_or_else(|| anyhow!("huge file size is too small"))?;
+                file.seek(SeekFrom::Start(tail_offset))?;
+                file.write_all(HUGE_FILE_TAIL)?;
+            }
+            Entry::Symlink(path, target) => symlink(target, create_parent(path)?)?,
+            Entry::SymlinkOutside(path) => symlink(outside, create_parent(path)?)?,
+            Entry::Hardlink(path, target) => {
+                fs::hard_link(layer.join(target), create_parent(path)?)?
+            }
+            Entry::Whiteout(path) => {
+                let path = Path::new(path);
+                let name = path
+                    .file_name()
+                    .ok_or_else(|| anyhow!("whiteout {} has no name", path.display()))?;
+                let whiteout = path.with_file_name(format!(".wh.{}", name.to_string_lossy()));
+                fs::write(create_parent(&whiteout.to_string_lossy())?, b"")?;
+            }
+            Entry::OpaqueDir(path) => {
+                fs::create_dir_all(layer.join(path))?;
+                fs::write(layer.join(path).join(".wh..wh..opq"), b"")?;
+            }
+            Entry::Xattr(path, name, value) => {
+                if let Err(e) = set_xattr(&layer.join(path), name, value) {
+                    return Err(match e.raw_os_error() {
+                        Some(libc::ENOTSUP) => BuildError::Unsupported(format!(
+                            "xattrs are not supported in the work dir: {e}"
+                        )),
+                        _ => BuildError::Failed(anyhow!("set xattr {name} of {path}: {e}")),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check(&self, expect: &Expect, rootfs: &Path, outside: &Path) -> Result<()> {
+        let metadata = |path: &str| {
+            fs::symlink_metadata(rootfs.join(path)).with_context(|| format!("{path} is missing"))
+        };
+
+        match expect {
+            Expect::Dir(path) => {
+                ensure!(metadata(path)?.is_dir(), "{path} is not a dir");
+            }
+            Expect::File(path, data) => {
+                ensure!(metadata(path)?.is_file(), "{path} is not a regular file");
+                let content = fs::read(rootfs.join(path))?;
+                ensure!(
+                    content == *data,
+                    "{path} holds {:?} instead of {:?}",
+                    String::from_utf8_lossy(&content),
+                    String::from_utf8_lossy(data)
+                );
+            }
+            Expect::Huge(path) => {
+                let metadata = metadata(path)?;
+                ensure!(metadata.is_file(), "{path} is not a regular file");
+                ensure!(
+                    metadata.len() == self.huge_file_size,
+                    "{path} has {} bytes instead of {}",
+                    metadata.len(),
+                    self.huge_file_size
+                );
+                let mut file = fs::File::open(rootfs.join(path))?;
+                file.seek(SeekFrom::End(-(HUGE_FILE_TAIL.len() as i64)))?;
+                let mut tail = vec![0; HUGE_FILE_TAIL.len()];
+                io::Read::read_exact(&mut file, &mut tail)?;
+                ensure!(tail == HUGE_FILE_TAIL, "{path} has a wrong tail");
+            }
+            Expect::Symlink(path, target) => {
+                ensure!(metadata(path)?.is_symlink(), "{path} is not a symlink");
+                let link = fs::read_link(rootfs.join(path))?;
+                ensure!(
+                    link == Path::new(target),
+                    "{path} links to {} instead of {target}",
+                    link.display()
+                );
+            }
+            Expect::SameFile(path, other) => {
+                let (a, b) = (metadata(path)?, metadata(other)?);
+                ensure!(
+                    (a.dev(), a.ino()) == (b.dev(), b.ino()),
+                    "{path} and {other} are not hardlinked"
+                );
+            }
+            Expect::Absent(path) => {
+                if fs::symlink_metadata(rootfs.join(path)).is_ok() {
+                    bail!("{path} is present");
+                }
+            }
+            Expect::Xattr(path, name, value) => {
+                metadata(path)?;
+                let got = get_xattr(&rootfs.join(path), name)
+                    .with_context(|| format!("get xattr {name} of {path}"))?;
+                ensure!(
+                    got.as_deref() == Some(*value),
+                    "xattr {name} of {path} is {got:?} instead of {value:?}"
+                );
+            }
+            Expect::NoEscape => {
+                let escaped: Vec<_> = fs::read_dir(outside)?
+                    .map(|entry| entry.map(|entry| entry.file_name()))
+                    .collect::<io::Result<_>>()?;
+                ensure!(escaped.is_empty(), "{escaped:?} written out of the rootfs");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn set_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let name = CString::new(name)?;
+    // SAFETY: the strings are nul-terminated and `value` outlives the call
+    let ret = unsafe {
+        libc::lsetxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+fn get_xattr(path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let name = CString::new(name)?;
+    let mut value = vec![0u8; 4096];
+    // SAFETY: the strings are nul-terminated and `value` has the given size
+    let ret = unsafe {
+        libc::lgetxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_mut_ptr().cast(),
+            value.len(),
+        )
+    };
+    if ret < 0 {
+        let e = io::Error::last_os_error();
+        return match e.raw_os_error() {
+            Some(libc::ENODATA) => Ok(None),
+            _ => Err(e),
+        };
+    }
+
+    value.truncate(ret as usize);
+    Ok(Some(value))
+}
+
+/// Run the suite against `snapshotter` in `work_dir`, panicking with the
+/// failed cases.
+pub fn assert_conformance(snapshotter: &mut dyn Snapshotter, work_dir: &Path) {
+    let failures: Vec<String> = Suite::default()
+        .run(snapshotter, work_dir)
+        .into_iter()
+        .filter_map(|report| match report.outcome {
+            Outcome::Failed(e) => Some(format!("{}: {e:#}", report.case)),
+            _ => None,
+        })
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "snapshotter failed conformance:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshots::MountPoint;
+
+    /// A snapshotter copying the layers into the rootfs, as
+    /// `occlum_unionfs` does before mounting it.
+    struct CopySnapshotter;
+
+    impl Snapshotter for CopySnapshotter {
+        fn mount(&mut self, layer_path: &[&str], mount_path: &Path) -> Result<MountPoint> {
+            crate::flatten::apply_layers(layer_path, mount_path, 2)?;
+            Ok(MountPoint {
+                r#type: "copy".into(),
+                mount_path: mount_path.to_path_buf(),
+                work_dir: mount_path.to_path_buf(),
+                aux_resources: Vec::new(),
+            })
+        }
+
+        fn unmount(&self, mount_point: &MountPoint) -> Result<()> {
+            fs::remove_dir_all(&mount_point.mount_path)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_suite() {
+        let work_dir = tempfile::tempdir().unwrap();
+        let suite = Suite {
+            huge_file_size: 1 << 20,
+        };
+        let reports = suite.run(&mut CopySnapshotter, work_dir.path());
+        assert_eq!(
+            reports.iter().map(|r| r.case).collect::<Vec<_>>(),
+            suite.cases()
+        );
+
+        for report in reports {
+            match (report.case, report.outcome) {
+                // copies of the layers break the hardlinks and lose the
+                // xattrs, which the suite must catch
+                ("hardlinks", Outcome::Failed(e)) => {
+                    assert!(format!("{e}").contains("not hardlinked"), "{e:#}")
+                }
+                ("xattrs", Outcome::Failed(_) | Outcome::Skipped(_)) => {}
+                (case, Outcome::Passed) => assert_ne!(case, "hardlinks"),
+                (case, outcome) => panic!("{case}: {outcome:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let rootfs = dir.path().join("rootfs");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(rootfs.join("etc")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(rootfs.join("etc/hostname"), b"coco").unwrap();
+        symlink("/proc/mounts", rootfs.join("mtab")).unwrap();
+
+        let suite = Suite::default();
+        let check = |expect| suite.check(&expect, &rootfs, &outside);
+        assert!(check(Expect::File("etc/hostname", b"coco")).is_ok());
+        assert!(check(Expect::File("etc/hostname", b"other")).is_err());
+        assert!(check(Expect::File("etc", b"")).is_err());
+        assert!(check(Expect::Dir("etc")).is_ok());
+        assert!(check(Expect::Absent("etc/passwd")).is_ok());
+        assert!(check(Expect::Absent("etc")).is_err());
+        assert!(check(Expect::Symlink("mtab", "/proc/mounts")).is_ok());
+        assert!(check(Expect::Symlink("mtab", "/proc/self/mounts")).is_err());
+        assert!(check(Expect::SameFile("etc/hostname", "etc/hostname")).is_ok());
+        assert!(check(Expect::NoEscape).is_ok());
+        fs::write(outside.join("pwned"), b"").unwrap();
+        assert!(check(Expect::NoEscape).is_err());
+    }
+}
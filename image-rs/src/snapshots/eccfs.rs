@@ -1,20 +1,1325 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
 use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
-use log::info;
-use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use anyhow::{anyhow, bail, Context, Result};
+use nix::errno::Errno;
 use nix::mount::MsFlags;
+use nix::sys::stat::{utimensat, UtimensatFlags};
+use nix::sys::time::TimeSpec;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use fs_extra::dir;
+use tokio_util::sync::CancellationToken;
+use walkdir::WalkDir;
+use zeroize::Zeroizing;
 
 use ocicrypt_rs::blockcipher::rand::rand_bytes;
 
-use crate::snapshots::{MountPoint, Snapshotter};
+use crate::config::{BuildVerification, EccfsConfig, HybridPolicy, ScratchBacking};
+use crate::pull::blob_id;
+use crate::snapshots::{AuxKind, AuxMount, AuxResource, MountPoint, Snapshotter};
+use crate::ERR_PULL_CANCELLED;
 
 const LD_LIB: &str = "ld-linux-x86-64.so.2";
 
+/// Dir the glibc of occlum is copied from into the occlum environment.
+const OCCLUM_GLIBC_DIR: &str = "/opt/occlum/glibc/lib";
+
+/// Libs of [`OCCLUM_GLIBC_DIR`] copied into the occlum environment.
+const OCCLUM_LIBS: [&str; 6] = [
+    "libc.so.6",
+    "libdl.so.2",
+    "libm.so.6",
+    "libpthread.so.0",
+    "libresolv.so.2",
+    "librt.so.1",
+];
+
+/// Dir under the data dir the occlum environment roimage is cached in for
+/// the lifetime of the process. Like [`PREFLIGHT_ID`], it is not a valid
+/// container id.
+const CACHE_DIR: &str = ".cache";
+
+/// Dir under [`CACHE_DIR`] the layer roimages shared by containers are kept
+/// in, see [`EccfsConfig::share_layers`]. Unlike the occlum environment
+/// ones, the roimages of an earlier process are kept as long as they are
+/// recorded in the [`SharedLayers`].
+const SHARED_LAYERS_DIR: &str = "layers";
+
+/// Dir the per-container scratch dirs the roimages are built in are under.
+const ECCFS_WORK_DIR: &str = "/eccfs_tmp";
+
+/// Dir the sefs holding the keys of the roimages of a container is mounted
+/// under.
+const KEYS_MOUNT_PATH: &str = "/keys";
+
+/// Dir the tmpfs the small layers of a container are converted in, in
+/// hybrid mode, are mounted under.
+const ECCFS_MEM_DIR: &str = "/eccfs_mem";
+
+const CRYPTSETUP_BIN: &str = "/usr/sbin/cryptsetup";
+
+const MKFS_EXT4_BIN: &str = "/usr/sbin/mkfs.ext4";
+
+/// Name of the dm-crypt mapping of the work dir with
+/// [`ScratchBacking::DmCrypt`].
+const SCRATCH_CRYPT_NAME: &str = "eccfs_scratch";
+
+/// Dir the host dirs of the sefs lower dirs are mounted under with hostfs,
+/// to create and remove the lower dirs, see [`sefs_lower_dir`].
+const SEFS_HOST_DIR: &str = "/eccfs_host";
+
+/// Annotation of an image manifest with the resource uri of the
+/// [`SuppliedKeys`] of the image, instead of the one configured. Only taken
+/// if the uri is in the `allowed_keys_uris` of the configuration.
+pub const ANNOTATION_ECCFS_KEYS: &str = "io.confidential-containers.eccfs.keys";
+
+/// Name the probe dirs of [`EccOvlFs::preflight`] take instead of the one
+/// of a container.
+const PREFLIGHT_ID: &str = ".preflight";
+
+/// Containers being mounted by this process. A second mount of the same
+/// container would clobber the roimages and keys of the first one.
+static MOUNTING: Mutex<BTreeSet<OsString>> = Mutex::new(BTreeSet::new());
+
+/// Device the dm-crypt mapping of the work dir was opened on by this
+/// process, if any. It is opened once and shared by all containers.
+static SCRATCH_CRYPT_DEVICE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// The cache dirs the roimages left by an earlier process were removed from,
+/// once per dir.
+static CACHE_RESET: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+/// The occlum environment roimage, built once and shared by all containers.
+#[derive(Clone, Debug)]
+pub struct OcclumEnv {
+    pub roimage: PathBuf,
+    pub digest: String,
+    mode_entry: String,
+}
+
+/// A layer roimage built once and linked read-only into the eccfs dirs of
+/// the containers using the layer.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct SharedLayer {
+    roimage: PathBuf,
+    digest: String,
+
+    /// The sha256 of the key of the roimage, not to record the key itself.
+    key_digest: String,
+    mode_entry: String,
+
+    /// Containers the roimage is linked into.
+    users: BTreeSet<String>,
+}
+
+/// The layer roimages shared by the containers, by the blob id of their
+/// layer. A roimage is removed once no container uses it. The table is kept
+/// in the [`crate::meta_store::MetaStore`] of the client, so that it is
+/// shared by the snapshotters built for its pulls, and outlives the process
+/// with it.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct SharedLayers(Arc<Mutex<BTreeMap<String, SharedLayer>>>);
+
+impl SharedLayers {
+    // Share the roimage of the layer `id` with `cid`, if one was built with
+    // the key of `key_digest`. Fails if it was built with another key: the
+    // container has to build its own roimage then. A roimage gone since it
+    // was recorded is forgotten, to be built again.
+    fn acquire(&self, id: &str, cid: &OsStr, key_digest: &str) -> Result<Option<SharedLayer>> {
+        let mut shared = self.lock();
+        match shared.get_mut(id) {
+            Some(layer) if !layer.roimage.exists() => {
+                warn!("shared roimage {:?} is gone", layer.roimage);
+                shared.remove(id);
+                Ok(None)
+            }
+            Some(layer) if layer.key_digest == key_digest => {
+                layer.users.insert(cid.to_string_lossy().into_owned());
+                Ok(Some(layer.clone()))
+            }
+            Some(_) => bail!("layer {} is shared with another key", id),
+            None => Ok(None),
+        }
+    }
+
+    // register the roimage `layer` of the layer `id`, or share the one
+    // another container registered meanwhile with the same key instead. None
+    // if that one has another key.
+    fn register(&self, id: &str, layer: SharedLayer) -> Option<SharedLayer> {
+        let mut shared = self.lock();
+        match shared.get_mut(id) {
+            Some(registered) if registered.key_digest == layer.key_digest => {
+                if let Err(e) = fs::remove_file(&layer.roimage) {
+                    warn!("failed to remove {:?}: {}", layer.roimage, e);
+                }
+                registered.users.extend(layer.users);
+                Some(registered.clone())
+            }
+            Some(_) => None,
+            None => {
+                shared.insert(id.to_string(), layer.clone());
+                Some(layer)
+            }
+        }
+    }
+
+    // stop sharing the layer roimages with `cid`, removing the ones no
+    // container uses anymore
+    fn release(&self, cid: &OsStr) -> Result<()> {
+        let cid = cid.to_string_lossy();
+        let mut unused = Vec::new();
+        self.lock().retain(|_, layer| {
+            layer.users.remove(cid.as_ref());
+            if layer.users.is_empty() {
+                unused.push(layer.roimage.clone());
+            }
+            !layer.users.is_empty()
+        });
+
+        for roimage in unused {
+            debug!("removing unused shared roimage {:?}", roimage);
+            match fs::remove_file(&roimage) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // the roimages shared
+    fn roimages(&self) -> BTreeSet<PathBuf> {
+        self.lock()
+            .values()
+            .map(|layer| layer.roimage.clone())
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, SharedLayer>> {
+        self.0.lock().expect("shared layers poisoned")
+    }
+}
+
+/// Keys supplied by the operator, e.g. delivered by the KBS, instead of
+/// the generated ones, so that the images of a container can be mounted
+/// or inspected offline later with the same keys. They are given as JSON
+/// with the hex encoded keys:
+///
+/// ```json
+/// {
+///     "rw": "<key of the rw image>",
+///     "occlum_env": "<key of the occlum environment roimage>",
+///     "layers": {"sha256:<layer digest>": "<key of the roimage of the layer>"}
+/// }
+/// ```
+///
+/// Every layer converted to a roimage needs a key. The rw image and the
+/// occlum environment get generated keys if theirs are not given.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SuppliedKeys {
+    pub rw: Option<[u8; 16]>,
+    pub occlum_env: Option<[u8; 16]>,
+
+    /// Keys of the roimages by the digest of their layer in the manifest.
+    pub layers: HashMap<String, [u8; 16]>,
+}
+
+impl SuppliedKeys {
+    pub fn from_json(data: &[u8]) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Encoded {
+            #[serde(default)]
+            rw: Option<String>,
+            #[serde(default)]
+            occlum_env: Option<String>,
+            #[serde(default)]
+            layers: HashMap<String, String>,
+        }
+
+        let encoded: Encoded =
+            serde_json::from_slice(data).map_err(|e| anyhow!("invalid eccfs keys: {}", e))?;
+        let layers = encoded
+            .layers
+            .iter()
+            .map(|(digest, key)| Ok((digest.clone(), decode_key(key)?)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            rw: encoded.rw.as_deref().map(decode_key).transpose()?,
+            occlum_env: encoded.occlum_env.as_deref().map(decode_key).transpose()?,
+            layers,
+        })
+    }
+
+    // key of the layer unpacked to `layer_path`, whose name is the blob id
+    // of the layer digest
+    fn layer(&self, layer_path: &Path) -> Result<[u8; 16]> {
+        let name = layer_path.file_name().unwrap_or_default();
+        self.layers
+            .iter()
+            .find(|(digest, _)| OsStr::new(&blob_id(digest)) == name)
+            .map(|(_, key)| *key)
+            .ok_or_else(|| anyhow!("no eccfs key supplied for layer {}", layer_path.display()))
+    }
+}
+
+fn decode_key(key: &str) -> Result<[u8; 16]> {
+    let key = hex::decode(key).map_err(|e| anyhow!("invalid eccfs key: {}", e))?;
+    key.as_slice()
+        .try_into()
+        .map_err(|_| anyhow!("eccfs key must be 16 bytes, got {}", key.len()))
+}
+
 #[derive(Debug)]
 pub struct EccOvlFs {
     pub data_dir: PathBuf,
+
+    /// Fixed key the roimage keys are derived from. If set, roimages are
+    /// built deterministically: the same layers always give the same
+    /// roimages. Otherwise every roimage gets a random key.
+    pub build_key: Option<[u8; 16]>,
+
+    /// Timestamp the layer files are normalized to in deterministic mode.
+    pub source_date_epoch: i64,
+
+    /// Digests of the roimages built by the last mount, in layer order
+    /// (the occlum environment first).
+    pub roimage_digests: Vec<String>,
+
+    /// Timing and sizes of the layers built by the last mount.
+    pub build_report: Option<BuildReport>,
+
+    /// The cached occlum environment, built by the first mount.
+    pub occlum_env: Option<OcclumEnv>,
+
+    /// Keys supplied instead of the generated ones. They take precedence
+    /// over the ones derived in deterministic mode.
+    pub supplied_keys: Option<SuppliedKeys>,
+
+    /// Keep the roimages of a container on unmount, for cached reuse. The
+    /// key material and the scratch data are removed anyway.
+    pub retain_artifacts: bool,
+
+    /// If set, small layers are converted in memory instead of on the
+    /// scratch storage.
+    pub hybrid: Option<HybridPolicy>,
+
+    /// Storage backing the work dir the roimages are built in.
+    pub scratch: ScratchBacking,
+
+    /// Share the layer roimages with the other containers using the same
+    /// layers, instead of building them per container. Only roimages whose
+    /// keys are derived in deterministic mode, or supplied, are shared: a
+    /// random key is never the one of another container.
+    pub share_layers: bool,
+
+    /// The layer roimages shared, see [`SharedLayers`].
+    pub shared_layers: SharedLayers,
+
+    /// If set, the roimages are read back and checked against their
+    /// sources once built.
+    pub verify: Option<BuildVerification>,
+
+    /// Build the roimages of the layers without encryption, only integrity
+    /// protected, e.g. for image volumes holding no secrets. The layers are
+    /// not shared then.
+    pub integrity_only: bool,
+
+    /// Build no rw image, so that the enclave can only mount the roimages
+    /// read-only, e.g. for image volumes.
+    pub read_only: bool,
+
+    /// Name of the resources of the mounts instead of the container id, the
+    /// name of the bundle dir, e.g. for image volumes, whose parent dirs are
+    /// not their own.
+    pub name: Option<OsString>,
+}
+
+/// Stats of the build of a layer of a container.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LayerBuildStats {
+    /// Position of the layer in the image, from 1.
+    pub index: usize,
+
+    /// The layer was converted in memory in hybrid mode instead of on the
+    /// scratch storage.
+    pub in_memory: bool,
+
+    /// Milliseconds the conversion took.
+    pub millis: u64,
+
+    /// Unpacked size in bytes of the layer.
+    pub input_bytes: u64,
+
+    /// Size in bytes of the roimage.
+    pub output_bytes: u64,
+
+    /// Output bytes per input byte: below 1 the compression of the roimage
+    /// saved more than its encryption and integrity metadata cost.
+    pub ratio: f64,
+}
+
+impl LayerBuildStats {
+    fn new(index: usize, in_memory: bool, elapsed: Duration, input: u64, output: u64) -> Self {
+        Self {
+            index,
+            in_memory,
+            millis: elapsed.as_millis() as u64,
+            input_bytes: input,
+            output_bytes: output,
+            ratio: if input == 0 {
+                1.0
+            } else {
+                output as f64 / input as f64
+            },
+        }
+    }
+}
+
+/// Stats of the build of the layers of a container by a mount, to find
+/// the layers slowing it down or blowing up in size.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct BuildReport {
+    /// Stats of the layers, in layer order.
+    pub layers: Vec<LayerBuildStats>,
+
+    /// Milliseconds the build of all the layers took.
+    pub millis: u64,
+}
+
+impl BuildReport {
+    /// The layer whose build took longest.
+    pub fn slowest(&self) -> Option<&LayerBuildStats> {
+        self.layers.iter().max_by_key(|layer| layer.millis)
+    }
+
+    /// Total unpacked size in bytes of the layers.
+    pub fn input_bytes(&self) -> u64 {
+        self.layers.iter().map(|layer| layer.input_bytes).sum()
+    }
+
+    /// Total size in bytes of the roimages.
+    pub fn output_bytes(&self) -> u64 {
+        self.layers.iter().map(|layer| layer.output_bytes).sum()
+    }
+}
+
+/// Outcome of a check of [`EccOvlFs::preflight`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PreflightCheck {
+    /// What was checked, e.g. `keys`.
+    pub name: &'static str,
+
+    /// Why the check failed, if it did.
+    pub error: Option<String>,
+}
+
+/// Outcome of the checks of [`EccOvlFs::preflight`], in the order they ran.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PreflightReport {
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    // run the check `name`, returning whether it passed
+    fn check(&mut self, name: &'static str, check: impl FnOnce() -> Result<()>) -> bool {
+        let error = check().err().map(|e| format!("{:#}", e));
+        let passed = error.is_none();
+        self.checks.push(PreflightCheck { name, error });
+        passed
+    }
+
+    /// Whether all the checks passed.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.error.is_none())
+    }
+
+    /// Get an error listing the failed checks, if any.
+    pub fn into_result(self) -> Result<()> {
+        let failed: Vec<_> = self
+            .checks
+            .iter()
+            .filter_map(|check| {
+                let error = check.error.as_ref()?;
+                Some(format!("{}: {}", check.name, error))
+            })
+            .collect();
+        if !failed.is_empty() {
+            bail!("eccfs preflight failed: {}", failed.join("; "));
+        }
+
+        Ok(())
+    }
+}
+
+/// Error returned when a roimage does not read back as the dir it was
+/// built from, see [`BuildVerification`]. The mount fails with it.
+#[derive(Debug)]
+pub struct RoImageMismatch {
+    /// The roimage.
+    pub roimage: PathBuf,
+
+    /// Entry that differs, relative to the root of the dir. None if the
+    /// roimage does not open with its key and root MAC.
+    pub entry: Option<PathBuf>,
+
+    /// How it differs.
+    pub reason: String,
+}
+
+impl fmt::Display for RoImageMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.entry {
+            Some(entry) => write!(
+                f,
+                "roimage {} does not match its source at {}: {}",
+                self.roimage.display(),
+                entry.display(),
+                self.reason
+            ),
+            None => write!(
+                f,
+                "roimage {} does not open with its key: {}",
+                self.roimage.display(),
+                self.reason
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RoImageMismatch {}
+
+/// Where a layer of a container goes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LayerTarget {
+    RoImage,
+    Memory,
+}
+
+impl EccOvlFs {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self {
+            data_dir,
+            build_key: None,
+            source_date_epoch: 0,
+            roimage_digests: Vec::new(),
+            build_report: None,
+            occlum_env: None,
+            supplied_keys: None,
+            retain_artifacts: false,
+            hybrid: None,
+            scratch: ScratchBacking::default(),
+            share_layers: false,
+            shared_layers: SharedLayers::default(),
+            verify: None,
+            integrity_only: false,
+            read_only: false,
+            name: None,
+        }
+    }
+
+    /// Create an eccfs snapshotter building roimages deterministically.
+    pub fn new_deterministic(
+        data_dir: PathBuf,
+        build_key: [u8; 16],
+        source_date_epoch: i64,
+    ) -> Self {
+        Self {
+            data_dir,
+            build_key: Some(build_key),
+            source_date_epoch,
+            roimage_digests: Vec::new(),
+            build_report: None,
+            occlum_env: None,
+            supplied_keys: None,
+            retain_artifacts: false,
+            hybrid: None,
+            scratch: ScratchBacking::default(),
+            share_layers: false,
+            shared_layers: SharedLayers::default(),
+            verify: None,
+            integrity_only: false,
+            read_only: false,
+            name: None,
+        }
+    }
+
+    /// Apply the options of `config` not needed to construct the snapshotter.
+    pub fn configure(&mut self, config: &EccfsConfig) {
+        self.retain_artifacts = config.retain_artifacts;
+        self.hybrid = config.hybrid.clone();
+        self.scratch = config.scratch.clone();
+        if self.scratch == ScratchBacking::Disk {
+            warn!("the eccfs work dir is on disk, the plaintext layers may be visible to the host");
+        }
+        self.share_layers = config.share_layers;
+        self.verify = config.verify.clone();
+    }
+
+    /// Check that mounts can succeed here, so that a missing piece fails
+    /// the startup with a clear message instead of a pull half way: the
+    /// sources of the occlum environment, the sefs the keys are written to,
+    /// the hostfs the roimages are written to, the scratch storage, and a
+    /// build of a roimage and a rw image by eccfs_builder with them. The
+    /// build is skipped if the storage it needs failed.
+    ///
+    /// The checks go through the same paths as the mounts, with probe dirs
+    /// named `.preflight` instead of a container id, removed afterwards.
+    pub fn preflight(&self) -> PreflightReport {
+        let mut report = PreflightReport::default();
+        report.check("occlum_env", || {
+            for path in occlum_env_sources() {
+                if !path.is_file() {
+                    bail!("{:?} is missing", path);
+                }
+            }
+            Ok(())
+        });
+
+        let probe_dir = self.data_dir.join(PREFLIGHT_ID);
+        let mount_path = probe_dir.join("rootfs");
+        let resources = aux_resources(OsStr::new(PREFLIGHT_ID), &mount_path, &self.scratch);
+        let [keys, roimages, scratch, _] = resources.as_slice() else {
+            unreachable!("eccfs has four auxiliary resources");
+        };
+
+        let keys_mounted = report.check("keys", || {
+            create_dir(&keys.path)?;
+            mount_aux(keys.mount.as_ref().expect("keys are on sefs"), &keys.path)?;
+            let written = fs::write(keys.path.join("key.txt"), PREFLIGHT_ID)
+                .map_err(|e| anyhow!("key dir {:?} is not writable: {}", keys.path, e));
+            nix::mount::umount(keys.path.as_path())?;
+            written
+        });
+        let roimages_mounted = report.check("roimages", || {
+            fs::create_dir_all(&mount_path)?;
+            mount_aux(
+                roimages.mount.as_ref().expect("roimages are on hostfs"),
+                &mount_path,
+            )
+        });
+        let scratch_ready = report.check("scratch", || {
+            // the dm-crypt mapping is only opened by the first mount, which
+            // would wipe the one of a running process
+            if let ScratchBacking::DmCrypt { device } = &self.scratch {
+                for bin in [CRYPTSETUP_BIN, MKFS_EXT4_BIN] {
+                    if !Path::new(bin).is_file() {
+                        bail!("{} is missing", bin);
+                    }
+                }
+                if !device.exists() {
+                    bail!("scratch device {:?} is missing", device);
+                }
+            }
+            mount_scratch(scratch)?;
+            prepare_work_dir(&scratch.path)
+        });
+        if roimages_mounted && scratch_ready {
+            report.check("eccfs_builder", || {
+                preflight_build(&mount_path, &scratch.path)
+            });
+        }
+
+        report.check("cleanup", || {
+            if keys_mounted {
+                release(keys)?;
+                fs::remove_dir(&keys.path)?;
+            }
+            if roimages_mounted {
+                clear_path(&mount_path)?;
+                nix::mount::umount(mount_path.as_path())?;
+            }
+            if scratch_ready {
+                release(scratch)?;
+                if scratch.path.exists() {
+                    fs::remove_dir(&scratch.path)?;
+                }
+            }
+            if probe_dir.exists() {
+                fs::remove_dir_all(&probe_dir)?;
+            }
+            Ok(())
+        });
+
+        report
+    }
+
+    // key of the given roimage
+    fn roimage_key(&self, name: &str) -> [u8; 16] {
+        match &self.build_key {
+            Some(build_key) => derive_key(build_key, name),
+            None => generate_random_key(),
+        }
+    }
+
+    // key of the occlum environment roimage
+    fn occlum_env_key(&self, name: &str) -> [u8; 16] {
+        match self.supplied_keys.as_ref().and_then(|keys| keys.occlum_env) {
+            Some(key) => key,
+            None => self.roimage_key(name),
+        }
+    }
+
+    // key of the roimage of the layer at `layer_path`. In deterministic
+    // mode it is derived from the digest of the layer, not from its
+    // position, so that a layer gets the same key in every image.
+    fn layer_key(&self, layer_path: &Path) -> Result<[u8; 16]> {
+        match &self.supplied_keys {
+            Some(keys) => keys.layer(layer_path),
+            None => Ok(self.roimage_key(&layer_key_name(layer_path)?)),
+        }
+    }
+
+    // key of the rw image, only ever supplied or random
+    fn rw_key(&self) -> [u8; 16] {
+        match self.supplied_keys.as_ref().and_then(|keys| keys.rw) {
+            Some(key) => key,
+            None => generate_random_key(),
+        }
+    }
+
+    // in deterministic mode, make sure no build time ends up in the roimage
+    fn prepare_dir(&self, dir: &Path) -> Result<()> {
+        if self.build_key.is_some() {
+            normalize_times(dir, self.source_date_epoch)?;
+        }
+
+        Ok(())
+    }
+
+    // with verification on, read back the roimage built from the dir
+    // `source` with `mode`, and check it against the dir
+    fn verify_build(
+        &self,
+        source: &Path,
+        roimage: &Path,
+        mode: &eccfs_builder::FSMode,
+    ) -> Result<()> {
+        let Some(verify) = &self.verify else {
+            return Ok(());
+        };
+
+        let started = Instant::now();
+        let mut reader = BuiltRoImage::open(roimage, mode).map_err(|e| RoImageMismatch {
+            roimage: roimage.to_path_buf(),
+            entry: None,
+            reason: format!("{:#}", e),
+        })?;
+        verify_entries(&mut reader, source, roimage, verify.samples)?;
+        debug!(
+            "roimage {:?} verified in {} ms",
+            roimage,
+            started.elapsed().as_millis()
+        );
+
+        Ok(())
+    }
+
+    // whether the roimages of the layers are shared, only if their keys are
+    // derived or supplied
+    fn shares_layers(&self) -> bool {
+        self.share_layers
+            && !self.integrity_only
+            && (self.build_key.is_some() || self.supplied_keys.is_some())
+    }
+
+    // the dir the roimages shared by containers are cached in, emptied of
+    // the ones of an earlier process on first use but the shared layers
+    // still used. It is under the data dir, so that they can be hard linked
+    // into the containers.
+    fn cache_dir(&self) -> Result<PathBuf> {
+        let cache_dir = self.data_dir.join(CACHE_DIR);
+        let mut reset = CACHE_RESET.lock().expect("cache reset poisoned");
+        if !reset.contains(&cache_dir) {
+            if cache_dir.exists() {
+                reset_cache_dir(&cache_dir, &self.shared_layers.roimages())?;
+            }
+            reset.insert(cache_dir.clone());
+        }
+        fs::create_dir_all(&cache_dir)?;
+
+        Ok(cache_dir)
+    }
+
+    // The occlum environment is the same for every container, so its
+    // roimage is only built by the first mount. The key stays the same as
+    // well: in deterministic mode it is derived from the same name anyway.
+    //
+    // The cached roimage is named after the container it is built for:
+    // other snapshotters of the process build their own one concurrently,
+    // and rewriting a cached roimage would rewrite the roimages of the
+    // containers it is hard linked into.
+    fn occlum_env(&mut self, cid: &OsStr, work_dir: &Path) -> Result<OcclumEnv> {
+        if let Some(env) = &self.occlum_env {
+            return Ok(env.clone());
+        }
+
+        let cache_dir = self.cache_dir()?;
+        let cache_dir = cache_dir.as_path();
+
+        let env_dir = work_dir.join("occlum_env");
+        fs::create_dir_all(&env_dir)?;
+        let name = roimage_name(0);
+        let cached_name = format!("{}-{}", cid.to_string_lossy(), name);
+        let roimage = cache_dir.join(&cached_name);
+        if roimage.exists() {
+            fs::remove_file(&roimage)?;
+        }
+        let built = create_environment(&env_dir)
+            .and_then(|_| self.prepare_dir(&env_dir))
+            .and_then(|_| {
+                eccfs_builder::ro::build_from_dir(
+                    &env_dir,
+                    cache_dir,
+                    Path::new(cached_name.as_str()),
+                    work_dir,
+                    Some(self.occlum_env_key(&name)),
+                )
+            })
+            .and_then(|mode| {
+                self.verify_build(&env_dir, &roimage, &mode)?;
+                Ok(mode)
+            });
+        clear_path(work_dir)?;
+
+        let mode = built?;
+        let env = OcclumEnv {
+            digest: roimage_digest(&roimage)?,
+            mode_entry: mode_entry(mode.is_encrypted(), mode.into_key_entry()),
+            roimage,
+        };
+        info!("occlum env roimage built: {}", env.digest);
+        self.occlum_env = Some(env.clone());
+
+        Ok(env)
+    }
+
+    // Link the shared roimage of the layer at `layer_path` to `target` for
+    // the container `cid`, building it first if no container shares it
+    // yet. None if the layer is shared with another key than the one of
+    // this container, which then builds its own roimage.
+    //
+    // Like the occlum environment, the roimage is named after the container
+    // it is built for, and the first one registered is shared.
+    fn shared_layer(
+        &self,
+        cid: &OsStr,
+        layer_path: &Path,
+        target: &Path,
+        work_dir: &Path,
+    ) -> Result<Option<SharedLayer>> {
+        let name = layer_key_name(layer_path)?;
+        let id = name.trim_end_matches(".roimage").to_string();
+        let key = self.layer_key(layer_path)?;
+        let key_digest = format!("{:x}", Sha256::digest(key));
+        let shared = match self.shared_layers.acquire(&id, cid, &key_digest) {
+            Err(e) => {
+                debug!("not sharing the roimage with {:?}: {}", cid, e);
+                return Ok(None);
+            }
+            Ok(Some(shared)) => shared,
+            Ok(None) => {
+                let cache_dir = self.cache_dir()?.join(SHARED_LAYERS_DIR);
+                fs::create_dir_all(&cache_dir)?;
+                let cache_dir = cache_dir.as_path();
+                let built_name = format!("{}-{}", cid.to_string_lossy(), name);
+                let roimage = cache_dir.join(&built_name);
+                let built = eccfs_builder::ro::build_from_dir(
+                    layer_path,
+                    cache_dir,
+                    Path::new(built_name.as_str()),
+                    work_dir,
+                    Some(key),
+                )
+                .and_then(|mode| {
+                    self.verify_build(layer_path, &roimage, &mode)?;
+                    Ok(mode)
+                });
+                clear_path(work_dir)?;
+                let mode = match built {
+                    Ok(mode) => mode,
+                    Err(e) => {
+                        if roimage.exists() {
+                            fs::remove_file(&roimage)?;
+                        }
+                        return Err(e);
+                    }
+                };
+
+                let layer = SharedLayer {
+                    digest: roimage_digest(&roimage)?,
+                    mode_entry: mode_entry(mode.is_encrypted(), mode.into_key_entry()),
+                    roimage,
+                    key_digest,
+                    users: BTreeSet::from([cid.to_string_lossy().into_owned()]),
+                };
+                match self.shared_layers.register(&id, layer.clone()) {
+                    Some(shared) => shared,
+                    // shared with another key meanwhile
+                    None => {
+                        fs::remove_file(&layer.roimage)?;
+                        return Ok(None);
+                    }
+                }
+            }
+        };
+
+        link_roimage(&shared.roimage, target)?;
+        Ok(Some(shared))
+    }
+}
+
+// remove what an earlier process left in the cache dir but the `shared`
+// layer roimages
+fn reset_cache_dir(cache_dir: &Path, shared: &BTreeSet<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(cache_dir)? {
+        let path = entry?.path();
+        if path.file_name() != Some(OsStr::new(SHARED_LAYERS_DIR)) {
+            remove_path(&path)?;
+            continue;
+        }
+
+        for roimage in fs::read_dir(&path)? {
+            let roimage = roimage?.path();
+            if !shared.contains(&roimage) {
+                remove_path(&roimage)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path)?.is_dir() {
+        true => fs::remove_dir_all(path)?,
+        false => fs::remove_file(path)?,
+    }
+
+    Ok(())
+}
+
+/// Marks a container as being mounted until dropped.
+#[derive(Debug)]
+struct MountGuard(OsString);
+
+impl MountGuard {
+    fn acquire(cid: &OsStr) -> Result<Self> {
+        let mut mounting = MOUNTING.lock().expect("mount registry poisoned");
+        if !mounting.insert(cid.to_os_string()) {
+            bail!("container {:?} is already being mounted", cid);
+        }
+
+        Ok(Self(cid.to_os_string()))
+    }
+}
+
+impl Drop for MountGuard {
+    fn drop(&mut self) {
+        if let Ok(mut mounting) = MOUNTING.lock() {
+            mounting.remove(&self.0);
+        }
+    }
+}
+
+// create the scratch dir of a container, or empty it if an earlier mount
+// left something behind
+fn prepare_work_dir(work_dir: &Path) -> Result<()> {
+    if !work_dir.exists() {
+        fs::create_dir_all(work_dir)?;
+    } else {
+        clear_path(work_dir)?;
+    }
+
+    Ok(())
+}
+
+// decide, in layer order, which of the layers with the given unpacked sizes
+// are converted in memory
+fn plan_layers(sizes: &[u64], policy: Option<&HybridPolicy>) -> Vec<LayerTarget> {
+    let Some(policy) = policy else {
+        return vec![LayerTarget::RoImage; sizes.len()];
+    };
+
+    let mut budget = policy.memory_budget.unwrap_or(u64::MAX);
+    sizes
+        .iter()
+        .map(|&size| {
+            if size < policy.small_layer_threshold && size <= budget {
+                budget -= size;
+                LayerTarget::Memory
+            } else {
+                LayerTarget::RoImage
+            }
+        })
+        .collect()
+}
+
+// total size of the files under dir
+fn tree_size(dir: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let meta = fs::symlink_metadata(&path)?;
+        if meta.is_dir() {
+            size += tree_size(&path)?;
+        } else {
+            size += meta.len();
+        }
+    }
+
+    Ok(size)
+}
+
+// key entry of an eccfs image in the key file of the container
+fn mode_entry(encrypted: bool, key_entry: impl AsRef<[u8]>) -> String {
+    let s = if encrypted { "enc" } else { "int" };
+    format!("{}-{}", s, hex::encode_upper(key_entry))
+}
+
+// the key file of a container: the key entries of the rw image, unless it
+// is read-only, and of the roimages, in layer order, separated by ':', which no entry may hold
+fn key_file(mode_entries: &[String]) -> Result<String> {
+    for entry in mode_entries {
+        let valid = match entry.split_once('-') {
+            Some(("enc" | "int", key)) => {
+                !key.is_empty() && key.bytes().all(|b| b.is_ascii_hexdigit())
+            }
+            _ => false,
+        };
+        if !valid {
+            bail!("invalid eccfs key entry {:?}", entry);
+        }
+    }
+
+    Ok(mode_entries.join(":"))
+}
+
+// hard link the cached roimage into the eccfs dir of a container; copy it
+// if they are on different file systems
+fn link_roimage(cached: &Path, target: &Path) -> Result<()> {
+    match fs::hard_link(cached, target) {
+        Err(e) if e.raw_os_error() == Some(Errno::EXDEV as i32) => {
+            debug!("{:?} is on another file system, copying it", cached);
+            fs::copy(cached, target)?;
+        }
+        res => res.with_context(|| format!("link {:?} to {:?}", cached, target))?,
+    }
+
+    Ok(())
+}
+
+// the rootfs of a container is stored in different places according to its
+// cid, which is the name of the bundle dir
+fn container_id(mount_path: &Path) -> Result<&OsStr> {
+    mount_path
+        .parent()
+        .ok_or(anyhow!("parent do not exist"))?
+        .file_name()
+        .ok_or(anyhow!("Unknown error: file name parse fail"))
+}
+
+// the name the resources of `mount_point` were mounted with: the one of its
+// key dir if recorded, as a name given to the snapshotter of the mount is
+// not to the one unmounting it, or else the container id
+fn mount_name(mount_point: &MountPoint) -> Result<&OsStr> {
+    let keys = mount_point
+        .aux_resources
+        .iter()
+        .find(|resource| resource.kind == AuxKind::Secret)
+        .and_then(|resource| resource.path.file_name());
+    match keys {
+        Some(name) => Ok(name),
+        None => container_id(&mount_point.mount_path),
+    }
+}
+
+// open the dm-crypt mapping of the work dir on `device` with a random key,
+// and mount a fresh filesystem of it on the work dir, unless this process
+// did already. A mapping left by an earlier process is closed first: its
+// key is gone anyway.
+fn open_scratch_crypt(device: &Path) -> Result<()> {
+    let mut opened = SCRATCH_CRYPT_DEVICE
+        .lock()
+        .expect("scratch device poisoned");
+    match opened.as_deref() {
+        Some(opened) if opened == device => return Ok(()),
+        Some(opened) => bail!("eccfs scratch is already on {:?}", opened),
+        None => {}
+    }
+
+    let work_dir = Path::new(ECCFS_WORK_DIR);
+    match nix::mount::umount(work_dir) {
+        Ok(()) | Err(Errno::EINVAL) | Err(Errno::ENOENT) => {}
+        Err(e) => bail!("failed to umount {:?}: {}", work_dir, e),
+    }
+    let mapper = Path::new("/dev/mapper").join(SCRATCH_CRYPT_NAME);
+    if mapper.exists() {
+        run(Command::new(CRYPTSETUP_BIN).args(["close", SCRATCH_CRYPT_NAME]))?;
+    }
+
+    let mut key = Zeroizing::new([0u8; 64]);
+    rand_bytes(&mut *key)?;
+    let mut cryptsetup = Command::new(CRYPTSETUP_BIN)
+        .args([
+            "open",
+            "--type",
+            "plain",
+            "--cipher",
+            "aes-xts-plain64",
+            "--key-size",
+            "512",
+            "--key-file",
+            "-",
+        ])
+        .arg(device)
+        .arg(SCRATCH_CRYPT_NAME)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    io::Write::write_all(&mut cryptsetup.stdin.take().expect("stdin is piped"), &*key)?;
+    if !cryptsetup.wait()?.success() {
+        bail!("failed to open dm-crypt scratch on {:?}", device);
+    }
+
+    run(Command::new(MKFS_EXT4_BIN).arg("-q").arg(&mapper))?;
+    fs::create_dir_all(work_dir)?;
+    mount_aux(
+        &AuxMount {
+            source: mapper.to_string_lossy().into(),
+            fstype: "ext4".into(),
+            options: "".into(),
+            persistent: true,
+        },
+        work_dir,
+    )?;
+    info!("eccfs scratch on dm-crypt device {:?}", device);
+    *opened = Some(device.to_path_buf());
+
+    Ok(())
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let output = command.output()?;
+    if !output.status.success() {
+        bail!(
+            "{:?} failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(())
+}
+
+// the filesystem the scratch dir of the container `cid` is on, if it is
+// mounted per container
+fn scratch_mount(cid: &OsStr, scratch: &ScratchBacking) -> Option<AuxMount> {
+    match scratch {
+        // the dm-crypt one is shared by all containers, see
+        // open_scratch_crypt
+        ScratchBacking::Disk | ScratchBacking::DmCrypt { .. } => None,
+        ScratchBacking::Tmpfs { size } => Some(AuxMount {
+            source: "tmpfs".into(),
+            fstype: "tmpfs".into(),
+            options: match size {
+                Some(size) => format!("mode=0700,size={}", size),
+                None => "mode=0700".into(),
+            },
+            persistent: true,
+        }),
+        ScratchBacking::Sefs => Some(AuxMount {
+            source: "sefs".into(),
+            fstype: "sefs".into(),
+            options: format!(
+                "dir={}",
+                Path::new("/images")
+                    .join(cid)
+                    .join("scratch/sefs/lower")
+                    .display()
+            ),
+            persistent: true,
+        }),
+    }
+}
+
+// the resources a mount of the container `cid` allocates besides the rootfs:
+// the keys, the roimages, the scratch dir and the tmpfs the small layers
+// are converted in. All of them are per container, so that containers can be
+// mounted concurrently.
+fn aux_resources(cid: &OsStr, mount_path: &Path, scratch: &ScratchBacking) -> Vec<AuxResource> {
+    let host_dir = Path::new("/images").join(cid);
+    vec![
+        AuxResource {
+            kind: AuxKind::Secret,
+            path: Path::new(KEYS_MOUNT_PATH).join(cid),
+            mount: Some(AuxMount {
+                source: "sefs".into(),
+                fstype: "sefs".into(),
+                options: format!("dir={}", host_dir.join("keys/sefs/lower").display()),
+                persistent: false,
+            }),
+        },
+        AuxResource {
+            kind: AuxKind::Artifact,
+            path: mount_path.to_path_buf(),
+            mount: Some(AuxMount {
+                source: "hostfs".into(),
+                fstype: "hostfs".into(),
+                options: format!("dir={}", host_dir.join("eccfs").display()),
+                persistent: false,
+            }),
+        },
+        AuxResource {
+            kind: AuxKind::Scratch,
+            path: Path::new(ECCFS_WORK_DIR).join(cid),
+            mount: scratch_mount(cid, scratch),
+        },
+        AuxResource {
+            kind: AuxKind::Scratch,
+            path: Path::new(ECCFS_MEM_DIR).join(cid),
+            mount: Some(AuxMount {
+                source: "tmpfs".into(),
+                fstype: "tmpfs".into(),
+                options: "mode=0755".into(),
+                persistent: true,
+            }),
+        },
+    ]
+}
+
+// mount the filesystem of a resource to `target`
+fn mount_aux(mount: &AuxMount, target: &Path) -> Result<()> {
+    nix::mount::mount(
+        Some(mount.source.as_str()),
+        target,
+        Some(mount.fstype.as_str()),
+        MsFlags::empty(),
+        Some(mount.options.as_str()),
+    )
+    .map_err(|e| {
+        anyhow!(
+            "failed to mount {:?} to {:?}, with error: {}",
+            mount.source,
+            target,
+            e
+        )
+    })
+}
+
+// mount the filesystem of the scratch dir of a container, if it is mounted
+// per container, over an empty dir; a sefs gets a new lower dir
+fn mount_scratch(scratch: &AuxResource) -> Result<()> {
+    let Some(mount) = &scratch.mount else {
+        return Ok(());
+    };
+
+    release(scratch)?;
+    if mount.fstype == "sefs" {
+        sefs_lower_dir(mount, true)?;
+    }
+    fs::create_dir_all(&scratch.path)?;
+    mount_aux(mount, &scratch.path)
+}
+
+// remove the lower dir on the host of the sefs `mount`, and create it again
+// if `create`. The enclave only sees the host through hostfs, so the parent
+// of the lower dir is mounted with it meanwhile.
+fn sefs_lower_dir(mount: &AuxMount, create: bool) -> Result<()> {
+    let lower = mount
+        .options
+        .strip_prefix("dir=")
+        .map(Path::new)
+        .ok_or(anyhow!("sefs {:?} has no lower dir", mount.options))?;
+    let (Some(parent), Some(name)) = (lower.parent(), lower.file_name()) else {
+        bail!("invalid sefs lower dir {:?}", lower);
+    };
+
+    let staging = Path::new(SEFS_HOST_DIR).join(parent.strip_prefix("/").unwrap_or(parent));
+    fs::create_dir_all(&staging)?;
+    mount_aux(
+        &AuxMount {
+            source: "hostfs".into(),
+            fstype: "hostfs".into(),
+            options: format!("dir={}", parent.display()),
+            persistent: true,
+        },
+        &staging,
+    )?;
+    let res = (|| -> Result<()> {
+        let staged = staging.join(name);
+        match fs::remove_dir_all(&staged) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        if create {
+            fs::create_dir(&staged)?;
+        }
+        Ok(())
+    })();
+    nix::mount::umount(&staging)?;
+    fs::remove_dir(&staging)?;
+
+    res.with_context(|| format!("failed to set up sefs lower dir {:?}", lower))
+}
+
+// remove what a resource holds, mounting its filesystem for the time being
+fn release(resource: &AuxResource) -> Result<()> {
+    match &resource.mount {
+        Some(mount) if mount.persistent => {
+            match nix::mount::umount(&resource.path) {
+                Ok(()) | Err(Errno::EINVAL) | Err(Errno::ENOENT) => {}
+                Err(e) => bail!("failed to umount {:?}: {}", resource.path, e),
+            }
+            if resource.path.exists() {
+                fs::remove_dir_all(&resource.path)?;
+            }
+            // the data of a sefs scratch is on the host
+            if mount.fstype == "sefs" {
+                sefs_lower_dir(mount, false)?;
+            }
+            Ok(())
+        }
+        Some(mount) => {
+            mount_aux(mount, &resource.path)?;
+            let cleared = clear_path(&resource.path);
+            nix::mount::umount(&resource.path)?;
+            cleared
+        }
+        None if resource.path.exists() => clear_path(&resource.path),
+        None => Ok(()),
+    }
+}
+
+// release all the resources, the key material first, even if some of them
+// fail; the first error is returned
+fn release_all(resources: &[AuxResource], retain_artifacts: bool) -> Result<()> {
+    let mut res = Ok(());
+    for kind in [AuxKind::Secret, AuxKind::Artifact, AuxKind::Scratch] {
+        if kind == AuxKind::Artifact && retain_artifacts {
+            continue;
+        }
+
+        for resource in resources.iter().filter(|r| r.kind == kind) {
+            if let Err(e) = release(resource) {
+                warn!("failed to release {:?}: {:?}", resource.path, e);
+                if res.is_ok() {
+                    res = Err(e);
+                }
+            }
+        }
+    }
+
+    res
 }
 
 fn clear_path(mount_path: &Path) -> Result<()> {
@@ -49,6 +1354,265 @@ fn generate_random_key() -> [u8; 16] {
     key
 }
 
+// derives the key of a roimage from the build key, so that every roimage
+// still gets its own key
+fn derive_key(build_key: &[u8; 16], name: &str) -> [u8; 16] {
+    let mut hasher = Sha256::new();
+    hasher.update(build_key);
+    hasher.update(name.as_bytes());
+
+    let mut key: [u8; 16] = [0u8; 16];
+    key.copy_from_slice(&hasher.finalize()[..16]);
+
+    key
+}
+
+// set atime and mtime of everything under dir (and dir itself) to epoch
+fn normalize_times(dir: &Path, epoch: i64) -> Result<()> {
+    let ts = TimeSpec::new(epoch, 0);
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if fs::symlink_metadata(&path)?.is_dir() {
+            normalize_times(&path, epoch)?;
+        } else {
+            utimensat(None, &path, &ts, &ts, UtimensatFlags::NoFollowSymlink)?;
+        }
+    }
+    utimensat(None, dir, &ts, &ts, UtimensatFlags::NoFollowSymlink)?;
+
+    Ok(())
+}
+
+/// Returns the digest (`sha256:<hex>`) of the given roimage. For roimages
+/// built deterministically, this is what attestation evidence should refer
+/// to.
+pub fn roimage_digest(roimage: &Path) -> Result<String> {
+    let mut file = fs::File::open(roimage)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+
+    Ok(format!("sha256:{}", hex::encode(hasher.finalize())))
+}
+
+fn roimage_name(index: usize) -> String {
+    format!("{:04}.roimage", index)
+}
+
+// name the roimage of the layer at `layer_path` is keyed by: the layer
+// store names the dir of a layer after its digest, see `blob_id`
+fn layer_key_name(layer_path: &Path) -> Result<String> {
+    let id = layer_path
+        .file_name()
+        .ok_or_else(|| anyhow!("layer {:?} has no name", layer_path))?;
+    Ok(format!("{}.roimage", id.to_string_lossy()))
+}
+
+/// An entry of a dir or of the roimage built from it, as compared by
+/// [`BuildVerification`]: its kind, permission bits and owner, and the
+/// sha256 of the content of a file or the target of a symlink.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct EntrySummary {
+    kind: &'static str,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    content: String,
+}
+
+// read access to the entries of a built roimage
+trait RoImageReader {
+    // the entry at `path`, relative to the root, if any
+    fn entry(&mut self, path: &Path) -> Result<Option<EntrySummary>>;
+}
+
+// a roimage opened by eccfs_builder with the mode it was built with
+struct BuiltRoImage(eccfs_builder::ro::ROFS);
+
+impl BuiltRoImage {
+    // opening checks the root MAC of the roimage against the one of `mode`
+    fn open(roimage: &Path, mode: &eccfs_builder::FSMode) -> Result<Self> {
+        eccfs_builder::ro::ROFS::new(roimage, mode.clone(), false)
+            .map(Self)
+            .map_err(eccfs_error)
+    }
+}
+
+impl RoImageReader for BuiltRoImage {
+    fn entry(&mut self, path: &Path) -> Result<Option<EntrySummary>> {
+        use eccfs_builder::vfs::{FileSystem, FileType, ROOT_INODE_ID};
+
+        let mut iid = ROOT_INODE_ID;
+        for name in path.iter() {
+            match self.0.lookup(iid, name).map_err(eccfs_error)? {
+                Some(child) => iid = child,
+                None => return Ok(None),
+            }
+        }
+
+        let meta = self.0.get_meta(iid).map_err(eccfs_error)?;
+        let (kind, content) = match meta.ftype {
+            FileType::Dir => ("dir", String::new()),
+            FileType::Lnk => {
+                let target = self.0.iread_link(iid).map_err(eccfs_error)?;
+                ("symlink", target.to_string_lossy().into_owned())
+            }
+            _ => {
+                let mut hasher = Sha256::new();
+                let mut buf = vec![0; 64 * 1024];
+                let mut offset = 0;
+                loop {
+                    let read = self.0.iread(iid, offset, &mut buf).map_err(eccfs_error)?;
+                    if read == 0 {
+                        break;
+                    }
+                    hasher.update(&buf[..read]);
+                    offset += read;
+                }
+                ("file", hex::encode(hasher.finalize()))
+            }
+        };
+
+        Ok(Some(EntrySummary {
+            kind,
+            mode: meta.perm as u32 & 0o7777,
+            uid: meta.uid,
+            gid: meta.gid,
+            content,
+        }))
+    }
+}
+
+// the errors of eccfs_builder only implement Debug
+fn eccfs_error(e: impl fmt::Debug) -> anyhow::Error {
+    anyhow!("{:?}", e)
+}
+
+// the entry at `path` of a dir
+fn dir_entry(path: &Path) -> Result<EntrySummary> {
+    let meta = fs::symlink_metadata(path)?;
+    let (kind, content) = if meta.is_dir() {
+        ("dir", String::new())
+    } else if meta.file_type().is_symlink() {
+        let target = fs::read_link(path)?;
+        ("symlink", target.to_string_lossy().into_owned())
+    } else {
+        let mut hasher = Sha256::new();
+        io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+        ("file", hex::encode(hasher.finalize()))
+    };
+
+    Ok(EntrySummary {
+        kind,
+        mode: meta.mode() & 0o7777,
+        uid: meta.uid(),
+        gid: meta.gid(),
+        content,
+    })
+}
+
+// `count` of the files, dirs and symlinks under `dir`, relative to it and
+// spread evenly over the tree in name order; all of them if it has fewer
+fn sample_entries(dir: &Path, count: usize) -> Result<Vec<PathBuf>> {
+    let mut entries = Vec::new();
+    for entry in WalkDir::new(dir).min_depth(1).sort_by_file_name() {
+        let entry = entry?;
+        let file_type = entry.file_type();
+        if file_type.is_file() || file_type.is_dir() || file_type.is_symlink() {
+            entries.push(entry.path().strip_prefix(dir)?.to_path_buf());
+        }
+    }
+
+    if entries.len() <= count {
+        return Ok(entries);
+    }
+    Ok((0..count)
+        .map(|i| entries[i * entries.len() / count].clone())
+        .collect())
+}
+
+// compare a sample of `count` entries of the dir `source` with the ones
+// read from the roimage built from it
+fn verify_entries(
+    reader: &mut impl RoImageReader,
+    source: &Path,
+    roimage: &Path,
+    count: usize,
+) -> Result<()> {
+    for entry in sample_entries(source, count)? {
+        let expected = dir_entry(&source.join(&entry))?;
+        let reason = match reader.entry(&entry) {
+            Err(e) => Some(format!("unreadable in the roimage: {:#}", e)),
+            Ok(None) => Some("missing from the roimage".to_string()),
+            Ok(Some(found)) if found.kind != expected.kind => Some(format!(
+                "{} in the source, {} in the roimage",
+                expected.kind, found.kind
+            )),
+            Ok(Some(found)) if found.mode != expected.mode => Some(format!(
+                "mode {:o} in the source, {:o} in the roimage",
+                expected.mode, found.mode
+            )),
+            Ok(Some(found)) if (found.uid, found.gid) != (expected.uid, expected.gid) => {
+                Some(format!(
+                    "owner {}:{} in the source, {}:{} in the roimage",
+                    expected.uid, expected.gid, found.uid, found.gid
+                ))
+            }
+            Ok(Some(found)) if found.content != expected.content => {
+                Some(format!("{} content differs", expected.kind))
+            }
+            Ok(Some(_)) => None,
+        };
+
+        if let Some(reason) = reason {
+            return Err(RoImageMismatch {
+                roimage: roimage.to_path_buf(),
+                entry: Some(entry),
+                reason,
+            }
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+// the files of the guest copied into the occlum environment
+fn occlum_env_sources() -> Vec<PathBuf> {
+    let mut sources = vec![Path::new("/lib64").join(LD_LIB)];
+    sources.extend(
+        OCCLUM_LIBS
+            .iter()
+            .map(|lib| Path::new(OCCLUM_GLIBC_DIR).join(lib)),
+    );
+    sources
+}
+
+// build a roimage of a small dir and an empty rw image into `to_dir`, with
+// `work_dir` as scratch
+fn preflight_build(to_dir: &Path, work_dir: &Path) -> Result<()> {
+    let source = work_dir.join("source");
+    let build_dir = work_dir.join("build");
+    fs::create_dir_all(source.join("etc"))?;
+    fs::create_dir_all(&build_dir)?;
+    fs::write(source.join("etc").join("hostname"), PREFLIGHT_ID)?;
+
+    let name = roimage_name(0);
+    eccfs_builder::ro::build_from_dir(
+        &source,
+        to_dir,
+        Path::new(name.as_str()),
+        &build_dir,
+        Some(generate_random_key()),
+    )?;
+    roimage_digest(&to_dir.join(&name))?;
+    eccfs_builder::rw::create_empty(
+        &to_dir.join(ECCFS_RW_IMAGE_NAME),
+        Some(generate_random_key()),
+    )?;
+
+    Ok(())
+}
+
 fn create_environment(mount_path: &Path) -> Result<()> {
     let mut from_paths = Vec::new();
     let mut copy_options = dir::CopyOptions::new();
@@ -81,22 +1645,8 @@ fn create_environment(mount_path: &Path) -> Result<()> {
         .join("lib");
     fs::create_dir_all(&path_opt)?;
 
-    let occlum_lib = [
-        "libc.so.6",
-        "libdl.so.2",
-        "libm.so.6",
-        "libpthread.so.0",
-        "libresolv.so.2",
-        "librt.so.1",
-    ];
-
-    let ori_occlum_lib_path = Path::new("/")
-        .join("opt")
-        .join("occlum")
-        .join("glibc")
-        .join("lib");
-    for lib in occlum_lib.iter() {
-        from_paths.push(ori_occlum_lib_path.join(lib));
+    for lib in OCCLUM_LIBS.iter() {
+        from_paths.push(Path::new(OCCLUM_GLIBC_DIR).join(lib));
     }
     fs_extra::copy_items(&from_paths, &path_opt, &copy_options)?;
     from_paths.clear();
@@ -113,117 +1663,203 @@ const ECCFS_RW_IMAGE_NAME: &str = "run.rwimage";
 
 impl Snapshotter for EccOvlFs {
     fn mount(&mut self, layer_path: &[&str], mount_path: &Path) -> Result<MountPoint> {
-        let flags = MsFlags::empty();
+        self.mount_with_cancellation(layer_path, mount_path, &CancellationToken::new())
+    }
+
+    fn mount_with_cancellation(
+        &mut self,
+        layer_path: &[&str],
+        mount_path: &Path,
+        cancel: &CancellationToken,
+    ) -> Result<MountPoint> {
+        if cancel.is_cancelled() {
+            bail!(ERR_PULL_CANCELLED);
+        }
 
         if !mount_path.exists() {
             fs::create_dir_all(mount_path)?;
         }
 
-        // store the rootfs in different places according to the cid
-        let cid = mount_path
-            .parent()
-            .ok_or(anyhow!("parent do not exist"))?
-            .file_name()
-            .ok_or(anyhow!("Unknown error: file name parse fail"))?;
+        let cid = match &self.name {
+            Some(name) => name.as_os_str(),
+            None => container_id(mount_path)?,
+        };
+        let _guard = MountGuard::acquire(cid)?;
+        let resources = aux_resources(cid, mount_path, &self.scratch);
+        let [keys, roimages, scratch, memory] = resources.as_slice() else {
+            unreachable!("eccfs has four auxiliary resources");
+        };
 
-        let eccfs_dir_host = Path::new("/images").join(cid).join("eccfs");
-        let eccfs_work_dir = Path::new("/eccfs_tmp");
-        if !eccfs_work_dir.exists() {
-            fs::create_dir_all(eccfs_work_dir)?;
-        } else {
-            clear_path(&eccfs_work_dir)?;
-        }
-
-        nix::mount::mount(
-            Some("hostfs"),
-            mount_path,
-            Some("hostfs"),
-            flags,
-            Some(format!("dir={}", eccfs_dir_host.display()).as_str()),
-        ).map_err(|e| {
-            anyhow!(
-                "failed to mount {:?} to {:?}, with error: {}",
-                "hostfs",
-                mount_path,
-                e
-            )
-        })?;
+        let sizes = layer_path
+            .iter()
+            .map(|p| tree_size(Path::new(p)))
+            .collect::<Result<Vec<_>>>()?;
+        let targets = plan_layers(&sizes, self.hybrid.as_ref());
+        let memory_layers = targets.contains(&LayerTarget::Memory);
 
-        // clear the mount_path if there is something
-        clear_path(mount_path)?;
-
-        let mut fsmodes = Vec::new();
-
-        // build empty rw layer
-        let rw_mode = eccfs_builder::rw::create_empty(
-            &mount_path.join(ECCFS_RW_IMAGE_NAME),
-            Some(generate_random_key()),
-        )?;
-        fsmodes.push(rw_mode);
-
-        // occlum env is the first RO layer
-        let occlum_env = eccfs_work_dir.join("occlum_env");
-        fs::create_dir_all(&occlum_env)?;
-        create_environment(&occlum_env)?;
-        let fsmode = eccfs_builder::ro::build_from_dir(
-            &occlum_env,
-            &mount_path,
-            Path::new(format!("{:04}.roimage", 0).as_str()),
-            eccfs_work_dir,
-            Some(generate_random_key()),
-        )?;
-        fsmodes.push(fsmode);
-        clear_path(eccfs_work_dir)?;
-
-        // container image layers
-        for (i, p) in layer_path.iter().enumerate() {
-            let fsmode = eccfs_builder::ro::build_from_dir(
-                Path::new(p),
-                &mount_path,
-                Path::new(format!("{:04}.roimage", i+1).as_str()),
-                eccfs_work_dir,
-                Some(generate_random_key()),
-            )?;
-            fsmodes.push(fsmode);
-            clear_path(eccfs_work_dir)?;
+        // the plaintext layer content goes through the work dir, so its
+        // storage is set up before anything is written to it
+        if let ScratchBacking::DmCrypt { device } = &self.scratch {
+            open_scratch_crypt(device)?;
         }
+        let eccfs_work_dir = scratch.path.as_path();
+        mount_scratch(scratch)?;
+        prepare_work_dir(eccfs_work_dir)?;
 
-        nix::mount::umount(mount_path)?;
+        let occlum_env = self.occlum_env(cid, eccfs_work_dir)?;
 
-        let key_mount_options = format!(
-            "dir={}",
-            Path::new("/images")
-                .join(cid)
-                .join("keys/sefs/lower")
-                .display()
-        );
-
-        let keys_mount_path = Path::new("/keys");
-        nix::mount::mount(
-            Some("sefs"),
-            keys_mount_path,
-            Some("sefs"),
-            flags,
-            Some(key_mount_options.as_str()),
-        ).map_err(|e| {
-            anyhow!(
-                "failed to mount {:?} to {:?}, with error: {}",
-                "sefs",
-                keys_mount_path,
-                e
-            )
-        })?;
+        mount_aux(roimages.mount.as_ref().expect("roimages are on hostfs"), mount_path)?;
+
+        // Build all roimages. Any failure (including cancellation) leaves
+        // partially written images behind, which are removed right below.
+        let built = (|| -> Result<(Vec<_>, Vec<String>, BuildReport)> {
+            let build_started = Instant::now();
+            // clear the mount_path if there is something
+            clear_path(mount_path)?;
+
+            if memory_layers {
+                release(memory)?;
+                fs::create_dir_all(&memory.path)?;
+                mount_aux(memory.mount.as_ref().expect("memory is a tmpfs"), &memory.path)?;
+            }
+
+            let mut mode_entries = Vec::new();
+            let mut digests = vec![occlum_env.digest.clone()];
+            let mut report = BuildReport::default();
+
+            // build empty rw layer
+            if !self.read_only {
+                let rw_mode = eccfs_builder::rw::create_empty(
+                    &mount_path.join(ECCFS_RW_IMAGE_NAME),
+                    Some(self.rw_key()),
+                )?;
+                mode_entries.push(mode_entry(rw_mode.is_encrypted(), rw_mode.into_key_entry()));
+            }
 
-        let mode_str = fsmodes.into_iter().map(
-            |m| {
-                let s = if m.is_encrypted() {
-                    "enc"
-                } else {
-                    "int"
+            if cancel.is_cancelled() {
+                bail!(ERR_PULL_CANCELLED);
+            }
+
+            // occlum env is the first RO layer
+            link_roimage(&occlum_env.roimage, &mount_path.join(roimage_name(0)))?;
+            mode_entries.push(occlum_env.mode_entry.clone());
+
+            // container image layers
+            for (i, p) in layer_path.iter().enumerate() {
+                if cancel.is_cancelled() {
+                    bail!(ERR_PULL_CANCELLED);
+                }
+
+                let started = Instant::now();
+                self.prepare_dir(Path::new(p))?;
+
+                // like the other ones, a small layer is given to the enclave
+                // as a roimage in the eccfs dir, only its plaintext stays in
+                // memory while it is converted
+                let in_memory = targets[i] == LayerTarget::Memory;
+                let work_dir = match in_memory {
+                    true => memory.path.as_path(),
+                    false => eccfs_work_dir,
+                };
+                let name = roimage_name(i + 1);
+                let roimage = mount_path.join(&name);
+                let shared = match self.shares_layers() {
+                    true => self.shared_layer(cid, Path::new(p), &roimage, work_dir)?,
+                    false => None,
                 };
-                format!("{}-{}", s, hex::encode_upper(m.into_key_entry()))
+                match shared {
+                    Some(shared) => {
+                        mode_entries.push(shared.mode_entry);
+                        digests.push(shared.digest);
+                    }
+                    None => {
+                        let key = match self.integrity_only {
+                            true => None,
+                            false => Some(self.layer_key(Path::new(p))?),
+                        };
+                        let fsmode = eccfs_builder::ro::build_from_dir(
+                            Path::new(p),
+                            &mount_path,
+                            Path::new(name.as_str()),
+                            work_dir,
+                            key,
+                        )?;
+                        self.verify_build(Path::new(p), &roimage, &fsmode)?;
+                        mode_entries
+                            .push(mode_entry(fsmode.is_encrypted(), fsmode.into_key_entry()));
+                        clear_path(work_dir)?;
+                        digests.push(roimage_digest(&roimage)?);
+                    }
+                }
+                report.layers.push(LayerBuildStats::new(
+                    i + 1,
+                    in_memory,
+                    started.elapsed(),
+                    sizes[i],
+                    fs::metadata(&roimage)?.len(),
+                ));
+            }
+
+            report.millis = build_started.elapsed().as_millis() as u64;
+            Ok((mode_entries, digests, report))
+        })();
+
+        let mode_entries = match built {
+            Ok((mode_entries, digests, report)) => {
+                info!("eccfs roimages built for {:?}: {}", cid, digests.join(", "));
+                for layer in &report.layers {
+                    debug!(
+                        "eccfs layer {} of {:?}: {} ms, {} -> {} bytes ({:.2}){}",
+                        layer.index,
+                        cid,
+                        layer.millis,
+                        layer.input_bytes,
+                        layer.output_bytes,
+                        layer.ratio,
+                        if layer.in_memory { ", in memory" } else { "" }
+                    );
+                }
+                match serde_json::to_string(&report) {
+                    Ok(json) => debug!("eccfs build report for {:?}: {}", cid, json),
+                    Err(e) => warn!("failed to serialize eccfs build report: {}", e),
+                }
+                self.roimage_digests = digests;
+                self.build_report = Some(report);
+                mode_entries
+            }
+            Err(e) => {
+                info!("eccfs build for {:?} aborted, cleaning up: {}", cid, e);
+                if let Err(ce) = clear_path(mount_path) {
+                    warn!("failed to remove partial roimages: {}", ce);
+                }
+                if let Err(ce) = nix::mount::umount(mount_path) {
+                    warn!("failed to umount {:?}: {}", mount_path, ce);
+                }
+                if let Err(ce) = clear_path(eccfs_work_dir) {
+                    warn!("failed to clear eccfs work dir: {}", ce);
+                }
+                if let Err(ce) = release(memory) {
+                    warn!("failed to remove in-memory layers: {}", ce);
+                }
+                if let Err(ce) = self.shared_layers.release(cid) {
+                    warn!("failed to release shared roimages: {}", ce);
+                }
+                return Err(e);
             }
-        ).collect::<Vec<_>>().join(":");
+        };
+
+        nix::mount::umount(mount_path)?;
+
+        // the small layers are in their roimages now
+        if memory_layers {
+            release(memory)?;
+        }
+
+        let keys_mount_path = keys.path.as_path();
+        create_dir(keys_mount_path)?;
+        mount_aux(keys.mount.as_ref().expect("keys are on sefs"), keys_mount_path)?;
+
+        let mode_str = key_file(&mode_entries)?;
 
         std::fs::write(&keys_mount_path.join("key.txt"), &mode_str)?;
         nix::mount::umount(keys_mount_path)?;
@@ -232,12 +1868,653 @@ impl Snapshotter for EccOvlFs {
             r#type: "eccfs".into(),
             mount_path: mount_path.to_path_buf(),
             work_dir: self.data_dir.to_path_buf(),
+            aux_resources: resources,
         })
     }
 
     fn unmount(&self, mount_point: &MountPoint) -> Result<()> {
-        nix::mount::umount(mount_point.mount_path.as_path())?;
+        // mount leaves the rootfs unmounted, the roimages are mounted by the
+        // enclave itself
+        match nix::mount::umount(mount_point.mount_path.as_path()) {
+            Ok(()) | Err(Errno::EINVAL) => {}
+            Err(e) => bail!("failed to umount {:?}: {}", mount_point.mount_path, e),
+        }
 
-        Ok(())
+        // mount points not recorded by mount, e.g. rebuilt by a caller,
+        // still get their resources released
+        let cid = mount_name(mount_point)?;
+        let resources = if mount_point.aux_resources.is_empty() {
+            aux_resources(cid, &mount_point.mount_path, &self.scratch)
+        } else {
+            mount_point.aux_resources.clone()
+        };
+
+        // the links of the container to the shared roimages go with its
+        // roimages, the shared ones with their last container
+        let released = release_all(&resources, self.retain_artifacts);
+        self.shared_layers.release(cid)?;
+        released
+    }
+
+    // the rootfs is only assembled inside the enclave, so the roimages
+    // are what gets measured
+    fn layer_digests(&self) -> Option<Vec<String>> {
+        Some(self.roimage_digests.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_VERIFY_SAMPLES;
+    use std::os::unix::fs::lchown;
+
+    #[test]
+    fn test_release_all() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let resource = |kind, name: &str| {
+            let path = tempdir.path().join(name);
+            fs::create_dir(&path).unwrap();
+            fs::write(path.join("data"), name).unwrap();
+            AuxResource {
+                kind,
+                path,
+                mount: None,
+            }
+        };
+        let resources = vec![
+            resource(AuxKind::Secret, "keys"),
+            resource(AuxKind::Artifact, "roimages"),
+            resource(AuxKind::Scratch, "scratch"),
+        ];
+
+        release_all(&resources, true).unwrap();
+        assert!(!resources[0].path.join("data").exists());
+        assert!(resources[1].path.join("data").exists());
+        assert!(!resources[2].path.join("data").exists());
+
+        release_all(&resources, false).unwrap();
+        assert!(!resources[1].path.join("data").exists());
+
+        let resources = aux_resources(
+            OsStr::new("cid"),
+            Path::new("/run/cid/rootfs"),
+            &ScratchBacking::Disk,
+        );
+        assert_eq!(resources[0].path, Path::new("/keys/cid"));
+        assert_eq!(resources[2].path, Path::new("/eccfs_tmp/cid"));
+        assert_eq!(resources[2].mount, None);
+        assert_eq!(resources[3].path, Path::new("/eccfs_mem/cid"));
+        assert_eq!(
+            resources[0].mount.as_ref().unwrap().options,
+            "dir=/images/cid/keys/sefs/lower"
+        );
+        assert_eq!(resources[1].path, Path::new("/run/cid/rootfs"));
+        assert_eq!(
+            container_id(Path::new("/run/cid/rootfs")).unwrap(),
+            OsStr::new("cid")
+        );
+
+        // a mount named apart from its container is released by its name
+        let mount_path = Path::new("/run/volumes/data");
+        let mount_point = MountPoint {
+            r#type: "eccfs".into(),
+            mount_path: mount_path.to_path_buf(),
+            work_dir: PathBuf::from("/images"),
+            aux_resources: aux_resources(
+                OsStr::new("volume-data"),
+                mount_path,
+                &ScratchBacking::Disk,
+            ),
+        };
+        assert_eq!(mount_name(&mount_point).unwrap(), OsStr::new("volume-data"));
+        let mount_point = MountPoint {
+            aux_resources: Vec::new(),
+            ..mount_point
+        };
+        assert_eq!(mount_name(&mount_point).unwrap(), OsStr::new("volumes"));
+    }
+
+    #[test]
+    fn test_scratch_mount() {
+        // the plaintext layers are kept off the disk by default
+        let eccfs = EccOvlFs::new(PathBuf::from("/images"));
+        assert_eq!(eccfs.scratch, ScratchBacking::Tmpfs { size: None });
+
+        let cid = OsStr::new("cid");
+        assert_eq!(scratch_mount(cid, &ScratchBacking::Disk), None);
+        assert_eq!(
+            scratch_mount(
+                cid,
+                &ScratchBacking::DmCrypt {
+                    device: "/dev/vdb".into()
+                }
+            ),
+            None
+        );
+
+        let tmpfs = scratch_mount(cid, &ScratchBacking::Tmpfs { size: Some(4096) }).unwrap();
+        assert_eq!(tmpfs.fstype, "tmpfs");
+        assert_eq!(tmpfs.options, "mode=0700,size=4096");
+        assert!(tmpfs.persistent);
+        let tmpfs = scratch_mount(cid, &ScratchBacking::Tmpfs { size: None }).unwrap();
+        assert_eq!(tmpfs.options, "mode=0700");
+
+        let resources = aux_resources(cid, Path::new("/run/cid/rootfs"), &ScratchBacking::Sefs);
+        let sefs = resources[2].mount.as_ref().unwrap();
+        assert_eq!(resources[2].path, Path::new("/eccfs_tmp/cid"));
+        assert_eq!(sefs.fstype, "sefs");
+        assert_eq!(sefs.options, "dir=/images/cid/scratch/sefs/lower");
+    }
+
+    #[test]
+    fn test_concurrent_mounts() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let threads: Vec<_> = (0..16)
+            .map(|i| {
+                let base = tempdir.path().to_path_buf();
+                std::thread::spawn(move || {
+                    let cid = OsString::from(format!("stress-{i}"));
+                    let _guard = MountGuard::acquire(&cid).unwrap();
+                    let resources =
+                        aux_resources(&cid, &base.join(&cid).join("rootfs"), &ScratchBacking::Disk);
+                    let work_dir = base.join(resources[2].path.strip_prefix("/").unwrap());
+                    for round in 0..20 {
+                        prepare_work_dir(&work_dir).unwrap();
+                        let data = format!("{i}-{round}");
+                        fs::write(work_dir.join("layer"), &data).unwrap();
+                        std::thread::yield_now();
+                        assert_eq!(fs::read_to_string(work_dir.join("layer")).unwrap(), data);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let cid = OsStr::new("stress-same");
+        let guard = MountGuard::acquire(cid).unwrap();
+        assert!(MountGuard::acquire(cid).is_err());
+        drop(guard);
+        MountGuard::acquire(cid).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_mount() {
+        if !nix::unistd::Uid::effective().is_root() {
+            println!("INFO: skipping {} which needs root", module_path!());
+            return;
+        }
+
+        let data_dir = tempfile::tempdir().unwrap();
+        let preflight = EccOvlFs::new(data_dir.path().to_path_buf()).preflight();
+        if !preflight.passed() {
+            println!("INFO: skipping {} which needs occlum", module_path!());
+            return;
+        }
+
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let data_dir = data_dir.path().to_path_buf();
+                std::thread::spawn(move || {
+                    let layer = data_dir.join(format!("layer-{i}"));
+                    fs::create_dir_all(&layer).unwrap();
+                    fs::write(layer.join("file"), format!("layer {i}")).unwrap();
+
+                    let mut eccfs = EccOvlFs::new(data_dir.clone());
+                    let mount_path = data_dir.join(format!("mount-{i}")).join("rootfs");
+                    let mount_point = eccfs
+                        .mount(&[layer.to_str().unwrap()], &mount_path)
+                        .unwrap();
+                    (eccfs, mount_point)
+                })
+            })
+            .collect();
+        let mounted: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+
+        // each container got its own roimage of its layer
+        let layer_digests: BTreeSet<_> = mounted
+            .iter()
+            .map(|(eccfs, _)| eccfs.layer_digests().unwrap()[1].clone())
+            .collect();
+        assert_eq!(layer_digests.len(), mounted.len());
+
+        for (eccfs, mount_point) in &mounted {
+            eccfs.unmount(mount_point).unwrap();
+            MountGuard::acquire(container_id(&mount_point.mount_path).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_read_only_mount() {
+        // mounting needs root, and occlum
+        if !nix::unistd::Uid::effective().is_root() {
+            return;
+        }
+        let data_dir = tempfile::tempdir().unwrap();
+        let mut eccfs = EccOvlFs::new(data_dir.path().to_path_buf());
+        if !eccfs.preflight().passed() {
+            return;
+        }
+
+        let layer = data_dir.path().join("layer");
+        fs::create_dir_all(&layer).unwrap();
+        fs::write(layer.join("file"), "layer").unwrap();
+
+        // the volumes of a container share their parent dir
+        eccfs.integrity_only = true;
+        eccfs.read_only = true;
+        eccfs.name = Some(OsString::from("volume-data"));
+        let mount_path = data_dir.path().join("volumes").join("data");
+        let mount_point = eccfs
+            .mount(&[layer.to_str().unwrap()], &mount_path)
+            .unwrap();
+        assert!(!mount_path.join(ECCFS_RW_IMAGE_NAME).exists());
+        assert_eq!(
+            mount_point.aux_resources[0].path,
+            Path::new("/keys/volume-data")
+        );
+        assert_eq!(mount_name(&mount_point).unwrap(), OsStr::new("volume-data"));
+
+        eccfs.unmount(&mount_point).unwrap();
+        MountGuard::acquire(OsStr::new("volume-data")).unwrap();
+    }
+
+    #[test]
+    fn test_supplied_keys() {
+        let keys = SuppliedKeys::from_json(
+            format!(
+                r#"{{"rw": "{}", "layers": {{"sha256:abcd": "{}"}}}}"#,
+                "01".repeat(16),
+                "02".repeat(16)
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(keys.rw, Some([1; 16]));
+        assert_eq!(keys.occlum_env, None);
+        assert_eq!(
+            keys.layer(Path::new("/var/lib/image-rs/layers/sha256_abcd"))
+                .unwrap(),
+            [2; 16]
+        );
+        assert!(keys
+            .layer(Path::new("/var/lib/image-rs/layers/sha256_ef01"))
+            .is_err());
+
+        let mut eccfs = EccOvlFs::new_deterministic(PathBuf::from("/eccfs"), [3; 16], 0);
+        let derived = eccfs.occlum_env_key("0000.roimage");
+        eccfs.supplied_keys = Some(keys);
+        assert_eq!(eccfs.rw_key(), [1; 16]);
+        assert_eq!(eccfs.occlum_env_key("0000.roimage"), derived);
+
+        assert!(SuppliedKeys::from_json(br#"{"rw": "0102"}"#).is_err());
+        assert!(SuppliedKeys::from_json(br#"{"rw": "not hex"}"#).is_err());
+    }
+
+    #[test]
+    fn test_layer_key() {
+        let eccfs = EccOvlFs::new_deterministic(PathBuf::from("/eccfs"), [3; 16], 0);
+        let layer = Path::new("/var/lib/image-rs/layers/sha256_abcd");
+        let key = eccfs.layer_key(layer).unwrap();
+
+        // derived from the digest of the layer, wherever it is in the image
+        assert_eq!(key, derive_key(&[3; 16], "sha256_abcd.roimage"));
+        assert_eq!(
+            eccfs
+                .layer_key(Path::new("/run/image-rs/layers/sha256_abcd"))
+                .unwrap(),
+            key
+        );
+        assert_ne!(
+            eccfs
+                .layer_key(Path::new("/var/lib/image-rs/layers/sha256_ef01"))
+                .unwrap(),
+            key
+        );
+        assert_ne!(key, eccfs.roimage_key(&roimage_name(1)));
+    }
+
+    #[test]
+    fn test_plan_layers() {
+        use LayerTarget::*;
+
+        let sizes = [10, 1000, 20, 30];
+        assert_eq!(plan_layers(&sizes, None), vec![RoImage; 4]);
+
+        let mut policy = HybridPolicy {
+            small_layer_threshold: 100,
+            memory_budget: None,
+        };
+        assert_eq!(
+            plan_layers(&sizes, Some(&policy)),
+            vec![Memory, RoImage, Memory, Memory]
+        );
+
+        policy.memory_budget = Some(40);
+        assert_eq!(
+            plan_layers(&sizes, Some(&policy)),
+            vec![Memory, RoImage, Memory, RoImage]
+        );
+    }
+
+    #[test]
+    fn test_build_report() {
+        let report = BuildReport {
+            layers: vec![
+                LayerBuildStats::new(1, false, Duration::from_millis(30), 1000, 1200),
+                LayerBuildStats::new(2, true, Duration::from_millis(5), 10, 10),
+                LayerBuildStats::new(3, false, Duration::from_millis(10), 0, 4096),
+            ],
+            millis: 45,
+        };
+
+        assert_eq!(report.layers[0].ratio, 1.2);
+        assert_eq!(report.layers[1].ratio, 1.0);
+        assert_eq!(report.layers[2].ratio, 1.0);
+        assert_eq!(report.slowest().unwrap().index, 1);
+        assert_eq!(report.input_bytes(), 1010);
+        assert_eq!(report.output_bytes(), 5306);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["layers"][1]["in_memory"], true);
+        assert_eq!(json["layers"][0]["output_bytes"], 1200);
+    }
+
+    #[test]
+    fn test_preflight_report() {
+        let mut report = PreflightReport::default();
+        assert!(report.check("keys", || Ok(())));
+        assert!(report.passed());
+        assert!(!report.check("roimages", || bail!("hostfs is not supported")));
+        assert!(!report.passed());
+        assert_eq!(
+            report.checks[1],
+            PreflightCheck {
+                name: "roimages",
+                error: Some("hostfs is not supported".into()),
+            }
+        );
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["checks"][0]["error"], serde_json::Value::Null);
+        let e = report.into_result().unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "eccfs preflight failed: roimages: hostfs is not supported"
+        );
+
+        let sources = occlum_env_sources();
+        assert_eq!(sources[0], Path::new("/lib64/ld-linux-x86-64.so.2"));
+        assert!(sources.contains(&PathBuf::from("/opt/occlum/glibc/lib/libc.so.6")));
+        assert_eq!(sources.len(), 1 + OCCLUM_LIBS.len());
+    }
+
+    // copy the tree under from to to, keeping symlinks, modes and ownership
+    fn copy_tree(from: &Path, to: &Path) {
+        for entry in fs::read_dir(from).unwrap() {
+            let path = entry.unwrap().path();
+            let target = to.join(path.file_name().unwrap());
+            let meta = fs::symlink_metadata(&path).unwrap();
+            if meta.is_dir() {
+                fs::create_dir(&target).unwrap();
+                copy_tree(&path, &target);
+            } else if meta.file_type().is_symlink() {
+                std::os::unix::fs::symlink(fs::read_link(&path).unwrap(), &target).unwrap();
+            } else {
+                fs::copy(&path, &target).unwrap();
+            }
+
+            // chown clears the setuid and setgid bits, so it goes first
+            lchown(&target, Some(meta.uid()), Some(meta.gid())).unwrap();
+            if !meta.file_type().is_symlink() {
+                fs::set_permissions(&target, meta.permissions()).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_tree_size() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let layer = tempdir.path().join("layer");
+        fs::create_dir_all(layer.join("etc")).unwrap();
+        fs::write(layer.join("etc/hostname"), b"eccfs").unwrap();
+        std::os::unix::fs::symlink("etc/hostname", layer.join("hostname")).unwrap();
+        assert_eq!(tree_size(&layer).unwrap(), 5 + "etc/hostname".len() as u64);
+    }
+
+    #[test]
+    fn test_key_file() {
+        let entries = [mode_entry(true, [0xab; 4]), mode_entry(false, [0x01; 4])];
+        assert_eq!(key_file(&entries).unwrap(), "enc-ABABABAB:int-01010101");
+
+        // nothing but the key entries eccfs reads
+        for entry in ["dir-/eccfs_mem/cid/0001", "enc-AB:CD", "int-"] {
+            assert!(key_file(&[entry.to_string()]).is_err(), "{entry}");
+        }
+    }
+
+    // reads the entries of a dir, as if it were the roimage built from it
+    struct DirReader(PathBuf);
+
+    impl RoImageReader for DirReader {
+        fn entry(&mut self, path: &Path) -> Result<Option<EntrySummary>> {
+            let path = self.0.join(path);
+            match path.symlink_metadata() {
+                Ok(_) => dir_entry(&path).map(Some),
+                Err(_) => Ok(None),
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_entries() {
+        let tempdir = tempfile::tempdir().unwrap();
+        for i in 0..10 {
+            fs::write(tempdir.path().join(format!("{i}")), b"eccfs").unwrap();
+        }
+
+        assert_eq!(sample_entries(tempdir.path(), 20).unwrap().len(), 10);
+        assert_eq!(
+            sample_entries(tempdir.path(), 4).unwrap(),
+            ["0", "2", "5", "7"].map(PathBuf::from)
+        );
+        assert!(sample_entries(tempdir.path(), 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_verify_entries() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let layer = tempdir.path().join("layer");
+        fs::create_dir_all(layer.join("etc")).unwrap();
+        fs::write(layer.join("etc/hostname"), b"eccfs").unwrap();
+        std::os::unix::fs::symlink("etc/hostname", layer.join("hostname")).unwrap();
+
+        let built = tempdir.path().join("built");
+        fs::create_dir(&built).unwrap();
+        copy_tree(&layer, &built);
+        let roimage = Path::new("0001.roimage");
+        let mut reader = DirReader(built.clone());
+        verify_entries(&mut reader, &layer, roimage, DEFAULT_VERIFY_SAMPLES).unwrap();
+
+        fs::write(built.join("etc/hostname"), b"ECCFS").unwrap();
+        let e = verify_entries(&mut reader, &layer, roimage, DEFAULT_VERIFY_SAMPLES).unwrap_err();
+        let e = e.downcast_ref::<RoImageMismatch>().unwrap();
+        assert_eq!(e.entry.as_deref(), Some(Path::new("etc/hostname")));
+        assert_eq!(e.reason, "file content differs");
+
+        fs::remove_file(built.join("etc/hostname")).unwrap();
+        let e = verify_entries(&mut reader, &layer, roimage, DEFAULT_VERIFY_SAMPLES).unwrap_err();
+        let e = e.downcast_ref::<RoImageMismatch>().unwrap();
+        assert_eq!(e.reason, "missing from the roimage");
+
+        // with no sample, only the root MAC is checked when opening
+        verify_entries(&mut reader, &layer, roimage, 0).unwrap();
+    }
+
+    #[test]
+    fn test_conformance() {
+        use crate::snapshots::conformance::Suite;
+
+        // the layers are merged by the unionfs of Occlum in the enclave, so
+        // only the roimages built of the layers of each case are checked,
+        // whiteouts included, against the layers
+        let tempdir = tempfile::tempdir().unwrap();
+        let suite = Suite {
+            huge_file_size: 1 << 20,
+        };
+        let eccfs = EccOvlFs::new_deterministic(tempdir.path().join("eccfs"), [3; 16], 0);
+        for case in suite.cases() {
+            let dir = tempdir.path().join(case);
+            let Some(layers) = suite.build(case, &dir).unwrap() else {
+                continue;
+            };
+
+            let to_dir = dir.join("roimages");
+            fs::create_dir_all(&to_dir).unwrap();
+            for (i, layer) in layers.iter().enumerate() {
+                let name = roimage_name(i + 1);
+                let build_dir = dir.join("build").join(&name);
+                fs::create_dir_all(&build_dir).unwrap();
+                let fsmode = eccfs_builder::ro::build_from_dir(
+                    layer,
+                    &to_dir,
+                    Path::new(name.as_str()),
+                    &build_dir,
+                    Some(eccfs.roimage_key(&name)),
+                )
+                .unwrap();
+
+                let roimage = to_dir.join(&name);
+                let mut reader = BuiltRoImage::open(&roimage, &fsmode).unwrap();
+                verify_entries(&mut reader, layer, &roimage, usize::MAX)
+                    .unwrap_or_else(|e| panic!("{case}: {e:#}"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_shared_layers() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let layer = |cid: &str, key_digest: &str| {
+            let roimage = tempdir.path().join(format!("{cid}-sha256_share.roimage"));
+            fs::write(&roimage, cid).unwrap();
+            SharedLayer {
+                digest: roimage_digest(&roimage).unwrap(),
+                roimage,
+                key_digest: key_digest.into(),
+                mode_entry: "enc-01".into(),
+                users: BTreeSet::from([cid.to_string()]),
+            }
+        };
+        let (first, second, third) = (
+            OsStr::new("share-1"),
+            OsStr::new("share-2"),
+            OsStr::new("share-3"),
+        );
+
+        let shared_layers = SharedLayers::default();
+        assert!(shared_layers
+            .acquire("sha256_share", first, "key-1")
+            .unwrap()
+            .is_none());
+        let shared = shared_layers
+            .register("sha256_share", layer("share-1", "key-1"))
+            .unwrap();
+
+        // built concurrently by another container
+        let raced = layer("share-2", "key-1");
+        assert_eq!(
+            shared_layers
+                .register("sha256_share", raced.clone())
+                .unwrap()
+                .roimage,
+            shared.roimage
+        );
+        assert!(!raced.roimage.exists());
+        assert!(shared_layers
+            .register("sha256_share", layer("share-3", "key-2"))
+            .is_none());
+
+        // the table is recorded with the meta store of the client
+        let recorded: SharedLayers =
+            serde_json::from_str(&serde_json::to_string(&shared_layers).unwrap()).unwrap();
+        assert_eq!(
+            recorded.roimages(),
+            BTreeSet::from([shared.roimage.clone()])
+        );
+
+        assert!(shared_layers
+            .acquire("sha256_share", third, "key-2")
+            .is_err());
+        let acquired = shared_layers
+            .acquire("sha256_share", third, "key-1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(acquired.digest, shared.digest);
+        assert_eq!(acquired.users.len(), 3);
+
+        shared_layers.release(first).unwrap();
+        shared_layers.release(second).unwrap();
+        assert!(shared.roimage.exists());
+        shared_layers.release(third).unwrap();
+        assert!(!shared.roimage.exists());
+        assert!(shared_layers.roimages().is_empty());
+
+        // a recorded roimage gone meanwhile is built again
+        assert!(recorded
+            .acquire("sha256_share", first, "key-1")
+            .unwrap()
+            .is_none());
+        assert!(recorded.roimages().is_empty());
+    }
+
+    #[test]
+    fn test_shares_layers() {
+        let data_dir = PathBuf::from("/tmp/eccfs");
+        let mut random = EccOvlFs::new(data_dir.clone());
+        random.share_layers = true;
+        assert!(!random.shares_layers());
+
+        let mut derived = EccOvlFs::new_deterministic(data_dir, [1; 16], 0);
+        derived.share_layers = true;
+        assert!(derived.shares_layers());
+        derived.integrity_only = true;
+        assert!(!derived.shares_layers());
+    }
+
+    #[test]
+    fn test_reset_cache_dir() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let env = cache_dir.path().join("share-1-00000000.roimage");
+        let layers = cache_dir.path().join(SHARED_LAYERS_DIR);
+        let used = layers.join("share-1-sha256_used.roimage");
+        let unused = layers.join("share-1-sha256_unused.roimage");
+        fs::create_dir_all(&layers).unwrap();
+        for roimage in [&env, &used, &unused] {
+            fs::write(roimage, b"roimage").unwrap();
+        }
+
+        reset_cache_dir(cache_dir.path(), &BTreeSet::from([used.clone()])).unwrap();
+        assert!(!env.exists());
+        assert!(used.exists());
+        assert!(!unused.exists());
+    }
+
+    #[test]
+    fn test_link_roimage() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let cached = tempdir.path().join("0000.roimage");
+        fs::write(&cached, b"occlum env").unwrap();
+
+        let target = tempdir.path().join("container");
+        fs::create_dir(&target).unwrap();
+        link_roimage(&cached, &target.join("0000.roimage")).unwrap();
+        assert_eq!(fs::read(target.join("0000.roimage")).unwrap(), b"occlum env");
+        assert_eq!(
+            roimage_digest(&cached).unwrap(),
+            roimage_digest(&target.join("0000.roimage")).unwrap()
+        );
     }
 }
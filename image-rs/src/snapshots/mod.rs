@@ -2,19 +2,26 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::Result;
-use serde::Deserialize;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use tokio_util::sync::CancellationToken;
 
+use crate::ERR_PULL_CANCELLED;
+
+#[cfg(any(test, feature = "snapshot-conformance"))]
+pub mod conformance;
 #[cfg(feature = "snapshot-unionfs")]
 pub mod occlum;
 #[cfg(feature = "snapshot-overlayfs")]
 pub mod overlay;
 #[cfg(feature = "snapshot-eccfs")]
 pub mod eccfs;
+#[cfg(feature = "snapshot-wasm")]
+pub mod wasm;
 
 /// Snapshot types.
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum SnapshotType {
     Unknown,
@@ -24,6 +31,8 @@ pub enum SnapshotType {
     OcclumUnionfs,
     #[cfg(feature = "snapshot-eccfs")]
     Eccfs,
+    #[cfg(feature = "snapshot-wasm")]
+    Wasm,
 }
 
 impl std::fmt::Display for SnapshotType {
@@ -36,14 +45,58 @@ impl std::fmt::Display for SnapshotType {
             Self::OcclumUnionfs => "occlum_unionfs",
             #[cfg(feature = "snapshot-eccfs")]
             Self::Eccfs => "eccfs",
+            #[cfg(feature = "snapshot-wasm")]
+            Self::Wasm => "wasm",
         };
 
         write!(f, "{out}")
     }
 }
 
+/// What an auxiliary resource of a mount point holds.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuxKind {
+    /// Key material, always removed on unmount.
+    Secret,
+
+    /// Scratch data of the snapshot, always removed on unmount.
+    Scratch,
+
+    /// Data built from the image layers, which may be kept on unmount to be
+    /// reused by later mounts of the same image.
+    Artifact,
+}
+
+/// A filesystem a resource is reached through.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct AuxMount {
+    pub source: String,
+    pub fstype: String,
+    pub options: String,
+
+    /// Whether the filesystem stays mounted as long as the mount point,
+    /// e.g. a tmpfs, instead of only while the resource is accessed.
+    #[serde(default)]
+    pub persistent: bool,
+}
+
+/// A resource allocated by a snapshot besides the rootfs, released by
+/// [`Snapshotter::unmount`].
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct AuxResource {
+    pub kind: AuxKind,
+
+    /// Dir holding the resource.
+    pub path: PathBuf,
+
+    /// Filesystem mounted to `path` to reach the resource, if any.
+    #[serde(default)]
+    pub mount: Option<AuxMount>,
+}
+
 /// A MountPoint contains the info to represents a mount point.
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct MountPoint {
     /// The filesystem type of mount point.
     pub r#type: String,
@@ -53,6 +106,10 @@ pub struct MountPoint {
 
     /// The work dir generated by snapshot.
     pub work_dir: PathBuf,
+
+    /// Resources of the mount besides the rootfs, e.g. key material.
+    #[serde(default)]
+    pub aux_resources: Vec<AuxResource>,
 }
 
 /// Trait to mount/umount image snapshots.
@@ -60,6 +117,30 @@ pub trait Snapshotter: Send + Sync {
     // mount the OCI image layers to destination mount path.
     fn mount(&mut self, layer_path: &[&str], mount_path: &Path) -> Result<MountPoint>;
 
-    // unmount the mount_point and cleanup snapshot work dir.
+    // mount the OCI image layers, giving up once `cancel` fires. Snapshotters
+    // doing long-running per-layer work should override this to check the
+    // token between steps and clean up whatever they already created.
+    fn mount_with_cancellation(
+        &mut self,
+        layer_path: &[&str],
+        mount_path: &Path,
+        cancel: &CancellationToken,
+    ) -> Result<MountPoint> {
+        if cancel.is_cancelled() {
+            bail!(ERR_PULL_CANCELLED);
+        }
+
+        self.mount(layer_path, mount_path)
+    }
+
+    // unmount the mount_point, and cleanup snapshot work dir and the
+    // auxiliary resources of the mount point.
     fn unmount(&self, mount_point: &MountPoint) -> Result<()>;
+
+    // digests of the per-layer images built by the last mount, for
+    // snapshotters whose mounted rootfs can't be walked to measure it.
+    // `None` means the mounted tree itself is measured.
+    fn layer_digests(&self) -> Option<Vec<String>> {
+        None
+    }
 }
@@ -9,7 +9,6 @@ use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicUsize;
 
 use anyhow::{anyhow, Result};
-use dircpy::CopyBuilder;
 use fs_extra;
 use fs_extra::dir;
 use nix::mount::MsFlags;
@@ -24,6 +23,9 @@ const LD_LIB: &str = "ld-linux-x86-64.so.2";
 pub struct Unionfs {
     pub data_dir: PathBuf,
     pub index: AtomicUsize,
+
+    /// Max number of layers copied to the rootfs concurrently.
+    pub concurrency: usize,
 }
 
 fn clear_path(mount_path: &Path) -> Result<()> {
@@ -196,14 +198,7 @@ impl Snapshotter for Unionfs {
         clear_path(mount_path)?;
 
         // copy dirs to the specified mount directory
-        let mut layer_path_vec = layer_path.to_vec();
-        let len = layer_path_vec.len();
-        for _i in 0..len {
-            let layer = layer_path_vec
-                .pop()
-                .ok_or(anyhow!("Pop() failed from Vec"))?;
-            CopyBuilder::new(layer, mount_path).overwrite(true).run()?;
-        }
+        crate::flatten::apply_layers(layer_path, mount_path, self.concurrency)?;
 
         // create environment for Occlum
         create_environment(mount_path)?;
@@ -214,6 +209,7 @@ impl Snapshotter for Unionfs {
             r#type: fs_type,
             mount_path: mount_path.to_path_buf(),
             work_dir: self.data_dir.to_path_buf(),
+            aux_resources: Vec::new(),
         })
     }
 
@@ -286,6 +282,7 @@ mod tests {
         let mut occlum_unionfs = Unionfs {
             data_dir: work_dir,
             index: AtomicUsize::new(unionfs_index),
+            concurrency: 1,
         };
 
         let path_1 = tempfile::tempdir().unwrap();
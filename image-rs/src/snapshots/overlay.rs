@@ -2,14 +2,29 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use nix::mount::MsFlags;
+use nix::sys::stat::{makedev, mknod, utimensat, Mode, SFlag, UtimensatFlags};
+use nix::sys::time::TimeSpec;
+use std::ffi::CString;
 use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{lchown, symlink, MetadataExt, PermissionsExt};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use walkdir::WalkDir;
 
 use crate::snapshots::{MountPoint, SnapshotType, Snapshotter};
 
+const WHITEOUT_PREFIX: &[u8] = b".wh.";
+const WHITEOUT_OPAQUE_DIR: &[u8] = b".wh..wh..opq";
+const OVERLAY_OPAQUE_XATTR: &str = "trusted.overlay.opaque";
+const XATTR_SIZE_MAX: usize = 64 << 10;
+
+// the lowers of a mount with whiteouts are under its work dir
+const LOWER_PREFIX: &str = "lower-";
+
 #[derive(Debug)]
 pub struct OverlayFs {
     data_dir: PathBuf,
@@ -26,7 +41,6 @@ impl OverlayFs {
 impl Snapshotter for OverlayFs {
     fn mount(&mut self, layer_path: &[&str], mount_path: &Path) -> Result<MountPoint> {
         let fs_type = SnapshotType::Overlay.to_string();
-        let overlay_lowerdir = layer_path.join(":");
         let index = self.index.fetch_add(1, Ordering::SeqCst).to_string();
         let work_dir = self.data_dir.join(index);
         let overlay_upperdir = work_dir.join("upperdir");
@@ -39,6 +53,14 @@ impl Snapshotter for OverlayFs {
         fs::create_dir_all(&overlay_upperdir)?;
         fs::create_dir_all(&overlay_workdir)?;
 
+        let overlay_lowerdir = match overlay_lowers(layer_path, &work_dir) {
+            Ok(lowers) => lowers.join(":"),
+            Err(e) => {
+                remove_lowers(&work_dir);
+                return Err(e);
+            }
+        };
+
         if !mount_path.exists() {
             fs::create_dir_all(mount_path)?;
         }
@@ -60,6 +82,7 @@ impl Snapshotter for OverlayFs {
             Some(options.as_str()),
         )
         .map_err(|e| {
+            remove_lowers(&work_dir);
             anyhow!(
                 "failed to mount {:?} to {:?}, with error: {}",
                 source,
@@ -72,12 +95,230 @@ impl Snapshotter for OverlayFs {
             r#type: fs_type,
             mount_path: mount_path.to_path_buf(),
             work_dir,
+            aux_resources: Vec::new(),
         })
     }
 
     fn unmount(&self, mount_point: &MountPoint) -> Result<()> {
         nix::mount::umount(mount_point.mount_path.as_path())?;
+        remove_lowers(&mount_point.work_dir);
 
         Ok(())
     }
 }
+
+// The lowerdirs of the layers, top first. The layers keep whiteouts the OCI
+// way, as `.wh.` files, which overlayfs ignores, so each layer holding any
+// is mirrored under `work_dir` with the whiteouts of overlayfs instead. The
+// layers themselves are left as they are, for they are shared with the
+// other snapshotters.
+fn overlay_lowers(layer_path: &[&str], work_dir: &Path) -> Result<Vec<String>> {
+    let mut lowers = Vec::with_capacity(layer_path.len());
+    for (i, layer) in layer_path.iter().enumerate() {
+        let layer = Path::new(layer);
+        if !has_whiteouts(layer)? {
+            lowers.push(layer.display().to_string());
+            continue;
+        }
+
+        let lower = work_dir.join(format!("{LOWER_PREFIX}{i}"));
+        mirror_layer(layer, &lower)
+            .with_context(|| format!("failed to convert whiteouts of {}", layer.display()))?;
+        lowers.push(lower.display().to_string());
+    }
+
+    Ok(lowers)
+}
+
+fn has_whiteouts(layer: &Path) -> Result<bool> {
+    for entry in WalkDir::new(layer).min_depth(1) {
+        if entry?.file_name().as_bytes().starts_with(WHITEOUT_PREFIX) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+// Mirror `layer` at `lower`, turning `.wh.<name>` files into 0/0 character
+// devices `<name>`, and `.wh..wh..opq` files into the opaque xattr of their
+// dir. Files are hardlinked, or copied if `lower` is on another filesystem.
+fn mirror_layer(layer: &Path, lower: &Path) -> Result<()> {
+    let mut dirs = Vec::new();
+    // parents come before their children
+    for entry in WalkDir::new(layer).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        let target = lower.join(path.strip_prefix(layer)?);
+        let name = entry.file_name().as_bytes();
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            fs::create_dir(&target)?;
+            copy_xattrs(path, &target)?;
+            lchown(&target, Some(metadata.uid()), Some(metadata.gid()))?;
+            fs::set_permissions(&target, fs::Permissions::from_mode(metadata.mode()))?;
+            dirs.push((target, metadata));
+        } else if name == WHITEOUT_OPAQUE_DIR {
+            let dir = target.parent().expect("whiteouts are in a dir");
+            set_xattr(dir, OVERLAY_OPAQUE_XATTR, b"y")?;
+        } else if let Some(hidden) = name.strip_prefix(WHITEOUT_PREFIX) {
+            // the layer's own entry hides the lower ones already
+            let hidden = std::ffi::OsStr::from_bytes(hidden);
+            if fs::symlink_metadata(path.with_file_name(hidden)).is_err() {
+                mknod(
+                    &target.with_file_name(hidden),
+                    SFlag::S_IFCHR,
+                    Mode::empty(),
+                    makedev(0, 0),
+                )?;
+            }
+        } else {
+            link_or_copy(path, &target, &metadata)?;
+        }
+    }
+
+    // the entries created above touched the dirs, children first
+    for (dir, metadata) in dirs.iter().rev() {
+        utimensat(
+            None,
+            dir,
+            &TimeSpec::new(metadata.atime(), metadata.atime_nsec()),
+            &TimeSpec::new(metadata.mtime(), metadata.mtime_nsec()),
+            UtimensatFlags::NoFollowSymlink,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn link_or_copy(path: &Path, target: &Path, metadata: &fs::Metadata) -> Result<()> {
+    match fs::hard_link(path, target) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {}
+        res => return Ok(res?),
+    }
+
+    if metadata.is_symlink() {
+        symlink(fs::read_link(path)?, target)?;
+    } else if metadata.is_file() {
+        fs::copy(path, target)?;
+    } else {
+        let kind = SFlag::from_bits_truncate(metadata.mode());
+        let perm = Mode::from_bits_truncate(metadata.mode());
+        mknod(target, kind, perm, metadata.rdev())?;
+    }
+    copy_xattrs(path, target)?;
+    lchown(target, Some(metadata.uid()), Some(metadata.gid()))?;
+    if !metadata.is_symlink() {
+        // chown clears the setuid and setgid bits
+        fs::set_permissions(target, fs::Permissions::from_mode(metadata.mode()))?;
+    }
+
+    Ok(())
+}
+
+fn remove_lowers(work_dir: &Path) {
+    let Ok(entries) = fs::read_dir(work_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry
+            .file_name()
+            .as_bytes()
+            .starts_with(LOWER_PREFIX.as_bytes())
+        {
+            if let Err(e) = fs::remove_dir_all(entry.path()) {
+                log::warn!("failed to remove {}: {e}", entry.path().display());
+            }
+        }
+    }
+}
+
+fn copy_xattrs(path: &Path, target: &Path) -> io::Result<()> {
+    let src = CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: `src` is nul-terminated, and a null list only gets the size
+    let size = unsafe { libc::llistxattr(src.as_ptr(), std::ptr::null_mut(), 0) };
+    if size < 0 {
+        let e = io::Error::last_os_error();
+        return match e.raw_os_error() {
+            Some(libc::ENOTSUP) => Ok(()),
+            _ => Err(e),
+        };
+    }
+
+    let mut names = vec![0u8; size as usize];
+    // SAFETY: `names` has the given size
+    let size = unsafe { libc::llistxattr(src.as_ptr(), names.as_mut_ptr().cast(), names.len()) };
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    names.truncate(size as usize);
+
+    for name in names.split(|b| *b == 0).filter(|name| !name.is_empty()) {
+        let name = CString::new(name)?;
+        let mut value = vec![0u8; XATTR_SIZE_MAX];
+        // SAFETY: the strings are nul-terminated and `value` has the given
+        // size
+        let len = unsafe {
+            libc::lgetxattr(
+                src.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        value.truncate(len as usize);
+        set_xattr(target, &name.to_string_lossy(), &value)?;
+    }
+
+    Ok(())
+}
+
+fn set_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let name = CString::new(name)?;
+    // SAFETY: the strings are nul-terminated and `value` outlives the call
+    let ret = unsafe {
+        libc::lsetxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshots::conformance::assert_conformance;
+
+    #[test]
+    fn test_conformance() {
+        // mounting needs root
+        if !nix::unistd::Uid::effective().is_root() {
+            return;
+        }
+
+        let work_dir = tempfile::tempdir().unwrap();
+        let data_dir = work_dir.path().join("snapshots");
+        let mut overlay = OverlayFs::new(data_dir.clone(), AtomicUsize::new(0));
+        assert_conformance(&mut overlay, &work_dir.path().join("cases"));
+
+        // the lowers with whiteouts converted are gone with the mounts
+        for entry in WalkDir::new(&data_dir).max_depth(2) {
+            let entry = entry.unwrap();
+            let name = entry.file_name().to_string_lossy();
+            assert!(!name.starts_with(LOWER_PREFIX), "{name} left");
+        }
+    }
+}
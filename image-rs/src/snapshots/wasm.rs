@@ -0,0 +1,333 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Snapshot of WASM artifacts.
+//!
+//! WASM workloads are pushed as OCI artifacts whose layers are WASM modules
+//! or components instead of tarballs of a Linux rootfs. They are pulled,
+//! verified and decrypted like any other image, but every layer is stored
+//! as a single module file, and [`WasmSnapshot`] places the modules in the
+//! rootfs dir of the bundle as they are. The `config.json` of the bundle is
+//! converted from the artifact config and runs the module of the top layer,
+//! for a WASM runtime such as a runwasi shim to pick up.
+
+use anyhow::{anyhow, bail, Context, Result};
+use oci_distribution::manifest::{OciDescriptor, OciImageManifest};
+use oci_spec::image::ImageConfiguration;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::Path;
+use tokio::io::AsyncRead;
+use tokio_util::sync::CancellationToken;
+
+use crate::decrypt::Decryptor;
+use crate::digest::{hasher_for, HashingReader};
+use crate::pull::blob_id;
+use crate::snapshots::{MountPoint, SnapshotType, Snapshotter};
+use crate::ERR_PULL_CANCELLED;
+
+/// Config media types of WASM artifacts, of the CNCF WASM OCI artifact
+/// layout and of `wasm-to-oci`.
+pub const WASM_CONFIG_MEDIA_TYPES: &[&str] = &[
+    "application/vnd.wasm.config.v0+json",
+    "application/vnd.wasm.config.v1+json",
+];
+
+/// Layer media types of WASM modules and components.
+pub const WASM_LAYER_MEDIA_TYPES: &[&str] = &[
+    "application/wasm",
+    "application/vnd.wasm.content.layer.v1+wasm",
+    "application/vnd.w3c.wasm.module.v1+wasm",
+    "application/vnd.bytecodealliance.wasm.component.layer.v0+wasm",
+];
+
+/// Name of the module file in the dir of a WASM layer.
+pub const MODULE_FILE: &str = "module.wasm";
+
+/// Architecture of the image configuration converted from a WASM artifact.
+pub const WASM_ARCH: &str = "wasm";
+
+const DEFAULT_WASM_OS: &str = "wasip1";
+
+/// Whether `media_type` is the one of a plaintext WASM module layer.
+pub fn is_wasm_media_type(media_type: &str) -> bool {
+    WASM_LAYER_MEDIA_TYPES.contains(&media_type)
+}
+
+fn is_wasm_layer(layer: &OciDescriptor) -> bool {
+    let decryptor = Decryptor::from_descriptor(layer);
+    match decryptor.is_encrypted() {
+        true => is_wasm_media_type(&decryptor.media_type),
+        false => is_wasm_media_type(&layer.media_type),
+    }
+}
+
+/// Whether the manifest is the one of a WASM artifact, i.e. it has a WASM
+/// config media type, or all its layers are WASM modules.
+pub fn is_wasm_artifact(manifest: &OciImageManifest) -> bool {
+    WASM_CONFIG_MEDIA_TYPES.contains(&manifest.config.media_type.as_str())
+        || (!manifest.layers.is_empty() && manifest.layers.iter().all(is_wasm_layer))
+}
+
+/// Whether the image configuration was converted from a WASM artifact by
+/// [`image_config`].
+pub fn is_wasm_config(image_config: &ImageConfiguration) -> bool {
+    image_config.architecture().to_string() == WASM_ARCH
+}
+
+/// Path in the rootfs the module of the layer with `digest` is placed at.
+pub fn module_path(digest: &str) -> String {
+    format!("/{}.wasm", blob_id(digest))
+}
+
+/// The fields of a WASM artifact config carried over to the image
+/// configuration. `wasm-to-oci` pushes an empty config.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactConfig {
+    architecture: Option<String>,
+    os: Option<String>,
+    created: Option<String>,
+    author: Option<String>,
+
+    /// Digests of the plaintext layers.
+    layer_digests: Option<Vec<String>>,
+}
+
+/// Convert the config of a WASM artifact into an image configuration, whose
+/// `Entrypoint` is the module of the top layer.
+///
+/// The plaintext digests of the layers are taken from the `layerDigests` of
+/// the artifact config, or are the digests of the layers if they are not
+/// encrypted.
+pub fn image_config(artifact_config: &str, manifest: &OciImageManifest) -> Result<String> {
+    let artifact: ArtifactConfig = match artifact_config.trim() {
+        "" => ArtifactConfig::default(),
+        config => serde_json::from_str(config).context("parse WASM artifact config")?,
+    };
+
+    let top = manifest
+        .layers
+        .last()
+        .ok_or_else(|| anyhow!("WASM artifact has no layers"))?;
+    let diff_ids = match artifact.layer_digests {
+        Some(digests) => digests,
+        None => manifest
+            .layers
+            .iter()
+            .map(
+                |layer| match Decryptor::from_descriptor(layer).is_encrypted() {
+                    true => bail!("encrypted WASM layer {} needs layerDigests", layer.digest),
+                    false => Ok(layer.digest.clone()),
+                },
+            )
+            .collect::<Result<_>>()?,
+    };
+
+    let mut config = Map::new();
+    config.insert(
+        "architecture".into(),
+        artifact.architecture.unwrap_or(WASM_ARCH.into()).into(),
+    );
+    config.insert(
+        "os".into(),
+        artifact.os.unwrap_or(DEFAULT_WASM_OS.into()).into(),
+    );
+    if let Some(created) = artifact.created {
+        config.insert("created".into(), created.into());
+    }
+    if let Some(author) = artifact.author {
+        config.insert("author".into(), author.into());
+    }
+    config.insert(
+        "config".into(),
+        json!({ "Entrypoint": [module_path(&top.digest)] }),
+    );
+    config.insert(
+        "rootfs".into(),
+        json!({ "type": "layers", "diff_ids": diff_ids }),
+    );
+
+    Ok(serde_json::to_string(&Value::Object(config))?)
+}
+
+/// Write the plaintext module of a WASM layer to [`MODULE_FILE`] under
+/// `destination`, returning its digest for verification.
+///
+/// If `cancel` fires while the module is still streaming, the destination
+/// is removed and [`ERR_PULL_CANCELLED`] is returned.
+pub(crate) async fn store_module(
+    module: impl AsyncRead + Unpin,
+    diff_id: &str,
+    destination: &Path,
+    cancel: &CancellationToken,
+) -> Result<String> {
+    let mut module = HashingReader::new(module, hasher_for(diff_id)?);
+    let stored = async {
+        tokio::fs::create_dir_all(destination).await?;
+        let mut file = tokio::fs::File::create(destination.join(MODULE_FILE)).await?;
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => bail!(ERR_PULL_CANCELLED),
+            res = tokio::io::copy(&mut module, &mut file) => res?,
+        };
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = stored {
+        let _ = tokio::fs::remove_dir_all(destination).await;
+        return Err(e);
+    }
+
+    Ok(module.digest_finalize())
+}
+
+/// Places the modules of the layers in the rootfs dir, at their
+/// [`module_path`].
+#[derive(Debug, Default)]
+pub struct WasmSnapshot {}
+
+impl Snapshotter for WasmSnapshot {
+    fn mount(&mut self, layer_path: &[&str], mount_path: &Path) -> Result<MountPoint> {
+        fs::create_dir_all(mount_path)?;
+        for layer in layer_path {
+            let layer = Path::new(layer);
+            let name = layer
+                .file_name()
+                .ok_or_else(|| anyhow!("invalid WASM layer dir {}", layer.display()))?;
+            let target = mount_path.join(name).with_extension("wasm");
+            fs::copy(layer.join(MODULE_FILE), &target)
+                .with_context(|| format!("place WASM module {}", target.display()))?;
+        }
+
+        Ok(MountPoint {
+            r#type: SnapshotType::Wasm.to_string(),
+            mount_path: mount_path.to_path_buf(),
+            work_dir: mount_path.to_path_buf(),
+            aux_resources: Vec::new(),
+        })
+    }
+
+    fn unmount(&self, mount_point: &MountPoint) -> Result<()> {
+        fs::remove_dir_all(&mount_point.mount_path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    const MODULE: &[u8] = b"\0asm\x01\0\0\0";
+
+    fn manifest(config_media_type: &str, layer_media_types: &[&str]) -> OciImageManifest {
+        OciImageManifest {
+            config: OciDescriptor {
+                media_type: config_media_type.to_string(),
+                ..Default::default()
+            },
+            layers: layer_media_types
+                .iter()
+                .enumerate()
+                .map(|(i, media_type)| OciDescriptor {
+                    media_type: media_type.to_string(),
+                    digest: format!("sha256:{i:064}"),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_wasm_artifact() {
+        let image_config = "application/vnd.oci.image.config.v1+json";
+        let tar = "application/vnd.oci.image.layer.v1.tar+gzip";
+
+        assert!(is_wasm_artifact(&manifest(WASM_CONFIG_MEDIA_TYPES[0], &[])));
+        assert!(is_wasm_artifact(&manifest(
+            image_config,
+            &["application/wasm", "application/wasm+encrypted"]
+        )));
+        assert!(!is_wasm_artifact(&manifest(image_config, &[])));
+        assert!(!is_wasm_artifact(&manifest(
+            image_config,
+            &["application/wasm", tar]
+        )));
+    }
+
+    #[test]
+    fn test_image_config() {
+        let artifact = manifest(
+            WASM_CONFIG_MEDIA_TYPES[0],
+            &["application/wasm", "application/wasm"],
+        );
+        let config: ImageConfiguration =
+            serde_json::from_str(&image_config("", &artifact).unwrap()).unwrap();
+        assert!(is_wasm_config(&config));
+        assert_eq!(
+            config
+                .config()
+                .as_ref()
+                .unwrap()
+                .entrypoint()
+                .as_ref()
+                .unwrap(),
+            &vec![module_path(&artifact.layers[1].digest)]
+        );
+        assert_eq!(
+            config.rootfs().diff_ids(),
+            &vec![
+                artifact.layers[0].digest.clone(),
+                artifact.layers[1].digest.clone()
+            ]
+        );
+
+        let config: ImageConfiguration = serde_json::from_str(
+            &image_config(r#"{"layerDigests":["sha256:1","sha256:2"]}"#, &artifact).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config.rootfs().diff_ids(), &vec!["sha256:1", "sha256:2"]);
+        assert!(image_config("not json", &artifact).is_err());
+
+        let encrypted = manifest(WASM_CONFIG_MEDIA_TYPES[0], &["application/wasm+encrypted"]);
+        assert!(image_config("{}", &encrypted).is_err());
+        let empty = manifest(WASM_CONFIG_MEDIA_TYPES[0], &[]);
+        assert!(image_config("{}", &empty).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_store_and_mount() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let digest = format!("sha256:{:x}", Sha256::digest(MODULE));
+        let layer = tempdir.path().join(blob_id(&digest));
+
+        let stored = store_module(MODULE, &digest, &layer, &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(stored, digest);
+        assert_eq!(fs::read(layer.join(MODULE_FILE)).unwrap(), MODULE);
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let cancelled = tempdir.path().join("cancelled");
+        assert!(store_module(MODULE, &digest, &cancelled, &cancel)
+            .await
+            .is_err());
+        assert!(!cancelled.exists());
+
+        let rootfs = tempdir.path().join("rootfs");
+        let mut snapshot = WasmSnapshot::default();
+        let mount_point = snapshot.mount(&[layer.to_str().unwrap()], &rootfs).unwrap();
+        let module = rootfs.join(module_path(&digest).trim_start_matches('/'));
+        assert_eq!(fs::read(module).unwrap(), MODULE);
+
+        snapshot.unmount(&mount_point).unwrap();
+        assert!(!rootfs.exists());
+    }
+}
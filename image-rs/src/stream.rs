@@ -3,29 +3,131 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::{anyhow, bail, Context, Result};
+use oci_distribution::manifest::OciDescriptor;
 use sha2::Digest;
+use std::fmt;
 use std::fs;
-use std::io::{Cursor, Read};
+use std::io::{self, Cursor, Read};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver};
 use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::sync::CancellationToken;
 
+use crate::config::BackgroundPriority;
+use crate::decoder::Compression;
+use crate::decrypt::Decryptor;
 use crate::digest::{DigestHasher, LayerDigestHasher, DIGEST_SHA256_PREFIX, DIGEST_SHA512_PREFIX};
+use crate::pull::ERR_NO_DECRYPT_CFG;
 use crate::unpack::unpack;
-use crate::ERR_BAD_UNCOMPRESSED_DIGEST;
+use crate::{ERR_BAD_UNCOMPRESSED_DIGEST, ERR_PULL_CANCELLED};
 
 const CAPACITY: usize = 32768;
 
+/// Error returned when the unpack destination of a layer ran out of space.
+/// The partially unpacked layer has been removed.
+#[derive(Debug)]
+pub struct LayerStorageFull {
+    /// The error of the unpack.
+    pub source: anyhow::Error,
+}
+
+impl fmt::Display for LayerStorageFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no space left to unpack layer: {:?}", self.source)
+    }
+}
+
+impl std::error::Error for LayerStorageFull {}
+
+fn is_storage_full(e: &anyhow::Error) -> bool {
+    e.chain().any(|c| {
+        c.downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::StorageFull)
+    })
+}
+
+/// How the blob of a layer is turned into its tar stream, decrypting it
+/// if needed and decompressing it.
+///
+/// The stages are reader adapters, so a layer is decrypted, decompressed
+/// and unpacked in a single pass over the blob while it is downloaded,
+/// without ever being stored as a whole.
+pub(crate) struct LayerDecoding {
+    compression: Compression,
+
+    /// The decryptor of an encrypted layer and the layer key.
+    decryption: Option<(Decryptor, Vec<u8>)>,
+
+    descriptor: OciDescriptor,
+}
+
+impl LayerDecoding {
+    /// Check that the media type of `layer` is handled, and get its layer
+    /// key from `decrypt_config` if it is encrypted.
+    pub(crate) fn new(layer: &OciDescriptor, decrypt_config: &Option<&str>) -> Result<Self> {
+        let decryptor = Decryptor::from_descriptor(layer);
+        if !decryptor.is_encrypted() {
+            return Ok(Self {
+                compression: Compression::try_from(layer.media_type.as_str())?,
+                decryption: None,
+                descriptor: layer.clone(),
+            });
+        }
+
+        let Some(dc) = decrypt_config else {
+            bail!(ERR_NO_DECRYPT_CFG);
+        };
+        let decrypt_key = decryptor
+            .get_decrypt_key(layer, dc)
+            .map_err(|e| anyhow!("failed to get decrypt key {}", e))?;
+        Ok(Self {
+            compression: Compression::try_from(decryptor.media_type.as_str())?,
+            decryption: Some((decryptor, decrypt_key)),
+            descriptor: layer.clone(),
+        })
+    }
+
+    pub(crate) fn is_encrypted(&self) -> bool {
+        self.decryption.is_some()
+    }
+
+    /// Media type of the plaintext layer.
+    pub(crate) fn media_type(&self) -> &str {
+        match &self.decryption {
+            Some((decryptor, _)) => &decryptor.media_type,
+            None => &self.descriptor.media_type,
+        }
+    }
+
+    /// Chain the decryption and the decompression of the layer onto `blob`.
+    ///
+    /// The decompressor stops at the end of the compressed stream, which
+    /// may leave the end of `blob` unread.
+    pub(crate) fn tar_reader<'a>(
+        &self,
+        blob: impl AsyncRead + Unpin + Send + 'a,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send + 'a>> {
+        let Some((decryptor, decrypt_key)) = &self.decryption else {
+            return Ok(self.compression.async_decompress(blob));
+        };
+
+        let plaintext_layer = decryptor
+            .async_get_plaintext_layer(blob, &self.descriptor, decrypt_key)
+            .map_err(|e| anyhow!("failed to async_get_plaintext_layer: {:?}", e))?;
+        Ok(self.compression.async_decompress(Box::pin(plaintext_layer)))
+    }
+}
+
 // Wrap a channel with [`Read`](std::io::Read) support.
 // This can bridge the [`AsyncRead`](tokio::io::AsyncRead) from
 // decrypt/decompress and impl Read for unpack.
-struct ChannelRead {
+pub(crate) struct ChannelRead {
     rx: Receiver<Vec<u8>>,
     current: Cursor<Vec<u8>>,
 }
 
 impl ChannelRead {
-    fn new(rx: Receiver<Vec<u8>>) -> ChannelRead {
+    pub(crate) fn new(rx: Receiver<Vec<u8>>) -> ChannelRead {
         ChannelRead {
             rx,
             current: Cursor::new(vec![]),
@@ -52,10 +154,16 @@ impl Read for ChannelRead {
 
 /// stream_processing will handle async uncompressed layer data and
 /// unpack to the destination, returns layer digest for verification.
+///
+/// If `cancel` fires while the layer is still streaming, the partially
+/// unpacked destination is removed and [`ERR_PULL_CANCELLED`] is returned.
+/// If the destination runs out of space, [`LayerStorageFull`] is returned.
 pub async fn stream_processing(
     layer_reader: impl AsyncRead + Unpin,
     diff_id: &str,
     destination: &Path,
+    cancel: &CancellationToken,
+    priority: Option<&BackgroundPriority>,
 ) -> Result<String> {
     let dest = destination.to_path_buf();
     let hasher = if diff_id.starts_with(DIGEST_SHA256_PREFIX) {
@@ -66,23 +174,32 @@ pub async fn stream_processing(
         bail!("{}: {:?}", ERR_BAD_UNCOMPRESSED_DIGEST, diff_id);
     };
 
-    channel_processing(layer_reader, hasher, dest)
+    channel_processing(layer_reader, hasher, dest, cancel, priority.cloned())
         .await
-        .map_err(|e| anyhow!("hasher {} {:?}", DIGEST_SHA256_PREFIX, e))
+        .map_err(|e| match is_storage_full(&e) {
+            true => anyhow::Error::new(LayerStorageFull { source: e }),
+            false => anyhow!("hasher {} {:?}", DIGEST_SHA256_PREFIX, e),
+        })
 }
 
 async fn channel_processing(
     mut layer_reader: (impl AsyncRead + Unpin),
     mut hasher: LayerDigestHasher,
     destination: PathBuf,
+    cancel: &CancellationToken,
+    priority: Option<BackgroundPriority>,
 ) -> Result<String> {
     let (tx, rx) = channel();
+    let unpack_destination = destination.clone();
     let unpack_thread = std::thread::spawn(move || {
+        if let Some(priority) = &priority {
+            crate::priority::apply_or_warn(priority);
+        }
         let mut input = ChannelRead::new(rx);
 
-        if let Err(e) = unpack(&mut input, destination.as_path()) {
+        if let Err(e) = unpack(&mut input, unpack_destination.as_path()) {
             // TODO
-            fs::remove_dir_all(destination.as_path())
+            fs::remove_dir_all(unpack_destination.as_path())
                 .context("Failed to roll back when unpacking")?;
             return Err(e);
         }
@@ -90,29 +207,63 @@ async fn channel_processing(
         Result::<()>::Ok(())
     });
 
+    let mut cancelled = false;
+    let mut read_error = None;
+    let mut send_failed = false;
     loop {
         let mut buffer = vec![0u8; CAPACITY];
-        let n = layer_reader
-            .read(&mut buffer)
-            .await
-            .map_err(|e| anyhow!("channel: read failed {:?}", e))?;
+        let n = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => {
+                cancelled = true;
+                break;
+            }
+            n = layer_reader.read(&mut buffer) => match n {
+                Ok(n) => n,
+                Err(e) => {
+                    read_error = Some(e);
+                    break;
+                }
+            }
+        };
         if n == 0 {
             break;
         }
 
         buffer.resize(n, 0);
         hasher.digest_update(&buffer);
-        tx.send(buffer)
-            .map_err(|e| anyhow!("channel: send failed {:?}", e))?;
+        if tx.send(buffer).is_err() {
+            // The unpack thread stopped receiving, the reason (if it
+            // failed) is returned below.
+            send_failed = true;
+            break;
+        }
     }
 
     // Close the channel to signal EOF.
     drop(tx);
 
-    tokio::task::spawn_blocking(|| unpack_thread.join())
+    let unpack_result = tokio::task::spawn_blocking(|| unpack_thread.join())
         .await?
         .map_err(|e| anyhow!("channel: unpack thread failed {:?}", e))
-        .unwrap()?;
+        .unwrap();
+
+    // The unpack thread has exited at this point, so whatever it managed to
+    // extract from the truncated stream can be safely removed.
+    if (cancelled || read_error.is_some()) && destination.exists() {
+        fs::remove_dir_all(destination.as_path()).context("Failed to clean up truncated layer")?;
+    }
+    if cancelled {
+        bail!(ERR_PULL_CANCELLED);
+    }
+    if let Some(e) = read_error {
+        bail!("channel: read failed {:?}", e);
+    }
+
+    unpack_result?;
+    if send_failed {
+        bail!("channel: send failed, unpack finished before the end of the layer");
+    }
 
     Ok(hasher.digest_finalize())
 }
@@ -152,10 +303,15 @@ mod tests {
 
         let hasher = LayerDigestHasher::Sha256(sha2::Sha256::new());
 
-        let layer_digest_new =
-            channel_processing(layer_data.as_slice(), hasher, file_path.to_path_buf())
-                .await
-                .unwrap();
+        let layer_digest_new = channel_processing(
+            layer_data.as_slice(),
+            hasher,
+            file_path.to_path_buf(),
+            &CancellationToken::new(),
+            None,
+        )
+        .await
+        .unwrap();
         assert_eq!(layer_digest, layer_digest_new);
 
         let file = File::open(file_path.join("file.txt")).unwrap();
@@ -190,9 +346,15 @@ mod tests {
         let tempdir = tempfile::tempdir().unwrap();
         let file_path = tempdir.path().join("layer0");
 
-        let layer_digest_new = stream_processing(layer_data.as_slice(), &layer_digest, &file_path)
-            .await
-            .unwrap();
+        let layer_digest_new = stream_processing(
+            layer_data.as_slice(),
+            &layer_digest,
+            &file_path,
+            &CancellationToken::new(),
+            None,
+        )
+        .await
+        .unwrap();
         assert_eq!(layer_digest, layer_digest_new);
 
         let tempdir = tempfile::tempdir().unwrap();
@@ -203,9 +365,112 @@ mod tests {
             sha2::Sha512::digest(layer_data.as_slice())
         );
 
-        let layer_digest_new = stream_processing(layer_data.as_slice(), &layer_digest, &file_path)
-            .await
-            .unwrap();
+        let layer_digest_new = stream_processing(
+            layer_data.as_slice(),
+            &layer_digest,
+            &file_path,
+            &CancellationToken::new(),
+            None,
+        )
+        .await
+        .unwrap();
         assert_eq!(layer_digest, layer_digest_new);
     }
+
+    #[tokio::test]
+    async fn test_stream_processing_cancelled() {
+        let mut data = [0; 100000];
+        rand_bytes(&mut data).unwrap();
+
+        let mut ar = Builder::new(Vec::new());
+        let mut header = Header::new_gnu();
+        header.set_size(100000);
+        header.set_cksum();
+        ar.append_data(&mut header, "file.txt", data.as_slice())
+            .unwrap();
+
+        let layer_data = ar.into_inner().unwrap();
+        let layer_digest = format!(
+            "{}{:x}",
+            DIGEST_SHA256_PREFIX,
+            sha2::Sha256::digest(layer_data.as_slice())
+        );
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = tempdir.path().join("layer0");
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        let err = stream_processing(
+            layer_data.as_slice(),
+            &layer_digest,
+            &file_path,
+            &cancel,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(format!("{err:?}").contains(ERR_PULL_CANCELLED));
+        assert!(!file_path.exists());
+    }
+
+    struct FailingReader;
+
+    impl AsyncRead for FailingReader {
+        fn poll_read(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            _buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<io::Result<()>> {
+            std::task::Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                "connection reset",
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_processing_read_error() {
+        let mut data = [0; 100000];
+        rand_bytes(&mut data).unwrap();
+
+        let mut ar = Builder::new(Vec::new());
+        let mut header = Header::new_gnu();
+        header.set_size(100000);
+        header.set_cksum();
+        ar.append_data(&mut header, "file.txt", data.as_slice())
+            .unwrap();
+
+        let layer_data = ar.into_inner().unwrap();
+        let layer_digest = format!(
+            "{}{:x}",
+            DIGEST_SHA256_PREFIX,
+            sha2::Sha256::digest(layer_data.as_slice())
+        );
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let file_path = tempdir.path().join("layer0");
+
+        // the connection breaks in the middle of the layer
+        let truncated = (&layer_data[..50000]).chain(FailingReader);
+        let err = stream_processing(
+            truncated,
+            &layer_digest,
+            &file_path,
+            &CancellationToken::new(),
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(format!("{err:?}").contains("connection reset"));
+        assert!(!file_path.exists());
+    }
+
+    #[test]
+    fn test_is_storage_full() {
+        let full = anyhow::Error::new(io::Error::from_raw_os_error(libc::ENOSPC)).context("unpack");
+        assert!(is_storage_full(&full));
+        assert!(!is_storage_full(&anyhow!("unpack failed")));
+    }
 }
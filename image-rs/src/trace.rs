@@ -0,0 +1,33 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Spans of the pull pipeline. With the `telemetry` feature they are
+//! recorded by the `telemetry` crate, children of the span of the request
+//! the image is pulled for; without it the futures are run as they are.
+
+use std::future::Future;
+
+/// Run `fut` in a span `name`.
+pub(crate) async fn in_span<F: Future>(name: &'static str, fut: F) -> F::Output {
+    in_span_with(name, [], fut).await
+}
+
+/// Run `fut` in a span `name` with `attributes`.
+#[cfg(feature = "telemetry")]
+pub(crate) async fn in_span_with<F: Future>(
+    name: &'static str,
+    attributes: impl IntoIterator<Item = (&'static str, String)>,
+    fut: F,
+) -> F::Output {
+    telemetry::in_span_with(name, attributes, fut).await
+}
+
+#[cfg(not(feature = "telemetry"))]
+pub(crate) async fn in_span_with<F: Future>(
+    _name: &'static str,
+    _attributes: impl IntoIterator<Item = (&'static str, String)>,
+    fut: F,
+) -> F::Output {
+    fut.await
+}
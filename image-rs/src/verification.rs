@@ -0,0 +1,96 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Outcome of the signature verification of an image.
+//!
+//! When `security_validate` is enabled, the policy requirements an image
+//! satisfied, and the signatures which satisfied them, are recorded in its
+//! [`ImageMeta`](crate::image::ImageMeta), so that agents can log or attest
+//! why it was accepted. The report of a pulled image can be queried later
+//! with [`ImageClient::verification_report`](crate::image::ImageClient::verification_report).
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Error returned when the policy rejects an image.
+#[derive(Debug)]
+pub struct SignatureRejected(pub String);
+
+impl fmt::Display for SignatureRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Security validate failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for SignatureRejected {}
+
+/// Why an image was accepted by the policy.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct VerificationReport {
+    /// Transport of the image, e.g. `docker`.
+    pub transport: String,
+
+    /// Scope of the transport whose requirements were applied, e.g.
+    /// `quay.io/example`, or `""` for the default of the transport. `None`
+    /// if the `default` requirements of the policy were applied.
+    pub scope: Option<String>,
+
+    /// Digest of the manifest the signatures were verified for.
+    pub manifest_digest: String,
+
+    /// The requirements the image satisfied, in the order of the policy.
+    pub requirements: Vec<RequirementReport>,
+}
+
+impl VerificationReport {
+    /// Whether the image was accepted by a verified signature, instead of
+    /// `insecureAcceptAnything` only.
+    pub fn is_signed(&self) -> bool {
+        self.requirements
+            .iter()
+            .any(|requirement| !requirement.signatures.is_empty())
+    }
+}
+
+/// A policy requirement satisfied by an image.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RequirementReport {
+    /// Type of the requirement in the policy, e.g. `signedBy`.
+    #[serde(rename = "type")]
+    pub requirement_type: String,
+
+    /// The signatures which satisfied the requirement, none for
+    /// `insecureAcceptAnything`.
+    pub signatures: Vec<VerifiedSignature>,
+}
+
+/// A signature verified for an image.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct VerifiedSignature {
+    /// Key the signature was verified with, the fingerprint of GPG keys or
+    /// `sha256:<hex>` of the cosign public key as configured.
+    pub key_id: String,
+
+    /// Docker reference claimed by the signature.
+    pub signed_reference: String,
+
+    /// Type of the `signedIdentity` rule the claimed reference matched,
+    /// e.g. `matchRepoDigestOrExact`. `None` if the reference was not
+    /// checked.
+    pub signed_identity: Option<String>,
+
+    /// Identity of the certificate of keyless cosign signatures.
+    pub certificate_identity: Option<CertificateIdentity>,
+}
+
+/// Identity of the signing certificate of a cosign signature.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CertificateIdentity {
+    /// Email or URI of the subject of the certificate.
+    pub subject: String,
+
+    /// OIDC issuer which authenticated the subject.
+    pub issuer: Option<String>,
+}
@@ -0,0 +1,172 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Images mounted as volumes of containers.
+//!
+//! An image volume (the OCI volume source of Kubernetes) carries data, e.g.
+//! model weights or static assets, to be mounted into a container rather
+//! than run. [`crate::image::ImageClient::mount_image_volume`] pulls it like
+//! a container image, with the signature verification and the decryption
+//! of its layers, and mounts all its layers read-only at the target dir,
+//! without a bundle around them, with the snapshotter of
+//! [`ImageVolumeConfig`].
+//!
+//! Image volumes are mounted with eccfs by default if built in. Their
+//! roimages are only integrity protected then, unless the image has
+//! encrypted layers or the volumes are configured encrypted: the content of
+//! a plaintext image is public anyway, and integrity-only roimages are
+//! cheaper to read, and need no keys from the KBS. Eccfs builds no rw image
+//! for a volume, so that the enclave can only mount it read-only, and names
+//! its resources after the target. Other snapshotters have their mount
+//! remounted read-only.
+//!
+//! The volumes are recorded apart from the bundles in the
+//! [`crate::meta_store::MetaStore`], and the layers of their images are not
+//! evicted while they are mounted, see [`crate::layer_cache`].
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+#[cfg(feature = "snapshot-eccfs")]
+use std::ffi::OsString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use oci_distribution::manifest::OciImageManifest;
+#[cfg(feature = "snapshot-eccfs")]
+use sha2::{Digest, Sha256};
+
+use crate::config::ImageVolumeConfig;
+use crate::decrypt::Decryptor;
+use crate::layer_storage::is_mount_point;
+use crate::snapshots::{MountPoint, SnapshotType};
+
+/// An image mounted as a volume.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ImageVolume {
+    /// The reference the image was pulled with.
+    pub image_url: String,
+
+    /// The ID of the image.
+    pub image_id: String,
+
+    /// The snapshot the volume is mounted with.
+    pub snapshot: SnapshotType,
+
+    /// The mount of the volume, at the target dir.
+    pub mount_point: MountPoint,
+}
+
+impl ImageVolume {
+    /// The dir the volume is mounted at.
+    pub fn target(&self) -> &Path {
+        &self.mount_point.mount_path
+    }
+}
+
+/// Whether the eccfs roimages of a volume of the image of `manifest` are
+/// only integrity protected.
+pub fn integrity_only(config: Option<&ImageVolumeConfig>, manifest: &OciImageManifest) -> bool {
+    let encrypted = config.is_some_and(|config| config.encrypted)
+        || manifest
+            .layers
+            .iter()
+            .any(|layer| Decryptor::from_descriptor(layer).is_encrypted());
+    !encrypted
+}
+
+/// The name eccfs gives the resources of the volume at `target`, instead
+/// of the name of its parent dir, which the other volumes of the container
+/// share.
+#[cfg(feature = "snapshot-eccfs")]
+pub fn eccfs_name(target: &Path) -> OsString {
+    let digest = Sha256::digest(target.as_os_str().as_bytes());
+    OsString::from(format!("volume-{digest:x}"))
+}
+
+/// Remount the mount at `target` read-only. Not for eccfs, which leaves the
+/// target unmounted: the roimages are mounted by the enclave itself, which
+/// has no rw image to mount for a volume.
+pub fn remount_read_only(target: &Path) -> Result<()> {
+    if !is_mount_point(target)? {
+        bail!("{} is not mounted", target.display());
+    }
+
+    let path = CString::new(target.as_os_str().as_bytes())?;
+    let flags = libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY;
+    let res = unsafe {
+        libc::mount(
+            std::ptr::null(),
+            path.as_ptr(),
+            std::ptr::null(),
+            flags,
+            std::ptr::null(),
+        )
+    };
+    if res != 0 {
+        bail!(
+            "failed to remount {} read-only: {}",
+            target.display(),
+            io::Error::last_os_error()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use oci_distribution::manifest::OciDescriptor;
+
+    fn manifest(media_types: &[&str]) -> OciImageManifest {
+        OciImageManifest {
+            layers: media_types
+                .iter()
+                .map(|media_type| OciDescriptor {
+                    media_type: media_type.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_integrity_only() {
+        let plain = manifest(&["application/vnd.oci.image.layer.v1.tar+gzip"]);
+        let encrypted = manifest(&[
+            "application/vnd.oci.image.layer.v1.tar+gzip",
+            "application/vnd.oci.image.layer.v1.tar+gzip+encrypted",
+        ]);
+        assert!(integrity_only(None, &plain));
+        assert!(!integrity_only(None, &encrypted));
+
+        let config = ImageVolumeConfig {
+            encrypted: true,
+            ..Default::default()
+        };
+        assert!(!integrity_only(Some(&config), &plain));
+        assert!(integrity_only(Some(&ImageVolumeConfig::default()), &plain));
+    }
+
+    #[test]
+    fn test_remount_unmounted() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let target = tempdir.path().join("volume");
+        std::fs::create_dir(&target).unwrap();
+        assert!(remount_read_only(&target).is_err());
+    }
+
+    #[cfg(feature = "snapshot-eccfs")]
+    #[test]
+    fn test_eccfs_name() {
+        let data = eccfs_name(Path::new("/run/volumes/data"));
+        assert!(data.to_str().unwrap().starts_with("volume-"));
+        assert_eq!(data, eccfs_name(Path::new("/run/volumes/data")));
+        assert_ne!(data, eccfs_name(Path::new("/run/volumes/models")));
+    }
+}
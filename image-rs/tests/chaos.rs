@@ -0,0 +1,321 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Chaos tests of the pull pipeline: a fault is injected into a pull, see
+//! `image_rs::fault`, and the failed pull must leave nothing behind, before
+//! the image is pulled again.
+
+#![cfg(all(feature = "fault-injection", feature = "snapshot-overlayfs"))]
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use image_rs::config::RelabelConfig;
+use image_rs::fault::{Fault, InjectedFault};
+use image_rs::image::ImageClient;
+use image_rs::pull::{LayerDigestMismatch, QuarantinedLayer};
+
+pub mod common;
+
+const IMAGE: &str = "mcr.microsoft.com/hello-world";
+
+#[cfg(all(
+    feature = "getresource",
+    feature = "encryption",
+    feature = "keywrap-ttrpc"
+))]
+const ENCRYPTED_IMAGE: &str = "ghcr.io/confidential-containers/test-container:encrypted";
+
+#[cfg(all(
+    feature = "getresource",
+    feature = "encryption",
+    feature = "keywrap-ttrpc"
+))]
+const OCICRYPT_CONFIG: &str = "test_data/ocicrypt_keyprovider_ttrpc.conf";
+
+// the mount points under `dir`
+fn mounts_under(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_to_string("/proc/self/mountinfo")
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.split(' ').nth(4))
+        .map(PathBuf::from)
+        .filter(|mount_point| mount_point.starts_with(dir))
+        .collect()
+}
+
+fn dir_entries(dir: &Path) -> Vec<PathBuf> {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => entries.map(|entry| entry.unwrap().path()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+// the eccfs roimages under `dir`
+#[cfg(feature = "snapshot-eccfs")]
+fn roimages_under(dir: &Path) -> Vec<PathBuf> {
+    let mut roimages = Vec::new();
+    for path in dir_entries(dir) {
+        if path.is_dir() {
+            roimages.extend(roimages_under(&path));
+        } else if path.extension().is_some_and(|ext| ext == "roimage") {
+            roimages.push(path);
+        }
+    }
+    roimages
+}
+
+// no mount is left, no bundle recorded, and no layer is in the layer store
+// but the ones fully pulled and verified
+async fn assert_clean(client: &ImageClient, work_dir: &Path, bundle_dir: &Path) {
+    assert_eq!(mounts_under(work_dir), Vec::<PathBuf>::new());
+    assert_eq!(mounts_under(bundle_dir), Vec::<PathBuf>::new());
+
+    let m = client.meta_store.lock().await;
+    assert!(m.bundle_db.is_empty());
+    let recorded: BTreeSet<PathBuf> = m
+        .layer_db
+        .values()
+        .map(|layer| PathBuf::from(&layer.store_path))
+        .collect();
+    for layer in dir_entries(&work_dir.join("layers")) {
+        assert!(recorded.contains(&layer), "{} left", layer.display());
+    }
+}
+
+// the image is pulled fine once the fault is over
+async fn assert_pulls(client: &ImageClient, bundle_dir: &tempfile::TempDir) {
+    client
+        .pull_image(IMAGE, bundle_dir.path(), &None, &None)
+        .await
+        .unwrap();
+    assert_eq!(mounts_under(bundle_dir.path()).len(), 1);
+    common::umount_bundle(bundle_dir);
+}
+
+#[tokio::test]
+async fn test_drop_blob_request() {
+    let work_dir = tempfile::tempdir().unwrap();
+    let bundle_dir = tempfile::tempdir().unwrap();
+    let client = ImageClient::new(work_dir.path().to_path_buf());
+
+    client.faults.inject(Fault::DropBlobRequest(0));
+    let e = client
+        .pull_image(IMAGE, bundle_dir.path(), &None, &None)
+        .await
+        .unwrap_err();
+    assert!(e.chain().any(|e| e.is::<InjectedFault>()), "{e:#}");
+    assert_eq!(client.faults.fired(), [Fault::DropBlobRequest(0)]);
+    assert_clean(&client, work_dir.path(), bundle_dir.path()).await;
+
+    assert_pulls(&client, &bundle_dir).await;
+}
+
+#[tokio::test]
+async fn test_corrupt_layer_digest() {
+    let work_dir = tempfile::tempdir().unwrap();
+    let bundle_dir = tempfile::tempdir().unwrap();
+    let mut client = ImageClient::new(work_dir.path().to_path_buf());
+    client.config.max_layer_retries = 0;
+
+    client.faults.inject(Fault::CorruptLayerDigest);
+    let e = client
+        .pull_image(IMAGE, bundle_dir.path(), &None, &None)
+        .await
+        .unwrap_err();
+    assert!(e.chain().any(|e| e.is::<LayerDigestMismatch>()), "{e:#}");
+    assert_clean(&client, work_dir.path(), bundle_dir.path()).await;
+    assert!(client.meta_store.lock().await.layer_db.is_empty());
+
+    // the quarantine only records the corrupt layer, none of its contents
+    for entry in dir_entries(&client.config.quarantine_dir()) {
+        assert!(entry.is_file(), "{} left", entry.display());
+        let record: QuarantinedLayer =
+            serde_json::from_slice(&std::fs::read(&entry).unwrap()).unwrap();
+        assert!(!record.encrypted);
+    }
+
+    assert_pulls(&client, &bundle_dir).await;
+}
+
+#[tokio::test]
+async fn test_corrupt_layer_digest_retried() {
+    let work_dir = tempfile::tempdir().unwrap();
+    let bundle_dir = tempfile::tempdir().unwrap();
+    let client = ImageClient::new(work_dir.path().to_path_buf());
+
+    client.faults.inject(Fault::CorruptLayerDigest);
+    assert_pulls(&client, &bundle_dir).await;
+    assert_eq!(client.faults.fired(), [Fault::CorruptLayerDigest]);
+}
+
+#[cfg(all(
+    feature = "getresource",
+    feature = "encryption",
+    feature = "keywrap-ttrpc"
+))]
+#[tokio::test]
+#[serial_test::serial]
+async fn test_delay_decrypt_cancelled() {
+    use image_rs::ERR_PULL_CANCELLED;
+    use std::time::Duration;
+    use tokio_util::sync::CancellationToken;
+
+    common::prepare_test(common::OFFLINE_FS_KBC_RESOURCES_FILE).await;
+    let _cdh = common::start_confidential_data_hub()
+        .await
+        .expect("Failed to start confidential data hub!");
+    let manifest_dir = std::env!("CARGO_MANIFEST_DIR");
+    let keyprovider_config = format!("{}/{}", manifest_dir, OCICRYPT_CONFIG);
+    std::env::set_var("OCICRYPT_KEYPROVIDER_CONFIG", keyprovider_config);
+
+    let work_dir = tempfile::tempdir().unwrap();
+    let bundle_dir = tempfile::tempdir().unwrap();
+    let client = ImageClient::new(work_dir.path().to_path_buf());
+    let decrypt_config = Some(common::AA_PARAMETER);
+
+    client
+        .faults
+        .inject(Fault::DelayDecrypt(Duration::from_secs(600)));
+    let cancel = CancellationToken::new();
+    let pull = client.pull_image_with_cancellation(
+        ENCRYPTED_IMAGE,
+        bundle_dir.path(),
+        &None,
+        &decrypt_config,
+        &cancel,
+    );
+    let cancelling = async {
+        // the layer is being unpacked once its stream stalls
+        while client.faults.fired().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        cancel.cancel();
+    };
+    let (res, ()) = tokio::time::timeout(Duration::from_secs(120), async {
+        tokio::join!(pull, cancelling)
+    })
+    .await
+    .expect("the cancelled pull is stuck");
+
+    let e = res.unwrap_err();
+    assert!(format!("{e:#}").contains(ERR_PULL_CANCELLED), "{e:#}");
+    assert_clean(&client, work_dir.path(), bundle_dir.path()).await;
+
+    // no plaintext of the layer is left
+    assert!(client.meta_store.lock().await.layer_db.is_empty());
+    assert!(dir_entries(&work_dir.path().join("layers")).is_empty());
+
+    client
+        .pull_image(ENCRYPTED_IMAGE, bundle_dir.path(), &None, &decrypt_config)
+        .await
+        .unwrap();
+    common::umount_bundle(&bundle_dir);
+
+    common::clean().await;
+}
+
+#[tokio::test]
+async fn test_fail_mount() {
+    let work_dir = tempfile::tempdir().unwrap();
+    let bundle_dir = tempfile::tempdir().unwrap();
+    let client = ImageClient::new(work_dir.path().to_path_buf());
+    client
+        .faults
+        .wrap_snapshots(&mut *client.snapshots.lock().await);
+
+    client.faults.inject(Fault::FailMount(0));
+    let e = client
+        .pull_image(IMAGE, bundle_dir.path(), &None, &None)
+        .await
+        .unwrap_err();
+    assert!(e.chain().any(|e| e.is::<InjectedFault>()), "{e:#}");
+    assert_clean(&client, work_dir.path(), bundle_dir.path()).await;
+
+    // the layers are kept for the next pull
+    assert!(!client.meta_store.lock().await.layer_db.is_empty());
+
+    assert_pulls(&client, &bundle_dir).await;
+}
+
+#[cfg(feature = "snapshot-eccfs")]
+#[tokio::test]
+async fn test_fail_mount_eccfs() {
+    // mounting needs root, and occlum
+    if !nix::unistd::Uid::effective().is_root() {
+        return;
+    }
+    let work_dir = tempfile::tempdir().unwrap();
+    let volumes_dir = tempfile::tempdir().unwrap();
+    let client = ImageClient::new(work_dir.path().to_path_buf());
+    if !client.eccfs_preflight().passed() {
+        return;
+    }
+    // the eccfs snapshotter built for the volume is wrapped as well
+    client
+        .faults
+        .wrap_snapshots(&mut *client.snapshots.lock().await);
+
+    let target = volumes_dir.path().join("data");
+    client.faults.inject(Fault::FailMount(0));
+    let e = client
+        .mount_image_volume(IMAGE, &target, &None, &None)
+        .await
+        .unwrap_err();
+    assert!(e.chain().any(|e| e.is::<InjectedFault>()), "{e:#}");
+    assert_eq!(client.faults.fired(), [Fault::FailMount(0)]);
+    assert_eq!(mounts_under(volumes_dir.path()), Vec::<PathBuf>::new());
+    assert!(client.image_volumes().await.is_empty());
+    assert_eq!(roimages_under(volumes_dir.path()), Vec::<PathBuf>::new());
+
+    // the volume is mounted once the fault is over, and leaves no roimage
+    // behind once unmounted
+    client
+        .mount_image_volume(IMAGE, &target, &None, &None)
+        .await
+        .unwrap();
+    assert!(client.unmount_image_volume(&target).await.unwrap());
+    assert_eq!(mounts_under(volumes_dir.path()), Vec::<PathBuf>::new());
+    assert_eq!(roimages_under(volumes_dir.path()), Vec::<PathBuf>::new());
+}
+
+#[tokio::test]
+async fn test_fail_relabel() {
+    let work_dir = tempfile::tempdir().unwrap();
+    let bundle_dir = tempfile::tempdir().unwrap();
+    let mut client = ImageClient::new(work_dir.path().to_path_buf());
+    client.config.rootfs_relabel = Some(RelabelConfig::default());
+
+    client.faults.inject(Fault::FailRelabel);
+    let e = client
+        .pull_image(IMAGE, bundle_dir.path(), &None, &None)
+        .await
+        .unwrap_err();
+    assert!(e.chain().any(|e| e.is::<InjectedFault>()), "{e:#}");
+    assert_clean(&client, work_dir.path(), bundle_dir.path()).await;
+
+    assert_pulls(&client, &bundle_dir).await;
+}
+
+#[tokio::test]
+async fn test_fail_after_mount() {
+    let work_dir = tempfile::tempdir().unwrap();
+    let bundle_dir = tempfile::tempdir().unwrap();
+    let client = ImageClient::new(work_dir.path().to_path_buf());
+
+    // the runtime config is written once the rootfs is mounted, and fails
+    // if one is there already
+    let config = bundle_dir.path().join("config.json");
+    std::fs::write(&config, b"{}").unwrap();
+    assert!(client
+        .pull_image(IMAGE, bundle_dir.path(), &None, &None)
+        .await
+        .is_err());
+    assert_clean(&client, work_dir.path(), bundle_dir.path()).await;
+
+    std::fs::remove_file(&config).unwrap();
+    assert_pulls(&client, &bundle_dir).await;
+}
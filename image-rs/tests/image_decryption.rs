@@ -66,7 +66,7 @@ async fn test_decrypt_layers(#[case] image: &str) {
     common::clean_configs()
         .await
         .expect("Delete configs failed.");
-    let mut image_client = ImageClient::new(work_dir.path().to_path_buf());
+    let image_client = ImageClient::new(work_dir.path().to_path_buf());
     if cfg!(feature = "snapshot-overlayfs") {
         image_client
             .pull_image(image, bundle_dir.path(), &None, &Some(common::AA_PARAMETER))
@@ -0,0 +1,33 @@
+// Copyright The ocicrypt Authors.
+// SPDX-License-Identifier: Apache-2.0
+
+#![no_main]
+
+use std::collections::HashMap;
+
+use libfuzzer_sys::fuzz_target;
+use ocicrypt_rs::annotations::{parse_pub_opts, parse_wrapped_keys, ANNOTATION_PUBOPTS};
+use ocicrypt_rs::config::DecryptConfig;
+use ocicrypt_rs::encryption::decrypt_layer;
+
+const ANNOTATION_JWE: &str = "org.opencontainers.image.enc.keys.jwe";
+
+// The first line is taken as the wrapped keys, the rest as the public
+// options of the layer.
+fuzz_target!(|data: &str| {
+    let (keys, pub_opts) = data.split_once('\n').unwrap_or((data, ""));
+    let _ = parse_wrapped_keys(ANNOTATION_JWE, keys);
+    let _ = parse_pub_opts(pub_opts);
+
+    let mut dc = DecryptConfig::default();
+    dc.decrypt_with_priv_keys(
+        vec![include_bytes!("../../data/private_key.pem").to_vec()],
+        vec![vec![]],
+    )
+    .unwrap();
+    let annotations = HashMap::from([
+        (ANNOTATION_JWE.to_string(), keys.to_string()),
+        (ANNOTATION_PUBOPTS.to_string(), pub_opts.to_string()),
+    ]);
+    let _ = decrypt_layer(&dc, &b""[..], Some(&annotations), false);
+});
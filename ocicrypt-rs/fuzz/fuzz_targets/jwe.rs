@@ -0,0 +1,22 @@
+// Copyright The ocicrypt Authors.
+// SPDX-License-Identifier: Apache-2.0
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ocicrypt_rs::annotations::check_jwe;
+use ocicrypt_rs::config::DecryptConfig;
+use ocicrypt_rs::keywrap::jwe::JweKeyWrapper;
+use ocicrypt_rs::keywrap::KeyWrapper;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = check_jwe(data);
+
+    let mut dc = DecryptConfig::default();
+    dc.decrypt_with_priv_keys(
+        vec![include_bytes!("../../data/private_key_ec.der").to_vec()],
+        vec![vec![]],
+    )
+    .unwrap();
+    let _ = JweKeyWrapper {}.unwrap_keys(&dc, data);
+});
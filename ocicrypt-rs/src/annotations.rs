@@ -0,0 +1,316 @@
+// Copyright The ocicrypt Authors.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Parsing of the `org.opencontainers.image.enc.*` annotations.
+//!
+//! The annotations of a layer descriptor come from the registry, which is
+//! not trusted. They are checked here before any of their content reaches
+//! a key wrapper or the block cipher, and malformed values are rejected
+//! with an [`AnnotationError`].
+
+use base64::Engine;
+use thiserror::Error;
+
+#[cfg(feature = "block-cipher")]
+use crate::blockcipher::{PrivateLayerBlockCipherOptions, PublicLayerBlockCipherOptions};
+
+/// Annotation of the public options of the layer block cipher.
+pub const ANNOTATION_PUBOPTS: &str = "org.opencontainers.image.enc.pubopts";
+
+/// Max size of an annotation value, or of a JWE.
+pub const MAX_ANNOTATION_SIZE: usize = 1024 * 1024;
+
+/// Max number of wrapped keys in an annotation, or of JWE recipients.
+pub const MAX_WRAPPED_KEYS: usize = 64;
+
+/// A malformed annotation.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AnnotationError {
+    #[error("annotation {0} is empty")]
+    Empty(String),
+
+    #[error("annotation {name} of {size} bytes exceeds the limit of {MAX_ANNOTATION_SIZE} bytes")]
+    TooLarge { name: String, size: usize },
+
+    #[error("annotation {name} has {count} wrapped keys, the limit is {MAX_WRAPPED_KEYS}")]
+    TooManyKeys { name: String, count: usize },
+
+    #[error("entry {index} of annotation {name} is not valid base64")]
+    Base64 { name: String, index: usize },
+
+    #[error("annotation {name} is not valid JSON: {reason}")]
+    Json { name: String, reason: String },
+
+    #[error("unwrapped private options are not valid JSON: {0}")]
+    PrivOpts(String),
+
+    #[error("malformed JWE: {0}")]
+    Jwe(String),
+}
+
+fn check_size(name: &str, size: usize) -> Result<(), AnnotationError> {
+    if size > MAX_ANNOTATION_SIZE {
+        return Err(AnnotationError::TooLarge {
+            name: name.to_string(),
+            size,
+        });
+    }
+
+    Ok(())
+}
+
+/// Decode the comma separated base64 wrapped keys of the annotation
+/// `name`.
+pub fn parse_wrapped_keys(name: &str, value: &str) -> Result<Vec<Vec<u8>>, AnnotationError> {
+    if value.is_empty() {
+        return Err(AnnotationError::Empty(name.to_string()));
+    }
+    check_size(name, value.len())?;
+
+    let count = value.split(',').count();
+    if count > MAX_WRAPPED_KEYS {
+        return Err(AnnotationError::TooManyKeys {
+            name: name.to_string(),
+            count,
+        });
+    }
+
+    value
+        .split(',')
+        .enumerate()
+        .map(|(index, key)| {
+            let base64_err = || AnnotationError::Base64 {
+                name: name.to_string(),
+                index,
+            };
+            let key = base64::engine::general_purpose::STANDARD
+                .decode(key)
+                .map_err(|_| base64_err())?;
+            if key.is_empty() {
+                return Err(base64_err());
+            }
+            Ok(key)
+        })
+        .collect()
+}
+
+/// Decode the [`ANNOTATION_PUBOPTS`] annotation.
+#[cfg(feature = "block-cipher")]
+pub fn parse_pub_opts(value: &str) -> Result<PublicLayerBlockCipherOptions, AnnotationError> {
+    check_size(ANNOTATION_PUBOPTS, value.len())?;
+    let json = base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map_err(|_| AnnotationError::Base64 {
+            name: ANNOTATION_PUBOPTS.to_string(),
+            index: 0,
+        })?;
+
+    serde_json::from_slice(&json).map_err(|e| AnnotationError::Json {
+        name: ANNOTATION_PUBOPTS.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// Decode the private options of the layer block cipher unwrapped from a
+/// key annotation.
+#[cfg(feature = "block-cipher")]
+pub fn parse_priv_opts(data: &[u8]) -> Result<PrivateLayerBlockCipherOptions, AnnotationError> {
+    serde_json::from_slice(data).map_err(|e| AnnotationError::PrivOpts(e.to_string()))
+}
+
+/// Check that `data` is a JWE in JSON serialization, general or flattened,
+/// with at most [`MAX_WRAPPED_KEYS`] recipients, and return it as a string.
+pub fn check_jwe(data: &[u8]) -> Result<&str, AnnotationError> {
+    if data.len() > MAX_ANNOTATION_SIZE {
+        return Err(AnnotationError::Jwe(format!(
+            "{} bytes exceed the limit of {MAX_ANNOTATION_SIZE} bytes",
+            data.len()
+        )));
+    }
+    let jwe = std::str::from_utf8(data).map_err(|_| AnnotationError::Jwe("not UTF-8".into()))?;
+    let value: serde_json::Value =
+        serde_json::from_str(jwe).map_err(|e| AnnotationError::Jwe(e.to_string()))?;
+    let object = value
+        .as_object()
+        .ok_or_else(|| AnnotationError::Jwe("not a JSON object".into()))?;
+
+    check_base64url_member(object, "ciphertext", true)?;
+    check_base64url_member(object, "protected", false)?;
+    check_base64url_member(object, "iv", false)?;
+    check_base64url_member(object, "tag", false)?;
+
+    match object.get("recipients") {
+        Some(recipients) => {
+            let recipients = recipients
+                .as_array()
+                .ok_or_else(|| AnnotationError::Jwe("recipients is not an array".into()))?;
+            if recipients.is_empty() || recipients.len() > MAX_WRAPPED_KEYS {
+                return Err(AnnotationError::Jwe(format!(
+                    "{} recipients, expected 1 to {MAX_WRAPPED_KEYS}",
+                    recipients.len()
+                )));
+            }
+            for recipient in recipients {
+                let recipient = recipient
+                    .as_object()
+                    .ok_or_else(|| AnnotationError::Jwe("recipient is not an object".into()))?;
+                check_base64url_member(recipient, "encrypted_key", false)?;
+            }
+        }
+        None => check_base64url_member(object, "encrypted_key", false)?,
+    }
+
+    Ok(jwe)
+}
+
+fn check_base64url_member(
+    object: &serde_json::Map<String, serde_json::Value>,
+    member: &str,
+    required: bool,
+) -> Result<(), AnnotationError> {
+    let Some(value) = object.get(member) else {
+        if required {
+            return Err(AnnotationError::Jwe(format!("missing {member}")));
+        }
+        return Ok(());
+    };
+
+    let value = value
+        .as_str()
+        .ok_or_else(|| AnnotationError::Jwe(format!("{member} is not a string")))?;
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(value)
+        .map_err(|_| AnnotationError::Jwe(format!("{member} is not valid base64url")))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAME: &str = "org.opencontainers.image.enc.keys.jwe";
+
+    #[test]
+    fn test_parse_wrapped_keys() {
+        assert_eq!(
+            parse_wrapped_keys(NAME, "YQ==,YmM=").unwrap(),
+            vec![b"a".to_vec(), b"bc".to_vec()]
+        );
+
+        let vectors = [
+            ("", AnnotationError::Empty(NAME.into())),
+            (
+                "YQ==,",
+                AnnotationError::Base64 {
+                    name: NAME.into(),
+                    index: 1,
+                },
+            ),
+            (
+                ",YQ==",
+                AnnotationError::Base64 {
+                    name: NAME.into(),
+                    index: 0,
+                },
+            ),
+            (
+                "YQ==,!!!!",
+                AnnotationError::Base64 {
+                    name: NAME.into(),
+                    index: 1,
+                },
+            ),
+            (
+                "YQ",
+                AnnotationError::Base64 {
+                    name: NAME.into(),
+                    index: 0,
+                },
+            ),
+        ];
+        for (value, expected) in vectors {
+            assert_eq!(parse_wrapped_keys(NAME, value).unwrap_err(), expected);
+        }
+
+        let many = vec!["YQ=="; MAX_WRAPPED_KEYS + 1].join(",");
+        assert_eq!(
+            parse_wrapped_keys(NAME, &many).unwrap_err(),
+            AnnotationError::TooManyKeys {
+                name: NAME.into(),
+                count: MAX_WRAPPED_KEYS + 1
+            }
+        );
+
+        let large = "A".repeat(MAX_ANNOTATION_SIZE + 4);
+        assert!(matches!(
+            parse_wrapped_keys(NAME, &large).unwrap_err(),
+            AnnotationError::TooLarge { .. }
+        ));
+    }
+
+    #[cfg(feature = "block-cipher")]
+    #[test]
+    fn test_parse_opts() {
+        let b64 = |s: &str| base64::engine::general_purpose::STANDARD.encode(s);
+
+        let opts = parse_pub_opts(&b64(
+            r#"{"cipher":"AES_256_CTR_HMAC_SHA256","hmac":"YQ==","cipheroptions":{}}"#,
+        ))
+        .unwrap();
+        assert_eq!(opts.hmac, b"a");
+
+        for value in [
+            "!!".to_string(),
+            b64("not json"),
+            b64(r#"{"hmac":"","cipheroptions":{}}"#),
+            b64(r#"{"cipher":"AES_256_CTR_HMAC_SHA256","hmac":"!!","cipheroptions":{}}"#),
+            b64(r#"{"cipher":"AES_256_CTR_HMAC_SHA256","hmac":"","cipheroptions":{"nonce":1}}"#),
+        ] {
+            assert!(parse_pub_opts(&value).is_err(), "{value}");
+        }
+
+        let opts =
+            parse_priv_opts(br#"{"symkey":"YQ==","cipheroptions":{"nonce":"Yg=="},"digest":""}"#)
+                .unwrap();
+        assert_eq!(opts.symmetric_key, b"a");
+        assert_eq!(opts.cipher_options["nonce"], b"b");
+        assert!(parse_priv_opts(b"\xff").is_err());
+        assert!(parse_priv_opts(br#"{"symkey":5}"#).is_err());
+    }
+
+    #[test]
+    fn test_check_jwe() {
+        let general = r#"{"protected":"eyJlbmMiOiJBMjU2R0NNIn0","recipients":[{"header":{"alg":"RSA-OAEP"},"encrypted_key":"YWJj"}],"iv":"YWJj","ciphertext":"YWJj","tag":"YWJj"}"#;
+        assert_eq!(check_jwe(general.as_bytes()).unwrap(), general);
+        let flattened = r#"{"protected":"eyJlbmMiOiJBMjU2R0NNIn0","encrypted_key":"YWJj","iv":"YWJj","ciphertext":"YWJj","tag":"YWJj"}"#;
+        assert!(check_jwe(flattened.as_bytes()).is_ok());
+
+        let malformed: &[&[u8]] = &[
+            b"",
+            b"\xff\xfe",
+            b"null",
+            b"[]",
+            b"{}",
+            br#"{"ciphertext":5}"#,
+            br#"{"ciphertext":"YW=j"}"#,
+            br#"{"ciphertext":"YWJj","protected":"+/"}"#,
+            br#"{"ciphertext":"YWJj","recipients":{}}"#,
+            br#"{"ciphertext":"YWJj","recipients":[]}"#,
+            br#"{"ciphertext":"YWJj","recipients":["a"]}"#,
+            br#"{"ciphertext":"YWJj","recipients":[{"encrypted_key":null}]}"#,
+            br#"{"ciphertext":"YWJj","encrypted_key":"*"}"#,
+        ];
+        for jwe in malformed {
+            assert!(
+                matches!(check_jwe(jwe), Err(AnnotationError::Jwe(_))),
+                "{}",
+                String::from_utf8_lossy(jwe)
+            );
+        }
+
+        let recipients = vec![r#"{"encrypted_key":"YWJj"}"#; MAX_WRAPPED_KEYS + 1].join(",");
+        let jwe = format!(r#"{{"ciphertext":"YWJj","recipients":[{recipients}]}}"#);
+        assert!(check_jwe(jwe.as_bytes()).is_err());
+    }
+}
@@ -9,7 +9,9 @@ use ctr::cipher::{KeyIvInit, StreamCipher};
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
-use crate::blockcipher::{EncryptionFinalizer, LayerBlockCipher, LayerBlockCipherOptions};
+use crate::blockcipher::{
+    EncryptionFinalizer, LayerBlockCipher, LayerBlockCipherOptions, MAX_CHUNK_SIZE,
+};
 
 use super::rand::rand_bytes;
 
@@ -145,7 +147,8 @@ impl<R: Read> Read for AESCTRBlockCipher<R> {
             return Ok(0);
         }
 
-        let read_len = state.reader.read(buf)?;
+        let chunk_len = buf.len().min(MAX_CHUNK_SIZE);
+        let read_len = state.reader.read(&mut buf[..chunk_len])?;
         if read_len == 0 {
             state.done = true;
         }
@@ -210,10 +213,16 @@ impl<R: tokio::io::AsyncRead> tokio::io::AsyncRead for AESCTRBlockCipher<R> {
         }
 
         let start_pos = buf.filled().len();
-        match reader.poll_read(cx, buf) {
+        let mut chunk = buf.take(MAX_CHUNK_SIZE);
+        match reader.poll_read(cx, &mut chunk) {
             Poll::Pending => return Poll::Pending,
             Poll::Ready(res) => res?,
         }
+        let read_len = chunk.filled().len();
+        // The chunk is the start of the unfilled part of `buf`, so the bytes
+        // read into it are initialized in `buf` as well.
+        unsafe { buf.assume_init(read_len) };
+        buf.advance(read_len);
         let buf_filled = &mut buf.filled_mut()[start_pos..];
         if buf_filled.is_empty() {
             *done = true;
@@ -12,6 +12,11 @@ use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 mod aes_ctr;
 use aes_ctr::AESCTRBlockCipher;
 
+#[cfg(feature = "block-cipher-sm4")]
+mod sm4_ctr;
+#[cfg(feature = "block-cipher-sm4")]
+use sm4_ctr::SM4CTRBlockCipher;
+
 pub mod rand;
 
 /// Type of the cipher algorithm used to encrypt/decrypt image layers.
@@ -21,6 +26,16 @@ pub type LayerCipherType = String;
 /// The default cipher algorithm for image layer encryption/decryption.
 pub const AES256CTR: &str = "AES_256_CTR_HMAC_SHA256";
 
+/// The ShangMi cipher algorithm for image layer encryption/decryption.
+pub const SM4CTR: &str = "SM4_128_CTR_HMAC_SM3";
+
+/// Most bytes the block ciphers read from their input at once, whatever the
+/// size of the buffer they are read into. The layer is processed in place
+/// chunk by chunk, so decrypting it takes the same memory whatever its size,
+/// and the readers below, e.g. an HTTP body or a decompressor, are never
+/// asked for more than a chunk.
+pub const MAX_CHUNK_SIZE: usize = 64 * 1024;
+
 base64_serde_type!(Base64Vec, base64::engine::general_purpose::STANDARD);
 
 fn base64_hashmap_s<S>(value: &HashMap<String, Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
@@ -151,6 +166,10 @@ pub trait EncryptionFinalizer {
 pub enum LayerBlockCipherHandler<R> {
     /// AES_256_CTR_HMAC_SHA256
     Aes256Ctr(AESCTRBlockCipher<R>),
+
+    /// SM4_128_CTR_HMAC_SM3
+    #[cfg(feature = "block-cipher-sm4")]
+    Sm4Ctr(SM4CTRBlockCipher<R>),
 }
 
 impl<R> LayerBlockCipherHandler<R> {
@@ -159,6 +178,17 @@ impl<R> LayerBlockCipherHandler<R> {
         let aes_ctr_block_cipher = AESCTRBlockCipher::new(256)?;
         Ok(LayerBlockCipherHandler::Aes256Ctr(aes_ctr_block_cipher))
     }
+
+    /// Create a [`LayerBlockCipherHandler`] object for the cipher algorithm `typ`, e.g. the
+    /// one recorded in the public options of an encrypted layer.
+    pub fn for_cipher(typ: &str) -> Result<LayerBlockCipherHandler<R>> {
+        match typ {
+            AES256CTR => Self::new(),
+            #[cfg(feature = "block-cipher-sm4")]
+            SM4CTR => Ok(LayerBlockCipherHandler::Sm4Ctr(SM4CTRBlockCipher::new())),
+            _ => Err(anyhow!("unsupported cipher type {}", typ)),
+        }
+    }
 }
 
 impl<R> LayerBlockCipherHandler<R> {
@@ -178,6 +208,15 @@ impl<R> LayerBlockCipherHandler<R> {
                 opts.public.cipher_type = AES256CTR.to_string();
                 block_cipher.encrypt(plain_data_reader, opts)?;
             }
+            #[cfg(feature = "block-cipher-sm4")]
+            LayerBlockCipherHandler::Sm4Ctr(block_cipher) => {
+                if typ != SM4CTR {
+                    return Err(anyhow!("unsupported cipher type {}", typ));
+                }
+                opts.private.symmetric_key = block_cipher.generate_key()?;
+                opts.public.cipher_type = SM4CTR.to_string();
+                block_cipher.encrypt(plain_data_reader, opts)?;
+            }
         }
 
         Ok(())
@@ -198,6 +237,13 @@ impl<R> LayerBlockCipherHandler<R> {
                 }
                 block_cipher.decrypt(enc_data_reader, opts)?;
             }
+            #[cfg(feature = "block-cipher-sm4")]
+            LayerBlockCipherHandler::Sm4Ctr(block_cipher) => {
+                if typ != SM4CTR {
+                    return Err(anyhow!("unsupported cipher type {}", typ));
+                }
+                block_cipher.decrypt(enc_data_reader, opts)?;
+            }
         }
 
         Ok(())
@@ -208,6 +254,8 @@ impl<R> EncryptionFinalizer for LayerBlockCipherHandler<R> {
     fn finalized_lbco(&self, opts: &mut LayerBlockCipherOptions) -> Result<()> {
         match self {
             LayerBlockCipherHandler::Aes256Ctr(block_cipher) => block_cipher.finalized_lbco(opts),
+            #[cfg(feature = "block-cipher-sm4")]
+            LayerBlockCipherHandler::Sm4Ctr(block_cipher) => block_cipher.finalized_lbco(opts),
         }
     }
 }
@@ -216,6 +264,8 @@ impl<R: Read> Read for LayerBlockCipherHandler<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
             LayerBlockCipherHandler::Aes256Ctr(block_cipher) => block_cipher.read(buf),
+            #[cfg(feature = "block-cipher-sm4")]
+            LayerBlockCipherHandler::Sm4Ctr(block_cipher) => block_cipher.read(buf),
         }
     }
 }
@@ -228,12 +278,17 @@ impl<R: tokio::io::AsyncRead> tokio::io::AsyncRead for LayerBlockCipherHandler<R
         buf: &mut tokio::io::ReadBuf<'_>,
     ) -> std::task::Poll<std::io::Result<()>> {
         // This is okay because `block_cipher` is pinned when `self` is.
-        let aes_ctr_256 = unsafe {
-            self.map_unchecked_mut(|v| match v {
-                LayerBlockCipherHandler::Aes256Ctr(block_cipher) => block_cipher,
-            })
-        };
-        aes_ctr_256.poll_read(cx, buf)
+        unsafe {
+            match self.get_unchecked_mut() {
+                LayerBlockCipherHandler::Aes256Ctr(block_cipher) => {
+                    std::pin::Pin::new_unchecked(block_cipher).poll_read(cx, buf)
+                }
+                #[cfg(feature = "block-cipher-sm4")]
+                LayerBlockCipherHandler::Sm4Ctr(block_cipher) => {
+                    std::pin::Pin::new_unchecked(block_cipher).poll_read(cx, buf)
+                }
+            }
+        }
     }
 }
 
@@ -242,6 +297,8 @@ mod tests {
     use super::*;
 
     #[test]
+    // the handler has a single variant unless block-cipher-sm4 is enabled
+    #[allow(irrefutable_let_patterns)]
     fn test_layer_block_cipher_handler() {
         let layer_data: Vec<u8> = b"this is some data".to_vec();
 
@@ -255,7 +312,9 @@ mod tests {
         assert!(lbch
             .encrypt(layer_data.as_slice(), AES256CTR, &mut lbco)
             .is_ok());
-        let LayerBlockCipherHandler::Aes256Ctr(mut encryptor) = lbch;
+        let LayerBlockCipherHandler::Aes256Ctr(mut encryptor) = lbch else {
+            panic!("not an aes ctr handler");
+        };
         assert!(encryptor.read_to_end(&mut encrypted_data).is_ok());
         assert!(encryptor.finalized_lbco(&mut lbco).is_ok());
 
@@ -267,7 +326,9 @@ mod tests {
             serde_json::from_str(&serialized_json).unwrap_or_default();
 
         assert!(lbch.decrypt(encrypted_data.as_slice(), &mut lbco).is_ok());
-        let LayerBlockCipherHandler::Aes256Ctr(mut decryptor) = lbch;
+        let LayerBlockCipherHandler::Aes256Ctr(mut decryptor) = lbch else {
+            panic!("not an aes ctr handler");
+        };
         let mut plaintxt_data: Vec<u8> = Vec::new();
         assert!(decryptor.read_to_end(&mut plaintxt_data).is_ok());
 
@@ -278,8 +339,50 @@ mod tests {
         let mut lbch = LayerBlockCipherHandler::new().unwrap();
         lbco.private.symmetric_key = vec![0; 32];
         assert!(lbch.decrypt(encrypted_data.as_slice(), &mut lbco).is_ok());
-        let LayerBlockCipherHandler::Aes256Ctr(mut decryptor) = lbch;
+        let LayerBlockCipherHandler::Aes256Ctr(mut decryptor) = lbch else {
+            panic!("not an aes ctr handler");
+        };
         let mut plaintxt_data: Vec<u8> = Vec::new();
         assert!(decryptor.read_to_end(&mut plaintxt_data).is_err());
     }
+
+    #[cfg(feature = "block-cipher-sm4")]
+    #[test]
+    fn test_layer_block_cipher_handler_sm4() {
+        let layer_data: Vec<u8> = b"this is some data".to_vec();
+
+        let mut lbco = LayerBlockCipherOptions::default();
+        let mut lbch = LayerBlockCipherHandler::for_cipher(SM4CTR).unwrap();
+        assert!(lbch
+            .encrypt(layer_data.as_slice(), AES256CTR, &mut lbco)
+            .is_err());
+        lbch.encrypt(layer_data.as_slice(), SM4CTR, &mut lbco)
+            .unwrap();
+        let mut encrypted_data: Vec<u8> = Vec::new();
+        lbch.read_to_end(&mut encrypted_data).unwrap();
+        lbch.finalized_lbco(&mut lbco).unwrap();
+        assert_eq!(lbco.public.cipher_type, SM4CTR);
+
+        // the cipher is detected from the public options
+        let serialized_json = serde_json::to_string(&lbco).unwrap();
+        let mut lbco: LayerBlockCipherOptions = serde_json::from_str(&serialized_json).unwrap();
+        let mut lbch = LayerBlockCipherHandler::for_cipher(&lbco.public.cipher_type).unwrap();
+        lbch.decrypt(encrypted_data.as_slice(), &mut lbco).unwrap();
+        let mut plaintxt_data: Vec<u8> = Vec::new();
+        lbch.read_to_end(&mut plaintxt_data).unwrap();
+        assert_eq!(layer_data, plaintxt_data);
+
+        // an AES handler refuses SM4 layers
+        let mut lbch = LayerBlockCipherHandler::new().unwrap();
+        assert!(lbch.decrypt(encrypted_data.as_slice(), &mut lbco).is_err());
+    }
+
+    #[test]
+    fn test_layer_block_cipher_handler_for_cipher() {
+        assert!(matches!(
+            LayerBlockCipherHandler::<&[u8]>::for_cipher(AES256CTR),
+            Ok(LayerBlockCipherHandler::Aes256Ctr(_))
+        ));
+        assert!(LayerBlockCipherHandler::<&[u8]>::for_cipher("").is_err());
+    }
 }
@@ -0,0 +1,389 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! SM4 in CTR mode with an HMAC-SM3 over the ciphertext, the ShangMi
+//! counterpart of [`AESCTRBlockCipher`](super::aes_ctr::AESCTRBlockCipher)
+//! for users required to use the Chinese national algorithms.
+
+use std::io::Read;
+
+use anyhow::{anyhow, Result};
+use ctr::cipher::generic_array::GenericArray;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use sm3::Sm3;
+
+use crate::blockcipher::{
+    EncryptionFinalizer, LayerBlockCipher, LayerBlockCipherOptions, MAX_CHUNK_SIZE,
+};
+
+use super::rand::rand_bytes;
+
+const SM4_KEY_SIZE: usize = 16;
+const SM4_NONCE_SIZE: usize = 16;
+type Sm4Ctr = ctr::Ctr128BE<sm4::Sm4>;
+type HmacSm3 = Hmac<Sm3>;
+
+pin_project_lite::pin_project! {
+    struct SM4CTRBlockCipherState<R> {
+        done: bool,
+        cipher: Sm4Ctr,
+        exp_hmac: Vec<u8>,
+        hmac: HmacSm3,
+        #[pin]
+        reader: R,
+    }
+}
+
+/// Implementation of the SM4 CTR stream cipher, authenticated with HMAC-SM3.
+pub struct SM4CTRBlockCipher<R> {
+    key_len: usize,
+    encrypt: bool,
+    state: Option<SM4CTRBlockCipherState<R>>,
+}
+
+impl<R> SM4CTRBlockCipher<R> {
+    /// Create a new instance of `SM4CTRBlockCipher`.
+    pub fn new() -> SM4CTRBlockCipher<R> {
+        SM4CTRBlockCipher {
+            key_len: SM4_KEY_SIZE,
+            encrypt: false,
+            state: None,
+        }
+    }
+
+    // init initializes an instance
+    fn init(&mut self, encrypt: bool, reader: R, opts: &mut LayerBlockCipherOptions) -> Result<()> {
+        let symmetric_key = &opts.private.symmetric_key;
+        if symmetric_key.len() != SM4_KEY_SIZE {
+            return Err(anyhow!(
+                "invalid key length of {} bytes; expect {} bytes",
+                symmetric_key.len(),
+                SM4_KEY_SIZE
+            ));
+        }
+        if !encrypt && opts.public.hmac.is_empty() {
+            return Err(anyhow!("HMAC is not provided for decryption process"));
+        }
+
+        let mut nonce = vec![0u8; SM4_NONCE_SIZE];
+        match opts.get_opt("nonce") {
+            Some(v) => {
+                if v.len() != SM4_NONCE_SIZE {
+                    return Err(anyhow!(
+                        "invalid nonce length of {} bytes; need {} bytes",
+                        v.len(),
+                        SM4_NONCE_SIZE
+                    ));
+                }
+                nonce = v;
+            }
+            None => rand_bytes(&mut nonce[..])?,
+        }
+
+        let cipher = Sm4Ctr::new(
+            GenericArray::from_slice(symmetric_key.as_slice()),
+            GenericArray::from_slice(nonce.as_slice()),
+        );
+        let hmac = HmacSm3::new_from_slice(symmetric_key.as_slice())
+            .map_err(|_| anyhow!("Failed to create HMAC"))?;
+
+        self.encrypt = encrypt;
+        self.state = Some(SM4CTRBlockCipherState {
+            cipher,
+            done: false,
+            hmac,
+            exp_hmac: opts.public.hmac.clone(),
+            reader,
+        });
+
+        opts.private
+            .cipher_options
+            .entry("nonce".to_string())
+            .or_insert(nonce);
+
+        Ok(())
+    }
+}
+
+impl<R> Default for SM4CTRBlockCipher<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> LayerBlockCipher<R> for SM4CTRBlockCipher<R> {
+    fn generate_key(&self) -> Result<Vec<u8>> {
+        let mut key = vec![0; self.key_len];
+        rand_bytes(&mut key[..])?;
+        Ok(key)
+    }
+
+    fn encrypt(&mut self, input: R, opts: &mut LayerBlockCipherOptions) -> Result<()> {
+        self.init(true, input, opts)
+    }
+
+    fn decrypt(&mut self, input: R, opts: &mut LayerBlockCipherOptions) -> Result<()> {
+        self.init(false, input, opts)
+    }
+}
+
+impl<R> EncryptionFinalizer for SM4CTRBlockCipher<R> {
+    fn finalized_lbco(&self, opts: &mut LayerBlockCipherOptions) -> Result<()> {
+        let state = self
+            .state
+            .as_ref()
+            .ok_or_else(|| anyhow!("The SM4CTRBlockCipher object hasn't been initialized yet"))?;
+        if !state.done {
+            Err(anyhow!("Read()ing not complete, unable to finalize"))
+        } else {
+            opts.public.hmac = state.exp_hmac.to_vec();
+            Ok(())
+        }
+    }
+}
+
+impl<R: Read> Read for SM4CTRBlockCipher<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let state = self
+            .state
+            .as_mut()
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::Unsupported))?;
+        if state.done {
+            return Ok(0);
+        }
+
+        let chunk_len = buf.len().min(MAX_CHUNK_SIZE);
+        let read_len = state.reader.read(&mut buf[..chunk_len])?;
+        if read_len == 0 {
+            state.done = true;
+        }
+
+        if !self.encrypt {
+            if read_len > 0 {
+                state.hmac.update(&buf[0..read_len]);
+                state.cipher.apply_keystream(&mut buf[0..read_len]);
+            } else {
+                // If we done encrypting, let the HMAC comparison provide a verdict
+                state
+                    .hmac
+                    .clone()
+                    .verify_slice(&state.exp_hmac)
+                    .map_err(|_| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            format!(
+                                "failed decrypt byte stream, exp hmac: {:?} , actual hmac: {:?}",
+                                &state.exp_hmac,
+                                state.hmac.clone().finalize().into_bytes()
+                            ),
+                        )
+                    })?;
+            }
+        } else if read_len > 0 {
+            state.cipher.apply_keystream(&mut buf[0..read_len]);
+            state.hmac.update(&buf[0..read_len]);
+        } else {
+            state.exp_hmac = state.hmac.clone().finalize().into_bytes().to_vec();
+        }
+
+        Ok(read_len)
+    }
+}
+
+#[cfg(feature = "async-io")]
+impl<R: tokio::io::AsyncRead> tokio::io::AsyncRead for SM4CTRBlockCipher<R> {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        use std::task::Poll;
+        let encrypt = self.encrypt;
+
+        if self.state.is_none() {
+            return Poll::Ready(Err(std::io::Error::from(std::io::ErrorKind::Unsupported)));
+        }
+
+        // This is okay because `state` is pinned when `self` is.
+        let state = unsafe { self.map_unchecked_mut(|v| v.state.as_mut().unwrap()) };
+        let pinned_state = state.project();
+        let done = pinned_state.done;
+        let cipher = pinned_state.cipher;
+        let exp_hmac = pinned_state.exp_hmac;
+        let hmac = pinned_state.hmac;
+        let reader = pinned_state.reader;
+
+        if *done {
+            return Poll::Ready(Ok(()));
+        }
+
+        let start_pos = buf.filled().len();
+        let mut chunk = buf.take(MAX_CHUNK_SIZE);
+        match reader.poll_read(cx, &mut chunk) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(res) => res?,
+        }
+        let read_len = chunk.filled().len();
+        // The chunk is the start of the unfilled part of `buf`, so the bytes
+        // read into it are initialized in `buf` as well.
+        unsafe { buf.assume_init(read_len) };
+        buf.advance(read_len);
+        let buf_filled = &mut buf.filled_mut()[start_pos..];
+        if buf_filled.is_empty() {
+            *done = true;
+        }
+
+        if !encrypt {
+            if !buf_filled.is_empty() {
+                hmac.update(buf_filled);
+                cipher.apply_keystream(buf_filled);
+            } else {
+                // If we done encrypting, let the HMAC comparison provide a verdict
+                hmac.clone().verify_slice(exp_hmac).map_err(|_| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!(
+                            "failed decrypt byte stream, exp hmac: {:?} , actual hmac: {:?}",
+                            exp_hmac,
+                            hmac.clone().finalize().into_bytes()
+                        ),
+                    )
+                })?;
+            }
+        } else if !buf_filled.is_empty() {
+            cipher.apply_keystream(buf_filled);
+            hmac.update(buf_filled);
+        } else {
+            *exp_hmac = hmac.clone().finalize().into_bytes().to_vec();
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sm4_ctr_block_cipher() {
+        let layer_data: Vec<u8> = b"this is some data".to_vec();
+
+        let mut lbco = LayerBlockCipherOptions::default();
+        let mut sm4_ctr_block_cipher = SM4CTRBlockCipher::new();
+
+        // Error due to LayerBlockCipherOptions without symmetric key
+        assert!(sm4_ctr_block_cipher
+            .encrypt(layer_data.as_slice(), &mut lbco)
+            .is_err());
+
+        lbco.private.symmetric_key = sm4_ctr_block_cipher.generate_key().unwrap();
+        assert_eq!(lbco.private.symmetric_key.len(), SM4_KEY_SIZE);
+        assert!(sm4_ctr_block_cipher
+            .encrypt(layer_data.as_slice(), &mut lbco)
+            .is_ok());
+
+        let mut encrypted_data: Vec<u8> = Vec::new();
+        assert!(sm4_ctr_block_cipher
+            .read_to_end(&mut encrypted_data)
+            .is_ok());
+        assert_ne!(encrypted_data, layer_data);
+        assert!(sm4_ctr_block_cipher.finalized_lbco(&mut lbco).is_ok());
+        let exp_hmac = lbco.public.hmac.clone();
+
+        // Expected HMAC is wrong
+        lbco.public.hmac = b"wrong hmac".to_vec();
+        assert!(sm4_ctr_block_cipher
+            .decrypt(encrypted_data.as_slice(), &mut lbco)
+            .is_ok());
+        let mut plaintxt_data: Vec<u8> = Vec::new();
+        assert!(sm4_ctr_block_cipher
+            .read_to_end(&mut plaintxt_data)
+            .is_err());
+
+        // Expected HMAC is right
+        lbco.public.hmac = exp_hmac;
+        assert!(sm4_ctr_block_cipher
+            .decrypt(encrypted_data.as_slice(), &mut lbco)
+            .is_ok());
+        let mut plaintxt_data: Vec<u8> = Vec::new();
+        assert!(sm4_ctr_block_cipher.read_to_end(&mut plaintxt_data).is_ok());
+        assert_eq!(layer_data, plaintxt_data);
+    }
+
+    #[test]
+    fn test_sm4_ctr_block_cipher_nonce() {
+        let mut lbco = LayerBlockCipherOptions::default();
+        let mut sm4_ctr_block_cipher = SM4CTRBlockCipher::new();
+        lbco.private.symmetric_key = sm4_ctr_block_cipher.generate_key().unwrap();
+        lbco.private
+            .cipher_options
+            .insert("nonce".to_string(), vec![0; 12]);
+
+        let e = sm4_ctr_block_cipher
+            .encrypt(&b"this is some data"[..], &mut lbco)
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "invalid nonce length of 12 bytes; need 16 bytes"
+        );
+    }
+
+    #[cfg(feature = "async-io")]
+    #[tokio::test]
+    async fn test_async_sm4_ctr_block_cipher() {
+        let layer_data: Vec<u8> = b"this is some data".to_vec();
+        let mut lbco = LayerBlockCipherOptions::default();
+        let mut sm4_ctr_block_cipher = SM4CTRBlockCipher::new();
+        lbco.private.symmetric_key = sm4_ctr_block_cipher.generate_key().unwrap();
+        assert!(sm4_ctr_block_cipher
+            .encrypt(layer_data.as_slice(), &mut lbco)
+            .is_ok());
+
+        let mut encrypted_data = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut sm4_ctr_block_cipher, &mut encrypted_data)
+            .await
+            .unwrap();
+        assert!(sm4_ctr_block_cipher.finalized_lbco(&mut lbco).is_ok());
+
+        assert!(sm4_ctr_block_cipher
+            .decrypt(encrypted_data.as_slice(), &mut lbco)
+            .is_ok());
+        let mut plaintxt_data = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut sm4_ctr_block_cipher, &mut plaintxt_data)
+            .await
+            .unwrap();
+        assert_eq!(layer_data, plaintxt_data);
+    }
+
+    #[test]
+    // Verify the primitives against the examples of GB/T 32907-2016 and
+    // GB/T 32905-2016
+    fn test_sm_primitives() {
+        let key = hex_bytes("0123456789abcdeffedcba9876543210");
+
+        // the first keystream block is the SM4 encryption of the counter
+        let mut block = vec![0u8; SM4_NONCE_SIZE];
+        let mut cipher = Sm4Ctr::new(
+            GenericArray::from_slice(key.as_slice()),
+            GenericArray::from_slice(key.as_slice()),
+        );
+        cipher.apply_keystream(&mut block);
+        assert_eq!(block, hex_bytes("681edf34d206965e86b3e94f536e4246"));
+
+        let digest = <Sm3 as sm3::Digest>::digest(b"abc");
+        assert_eq!(
+            digest.to_vec(),
+            hex_bytes("66c7f0f462eeedd9d1f2d46bdc10e4e24167c4875cf2f7a2297da02b8f4ba8e0")
+        );
+    }
+
+    fn hex_bytes(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+}
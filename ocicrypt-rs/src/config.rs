@@ -75,6 +75,16 @@ pub struct KeyProviderAttrs {
     pub grpc: Option<String>,
     pub ttrpc: Option<String>,
     pub native: Option<String>,
+
+    /// Deadline of a single grpc/ttrpc call in seconds, 50 by default.
+    #[serde(default)]
+    pub timeout: Option<u64>,
+
+    /// Number of times a grpc/ttrpc call failing transiently (the
+    /// keyprovider is unreachable or does not answer in time) is repeated,
+    /// with exponential backoff. 3 by default.
+    #[serde(default)]
+    pub retries: Option<u32>,
 }
 
 /// DecryptConfig wraps the Parameters map that holds the decryption key
@@ -186,6 +196,13 @@ pub struct EncryptConfig {
     /// Allow for adding wrapped keys to an encrypted layer
     #[serde(rename = "DecryptConfig")]
     pub decrypt_config: Option<DecryptConfig>,
+
+    /// Cipher algorithm the layers are encrypted with, e.g.
+    /// `SM4_128_CTR_HMAC_SM3`. Decryption detects it from the layer.
+    ///
+    /// This defaults to `AES_256_CTR_HMAC_SHA256`.
+    #[serde(rename = "LayerCipher", default)]
+    pub layer_cipher: Option<String>,
 }
 
 impl EncryptConfig {
@@ -208,6 +225,12 @@ impl EncryptConfig {
         self.update_param("pubkeys", pubkeys)
     }
 
+    /// Add EncryptConfig with a KEK kept as KBS resource `kek_id` for encryption
+    pub fn encrypt_with_kbs_kek(&mut self, kek_id: &str, kek: Vec<u8>) -> Result<()> {
+        self.update_param("kbs-kek-id", vec![kek_id.as_bytes().to_vec()])?;
+        self.update_param("kbs-kek", vec![kek])
+    }
+
     /// Add EncryptConfig with pkcs7 x509 certs for encryption
     pub fn encrypt_with_pkcs7(&mut self, x509s: Vec<Vec<u8>>) -> Result<()> {
         self.update_param("x509s", x509s)
@@ -425,6 +448,8 @@ mod tests {
             grpc: None,
             ttrpc: None,
             native: None,
+            timeout: None,
+            retries: None,
         };
         provider.insert(String::from("keyprovider1"), attrs);
 
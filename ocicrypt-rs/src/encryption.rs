@@ -7,13 +7,14 @@ use std::io::Read;
 use anyhow::{anyhow, Result};
 use base64::Engine;
 
+use crate::annotations::{parse_priv_opts, parse_pub_opts, parse_wrapped_keys, ANNOTATION_PUBOPTS};
 use crate::blockcipher::{
     EncryptionFinalizer, LayerBlockCipherHandler, LayerBlockCipherOptions,
-    PrivateLayerBlockCipherOptions, PublicLayerBlockCipherOptions, AES256CTR,
+    PublicLayerBlockCipherOptions, AES256CTR,
 };
 use crate::config::{DecryptConfig, EncryptConfig};
 use crate::keywrap::KeyWrapper;
-use crate::{get_key_wrapper, KEY_WRAPPERS_ANNOTATIONS};
+use crate::{get_key_wrapper, unwrap_cache, KEY_WRAPPERS_ANNOTATIONS};
 
 lazy_static! {
     static ref DEFAULT_ANNOTATION_MAP: HashMap<String, String> = HashMap::new();
@@ -26,7 +27,9 @@ pub struct EncLayerFinalizer {
 }
 
 impl EncLayerFinalizer {
-    /// Generate annotations for image decryption.
+    /// Generate annotations for image decryption. The layer key is wrapped
+    /// for every recipient of `ec`, of all the keywrappers, so that a layer
+    /// encrypted once can be decrypted by any of them.
     pub fn finalize_annotations(
         &mut self,
         ec: &EncryptConfig,
@@ -67,7 +70,7 @@ impl EncLayerFinalizer {
         }
 
         new_annotations.insert(
-            "org.opencontainers.image.enc.pubopts".to_string(),
+            ANNOTATION_PUBOPTS.to_string(),
             base64::engine::general_purpose::STANDARD.encode(pub_opts),
         );
 
@@ -75,26 +78,26 @@ impl EncLayerFinalizer {
     }
 }
 
-// pre_wrap_keys calls wrap_keys and handles the base64 encoding and
-// concatenation of the annotation data.
+// pre_wrap_keys calls wrap_keys_for_recipients and handles the base64 encoding and
+// concatenation of the annotation data, one entry per recipient. Keywrappers
+// without a recipient in the encrypt config add none.
 fn pre_wrap_key(
     keywrapper: &dyn KeyWrapper,
     ec: &EncryptConfig,
     mut b64_annotations: String,
     opts_data: &[u8],
 ) -> Result<String> {
-    let new_annotation = keywrapper.wrap_keys(ec, opts_data)?;
-    if new_annotation.is_empty() {
-        return Err(anyhow!("new annotations is empty!"));
-    }
+    for new_annotation in keywrapper.wrap_keys_for_recipients(ec, opts_data)? {
+        if new_annotation.is_empty() {
+            return Err(anyhow!("new annotations is empty!"));
+        }
 
-    let b64_new_annotation = base64::engine::general_purpose::STANDARD.encode(new_annotation);
-    if b64_annotations.is_empty() {
-        return Ok(b64_new_annotation);
+        if !b64_annotations.is_empty() {
+            b64_annotations.push(',');
+        }
+        b64_annotations.push_str(&base64::engine::general_purpose::STANDARD.encode(new_annotation));
     }
 
-    b64_annotations.push(',');
-    b64_annotations.push_str(&b64_new_annotation);
     Ok(b64_annotations)
 }
 
@@ -104,16 +107,11 @@ fn pre_wrap_key(
 fn pre_unwrap_key(
     keywrapper: &dyn KeyWrapper,
     dc: &DecryptConfig,
+    annotations_id: &str,
     b64_annotations: &str,
 ) -> Result<Vec<u8>> {
-    if b64_annotations.is_empty() {
-        return Err(anyhow!("annotations is empty!"));
-    }
-
     let mut errs = String::new();
-    for b64_annotation in b64_annotations.split(',') {
-        let annotation = base64::engine::general_purpose::STANDARD.decode(b64_annotation)?;
-
+    for annotation in parse_wrapped_keys(annotations_id, b64_annotations)? {
         match keywrapper.unwrap_keys(dc, &annotation) {
             Err(e) => {
                 errs.push_str(&e.to_string());
@@ -131,16 +129,13 @@ fn pre_unwrap_key(
     ))
 }
 
-fn get_layer_pub_opts(annotations: &HashMap<String, String>) -> Result<Vec<u8>> {
-    if let Some(pub_opts) = annotations.get("org.opencontainers.image.enc.pubopts") {
-        return Ok(base64::engine::general_purpose::STANDARD.decode(pub_opts)?);
+fn get_layer_pub_opts(
+    annotations: &HashMap<String, String>,
+) -> Result<PublicLayerBlockCipherOptions> {
+    match annotations.get(ANNOTATION_PUBOPTS) {
+        Some(pub_opts) => Ok(parse_pub_opts(pub_opts)?),
+        None => Ok(PublicLayerBlockCipherOptions::default()),
     }
-
-    Ok(
-        serde_json::to_string(&PublicLayerBlockCipherOptions::default())?
-            .as_bytes()
-            .to_vec(),
-    )
 }
 
 fn get_layer_key_opts(
@@ -169,7 +164,9 @@ fn get_layer_key_opts(
     value.cloned()
 }
 
-/// Unwrap layer decryption key from OCI descriptor annotations.
+/// Unwrap layer decryption key from OCI descriptor annotations. The keys
+/// unwrapped before with the same decrypt config are taken from the
+/// [`unwrap_cache`].
 pub fn decrypt_layer_key_opts_data(
     dc: &DecryptConfig,
     annotations: Option<&HashMap<String, String>>,
@@ -188,8 +185,14 @@ pub fn decrypt_layer_key_opts_data(
                 priv_key_given = true;
             }
 
-            if let Ok(opts_data) = pre_unwrap_key(keywrapper, dc, &b64_annotation) {
+            let cache_key = unwrap_cache::cache_key(dc, annotations_id, &b64_annotation);
+            if let Some(opts_data) = unwrap_cache::get(&cache_key) {
+                return Ok(opts_data);
+            }
+
+            if let Ok(opts_data) = pre_unwrap_key(keywrapper, dc, annotations_id, &b64_annotation) {
                 if !opts_data.is_empty() {
+                    unwrap_cache::insert(cache_key, &opts_data);
                     return Ok(opts_data);
                 }
             }
@@ -235,10 +238,11 @@ pub fn encrypt_layer<'a, R: 'a + Read>(
     }
 
     if !encrypted {
-        let mut lbch = LayerBlockCipherHandler::new()?;
+        let cipher = ec.layer_cipher.as_deref().unwrap_or(AES256CTR);
+        let mut lbch = LayerBlockCipherHandler::for_cipher(cipher)?;
         let mut lbco = LayerBlockCipherOptions::default();
 
-        lbch.encrypt(layer_reader, AES256CTR, &mut lbco)?;
+        lbch.encrypt(layer_reader, cipher, &mut lbco)?;
         lbco.private.digest = digest.to_string();
         let enc_layer_finalizer = EncLayerFinalizer { lbco };
 
@@ -259,19 +263,18 @@ pub fn decrypt_layer<R: Read>(
 ) -> Result<(Option<impl Read>, String)> {
     let priv_opts_data = decrypt_layer_key_opts_data(dc, annotations)?;
     let annotations = annotations.unwrap_or(&DEFAULT_ANNOTATION_MAP);
-    let pub_opts_data = get_layer_pub_opts(annotations)?;
+    let pub_opts = get_layer_pub_opts(annotations)?;
 
     if unwrap_only {
         return Ok((None, "".to_string()));
     }
 
-    let priv_opts: PrivateLayerBlockCipherOptions = serde_json::from_slice(&priv_opts_data)?;
-    let pub_opts: PublicLayerBlockCipherOptions = serde_json::from_slice(&pub_opts_data)?;
+    let priv_opts = parse_priv_opts(&priv_opts_data)?;
     let mut opts = LayerBlockCipherOptions {
         public: pub_opts,
         private: priv_opts,
     };
-    let mut lbch = LayerBlockCipherHandler::new()?;
+    let mut lbch = LayerBlockCipherHandler::for_cipher(&opts.public.cipher_type)?;
 
     lbch.decrypt(layer_reader, &mut opts)?;
 
@@ -288,14 +291,13 @@ pub fn async_decrypt_layer<R: tokio::io::AsyncRead + Send>(
     priv_opts_data: &[u8],
 ) -> Result<(impl tokio::io::AsyncRead + Send, String)> {
     let annotations = annotations.unwrap_or(&DEFAULT_ANNOTATION_MAP);
-    let pub_opts_data = get_layer_pub_opts(annotations)?;
-    let pub_opts: PublicLayerBlockCipherOptions = serde_json::from_slice(&pub_opts_data)?;
-    let priv_opts: PrivateLayerBlockCipherOptions = serde_json::from_slice(priv_opts_data)?;
+    let pub_opts = get_layer_pub_opts(annotations)?;
+    let priv_opts = parse_priv_opts(priv_opts_data)?;
     let mut opts = LayerBlockCipherOptions {
         public: pub_opts,
         private: priv_opts,
     };
-    let mut lbch = LayerBlockCipherHandler::new()?;
+    let mut lbch = LayerBlockCipherHandler::for_cipher(&opts.public.cipher_type)?;
 
     lbch.decrypt(layer_reader, &mut opts)?;
 
@@ -359,6 +361,67 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_encrypt_layer_for_recipients() {
+        let path = load_data_path();
+        let test_conf_path = format!("{}/{}", path, "ocicrypt_config.json");
+        env::set_var("OCICRYPT_KEYPROVIDER_CONFIG", test_conf_path);
+
+        let pub_key = fs::read(format!("{}/{}", path, "public_key.pem")).unwrap();
+        let priv_key = fs::read(format!("{}/{}", path, "private_key.pem")).unwrap();
+
+        let mut ec = EncryptConfig::default();
+        ec.encrypt_with_jwe(vec![pub_key]).unwrap();
+        #[cfg(feature = "keywrap-kbs")]
+        for (kid, kek) in [
+            ("kbs:///default/key/1", [1; 32]),
+            ("kbs:///default/key/2", [2; 32]),
+        ] {
+            ec.encrypt_with_kbs_kek(kid, kek.to_vec()).unwrap();
+        }
+
+        let layer_data: Vec<u8> = b"This is some text!".to_vec();
+        let digest = format!("sha256:{:x}", Sha256::digest(&layer_data));
+        let (layer_encryptor, mut elf) =
+            encrypt_layer(&ec, layer_data.as_slice(), None, &digest).unwrap();
+        let mut encrypted_data: Vec<u8> = Vec::new();
+        let mut encryptor = layer_encryptor.unwrap();
+        encryptor.read_to_end(&mut encrypted_data).unwrap();
+        encryptor.finalized_lbco(&mut elf.lbco).unwrap();
+
+        let annotations = elf
+            .finalize_annotations(&ec, None, Some(&mut encryptor))
+            .unwrap();
+        let wrapped = |id: &str| annotations.get(id).map(|keys| keys.split(',').count());
+        assert_eq!(wrapped("org.opencontainers.image.enc.keys.jwe"), Some(1));
+        #[cfg(feature = "keywrap-kbs")]
+        assert_eq!(wrapped("org.opencontainers.image.enc.keys.kbs"), Some(2));
+
+        let mut dc = DecryptConfig::default();
+        dc.decrypt_with_priv_keys(vec![priv_key], vec![vec![]])
+            .unwrap();
+        let (layer_decryptor, dec_digest) =
+            decrypt_layer(&dc, encrypted_data.as_slice(), Some(&annotations), false).unwrap();
+        let mut plaintxt_data: Vec<u8> = Vec::new();
+        layer_decryptor
+            .unwrap()
+            .read_to_end(&mut plaintxt_data)
+            .unwrap();
+        assert_eq!(layer_data, plaintxt_data);
+        assert_eq!(digest, dec_digest);
+
+        // no recipient at all
+        let ec = EncryptConfig::default();
+        let (layer_encryptor, mut elf) =
+            encrypt_layer(&ec, layer_data.as_slice(), None, &digest).unwrap();
+        let mut encryptor = layer_encryptor.unwrap();
+        encryptor.read_to_end(&mut Vec::new()).unwrap();
+        encryptor.finalized_lbco(&mut elf.lbco).unwrap();
+        assert!(elf
+            .finalize_annotations(&ec, None, Some(&mut encryptor))
+            .is_err());
+    }
+
     #[cfg(feature = "async-io")]
     #[tokio::test]
     async fn test_async_decrypt_layer() {
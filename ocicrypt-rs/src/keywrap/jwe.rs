@@ -9,14 +9,28 @@ use josekit::jwe::{
     ECDH_ES_A256KW, RSA_OAEP,
 };
 use josekit::jwk::{Jwk, KeyAlg, KeyFormat, KeyInfo};
+use serde_json::Value;
 
+use crate::annotations::check_jwe;
 use crate::config::{DecryptConfig, EncryptConfig};
 use crate::keywrap::KeyWrapper;
 
-/// A Jwe keywrapper
+/// A Jwe keywrapper. The layer keys are wrapped with RSA-OAEP for RSA
+/// recipient keys, and with ECDH-ES+A256KW for EC (e.g. P-256) and X25519
+/// (or X448) recipient keys.
 #[derive(Debug)]
 pub struct JweKeyWrapper {}
 
+// EdDSA keys can only sign, and RSA is tried for the unknown ones
+fn check_key_alg(key_info: &KeyInfo) -> Result<()> {
+    match key_info.alg() {
+        Some(KeyAlg::Ed { .. }) => Err(anyhow!(
+            "jwe: EdDSA keys can't wrap keys, use an X25519 key instead"
+        )),
+        _ => Ok(()),
+    }
+}
+
 // Get the encrypter from public key
 fn encrypter(pubkey: &[u8]) -> Result<Box<dyn JweEncrypter>> {
     let key_info =
@@ -24,9 +38,10 @@ fn encrypter(pubkey: &[u8]) -> Result<Box<dyn JweEncrypter>> {
     if !key_info.is_public_key() {
         return Err(anyhow!("jwe: expect public key, found private key"));
     }
+    check_key_alg(&key_info)?;
 
     match key_info.alg() {
-        Some(KeyAlg::Ec { curve: Some(_) }) => {
+        Some(KeyAlg::Ec { curve: Some(_) }) | Some(KeyAlg::Ecx { curve: Some(_) }) => {
             let ecdh_encrypter = match key_info.format() {
                 KeyFormat::Der { raw: _ } => ECDH_ES_A256KW.encrypter_from_der(pubkey)?,
                 KeyFormat::Pem { traditional: _ } => ECDH_ES_A256KW.encrypter_from_pem(pubkey)?,
@@ -54,9 +69,10 @@ fn decrypter(priv_key: &[u8]) -> Result<Box<dyn JweDecrypter>> {
     if key_info.is_public_key() {
         return Err(anyhow!("jwe: expect private key, found public key"));
     }
+    check_key_alg(&key_info)?;
 
     match key_info.alg() {
-        Some(KeyAlg::Ec { curve: Some(_) }) => {
+        Some(KeyAlg::Ec { curve: Some(_) }) | Some(KeyAlg::Ecx { curve: Some(_) }) => {
             let ecdh_decrypter = match key_info.format() {
                 KeyFormat::Der { raw: _ } => ECDH_ES_A256KW.decrypter_from_der(priv_key)?,
                 KeyFormat::Pem { traditional: _ } => ECDH_ES_A256KW.decrypter_from_pem(priv_key)?,
@@ -77,6 +93,30 @@ fn decrypter(priv_key: &[u8]) -> Result<Box<dyn JweDecrypter>> {
     }
 }
 
+// Split a JWE of the general JSON serialization into a flattened JWE per
+// recipient. josekit gives up at the first recipient of the algorithm of the
+// key, e.g. a P-256 recipient for an X25519 key.
+fn recipient_jwes(data: &str) -> Vec<String> {
+    let Ok(Value::Object(mut jwe)) = serde_json::from_str(data) else {
+        return vec![data.to_string()];
+    };
+    let Some(Value::Array(recipients)) = jwe.remove("recipients") else {
+        return vec![data.to_string()];
+    };
+
+    recipients
+        .into_iter()
+        .filter_map(|recipient| {
+            let Value::Object(recipient) = recipient else {
+                return None;
+            };
+            let mut flattened = jwe.clone();
+            flattened.extend(recipient);
+            serde_json::to_string(&flattened).ok()
+        })
+        .collect()
+}
+
 impl KeyWrapper for JweKeyWrapper {
     fn wrap_keys(&self, ec: &EncryptConfig, opts_data: &[u8]) -> Result<Vec<u8>> {
         let pubkeys = ec
@@ -102,16 +142,30 @@ impl KeyWrapper for JweKeyWrapper {
         Ok(json.as_bytes().to_vec())
     }
 
+    // a single JWE holds the keys for all the public keys
+    fn wrap_keys_for_recipients(
+        &self,
+        ec: &EncryptConfig,
+        opts_data: &[u8],
+    ) -> Result<Vec<Vec<u8>>> {
+        match ec.param.get("pubkeys") {
+            Some(pubkeys) if !pubkeys.is_empty() => Ok(vec![self.wrap_keys(ec, opts_data)?]),
+            _ => Ok(vec![]),
+        }
+    }
+
     fn unwrap_keys(&self, dc: &DecryptConfig, jwe_string: &[u8]) -> Result<Vec<u8>> {
-        let data = std::str::from_utf8(jwe_string)
-            .map_err(|_e| anyhow!("jwe: invalid data to unwrap_keys()"))?;
+        let data = check_jwe(jwe_string)?;
         let privkeys = self
             .private_keys(&dc.param)
             .ok_or_else(|| anyhow!("jwe: invalid configuration for keyunwrap"))?;
+        let jwes = recipient_jwes(data);
         for privkey in privkeys {
             let decrypter = decrypter(&privkey)?;
-            if let Ok((keys, _)) = deserialize_json(data, &*decrypter) {
-                return Ok(keys);
+            for jwe in &jwes {
+                if let Ok((keys, _)) = deserialize_json(jwe, &*decrypter) {
+                    return Ok(keys);
+                }
             }
         }
 
@@ -205,6 +259,44 @@ mod tests {
             .is_none());
     }
 
+    #[test]
+    fn test_keywrap_jwe_ecdh_es() {
+        let path = load_data_path();
+        let read = |name: &str| fs::read(path.join(name)).unwrap();
+        let payload = b"test".to_vec();
+        let jwe_key_wrapper = JweKeyWrapper {};
+
+        let mut ec = EncryptConfig::default();
+        ec.encrypt_with_jwe(vec![
+            read("public_key_p256.pem"),
+            read("public_key_x25519.pem"),
+        ])
+        .unwrap();
+        let json = jwe_key_wrapper.wrap_keys(&ec, &payload).unwrap();
+        let jwe: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        let recipients = jwe["recipients"].as_array().unwrap();
+        assert_eq!(recipients.len(), 2);
+        for (recipient, crv) in recipients.iter().zip(["P-256", "X25519"]) {
+            assert_eq!(recipient["header"]["alg"], "ECDH-ES+A256KW");
+            assert_eq!(recipient["header"]["epk"]["crv"], crv);
+        }
+
+        // every recipient can unwrap the keys on its own
+        for private_key in ["private_key_p256.pem", "private_key_x25519.pem"] {
+            let mut dc = DecryptConfig::default();
+            dc.decrypt_with_priv_keys(vec![read(private_key)], vec![vec![]])
+                .unwrap();
+            assert_eq!(jwe_key_wrapper.unwrap_keys(&dc, &json).unwrap(), payload);
+        }
+
+        let mut dc = DecryptConfig::default();
+        dc.decrypt_with_priv_keys(vec![read("private_key_ec.der")], vec![vec![]])
+            .unwrap();
+        assert!(jwe_key_wrapper.unwrap_keys(&dc, &json).is_err());
+
+        assert!(encrypter(&read("public_key_ed25519.pem")).is_err());
+    }
+
     fn load_data_path() -> PathBuf {
         let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         path.push("data");
@@ -0,0 +1,180 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! A keywrapper wrapping the layer key directly with a key encryption key
+//! (KEK) kept as a KBS resource, without a keyprovider in between.
+//!
+//! The annotation holds the [`AnnotationPacket`] of the wrapped key, whose
+//! `kid` is the resource URI of the KEK:
+//!
+//! ```json
+//! {"kid":"kbs:///default/key/1","wrapped_data":"...","iv":"...","wrap_type":"A256GCM"}
+//! ```
+//!
+//! At build time, the KEK and its id are given with
+//! [`EncryptConfig::encrypt_with_kbs_kek`], once for every KBS the image is
+//! encrypted for, each adding an annotation packet. In the guest, the KEK is fetched
+//! from the KBS by the KBC of the `attestation-agent` parameter, i.e.
+//! `provider:attestation-agent:<kbc>::<kbs>` as for the native keyprovider.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use crypto::WrapType;
+use kbc::AnnotationPacket;
+use resource_uri::ResourceUri;
+use zeroize::Zeroizing;
+
+use crate::blockcipher::rand::rand_bytes;
+use crate::config::{DecryptConfig, EncryptConfig};
+use crate::keywrap::keyprovider::native;
+use crate::keywrap::KeyWrapper;
+
+/// Length of the AES-256-GCM IV.
+const IV_LEN: usize = 12;
+
+/// A KBS KEK keywrapper
+#[derive(Debug)]
+pub struct KbsKeyWrapper {}
+
+fn first_param<'a>(param: &'a HashMap<String, Vec<Vec<u8>>>, key: &str) -> Result<&'a [u8]> {
+    param
+        .get(key)
+        .and_then(|values| values.first())
+        .map(Vec::as_slice)
+        .ok_or_else(|| anyhow!("kbs: invalid configuration for keywrap, {key} is missing"))
+}
+
+// wrap `opts_data` with the KEK `kek` of id `kid` into an annotation packet
+fn wrap_with_kek(kid: &[u8], kek: &[u8], opts_data: &[u8]) -> Result<Vec<u8>> {
+    let kid = std::str::from_utf8(kid)?;
+    let kid = ResourceUri::try_from(kid).map_err(|e| anyhow!("kbs: invalid KEK id {kid}: {e}"))?;
+    let kek = Zeroizing::new(kek.to_vec());
+
+    let mut iv = [0; IV_LEN];
+    rand_bytes(&mut iv)?;
+    let wrapped_data = crypto::encrypt(kek, opts_data.to_vec(), iv.to_vec(), WrapType::Aes256Gcm)
+        .map_err(|e| anyhow!("kbs: failed to wrap keys: {e}"))?;
+
+    let engine = base64::engine::general_purpose::STANDARD;
+    let packet = AnnotationPacket {
+        kid,
+        wrapped_data: engine.encode(wrapped_data),
+        iv: engine.encode(iv),
+        wrap_type: WrapType::Aes256Gcm.as_ref().to_string(),
+    };
+
+    Ok(serde_json::to_vec(&packet)?)
+}
+
+impl KeyWrapper for KbsKeyWrapper {
+    fn wrap_keys(&self, ec: &EncryptConfig, opts_data: &[u8]) -> Result<Vec<u8>> {
+        wrap_with_kek(
+            first_param(&ec.param, "kbs-kek-id")?,
+            first_param(&ec.param, "kbs-kek")?,
+            opts_data,
+        )
+    }
+
+    fn wrap_keys_for_recipients(
+        &self,
+        ec: &EncryptConfig,
+        opts_data: &[u8],
+    ) -> Result<Vec<Vec<u8>>> {
+        let Some(kids) = ec.param.get("kbs-kek-id") else {
+            return Ok(vec![]);
+        };
+        let keks = ec
+            .param
+            .get("kbs-kek")
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        if kids.len() != keks.len() {
+            return Err(anyhow!(
+                "kbs: invalid configuration for keywrap, {} KEK ids for {} KEKs",
+                kids.len(),
+                keks.len()
+            ));
+        }
+
+        kids.iter()
+            .zip(keks)
+            .map(|(kid, kek)| wrap_with_kek(kid, kek, opts_data))
+            .collect()
+    }
+
+    fn unwrap_keys(&self, dc: &DecryptConfig, annotation: &[u8]) -> Result<Vec<u8>> {
+        let annotation = std::str::from_utf8(annotation)
+            .map_err(|_e| anyhow!("kbs: invalid data to unwrap_keys()"))?;
+
+        native::unwrap_key(annotation, dc).map_err(|e| anyhow!("kbs: failed to unwrap keys: {e}"))
+    }
+
+    fn annotation_id(&self) -> String {
+        "org.opencontainers.image.enc.keys.kbs".to_string()
+    }
+
+    fn probe(&self, dc_param: &HashMap<String, Vec<Vec<u8>>>) -> bool {
+        dc_param.get("attestation-agent").is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::create_decrypt_config;
+
+    /// The KEK of every `kid` in the sample KBC.
+    const SAMPLE_KBC_KEK: &[u8] = &[
+        217, 155, 119, 5, 176, 186, 122, 22, 130, 149, 179, 163, 54, 114, 112, 176, 221, 155, 55,
+        27, 245, 20, 202, 139, 155, 167, 240, 163, 55, 17, 218, 234,
+    ];
+
+    #[test]
+    fn test_keywrap_kbs() {
+        let wrapper = KbsKeyWrapper {};
+        let opts_data = br#"{"symkey":"test"}"#;
+
+        let mut ec = EncryptConfig::default();
+        assert!(wrapper.wrap_keys(&ec, opts_data).is_err());
+        ec.encrypt_with_kbs_kek("kbs:///default/key/1", SAMPLE_KBC_KEK.to_vec())
+            .unwrap();
+        let annotation = wrapper.wrap_keys(&ec, opts_data).unwrap();
+
+        let packet: serde_json::Value = serde_json::from_slice(&annotation).unwrap();
+        assert_eq!(packet["kid"], "kbs:///default/key/1");
+        assert_eq!(packet["wrap_type"], "A256GCM");
+
+        let cc = create_decrypt_config(
+            vec!["provider:attestation-agent:sample_kbc::null".to_string()],
+            vec![],
+        )
+        .unwrap();
+        let dc = cc.decrypt_config.unwrap();
+        assert!(wrapper.probe(&dc.param));
+        assert_eq!(wrapper.unwrap_keys(&dc, &annotation).unwrap(), opts_data);
+
+        assert!(wrapper
+            .wrap_keys_for_recipients(&EncryptConfig::default(), opts_data)
+            .unwrap()
+            .is_empty());
+        ec.encrypt_with_kbs_kek("kbs:///default/key/2", SAMPLE_KBC_KEK.to_vec())
+            .unwrap();
+        let annotations = wrapper.wrap_keys_for_recipients(&ec, opts_data).unwrap();
+        assert_eq!(annotations.len(), 2);
+        for annotation in &annotations {
+            assert_eq!(wrapper.unwrap_keys(&dc, annotation).unwrap(), opts_data);
+        }
+        ec.param.get_mut("kbs-kek").unwrap().pop();
+        assert!(wrapper.wrap_keys_for_recipients(&ec, opts_data).is_err());
+
+        assert!(!wrapper.probe(&DecryptConfig::default().param));
+        assert_eq!(
+            wrapper.annotation_id(),
+            "org.opencontainers.image.enc.keys.kbs"
+        );
+    }
+}
@@ -3,6 +3,7 @@
 
 use std::collections::HashMap;
 use std::fmt::{self, Debug};
+use std::time::Duration;
 
 use anyhow::{anyhow, bail, Result};
 use base64::Engine;
@@ -13,9 +14,23 @@ use crate::keywrap::KeyWrapper;
 use crate::utils::{self, CommandExecuter};
 
 #[cfg(feature = "keywrap-keyprovider-native")]
-mod native;
+pub(crate) mod native;
 
-#[derive(Debug)]
+/// Default deadline of a single grpc/ttrpc keyprovider call.
+pub const DEFAULT_KEYPROVIDER_TIMEOUT: Duration = Duration::from_secs(50);
+
+/// Default number of times a grpc/ttrpc keyprovider call failing
+/// transiently is repeated.
+pub const DEFAULT_KEYPROVIDER_RETRIES: u32 = 3;
+
+/// Delay before the first repetition of a call, doubled on every further one.
+#[cfg(any(
+    feature = "keywrap-keyprovider-grpc",
+    feature = "keywrap-keyprovider-ttrpc"
+))]
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy)]
 enum OpKey {
     Wrap,
     Unwrap,
@@ -30,6 +45,73 @@ impl fmt::Display for OpKey {
     }
 }
 
+#[cfg(any(
+    feature = "keywrap-keyprovider-grpc",
+    feature = "keywrap-keyprovider-ttrpc"
+))]
+#[derive(Debug, Clone, Copy)]
+enum Transport {
+    #[cfg(feature = "keywrap-keyprovider-grpc")]
+    Grpc,
+    #[cfg(feature = "keywrap-keyprovider-ttrpc")]
+    Ttrpc,
+}
+
+/// Error of a keyprovider call.
+#[cfg(any(
+    feature = "keywrap-keyprovider-grpc",
+    feature = "keywrap-keyprovider-ttrpc"
+))]
+#[derive(Debug)]
+enum CallError {
+    /// The keyprovider could not be reached or did not answer in time, so
+    /// that repeating the call may succeed.
+    Transient(anyhow::Error),
+
+    /// The keyprovider refused the request or gave an invalid reply.
+    Permanent(anyhow::Error),
+}
+
+/// Call `call` until it succeeds, fails permanently or has been repeated
+/// `retries` times. Every attempt is aborted after `timeout`.
+#[cfg(any(
+    feature = "keywrap-keyprovider-grpc",
+    feature = "keywrap-keyprovider-ttrpc"
+))]
+async fn call_with_retry<T, F, Fut>(
+    timeout: Duration,
+    retries: u32,
+    operation: OpKey,
+    mut call: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = std::result::Result<T, CallError>>,
+{
+    let mut backoff = RETRY_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        let err = match tokio::time::timeout(timeout, call()).await {
+            Ok(Ok(v)) => return Ok(v),
+            Ok(Err(CallError::Permanent(e))) => return Err(e),
+            Ok(Err(CallError::Transient(e))) => e,
+            Err(_) => anyhow!("keyprovider: {operation} operation timed out after {timeout:?}"),
+        };
+
+        if attempt >= retries {
+            return Err(err.context(format!(
+                "keyprovider: {operation} operation failed after {} attempts",
+                attempt + 1
+            )));
+        }
+
+        log::warn!("keyprovider: {operation} operation failed, retrying in {backoff:?}: {err:#}");
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 struct KeyWrapParams {
     ec: Option<EncryptConfig>,
@@ -80,13 +162,21 @@ struct KeyProviderKeyWrapProtocolOutput {
 
 impl KeyProviderKeyWrapProtocolOutput {
     #[cfg(feature = "keywrap-keyprovider-grpc")]
-    async fn from_grpc(input: Vec<u8>, conn: &str, operation: OpKey) -> Result<Self> {
-        let uri = conn.parse::<tonic::codegen::http::Uri>().unwrap();
+    async fn from_grpc(
+        input: Vec<u8>,
+        conn: &str,
+        operation: OpKey,
+    ) -> std::result::Result<Self, CallError> {
+        let uri = conn
+            .parse::<tonic::codegen::http::Uri>()
+            .map_err(|e| CallError::Permanent(anyhow!("keyprovider: invalid grpc uri: {e}")))?;
         // create a channel ie connection to server
         let channel = tonic::transport::Channel::builder(uri)
             .connect()
             .await
-            .map_err(|e| anyhow!("keyprovider: error while creating channel: {e}"))?;
+            .map_err(|e| {
+                CallError::Transient(anyhow!("keyprovider: error while creating channel: {e}"))
+            })?;
 
         let mut client =
             crate::utils::grpc::keyprovider::key_provider_service_client::KeyProviderServiceClient::new(
@@ -97,22 +187,22 @@ impl KeyProviderKeyWrapProtocolOutput {
         };
         let request = tonic::Request::new(msg);
         let grpc_output = match operation {
-            OpKey::Wrap => client.wrap_key(request).await.map_err(|e| {
-                anyhow!(
-                    "keyprovider: error from grpc server for {} operation, {}",
-                    OpKey::Wrap,
-                    e
-                )
-            })?,
-
-            OpKey::Unwrap => client.un_wrap_key(request).await.map_err(|e| {
-                anyhow!(
-                    "keyprovider: error from grpc server for {} operation, {}",
-                    OpKey::Unwrap,
-                    e
-                )
-            })?,
-        };
+            OpKey::Wrap => client.wrap_key(request).await,
+            OpKey::Unwrap => client.un_wrap_key(request).await,
+        }
+        .map_err(|status| {
+            let e = anyhow!(
+                "keyprovider: error from grpc server for {} operation, {}",
+                operation,
+                status
+            );
+            match status.code() {
+                tonic::Code::Unavailable
+                | tonic::Code::DeadlineExceeded
+                | tonic::Code::ResourceExhausted => CallError::Transient(e),
+                _ => CallError::Permanent(e),
+            }
+        })?;
 
         serde_json::from_slice(
             &grpc_output
@@ -120,49 +210,60 @@ impl KeyProviderKeyWrapProtocolOutput {
                 .key_provider_key_wrap_protocol_output,
         )
         .map_err(|_| {
-            anyhow!(
+            CallError::Permanent(anyhow!(
                 "Error while deserializing grpc output on {} operation",
-                OpKey::Unwrap
-            )
+                operation
+            ))
         })
     }
 
     #[cfg(feature = "keywrap-keyprovider-ttrpc")]
-    async fn from_ttrpc(input: Vec<u8>, conn: &str, operation: OpKey) -> Result<Self> {
-        let c = ttrpc::r#async::Client::connect(conn)?;
+    async fn from_ttrpc(
+        input: Vec<u8>,
+        conn: &str,
+        operation: OpKey,
+        timeout: Duration,
+    ) -> std::result::Result<Self, CallError> {
+        let c = ttrpc::r#async::Client::connect(conn).map_err(|e| {
+            CallError::Transient(anyhow!("keyprovider: failed to connect to {conn}: {e}"))
+        })?;
 
         let kc = crate::utils::ttrpc::keyprovider_ttrpc::KeyProviderServiceClient::new(c);
-        let kc1 = kc.clone();
         let mut req = crate::utils::ttrpc::keyprovider::KeyProviderKeyWrapProtocolInput::new();
         req.KeyProviderKeyWrapProtocolInput = input;
 
+        let mut ctx = ttrpc::context::with_timeout(timeout.as_nanos() as i64);
+        telemetry::inject_ttrpc(&mut ctx.metadata);
         let ttrpc_output = match operation {
-            OpKey::Wrap => kc1
-                .wrap_key(ttrpc::context::with_timeout(50 * 1000 * 1000 * 1000), &req)
-                .await
-                .map_err(|_| {
-                    anyhow!(
-                        "keyprovider: Error from ttrpc server for {:?} operation",
-                        OpKey::Wrap.to_string()
-                    )
-                })?,
-
-            OpKey::Unwrap => kc1
-                .un_wrap_key(ttrpc::context::with_timeout(50 * 1000 * 1000 * 1000), &req)
-                .await
-                .map_err(|e| {
-                    anyhow!(
-                        "keyprovider: Error from ttrpc server for {:?} operation: {e:?}",
-                        OpKey::Unwrap.to_string()
-                    )
-                })?,
-        };
+            OpKey::Wrap => kc.wrap_key(ctx, &req).await,
+            OpKey::Unwrap => kc.un_wrap_key(ctx, &req).await,
+        }
+        .map_err(|e| {
+            let transient = match &e {
+                ttrpc::Error::RpcStatus(status) => matches!(
+                    status.code.enum_value(),
+                    Ok(ttrpc::Code::UNAVAILABLE
+                        | ttrpc::Code::DEADLINE_EXCEEDED
+                        | ttrpc::Code::RESOURCE_EXHAUSTED)
+                ),
+                _ => true,
+            };
+            let e = anyhow!(
+                "keyprovider: Error from ttrpc server for {:?} operation: {e:?}",
+                operation.to_string()
+            );
+            if transient {
+                CallError::Transient(e)
+            } else {
+                CallError::Permanent(e)
+            }
+        })?;
 
         serde_json::from_slice(&ttrpc_output.KeyProviderKeyWrapProtocolOutput).map_err(|_| {
-            anyhow!(
+            CallError::Permanent(anyhow!(
                 "Error while deserializing ttrpc output on {:?} operation",
-                OpKey::Unwrap.to_string()
-            )
+                operation.to_string()
+            ))
         })
     }
 
@@ -185,36 +286,11 @@ impl KeyProviderKeyWrapProtocolOutput {
 
     #[cfg(feature = "keywrap-keyprovider-native")]
     fn from_native(annotation: &str, dc_config: &DecryptConfig) -> Result<Self> {
-        let kbc_kbs_pair = if let Some(list) = dc_config.param.get("attestation-agent") {
-            list.first()
-                .ok_or_else(|| anyhow!("keyprovider: empty kbc::kbs pair"))?
-        } else {
-            return Err(anyhow!("keyprovider: not supported attestation agent"));
-        };
-        let pair_str = String::from_utf8(kbc_kbs_pair.to_vec())?;
-        let (kbc, kbs) = pair_str
-            .split_once("::")
-            .ok_or_else(|| anyhow!("keyprovider: invalid kbc::kbs pair"))?;
-        let kbs = kbs.to_string();
-        let kbc = kbc.to_string();
-        let annotation = annotation.to_string();
-
-        let handler = std::thread::spawn(move || {
-            create_async_runtime()?.block_on(async {
-                native::decrypt_image_layer_annotation(&kbs, &kbc, &annotation)
-                    .await
-                    .map_err(|e| format!("{e:?}"))
-            })
-        });
-
-        match handler.join() {
-            Ok(Ok(v)) => Ok(KeyProviderKeyWrapProtocolOutput {
-                key_unwrap_results: Some(KeyUnwrapResults { opts_data: v }),
-                ..Default::default()
-            }),
-            Ok(Err(e)) => Err(anyhow!("keyprovider: retrieve opts_data failed: {e:?}")),
-            Err(e) => Err(anyhow!("keyprovider: retrieve opts_data failed: {e:?}")),
-        }
+        let opts_data = native::unwrap_key(annotation, dc_config)?;
+        Ok(KeyProviderKeyWrapProtocolOutput {
+            key_unwrap_results: Some(KeyUnwrapResults { opts_data }),
+            ..Default::default()
+        })
     }
 }
 
@@ -284,30 +360,14 @@ impl KeyProviderKeyWrapper {
         }
         #[cfg(feature = "keywrap-keyprovider-grpc")]
         {
-            let grpc = grpc.to_string();
-            let handler = std::thread::spawn(move || {
-                create_async_runtime()?.block_on(async {
-                    KeyProviderKeyWrapProtocolOutput::from_grpc(_input, &grpc, OpKey::Wrap)
-                        .await
-                        .map_err(|e| format!("{e}"))
-                })
-            });
-            let protocol_output = match handler.join() {
-                Ok(Ok(v)) => v,
-                Ok(Err(e)) => {
-                    return Err(anyhow!(
-                        "keyprovider: grpc provider failed to execute {} operation: {}",
-                        OpKey::Wrap,
-                        e
-                    ));
-                }
-                Err(e) => {
-                    return Err(anyhow!(
-                        "keyprovider: grpc provider failed to execute {} operation: {e:?}",
+            let protocol_output = self
+                .call_remote(_input, grpc, Transport::Grpc, OpKey::Wrap)
+                .map_err(|e| {
+                    anyhow!(
+                        "keyprovider: grpc provider failed to execute {} operation: {e:#}",
                         OpKey::Wrap,
-                    ));
-                }
-            };
+                    )
+                })?;
             if let Some(result) = protocol_output.key_wrap_results {
                 Ok(result.annotation)
             } else {
@@ -326,30 +386,14 @@ impl KeyProviderKeyWrapper {
         }
         #[cfg(feature = "keywrap-keyprovider-ttrpc")]
         {
-            let ttrpc = ttrpc.to_string();
-            let handler = std::thread::spawn(move || {
-                create_async_runtime()?.block_on(async {
-                    KeyProviderKeyWrapProtocolOutput::from_ttrpc(_input, &ttrpc, OpKey::Wrap)
-                        .await
-                        .map_err(|e| format!("{e}"))
-                })
-            });
-            let protocol_output = match handler.join() {
-                Ok(Ok(v)) => v,
-                Ok(Err(e)) => {
-                    return Err(anyhow!(
-                        "keyprovider: ttrpc provider failed to execute {} operation: {}",
-                        OpKey::Wrap,
-                        e
-                    ));
-                }
-                Err(e) => {
-                    return Err(anyhow!(
-                        "keyprovider: ttrpc provider failed to execute {} operation: {e:?}",
+            let protocol_output = self
+                .call_remote(_input, ttrpc, Transport::Ttrpc, OpKey::Wrap)
+                .map_err(|e| {
+                    anyhow!(
+                        "keyprovider: ttrpc provider failed to execute {} operation: {e:#}",
                         OpKey::Wrap,
-                    ));
-                }
-            };
+                    )
+                })?;
             if let Some(result) = protocol_output.key_wrap_results {
                 Ok(result.annotation)
             } else {
@@ -391,26 +435,8 @@ impl KeyProviderKeyWrapper {
             grpc
         ));
         #[cfg(feature = "keywrap-keyprovider-grpc")]
-        {
-            let grpc = grpc.to_string();
-            let handler = std::thread::spawn(move || {
-                create_async_runtime()?.block_on(async {
-                    KeyProviderKeyWrapProtocolOutput::from_grpc(_input, &grpc, OpKey::Unwrap)
-                        .await
-                        .map_err(|e| {
-                            format!(
-                                "keyprovider: grpc provider failed to execute {} operation: {e}",
-                                OpKey::Wrap,
-                            )
-                        })
-                })
-            });
-            match handler.join() {
-                Ok(Ok(v)) => Ok(v),
-                Ok(Err(e)) => bail!("failed to unwrap key by gRPC, {e}"),
-                Err(e) => bail!("failed to unwrap key by gRPC, {e:?}"),
-            }
-        }
+        self.call_remote(_input, grpc, Transport::Grpc, OpKey::Unwrap)
+            .map_err(|e| anyhow!("failed to unwrap key by gRPC, {e:#}"))
     }
 
     fn unwrap_key_ttrpc(
@@ -424,25 +450,68 @@ impl KeyProviderKeyWrapper {
             ttrpc
         ));
         #[cfg(feature = "keywrap-keyprovider-ttrpc")]
-        {
-            let ttrpc = ttrpc.to_string();
-            let handler = std::thread::spawn(move || {
-                create_async_runtime()?.block_on(async {
-                    KeyProviderKeyWrapProtocolOutput::from_ttrpc(_input, &ttrpc, OpKey::Unwrap)
-                        .await
-                        .map_err(|e| {
-                            format!(
-                                "keyprovider: ttrpc provider failed to execute {} operation: {e}",
-                                OpKey::Wrap,
-                            )
-                        })
+        self.call_remote(_input, ttrpc, Transport::Ttrpc, OpKey::Unwrap)
+            .map_err(|e| anyhow!("failed to unwrap key by ttrpc, {e:#}"))
+    }
+
+    /// Call a grpc/ttrpc keyprovider with the deadline and retries of
+    /// `attrs`. The call runs on its own runtime, as the [`KeyWrapper`]
+    /// interface is blocking and may be called from within a runtime.
+    #[cfg(any(
+        feature = "keywrap-keyprovider-grpc",
+        feature = "keywrap-keyprovider-ttrpc"
+    ))]
+    fn call_remote(
+        &self,
+        input: Vec<u8>,
+        conn: &str,
+        transport: Transport,
+        operation: OpKey,
+    ) -> Result<KeyProviderKeyWrapProtocolOutput> {
+        let conn = conn.to_string();
+        let timeout = self
+            .attrs
+            .timeout
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_KEYPROVIDER_TIMEOUT);
+        let retries = self.attrs.retries.unwrap_or(DEFAULT_KEYPROVIDER_RETRIES);
+
+        // the thread does not inherit the trace context of the caller
+        #[cfg(feature = "keywrap-keyprovider-ttrpc")]
+        let trace_cx = telemetry::Context::current();
+        let handler = std::thread::spawn(move || {
+            #[cfg(feature = "keywrap-keyprovider-ttrpc")]
+            let _trace_cx = trace_cx.attach();
+            create_async_runtime()?.block_on(async {
+                call_with_retry(timeout, retries, operation, || {
+                    let input = input.clone();
+                    let conn = conn.clone();
+                    async move {
+                        match transport {
+                            #[cfg(feature = "keywrap-keyprovider-grpc")]
+                            Transport::Grpc => {
+                                KeyProviderKeyWrapProtocolOutput::from_grpc(input, &conn, operation)
+                                    .await
+                            }
+                            #[cfg(feature = "keywrap-keyprovider-ttrpc")]
+                            Transport::Ttrpc => {
+                                KeyProviderKeyWrapProtocolOutput::from_ttrpc(
+                                    input, &conn, operation, timeout,
+                                )
+                                .await
+                            }
+                        }
+                    }
                 })
-            });
-            match handler.join() {
-                Ok(Ok(v)) => Ok(v),
-                Ok(Err(e)) => bail!("failed to unwrap key by ttrpc, {e}"),
-                Err(e) => bail!("failed to unwrap key by ttrpc, {e:?}"),
-            }
+                .await
+                .map_err(|e| format!("{e:#}"))
+            })
+        });
+
+        match handler.join() {
+            Ok(Ok(v)) => Ok(v),
+            Ok(Err(e)) => bail!("{e}"),
+            Err(e) => bail!("{e:?}"),
         }
     }
 
@@ -510,6 +579,28 @@ impl KeyWrapper for KeyProviderKeyWrapper {
         }
     }
 
+    /// Every parameter of the provider in the encrypt config is a recipient,
+    /// e.g. `provider:attestation-agent:keyid=...` given once for every KBS,
+    /// so the keyprovider is called once for each of them.
+    fn wrap_keys_for_recipients(
+        &self,
+        enc_config: &EncryptConfig,
+        opts_data: &[u8],
+    ) -> Result<Vec<Vec<u8>>> {
+        let Some(params) = enc_config.param.get(&self.provider) else {
+            return Ok(vec![]);
+        };
+
+        params
+            .iter()
+            .map(|param| {
+                let mut ec = enc_config.clone();
+                ec.param.insert(self.provider.clone(), vec![param.clone()]);
+                self.wrap_keys(&ec, opts_data)
+            })
+            .collect()
+    }
+
     /// UnwrapKey calls appropriate binary-executable or grpc/ttrpc server for unwrapping the
     /// session key based on the protocol given in annotation for recipients and gets decrypted
     /// optsData, which describe the symmetric key used for decrypting the layer
@@ -962,6 +1053,8 @@ mod tests {
             grpc: None,
             ttrpc: None,
             native: None,
+            timeout: None,
+            retries: None,
         };
         provider.insert(String::from("provider"), attrs.clone());
         let mut keyprovider_key_wrapper = KeyProviderKeyWrapper::new(
@@ -1002,6 +1095,8 @@ mod tests {
             grpc: None,
             ttrpc: None,
             native: None,
+            timeout: None,
+            retries: None,
         };
         provider.insert(String::from("provider"), attrs.clone());
         keyprovider_key_wrapper = KeyProviderKeyWrapper::new(
@@ -1033,6 +1128,8 @@ mod tests {
             grpc: None,
             ttrpc: None,
             native: None,
+            timeout: None,
+            retries: None,
         };
         provider.insert(String::from("provider"), attrs.clone());
         let keyprovider_key_wrapper = KeyProviderKeyWrapper::new(
@@ -1067,6 +1164,8 @@ mod tests {
             grpc: None,
             ttrpc: None,
             native: None,
+            timeout: None,
+            retries: None,
         };
         provider.insert(String::from("provider"), attrs.clone());
         let keyprovider_key_wrapper = KeyProviderKeyWrapper::new(
@@ -1119,6 +1218,8 @@ mod tests {
             grpc: Some("tcp://127.0.0.1:8990".to_string()),
             ttrpc: None,
             native: None,
+            timeout: None,
+            retries: None,
         };
         provider.insert(String::from("provider"), attrs.clone());
         let keyprovider_key_wrapper =
@@ -1170,6 +1271,8 @@ mod tests {
             grpc: Some("http://127.0.0.1:8991".to_string()),
             ttrpc: None,
             native: None,
+            timeout: None,
+            retries: None,
         };
         provider.insert(String::from("provider"), attrs.clone());
         let keyprovider_key_wrapper =
@@ -1221,6 +1324,8 @@ mod tests {
             grpc: None,
             ttrpc: Some(self::ttrpc_test::SOCK_ADDR.to_string()),
             native: None,
+            timeout: None,
+            retries: None,
         };
         provider.insert(String::from("provider"), attrs.clone());
         let keyprovider_key_wrapper =
@@ -1252,6 +1357,53 @@ mod tests {
         rt.shutdown_background();
     }
 
+    #[cfg(any(
+        feature = "keywrap-keyprovider-grpc",
+        feature = "keywrap-keyprovider-ttrpc"
+    ))]
+    #[test]
+    fn test_call_with_retry() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let timeout = Duration::from_secs(1);
+            let calls = std::cell::Cell::new(0);
+            let flaky = || {
+                calls.set(calls.get() + 1);
+                let n = calls.get();
+                async move {
+                    if n < 3 {
+                        Err(CallError::Transient(anyhow!("unavailable")))
+                    } else {
+                        Ok(n)
+                    }
+                }
+            };
+            let res = call_with_retry(timeout, 3, OpKey::Unwrap, flaky).await;
+            assert_eq!(res.unwrap(), 3);
+
+            calls.set(0);
+            let res = call_with_retry(timeout, 1, OpKey::Unwrap, flaky).await;
+            assert!(res.is_err());
+            assert_eq!(calls.get(), 2);
+
+            calls.set(0);
+            let refused = || {
+                calls.set(calls.get() + 1);
+                async { Err::<(), _>(CallError::Permanent(anyhow!("refused"))) }
+            };
+            let res = call_with_retry(timeout, 3, OpKey::Unwrap, refused).await;
+            assert!(res.unwrap_err().to_string().contains("refused"));
+            assert_eq!(calls.get(), 1);
+
+            let hung = || async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok::<(), CallError>(())
+            };
+            let res = call_with_retry(Duration::from_millis(10), 0, OpKey::Wrap, hung).await;
+            assert!(format!("{:#}", res.unwrap_err()).contains("timed out"));
+        });
+    }
+
     #[cfg(feature = "keywrap-keyprovider-native")]
     #[test]
     fn test_key_provider_native_fail() {
@@ -1262,6 +1414,8 @@ mod tests {
             grpc: None,
             ttrpc: None,
             native: Some("attestation-agent".to_string()),
+            timeout: None,
+            retries: None,
         };
         provider.insert(String::from("provider"), attrs.clone());
         let keyprovider_key_wrapper =
@@ -1306,6 +1460,8 @@ mod tests {
             grpc: None,
             ttrpc: None,
             native: Some("attestation-agent".to_string()),
+            timeout: None,
+            retries: None,
         };
         provider.insert(String::from("provider"), attrs.clone());
         let keyprovider_key_wrapper =
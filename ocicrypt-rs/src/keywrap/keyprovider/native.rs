@@ -9,6 +9,9 @@ use anyhow::*;
 use kbc::{cc_kbc::Kbc as CcKbc, sample_kbc::SampleKbc, AnnotationPacket, KbcInterface};
 use tokio::sync::RwLock;
 
+use super::create_async_runtime;
+use crate::config::DecryptConfig;
+
 pub enum Kbc {
     Sample(SampleKbc),
     Cc(CcKbc),
@@ -54,3 +57,35 @@ pub async fn decrypt_image_layer_annotation(
 
     Ok(res)
 }
+
+/// Unwrap the key of the annotation packet `annotation`, with the KBC and
+/// KBS given by the `attestation-agent` parameter (`<kbc>::<kbs>`) of
+/// `dc_config`.
+pub fn unwrap_key(annotation: &str, dc_config: &DecryptConfig) -> Result<Vec<u8>> {
+    let kbc_kbs_pair = if let Some(list) = dc_config.param.get("attestation-agent") {
+        list.first()
+            .ok_or_else(|| anyhow!("keyprovider: empty kbc::kbs pair"))?
+    } else {
+        return Err(anyhow!("keyprovider: not supported attestation agent"));
+    };
+    let pair_str = String::from_utf8(kbc_kbs_pair.to_vec())?;
+    let (kbc, kbs) = pair_str
+        .split_once("::")
+        .ok_or_else(|| anyhow!("keyprovider: invalid kbc::kbs pair"))?;
+    let kbs = kbs.to_string();
+    let kbc = kbc.to_string();
+    let annotation = annotation.to_string();
+
+    let handler = std::thread::spawn(move || {
+        create_async_runtime()?.block_on(async {
+            decrypt_image_layer_annotation(&kbs, &kbc, &annotation)
+                .await
+                .map_err(|e| format!("{e:?}"))
+        })
+    });
+
+    handler
+        .join()
+        .map_err(|e| anyhow!("keyprovider: retrieve opts_data failed: {e:?}"))?
+        .map_err(|e| anyhow!("keyprovider: retrieve opts_data failed: {e:?}"))
+}
@@ -9,8 +9,12 @@ use crate::config::{DecryptConfig, EncryptConfig};
 
 #[cfg(feature = "keywrap-jwe")]
 pub mod jwe;
+#[cfg(feature = "keywrap-kbs")]
+pub mod kbs;
 #[cfg(feature = "keywrap-keyprovider")]
 pub mod keyprovider;
+#[cfg(feature = "keywrap-pkcs7")]
+pub mod pkcs7;
 
 /// KeyWrapper is the interface used for wrapping keys using
 /// a specific encryption technology (pgp, jwe, pkcs7, pkcs11, keyprovider)
@@ -19,6 +23,19 @@ pub trait KeyWrapper: Send + Sync {
     /// wrap keys data with encrypt config.
     fn wrap_keys(&self, ec: &EncryptConfig, opts_data: &[u8]) -> Result<Vec<u8>>;
 
+    /// wrap keys data once for every recipient of this keywrapper in the
+    /// encrypt config, e.g. every KEK of the kbs keywrapper, so that the
+    /// layer is encrypted once for all of them. Empty if the encrypt config
+    /// has no recipient for it. By default the keys are wrapped once with
+    /// [`KeyWrapper::wrap_keys`].
+    fn wrap_keys_for_recipients(
+        &self,
+        ec: &EncryptConfig,
+        opts_data: &[u8],
+    ) -> Result<Vec<Vec<u8>>> {
+        Ok(vec![self.wrap_keys(ec, opts_data)?])
+    }
+
     /// unwrap keys data with decrypt config.
     fn unwrap_keys(&self, dc: &DecryptConfig, annotation: &[u8]) -> Result<Vec<u8>>;
 
@@ -56,6 +73,15 @@ impl<W: KeyWrapper + ?Sized> KeyWrapper for Box<W> {
         (**self).wrap_keys(ec, opts_data)
     }
 
+    #[inline]
+    fn wrap_keys_for_recipients(
+        &self,
+        ec: &EncryptConfig,
+        opts_data: &[u8],
+    ) -> Result<Vec<Vec<u8>>> {
+        (**self).wrap_keys_for_recipients(ec, opts_data)
+    }
+
     #[inline]
     fn unwrap_keys(&self, dc: &DecryptConfig, annotation: &[u8]) -> Result<Vec<u8>> {
         (**self).unwrap_keys(dc, annotation)
@@ -0,0 +1,582 @@
+// Copyright The ocicrypt Authors.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use openssl::cms::CmsContentInfo;
+use openssl::encrypt::{Decrypter, Encrypter};
+use openssl::pkey::{Id, PKey, Private};
+use openssl::rand::rand_bytes;
+use openssl::rsa::Padding;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use openssl::x509::X509;
+
+use crate::config::{DecryptConfig, EncryptConfig};
+use crate::keywrap::KeyWrapper;
+
+/// A Pkcs7 keywrapper. The layer keys are enveloped as containerd/imgcrypt
+/// does: a CMS EnvelopedData with the content encrypted with AES-128-GCM,
+/// and the content key wrapped with RSA PKCS#1 v1.5 for the x509
+/// certificate of every recipient.
+///
+/// Unwrapping takes the envelopes of imgcrypt, and any CMS EnvelopedData or
+/// AuthEnvelopedData OpenSSL reads, e.g. with RSAES-OAEP or ECDH and AES key
+/// wrap (RFC 5753) recipients.
+#[derive(Debug)]
+pub struct Pkcs7KeyWrapper {}
+
+// DER tags
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const CONTEXT_0: u8 = 0x80;
+const CONTEXT_0_CONSTRUCTED: u8 = 0xa0;
+
+// DER encoded OIDs
+const OID_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x01];
+const OID_ENVELOPED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x03];
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const OID_AES_128_GCM: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x06];
+const OID_AES_256_GCM: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2e];
+
+const GCM_NONCE_LEN: usize = 12;
+const GCM_TAG_LEN: usize = 16;
+
+// Encode a DER value
+fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = contents.len().to_be_bytes();
+    let skip = len.iter().take_while(|b| **b == 0).count();
+    match contents.len() {
+        0..=0x7f => out.push(contents.len() as u8),
+        _ => {
+            out.push(0x80 | (len.len() - skip) as u8);
+            out.extend_from_slice(&len[skip..]);
+        }
+    }
+    out.extend_from_slice(contents);
+    out
+}
+
+// Encode a DER INTEGER of the unsigned big endian `value`
+fn der_uint(value: &[u8]) -> Vec<u8> {
+    let skip = value.iter().take_while(|b| **b == 0).count();
+    let mut contents = value[skip..].to_vec();
+    if contents.first().map_or(true, |b| b & 0x80 != 0) {
+        contents.insert(0, 0);
+    }
+
+    der(INTEGER, &contents)
+}
+
+fn der_seq(values: &[&[u8]]) -> Vec<u8> {
+    der(SEQUENCE, &values.concat())
+}
+
+// Decode the DER value at the start of `data`, into its tag, its contents
+// and the data after it
+fn der_next(data: &[u8]) -> Result<(u8, &[u8], &[u8])> {
+    let malformed = || anyhow!("pkcs7: malformed DER value");
+    let (&tag, data) = data.split_first().ok_or_else(malformed)?;
+    let (&len, mut data) = data.split_first().ok_or_else(malformed)?;
+    let len = match len {
+        0..=0x7f => len as usize,
+        0x81..=0x84 => {
+            let bytes = data.get(..(len & 0x7f) as usize).ok_or_else(malformed)?;
+            data = &data[bytes.len()..];
+            bytes.iter().fold(0, |len, b| len << 8 | *b as usize)
+        }
+        _ => bail!("pkcs7: unsupported DER length"),
+    };
+    let contents = data.get(..len).ok_or_else(malformed)?;
+
+    Ok((tag, contents, &data[len..]))
+}
+
+// Decode the DER value at the start of `data`, expecting `tag`
+fn der_expect(tag: u8, data: &[u8]) -> Result<(&[u8], &[u8])> {
+    match der_next(data)? {
+        (t, contents, rest) if t == tag => Ok((contents, rest)),
+        (t, _, _) => bail!("pkcs7: unexpected DER tag {t:#04x}, expected {tag:#04x}"),
+    }
+}
+
+// The IssuerAndSerialNumber of `cert`, which identifies its recipient
+fn issuer_and_serial(cert: &X509) -> Result<Vec<u8>> {
+    let issuer = cert.issuer_name().to_der()?;
+    let serial = cert.serial_number().to_bn()?.to_vec();
+
+    Ok(der_seq(&[&issuer, &der_uint(&serial)]))
+}
+
+// Parse a x509 certificate in PEM or DER
+fn certificate(data: &[u8]) -> Result<X509> {
+    X509::from_pem(data)
+        .or_else(|_| X509::from_der(data))
+        .map_err(|e| anyhow!("pkcs7: failed to parse x509 certificate: {e}"))
+}
+
+// Parse a private key in PEM or DER, PKCS#1 or PKCS#8, with its password
+fn private_key(data: &[u8], password: &[u8]) -> Result<PKey<Private>> {
+    let pkey = if data.starts_with(b"-----BEGIN") {
+        PKey::private_key_from_pem_passphrase(data, password)
+    } else if password.is_empty() {
+        PKey::private_key_from_der(data)
+    } else {
+        PKey::private_key_from_pkcs8_passphrase(data, password)
+    };
+
+    pkey.map_err(|e| anyhow!("pkcs7: failed to parse private key: {e}"))
+}
+
+// Envelope `data` for all the `certs`, as imgcrypt does
+fn envelope(certs: &[X509], data: &[u8]) -> Result<Vec<u8>> {
+    let mut key = [0; 16];
+    let mut nonce = [0; GCM_NONCE_LEN];
+    let mut tag = [0; GCM_TAG_LEN];
+    rand_bytes(&mut key)?;
+    rand_bytes(&mut nonce)?;
+    let mut ciphertext = encrypt_aead(
+        Cipher::aes_128_gcm(),
+        &key,
+        Some(&nonce[..]),
+        &[],
+        data,
+        &mut tag,
+    )?;
+    ciphertext.extend_from_slice(&tag);
+
+    let mut recipients = vec![];
+    for cert in certs {
+        let pkey = cert.public_key()?;
+        if pkey.id() != Id::RSA {
+            bail!(
+                "pkcs7: keys of type {:?} can't wrap keys, use an RSA certificate",
+                pkey.id()
+            );
+        }
+
+        let mut encrypter = Encrypter::new(&pkey)?;
+        encrypter.set_rsa_padding(Padding::PKCS1)?;
+        let mut encrypted_key = vec![0; encrypter.encrypt_len(&key)?];
+        let len = encrypter.encrypt(&key, &mut encrypted_key)?;
+        encrypted_key.truncate(len);
+
+        recipients.extend(der_seq(&[
+            &der_uint(&[0]),
+            &issuer_and_serial(cert)?,
+            &der_seq(&[&der(OID, OID_RSA_ENCRYPTION)]),
+            &der(OCTET_STRING, &encrypted_key),
+        ]));
+    }
+
+    // imgcrypt reads the GCMParameters from the contents of the parameters,
+    // so they are nested in another SEQUENCE as its encoder does
+    let gcm_parameters = der_seq(&[&der(OCTET_STRING, &nonce), &der_uint(&[GCM_TAG_LEN as u8])]);
+    let encrypted_content_info = der_seq(&[
+        &der(OID, OID_DATA),
+        &der_seq(&[&der(OID, OID_AES_128_GCM), &der_seq(&[&gcm_parameters])]),
+        &der(CONTEXT_0, &ciphertext),
+    ]);
+    let enveloped_data = der_seq(&[
+        &der_uint(&[0]),
+        &der(SET, &recipients),
+        &encrypted_content_info,
+    ]);
+
+    Ok(der_seq(&[
+        &der(OID, OID_ENVELOPED_DATA),
+        &der(CONTEXT_0_CONSTRUCTED, &enveloped_data),
+    ]))
+}
+
+/// An EnvelopedData with its content encrypted with AES-GCM, as imgcrypt
+/// makes it. OpenSSL only reads AES-GCM in AuthEnvelopedData, and refuses
+/// the parameters imgcrypt writes.
+struct GcmEnvelope<'a> {
+    /// The IssuerAndSerialNumber and the encrypted key of the RSA recipients
+    recipients: Vec<(&'a [u8], &'a [u8])>,
+    cipher: Cipher,
+    nonce: &'a [u8],
+    tag_len: usize,
+    ciphertext: Vec<u8>,
+}
+
+impl<'a> GcmEnvelope<'a> {
+    /// Parse `packet`, or `None` if it is no EnvelopedData with AES-GCM
+    fn parse(packet: &'a [u8]) -> Result<Option<Self>> {
+        let (content_info, _) = der_expect(SEQUENCE, packet)?;
+        let (content_type, content_info) = der_expect(OID, content_info)?;
+        if content_type != OID_ENVELOPED_DATA {
+            return Ok(None);
+        }
+        let (content, _) = der_expect(CONTEXT_0_CONSTRUCTED, content_info)?;
+        let (enveloped_data, _) = der_expect(SEQUENCE, content)?;
+        let (_version, mut enveloped_data) = der_expect(INTEGER, enveloped_data)?;
+        // the optional originatorInfo
+        if let (CONTEXT_0_CONSTRUCTED, _, rest) = der_next(enveloped_data)? {
+            enveloped_data = rest;
+        }
+        let (mut recipient_infos, enveloped_data) = der_expect(SET, enveloped_data)?;
+        let (encrypted_content_info, _) = der_expect(SEQUENCE, enveloped_data)?;
+
+        let (_content_type, encrypted_content_info) = der_expect(OID, encrypted_content_info)?;
+        let (algorithm, encrypted_content) = der_expect(SEQUENCE, encrypted_content_info)?;
+        let (algorithm, parameters) = der_expect(OID, algorithm)?;
+        let cipher = match algorithm {
+            OID_AES_128_GCM => Cipher::aes_128_gcm(),
+            OID_AES_256_GCM => Cipher::aes_256_gcm(),
+            _ => return Ok(None),
+        };
+
+        // the GCMParameters (RFC 5084), which imgcrypt nests in a SEQUENCE
+        // it encodes as primitive
+        let (_, mut gcm_parameters, _) = der_next(parameters)?;
+        if let (SEQUENCE, nested, _) = der_next(gcm_parameters)? {
+            gcm_parameters = nested;
+        }
+        let (nonce, gcm_parameters) = der_expect(OCTET_STRING, gcm_parameters)?;
+        let tag_len = match der_next(gcm_parameters) {
+            Ok((INTEGER, &[tag_len], _)) => tag_len as usize,
+            Ok(_) => bail!("pkcs7: malformed GCM parameters"),
+            // the default of RFC 5084
+            Err(_) => 12,
+        };
+
+        // the ciphertext with the tag, which imgcrypt encodes as OCTET
+        // STRING in a constructed [0]
+        let ciphertext = match der_next(encrypted_content)? {
+            (CONTEXT_0, ciphertext, _) => ciphertext.to_vec(),
+            (CONTEXT_0_CONSTRUCTED, mut parts, _) => {
+                let mut ciphertext = vec![];
+                while !parts.is_empty() {
+                    let (part, rest) = der_expect(OCTET_STRING, parts)?;
+                    ciphertext.extend_from_slice(part);
+                    parts = rest;
+                }
+                ciphertext
+            }
+            (tag, _, _) => bail!("pkcs7: unexpected encrypted content tag {tag:#04x}"),
+        };
+        if !(12..=GCM_TAG_LEN).contains(&tag_len) || ciphertext.len() < tag_len {
+            bail!("pkcs7: malformed GCM encrypted content");
+        }
+
+        // the key transport recipients with an IssuerAndSerialNumber and
+        // RSA, the other ones are left to OpenSSL
+        let mut recipients = vec![];
+        while !recipient_infos.is_empty() {
+            let (tag, recipient_info, rest) = der_next(recipient_infos)?;
+            recipient_infos = rest;
+            if tag != SEQUENCE {
+                continue;
+            }
+            let (_version, recipient_info) = der_expect(INTEGER, recipient_info)?;
+            let (rid_tag, _, after_rid) = der_next(recipient_info)?;
+            if rid_tag != SEQUENCE {
+                continue;
+            }
+            let rid = &recipient_info[..recipient_info.len() - after_rid.len()];
+            let (key_algorithm, after_algorithm) = der_expect(SEQUENCE, after_rid)?;
+            let (key_algorithm, _) = der_expect(OID, key_algorithm)?;
+            let (encrypted_key, _) = der_expect(OCTET_STRING, after_algorithm)?;
+            if key_algorithm == OID_RSA_ENCRYPTION {
+                recipients.push((rid, encrypted_key));
+            }
+        }
+
+        Ok(Some(Self {
+            recipients,
+            cipher,
+            nonce,
+            tag_len,
+            ciphertext,
+        }))
+    }
+
+    /// Decrypt the content with `pkey`, for the recipients of `certs`, or
+    /// for any recipient without them
+    fn decrypt(&self, pkey: &PKey<Private>, certs: &[X509]) -> Option<Vec<u8>> {
+        let rids: Vec<Vec<u8>> = certs
+            .iter()
+            .filter_map(|cert| issuer_and_serial(cert).ok())
+            .collect();
+        let (ciphertext, tag) = self
+            .ciphertext
+            .split_at(self.ciphertext.len() - self.tag_len);
+
+        self.recipients
+            .iter()
+            .filter(|(rid, _)| rids.is_empty() || rids.iter().any(|r| r == rid))
+            .find_map(|(_, encrypted_key)| {
+                let mut decrypter = Decrypter::new(pkey).ok()?;
+                decrypter.set_rsa_padding(Padding::PKCS1).ok()?;
+                let mut key = vec![0; decrypter.decrypt_len(encrypted_key).ok()?];
+                let len = decrypter.decrypt(encrypted_key, &mut key).ok()?;
+                key.truncate(len);
+                if key.len() != self.cipher.key_len() {
+                    return None;
+                }
+
+                // a wrong key, e.g. of the implicit rejection of PKCS#1 v1.5,
+                // fails the tag
+                decrypt_aead(self.cipher, &key, Some(self.nonce), &[], ciphertext, tag).ok()
+            })
+    }
+}
+
+/// An envelope of layer keys, of imgcrypt or of anything OpenSSL reads
+enum Envelope<'a> {
+    Gcm(GcmEnvelope<'a>),
+    Cms(CmsContentInfo),
+}
+
+impl<'a> Envelope<'a> {
+    fn parse(packet: &'a [u8]) -> Result<Self> {
+        if let Ok(Some(envelope)) = GcmEnvelope::parse(packet) {
+            return Ok(Self::Gcm(envelope));
+        }
+
+        CmsContentInfo::from_der(packet)
+            .map(Self::Cms)
+            .map_err(|e| anyhow!("pkcs7: malformed CMS envelope: {e}"))
+    }
+
+    // the certificates of the recipients are optional, as image-rs only
+    // gets the private keys
+    fn decrypt(&self, pkey: &PKey<Private>, certs: &[X509]) -> Option<Vec<u8>> {
+        match self {
+            Self::Gcm(envelope) => envelope.decrypt(pkey, certs),
+            Self::Cms(cms) if certs.is_empty() => cms.decrypt_without_cert_check(pkey).ok(),
+            Self::Cms(cms) => certs.iter().find_map(|cert| cms.decrypt(pkey, cert).ok()),
+        }
+    }
+}
+
+impl KeyWrapper for Pkcs7KeyWrapper {
+    fn wrap_keys(&self, ec: &EncryptConfig, opts_data: &[u8]) -> Result<Vec<u8>> {
+        let x509s = ec
+            .param
+            .get("x509s")
+            .ok_or_else(|| anyhow!("pkcs7: invalid configuration for keywrap"))?;
+        let certs = x509s
+            .iter()
+            .map(|x509| certificate(x509))
+            .collect::<Result<Vec<_>>>()?;
+
+        envelope(&certs, opts_data)
+    }
+
+    // a single envelope holds the keys for all the certificates
+    fn wrap_keys_for_recipients(
+        &self,
+        ec: &EncryptConfig,
+        opts_data: &[u8],
+    ) -> Result<Vec<Vec<u8>>> {
+        match ec.param.get("x509s") {
+            Some(x509s) if !x509s.is_empty() => Ok(vec![self.wrap_keys(ec, opts_data)?]),
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn unwrap_keys(&self, dc: &DecryptConfig, pkcs7_packet: &[u8]) -> Result<Vec<u8>> {
+        let privkeys = self
+            .private_keys(&dc.param)
+            .ok_or_else(|| anyhow!("pkcs7: invalid configuration for keyunwrap"))?;
+        let passwords = dc.param.get("privkeys-passwords");
+        let certs: Vec<X509> = dc
+            .param
+            .get("x509s")
+            .into_iter()
+            .flatten()
+            .filter_map(|x509| certificate(x509).ok())
+            .collect();
+        let envelope = Envelope::parse(pkcs7_packet)?;
+
+        for (index, privkey) in privkeys.iter().enumerate() {
+            // the private keys are shared with the other keywrappers, e.g.
+            // JWK keys of the jwe one
+            let password = passwords
+                .and_then(|passwords| passwords.get(index))
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let Ok(pkey) = private_key(privkey, password) else {
+                continue;
+            };
+
+            if let Some(keys) = envelope.decrypt(&pkey, &certs) {
+                return Ok(keys);
+            }
+        }
+
+        Err(anyhow!(
+            "pkcs7: No suitable private key found for decryption"
+        ))
+    }
+
+    fn annotation_id(&self) -> String {
+        "org.opencontainers.image.enc.keys.pkcs7".to_string()
+    }
+
+    fn probe(&self, dc_param: &HashMap<String, Vec<Vec<u8>>>) -> bool {
+        dc_param.get("privkeys").is_some()
+    }
+
+    fn private_keys(&self, dc_param: &HashMap<String, Vec<Vec<u8>>>) -> Option<Vec<Vec<u8>>> {
+        dc_param.get("privkeys").cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn load_data_path() -> PathBuf {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("data");
+        path
+    }
+
+    fn decrypt_config(privkey: &[u8], x509s: Vec<Vec<u8>>) -> DecryptConfig {
+        let mut dc = DecryptConfig::default();
+        dc.decrypt_with_priv_keys(vec![privkey.to_vec()], vec![vec![]])
+            .unwrap();
+        if !x509s.is_empty() {
+            dc.decrypt_with_x509s(x509s).unwrap();
+        }
+        dc
+    }
+
+    #[test]
+    fn test_keywrap_pkcs7() {
+        let path = load_data_path();
+        let read = |name: &str| fs::read(path.join(name)).unwrap();
+        let payload = b"test".to_vec();
+        let pkcs7_key_wrapper = Pkcs7KeyWrapper {};
+
+        let mut ec = EncryptConfig::default();
+        assert!(pkcs7_key_wrapper.wrap_keys(&ec, &payload).is_err());
+        assert!(pkcs7_key_wrapper
+            .wrap_keys_for_recipients(&ec, &payload)
+            .unwrap()
+            .is_empty());
+        ec.encrypt_with_pkcs7(vec![
+            read("certificate.pem"),
+            read("public_certificate.pem"),
+        ])
+        .unwrap();
+        let envelope = pkcs7_key_wrapper.wrap_keys(&ec, &payload).unwrap();
+
+        // every recipient can unwrap the keys on its own, with or without
+        // its certificate
+        for (privkey, cert) in [
+            ("private_key.pem", "public_certificate.pem"),
+            ("private_key8.der", "public_certificate.pem"),
+            ("certificate_key.pem", "certificate.pem"),
+        ] {
+            for x509s in [vec![], vec![read(cert)]] {
+                let dc = decrypt_config(&read(privkey), x509s);
+                assert!(pkcs7_key_wrapper.probe(&dc.param));
+                assert_eq!(
+                    pkcs7_key_wrapper.unwrap_keys(&dc, &envelope).unwrap(),
+                    payload
+                );
+            }
+        }
+
+        // the key of another recipient, or of another certificate
+        let dc = decrypt_config(&read("private_key.pem"), vec![read("certificate_p256.pem")]);
+        assert!(pkcs7_key_wrapper.unwrap_keys(&dc, &envelope).is_err());
+        let dc = decrypt_config(&read("private_key_p256.pem"), vec![]);
+        assert!(pkcs7_key_wrapper.unwrap_keys(&dc, &envelope).is_err());
+        assert!(pkcs7_key_wrapper
+            .unwrap_keys(&dc, b"not a CMS envelope")
+            .is_err());
+
+        assert_eq!(
+            pkcs7_key_wrapper.annotation_id(),
+            "org.opencontainers.image.enc.keys.pkcs7".to_string()
+        );
+    }
+
+    #[test]
+    fn test_keywrap_pkcs7_format() {
+        let path = load_data_path();
+        let read = |name: &str| fs::read(path.join(name)).unwrap();
+        let pkcs7_key_wrapper = Pkcs7KeyWrapper {};
+
+        let mut ec = EncryptConfig::default();
+        ec.encrypt_with_pkcs7(vec![read("public_certificate.pem")])
+            .unwrap();
+        let envelope = pkcs7_key_wrapper.wrap_keys(&ec, b"test").unwrap();
+
+        // EnvelopedData with AES-128-GCM, and RSA PKCS#1 v1.5, which is valid
+        // DER for OpenSSL too
+        let der = hex(&envelope);
+        for oid in [
+            "06092a864886f70d010703",
+            "060960864801650304010630",
+            "06092a864886f70d010101",
+        ] {
+            assert!(der.contains(oid), "{oid}");
+        }
+        assert!(CmsContentInfo::from_der(&envelope).is_ok());
+
+        // imgcrypt only wraps keys for RSA certificates
+        for cert in ["certificate_p256.pem", "public_key.pem"] {
+            let mut ec = EncryptConfig::default();
+            ec.encrypt_with_pkcs7(vec![read(cert)]).unwrap();
+            assert!(pkcs7_key_wrapper.wrap_keys(&ec, b"test").is_err());
+        }
+    }
+
+    // envelopes made by `openssl cms -encrypt` and in the format of
+    // imgcrypt, see data/generate_keys.md
+    #[test]
+    fn test_keywrap_pkcs7_interop() {
+        let path = load_data_path();
+        let read = |name: &str| fs::read(path.join(name)).unwrap();
+        let pkcs7_key_wrapper = Pkcs7KeyWrapper {};
+
+        for (envelope, privkey, cert) in [
+            (
+                "pkcs7_imgcrypt.der",
+                "private_key.pem",
+                "public_certificate.pem",
+            ),
+            (
+                "pkcs7_rsa_aes128cbc.der",
+                "private_key.pem",
+                "public_certificate.pem",
+            ),
+            (
+                "pkcs7_rsa_oaep_aes256gcm.der",
+                "private_key.pem",
+                "public_certificate.pem",
+            ),
+            (
+                "pkcs7_ecdh_aes256gcm.der",
+                "private_key_p256.pem",
+                "certificate_p256.pem",
+            ),
+        ] {
+            for x509s in [vec![], vec![read(cert)]] {
+                let dc = decrypt_config(&read(privkey), x509s);
+                assert_eq!(
+                    pkcs7_key_wrapper.unwrap_keys(&dc, &read(envelope)).unwrap(),
+                    b"test",
+                    "{envelope}"
+                );
+            }
+        }
+    }
+
+    fn hex(data: &[u8]) -> String {
+        data.iter().map(|b| format!("{b:02x}")).collect()
+    }
+}
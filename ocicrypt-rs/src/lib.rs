@@ -10,6 +10,7 @@ use crate::keywrap::KeyWrapper;
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
 
+pub mod annotations;
 pub mod config;
 pub mod helpers;
 pub mod keywrap;
@@ -20,6 +21,8 @@ pub mod utils;
 pub mod blockcipher;
 #[cfg(feature = "block-cipher")]
 pub mod encryption;
+#[cfg(feature = "block-cipher")]
+pub mod unwrap_cache;
 
 lazy_static! {
     pub static ref KEY_WRAPPERS: HashMap<String, Box<dyn KeyWrapper>> = {
@@ -34,6 +37,22 @@ lazy_static! {
             );
         }
 
+        #[cfg(feature = "keywrap-pkcs7")]
+        {
+            m.insert(
+                "pkcs7".to_string(),
+                Box::new(crate::keywrap::pkcs7::Pkcs7KeyWrapper {}) as Box<dyn KeyWrapper>,
+            );
+        }
+
+        #[cfg(feature = "keywrap-kbs")]
+        {
+            m.insert(
+                "kbs".to_string(),
+                Box::new(crate::keywrap::kbs::KbsKeyWrapper {}) as Box<dyn KeyWrapper>,
+            );
+        }
+
         #[cfg(feature = "keywrap-keyprovider")]
         {
             let ocicrypt_config =
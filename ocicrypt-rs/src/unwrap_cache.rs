@@ -0,0 +1,212 @@
+// Copyright The ocicrypt Authors.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Cache of the unwrapped layer keys.
+//!
+//! Containers of the same encrypted image share the wrapped keys of its
+//! layers, and unwrapping them again, e.g. by a keyprovider attesting to
+//! the KBS, is costly. The private options unwrapped from a layer are kept
+//! in memory by the digest of its wrapped keys and of the decrypt config,
+//! so that only the holders of the same private keys hit them. The cache
+//! keeps the layers used last up to its capacity, and zeroes the evicted
+//! options.
+//!
+//! Keys are only cached for the ttl set by [`configure`], so that a key
+//! revoked on the KBS stops being served once it expires. The cache is
+//! disabled until configured.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::config::DecryptConfig;
+
+static CACHE: Mutex<UnwrapCache> = Mutex::new(UnwrapCache::new(0, Duration::ZERO));
+
+/// Cache the `capacity` layers used last for `ttl` each, evicting the ones
+/// beyond. A capacity or ttl of 0 disables the cache and drops the cached
+/// keys.
+pub fn configure(capacity: usize, ttl: Duration) {
+    let mut cache = CACHE.lock().expect("poisoned unwrap cache");
+    cache.capacity = capacity;
+    cache.ttl = ttl;
+    if ttl.is_zero() {
+        cache.entries.clear();
+    }
+    cache.shrink();
+}
+
+/// Drop the cached keys.
+pub fn clear() {
+    CACHE.lock().expect("poisoned unwrap cache").entries.clear();
+}
+
+pub(crate) fn get(key: &[u8; 32]) -> Option<Vec<u8>> {
+    CACHE
+        .lock()
+        .expect("poisoned unwrap cache")
+        .get(key, Instant::now())
+}
+
+pub(crate) fn insert(key: [u8; 32], opts_data: &[u8]) {
+    CACHE
+        .lock()
+        .expect("poisoned unwrap cache")
+        .insert(key, opts_data, Instant::now());
+}
+
+/// The key of the options unwrapped with `dc` from the `b64_annotation` of
+/// the `annotations_id` keywrapper.
+pub(crate) fn cache_key(
+    dc: &DecryptConfig,
+    annotations_id: &str,
+    b64_annotation: &str,
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for data in [annotations_id.as_bytes(), b64_annotation.as_bytes()] {
+        hasher.update((data.len() as u64).to_le_bytes());
+        hasher.update(data);
+    }
+
+    let mut params: Vec<_> = dc.param.iter().collect();
+    params.sort_by_key(|(name, _)| *name);
+    for (name, values) in params {
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update((values.len() as u64).to_le_bytes());
+        for value in values {
+            hasher.update((value.len() as u64).to_le_bytes());
+            hasher.update(value);
+        }
+    }
+
+    hasher.finalize().into()
+}
+
+struct Entry {
+    key: [u8; 32],
+    opts_data: Zeroizing<Vec<u8>>,
+    inserted: Instant,
+}
+
+struct UnwrapCache {
+    capacity: usize,
+    ttl: Duration,
+    /// Least recently used first.
+    entries: VecDeque<Entry>,
+}
+
+impl UnwrapCache {
+    const fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: VecDeque::new(),
+        }
+    }
+
+    fn get(&mut self, key: &[u8; 32], now: Instant) -> Option<Vec<u8>> {
+        let ttl = self.ttl;
+        self.entries
+            .retain(|entry| now.saturating_duration_since(entry.inserted) < ttl);
+        let index = self.entries.iter().position(|entry| entry.key == *key)?;
+        let entry = self.entries.remove(index)?;
+        let opts_data = entry.opts_data.to_vec();
+        self.entries.push_back(entry);
+        Some(opts_data)
+    }
+
+    fn insert(&mut self, key: [u8; 32], opts_data: &[u8], now: Instant) {
+        if self.capacity == 0 || self.ttl.is_zero() {
+            return;
+        }
+
+        self.entries.retain(|entry| entry.key != key);
+        self.entries.push_back(Entry {
+            key,
+            opts_data: Zeroizing::new(opts_data.to_vec()),
+            inserted: now,
+        });
+        self.shrink();
+    }
+
+    fn shrink(&mut self) {
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unwrap_cache() {
+        let now = Instant::now();
+        let mut cache = UnwrapCache::new(2, Duration::from_secs(60));
+        cache.insert([1; 32], b"one", now);
+        cache.insert([2; 32], b"two", now);
+        assert_eq!(cache.get(&[1; 32], now), Some(b"one".to_vec()));
+
+        // [2; 32] is used least recently
+        cache.insert([3; 32], b"three", now);
+        assert_eq!(cache.get(&[2; 32], now), None);
+        assert_eq!(cache.get(&[1; 32], now), Some(b"one".to_vec()));
+        assert_eq!(cache.get(&[3; 32], now), Some(b"three".to_vec()));
+
+        cache.insert([3; 32], b"new three", now);
+        assert_eq!(cache.entries.len(), 2);
+        assert_eq!(cache.get(&[3; 32], now), Some(b"new three".to_vec()));
+
+        cache.capacity = 0;
+        cache.shrink();
+        cache.insert([4; 32], b"four", now);
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn test_unwrap_cache_ttl() {
+        let now = Instant::now();
+        let mut cache = UnwrapCache::new(2, Duration::from_secs(60));
+        cache.insert([1; 32], b"one", now);
+        cache.insert([2; 32], b"two", now + Duration::from_secs(30));
+
+        // using a key doesn't extend its ttl
+        let later = now + Duration::from_secs(59);
+        assert_eq!(cache.get(&[1; 32], later), Some(b"one".to_vec()));
+        let expired = now + Duration::from_secs(60);
+        assert_eq!(cache.get(&[1; 32], expired), None);
+        assert_eq!(cache.get(&[2; 32], expired), Some(b"two".to_vec()));
+        assert_eq!(cache.entries.len(), 1);
+
+        // disabled
+        let mut cache = UnwrapCache::new(2, Duration::ZERO);
+        cache.insert([1; 32], b"one", now);
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let mut dc = DecryptConfig::default();
+        let key = cache_key(&dc, "org.opencontainers.image.enc.keys.jwe", "a2V5");
+        assert_ne!(
+            key,
+            cache_key(&dc, "org.opencontainers.image.enc.keys.jwe", "a2V6")
+        );
+        assert_ne!(
+            key,
+            cache_key(&dc, "org.opencontainers.image.enc.keys.pgp", "a2V5")
+        );
+
+        dc.param
+            .insert("privkeys".to_string(), vec![b"private key".to_vec()]);
+        assert_ne!(
+            key,
+            cache_key(&dc, "org.opencontainers.image.enc.keys.jwe", "a2V5")
+        );
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright (c) 2023 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Decrypting a layer takes the same memory whatever its size. The heap is
+//! tracked by the allocator of this test binary, and the synthetic layers
+//! are generated and checked as they are read, so the only buffers growing
+//! with the layer would be the ones of the decryptor.
+
+#![cfg(feature = "block-cipher")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ctr::cipher::generic_array::GenericArray;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use ocicrypt_rs::blockcipher::{
+    LayerBlockCipherHandler, LayerBlockCipherOptions, AES256CTR, MAX_CHUNK_SIZE,
+};
+use sha2::Sha256;
+
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+
+/// Allocator keeping the peak of the live heap bytes.
+struct PeakAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAlloc = PeakAlloc;
+
+/// Heap the decryption may take on top of what was allocated before it.
+const MEMORY_CAP: usize = 16 * MAX_CHUNK_SIZE;
+
+const KEY: [u8; 32] = [7; 32];
+const NONCE: [u8; 16] = [9; 16];
+
+/// Byte the synthetic layers are filled with.
+const FILL: u8 = 0x5a;
+
+/// The synthetic layer of `size` bytes, encrypted as it is read.
+struct EncryptedLayer {
+    cipher: Aes256Ctr,
+    plaintext: io::Take<io::Repeat>,
+}
+
+impl EncryptedLayer {
+    fn new(size: u64) -> Self {
+        Self {
+            cipher: Aes256Ctr::new(
+                GenericArray::from_slice(&KEY),
+                GenericArray::from_slice(&NONCE),
+            ),
+            plaintext: io::repeat(FILL).take(size),
+        }
+    }
+}
+
+impl Read for EncryptedLayer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        assert!(
+            buf.len() <= MAX_CHUNK_SIZE,
+            "read more than a chunk at once"
+        );
+        let read_len = self.plaintext.read(buf)?;
+        self.cipher.apply_keystream(&mut buf[..read_len]);
+        Ok(read_len)
+    }
+}
+
+/// Checks the decrypted layer without keeping it.
+#[derive(Default)]
+struct CheckedLayer {
+    len: u64,
+}
+
+impl Write for CheckedLayer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        assert!(buf.iter().all(|b| *b == FILL), "corrupted layer data");
+        self.len += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn decrypt_under_cap(size: u64) {
+    // the hmac of the encrypted layer, streamed once beforehand
+    let mut hmac = Hmac::<Sha256>::new_from_slice(&KEY).unwrap();
+    let mut layer = EncryptedLayer::new(size);
+    let mut buf = vec![0u8; MAX_CHUNK_SIZE];
+    loop {
+        let read_len = layer.read(&mut buf).unwrap();
+        if read_len == 0 {
+            break;
+        }
+        hmac.update(&buf[..read_len]);
+    }
+    drop(buf);
+
+    let mut lbco = LayerBlockCipherOptions::default();
+    lbco.public.cipher_type = AES256CTR.to_string();
+    lbco.public.hmac = hmac.finalize().into_bytes().to_vec();
+    lbco.private.symmetric_key = KEY.to_vec();
+    lbco.private
+        .cipher_options
+        .insert("nonce".to_string(), NONCE.to_vec());
+
+    let baseline = ALLOCATED.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+
+    let mut decryptor = LayerBlockCipherHandler::new().unwrap();
+    decryptor
+        .decrypt(EncryptedLayer::new(size), &mut lbco)
+        .unwrap();
+    // read with a buffer larger than a chunk, as e.g. a tar reader would
+    let mut buf = vec![0u8; 4 * MAX_CHUNK_SIZE];
+    let mut checked = CheckedLayer::default();
+    loop {
+        let read_len = decryptor.read(&mut buf).unwrap();
+        if read_len == 0 {
+            break;
+        }
+        checked.write_all(&buf[..read_len]).unwrap();
+    }
+    assert_eq!(checked.len, size);
+
+    let peak = PEAK.load(Ordering::SeqCst) - baseline;
+    assert!(
+        peak <= MEMORY_CAP,
+        "decrypting {size} bytes took {peak} bytes of heap, more than {MEMORY_CAP}"
+    );
+}
+
+#[test]
+fn test_decrypt_memory() {
+    decrypt_under_cap(64 << 20);
+}
+
+// takes minutes in debug builds, run it with `cargo test --release -- --ignored`
+#[test]
+#[ignore]
+fn test_decrypt_memory_multi_gb() {
+    decrypt_under_cap(5 << 30);
+}